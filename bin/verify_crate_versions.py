@@ -51,17 +51,20 @@ def main():
     package_versions = {
         'swift': swift_version(),
         'java': java_version(),
-        'node': node_version()
+        'node': node_version(),
+        'wasm': node_version(),
     }
 
     bridge_versions = {
         'swift': bridge_version('ffi'),
         'java': bridge_version('jni'),
         'node': bridge_version('node'),
+        # The WASM build is published alongside the Node one.
+        'wasm': bridge_version('wasm'),
     }
 
     rc = 0
-    for bridge in ['swift', 'java', 'node']:
+    for bridge in ['swift', 'java', 'node', 'wasm']:
         if bridge_versions[bridge] != package_versions[bridge]:
             print("ERROR: Bridge %s has package version %s but crate version is %s" % (
                 bridge, package_versions[bridge], bridge_versions[bridge]))
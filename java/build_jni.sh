@@ -23,9 +23,14 @@ export CARGO_PROFILE_RELEASE_DEBUG=1 # enable line tables
 # https://github.com/rust-lang/rfcs/issues/2771
 export CARGO_PROFILE_RELEASE_LTO=thin 
 
+# Set LIBSIGNAL_TESTING=1 to include the TESTING_ entry points used by java/tests.
+# Features can only be selected for the package whose manifest cargo is pointed at.
+FEATURES=${LIBSIGNAL_TESTING:+--manifest-path rust/bridge/jni/Cargo.toml --features testing}
+
 if [ "$1" = 'desktop' ];
 then
-    echo_then_run cargo build -p libsignal-jni --release
+    # shellcheck disable=SC2086
+    echo_then_run cargo build -p libsignal-jni --release ${FEATURES}
     copy_built_library target/release signal_jni $DESKTOP_LIB_DIR/
 elif [ "$1" = 'android' ];
 then
@@ -21,7 +21,9 @@ import java.io.FileOutputStream;
 import java.io.IOException;
 import java.io.InputStream;
 import java.io.OutputStream;
+import java.nio.ByteBuffer;
 import java.nio.file.Files;
+import java.util.concurrent.CompletableFuture;
 
 public final class Native {
   private static void copyToTempFileAndLoad(InputStream in, String extension) throws IOException {
@@ -64,12 +66,23 @@ public final class Native {
 
   static {
     loadLibrary();
-    Logger_Initialize(SignalProtocolLogger.INFO, Log.class);
+    Logger_Initialize(SignalProtocolLogger.INFO, Log.class, null, 0);
+    // Loggers with a buffer only deliver records in batches, so deliver any left over at exit.
+    Runtime.getRuntime().addShutdownHook(new Thread() {
+      @Override
+      public void run() {
+        Logger_Flush();
+      }
+    });
   }
 
   private Native() {}
 
+  /** Does nothing, but makes sure the library is loaded, for NativeTesting. */
+  static void ensureLoaded() {}
+
   public static native byte[] Aes256GcmSiv_Decrypt(long aesGcmSiv, byte[] ctext, byte[] nonce, byte[] associatedData);
+  public static native byte[] Aes256GcmSiv_DecryptDirect(long aesGcmSiv, ByteBuffer ctext, ByteBuffer nonce, ByteBuffer associatedData);
   public static native void Aes256GcmSiv_Destroy(long handle);
   public static native byte[] Aes256GcmSiv_Encrypt(long aesGcmSiv, byte[] ptext, byte[] nonce, byte[] associatedData);
   public static native long Aes256GcmSiv_New(byte[] key);
@@ -88,6 +101,7 @@ public final class Native {
   public static native byte[] ECPublicKey_GetPublicKeyBytes(long obj);
   public static native byte[] ECPublicKey_Serialize(long obj);
   public static native boolean ECPublicKey_Verify(long key, byte[] message, byte[] signature);
+  public static native boolean ECPublicKey_VerifyBatch(long key, byte[] messages, byte[] signatures);
 
   public static native byte[] GroupCipher_DecryptMessage(long senderKeyName, byte[] message, SenderKeyStore store);
   public static native byte[] GroupCipher_EncryptMessage(long senderKeyName, byte[] message, SenderKeyStore store);
@@ -100,14 +114,18 @@ public final class Native {
   public static native long[] IdentityKeyPair_Deserialize(byte[] data);
   public static native byte[] IdentityKeyPair_Serialize(long publicKey, long privateKey);
 
-  public static native void Logger_Initialize(int maxLevel, Class loggerClass);
-  public static native void Logger_SetMaxLevel(int maxLevel);
+  public static native void Logger_Flush();
+  public static native void Logger_Initialize(int maxLevel, Class loggerClass, String[] targetPrefixes, int bufferSize);
+  public static native void Logger_SetMaxLevel(Class loggerClass, int maxLevel);
+  public static native void Logger_SetSampling(Class loggerClass, int messagesPerSec, int burstSize);
 
   public static native void NumericFingerprintGenerator_Destroy(long handle);
   public static native String NumericFingerprintGenerator_GetDisplayString(long obj);
   public static native byte[] NumericFingerprintGenerator_GetScannableEncoding(long obj);
   public static native long NumericFingerprintGenerator_New(int iterations, int version, byte[] localIdentifier, byte[] localKey, byte[] remoteIdentifier, byte[] remoteKey);
 
+  public static native byte[] Poksho_MultiScalarMul(byte[] scalars, byte[] points);
+
   public static native void PreKeyBundle_Destroy(long handle);
   public static native int PreKeyBundle_GetDeviceId(long obj);
   public static native long PreKeyBundle_GetIdentityKey(long p);
@@ -121,6 +139,7 @@ public final class Native {
 
   public static native long PreKeyRecord_Deserialize(byte[] data);
   public static native void PreKeyRecord_Destroy(long handle);
+  public static native long[] PreKeyRecord_GenerateBatch(int startId, int count);
   public static native int PreKeyRecord_GetId(long obj);
   public static native long PreKeyRecord_GetPrivateKey(long obj);
   public static native long PreKeyRecord_GetPublicKey(long obj);
@@ -146,6 +165,8 @@ public final class Native {
 
   public static native boolean ScannableFingerprint_Compare(byte[] fprint1, byte[] fprint2);
 
+  public static native byte[] SealedSender_MultiRecipientEncrypt(long[] recipients, long[] recipientSessions, long content, IdentityKeyStore identityStore);
+
   public static native long SealedSessionCipher_DecryptToUsmc(byte[] ctext, IdentityKeyStore identityStore);
   public static native byte[] SealedSessionCipher_Encrypt(long destination, long senderCert, byte[] ptext, SessionStore sessionStore, IdentityKeyStore identityStore);
 
@@ -191,6 +212,7 @@ public final class Native {
   public static native void SenderKeyRecord_Destroy(long handle);
   public static native byte[] SenderKeyRecord_GetSerialized(long obj);
   public static native long SenderKeyRecord_New();
+  public static native byte[] SenderKey_DeriveMessageKey(byte[] chainKey, int iteration);
 
   public static native long ServerCertificate_Deserialize(byte[] data);
   public static native void ServerCertificate_Destroy(long handle);
@@ -224,6 +246,7 @@ public final class Native {
   public static native long SessionRecord_InitializeBobSession(long identityKeyPrivate, long identityKeyPublic, long signedPrekeyPrivate, long signedPrekeyPublic, long ephPrivate, long ephPublic, long theirIdentityKey, long theirBaseKey);
   public static native long SessionRecord_NewFresh();
   public static native byte[] SessionRecord_Serialize(long obj);
+  public static native void SessionRecord_SetMaxSkippedMessageKeys(long sessionRecord, int limit);
 
   public static native long SignalMessage_Deserialize(byte[] data);
   public static native void SignalMessage_Destroy(long handle);
@@ -245,6 +268,7 @@ public final class Native {
   public static native long SignedPreKeyRecord_GetTimestamp(long obj);
   public static native long SignedPreKeyRecord_New(int id, long timestamp, long pubKey, long privKey, byte[] signature);
 
+
   public static native long UnidentifiedSenderMessageContent_Deserialize(byte[] data);
   public static native void UnidentifiedSenderMessageContent_Destroy(long handle);
   public static native byte[] UnidentifiedSenderMessageContent_GetContents(long obj);
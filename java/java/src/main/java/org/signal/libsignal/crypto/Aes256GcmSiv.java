@@ -5,6 +5,7 @@
 
 package org.signal.libsignal.crypto;
 
+import java.nio.ByteBuffer;
 import org.signal.client.internal.Native;
 import org.whispersystems.libsignal.InvalidMessageException;
 import org.whispersystems.libsignal.InvalidKeyException;
@@ -30,4 +31,15 @@ class Aes256GcmSiv {
       throws InvalidMessageException {
     return Native.Aes256GcmSiv_Decrypt(this.handle, ciphertext, nonce, associated_data);
   }
+
+  /**
+   * Decrypts the remaining contents of {@code ciphertext} without copying it first.
+   *
+   * All three buffers must be direct buffers (see {@link ByteBuffer#allocateDirect}).
+   * Their positions are not changed.
+   */
+  byte[] decrypt(ByteBuffer ciphertext, ByteBuffer nonce, ByteBuffer associated_data)
+      throws InvalidMessageException {
+    return Native.Aes256GcmSiv_DecryptDirect(this.handle, ciphertext, nonce, associated_data);
+  }
 }
@@ -20,11 +20,13 @@ import org.whispersystems.libsignal.UntrustedIdentityException;
 import org.whispersystems.libsignal.protocol.CiphertextMessage;
 import org.whispersystems.libsignal.protocol.PreKeySignalMessage;
 import org.whispersystems.libsignal.protocol.SignalMessage;
+import org.whispersystems.libsignal.state.SessionRecord;
 import org.whispersystems.libsignal.state.SignalProtocolStore;
 import org.whispersystems.libsignal.util.guava.Optional;
 
 import org.signal.client.internal.Native;
 
+import java.util.List;
 import java.util.UUID;
 
 public class SealedSessionCipher {
@@ -58,6 +60,29 @@ public class SealedSessionCipher {
        this.signalProtocolStore);
   }
 
+  public byte[] multiRecipientEncrypt(List<SignalProtocolAddress> recipients, UnidentifiedSenderMessageContent content)
+      throws InvalidKeyException, NoSessionException, UntrustedIdentityException
+  {
+    long[] recipientHandles = new long[recipients.size()];
+    long[] recipientSessionHandles = new long[recipients.size()];
+    // Keep the sessions reachable until the native call returns.
+    SessionRecord[] recipientSessions = new SessionRecord[recipients.size()];
+    for (int i = 0; i < recipients.size(); i++) {
+      SignalProtocolAddress recipient = recipients.get(i);
+      if (!this.signalProtocolStore.containsSession(recipient)) {
+        throw new NoSessionException("No session for: " + recipient);
+      }
+      recipientSessions[i] = this.signalProtocolStore.loadSession(recipient);
+      recipientHandles[i] = recipient.nativeHandle();
+      recipientSessionHandles[i] = recipientSessions[i].nativeHandle();
+    }
+    return Native.SealedSender_MultiRecipientEncrypt(
+      recipientHandles,
+      recipientSessionHandles,
+      content.nativeHandle(),
+      this.signalProtocolStore);
+  }
+
   public DecryptionResult decrypt(CertificateValidator validator, byte[] ciphertext, long timestamp)
       throws
       InvalidMetadataMessageException, InvalidMetadataVersionException,
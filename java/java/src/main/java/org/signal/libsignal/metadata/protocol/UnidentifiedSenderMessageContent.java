@@ -46,4 +46,8 @@ public class UnidentifiedSenderMessageContent {
     return Native.UnidentifiedSenderMessageContent_GetSerialized(this.handle);
   }
 
+  public long nativeHandle() {
+    return this.handle;
+  }
+
 }
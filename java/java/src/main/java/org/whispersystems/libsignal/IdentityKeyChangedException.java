@@ -0,0 +1,26 @@
+//
+// Copyright 2021 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+package org.whispersystems.libsignal;
+
+/**
+ * Thrown when an identity key is not trusted because it differs from the one previously saved for
+ * the same address.
+ *
+ * {@link #getUntrustedIdentity()} returns the new key.
+ */
+public class IdentityKeyChangedException extends UntrustedIdentityException {
+
+  private final IdentityKey oldKey;
+
+  public IdentityKeyChangedException(String name, IdentityKey oldKey, IdentityKey newKey) {
+    super(name, newKey);
+    this.oldKey = oldKey;
+  }
+
+  public IdentityKey getPreviousIdentity() {
+    return oldKey;
+  }
+}
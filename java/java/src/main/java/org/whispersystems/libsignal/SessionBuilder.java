@@ -90,4 +90,21 @@ public class SessionBuilder {
                         sessionStore,
                         identityKeyStore);
   }
+
+  /**
+   * Limits how many message keys a single incoming message may skip over in the current session
+   * with the remote address. Messages that would skip more fail to decrypt with an
+   * {@link InvalidMessageException}.
+   *
+   * @param limit The largest number of keys to skip, or 0 to restore the default.
+   * @throws NoSessionException when there is no session with the remote address.
+   */
+  public void setMaxSkippedMessageKeys(int limit) throws NoSessionException {
+    if (!sessionStore.containsSession(remoteAddress)) {
+      throw new NoSessionException("No session for: " + remoteAddress);
+    }
+    SessionRecord sessionRecord = sessionStore.loadSession(remoteAddress);
+    sessionRecord.setMaxSkippedMessageKeys(limit);
+    sessionStore.storeSession(remoteAddress, sessionRecord);
+  }
 }
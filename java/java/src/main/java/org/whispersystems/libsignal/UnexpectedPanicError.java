@@ -0,0 +1,18 @@
+//
+// Copyright 2021 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+package org.whispersystems.libsignal;
+
+/**
+ * Thrown when libsignal-client's Rust code panics.
+ *
+ * This always indicates a bug in libsignal-client. The message includes the original panic
+ * message to help track it down.
+ */
+public class UnexpectedPanicError extends AssertionError {
+  public UnexpectedPanicError(String message) {
+    super(message);
+  }
+}
@@ -0,0 +1,18 @@
+//
+// Copyright 2021 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+package org.whispersystems.libsignal;
+
+import java.io.IOException;
+
+/**
+ * Thrown when a serialized {@link org.whispersystems.libsignal.state.SessionRecord} was written in
+ * a newer format than this version of the library can read.
+ */
+public class UnknownSessionVersionException extends IOException {
+  public UnknownSessionVersionException(String detailMessage) {
+    super(detailMessage);
+  }
+}
@@ -87,5 +87,29 @@ public class Log {
     }
   }
 
+  private static void log(int[] priorities, String tag, String[] msgs) {
+    SignalProtocolLogger logger = SignalProtocolLoggerProvider.getProvider();
+
+    if (logger != null) {
+      for (int i = 0; i < msgs.length; i++) {
+        logger.log(priorities[i], tag, msgs[i]);
+      }
+    }
+  }
+
+  private static void logStructured(int priority, String tag, String msg, String[] keys, String[] values) {
+    SignalProtocolLogger logger = SignalProtocolLoggerProvider.getProvider();
+
+    if (logger instanceof SignalProtocolStructuredLogger) {
+      ((SignalProtocolStructuredLogger)logger).logStructured(priority, tag, msg, keys, values);
+    } else if (logger != null) {
+      StringBuilder builder = new StringBuilder(msg);
+      for (int i = 0; i < keys.length; i++) {
+        builder.append(' ').append(keys[i]).append('=').append(values[i]);
+      }
+      logger.log(priority, tag, builder.toString());
+    }
+  }
+
 
 }
@@ -0,0 +1,21 @@
+//
+// Copyright 2021 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+package org.whispersystems.libsignal.logging;
+
+/**
+ * A logger that can receive the key-value pairs attached to a log message separately.
+ *
+ * Loggers that only implement {@link SignalProtocolLogger} receive the pairs appended to the
+ * message instead, as {@code key=value}.
+ */
+public interface SignalProtocolStructuredLogger extends SignalProtocolLogger {
+  /**
+   * Logs a message along with its key-value pairs.
+   *
+   * {@code keys} and {@code values} always have the same length.
+   */
+  public void logStructured(int priority, String tag, String message, String[] keys, String[] values);
+}
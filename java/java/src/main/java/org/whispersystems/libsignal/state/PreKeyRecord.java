@@ -29,6 +29,10 @@ public class PreKeyRecord {
     this.handle = Native.PreKeyRecord_Deserialize(serialized);
   }
 
+  public PreKeyRecord(long handle) {
+    this.handle = handle;
+  }
+
   public int getId() {
     return Native.PreKeyRecord_GetId(this.handle);
   }
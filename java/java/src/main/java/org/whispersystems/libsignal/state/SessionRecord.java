@@ -39,6 +39,12 @@ public class SessionRecord {
     return new SessionRecord(Native.SessionRecord_FromSingleSessionState(sessionStateBytes));
   }
 
+  /**
+   * Deserializes a record produced by {@link #serialize}.
+   *
+   * @throws org.whispersystems.libsignal.UnknownSessionVersionException if the record was written
+   *     by a newer version of the library, in a format this one can't read.
+   */
   public SessionRecord(byte[] serialized) throws IOException {
     this.handle = Native.SessionRecord_Deserialize(serialized);
   }
@@ -51,6 +57,14 @@ public class SessionRecord {
     Native.SessionRecord_ArchiveCurrentState(this.handle);
   }
 
+  /**
+   * Limits how many message keys a single incoming message may skip over in the current session.
+   * Messages that would skip more fail to decrypt. A limit of 0 restores the default.
+   */
+  public void setMaxSkippedMessageKeys(int limit) {
+    Native.SessionRecord_SetMaxSkippedMessageKeys(this.handle, limit);
+  }
+
   public int getSessionVersion() {
     return Native.SessionRecord_GetSessionVersion(this.handle);
   }
@@ -145,7 +159,7 @@ public class SessionRecord {
             theirBaseKey.nativeHandle()));
   }
 
-  long nativeHandle() {
+  public long nativeHandle() {
     return this.handle;
   }
 }
@@ -5,6 +5,7 @@
  */
 package org.whispersystems.libsignal.util;
 
+import org.signal.client.internal.Native;
 import org.whispersystems.libsignal.IdentityKey;
 import org.whispersystems.libsignal.IdentityKeyPair;
 import org.whispersystems.libsignal.InvalidKeyException;
@@ -48,4 +49,19 @@ public class KeyHelper {
     }
   }
 
+  /**
+   * Generate a list of one-time PreKeys.
+   *
+   * @param start The ID of the first PreKey. Subsequent PreKeys get consecutive IDs.
+   * @param count The number of PreKeys to generate, at most 65535.
+   * @return the list of generated PreKeyRecords.
+   */
+  public static List<PreKeyRecord> generatePreKeys(int start, int count) {
+    List<PreKeyRecord> results = new LinkedList<>();
+    for (long handle : Native.PreKeyRecord_GenerateBatch(start, count)) {
+      results.add(new PreKeyRecord(handle));
+    }
+    return results;
+  }
+
 }
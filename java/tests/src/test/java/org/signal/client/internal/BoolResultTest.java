@@ -0,0 +1,26 @@
+//
+// Copyright 2021 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+package org.signal.client.internal;
+
+import junit.framework.TestCase;
+
+public class BoolResultTest extends TestCase {
+  public void testBool() {
+    assertTrue(NativeTesting.TESTING_IsEven(2));
+    assertFalse(NativeTesting.TESTING_IsEven(3));
+  }
+
+  public void testBoolResult() {
+    assertTrue(NativeTesting.TESTING_BoolFromInt(1));
+    assertFalse(NativeTesting.TESTING_BoolFromInt(0));
+    try {
+      NativeTesting.TESTING_BoolFromInt(2);
+      fail("should have thrown");
+    } catch (IllegalArgumentException e) {
+      // Expected.
+    }
+  }
+}
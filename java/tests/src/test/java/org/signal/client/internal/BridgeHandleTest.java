@@ -0,0 +1,54 @@
+//
+// Copyright 2021 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+package org.signal.client.internal;
+
+import java.util.Arrays;
+import junit.framework.TestCase;
+
+public class BridgeHandleTest extends TestCase {
+
+  public void testSerializeRoundTrip() {
+    long privateKey = Native.ECPrivateKey_Generate();
+    long publicKey = Native.ECPrivateKey_GetPublicKey(privateKey);
+    try {
+      byte[] privateBytes = Native.ECPrivateKey_Serialize(privateKey);
+      long privateCopy = Native.ECPrivateKey_Deserialize(privateBytes);
+      assertTrue(Arrays.equals(privateBytes, Native.ECPrivateKey_Serialize(privateCopy)));
+      Native.ECPrivateKey_Destroy(privateCopy);
+
+      byte[] publicBytes = Native.ECPublicKey_Serialize(publicKey);
+      long publicCopy = Native.ECPublicKey_Deserialize(publicBytes, 0);
+      assertEquals(0, Native.ECPublicKey_Compare(publicKey, publicCopy));
+      Native.ECPublicKey_Destroy(publicCopy);
+    } finally {
+      Native.ECPublicKey_Destroy(publicKey);
+      Native.ECPrivateKey_Destroy(privateKey);
+    }
+  }
+
+  public void testSerializeFallibleType() {
+    long record = Native.SessionRecord_NewFresh();
+    try {
+      byte[] serialized = Native.SessionRecord_Serialize(record);
+      long copy = Native.SessionRecord_Deserialize(serialized);
+      assertTrue(Arrays.equals(serialized, Native.SessionRecord_Serialize(copy)));
+      Native.SessionRecord_Destroy(copy);
+    } finally {
+      Native.SessionRecord_Destroy(record);
+    }
+  }
+
+  public void testNullHandle() {
+    try {
+      Native.ECPublicKey_Serialize(0);
+      fail("should have thrown");
+    } catch (NullPointerException e) {
+      // Expected.
+    }
+    // Destroying a null handle is a no-op.
+    Native.ECPublicKey_Destroy(0);
+  }
+}
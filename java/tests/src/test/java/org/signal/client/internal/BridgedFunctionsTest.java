@@ -0,0 +1,84 @@
+//
+// Copyright 2021 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+package org.signal.client.internal;
+
+import java.lang.reflect.Method;
+import java.lang.reflect.Modifier;
+import java.util.ArrayList;
+import java.util.Arrays;
+import java.util.HashSet;
+import java.util.List;
+import java.util.Map;
+import java.util.Set;
+import java.util.TreeMap;
+import junit.framework.TestCase;
+
+public class BridgedFunctionsTest extends TestCase {
+  private static final String SYMBOL_PREFIX = "Java_org_signal_client_internal_";
+
+  // Entry points written by hand in rust/bridge/jni instead of being generated by the bridge
+  // macros, and so missing from the description.
+  private static final Set<String> HAND_WRITTEN = new HashSet<>(Arrays.asList(
+      "IdentityKeyPair_Deserialize",
+      "Logger_Flush",
+      "Logger_Initialize",
+      "Logger_SetMaxLevel",
+      "Logger_SetSampling",
+      "SessionCipher_EncryptMessage",
+      "TESTING_BenchmarkByteArrayArg",
+      "TESTING_BenchmarkLogFilter",
+      "TESTING_BenchmarkLogStrings",
+      "TESTING_LogAtLevel",
+      "TESTING_LogBuffered",
+      "TESTING_LogBufferedWarning",
+      "TESTING_LogFlood",
+      "TESTING_LogKeyValues",
+      "TESTING_LogWhilePinned"));
+
+  /** Maps each JNI entry point's symbol to its number of parameters. */
+  private static Map<String, Integer> bridgedFunctions(String description) {
+    Map<String, Integer> result = new TreeMap<>();
+    for (String line : description.split("\n")) {
+      String[] parts = line.split(" ");
+      if (parts[0].equals("jni")) {
+        result.put(parts[1], Integer.parseInt(parts[2]));
+      }
+    }
+    return result;
+  }
+
+  public void testNativeMatchesBridgedFunctions() {
+    String description = NativeTesting.TESTING_DescribeBridgedFunctions();
+    if (description.isEmpty()) {
+      // The library was built without the "describe" feature, so there's nothing to check.
+      return;
+    }
+    Map<String, Integer> bridged = bridgedFunctions(description);
+
+    List<String> problems = new ArrayList<>();
+    for (Class<?> nativeClass : Arrays.asList(Native.class, NativeTesting.class)) {
+      for (Method method : nativeClass.getDeclaredMethods()) {
+        if (!Modifier.isNative(method.getModifiers()) || HAND_WRITTEN.contains(method.getName())) {
+          continue;
+        }
+        // Only underscores need escaping in the names used here.
+        String symbol = SYMBOL_PREFIX + nativeClass.getSimpleName() + "_"
+            + method.getName().replace("_", "_1");
+        Integer arity = bridged.remove(symbol);
+        if (arity == null) {
+          problems.add(method.getName() + " is declared but not bridged");
+        } else if (arity != method.getParameterCount()) {
+          problems.add(method.getName() + " takes " + method.getParameterCount()
+              + " parameters but is bridged with " + arity);
+        }
+      }
+    }
+    for (String symbol : bridged.keySet()) {
+      problems.add(symbol + " is bridged but not declared");
+    }
+    assertEquals(new ArrayList<String>(), problems);
+  }
+}
@@ -0,0 +1,34 @@
+//
+// Copyright 2021 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+package org.signal.client.internal;
+
+import java.util.Arrays;
+import junit.framework.TestCase;
+
+public class BufferListTest extends TestCase {
+
+  private static void checkBufferList(int count) {
+    byte[][] buffers = NativeTesting.TESTING_ReturnBufferList(count);
+    assertEquals(count, buffers.length);
+    for (int i = 0; i < count; i++) {
+      byte[] expected = new byte[i % 4];
+      Arrays.fill(expected, (byte)i);
+      assertTrue(Arrays.equals(expected, buffers[i]));
+    }
+  }
+
+  public void testEmpty() {
+    checkBufferList(0);
+  }
+
+  public void testSingle() {
+    checkBufferList(1);
+  }
+
+  public void testMany() {
+    checkBufferList(1500);
+  }
+}
@@ -0,0 +1,83 @@
+//
+// Copyright 2021 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+package org.signal.client.internal;
+
+import java.util.HashSet;
+import java.util.Set;
+import java.util.concurrent.CompletableFuture;
+import java.util.concurrent.ExecutionException;
+import java.util.concurrent.TimeUnit;
+import java.util.function.Function;
+import junit.framework.TestCase;
+
+public class FutureTest extends TestCase {
+
+  public void testSuccess() throws Exception {
+    CompletableFuture future = NativeTesting.TESTING_FutureSuccess(21);
+    assertEquals(42, future.get());
+  }
+
+  public void testFailure() throws Exception {
+    CompletableFuture future = NativeTesting.TESTING_FutureFailure(21);
+    try {
+      future.get();
+      throw new AssertionError("future should have failed");
+    } catch (ExecutionException e) {
+      assertTrue(e.getCause() instanceof IllegalArgumentException);
+    }
+  }
+
+  public void testPendingFutureDoesNotBlockCaller() throws Exception {
+    long start = System.nanoTime();
+    CompletableFuture future = NativeTesting.TESTING_FuturePending(21, 1000);
+    long elapsedMillis = TimeUnit.NANOSECONDS.toMillis(System.nanoTime() - start);
+    assertFalse(future.isDone());
+    assertTrue("call took " + elapsedMillis + "ms", elapsedMillis < 1000);
+    assertEquals(42, future.get(10, TimeUnit.SECONDS));
+  }
+
+  public void testPendingFutureCompletesOnDaemonThread() throws Exception {
+    CompletableFuture future = NativeTesting.TESTING_FuturePending(21, 100);
+    final Thread[] completingThread = new Thread[1];
+    CompletableFuture done = future.thenApply(new Function<Object, Object>() {
+      @Override
+      public Object apply(Object result) {
+        completingThread[0] = Thread.currentThread();
+        return result;
+      }
+    });
+    assertEquals(42, done.get(10, TimeUnit.SECONDS));
+    assertNotSame(Thread.currentThread(), completingThread[0]);
+    // Executor threads are attached as daemons, so they don't keep the JVM from exiting.
+    assertTrue(completingThread[0].isDaemon());
+  }
+
+  public void testOutstandingFuturesShareThreads() throws Exception {
+    // Start more pending futures than the executor has threads.
+    int count = 4 * Runtime.getRuntime().availableProcessors();
+    CompletableFuture[] futures = new CompletableFuture[count];
+    for (int i = 0; i < count; ++i) {
+      futures[i] = NativeTesting.TESTING_FutureThreadId(200);
+    }
+    Set<Object> threadIds = new HashSet<>();
+    for (CompletableFuture future : futures) {
+      threadIds.add(future.get(10, TimeUnit.SECONDS));
+    }
+    assertTrue("ran " + count + " futures on " + threadIds.size() + " threads",
+        threadIds.size() < count);
+  }
+
+  public void testCancellation() throws Exception {
+    // Use a future that's still pending, so the cancellation can't lose a race with completion.
+    CompletableFuture future = NativeTesting.TESTING_FuturePending(21, 500);
+    future.cancel(true);
+    assertTrue(future.isCancelled());
+
+    // The Rust side may still complete the cancelled future; make sure that's harmless.
+    CompletableFuture anotherFuture = NativeTesting.TESTING_FutureSuccess(1);
+    assertEquals(2, anotherFuture.get());
+  }
+}
@@ -0,0 +1,90 @@
+//
+// Copyright 2021 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+package org.signal.client.internal;
+
+import java.lang.management.GarbageCollectorMXBean;
+import java.lang.management.ManagementFactory;
+import junit.framework.TestCase;
+import org.whispersystems.libsignal.logging.Log;
+import org.whispersystems.libsignal.logging.SignalProtocolLogger;
+import org.whispersystems.libsignal.logging.SignalProtocolLoggerProvider;
+
+public class LogAllocationTest extends TestCase {
+  private static class CountingLogger implements SignalProtocolLogger {
+    int count = 0;
+    String lastTag = null;
+
+    @Override
+    public void log(int priority, String tag, String message) {
+      count += 1;
+      lastTag = tag;
+    }
+  }
+
+  private SignalProtocolLogger previousProvider;
+  private CountingLogger logger;
+
+  @Override
+  protected void setUp() {
+    // Load the library first, so its startup message isn't counted.
+    NativeTesting.TESTING_BenchmarkLogFilter(Log.class, null, "", 0);
+
+    previousProvider = SignalProtocolLoggerProvider.getProvider();
+    logger = new CountingLogger();
+    SignalProtocolLoggerProvider.setProvider(logger);
+  }
+
+  @Override
+  protected void tearDown() {
+    SignalProtocolLoggerProvider.setProvider(previousProvider);
+  }
+
+  private static long[] gcCountAndMillis() {
+    long count = 0;
+    long millis = 0;
+    for (GarbageCollectorMXBean gc : ManagementFactory.getGarbageCollectorMXBeans()) {
+      count += Math.max(gc.getCollectionCount(), 0);
+      millis += Math.max(gc.getCollectionTime(), 0);
+    }
+    return new long[] {count, millis};
+  }
+
+  public void testBenchmarkManyLogs() {
+    final int count = 100_000;
+    // All of these are logged within a single native call, so any local references they leak
+    // would pile up until it returns.
+    long[] before = gcCountAndMillis();
+    long nanos = NativeTesting.TESTING_BenchmarkLogFilter(Log.class, null, "libsignal_jni", count);
+    long[] after = gcCountAndMillis();
+    System.out.printf(
+        "%d logs: %.1f ns each, %d collections taking %d ms%n",
+        count, (double) nanos / count, after[0] - before[0], after[1] - before[1]);
+
+    assertEquals(count, logger.count);
+    assertEquals("libsignal-client", logger.lastTag);
+  }
+
+  /** Logs {@code count} structured records, printing how many collections ran while doing so. */
+  private static void benchmarkLogStrings(String label, boolean pooled, int count) {
+    // Start each run from a freshly collected heap, so one run's garbage isn't blamed on the next.
+    System.gc();
+    long[] before = gcCountAndMillis();
+    long nanos = NativeTesting.TESTING_BenchmarkLogStrings(Log.class, pooled, count);
+    long[] after = gcCountAndMillis();
+    System.out.printf(
+        "%d logs %s: %.1f ns each, %d collections taking %d ms%n",
+        count, label, (double) nanos / count, after[0] - before[0], after[1] - before[1]);
+  }
+
+  public void testBenchmarkStringPool() {
+    final int count = 100_000;
+    benchmarkLogStrings("without the string pool", false, count);
+    benchmarkLogStrings("with the string pool", true, count);
+
+    assertEquals(2 * count, logger.count);
+    assertEquals("libsignal-client", logger.lastTag);
+  }
+}
@@ -0,0 +1,102 @@
+//
+// Copyright 2021 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+package org.signal.client.internal;
+
+import java.util.ArrayList;
+import java.util.Arrays;
+import java.util.List;
+import junit.framework.TestCase;
+import org.whispersystems.libsignal.logging.SignalProtocolLogger;
+
+public class LogBufferTest extends TestCase {
+  /** Stands in for Log.java, recording each call from Rust. */
+  private static class RecordingLog {
+    static final List<Integer> batchSizes = new ArrayList<>();
+    static final List<String> messages = new ArrayList<>();
+
+    private static synchronized void log(int priority, String tag, String msg) {
+      batchSizes.add(1);
+      messages.add(msg);
+    }
+
+    private static synchronized void log(int[] priorities, String tag, String[] msgs) {
+      assertEquals(priorities.length, msgs.length);
+      batchSizes.add(msgs.length);
+      for (String msg : msgs) {
+        messages.add(msg);
+      }
+    }
+  }
+
+  @Override
+  protected void setUp() {
+    RecordingLog.batchSizes.clear();
+    RecordingLog.messages.clear();
+  }
+
+  private static void assertMessagesInOrder(int count) {
+    assertEquals(count, RecordingLog.messages.size());
+    for (int i = 0; i < count; i++) {
+      assertTrue(
+          RecordingLog.messages.get(i),
+          RecordingLog.messages.get(i).endsWith("buffered message " + i));
+    }
+  }
+
+  public void testBatchesAreDeliveredInOrder() {
+    NativeTesting.TESTING_LogBuffered(RecordingLog.class, 4, 12);
+    assertMessagesInOrder(12);
+    assertEquals(Arrays.asList(4, 4, 4), RecordingLog.batchSizes);
+  }
+
+  public void testRemainingRecordsAreDeliveredByFlush() {
+    NativeTesting.TESTING_LogBuffered(RecordingLog.class, 4, 10);
+    assertMessagesInOrder(10);
+    assertEquals(Arrays.asList(4, 4, 2), RecordingLog.batchSizes);
+  }
+
+  public void testWarningsAreDeliveredRightAway() {
+    NativeTesting.TESTING_LogBufferedWarning(RecordingLog.class, 4);
+    assertMessagesInOrder(2);
+    assertEquals(Arrays.asList(2), RecordingLog.batchSizes);
+  }
+
+  public void testUnbuffered() {
+    NativeTesting.TESTING_LogBuffered(RecordingLog.class, 0, 3);
+    assertMessagesInOrder(3);
+    assertEquals(Arrays.asList(1, 1, 1), RecordingLog.batchSizes);
+  }
+
+  public void testNegativeBufferSizeIsRejected() {
+    try {
+      Native.Logger_Initialize(SignalProtocolLogger.INFO, RecordingLog.class, null, -1);
+      fail("should have thrown");
+    } catch (IllegalArgumentException e) {
+      // Expected.
+    }
+  }
+
+  public void testInvalidLevelIsRejected() {
+    try {
+      Native.Logger_Initialize(SignalProtocolLogger.VERBOSE, RecordingLog.class, null, 0);
+      fail("should have thrown");
+    } catch (IllegalArgumentException e) {
+      // Expected.
+    }
+  }
+
+  public void testBufferedRecordsWaitForPinnedArrays() {
+    NativeTesting.TESTING_LogWhilePinned(RecordingLog.class, 4, new byte[64 * 1024]);
+    assertEquals(1, RecordingLog.messages.size());
+    assertTrue(RecordingLog.messages.get(0).endsWith("logged while pinned"));
+    assertEquals(Arrays.asList(1), RecordingLog.batchSizes);
+  }
+
+  public void testUnbufferedRecordsAreDroppedWhilePinned() {
+    NativeTesting.TESTING_LogWhilePinned(RecordingLog.class, 0, new byte[64 * 1024]);
+    assertEquals(new ArrayList<String>(), RecordingLog.messages);
+  }
+}
@@ -0,0 +1,79 @@
+//
+// Copyright 2021 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+package org.signal.client.internal;
+
+import junit.framework.TestCase;
+import org.whispersystems.libsignal.logging.Log;
+import org.whispersystems.libsignal.logging.SignalProtocolLogger;
+import org.whispersystems.libsignal.logging.SignalProtocolLoggerProvider;
+
+public class LogFilterTest extends TestCase {
+  private static class CountingLogger implements SignalProtocolLogger {
+    int count = 0;
+
+    @Override
+    public void log(int priority, String tag, String message) {
+      count += 1;
+    }
+  }
+
+  private static final String[] PREFIXES = {"libsignal_protocol", "libsignal_jni::"};
+
+  private SignalProtocolLogger previousProvider;
+  private CountingLogger logger;
+
+  @Override
+  protected void setUp() {
+    // Load the library first, so its startup message isn't counted.
+    NativeTesting.TESTING_BenchmarkLogFilter(Log.class, null, "", 0);
+
+    previousProvider = SignalProtocolLoggerProvider.getProvider();
+    logger = new CountingLogger();
+    SignalProtocolLoggerProvider.setProvider(logger);
+  }
+
+  @Override
+  protected void tearDown() {
+    SignalProtocolLoggerProvider.setProvider(previousProvider);
+  }
+
+  public void testAllTargetsEnabledByDefault() {
+    NativeTesting.TESTING_BenchmarkLogFilter(Log.class, null, "anything", 10);
+    assertEquals(10, logger.count);
+  }
+
+  public void testTargetPrefixes() {
+    NativeTesting.TESTING_BenchmarkLogFilter(Log.class, PREFIXES, "libsignal_protocol::session", 10);
+    assertEquals(10, logger.count);
+    NativeTesting.TESTING_BenchmarkLogFilter(Log.class, PREFIXES, "libsignal_jni::logging", 10);
+    assertEquals(20, logger.count);
+
+    NativeTesting.TESTING_BenchmarkLogFilter(Log.class, PREFIXES, "libsignal_jni", 10);
+    NativeTesting.TESTING_BenchmarkLogFilter(Log.class, PREFIXES, "jni::wrapper", 10);
+    assertEquals(20, logger.count);
+
+    NativeTesting.TESTING_BenchmarkLogFilter(Log.class, new String[0], "libsignal_protocol", 10);
+    assertEquals(20, logger.count);
+  }
+
+  public void testBenchmarkSuppressedLogs() {
+    final int count = 10_000;
+    // Warm up both paths first.
+    NativeTesting.TESTING_BenchmarkLogFilter(Log.class, PREFIXES, "libsignal_protocol", count);
+    NativeTesting.TESTING_BenchmarkLogFilter(Log.class, PREFIXES, "suppressed", count);
+
+    long enabledNanos =
+        NativeTesting.TESTING_BenchmarkLogFilter(Log.class, PREFIXES, "libsignal_protocol", count);
+    long suppressedNanos =
+        NativeTesting.TESTING_BenchmarkLogFilter(Log.class, PREFIXES, "suppressed", count);
+    System.out.printf(
+        "%d logs: %.1f ns each when enabled, %.1f ns each when suppressed%n",
+        count, (double) enabledNanos / count, (double) suppressedNanos / count);
+
+    // Only the suppressed logs are skipped; the timings are too noisy to assert on.
+    assertEquals(2 * count, logger.count);
+  }
+}
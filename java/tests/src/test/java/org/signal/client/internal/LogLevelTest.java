@@ -0,0 +1,87 @@
+//
+// Copyright 2021 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+package org.signal.client.internal;
+
+import java.util.ArrayList;
+import java.util.List;
+import junit.framework.TestCase;
+import org.whispersystems.libsignal.logging.Log;
+import org.whispersystems.libsignal.logging.SignalProtocolLogger;
+import org.whispersystems.libsignal.logging.SignalProtocolLoggerProvider;
+
+public class LogLevelTest extends TestCase {
+  private static class RecordingLogger implements SignalProtocolLogger {
+    final List<String> messages = new ArrayList<>();
+
+    @Override
+    public synchronized void log(int priority, String tag, String message) {
+      messages.add(message);
+    }
+  }
+
+  private SignalProtocolLogger previousProvider;
+  private RecordingLogger logger;
+
+  @Override
+  protected void setUp() {
+    // Load the library first, so its startup message isn't recorded.
+    Native.Logger_SetMaxLevel(Log.class, SignalProtocolLogger.INFO);
+
+    previousProvider = SignalProtocolLoggerProvider.getProvider();
+    logger = new RecordingLogger();
+    SignalProtocolLoggerProvider.setProvider(logger);
+  }
+
+  @Override
+  protected void tearDown() {
+    Native.Logger_SetMaxLevel(Log.class, SignalProtocolLogger.INFO);
+    SignalProtocolLoggerProvider.setProvider(previousProvider);
+  }
+
+  public void testRaisingLevel() {
+    NativeTesting.TESTING_LogAtLevel(SignalProtocolLogger.DEBUG, "dropped");
+    assertEquals(0, logger.messages.size());
+
+    Native.Logger_SetMaxLevel(Log.class, SignalProtocolLogger.DEBUG);
+    NativeTesting.TESTING_LogAtLevel(SignalProtocolLogger.DEBUG, "emitted");
+    assertEquals(1, logger.messages.size());
+    assertTrue(logger.messages.get(0).endsWith("emitted"));
+  }
+
+  public void testLoweringLevel() {
+    NativeTesting.TESTING_LogAtLevel(SignalProtocolLogger.INFO, "emitted");
+    assertEquals(1, logger.messages.size());
+
+    Native.Logger_SetMaxLevel(Log.class, SignalProtocolLogger.WARN);
+    NativeTesting.TESTING_LogAtLevel(SignalProtocolLogger.INFO, "dropped");
+    NativeTesting.TESTING_LogAtLevel(SignalProtocolLogger.WARN, "also emitted");
+    assertEquals(2, logger.messages.size());
+    assertTrue(logger.messages.get(1).endsWith("also emitted"));
+  }
+
+  public void testUnregisteredClassIsRejected() {
+    try {
+      Native.Logger_SetMaxLevel(LogLevelTest.class, SignalProtocolLogger.DEBUG);
+      fail("should have thrown");
+    } catch (IllegalArgumentException e) {
+      // Expected.
+    }
+  }
+
+  public void testInvalidLevelIsRejected() {
+    for (int level : new int[] {SignalProtocolLogger.VERBOSE, 0, 8}) {
+      try {
+        Native.Logger_SetMaxLevel(Log.class, level);
+        fail("should have thrown for " + level);
+      } catch (IllegalArgumentException e) {
+        // Expected.
+      }
+    }
+    // The level is left as it was.
+    NativeTesting.TESTING_LogAtLevel(SignalProtocolLogger.DEBUG, "dropped");
+    assertEquals(0, logger.messages.size());
+  }
+}
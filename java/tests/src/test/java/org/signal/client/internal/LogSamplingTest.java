@@ -0,0 +1,88 @@
+//
+// Copyright 2021 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+package org.signal.client.internal;
+
+import java.util.ArrayList;
+import java.util.List;
+import junit.framework.TestCase;
+import org.whispersystems.libsignal.logging.Log;
+import org.whispersystems.libsignal.logging.SignalProtocolLogger;
+import org.whispersystems.libsignal.logging.SignalProtocolLoggerProvider;
+
+public class LogSamplingTest extends TestCase {
+  private static class RecordingLogger implements SignalProtocolLogger {
+    final List<String> messages = new ArrayList<>();
+
+    @Override
+    public synchronized void log(int priority, String tag, String message) {
+      messages.add(message);
+    }
+  }
+
+  private SignalProtocolLogger previousProvider;
+  private RecordingLogger logger;
+
+  @Override
+  protected void setUp() {
+    // Load the library first, so its startup message isn't recorded.
+    NativeTesting.TESTING_LogFlood(0, 0);
+
+    previousProvider = SignalProtocolLoggerProvider.getProvider();
+    logger = new RecordingLogger();
+    SignalProtocolLoggerProvider.setProvider(logger);
+  }
+
+  @Override
+  protected void tearDown() {
+    Native.Logger_SetSampling(Log.class, 0, 0);
+    SignalProtocolLoggerProvider.setProvider(previousProvider);
+  }
+
+  public void testNoSamplingByDefault() {
+    NativeTesting.TESTING_LogFlood(100, 0);
+    assertEquals(100, logger.messages.size());
+  }
+
+  public void testNegativeParametersAreRejected() {
+    for (int[] params : new int[][] {{-1, 1}, {1, -1}}) {
+      try {
+        Native.Logger_SetSampling(Log.class, params[0], params[1]);
+        fail("should have thrown");
+      } catch (IllegalArgumentException e) {
+        // Expected.
+      }
+    }
+    // Sampling is left as it was.
+    NativeTesting.TESTING_LogFlood(100, 0);
+    assertEquals(100, logger.messages.size());
+  }
+
+  public void testFloodIsLimited() {
+    final int burstSize = 5;
+    Native.Logger_SetSampling(Log.class, 1, burstSize);
+    // 10,000 messages per second, for one second.
+    NativeTesting.TESTING_LogFlood(10_000, 1000);
+
+    assertTrue(logger.messages.size() >= burstSize);
+    assertTrue(
+        "too many messages: " + logger.messages.size(),
+        logger.messages.size() <= burstSize + 2);
+  }
+
+  public void testSuppressedCountIsReported() throws InterruptedException {
+    Native.Logger_SetSampling(Log.class, 10, 1);
+    NativeTesting.TESTING_LogFlood(10, 0);
+    assertEquals(1, logger.messages.size());
+
+    // Wait long enough to get another message through.
+    Thread.sleep(150);
+    NativeTesting.TESTING_LogFlood(1, 0);
+    assertEquals(2, logger.messages.size());
+    assertTrue(
+        logger.messages.get(1),
+        logger.messages.get(1).endsWith("flood message 0 (suppressed 9 messages)"));
+  }
+}
@@ -0,0 +1,76 @@
+//
+// Copyright 2021 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+package org.signal.client.internal;
+
+import java.util.Arrays;
+import junit.framework.TestCase;
+
+public class MultiScalarMulTest extends TestCase {
+  // The compressed Ristretto basepoint.
+  private static final byte[] BASEPOINT =
+      fromHex("e2f2ae0a6abc4e71a884a961c500515f58e30b6aa582dd8db6a65945e08d2d76");
+
+  private static byte[] fromHex(String hex) {
+    byte[] result = new byte[hex.length() / 2];
+    for (int i = 0; i < result.length; ++i) {
+      result[i] = (byte) Integer.parseInt(hex.substring(2 * i, 2 * i + 2), 16);
+    }
+    return result;
+  }
+
+  private static byte[] scalar(int value) {
+    byte[] result = new byte[32];
+    result[0] = (byte) value;
+    return result;
+  }
+
+  private static byte[] concat(byte[]... arrays) {
+    int length = 0;
+    for (byte[] array : arrays) {
+      length += array.length;
+    }
+    byte[] result = new byte[length];
+    int offset = 0;
+    for (byte[] array : arrays) {
+      System.arraycopy(array, 0, result, offset, array.length);
+      offset += array.length;
+    }
+    return result;
+  }
+
+  public void testMultiScalarMul() {
+    assertTrue(Arrays.equals(BASEPOINT, Native.Poksho_MultiScalarMul(scalar(1), BASEPOINT)));
+
+    byte[] twice = Native.Poksho_MultiScalarMul(scalar(2), BASEPOINT);
+    assertTrue(
+        Arrays.equals(
+            twice,
+            Native.Poksho_MultiScalarMul(
+                concat(scalar(1), scalar(1)), concat(BASEPOINT, BASEPOINT))));
+
+    // The identity point compresses to all zeros.
+    assertTrue(Arrays.equals(new byte[32], Native.Poksho_MultiScalarMul(new byte[0], new byte[0])));
+  }
+
+  public void testInvalidInputs() {
+    byte[] nonCanonical = new byte[32];
+    Arrays.fill(nonCanonical, (byte) 0xFF);
+    byte[][][] badInputs = {
+      {scalar(1), concat(BASEPOINT, BASEPOINT)},
+      {new byte[31], new byte[31]},
+      {nonCanonical, BASEPOINT},
+      {scalar(1), nonCanonical},
+    };
+    for (byte[][] input : badInputs) {
+      try {
+        Native.Poksho_MultiScalarMul(input[0], input[1]);
+        fail("should have thrown");
+      } catch (IllegalArgumentException e) {
+        // Expected.
+      }
+    }
+  }
+}
@@ -0,0 +1,112 @@
+//
+// Copyright 2021 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+package org.signal.client.internal;
+
+import java.util.ArrayList;
+import java.util.List;
+import junit.framework.TestCase;
+import org.whispersystems.libsignal.logging.Log;
+import org.whispersystems.libsignal.logging.SignalProtocolLogger;
+import org.whispersystems.libsignal.logging.SignalProtocolLoggerProvider;
+
+public class MultipleLoggersTest extends TestCase {
+  private static class RecordingLogger implements SignalProtocolLogger {
+    final List<String> messages = new ArrayList<>();
+
+    @Override
+    public synchronized void log(int priority, String tag, String message) {
+      messages.add(message);
+    }
+  }
+
+  /** A second logger class, called by the library the same way as {@code Log}. */
+  static class SecondLog {
+    static final List<String> messages = new ArrayList<>();
+
+    static synchronized void log(int priority, String tag, String message) {
+      messages.add(message);
+    }
+  }
+
+  private SignalProtocolLogger previousProvider;
+  private RecordingLogger logger;
+
+  @Override
+  protected void setUp() {
+    // Load the library first, so its startup message isn't recorded.
+    Native.Logger_SetMaxLevel(Log.class, SignalProtocolLogger.INFO);
+
+    previousProvider = SignalProtocolLoggerProvider.getProvider();
+    logger = new RecordingLogger();
+    SignalProtocolLoggerProvider.setProvider(logger);
+  }
+
+  @Override
+  protected void tearDown() {
+    Native.Logger_SetMaxLevel(Log.class, SignalProtocolLogger.INFO);
+    SignalProtocolLoggerProvider.setProvider(previousProvider);
+  }
+
+  public void testBothLoggersReceiveRecords() {
+    Native.Logger_Initialize(SignalProtocolLogger.WARN, SecondLog.class, null, 0);
+    SecondLog.messages.clear();
+
+    NativeTesting.TESTING_LogAtLevel(SignalProtocolLogger.WARN, "for both");
+    NativeTesting.TESTING_LogAtLevel(SignalProtocolLogger.INFO, "for the first");
+
+    assertEquals(2, logger.messages.size());
+    assertTrue(logger.messages.get(0).endsWith("for both"));
+    assertTrue(logger.messages.get(1).endsWith("for the first"));
+    synchronized (SecondLog.class) {
+      assertEquals(1, SecondLog.messages.size());
+      assertTrue(SecondLog.messages.get(0).endsWith("for both"));
+    }
+
+    // Registering the same class again is ignored, so records aren't delivered twice.
+    Native.Logger_Initialize(SignalProtocolLogger.INFO, SecondLog.class, null, 0);
+    SecondLog.messages.clear();
+    NativeTesting.TESTING_LogAtLevel(SignalProtocolLogger.WARN, "once");
+    synchronized (SecondLog.class) {
+      assertEquals(1, SecondLog.messages.size());
+    }
+  }
+
+  public void testLevelsAreIndependent() {
+    Native.Logger_Initialize(SignalProtocolLogger.WARN, SecondLog.class, null, 0);
+    Native.Logger_SetMaxLevel(SecondLog.class, SignalProtocolLogger.WARN);
+    // Ignore the warning if SecondLog was already registered.
+    logger.messages.clear();
+    SecondLog.messages.clear();
+
+    Native.Logger_SetMaxLevel(Log.class, SignalProtocolLogger.DEBUG);
+    NativeTesting.TESTING_LogAtLevel(SignalProtocolLogger.DEBUG, "for the first");
+
+    assertEquals(1, logger.messages.size());
+    assertTrue(logger.messages.get(0).endsWith("for the first"));
+    synchronized (SecondLog.class) {
+      assertEquals(0, SecondLog.messages.size());
+    }
+  }
+
+  public void testSamplingIsIndependent() {
+    Native.Logger_Initialize(SignalProtocolLogger.WARN, SecondLog.class, null, 0);
+    Native.Logger_SetMaxLevel(SecondLog.class, SignalProtocolLogger.INFO);
+    Native.Logger_SetSampling(SecondLog.class, 10, 1);
+    logger.messages.clear();
+    SecondLog.messages.clear();
+    try {
+      NativeTesting.TESTING_LogFlood(10, 0);
+
+      assertEquals(10, logger.messages.size());
+      synchronized (SecondLog.class) {
+        assertEquals(1, SecondLog.messages.size());
+      }
+    } finally {
+      Native.Logger_SetSampling(SecondLog.class, 0, 0);
+      Native.Logger_SetMaxLevel(SecondLog.class, SignalProtocolLogger.WARN);
+    }
+  }
+}
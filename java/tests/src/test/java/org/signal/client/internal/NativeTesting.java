@@ -0,0 +1,57 @@
+//
+// Copyright 2021 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+// WARNING: this file was automatically generated
+
+package org.signal.client.internal;
+
+import org.whispersystems.libsignal.state.SessionStore;
+
+import java.util.concurrent.CompletableFuture;
+
+/**
+ * The TESTING_ entry points, which libsignal_jni only provides when built with its "testing"
+ * feature.
+ */
+public final class NativeTesting {
+  static {
+    Native.ensureLoaded();
+  }
+
+  private NativeTesting() {}
+
+  public static native long TESTING_BenchmarkByteArrayArg(byte[] data, boolean pinned, int count);
+  public static native long TESTING_BenchmarkLogFilter(Class loggerClass, String[] targetPrefixes, String target, int count);
+  public static native long TESTING_BenchmarkLogStrings(Class loggerClass, boolean pooled, int count);
+  public static native boolean TESTING_BoolFromInt(int value);
+  public static native int TESTING_CountUtf8Bytes(String text);
+  /** @deprecated only for testing */
+  @Deprecated
+  public static native int TESTING_DeprecatedEcho(int value);
+  public static native String TESTING_DescribeBridgedFunctions();
+  public static native CompletableFuture TESTING_FutureFailure(int input);
+  public static native CompletableFuture TESTING_FuturePending(int input, int delayMs);
+  public static native CompletableFuture TESTING_FutureSuccess(int input);
+  public static native CompletableFuture TESTING_FutureThreadId(int delayMs);
+  public static native boolean TESTING_IsEven(int value);
+  public static native void TESTING_LogAtLevel(int level, String message);
+  public static native void TESTING_LogBuffered(Class loggerClass, int bufferSize, int count);
+  public static native void TESTING_LogBufferedWarning(Class loggerClass, int bufferSize);
+  public static native void TESTING_LogFlood(int count, int durationMs);
+  public static native void TESTING_LogKeyValues();
+  public static native void TESTING_LogWhilePinned(Class loggerClass, int bufferSize, byte[] data);
+  public static native void TESTING_PanicOnNewThread(String message);
+  public static native void TESTING_PanicWithMessage(String message);
+  public static native byte[][] TESTING_ReturnBufferList(int count);
+  public static native byte[] TESTING_ReturnOptionalBuffer(int len);
+  public static native String TESTING_ReturnOptionalString(String text);
+  public static native Object[] TESTING_ReturnTuple(int value);
+  public static native boolean TESTING_SessionStoreHasSession(long address, SessionStore store);
+  public static native String TESTING_StringFromUtf8(byte[] bytes);
+  public static native int TESTING_SumBytes(byte[] data, byte[] more);
+  public static native long TESTING_U64FromBytes(byte[] bytes);
+  public static native byte[] TESTING_U64ToBytes(long value);
+  public static native String TESTING_UuidToString(byte[] uuid);
+}
@@ -0,0 +1,29 @@
+//
+// Copyright 2021 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+package org.signal.client.internal;
+
+import java.util.Arrays;
+import junit.framework.TestCase;
+
+public class OptionalBufferTest extends TestCase {
+
+  public void testMissing() {
+    // Negative values are passed to Rust as None.
+    assertNull(NativeTesting.TESTING_ReturnOptionalBuffer(-1));
+  }
+
+  public void testEmpty() {
+    byte[] buffer = NativeTesting.TESTING_ReturnOptionalBuffer(0);
+    assertNotNull(buffer);
+    assertEquals(0, buffer.length);
+  }
+
+  public void testNonEmpty() {
+    byte[] expected = new byte[5];
+    Arrays.fill(expected, (byte)5);
+    assertTrue(Arrays.equals(expected, NativeTesting.TESTING_ReturnOptionalBuffer(5)));
+  }
+}
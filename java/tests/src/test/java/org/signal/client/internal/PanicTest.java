@@ -0,0 +1,90 @@
+//
+// Copyright 2021 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+package org.signal.client.internal;
+
+import java.util.ArrayList;
+import java.util.List;
+import junit.framework.TestCase;
+import org.whispersystems.libsignal.UnexpectedPanicError;
+import org.whispersystems.libsignal.logging.SignalProtocolLogger;
+import org.whispersystems.libsignal.logging.SignalProtocolLoggerProvider;
+
+public class PanicTest extends TestCase {
+  private static class RecordingLogger implements SignalProtocolLogger {
+    final List<String> errors = new ArrayList<String>();
+
+    @Override
+    public synchronized void log(int priority, String tag, String message) {
+      if (priority == SignalProtocolLogger.ERROR) {
+        errors.add(message);
+      }
+    }
+
+    synchronized String findError(String substring) {
+      for (String message : errors) {
+        if (message.contains(substring)) {
+          return message;
+        }
+      }
+      fail("no error logged containing '" + substring + "': " + errors);
+      return null;
+    }
+  }
+
+  private SignalProtocolLogger previousProvider;
+  private RecordingLogger logger;
+
+  @Override
+  protected void setUp() {
+    // Load the library (and install its logger) before swapping in our own.
+    NativeTesting.TESTING_CountUtf8Bytes("");
+
+    previousProvider = SignalProtocolLoggerProvider.getProvider();
+    logger = new RecordingLogger();
+    SignalProtocolLoggerProvider.setProvider(logger);
+  }
+
+  @Override
+  protected void tearDown() {
+    SignalProtocolLoggerProvider.setProvider(previousProvider);
+  }
+
+  public void testPanicMessageIsPreserved() {
+    try {
+      NativeTesting.TESTING_PanicWithMessage("the tortoise beat the hare");
+      fail("should have thrown");
+    } catch (UnexpectedPanicError e) {
+      assertTrue(e.getMessage(), e.getMessage().contains("the tortoise beat the hare"));
+    }
+  }
+
+  public void testPanicIsStillAnAssertionError() {
+    try {
+      NativeTesting.TESTING_PanicWithMessage("oops");
+      fail("should have thrown");
+    } catch (AssertionError e) {
+      assertTrue(e instanceof UnexpectedPanicError);
+    }
+  }
+
+  public void testPanicIsLoggedWithLocation() {
+    try {
+      NativeTesting.TESTING_PanicWithMessage("logged before unwinding");
+      fail("should have thrown");
+    } catch (UnexpectedPanicError e) {
+      // Expected.
+    }
+    String message = logger.findError("logged before unwinding");
+    assertTrue(message, message.contains("testing.rs:"));
+  }
+
+  public void testPanicOnUnattachedThreadIsLogged() {
+    NativeTesting.TESTING_PanicOnNewThread("far from Java");
+    String message = logger.findError("far from Java");
+    assertTrue(message, message.contains("testing.rs:"));
+    assertTrue(message, message.contains("<unnamed>"));
+  }
+}
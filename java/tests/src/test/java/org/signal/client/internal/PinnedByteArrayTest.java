@@ -0,0 +1,154 @@
+//
+// Copyright 2021 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+package org.signal.client.internal;
+
+import java.io.BufferedReader;
+import java.io.File;
+import java.io.InputStreamReader;
+import java.util.ArrayList;
+import java.util.List;
+import junit.framework.TestCase;
+
+public class PinnedByteArrayTest extends TestCase {
+  // Matches BYTE_ARRAY_PIN_THRESHOLD in the bridge.
+  private static final int PIN_THRESHOLD = 4 * 1024;
+  private static final int ONE_MIB = 1024 * 1024;
+
+  private static byte[] makeArray(int length) {
+    byte[] result = new byte[length];
+    for (int i = 0; i < length; ++i) {
+      result[i] = (byte) (i * 31);
+    }
+    return result;
+  }
+
+  private static int sum(byte[] array) {
+    int result = 0;
+    for (byte b : array) {
+      result += b & 0xFF;
+    }
+    return result;
+  }
+
+  public void testArraysArePassedIntact() {
+    int[] lengths = {0, 1, PIN_THRESHOLD, PIN_THRESHOLD + 1, ONE_MIB};
+    for (int length : lengths) {
+      byte[] data = makeArray(length);
+      assertEquals(sum(data), NativeTesting.TESTING_SumBytes(data, null));
+      for (int otherLength : lengths) {
+        byte[] more = makeArray(otherLength);
+        assertEquals(sum(data) + sum(more), NativeTesting.TESTING_SumBytes(data, more));
+      }
+    }
+  }
+
+  public void testNullArrays() {
+    byte[] data = makeArray(PIN_THRESHOLD + 1);
+    assertEquals(sum(data), NativeTesting.TESTING_SumBytes(data, null));
+    try {
+      NativeTesting.TESTING_SumBytes(null, data);
+      fail("should have thrown");
+    } catch (NullPointerException e) {
+      // Expected.
+    }
+  }
+
+  /**
+   * Runs {@link #main} in a separate JVM with {@code -Xcheck:jni}, which reports any JNI call
+   * made while an array is pinned (during which the JVM may not be able to reach a safepoint).
+   */
+  public void testNoJniCallsWhilePinned() throws Exception {
+    String java =
+        System.getProperty("java.home") + File.separator + "bin" + File.separator + "java";
+    ProcessBuilder builder =
+        new ProcessBuilder(
+            java,
+            "-Xcheck:jni",
+            "-cp",
+            System.getProperty("java.class.path"),
+            "-Djava.library.path=" + System.getProperty("java.library.path"),
+            PinnedByteArrayTest.class.getName());
+    builder.redirectErrorStream(true);
+    Process process = builder.start();
+
+    List<String> warnings = new ArrayList<String>();
+    BufferedReader output =
+        new BufferedReader(new InputStreamReader(process.getInputStream(), "UTF-8"));
+    String line;
+    while ((line = output.readLine()) != null) {
+      if (line.contains("Critical")) {
+        warnings.add(line);
+      }
+    }
+    assertEquals(0, process.waitFor());
+    assertEquals(new ArrayList<String>(), warnings);
+  }
+
+  /** Pins large arrays on several threads at once, while the main thread churns the heap. */
+  public static void main(String[] args) throws Exception {
+    final byte[] data = makeArray(ONE_MIB);
+    final byte[] more = makeArray(PIN_THRESHOLD * 2);
+    final byte[] small = makeArray(PIN_THRESHOLD);
+    final int expected = sum(data) + sum(more);
+    final int expectedWithSmall = sum(data) + sum(small);
+
+    final Throwable[] failure = new Throwable[1];
+    Thread[] threads = new Thread[4];
+    for (int i = 0; i < threads.length; ++i) {
+      threads[i] =
+          new Thread() {
+            @Override
+            public void run() {
+              try {
+                for (int j = 0; j < 200; ++j) {
+                  assertEquals(expected, NativeTesting.TESTING_SumBytes(data, more));
+                  assertEquals(expectedWithSmall, NativeTesting.TESTING_SumBytes(data, small));
+                }
+              } catch (Throwable t) {
+                synchronized (failure) {
+                  failure[0] = t;
+                }
+              }
+            }
+          };
+      threads[i].start();
+    }
+
+    List<byte[]> garbage = new ArrayList<byte[]>();
+    for (int i = 0; i < 2000; ++i) {
+      garbage.add(new byte[64 * 1024]);
+      if (garbage.size() > 32) {
+        garbage.clear();
+        System.gc();
+      }
+    }
+
+    for (Thread thread : threads) {
+      thread.join();
+    }
+    synchronized (failure) {
+      if (failure[0] != null) {
+        failure[0].printStackTrace();
+        System.exit(1);
+      }
+    }
+  }
+
+  public void testBenchmarkPinnedArrays() {
+    final int count = 1000;
+    byte[] data = makeArray(ONE_MIB);
+    // Warm up both paths first.
+    NativeTesting.TESTING_BenchmarkByteArrayArg(data, true, count);
+    NativeTesting.TESTING_BenchmarkByteArrayArg(data, false, count);
+
+    long pinnedNanos = NativeTesting.TESTING_BenchmarkByteArrayArg(data, true, count);
+    long copiedNanos = NativeTesting.TESTING_BenchmarkByteArrayArg(data, false, count);
+    System.out.printf(
+        "1 MiB argument: %.1f ns each when pinned, %.1f ns each when copied%n",
+        (double) pinnedNanos / count, (double) copiedNanos / count);
+    // Timings vary too much between machines to assert on; this is for reading in the test log.
+  }
+}
@@ -0,0 +1,67 @@
+//
+// Copyright 2021 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+package org.signal.client.internal;
+
+import java.util.Collections;
+import java.util.List;
+import junit.framework.TestCase;
+import org.whispersystems.libsignal.SignalProtocolAddress;
+import org.whispersystems.libsignal.state.SessionRecord;
+import org.whispersystems.libsignal.state.SessionStore;
+
+public class StoreArgTest extends TestCase {
+
+  private static class FakeSessionStore implements SessionStore {
+    int loadCount = 0;
+    SignalProtocolAddress lastAddress = null;
+
+    public SessionRecord loadSession(SignalProtocolAddress address) {
+      loadCount += 1;
+      lastAddress = address;
+      return null;
+    }
+
+    public List<Integer> getSubDeviceSessions(String name) {
+      return Collections.emptyList();
+    }
+
+    public void storeSession(SignalProtocolAddress address, SessionRecord record) {
+      throw new AssertionError("not used by these tests");
+    }
+
+    public boolean containsSession(SignalProtocolAddress address) {
+      return false;
+    }
+
+    public void deleteSession(SignalProtocolAddress address) {}
+
+    public void deleteAllSessions(String name) {}
+  }
+
+  public void testStoreIsCalled() {
+    SignalProtocolAddress address = new SignalProtocolAddress("+14155550100", 1);
+    FakeSessionStore store = new FakeSessionStore();
+    assertFalse(NativeTesting.TESTING_SessionStoreHasSession(address.nativeHandle(), store));
+    assertEquals(1, store.loadCount);
+    assertEquals(address, store.lastAddress);
+  }
+
+  public void testStoreExceptionIsRethrown() {
+    SignalProtocolAddress address = new SignalProtocolAddress("+14155550100", 1);
+    FakeSessionStore store = new FakeSessionStore() {
+      @Override
+      public SessionRecord loadSession(SignalProtocolAddress address) {
+        throw new IllegalStateException("store is closed");
+      }
+    };
+    try {
+      NativeTesting.TESTING_SessionStoreHasSession(address.nativeHandle(), store);
+      fail("should have thrown");
+    } catch (IllegalStateException e) {
+      assertEquals("store is closed", e.getMessage());
+    }
+  }
+}
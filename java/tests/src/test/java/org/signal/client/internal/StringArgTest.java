@@ -0,0 +1,57 @@
+//
+// Copyright 2021 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+package org.signal.client.internal;
+
+import junit.framework.TestCase;
+
+public class StringArgTest extends TestCase {
+
+  public void testUtf8Conversion() {
+    assertEquals(5, NativeTesting.TESTING_CountUtf8Bytes("hello"));
+    assertEquals(6, NativeTesting.TESTING_CountUtf8Bytes("h\u00e9llo"));
+    // Supplementary characters are a surrogate pair in Java but a single 4-byte UTF-8 sequence.
+    assertEquals(4, NativeTesting.TESTING_CountUtf8Bytes("\uD83D\uDE00"));
+    assertEquals(0, NativeTesting.TESTING_CountUtf8Bytes(""));
+  }
+
+  public void testUnpairedSurrogate() {
+    try {
+      NativeTesting.TESTING_CountUtf8Bytes("bad \uD800 string");
+      fail("should have thrown");
+    } catch (IllegalArgumentException e) {
+      assertTrue(e.getMessage().contains("unpaired surrogate"));
+    }
+  }
+
+  public void testNulCharacter() {
+    try {
+      NativeTesting.TESTING_CountUtf8Bytes("bad \u0000 string");
+      fail("should have thrown");
+    } catch (IllegalArgumentException e) {
+      assertTrue(e.getMessage().contains("NUL"));
+    }
+  }
+
+  public void testOwnedString() {
+    String text = "h\u00e9llo \uD83D\uDE00";
+    assertEquals(text, NativeTesting.TESTING_ReturnOptionalString(text));
+    assertEquals("", NativeTesting.TESTING_ReturnOptionalString(""));
+    assertNull(NativeTesting.TESTING_ReturnOptionalString(null));
+    try {
+      NativeTesting.TESTING_ReturnOptionalString("bad \uDE00 string");
+      fail("should have thrown");
+    } catch (IllegalArgumentException e) {
+      assertTrue(e.getMessage().contains("unpaired surrogate"));
+    }
+  }
+
+  public void testReturnedStrings() throws Exception {
+    assertEquals("h\u00e9llo", NativeTesting.TESTING_StringFromUtf8("h\u00e9llo".getBytes("UTF-8")));
+    assertEquals("", NativeTesting.TESTING_StringFromUtf8(new byte[0]));
+    // Unlike C strings, Java strings can contain NUL characters.
+    assertEquals("a\u0000b", NativeTesting.TESTING_StringFromUtf8(new byte[] {'a', 0, 'b'}));
+  }
+}
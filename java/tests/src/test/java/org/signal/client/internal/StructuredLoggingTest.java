@@ -0,0 +1,78 @@
+//
+// Copyright 2021 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+package org.signal.client.internal;
+
+import java.util.ArrayList;
+import java.util.Arrays;
+import java.util.List;
+import junit.framework.TestCase;
+import org.whispersystems.libsignal.logging.SignalProtocolLogger;
+import org.whispersystems.libsignal.logging.SignalProtocolLoggerProvider;
+import org.whispersystems.libsignal.logging.SignalProtocolStructuredLogger;
+
+public class StructuredLoggingTest extends TestCase {
+  private static class RecordingLogger implements SignalProtocolStructuredLogger {
+    final List<String> messages = new ArrayList<>();
+    String[] keys;
+    String[] values;
+
+    @Override
+    public void log(int priority, String tag, String message) {
+      messages.add(message);
+    }
+
+    @Override
+    public void logStructured(int priority, String tag, String message, String[] keys, String[] values) {
+      messages.add(message);
+      this.keys = keys;
+      this.values = values;
+    }
+  }
+
+  private static class FlatLogger implements SignalProtocolLogger {
+    final List<String> messages = new ArrayList<>();
+
+    @Override
+    public void log(int priority, String tag, String message) {
+      messages.add(message);
+    }
+  }
+
+  private SignalProtocolLogger previousProvider;
+
+  @Override
+  protected void setUp() {
+    // Load the library first, so its startup message isn't recorded.
+    Native.ensureLoaded();
+
+    previousProvider = SignalProtocolLoggerProvider.getProvider();
+  }
+
+  @Override
+  protected void tearDown() {
+    SignalProtocolLoggerProvider.setProvider(previousProvider);
+  }
+
+  public void testStructuredLogger() {
+    RecordingLogger logger = new RecordingLogger();
+    SignalProtocolLoggerProvider.setProvider(logger);
+    NativeTesting.TESTING_LogKeyValues();
+
+    assertEquals(1, logger.messages.size());
+    assertTrue(logger.messages.get(0).endsWith("structured logging test"));
+    assertTrue(Arrays.equals(new String[] {"count", "name"}, logger.keys));
+    assertTrue(Arrays.equals(new String[] {"2", "value with spaces"}, logger.values));
+  }
+
+  public void testFlatLoggerFallback() {
+    FlatLogger logger = new FlatLogger();
+    SignalProtocolLoggerProvider.setProvider(logger);
+    NativeTesting.TESTING_LogKeyValues();
+
+    assertEquals(1, logger.messages.size());
+    assertTrue(logger.messages.get(0).endsWith("structured logging test count=2 name=value with spaces"));
+  }
+}
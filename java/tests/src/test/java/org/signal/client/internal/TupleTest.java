@@ -0,0 +1,27 @@
+//
+// Copyright 2021 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+package org.signal.client.internal;
+
+import java.util.Arrays;
+import junit.framework.TestCase;
+
+public class TupleTest extends TestCase {
+
+  public void testTupleElements() {
+    Object[] result = NativeTesting.TESTING_ReturnTuple(0x01020305);
+    assertEquals(3, result.length);
+    assertTrue(Arrays.equals(new byte[] {1, 2, 3, 5}, (byte[])result[0]));
+    assertEquals(Integer.valueOf(0x01020305), result[1]);
+    assertEquals(Boolean.FALSE, result[2]);
+  }
+
+  public void testPrimitivesAreBoxed() {
+    Object[] result = NativeTesting.TESTING_ReturnTuple(2);
+    assertTrue(result[1] instanceof Integer);
+    assertTrue(result[2] instanceof Boolean);
+    assertEquals(Boolean.TRUE, result[2]);
+  }
+}
@@ -0,0 +1,41 @@
+//
+// Copyright 2021 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+package org.signal.client.internal;
+
+import java.nio.ByteBuffer;
+import java.util.Arrays;
+import junit.framework.TestCase;
+
+public class U64ArgTest extends TestCase {
+  private static final long MAX_SAFE_JS_INTEGER = (1L << 53) - 1;
+
+  private static byte[] bytesOf(long value) {
+    return ByteBuffer.allocate(8).putLong(value).array();
+  }
+
+  public void testRoundTrip() {
+    long[] values = {0, MAX_SAFE_JS_INTEGER, MAX_SAFE_JS_INTEGER + 1, Long.MAX_VALUE};
+    for (long value : values) {
+      assertTrue(Arrays.equals(bytesOf(value), NativeTesting.TESTING_U64ToBytes(value)));
+      assertEquals(value, NativeTesting.TESTING_U64FromBytes(bytesOf(value)));
+    }
+  }
+
+  public void testUnsignedValues() {
+    // Negative longs stand for the u64 values above Long.MAX_VALUE, bit for bit.
+    long[] values = {Long.MIN_VALUE, -2, -1};
+    for (long value : values) {
+      assertTrue(Arrays.equals(bytesOf(value), NativeTesting.TESTING_U64ToBytes(value)));
+      assertEquals(value, NativeTesting.TESTING_U64FromBytes(bytesOf(value)));
+    }
+
+    byte[] max = new byte[8];
+    Arrays.fill(max, (byte) 0xff);
+    long result = NativeTesting.TESTING_U64FromBytes(max);
+    assertEquals(-1, result);
+    assertEquals("18446744073709551615", Long.toUnsignedString(result));
+  }
+}
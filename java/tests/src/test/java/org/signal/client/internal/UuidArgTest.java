@@ -0,0 +1,40 @@
+//
+// Copyright 2021 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+package org.signal.client.internal;
+
+import junit.framework.TestCase;
+
+public class UuidArgTest extends TestCase {
+
+  public void testValidUuid() {
+    byte[] uuid = new byte[16];
+    for (int i = 0; i < uuid.length; i++) {
+      uuid[i] = (byte) i;
+    }
+    assertEquals("00010203-0405-0607-0809-0a0b0c0d0e0f", NativeTesting.TESTING_UuidToString(uuid));
+  }
+
+  public void testWrongLength() {
+    int[] lengths = {0, 15, 17};
+    for (int length : lengths) {
+      try {
+        NativeTesting.TESTING_UuidToString(new byte[length]);
+        fail("should have thrown for " + length + " bytes");
+      } catch (IllegalArgumentException e) {
+        assertTrue(e.getMessage().contains("expected a 16-byte UUID, got " + length + " bytes"));
+      }
+    }
+  }
+
+  public void testNull() {
+    try {
+      NativeTesting.TESTING_UuidToString(null);
+      fail("should have thrown");
+    } catch (NullPointerException e) {
+      // Expected.
+    }
+  }
+}
@@ -6,6 +6,8 @@
 package org.signal.libsignal.crypto;
 
 import java.io.IOException;
+import java.nio.ByteBuffer;
+import java.util.Arrays;
 import junit.framework.TestCase;
 import org.whispersystems.libsignal.InvalidKeyException;
 import org.whispersystems.libsignal.InvalidMessageException;
@@ -45,6 +47,49 @@ public class Aes256GcmSivTests extends TestCase {
         "874296d5cc1fd16132");
   }
 
+  private static ByteBuffer directBufferWith(byte[] contents) {
+    ByteBuffer buffer = ByteBuffer.allocateDirect(contents.length);
+    buffer.put(contents);
+    buffer.flip();
+    return buffer;
+  }
+
+  public void testAesGcmSivDirectBuffers() throws Exception {
+    byte[] key = new byte[32];
+    byte[] nonce = new byte[12];
+    byte[] ad = new byte[5];
+    Aes256GcmSiv gcm_siv = new Aes256GcmSiv(key);
+
+    byte[] plaintext = new byte[1024 * 1024];
+    for (int i = 0; i < plaintext.length; i++) {
+      plaintext[i] = (byte)i;
+    }
+    byte[] ciphertext = gcm_siv.encrypt(plaintext, nonce, ad);
+
+    // The 1 MiB ciphertext is read in place from native memory, with no byte[] copy.
+    ByteBuffer directCiphertext = directBufferWith(ciphertext);
+    byte[] recovered =
+        gcm_siv.decrypt(directCiphertext, directBufferWith(nonce), directBufferWith(ad));
+    assertTrue(Arrays.equals(plaintext, recovered));
+    assertEquals(0, directCiphertext.position());
+
+    // Only the bytes between the position and the limit are used.
+    ByteBuffer padded = ByteBuffer.allocateDirect(ciphertext.length + 20);
+    padded.position(10);
+    padded.put(ciphertext);
+    padded.position(10);
+    padded.limit(10 + ciphertext.length);
+    recovered = gcm_siv.decrypt(padded, directBufferWith(nonce), directBufferWith(ad));
+    assertTrue(Arrays.equals(plaintext, recovered));
+
+    try {
+      gcm_siv.decrypt(ByteBuffer.wrap(ciphertext), directBufferWith(nonce), directBufferWith(ad));
+      throw new AssertionError("Non-direct buffer accepted");
+    } catch (IllegalArgumentException e) {
+      /* good */
+    }
+  }
+
   private static void testAesGcmSivKat(
       String hex_key,
       String hex_plaintext,
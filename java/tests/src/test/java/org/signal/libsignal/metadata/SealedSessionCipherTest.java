@@ -7,14 +7,17 @@ import org.signal.libsignal.metadata.certificate.CertificateValidator;
 import org.signal.libsignal.metadata.certificate.InvalidCertificateException;
 import org.signal.libsignal.metadata.certificate.SenderCertificate;
 import org.signal.libsignal.metadata.certificate.ServerCertificate;
+import org.signal.libsignal.metadata.protocol.UnidentifiedSenderMessageContent;
 import org.whispersystems.libsignal.IdentityKeyPair;
 import org.whispersystems.libsignal.InvalidKeyException;
+import org.whispersystems.libsignal.NoSessionException;
 import org.whispersystems.libsignal.SessionBuilder;
 import org.whispersystems.libsignal.SignalProtocolAddress;
 import org.whispersystems.libsignal.UntrustedIdentityException;
 import org.whispersystems.libsignal.ecc.Curve;
 import org.whispersystems.libsignal.ecc.ECKeyPair;
 import org.whispersystems.libsignal.ecc.ECPublicKey;
+import org.whispersystems.libsignal.protocol.CiphertextMessage;
 import org.whispersystems.libsignal.state.PreKeyBundle;
 import org.whispersystems.libsignal.state.PreKeyRecord;
 import org.whispersystems.libsignal.state.SignedPreKeyRecord;
@@ -23,6 +26,8 @@ import org.signal.client.internal.Native;
 
 import org.whispersystems.libsignal.util.Pair;
 
+import java.util.Arrays;
+import java.util.Collections;
 import java.util.UUID;
 
 public class SealedSessionCipherTest extends TestCase {
@@ -60,6 +65,32 @@ public class SealedSessionCipherTest extends TestCase {
     assertEquals(plaintext.getDeviceId(), 1);
   }
 
+  public void testMultiRecipientEncrypt() throws Exception {
+    TestInMemorySignalProtocolStore aliceStore = new TestInMemorySignalProtocolStore();
+    TestInMemorySignalProtocolStore bobStore   = new TestInMemorySignalProtocolStore();
+
+    initializeSessions(aliceStore, bobStore);
+
+    ECKeyPair           trustRoot         = Curve.generateKeyPair();
+    SenderCertificate   senderCertificate = createCertificateFor(trustRoot, UUID.fromString("9d0652a3-dcc3-4d11-975f-74d61598733f"), "+14151111111", 1, aliceStore.getIdentityKeyPair().getPublicKey().getPublicKey(), 31337);
+    SealedSessionCipher aliceCipher       = new SealedSessionCipher(aliceStore, UUID.fromString("9d0652a3-dcc3-4d11-975f-74d61598733f"), "+14151111111", 1);
+
+    UnidentifiedSenderMessageContent content = new UnidentifiedSenderMessageContent(CiphertextMessage.WHISPER_TYPE, senderCertificate, "smert za smert".getBytes());
+
+    byte[] ciphertext = aliceCipher.multiRecipientEncrypt(Arrays.asList(new SignalProtocolAddress("+14152222222", 1)), content);
+    assertTrue(ciphertext.length > 0);
+
+    byte[] emptyCiphertext = aliceCipher.multiRecipientEncrypt(Collections.<SignalProtocolAddress>emptyList(), content);
+    assertTrue(emptyCiphertext.length < ciphertext.length);
+
+    try {
+      aliceCipher.multiRecipientEncrypt(Arrays.asList(new SignalProtocolAddress("+14153333333", 1)), content);
+      fail("should have failed");
+    } catch (NoSessionException e) {
+      // good
+    }
+  }
+
   public void testEncryptDecryptUntrusted() throws Exception {
     TestInMemorySignalProtocolStore aliceStore = new TestInMemorySignalProtocolStore();
     TestInMemorySignalProtocolStore bobStore   = new TestInMemorySignalProtocolStore();
@@ -91,8 +91,12 @@ public class SessionBuilderTest extends TestCase {
     try {
       plaintext = bobSessionCipher.decrypt(new PreKeySignalMessage(outgoingMessage.serialize()));
       throw new AssertionError("shouldn't be trusted!");
-    } catch (UntrustedIdentityException uie) {
-      bobStore.saveIdentity(ALICE_ADDRESS, new PreKeySignalMessage(outgoingMessage.serialize()).getIdentityKey());
+    } catch (IdentityKeyChangedException ikce) {
+      IdentityKey newIdentity = new PreKeySignalMessage(outgoingMessage.serialize()).getIdentityKey();
+      assertEquals(ALICE_ADDRESS.getName(), ikce.getName());
+      assertEquals(bobStore.getIdentity(ALICE_ADDRESS), ikce.getPreviousIdentity());
+      assertEquals(newIdentity, ikce.getUntrustedIdentity());
+      bobStore.saveIdentity(ALICE_ADDRESS, newIdentity);
     }
 
     plaintext = bobSessionCipher.decrypt(new PreKeySignalMessage(outgoingMessage.serialize()));
@@ -382,6 +386,61 @@ public class SessionBuilderTest extends TestCase {
   }
 
 
+  public void testMaxSkippedMessageKeys() throws Exception {
+    SignalProtocolStore aliceStore          = new TestInMemorySignalProtocolStore();
+    SessionBuilder      aliceSessionBuilder = new SessionBuilder(aliceStore, BOB_ADDRESS);
+
+    SignalProtocolStore bobStore            = new TestInMemorySignalProtocolStore();
+    SessionBuilder      bobSessionBuilder   = new SessionBuilder(bobStore, ALICE_ADDRESS);
+
+    try {
+      bobSessionBuilder.setMaxSkippedMessageKeys(2);
+      fail("should have thrown without a session");
+    } catch (NoSessionException e) {
+      // good
+    }
+
+    ECKeyPair bobPreKeyPair            = Curve.generateKeyPair();
+    ECKeyPair bobSignedPreKeyPair      = Curve.generateKeyPair();
+    byte[]    bobSignedPreKeySignature = Curve.calculateSignature(bobStore.getIdentityKeyPair().getPrivateKey(),
+                                                                  bobSignedPreKeyPair.getPublicKey().serialize());
+
+    PreKeyBundle bobPreKey = new PreKeyBundle(bobStore.getLocalRegistrationId(), 1,
+                                              31337, bobPreKeyPair.getPublicKey(),
+                                              22, bobSignedPreKeyPair.getPublicKey(),
+                                              bobSignedPreKeySignature,
+                                              bobStore.getIdentityKeyPair().getPublicKey());
+    bobStore.storePreKey(31337, new PreKeyRecord(bobPreKey.getPreKeyId(), bobPreKeyPair));
+    bobStore.storeSignedPreKey(22, new SignedPreKeyRecord(22, System.currentTimeMillis(), bobSignedPreKeyPair, bobSignedPreKeySignature));
+
+    aliceSessionBuilder.process(bobPreKey);
+
+    SessionCipher aliceSessionCipher = new SessionCipher(aliceStore, BOB_ADDRESS);
+    SessionCipher bobSessionCipher   = new SessionCipher(bobStore, ALICE_ADDRESS);
+
+    CiphertextMessage first = aliceSessionCipher.encrypt("msg 0".getBytes());
+    assertEquals("msg 0", new String(bobSessionCipher.decrypt(new PreKeySignalMessage(first.serialize()))));
+
+    bobSessionBuilder.setMaxSkippedMessageKeys(2);
+
+    CiphertextMessage[] inflight = new CiphertextMessage[4];
+    for (int i = 0; i < inflight.length; i++) {
+      inflight[i] = aliceSessionCipher.encrypt(("msg " + (i + 1)).getBytes());
+    }
+
+    // Decrypting the last message would skip three keys.
+    try {
+      bobSessionCipher.decrypt(new PreKeySignalMessage(inflight[3].serialize()));
+      fail("should have exceeded the limit");
+    } catch (InvalidMessageException e) {
+      // good
+    }
+
+    // Skipping two is fine, and then the last message is only one ahead.
+    assertEquals("msg 3", new String(bobSessionCipher.decrypt(new PreKeySignalMessage(inflight[2].serialize()))));
+    assertEquals("msg 4", new String(bobSessionCipher.decrypt(new PreKeySignalMessage(inflight[3].serialize()))));
+  }
+
   private void runInteraction(SignalProtocolStore aliceStore, SignalProtocolStore bobStore)
       throws DuplicateMessageException, LegacyMessageException, InvalidMessageException, NoSessionException, UntrustedIdentityException
   {
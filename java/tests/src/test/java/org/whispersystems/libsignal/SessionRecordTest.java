@@ -17,4 +17,17 @@ public class SessionRecordTest extends TestCase {
 
     assertEquals(empty_record.getSessionVersion(), 0);
   }
+
+  public void testUnknownVersion() throws Exception {
+    byte[] serialized = new SessionRecord().serialize();
+    assertEquals(new SessionRecord(serialized).getSessionVersion(), 0);
+
+    serialized[0] += 1;
+    try {
+      new SessionRecord(serialized);
+      fail("should have rejected the newer version");
+    } catch (UnknownSessionVersionException e) {
+      // good
+    }
+  }
 }
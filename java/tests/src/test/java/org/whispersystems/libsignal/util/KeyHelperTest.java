@@ -0,0 +1,36 @@
+//
+// Copyright 2021 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+package org.whispersystems.libsignal.util;
+
+import java.util.List;
+import junit.framework.TestCase;
+import org.whispersystems.libsignal.state.PreKeyRecord;
+
+public class KeyHelperTest extends TestCase {
+
+  public void testGeneratePreKeys() throws Exception {
+    List<PreKeyRecord> records = KeyHelper.generatePreKeys(100, 3);
+    assertEquals(3, records.size());
+    for (int i = 0; i < records.size(); i++) {
+      PreKeyRecord record = records.get(i);
+      assertEquals(100 + i, record.getId());
+      assertEquals(100 + i, new PreKeyRecord(record.serialize()).getId());
+    }
+  }
+
+  public void testGeneratePreKeysEmpty() {
+    assertTrue(KeyHelper.generatePreKeys(5, 0).isEmpty());
+  }
+
+  public void testGeneratePreKeysTooMany() {
+    try {
+      KeyHelper.generatePreKeys(5, 65536);
+      fail("should have thrown");
+    } catch (IllegalArgumentException e) {
+      // Expected.
+    }
+  }
+}
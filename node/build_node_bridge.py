@@ -72,6 +72,11 @@ def main(args=None):
     cmdline = ['cargo', 'build', '--target', cargo_target, '-p', 'libsignal-node']
     if configuration_name == 'Release':
         cmdline.append('--release')
+    if os.environ.get('LIBSIGNAL_TESTING'):
+        # Include the TESTING_ entry points used by node/test.
+        # Features can only be selected for the package whose manifest cargo is pointed at.
+        cmdline += ['--manifest-path', os.path.join('rust', 'bridge', 'node', 'Cargo.toml'),
+                    '--features', 'testing']
     print("Running '%s'" % (' '.join(cmdline)))
 
     cargo_env = os.environ.copy()
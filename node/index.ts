@@ -348,6 +348,12 @@ export class PreKeyRecord {
     return new PreKeyRecord(SC.PreKeyRecord_Deserialize(buffer));
   }
 
+  static generateBatch(startId: number, count: number): PreKeyRecord[] {
+    return SC.PreKeyRecord_GenerateBatch(startId, count).map(handle =>
+      PreKeyRecord._fromNativeHandle(handle)
+    );
+  }
+
   id(): number {
     return SC.PreKeyRecord_GetId(this);
   }
@@ -380,13 +386,19 @@ export class SignedPreKeyRecord {
 
   static new(
     id: number,
-    timestamp: number,
+    timestamp: number | bigint,
     pubKey: PublicKey,
     privKey: PrivateKey,
     signature: Buffer
   ): SignedPreKeyRecord {
     return new SignedPreKeyRecord(
-      SC.SignedPreKeyRecord_New(id, timestamp, pubKey, privKey, signature)
+      SC.SignedPreKeyRecord_New(
+        id,
+        BigInt(timestamp),
+        pubKey,
+        privKey,
+        signature
+      )
     );
   }
 
@@ -418,7 +430,7 @@ export class SignedPreKeyRecord {
     return SC.SignedPreKeyRecord_GetSignature(this);
   }
 
-  timestamp(): number {
+  timestamp(): bigint {
     return SC.SignedPreKeyRecord_GetTimestamp(this);
   }
 }
@@ -567,6 +579,10 @@ export class SessionRecord {
     SC.SessionRecord_ArchiveCurrentState(this);
   }
 
+  hasCurrentState(): boolean {
+    return SC.SessionRecord_HasCurrentState(this);
+  }
+
   localRegistrationId(): number {
     return SC.SessionRecord_GetLocalRegistrationId(this);
   }
@@ -574,6 +590,10 @@ export class SessionRecord {
   remoteRegistrationId(): number {
     return SC.SessionRecord_GetRemoteRegistrationId(this);
   }
+
+  remoteIdentityKeyBytes(): Buffer | null {
+    return SC.SessionRecord_GetRemoteIdentityKeyPublic(this);
+  }
 }
 
 export class SenderKeyName {
@@ -704,7 +724,7 @@ export class SenderCertificate {
     senderE164: string | null,
     senderDeviceId: number,
     senderKey: PublicKey,
-    expiration: number,
+    expiration: number | bigint,
     signerCert: ServerCertificate,
     signerKey: PrivateKey
   ): SenderCertificate {
@@ -714,7 +734,7 @@ export class SenderCertificate {
         senderE164,
         senderDeviceId,
         senderKey,
-        expiration,
+        BigInt(expiration),
         signerCert,
         signerKey
       )
@@ -732,7 +752,7 @@ export class SenderCertificate {
   certificate(): Buffer {
     return SC.SenderCertificate_GetCertificate(this);
   }
-  expiration(): number {
+  expiration(): bigint {
     return SC.SenderCertificate_GetExpiration(this);
   }
   key(): PublicKey {
@@ -755,8 +775,8 @@ export class SenderCertificate {
   signature(): Buffer {
     return SC.SenderCertificate_GetSignature(this);
   }
-  validate(trustRoot: PublicKey, time: number): boolean {
-    return SC.SenderCertificate_Validate(this, trustRoot, time);
+  validate(trustRoot: PublicKey, time: number | bigint): boolean {
+    return SC.SenderCertificate_Validate(this, trustRoot, BigInt(time));
   }
 }
 
@@ -1202,10 +1222,24 @@ export function sealedSenderEncryptMessage(
   );
 }
 
+export function sealedSenderMultiRecipientEncrypt(
+  content: UnidentifiedSenderMessageContent,
+  recipients: ProtocolAddress[],
+  recipientSessions: SessionRecord[],
+  identityStore: IdentityKeyStore
+): Promise<Buffer> {
+  return SC.SealedSender_MultiRecipientEncrypt(
+    recipients,
+    recipientSessions,
+    content,
+    identityStore
+  );
+}
+
 export async function sealedSenderDecryptMessage(
   message: Buffer,
   trustRoot: PublicKey,
-  timestamp: number,
+  timestamp: number | bigint,
   localE164: string | null,
   localUuid: string,
   localDeviceId: number,
@@ -1217,7 +1251,7 @@ export async function sealedSenderDecryptMessage(
   const ssdr = await SC.SealedSender_DecryptMessage(
     message,
     trustRoot,
-    timestamp,
+    BigInt(timestamp),
     localE164,
     localUuid,
     localDeviceId,
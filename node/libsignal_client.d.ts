@@ -34,24 +34,31 @@ export abstract class SenderKeyStore {
   _getSenderKey(name: SenderKeyName): Promise<SenderKeyRecord | null>;
 }
 
-interface Wrapper<T> {
+export interface Wrapper<T> {
   readonly _nativeHandle: T
 }
 
+export type Uint8Like = Uint8Array | ArrayBuffer;
+
+type CancellablePromise<T> = Promise<T> & {
+  cancel(): void;
+};
+
 
 export const enum LogLevel { Error, Warn, Info, Debug, Trace }
-export function Aes256GcmSiv_Decrypt(aesGcmSiv: Wrapper<Aes256GcmSiv>, ctext: Buffer, nonce: Buffer, associatedData: Buffer): Buffer;
-export function Aes256GcmSiv_Encrypt(aesGcmSiv: Wrapper<Aes256GcmSiv>, ptext: Buffer, nonce: Buffer, associatedData: Buffer): Buffer;
-export function Aes256GcmSiv_New(key: Buffer): Aes256GcmSiv;
+export function Aes256GcmSiv_Decrypt(aesGcmSiv: Wrapper<Aes256GcmSiv>, ctext: Uint8Like, nonce: Uint8Like, associatedData: Uint8Like): Buffer;
+export function Aes256GcmSiv_Encrypt(aesGcmSiv: Wrapper<Aes256GcmSiv>, ptext: Uint8Like, nonce: Uint8Like, associatedData: Uint8Like): Buffer;
+export function Aes256GcmSiv_New(key: Uint8Like): Aes256GcmSiv;
 export function CiphertextMessage_Serialize(obj: Wrapper<CiphertextMessage>): Buffer;
 export function CiphertextMessage_Type(msg: Wrapper<CiphertextMessage>): number;
 export function Fingerprint_DisplayString(obj: Wrapper<Fingerprint>): string;
-export function Fingerprint_New(iterations: number, version: number, localIdentifier: Buffer, localKey: Wrapper<PublicKey>, remoteIdentifier: Buffer, remoteKey: Wrapper<PublicKey>): Fingerprint;
+export function Fingerprint_New(iterations: number, version: number, localIdentifier: Uint8Like, localKey: Wrapper<PublicKey>, remoteIdentifier: Uint8Like, remoteKey: Wrapper<PublicKey>): Fingerprint;
 export function Fingerprint_ScannableEncoding(obj: Wrapper<Fingerprint>): Buffer;
-export function GroupCipher_Decrypt(senderKeyName: Wrapper<SenderKeyName>, message: Buffer, store: SenderKeyStore): Promise<Buffer>;
-export function GroupCipher_Encrypt(senderKeyName: Wrapper<SenderKeyName>, message: Buffer, store: SenderKeyStore): Promise<Buffer>;
-export function HKDF_DeriveSecrets(outputLength: number, version: number, ikm: Buffer, label: Buffer, salt: Buffer | null): Buffer;
+export function GroupCipher_Decrypt(senderKeyName: Wrapper<SenderKeyName>, message: Uint8Like, store: SenderKeyStore): Promise<Buffer>;
+export function GroupCipher_Encrypt(senderKeyName: Wrapper<SenderKeyName>, message: Uint8Like, store: SenderKeyStore): Promise<Buffer>;
+export function HKDF_DeriveSecrets(outputLength: number, version: number, ikm: Uint8Like, label: Uint8Like, salt: Uint8Like | null): Buffer;
 export function IdentityKeyPair_Serialize(publicKey: Wrapper<PublicKey>, privateKey: Wrapper<PrivateKey>): Buffer;
+export function Poksho_MultiScalarMul(scalars: Uint8Like, points: Uint8Like): Buffer;
 export function PreKeyBundle_GetDeviceId(obj: Wrapper<PreKeyBundle>): number;
 export function PreKeyBundle_GetIdentityKey(p: Wrapper<PreKeyBundle>): PublicKey;
 export function PreKeyBundle_GetPreKeyId(obj: Wrapper<PreKeyBundle>): number | null;
@@ -60,8 +67,9 @@ export function PreKeyBundle_GetRegistrationId(obj: Wrapper<PreKeyBundle>): numb
 export function PreKeyBundle_GetSignedPreKeyId(obj: Wrapper<PreKeyBundle>): number;
 export function PreKeyBundle_GetSignedPreKeyPublic(obj: Wrapper<PreKeyBundle>): PublicKey;
 export function PreKeyBundle_GetSignedPreKeySignature(obj: Wrapper<PreKeyBundle>): Buffer;
-export function PreKeyBundle_New(registrationId: number, deviceId: number, prekeyId: number | null, prekey: Wrapper<PublicKey> | null, signedPrekeyId: number, signedPrekey: Wrapper<PublicKey>, signedPrekeySignature: Buffer, identityKey: Wrapper<PublicKey>): PreKeyBundle;
+export function PreKeyBundle_New(registrationId: number, deviceId: number, prekeyId: number | null, prekey: Wrapper<PublicKey> | null, signedPrekeyId: number, signedPrekey: Wrapper<PublicKey>, signedPrekeySignature: Uint8Like, identityKey: Wrapper<PublicKey>): PreKeyBundle;
 export function PreKeyRecord_Deserialize(buffer: Buffer): PreKeyRecord;
+export function PreKeyRecord_GenerateBatch(startId: number, count: number): PreKeyRecord[];
 export function PreKeyRecord_GetId(obj: Wrapper<PreKeyRecord>): number;
 export function PreKeyRecord_GetPrivateKey(obj: Wrapper<PreKeyRecord>): PrivateKey;
 export function PreKeyRecord_GetPublicKey(obj: Wrapper<PreKeyRecord>): PublicKey;
@@ -79,7 +87,7 @@ export function PrivateKey_Deserialize(buffer: Buffer): PrivateKey;
 export function PrivateKey_Generate(): PrivateKey;
 export function PrivateKey_GetPublicKey(k: Wrapper<PrivateKey>): PublicKey;
 export function PrivateKey_Serialize(obj: Wrapper<PrivateKey>): Buffer;
-export function PrivateKey_Sign(key: Wrapper<PrivateKey>, message: Buffer): Buffer;
+export function PrivateKey_Sign(key: Wrapper<PrivateKey>, message: Uint8Like): Buffer;
 export function ProtocolAddress_DeviceId(obj: Wrapper<ProtocolAddress>): number;
 export function ProtocolAddress_Name(obj: Wrapper<ProtocolAddress>): string;
 export function ProtocolAddress_New(name: string, deviceId: number): ProtocolAddress;
@@ -87,40 +95,42 @@ export function PublicKey_Compare(key1: Wrapper<PublicKey>, key2: Wrapper<Public
 export function PublicKey_Deserialize(buffer: Buffer): PublicKey;
 export function PublicKey_GetPublicKeyBytes(obj: Wrapper<PublicKey>): Buffer;
 export function PublicKey_Serialize(obj: Wrapper<PublicKey>): Buffer;
-export function PublicKey_Verify(key: Wrapper<PublicKey>, message: Buffer, signature: Buffer): boolean;
-export function ScannableFingerprint_Compare(fprint1: Buffer, fprint2: Buffer): boolean;
+export function PublicKey_Verify(key: Wrapper<PublicKey>, message: Uint8Like, signature: Uint8Like): boolean;
+export function PublicKey_VerifyBatch(key: Wrapper<PublicKey>, messages: Uint8Like, signatures: Uint8Like): boolean;
+export function ScannableFingerprint_Compare(fprint1: Uint8Like, fprint2: Uint8Like): boolean;
 export function SealedSenderDecryptionResult_GetDeviceId(obj: Wrapper<SealedSenderDecryptionResult>): number;
 export function SealedSenderDecryptionResult_GetSenderE164(obj: Wrapper<SealedSenderDecryptionResult>): string | null;
 export function SealedSenderDecryptionResult_GetSenderUuid(obj: Wrapper<SealedSenderDecryptionResult>): string;
 export function SealedSenderDecryptionResult_Message(obj: Wrapper<SealedSenderDecryptionResult>): Buffer;
-export function SealedSender_DecryptMessage(message: Buffer, trustRoot: Wrapper<PublicKey>, timestamp: number, localE164: string | null, localUuid: string, localDeviceId: number, sessionStore: SessionStore, identityStore: IdentityKeyStore, prekeyStore: PreKeyStore, signedPrekeyStore: SignedPreKeyStore): Promise<SealedSenderDecryptionResult>;
-export function SealedSender_DecryptToUsmc(ctext: Buffer, identityStore: IdentityKeyStore): Promise<UnidentifiedSenderMessageContent>;
-export function SealedSender_EncryptMessage(destination: Wrapper<ProtocolAddress>, senderCert: Wrapper<SenderCertificate>, ptext: Buffer, sessionStore: SessionStore, identityStore: IdentityKeyStore): Promise<Buffer>;
+export function SealedSender_DecryptMessage(message: Uint8Like, trustRoot: Wrapper<PublicKey>, timestamp: bigint, localE164: string | null, localUuid: string, localDeviceId: number, sessionStore: SessionStore, identityStore: IdentityKeyStore, prekeyStore: PreKeyStore, signedPrekeyStore: SignedPreKeyStore): Promise<SealedSenderDecryptionResult>;
+export function SealedSender_DecryptToUsmc(ctext: Uint8Like, identityStore: IdentityKeyStore): Promise<UnidentifiedSenderMessageContent>;
+export function SealedSender_EncryptMessage(destination: Wrapper<ProtocolAddress>, senderCert: Wrapper<SenderCertificate>, ptext: Uint8Like, sessionStore: SessionStore, identityStore: IdentityKeyStore): Promise<Buffer>;
+export function SealedSender_MultiRecipientEncrypt(recipients: Wrapper<ProtocolAddress>[], recipientSessions: Wrapper<SessionRecord>[], content: Wrapper<UnidentifiedSenderMessageContent>, identityStore: IdentityKeyStore): Promise<Buffer>;
 export function SenderCertificate_Deserialize(buffer: Buffer): SenderCertificate;
 export function SenderCertificate_GetCertificate(obj: Wrapper<SenderCertificate>): Buffer;
 export function SenderCertificate_GetDeviceId(obj: Wrapper<SenderCertificate>): number;
-export function SenderCertificate_GetExpiration(obj: Wrapper<SenderCertificate>): number;
+export function SenderCertificate_GetExpiration(obj: Wrapper<SenderCertificate>): bigint;
 export function SenderCertificate_GetKey(obj: Wrapper<SenderCertificate>): PublicKey;
 export function SenderCertificate_GetSenderE164(obj: Wrapper<SenderCertificate>): string | null;
 export function SenderCertificate_GetSenderUuid(obj: Wrapper<SenderCertificate>): string;
 export function SenderCertificate_GetSerialized(obj: Wrapper<SenderCertificate>): Buffer;
-export function SenderCertificate_GetServerCertificate(cert: Wrapper<SenderCertificate>): ServerCertificate;
+export function SenderCertificate_GetServerCertificate(obj: Wrapper<SenderCertificate>): ServerCertificate;
 export function SenderCertificate_GetSignature(obj: Wrapper<SenderCertificate>): Buffer;
-export function SenderCertificate_New(senderUuid: string, senderE164: string | null, senderDeviceId: number, senderKey: Wrapper<PublicKey>, expiration: number, signerCert: Wrapper<ServerCertificate>, signerKey: Wrapper<PrivateKey>): SenderCertificate;
-export function SenderCertificate_Validate(cert: Wrapper<SenderCertificate>, key: Wrapper<PublicKey>, time: number): boolean;
+export function SenderCertificate_New(senderUuid: string, senderE164: string | null, senderDeviceId: number, senderKey: Wrapper<PublicKey>, expiration: bigint, signerCert: Wrapper<ServerCertificate>, signerKey: Wrapper<PrivateKey>): SenderCertificate;
+export function SenderCertificate_Validate(cert: Wrapper<SenderCertificate>, key: Wrapper<PublicKey>, time: bigint): boolean;
 export function SenderKeyDistributionMessage_Create(senderKeyName: Wrapper<SenderKeyName>, store: SenderKeyStore): Promise<SenderKeyDistributionMessage>;
 export function SenderKeyDistributionMessage_Deserialize(buffer: Buffer): SenderKeyDistributionMessage;
 export function SenderKeyDistributionMessage_GetChainKey(obj: Wrapper<SenderKeyDistributionMessage>): Buffer;
 export function SenderKeyDistributionMessage_GetId(obj: Wrapper<SenderKeyDistributionMessage>): number;
 export function SenderKeyDistributionMessage_GetIteration(obj: Wrapper<SenderKeyDistributionMessage>): number;
-export function SenderKeyDistributionMessage_New(keyId: number, iteration: number, chainkey: Buffer, pk: Wrapper<PublicKey>): SenderKeyDistributionMessage;
+export function SenderKeyDistributionMessage_New(keyId: number, iteration: number, chainkey: Uint8Like, pk: Wrapper<PublicKey>): SenderKeyDistributionMessage;
 export function SenderKeyDistributionMessage_Process(senderKeyName: Wrapper<SenderKeyName>, senderKeyDistributionMessage: Wrapper<SenderKeyDistributionMessage>, store: SenderKeyStore): Promise<void>;
 export function SenderKeyDistributionMessage_Serialize(obj: Wrapper<SenderKeyDistributionMessage>): Buffer;
 export function SenderKeyMessage_Deserialize(buffer: Buffer): SenderKeyMessage;
 export function SenderKeyMessage_GetCipherText(obj: Wrapper<SenderKeyMessage>): Buffer;
 export function SenderKeyMessage_GetIteration(obj: Wrapper<SenderKeyMessage>): number;
 export function SenderKeyMessage_GetKeyId(obj: Wrapper<SenderKeyMessage>): number;
-export function SenderKeyMessage_New(keyId: number, iteration: number, ciphertext: Buffer, pk: Wrapper<PrivateKey>): SenderKeyMessage;
+export function SenderKeyMessage_New(keyId: number, iteration: number, ciphertext: Uint8Like, pk: Wrapper<PrivateKey>): SenderKeyMessage;
 export function SenderKeyMessage_Serialize(obj: Wrapper<SenderKeyMessage>): Buffer;
 export function SenderKeyMessage_VerifySignature(skm: Wrapper<SenderKeyMessage>, pubkey: Wrapper<PublicKey>): boolean;
 export function SenderKeyName_GetGroupId(obj: Wrapper<SenderKeyName>): string;
@@ -130,6 +140,7 @@ export function SenderKeyName_New(groupId: string, senderName: string, senderDev
 export function SenderKeyRecord_Deserialize(buffer: Buffer): SenderKeyRecord;
 export function SenderKeyRecord_New(): SenderKeyRecord;
 export function SenderKeyRecord_Serialize(obj: Wrapper<SenderKeyRecord>): Buffer;
+export function SenderKey_DeriveMessageKey(chainKey: Uint8Like, iteration: number): Buffer;
 export function ServerCertificate_Deserialize(buffer: Buffer): ServerCertificate;
 export function ServerCertificate_GetCertificate(obj: Wrapper<ServerCertificate>): Buffer;
 export function ServerCertificate_GetKey(obj: Wrapper<ServerCertificate>): PublicKey;
@@ -140,50 +151,52 @@ export function ServerCertificate_New(keyId: number, serverKey: Wrapper<PublicKe
 export function SessionBuilder_ProcessPreKeyBundle(bundle: Wrapper<PreKeyBundle>, protocolAddress: Wrapper<ProtocolAddress>, sessionStore: SessionStore, identityKeyStore: IdentityKeyStore): Promise<void>;
 export function SessionCipher_DecryptPreKeySignalMessage(message: Wrapper<PreKeySignalMessage>, protocolAddress: Wrapper<ProtocolAddress>, sessionStore: SessionStore, identityKeyStore: IdentityKeyStore, prekeyStore: PreKeyStore, signedPrekeyStore: SignedPreKeyStore): Promise<Buffer>;
 export function SessionCipher_DecryptSignalMessage(message: Wrapper<SignalMessage>, protocolAddress: Wrapper<ProtocolAddress>, sessionStore: SessionStore, identityKeyStore: IdentityKeyStore): Promise<Buffer>;
-export function SessionCipher_EncryptMessage(ptext: Buffer, protocolAddress: Wrapper<ProtocolAddress>, sessionStore: SessionStore, identityKeyStore: IdentityKeyStore): Promise<CiphertextMessage>;
+export function SessionCipher_EncryptMessage(ptext: Uint8Like, protocolAddress: Wrapper<ProtocolAddress>, sessionStore: SessionStore, identityKeyStore: IdentityKeyStore): Promise<CiphertextMessage>;
 export function SessionRecord_ArchiveCurrentState(sessionRecord: Wrapper<SessionRecord>): void;
 export function SessionRecord_Deserialize(buffer: Buffer): SessionRecord;
 export function SessionRecord_GetLocalRegistrationId(obj: Wrapper<SessionRecord>): number;
+export function SessionRecord_GetRemoteIdentityKeyPublic(obj: Wrapper<SessionRecord>): Buffer | null;
 export function SessionRecord_GetRemoteRegistrationId(obj: Wrapper<SessionRecord>): number;
+export function SessionRecord_HasCurrentState(obj: Wrapper<SessionRecord>): boolean;
 export function SessionRecord_Serialize(obj: Wrapper<SessionRecord>): Buffer;
 export function SignalMessage_Deserialize(buffer: Buffer): SignalMessage;
 export function SignalMessage_GetBody(obj: Wrapper<SignalMessage>): Buffer;
 export function SignalMessage_GetCounter(obj: Wrapper<SignalMessage>): number;
 export function SignalMessage_GetMessageVersion(obj: Wrapper<SignalMessage>): number;
 export function SignalMessage_GetSerialized(obj: Wrapper<SignalMessage>): Buffer;
-export function SignalMessage_New(messageVersion: number, macKey: Buffer, senderRatchetKey: Wrapper<PublicKey>, counter: number, previousCounter: number, ciphertext: Buffer, senderIdentityKey: Wrapper<PublicKey>, receiverIdentityKey: Wrapper<PublicKey>): SignalMessage;
-export function SignalMessage_VerifyMac(msg: Wrapper<SignalMessage>, senderIdentityKey: Wrapper<PublicKey>, receiverIdentityKey: Wrapper<PublicKey>, macKey: Buffer): boolean;
+export function SignalMessage_New(messageVersion: number, macKey: Uint8Like, senderRatchetKey: Wrapper<PublicKey>, counter: number, previousCounter: number, ciphertext: Uint8Like, senderIdentityKey: Wrapper<PublicKey>, receiverIdentityKey: Wrapper<PublicKey>): SignalMessage;
+export function SignalMessage_VerifyMac(msg: Wrapper<SignalMessage>, senderIdentityKey: Wrapper<PublicKey>, receiverIdentityKey: Wrapper<PublicKey>, macKey: Uint8Like): boolean;
 export function SignedPreKeyRecord_Deserialize(buffer: Buffer): SignedPreKeyRecord;
 export function SignedPreKeyRecord_GetId(obj: Wrapper<SignedPreKeyRecord>): number;
 export function SignedPreKeyRecord_GetPrivateKey(obj: Wrapper<SignedPreKeyRecord>): PrivateKey;
 export function SignedPreKeyRecord_GetPublicKey(obj: Wrapper<SignedPreKeyRecord>): PublicKey;
 export function SignedPreKeyRecord_GetSignature(obj: Wrapper<SignedPreKeyRecord>): Buffer;
-export function SignedPreKeyRecord_GetTimestamp(obj: Wrapper<SignedPreKeyRecord>): number;
-export function SignedPreKeyRecord_New(id: number, timestamp: number, pubKey: Wrapper<PublicKey>, privKey: Wrapper<PrivateKey>, signature: Buffer): SignedPreKeyRecord;
+export function SignedPreKeyRecord_GetTimestamp(obj: Wrapper<SignedPreKeyRecord>): bigint;
+export function SignedPreKeyRecord_New(id: number, timestamp: bigint, pubKey: Wrapper<PublicKey>, privKey: Wrapper<PrivateKey>, signature: Uint8Like): SignedPreKeyRecord;
 export function SignedPreKeyRecord_Serialize(obj: Wrapper<SignedPreKeyRecord>): Buffer;
 export function UnidentifiedSenderMessageContent_Deserialize(buffer: Buffer): UnidentifiedSenderMessageContent;
 export function UnidentifiedSenderMessageContent_GetContents(obj: Wrapper<UnidentifiedSenderMessageContent>): Buffer;
 export function UnidentifiedSenderMessageContent_GetMsgType(m: Wrapper<UnidentifiedSenderMessageContent>): number;
-export function UnidentifiedSenderMessageContent_GetSenderCert(m: Wrapper<UnidentifiedSenderMessageContent>): SenderCertificate;
+export function UnidentifiedSenderMessageContent_GetSenderCert(obj: Wrapper<UnidentifiedSenderMessageContent>): SenderCertificate;
 export function UnidentifiedSenderMessageContent_Serialize(obj: Wrapper<UnidentifiedSenderMessageContent>): Buffer;
-export function initLogger(maxLevel: LogLevel, callback: (level: LogLevel, target: string, file: string | null, line: number | null, message: string) => void): void
-interface Aes256GcmSiv { readonly __type: unique symbol; }
-interface CiphertextMessage { readonly __type: unique symbol; }
-interface Fingerprint { readonly __type: unique symbol; }
-interface PreKeyBundle { readonly __type: unique symbol; }
-interface PreKeyRecord { readonly __type: unique symbol; }
-interface PreKeySignalMessage { readonly __type: unique symbol; }
-interface PrivateKey { readonly __type: unique symbol; }
-interface ProtocolAddress { readonly __type: unique symbol; }
-interface PublicKey { readonly __type: unique symbol; }
-interface SealedSenderDecryptionResult { readonly __type: unique symbol; }
-interface SenderCertificate { readonly __type: unique symbol; }
-interface SenderKeyDistributionMessage { readonly __type: unique symbol; }
-interface SenderKeyMessage { readonly __type: unique symbol; }
-interface SenderKeyName { readonly __type: unique symbol; }
-interface SenderKeyRecord { readonly __type: unique symbol; }
-interface ServerCertificate { readonly __type: unique symbol; }
-interface SessionRecord { readonly __type: unique symbol; }
-interface SignalMessage { readonly __type: unique symbol; }
-interface SignedPreKeyRecord { readonly __type: unique symbol; }
-interface UnidentifiedSenderMessageContent { readonly __type: unique symbol; }
+export function initLogger(maxLevel: LogLevel, callback: (level: LogLevel, target: string, file: string | null, line: number | null, message: string) => void): void;
+export interface Aes256GcmSiv { readonly __type: unique symbol; }
+export interface CiphertextMessage { readonly __type: unique symbol; }
+export interface Fingerprint { readonly __type: unique symbol; }
+export interface PreKeyBundle { readonly __type: unique symbol; }
+export interface PreKeyRecord { readonly __type: unique symbol; }
+export interface PreKeySignalMessage { readonly __type: unique symbol; }
+export interface PrivateKey { readonly __type: unique symbol; }
+export interface ProtocolAddress { readonly __type: unique symbol; }
+export interface PublicKey { readonly __type: unique symbol; }
+export interface SealedSenderDecryptionResult { readonly __type: unique symbol; }
+export interface SenderCertificate { readonly __type: unique symbol; }
+export interface SenderKeyDistributionMessage { readonly __type: unique symbol; }
+export interface SenderKeyMessage { readonly __type: unique symbol; }
+export interface SenderKeyName { readonly __type: unique symbol; }
+export interface SenderKeyRecord { readonly __type: unique symbol; }
+export interface ServerCertificate { readonly __type: unique symbol; }
+export interface SessionRecord { readonly __type: unique symbol; }
+export interface SignalMessage { readonly __type: unique symbol; }
+export interface SignedPreKeyRecord { readonly __type: unique symbol; }
+export interface UnidentifiedSenderMessageContent { readonly __type: unique symbol; }
@@ -0,0 +1,517 @@
+//
+// Copyright 2021 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+import { assert } from 'chai';
+import * as fs from 'fs';
+import * as os from 'os';
+import * as path from 'path';
+import { MessageChannel, Worker } from 'worker_threads';
+import bindings = require('bindings'); // eslint-disable-line @typescript-eslint/no-require-imports
+import * as Native from '../libsignal_client';
+import * as NativeTesting from './NativeTesting';
+
+const NATIVE_MODULE_NAME =
+  'libsignal_client_' + os.platform() + '_' + process.arch;
+const SC = bindings(NATIVE_MODULE_NAME) as typeof Native & typeof NativeTesting;
+
+describe('bridge', () => {
+  describe('Buffer[] results', () => {
+    function checkBufferList(count: number) {
+      const buffers = SC.TESTING_ReturnBufferList(count);
+      assert.lengthOf(buffers, count);
+      buffers.forEach((buffer, i) => {
+        assert.deepEqual(buffer, Buffer.alloc(i % 4, i % 256));
+      });
+    }
+
+    it('can be empty', () => {
+      checkBufferList(0);
+    });
+    it('can have one element', () => {
+      checkBufferList(1);
+    });
+    it('can have many elements', () => {
+      checkBufferList(1500);
+    });
+  });
+
+  describe('optional Buffer results', () => {
+    it('can be null', () => {
+      assert.isNull(SC.TESTING_ReturnOptionalBuffer(null));
+    });
+    it('can be empty without being null', () => {
+      const buffer = SC.TESTING_ReturnOptionalBuffer(0);
+      assert.isNotNull(buffer);
+      assert.lengthOf(buffer as Buffer, 0);
+    });
+    it('can have contents', () => {
+      assert.deepEqual(SC.TESTING_ReturnOptionalBuffer(5), Buffer.alloc(5, 5));
+    });
+  });
+
+  describe('tuple results', () => {
+    it('become objects with named fields', () => {
+      const result = SC.TESTING_ReturnTuple(0x01020305);
+      assert.deepEqual(result.buffer, Buffer.of(1, 2, 3, 5));
+      assert.equal(result.value, 0x01020305);
+      assert.isFalse(result.isEven);
+    });
+  });
+
+  describe('bool results', () => {
+    it('are booleans', () => {
+      assert.isTrue(SC.TESTING_IsEven(2));
+      assert.isFalse(SC.TESTING_IsEven(3));
+    });
+    it('can come from Results', () => {
+      assert.isTrue(SC.TESTING_BoolFromInt(1));
+      assert.isFalse(SC.TESTING_BoolFromInt(0));
+      assert.throws(() => SC.TESTING_BoolFromInt(2), 'expected 0 or 1');
+    });
+  });
+
+  describe('Buffer arguments', () => {
+    function sum(buffer: Uint8Array): number {
+      return buffer.reduce((total, b) => total + b, 0);
+    }
+    function makeBuffer(length: number): Buffer {
+      const buffer = Buffer.alloc(length);
+      for (let i = 0; i < length; ++i) {
+        buffer[i] = (i * 31) % 256;
+      }
+      return buffer;
+    }
+
+    it('are passed intact', () => {
+      for (const length of [0, 1, 1024, 1025, 1024 * 1024]) {
+        const data = makeBuffer(length);
+        assert.equal(SC.TESTING_SumBytes(data, null), sum(data));
+        assert.equal(
+          SC.TESTING_SumBytes(data, Buffer.of(1, 2, 3)),
+          sum(data) + 6
+        );
+      }
+    });
+    it('respect the offset and length of views into a larger buffer', () => {
+      const backing = makeBuffer(4096);
+      const view = backing.subarray(1000, 3000);
+      assert.equal(SC.TESTING_SumBytes(view, null), sum(view));
+      assert.equal(SC.TESTING_FirstByte(view), backing[1000]);
+      assert.isNull(SC.TESTING_FirstByte(backing.subarray(10, 10)));
+    });
+    it('see the current contents of the buffer', () => {
+      const data = makeBuffer(2048);
+      data[0] = 42;
+      assert.equal(SC.TESTING_FirstByte(data), 42);
+      data[0] = 43;
+      assert.equal(SC.TESTING_FirstByte(data), 43);
+    });
+    it('can be Uint8Array views into the middle of an ArrayBuffer', () => {
+      const backing = new ArrayBuffer(16);
+      new Uint8Array(backing).set(Buffer.from('xxxxhello, world'));
+      const view = new Uint8Array(backing, 4, 5);
+      assert.equal(SC.TESTING_StringFromUtf8(view), 'hello');
+      assert.equal(SC.TESTING_SumBytes(view, view), 2 * sum(view));
+      assert.equal(SC.TESTING_StringFromUtf8(new Uint8Array(backing, 16)), '');
+    });
+    it('can be ArrayBuffers', () => {
+      const data = makeBuffer(1025);
+      const copy = data.buffer.slice(
+        data.byteOffset,
+        data.byteOffset + data.byteLength
+      );
+      assert.equal(SC.TESTING_SumBytes(copy, new ArrayBuffer(0)), sum(data));
+    });
+    it('reject other objects', () => {
+      for (const value of [[1, 2, 3], { length: 0 }, 'abc', null]) {
+        assert.throws(
+          () => SC.TESTING_FirstByte((value as unknown) as Uint8Array),
+          TypeError
+        );
+      }
+    });
+    it('reject objects that only claim to be Uint8Arrays', () => {
+      const fakeView = {
+        [Symbol.toStringTag]: 'Uint8Array',
+        buffer: new ArrayBuffer(8),
+        byteOffset: 2,
+        byteLength: 7,
+      };
+      assert.throws(
+        () => SC.TESTING_FirstByte((fakeView as unknown) as Uint8Array),
+        TypeError
+      );
+    });
+    it('ignore overridden view properties', () => {
+      const backing = new ArrayBuffer(8);
+      new Uint8Array(backing).set([1, 2, 3, 4, 5, 6, 7, 8]);
+      const view = new Uint8Array(backing, 2, 3);
+      Object.defineProperty(view, 'byteLength', { value: 1000 });
+      Object.defineProperty(view, 'buffer', { value: new ArrayBuffer(0) });
+      assert.equal(SC.TESTING_SumBytes(view, null), 3 + 4 + 5);
+    });
+    it('are borrowed without copying (benchmark)', function () {
+      // Informational only: timings vary too much between machines to assert on.
+      this.timeout(10000);
+      const count = 1000;
+      const small = makeBuffer(16);
+      const large = makeBuffer(1024 * 1024);
+      function time(data: Buffer): number {
+        const start = process.hrtime.bigint();
+        for (let i = 0; i < count; ++i) {
+          SC.TESTING_FirstByte(data);
+        }
+        return Number(process.hrtime.bigint() - start) / count;
+      }
+      // Warm up first.
+      time(small);
+      time(large);
+      const smallNanos = time(small);
+      const largeNanos = time(large);
+      const copyStart = process.hrtime.bigint();
+      for (let i = 0; i < count; ++i) {
+        Buffer.from(large);
+      }
+      const copyNanos = Number(process.hrtime.bigint() - copyStart) / count;
+      console.log(
+        `      16 B: ${smallNanos.toFixed(0)} ns/call, ` +
+          `1 MiB: ${largeNanos.toFixed(0)} ns/call, ` +
+          `copying 1 MiB: ${copyNanos.toFixed(0)} ns`
+      );
+    });
+  });
+
+  describe('string arguments', () => {
+    it('are converted to UTF-8', () => {
+      assert.equal(SC.TESTING_CountUtf8Bytes(''), 0);
+      assert.equal(SC.TESTING_CountUtf8Bytes('abc'), 3);
+      assert.equal(SC.TESTING_CountUtf8Bytes('é中😀'), 9);
+    });
+    it('reject unpaired surrogates', () => {
+      for (const text of ['a\ud800', '\udc00b', '\ufffd\ud83d']) {
+        assert.throws(() => SC.TESTING_CountUtf8Bytes(text), TypeError);
+      }
+      // U+FFFD REPLACEMENT CHARACTER is fine on its own, and is three bytes in UTF-8.
+      assert.equal(SC.TESTING_CountUtf8Bytes('a\ufffd'), 4);
+    });
+    it('reject NUL characters', () => {
+      assert.throws(() => SC.TESTING_CountUtf8Bytes('a\0b'), TypeError);
+      assert.throws(() => SC.TESTING_ReturnOptionalString('a\0b'), TypeError);
+    });
+    it('can be owned and optional', () => {
+      assert.equal(SC.TESTING_ReturnOptionalString('é中😀'), 'é中😀');
+      assert.equal(SC.TESTING_ReturnOptionalString(''), '');
+      assert.isNull(SC.TESTING_ReturnOptionalString(null));
+    });
+  });
+
+  describe('string results', () => {
+    it('are converted from UTF-8', () => {
+      assert.equal(SC.TESTING_StringFromUtf8(Buffer.from('é中😀')), 'é中😀');
+      assert.equal(SC.TESTING_StringFromUtf8(Buffer.alloc(0)), '');
+    });
+    it('can contain NUL characters', () => {
+      assert.equal(SC.TESTING_StringFromUtf8(Buffer.of(0x61, 0, 0x62)), 'a\0b');
+    });
+  });
+
+  describe('u64 values', () => {
+    const bytesOf = (value: bigint) =>
+      Buffer.from(value.toString(16).padStart(16, '0'), 'hex');
+    const max = BigInt('18446744073709551615');
+
+    it('round-trip as bigints', () => {
+      for (const value of [
+        BigInt(0),
+        BigInt(Number.MAX_SAFE_INTEGER),
+        BigInt(Number.MAX_SAFE_INTEGER) + BigInt(1),
+        max,
+      ]) {
+        assert.deepEqual(SC.TESTING_U64ToBytes(value), bytesOf(value));
+        assert.strictEqual(SC.TESTING_U64FromBytes(bytesOf(value)), value);
+      }
+    });
+    it('reject numbers', () => {
+      for (const value of [0, 1, Number.MAX_SAFE_INTEGER]) {
+        assert.throws(
+          // eslint-disable-next-line @typescript-eslint/no-explicit-any
+          () => SC.TESTING_U64ToBytes(value as any),
+          TypeError
+        );
+      }
+    });
+    it('reject out-of-range bigints', () => {
+      assert.throws(() => SC.TESTING_U64ToBytes(max + BigInt(1)), RangeError);
+      assert.throws(() => SC.TESTING_U64ToBytes(BigInt(-1)), RangeError);
+    });
+  });
+
+  describe('UUID arguments', () => {
+    it('are 16-byte buffers', () => {
+      const uuid = Buffer.from('000102030405060708090a0b0c0d0e0f', 'hex');
+      assert.equal(
+        SC.TESTING_UuidToString(uuid),
+        '00010203-0405-0607-0809-0a0b0c0d0e0f'
+      );
+    });
+    it('reject any other length', () => {
+      for (const length of [0, 15, 17]) {
+        assert.throws(
+          () => SC.TESTING_UuidToString(Buffer.alloc(length)),
+          TypeError,
+          `expected a 16-byte UUID, got ${length} bytes`
+        );
+      }
+    });
+  });
+
+  describe('async functions', () => {
+    // A store without any sessions, which the panicking function loads from before it panics.
+    function makeStore(): Native.SessionStore {
+      return {
+        _getSession: () => Promise.resolve(null),
+        _saveSession: () => Promise.reject(new Error('not used by these tests')),
+      };
+    }
+    function makeAddress(): { _nativeHandle: Native.ProtocolAddress } {
+      return { _nativeHandle: SC.ProtocolAddress_New('+14155550100', 1) };
+    }
+
+    // Calls `use` with a new object, and then checks that the object can be garbage-collected,
+    // meaning the bridge didn't hold onto it.
+    //
+    // Requires running with --expose-gc (as `yarn test` does) and FinalizationRegistry
+    // (Node 14.6+); skips the test otherwise.
+    async function checkReleased(
+      test: Mocha.Context,
+      makeObject: () => object,
+      use: (object: object) => Promise<void>
+    ) {
+      const { gc, FinalizationRegistry } = global as {
+        gc?: () => void;
+        FinalizationRegistry?: new (cleanup: () => void) => {
+          register(target: object, heldValue: undefined): void;
+        };
+      };
+      if (gc === undefined || FinalizationRegistry === undefined) {
+        test.skip();
+      }
+
+      let released = false;
+      const registry = new FinalizationRegistry(() => {
+        released = true;
+      });
+      await (async () => {
+        const object = makeObject();
+        registry.register(object, undefined);
+        await use(object);
+      })();
+
+      for (let i = 0; i < 10 && !released; i++) {
+        gc();
+        await new Promise(resolve => setImmediate(resolve));
+      }
+      assert.isTrue(released, 'the argument should have been released');
+    }
+
+    it('reject the promise when they panic', async () => {
+      try {
+        await SC.TESTING_PanicInFuture(makeAddress(), makeStore(), 'oh no');
+        assert.fail('should have rejected');
+      } catch (e) {
+        assert.instanceOf(e, Error);
+        assert.equal(e.name, 'LibSignalInternalError');
+        assert.include(e.message, 'oh no');
+      }
+    });
+
+    it('release their arguments when they panic', async function () {
+      await checkReleased(this, makeStore, async store => {
+        await SC.TESTING_PanicInFuture(
+          makeAddress(),
+          store as Native.SessionStore,
+          'oh no'
+        ).then(
+          () => assert.fail('should have rejected'),
+          () => undefined
+        );
+      });
+    });
+
+    it('copy byte arguments that could be detached mid-call', async () => {
+      for (const makeData of [
+        // Buffer.from may allocate from a shared pool, which can't be transferred.
+        () => Buffer.alloc(3, Buffer.of(1, 2, 3)),
+        () => new Uint8Array([1, 2, 3]),
+        () => new Uint8Array([1, 2, 3]).buffer,
+      ]) {
+        const data = makeData();
+        const buffer =
+          data instanceof ArrayBuffer ? data : (data.buffer as ArrayBuffer);
+        const store: Native.SessionStore = {
+          _getSession: async () => {
+            // Transferring the ArrayBuffer detaches it, freeing its contents here.
+            const { port1 } = new MessageChannel();
+            port1.postMessage(null, [buffer]);
+            port1.close();
+            assert.equal(buffer.byteLength, 0);
+            return null;
+          },
+          _saveSession: () => Promise.reject(new Error('not used')),
+        };
+        assert.equal(
+          await SC.TESTING_SumBytesAfterLoadingSession(
+            makeAddress(),
+            store,
+            data
+          ),
+          6
+        );
+      }
+    });
+
+    it('release saved arguments when a later argument is invalid', async function () {
+      await checkReleased(this, makeAddress, async address => {
+        assert.throws(() =>
+          SC.TESTING_SessionStoreHasSession(
+            address as { _nativeHandle: Native.ProtocolAddress },
+            (42 as unknown) as Native.SessionStore
+          )
+        );
+      });
+    });
+
+    it('release their arguments if the event loop exits mid-call', async () => {
+      // Let any finalization scheduled by earlier tests run first.
+      await new Promise(resolve => setImmediate(resolve));
+      const liveBefore = SC.TESTING_LiveSavedArgumentCount();
+
+      // Start a call whose store never answers, then shut the worker down under it. If the saved
+      // arguments were touched on the wrong thread, Neon would abort the whole process here.
+      const worker = new Worker(
+        `
+        const { parentPort, workerData } = require('worker_threads');
+        const SC = require(workerData.bindingsPath)({
+          bindings: workerData.moduleName,
+          module_root: workerData.moduleRoot,
+        });
+        const store = {
+          _getSession: () => new Promise(() => {}),
+          _saveSession: () => Promise.resolve(),
+        };
+        const address = { _nativeHandle: SC.ProtocolAddress_New('+14155550100', 1) };
+        SC.TESTING_SessionStoreHasSession(address, store);
+        parentPort.postMessage('pending');
+        `,
+        {
+          eval: true,
+          workerData: {
+            bindingsPath: require.resolve('bindings'),
+            moduleName: NATIVE_MODULE_NAME,
+            moduleRoot: path.join(__dirname, '../..'),
+          },
+        }
+      );
+      await new Promise((resolve, reject) => {
+        worker.once('message', resolve);
+        worker.once('error', reject);
+      });
+      // The native module is shared with the worker, so this includes the worker's address and
+      // store.
+      assert.equal(SC.TESTING_LiveSavedArgumentCount(), liveBefore + 2);
+      assert.equal(await worker.terminate(), 1);
+
+      // Tearing down the worker released both of them.
+      for (
+        let i = 0;
+        i < 10 && SC.TESTING_LiveSavedArgumentCount() !== liveBefore;
+        i++
+      ) {
+        await new Promise(resolve => setImmediate(resolve));
+      }
+      assert.equal(SC.TESTING_LiveSavedArgumentCount(), liveBefore);
+
+      // The bridge still works on this thread.
+      assert.isFalse(
+        await SC.TESTING_SessionStoreHasSession(makeAddress(), makeStore())
+      );
+    });
+  });
+
+  describe('declarations', () => {
+    // Entry points written by hand in rust/bridge/node instead of being generated by the bridge
+    // macros, and so missing from the description.
+    const handWritten = new Set(['initLogger']);
+
+    // Counts the parameters in a declaration's parameter list, ignoring commas nested in types.
+    function countParams(params: string): number {
+      let count = 0;
+      let depth = 0;
+      let inParam = false;
+      for (let i = 0; i < params.length; i++) {
+        const c = params[i];
+        if (c === ',' && depth === 0) {
+          count += 1;
+          inParam = false;
+          continue;
+        }
+        if ('(<{['.includes(c)) {
+          depth += 1;
+        } else if (')}]'.includes(c) || (c === '>' && params[i - 1] !== '=')) {
+          depth -= 1;
+        }
+        if (c.trim() !== '') {
+          inParam = true;
+        }
+      }
+      return inParam ? count + 1 : count;
+    }
+
+    it('match the bridged functions', function () {
+      const description = SC.TESTING_DescribeBridgedFunctions();
+      if (description === '') {
+        // The library was built without the "describe" feature.
+        this.skip();
+      }
+      const bridged = new Map<string, number>();
+      for (const line of description.split('\n')) {
+        const [platform, name, arity] = line.split(' ');
+        if (platform === 'node') {
+          bridged.set(name, parseInt(arity, 10));
+        }
+      }
+
+      const declarationFiles = [
+        '../../libsignal_client.d.ts',
+        '../../test/NativeTesting.d.ts',
+      ];
+      const declarations = declarationFiles
+        .map(file => fs.readFileSync(path.join(__dirname, file), 'utf8'))
+        .join('\n');
+      const declaration = /^export function (\w+)\((.*)\): .*;$/gm;
+      const problems: string[] = [];
+      let match;
+      while ((match = declaration.exec(declarations)) !== null) {
+        const [, name, params] = match;
+        if (handWritten.has(name)) {
+          continue;
+        }
+        const arity = bridged.get(name);
+        bridged.delete(name);
+        if (arity === undefined) {
+          problems.push(`${name} is declared but not bridged`);
+        } else if (arity !== countParams(params)) {
+          problems.push(
+            `${name} takes ${countParams(params)} parameters but is bridged with ${arity}`
+          );
+        }
+      }
+      for (const name of bridged.keys()) {
+        problems.push(`${name} is bridged but not declared`);
+      }
+      assert.deepEqual(problems, []);
+    });
+  });
+});
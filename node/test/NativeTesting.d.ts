@@ -0,0 +1,34 @@
+//
+// Copyright 2021 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+// WARNING: this file was automatically generated
+
+// The TESTING_ entry points, which the native module only provides when libsignal-node is built
+// with its "testing" feature.
+
+import { ProtocolAddress, SessionStore, Uint8Like, Wrapper } from '../libsignal_client';
+
+export function TESTING_BoolFromInt(value: number): boolean;
+export function TESTING_CountUtf8Bytes(text: string): number;
+/**
+ * @deprecated only for testing
+ */
+export function TESTING_DeprecatedEcho(value: number): number;
+export function TESTING_DescribeBridgedFunctions(): string;
+export function TESTING_FirstByte(data: Uint8Like): number | null;
+export function TESTING_IsEven(value: number): boolean;
+export function TESTING_LiveSavedArgumentCount(): number;
+export function TESTING_PanicInFuture(address: Wrapper<ProtocolAddress>, store: SessionStore, message: string): Promise<void>;
+export function TESTING_ReturnBufferList(count: number): Buffer[];
+export function TESTING_ReturnOptionalBuffer(len: number | null): Buffer | null;
+export function TESTING_ReturnOptionalString(text: string | null): string | null;
+export function TESTING_ReturnTuple(value: number): { buffer: Buffer, value: number, isEven: boolean };
+export function TESTING_SessionStoreHasSession(address: Wrapper<ProtocolAddress>, store: SessionStore): Promise<boolean>;
+export function TESTING_StringFromUtf8(bytes: Uint8Like): string;
+export function TESTING_SumBytes(data: Uint8Like, more: Uint8Like | null): number;
+export function TESTING_SumBytesAfterLoadingSession(address: Wrapper<ProtocolAddress>, store: SessionStore, data: Uint8Like): Promise<number>;
+export function TESTING_U64FromBytes(bytes: Uint8Like): bigint;
+export function TESTING_U64ToBytes(value: bigint): Buffer;
+export function TESTING_UuidToString(uuid: Buffer): string;
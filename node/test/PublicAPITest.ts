@@ -488,6 +488,15 @@ describe('SignalClient', () => {
     assert.deepEqual(pkr2.publicKey(), pubKey);
     assert.deepEqual(pkr2.privateKey(), privKey);
   });
+  it('PreKeyRecord.generateBatch', () => {
+    const records = SignalClient.PreKeyRecord.generateBatch(0xfffffffe, 4);
+    assert.deepEqual(
+      records.map(record => record.id()),
+      [0xfffffffe, 0xffffffff, 0, 1]
+    );
+    assert.isEmpty(SignalClient.PreKeyRecord.generateBatch(5, 0));
+    assert.throws(() => SignalClient.PreKeyRecord.generateBatch(5, 65536));
+  });
   it('SignedPreKeyRecord', () => {
     const privKey = SignalClient.PrivateKey.generate();
     const pubKey = privKey.getPublicKey();
@@ -503,7 +512,7 @@ describe('SignalClient', () => {
     );
 
     assert.deepEqual(spkr.id(), keyId);
-    assert.deepEqual(spkr.timestamp(), timestamp);
+    assert.deepEqual(spkr.timestamp(), BigInt(timestamp));
     assert.deepEqual(spkr.publicKey(), pubKey);
     assert.deepEqual(spkr.privateKey(), privKey);
     assert.deepEqual(spkr.signature(), signature);
@@ -635,6 +644,20 @@ describe('SignalClient', () => {
       aSess,
       aKeys
     );
+    const aSession = await aSess.getSession(bAddress);
+    if (!aSession) {
+      throw new Error('session should have been saved to the store');
+    }
+    assert.deepEqual(aSession.remoteRegistrationId(), bRegistrationId);
+    assert(aSession.hasCurrentState());
+
+    const archivedSession = SignalClient.SessionRecord.deserialize(
+      aSession.serialize()
+    );
+    archivedSession.archiveCurrentState();
+    assert(!archivedSession.hasCurrentState());
+    assert(aSession.hasCurrentState());
+
     const aMessage = Buffer.from('Greetings hoo-man', 'utf8');
 
     const aCiphertext = await SignalClient.signalEncrypt(
@@ -663,6 +686,12 @@ describe('SignalClient', () => {
     );
     assert.deepEqual(bDPlaintext, aMessage);
 
+    const bSession = await bSess.getSession(aAddress);
+    assert.deepEqual(
+      bSession?.remoteIdentityKeyBytes(),
+      (await aKeys.getIdentityKey()).getPublicKey().serialize()
+    );
+
     const bMessage = Buffer.from(
       'Sometimes the only thing more dangerous than a question is an answer.',
       'utf8'
@@ -693,6 +722,15 @@ describe('SignalClient', () => {
 
     assert.deepEqual(aDPlaintext, bMessage);
   });
+  it('rejects session records from newer versions', () => {
+    // A version byte one past the current version, followed by an empty record.
+    try {
+      SignalClient.SessionRecord.deserialize(Buffer.from([2]));
+      assert.fail('deserialization should have failed');
+    } catch (e) {
+      assert.equal(e.name, 'UnknownSessionVersion');
+    }
+  });
   it('SealedSender', async () => {
     const aKeys = new InMemoryIdentityKeyStore();
     const bKeys = new InMemoryIdentityKeyStore();
@@ -808,6 +846,22 @@ describe('SignalClient', () => {
     assert.deepEqual(bPlaintext.senderE164(), aE164);
     assert.deepEqual(bPlaintext.senderUuid(), aUuid);
     assert.deepEqual(bPlaintext.deviceId(), aDeviceId);
+
+    const usmc = await SignalClient.sealedSenderDecryptToUsmc(
+      aCiphertext,
+      bKeys
+    );
+    const aSession = await aSess.getSession(bAddress);
+    if (!aSession) {
+      throw new Error('session should have been saved to the store');
+    }
+    const aMultiCiphertext = await SignalClient.sealedSenderMultiRecipientEncrypt(
+      usmc,
+      [bAddress],
+      [aSession],
+      aKeys
+    );
+    assert.equal(aMultiCiphertext[0], 0x22);
   });
   it('AES-GCM-SIV test vector', () => {
     // RFC 8452, appendix C.2
@@ -832,6 +886,16 @@ describe('SignalClient', () => {
     const decrypted = aes_gcm_siv.decrypt(ctext, nonce, aad);
 
     assert.deepEqual(decrypted.toString('hex'), '02000000');
+
+    const corruptedCtext = Buffer.from(ctext);
+    corruptedCtext[0] ^= 1;
+    try {
+      aes_gcm_siv.decrypt(corruptedCtext, nonce, aad);
+      assert.fail('decryption should have failed');
+    } catch (e) {
+      assert.equal(e.name, 'InvalidTag');
+      assert.equal(e.message, 'invalid AES-GCM-SIV tag');
+    }
   });
   it('ECC signatures work', () => {
     const priv_a = SignalClient.PrivateKey.generate();
@@ -0,0 +1,73 @@
+#!/usr/bin/env python3
+
+#
+# Copyright 2021 Signal Messenger, LLC.
+# SPDX-License-Identifier: AGPL-3.0-only
+#
+
+# Verify that every signal_* function exported by libsignal-ffi is declared in the C headers,
+# and that every function declared in the headers is actually exported.
+#
+# A library built with the "testing" feature also exports the functions declared in
+# rust/bridge/ffi/tests/signal_ffi_testing.h, which should then be passed as well.
+#
+# The header itself is generated by cbindgen (see swift/build_ffi.sh --generate-ffi); this catches
+# the cases where it's been edited by hand or cbindgen silently skipped a function.
+
+import re
+import subprocess
+import sys
+
+
+def print_usage_and_exit():
+    print('usage: %s path/to/libsignal_ffi.a path/to/signal_ffi.h [more headers...]' % sys.argv[0],
+          file=sys.stderr)
+    sys.exit(2)
+
+
+def exported_symbols(library_path):
+    nm = subprocess.run(['nm', '-g', '--defined-only', library_path],
+                        stdout=subprocess.PIPE, stderr=subprocess.PIPE, check=True)
+
+    # "0000000000001234 T signal_foo" (or "_signal_foo" on Darwin)
+    symbol_line = re.compile(r'^[0-9a-fA-F]*\s+T\s+_?(signal_\w+)$')
+    for line in nm.stdout.decode('utf8').split('\n'):
+        match = symbol_line.match(line.strip())
+        if match:
+            yield match.group(1)
+
+
+def declared_functions(header_path):
+    function_decl = re.compile(r'\b(signal_\w+)\(')
+    with open(header_path) as fh:
+        for line in fh:
+            for match in function_decl.finditer(line):
+                yield match.group(1)
+
+
+if len(sys.argv) < 3:
+    print_usage_and_exit()
+
+library_path = sys.argv[1]
+header_paths = sys.argv[2:]
+
+exported = set(exported_symbols(library_path))
+declared = set()
+for header_path in header_paths:
+    declared.update(declared_functions(header_path))
+
+if not exported:
+    sys.exit("error: no signal_* symbols found in %s" % library_path)
+
+had_error = False
+for symbol in sorted(exported - declared):
+    print("error: %s is exported but not declared in %s" % (symbol, ", ".join(header_paths)),
+          file=sys.stderr)
+    had_error = True
+for symbol in sorted(declared - exported):
+    print("error: %s is declared but not exported from %s" % (symbol, library_path),
+          file=sys.stderr)
+    had_error = True
+
+if had_error:
+    sys.exit(1)
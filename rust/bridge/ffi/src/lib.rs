@@ -37,6 +37,17 @@ pub unsafe extern "C" fn signal_free_buffer(buf: *const c_uchar, buf_len: size_t
     Box::from_raw(std::slice::from_raw_parts_mut(buf as *mut c_uchar, buf_len));
 }
 
+/// Frees an array of handles returned by a function like `signal_pre_key_record_generate_batch`.
+///
+/// The handles themselves are owned by the caller and must be destroyed separately.
+#[no_mangle]
+pub unsafe extern "C" fn signal_free_handle_list(list: *mut *mut c_void, count: size_t) {
+    if list.is_null() {
+        return;
+    }
+    Box::from_raw(std::slice::from_raw_parts_mut(list, count));
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn signal_error_get_message(
     err: *const SignalFfiError,
@@ -67,6 +78,15 @@ pub unsafe extern "C" fn signal_error_get_type(err: *const SignalFfiError) -> u3
     }
 }
 
+/// Returns the kind of `err`, or `SignalErrorCode_UnknownError` if it is null.
+#[no_mangle]
+pub unsafe extern "C" fn signal_error_get_code(err: *const SignalFfiError) -> SignalErrorCode {
+    match err.as_ref() {
+        Some(err) => err.into(),
+        None => SignalErrorCode::UnknownError,
+    }
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn signal_error_free(err: *mut SignalFfiError) {
     if !err.is_null() {
@@ -85,7 +105,7 @@ pub unsafe extern "C" fn signal_identitykeypair_deserialize(
         let input = as_slice(input, input_len)?;
         let identity_key_pair = IdentityKeyPair::try_from(input)?;
         box_object::<PublicKey>(public_key, Ok(*identity_key_pair.public_key()))?;
-        box_object::<PrivateKey>(private_key, Ok(*identity_key_pair.private_key()))
+        box_object::<PrivateKey>(private_key, Ok(identity_key_pair.private_key().clone()))
     })
 }
 
@@ -119,8 +139,9 @@ pub unsafe extern "C" fn signal_sealed_session_cipher_decrypt(
             .as_ref()
             .ok_or(SignalFfiError::NullPointer)?;
 
-        let local_e164 = Option::convert_from(local_e164)?;
-        let local_uuid = Option::convert_from(local_uuid)?.ok_or(SignalFfiError::NullPointer)?;
+        let local_e164 = Option::<String>::convert_from(local_e164)?;
+        let local_uuid =
+            Option::<String>::convert_from(local_uuid)?.ok_or(SignalFfiError::NullPointer)?;
 
         let decrypted = expect_ready(sealed_sender_decrypt(
             &ctext,
@@ -7,12 +7,16 @@ use aes_gcm_siv::Error as AesGcmSivError;
 use libc::{c_char, c_uchar, size_t};
 use libsignal_bridge::ffi::*;
 use libsignal_protocol::*;
+use static_assertions::const_assert;
 use std::ffi::CString;
 
-#[derive(Debug)]
+/// The kind of a [`SignalFfiError`], as reported by `signal_error_get_code`.
+///
+/// C clients compile these values in, so a code must never be renumbered or removed once released;
+/// see [`RELEASED_ERROR_CODES`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(C)]
 pub enum SignalErrorCode {
-    #[allow(dead_code)]
     UnknownError = 1,
     InvalidState = 2,
     InternalError = 3,
@@ -30,6 +34,7 @@ pub enum SignalErrorCode {
     UnrecognizedMessageVersion = 23,
     InvalidMessage = 30,
     SealedSenderSelfSend = 31,
+    CertificateExpired = 32,
 
     InvalidKey = 40,
     InvalidSignature = 41,
@@ -39,16 +44,142 @@ pub enum SignalErrorCode {
     FingerprintParsingError = 52,
 
     UntrustedIdentity = 60,
+    IdentityKeyChanged = 61,
 
     InvalidKeyIdentifier = 70,
 
     SessionNotFound = 80,
+    UnknownSessionVersion = 81,
 
     DuplicatedMessage = 90,
 
     CallbackError = 100,
 }
 
+/// Every error code, with the name it has in C.
+///
+/// New codes must be added here and to [`released_value`], which together are checked at compile
+/// time against the values in [`SignalErrorCode`]. The names and values are checked against
+/// signal_ffi.h by a unit test.
+const RELEASED_ERROR_CODES: [(SignalErrorCode, &str); 28] = [
+    (SignalErrorCode::UnknownError, "UnknownError"),
+    (SignalErrorCode::InvalidState, "InvalidState"),
+    (SignalErrorCode::InternalError, "InternalError"),
+    (SignalErrorCode::NullParameter, "NullParameter"),
+    (SignalErrorCode::InvalidArgument, "InvalidArgument"),
+    (SignalErrorCode::InvalidType, "InvalidType"),
+    (SignalErrorCode::InvalidUtf8String, "InvalidUtf8String"),
+    (
+        SignalErrorCode::InsufficientOutputSize,
+        "InsufficientOutputSize",
+    ),
+    (SignalErrorCode::ProtobufError, "ProtobufError"),
+    (SignalErrorCode::InvalidCiphertext, "InvalidCiphertext"),
+    (
+        SignalErrorCode::LegacyCiphertextVersion,
+        "LegacyCiphertextVersion",
+    ),
+    (
+        SignalErrorCode::UnknownCiphertextVersion,
+        "UnknownCiphertextVersion",
+    ),
+    (
+        SignalErrorCode::UnrecognizedMessageVersion,
+        "UnrecognizedMessageVersion",
+    ),
+    (SignalErrorCode::InvalidMessage, "InvalidMessage"),
+    (
+        SignalErrorCode::SealedSenderSelfSend,
+        "SealedSenderSelfSend",
+    ),
+    (SignalErrorCode::CertificateExpired, "CertificateExpired"),
+    (SignalErrorCode::InvalidKey, "InvalidKey"),
+    (SignalErrorCode::InvalidSignature, "InvalidSignature"),
+    (
+        SignalErrorCode::FingerprintIdentifierMismatch,
+        "FingerprintIdentifierMismatch",
+    ),
+    (
+        SignalErrorCode::FingerprintVersionMismatch,
+        "FingerprintVersionMismatch",
+    ),
+    (
+        SignalErrorCode::FingerprintParsingError,
+        "FingerprintParsingError",
+    ),
+    (SignalErrorCode::UntrustedIdentity, "UntrustedIdentity"),
+    (SignalErrorCode::IdentityKeyChanged, "IdentityKeyChanged"),
+    (
+        SignalErrorCode::InvalidKeyIdentifier,
+        "InvalidKeyIdentifier",
+    ),
+    (SignalErrorCode::SessionNotFound, "SessionNotFound"),
+    (
+        SignalErrorCode::UnknownSessionVersion,
+        "UnknownSessionVersion",
+    ),
+    (SignalErrorCode::DuplicatedMessage, "DuplicatedMessage"),
+    (SignalErrorCode::CallbackError, "CallbackError"),
+];
+
+/// The value each error code was released with.
+///
+/// This `match` must cover every code, so a new code can't be added without giving it a value here.
+const fn released_value(code: SignalErrorCode) -> u32 {
+    match code {
+        SignalErrorCode::UnknownError => 1,
+        SignalErrorCode::InvalidState => 2,
+        SignalErrorCode::InternalError => 3,
+        SignalErrorCode::NullParameter => 4,
+        SignalErrorCode::InvalidArgument => 5,
+        SignalErrorCode::InvalidType => 6,
+        SignalErrorCode::InvalidUtf8String => 7,
+        SignalErrorCode::InsufficientOutputSize => 8,
+        SignalErrorCode::ProtobufError => 10,
+        SignalErrorCode::InvalidCiphertext => 20,
+        SignalErrorCode::LegacyCiphertextVersion => 21,
+        SignalErrorCode::UnknownCiphertextVersion => 22,
+        SignalErrorCode::UnrecognizedMessageVersion => 23,
+        SignalErrorCode::InvalidMessage => 30,
+        SignalErrorCode::SealedSenderSelfSend => 31,
+        SignalErrorCode::CertificateExpired => 32,
+        SignalErrorCode::InvalidKey => 40,
+        SignalErrorCode::InvalidSignature => 41,
+        SignalErrorCode::FingerprintIdentifierMismatch => 50,
+        SignalErrorCode::FingerprintVersionMismatch => 51,
+        SignalErrorCode::FingerprintParsingError => 52,
+        SignalErrorCode::UntrustedIdentity => 60,
+        SignalErrorCode::IdentityKeyChanged => 61,
+        SignalErrorCode::InvalidKeyIdentifier => 70,
+        SignalErrorCode::SessionNotFound => 80,
+        SignalErrorCode::UnknownSessionVersion => 81,
+        SignalErrorCode::DuplicatedMessage => 90,
+        SignalErrorCode::CallbackError => 100,
+    }
+}
+
+/// Checks that every listed code still has its released value, and is listed only once.
+const fn error_codes_are_stable() -> bool {
+    let mut i = 0;
+    while i < RELEASED_ERROR_CODES.len() {
+        let code = RELEASED_ERROR_CODES[i].0;
+        if code as u32 != released_value(code) {
+            return false;
+        }
+        let mut j = 0;
+        while j < i {
+            if RELEASED_ERROR_CODES[j].0 as u32 == code as u32 {
+                return false;
+            }
+            j += 1;
+        }
+        i += 1;
+    }
+    true
+}
+
+const_assert!(error_codes_are_stable());
+
 impl From<&SignalFfiError> for SignalErrorCode {
     fn from(err: &SignalFfiError) -> Self {
         match err {
@@ -72,7 +203,8 @@ impl From<&SignalFfiError> for SignalErrorCode {
             SignalFfiError::InsufficientOutputSize(_, _) => SignalErrorCode::InsufficientOutputSize,
 
             SignalFfiError::Signal(SignalProtocolError::ProtobufEncodingError(_))
-            | SignalFfiError::Signal(SignalProtocolError::ProtobufDecodingError(_)) => {
+            | SignalFfiError::Signal(SignalProtocolError::ProtobufDecodingError(_))
+            | SignalFfiError::Signal(SignalProtocolError::ProtobufTooLarge(_, _)) => {
                 SignalErrorCode::ProtobufError
             }
 
@@ -90,7 +222,12 @@ impl From<&SignalFfiError> for SignalErrorCode {
                 SignalErrorCode::SealedSenderSelfSend
             }
 
-            SignalFfiError::Signal(SignalProtocolError::SignatureValidationFailed) => {
+            SignalFfiError::Signal(SignalProtocolError::CertificateExpired { .. }) => {
+                SignalErrorCode::CertificateExpired
+            }
+
+            SignalFfiError::Signal(SignalProtocolError::SignatureValidationFailed)
+            | SignalFfiError::Signal(SignalProtocolError::InvalidSignedPreKeySignature) => {
                 SignalErrorCode::InvalidSignature
             }
 
@@ -105,6 +242,10 @@ impl From<&SignalFfiError> for SignalErrorCode {
                 SignalErrorCode::SessionNotFound
             }
 
+            SignalFfiError::Signal(SignalProtocolError::UnknownSessionVersion(_)) => {
+                SignalErrorCode::UnknownSessionVersion
+            }
+
             SignalFfiError::Signal(SignalProtocolError::FingerprintIdentifierMismatch) => {
                 SignalErrorCode::FingerprintIdentifierMismatch
             }
@@ -133,6 +274,7 @@ impl From<&SignalFfiError> for SignalErrorCode {
             }
 
             SignalFfiError::Signal(SignalProtocolError::InvalidMessage(_))
+            | SignalFfiError::Signal(SignalProtocolError::TooManySkippedKeys(_, _))
             | SignalFfiError::Signal(SignalProtocolError::InvalidProtobufEncoding)
             | SignalFfiError::Signal(SignalProtocolError::InvalidSealedSenderMessage(_)) => {
                 SignalErrorCode::InvalidMessage
@@ -146,6 +288,10 @@ impl From<&SignalFfiError> for SignalErrorCode {
                 SignalErrorCode::UntrustedIdentity
             }
 
+            SignalFfiError::Signal(SignalProtocolError::IdentityKeyChanged(..)) => {
+                SignalErrorCode::IdentityKeyChanged
+            }
+
             SignalFfiError::Signal(SignalProtocolError::InvalidState(_, _))
             | SignalFfiError::Signal(SignalProtocolError::NoSenderKeyState)
             | SignalFfiError::Signal(SignalProtocolError::InvalidSessionStructure) => {
@@ -153,6 +299,9 @@ impl From<&SignalFfiError> for SignalErrorCode {
             }
 
             SignalFfiError::Signal(SignalProtocolError::InvalidArgument(_))
+            | SignalFfiError::Signal(SignalProtocolError::InvalidRegistrationId(_))
+            | SignalFfiError::Signal(SignalProtocolError::InvalidDeviceId(_))
+            | SignalFfiError::Signal(SignalProtocolError::InconsistentOneTimePreKey)
             | SignalFfiError::AesGcmSiv(_) => SignalErrorCode::InvalidArgument,
 
             SignalFfiError::Signal(SignalProtocolError::ApplicationCallbackError(_, _)) => {
@@ -186,8 +335,9 @@ pub(crate) unsafe fn write_optional_cstr_to(
 
     match value {
         Ok(Some(value)) => {
-            let cstr =
-                CString::new(value).expect("No NULL characters in string being returned to C");
+            let cstr = CString::new(value).map_err(|_| {
+                SignalProtocolError::InternalError("returned string contains a NUL character")
+            })?;
             *out = cstr.into_raw();
             Ok(())
         }
@@ -198,3 +348,27 @@ pub(crate) unsafe fn write_optional_cstr_to(
         Err(e) => Err(SignalFfiError::Signal(e)),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: &str = include_str!("../../../../swift/Sources/SignalFfi/signal_ffi.h");
+
+    #[test]
+    fn test_released_error_codes_declared_in_header() {
+        for &(code, name) in RELEASED_ERROR_CODES.iter() {
+            assert_eq!(format!("{:?}", code), name);
+            let declaration = format!("  SignalErrorCode_{} = {},\n", name, released_value(code));
+            assert!(
+                HEADER.contains(&declaration),
+                "missing from signal_ffi.h (regenerate it with swift/build_ffi.sh --generate-ffi): {}",
+                declaration.trim()
+            );
+        }
+        assert_eq!(
+            HEADER.matches("  SignalErrorCode_").count(),
+            RELEASED_ERROR_CODES.len()
+        );
+    }
+}
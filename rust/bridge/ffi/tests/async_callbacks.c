@@ -0,0 +1,158 @@
+/*
+Copyright 2021 Signal Messenger, LLC.
+SPDX-License-Identifier: AGPL-3.0-only
+*/
+
+/* Checks that ffi_async entry points call their completion callbacks exactly once, both for
+ * futures that are ready immediately and for futures that finish later on another thread, and that
+ * pending futures share a pool of threads rather than getting one each.
+ *
+ * Build and run with rust/bridge/ffi/tests/run_c_tests.sh. */
+
+#include <pthread.h>
+#include <stdio.h>
+#include <string.h>
+#include <unistd.h>
+
+#include "signal_ffi_testing.h"
+
+#define CHECK(condition)                                                                  \
+  do {                                                                                    \
+    if (!(condition)) {                                                                   \
+      fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__, __LINE__, #condition);      \
+      exit(1);                                                                            \
+    }                                                                                     \
+  } while (0)
+
+typedef struct {
+  pthread_mutex_t mutex;
+  pthread_cond_t cond;
+  int call_count;
+  pthread_t calling_thread;
+  SignalFfiError *error;
+  uint32_t result;
+} Completion;
+
+static void completion_init(Completion *completion) {
+  memset(completion, 0, sizeof(*completion));
+  pthread_mutex_init(&completion->mutex, NULL);
+  pthread_cond_init(&completion->cond, NULL);
+}
+
+static void completion_destroy(Completion *completion) {
+  signal_error_free(completion->error);
+  pthread_cond_destroy(&completion->cond);
+  pthread_mutex_destroy(&completion->mutex);
+}
+
+static void on_done(SignalFfiError *error, uint32_t result, void *ctx) {
+  Completion *completion = ctx;
+  pthread_mutex_lock(&completion->mutex);
+  completion->call_count += 1;
+  completion->calling_thread = pthread_self();
+  completion->error = error;
+  completion->result = result;
+  pthread_cond_broadcast(&completion->cond);
+  pthread_mutex_unlock(&completion->mutex);
+}
+
+static void wait_for_completion(Completion *completion) {
+  pthread_mutex_lock(&completion->mutex);
+  while (completion->call_count == 0) {
+    pthread_cond_wait(&completion->cond, &completion->mutex);
+  }
+  pthread_mutex_unlock(&completion->mutex);
+}
+
+static void test_ready_success(void) {
+  Completion completion;
+  completion_init(&completion);
+
+  signal_testing_future_success(on_done, &completion, 21);
+
+  /* No waiting: a ready future completes before the entry point returns. */
+  CHECK(completion.call_count == 1);
+  CHECK(pthread_equal(completion.calling_thread, pthread_self()));
+  CHECK(completion.error == NULL);
+  CHECK(completion.result == 42);
+
+  completion_destroy(&completion);
+}
+
+static void test_ready_failure(void) {
+  Completion completion;
+  completion_init(&completion);
+
+  signal_testing_future_failure(on_done, &completion, 21);
+
+  CHECK(completion.call_count == 1);
+  CHECK(completion.error != NULL);
+  CHECK(completion.result == 0);
+
+  const char *message = NULL;
+  CHECK(signal_error_get_message(completion.error, &message) == NULL);
+  CHECK(strstr(message, "failed with input 21") != NULL);
+  signal_free_string(message);
+
+  completion_destroy(&completion);
+}
+
+static void test_pending(void) {
+  Completion completion;
+  completion_init(&completion);
+
+  signal_testing_future_pending(on_done, &completion, 21, 100);
+  wait_for_completion(&completion);
+
+  CHECK(!pthread_equal(completion.calling_thread, pthread_self()));
+  CHECK(completion.error == NULL);
+  CHECK(completion.result == 42);
+
+  /* Give a stray second call a chance to show up. */
+  struct timespec delay = {0, 100 * 1000 * 1000};
+  nanosleep(&delay, NULL);
+  pthread_mutex_lock(&completion.mutex);
+  CHECK(completion.call_count == 1);
+  pthread_mutex_unlock(&completion.mutex);
+
+  completion_destroy(&completion);
+}
+
+static void test_many_pending(void) {
+  enum { COUNT = 64 };
+  static Completion completions[COUNT];
+  for (int i = 0; i < COUNT; ++i) {
+    completion_init(&completions[i]);
+    signal_testing_future_pending(on_done, &completions[i], i, 100);
+  }
+
+  pthread_t threads[COUNT];
+  int thread_count = 0;
+  for (int i = 0; i < COUNT; ++i) {
+    wait_for_completion(&completions[i]);
+    CHECK(completions[i].error == NULL);
+    CHECK(completions[i].result == 2 * (uint32_t)i);
+
+    int seen = 0;
+    for (int j = 0; j < thread_count; ++j) {
+      seen |= pthread_equal(threads[j], completions[i].calling_thread);
+    }
+    if (!seen) {
+      threads[thread_count++] = completions[i].calling_thread;
+    }
+    completion_destroy(&completions[i]);
+  }
+
+  /* The pool has one thread per CPU. */
+  long cpus = sysconf(_SC_NPROCESSORS_ONLN);
+  CHECK(cpus < 1 || thread_count <= cpus);
+}
+
+int main(void) {
+  test_ready_success();
+  test_ready_failure();
+  test_pending();
+  test_many_pending();
+  printf("async callback tests passed\n");
+  return 0;
+}
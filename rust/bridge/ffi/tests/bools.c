@@ -0,0 +1,51 @@
+/*
+Copyright 2021 Signal Messenger, LLC.
+SPDX-License-Identifier: AGPL-3.0-only
+*/
+
+/* Checks that functions returning bool and Result<bool> write to a bool out-parameter, and that
+ * the latter still reports errors.
+ *
+ * Build and run with rust/bridge/ffi/tests/run_c_tests.sh. */
+
+#include <stdbool.h>
+#include <stdio.h>
+#include <stdlib.h>
+
+#include "signal_ffi_testing.h"
+
+#define CHECK(condition)                                                                  \
+  do {                                                                                    \
+    if (!(condition)) {                                                                   \
+      fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__, __LINE__, #condition);      \
+      exit(1);                                                                            \
+    }                                                                                     \
+  } while (0)
+
+static void test_bool(void) {
+  bool out = false;
+  CHECK(signal_testing_is_even(&out, 2) == NULL);
+  CHECK(out);
+  CHECK(signal_testing_is_even(&out, 3) == NULL);
+  CHECK(!out);
+}
+
+static void test_bool_result(void) {
+  bool out = false;
+  CHECK(signal_testing_bool_from_int(&out, 1) == NULL);
+  CHECK(out);
+  CHECK(signal_testing_bool_from_int(&out, 0) == NULL);
+  CHECK(!out);
+
+  SignalFfiError *error = signal_testing_bool_from_int(&out, 2);
+  CHECK(error != NULL);
+  CHECK(signal_error_get_type(error) == SignalErrorCode_InvalidArgument);
+  signal_error_free(error);
+}
+
+int main(void) {
+  test_bool();
+  test_bool_result();
+  printf("bool tests passed\n");
+  return 0;
+}
@@ -0,0 +1,53 @@
+/*
+Copyright 2021 Signal Messenger, LLC.
+SPDX-License-Identifier: AGPL-3.0-only
+*/
+
+/* Checks that errors report their kind through signal_error_get_code, so that C callers don't have
+ * to parse the message.
+ *
+ * Build and run with rust/bridge/ffi/tests/run_c_tests.sh. */
+
+#include <stdio.h>
+#include <stdlib.h>
+
+#include "signal_ffi.h"
+
+#define CHECK(condition)                                                                  \
+  do {                                                                                    \
+    if (!(condition)) {                                                                   \
+      fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__, __LINE__, #condition);      \
+      exit(1);                                                                            \
+    }                                                                                     \
+  } while (0)
+
+static void test_invalid_key(void) {
+  /* A public key with an unknown type byte. */
+  unsigned char bytes[33] = {0x07};
+  SignalPublicKey *key = NULL;
+
+  SignalFfiError *error = signal_publickey_deserialize(&key, bytes, sizeof(bytes));
+  CHECK(error != NULL);
+  CHECK(key == NULL);
+  CHECK(signal_error_get_code(error) == SignalErrorCode_InvalidKey);
+  CHECK(signal_error_get_type(error) == (uint32_t)SignalErrorCode_InvalidKey);
+  signal_error_free(error);
+}
+
+static void test_released_values(void) {
+  /* Codes are compiled into clients, so they must keep their values. */
+  CHECK(SignalErrorCode_InvalidKey == 40);
+  CHECK(SignalErrorCode_CallbackError == 100);
+}
+
+static void test_null_error(void) {
+  CHECK(signal_error_get_code(NULL) == SignalErrorCode_UnknownError);
+}
+
+int main(void) {
+  test_invalid_key();
+  test_released_values();
+  test_null_error();
+  printf("error tests passed\n");
+  return 0;
+}
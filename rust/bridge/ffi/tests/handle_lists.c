@@ -0,0 +1,56 @@
+/*
+Copyright 2021 Signal Messenger, LLC.
+SPDX-License-Identifier: AGPL-3.0-only
+*/
+
+/* Checks that functions returning a list of objects hand back an array of handles that the caller
+ * owns, using signal_pre_key_record_generate_batch.
+ *
+ * Build and run with rust/bridge/ffi/tests/run_c_tests.sh. */
+
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+
+#include "signal_ffi_testing.h"
+
+#define CHECK(condition)                                                                  \
+  do {                                                                                    \
+    if (!(condition)) {                                                                   \
+      fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__, __LINE__, #condition);      \
+      exit(1);                                                                            \
+    }                                                                                     \
+  } while (0)
+
+static void test_generate_batch(void) {
+  SignalPreKeyRecord **records = NULL;
+  size_t count = 0;
+  CHECK(signal_pre_key_record_generate_batch(&records, &count, UINT32_MAX - 1, 4) == NULL);
+  CHECK(count == 4);
+
+  const uint32_t expected_ids[4] = {UINT32_MAX - 1, UINT32_MAX, 0, 1};
+  for (size_t i = 0; i < count; ++i) {
+    uint32_t id = 0;
+    CHECK(signal_pre_key_record_get_id(&id, records[i]) == NULL);
+    CHECK(id == expected_ids[i]);
+    CHECK(signal_pre_key_record_destroy(records[i]) == NULL);
+  }
+  signal_free_handle_list((void **)records, count);
+}
+
+static void test_batch_too_large(void) {
+  SignalPreKeyRecord **records = NULL;
+  size_t count = 0;
+  SignalFfiError *error = signal_pre_key_record_generate_batch(&records, &count, 1, UINT32_MAX);
+  CHECK(error != NULL);
+  CHECK(signal_error_get_type(error) == SignalErrorCode_InvalidArgument);
+  CHECK(records == NULL);
+  signal_error_free(error);
+}
+
+int main(void) {
+  test_generate_batch();
+  test_batch_too_large();
+  printf("handle list tests passed\n");
+  return 0;
+}
@@ -0,0 +1,70 @@
+/*
+Copyright 2021 Signal Messenger, LLC.
+SPDX-License-Identifier: AGPL-3.0-only
+*/
+
+/* Checks that 64-bit timestamps and other u64 values cross the C boundary as uint64_t without any
+ * loss of range, including values that can't be represented exactly in a JavaScript number.
+ *
+ * Build and run with rust/bridge/ffi/tests/run_c_tests.sh. */
+
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#include "signal_ffi_testing.h"
+
+#define CHECK(condition)                                                                  \
+  do {                                                                                    \
+    if (!(condition)) {                                                                   \
+      fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__, __LINE__, #condition);      \
+      exit(1);                                                                            \
+    }                                                                                     \
+  } while (0)
+
+static void to_big_endian(uint64_t value, unsigned char out[8]) {
+  for (int i = 7; i >= 0; --i) {
+    out[i] = (unsigned char)(value & 0xff);
+    value >>= 8;
+  }
+}
+
+static void check_round_trip(uint64_t value) {
+  unsigned char expected[8];
+  to_big_endian(value, expected);
+
+  const unsigned char *bytes = NULL;
+  size_t bytes_len = 0;
+  CHECK(signal_testing_u64_to_bytes(&bytes, &bytes_len, value) == NULL);
+  CHECK(bytes_len == 8);
+  CHECK(memcmp(bytes, expected, 8) == 0);
+  signal_free_buffer(bytes, bytes_len);
+
+  uint64_t out = 0;
+  CHECK(signal_testing_u64_from_bytes(&out, expected, sizeof(expected)) == NULL);
+  CHECK(out == value);
+}
+
+static void test_boundaries(void) {
+  check_round_trip(0);
+  check_round_trip((UINT64_C(1) << 53) - 1);
+  check_round_trip(UINT64_C(1) << 53);
+  check_round_trip(UINT64_MAX);
+}
+
+static void test_wrong_length(void) {
+  const unsigned char bytes[9] = {0};
+  uint64_t out = 0;
+  SignalFfiError *error = signal_testing_u64_from_bytes(&out, bytes, sizeof(bytes));
+  CHECK(error != NULL);
+  CHECK(signal_error_get_type(error) == SignalErrorCode_InvalidArgument);
+  signal_error_free(error);
+}
+
+int main(void) {
+  test_boundaries();
+  test_wrong_length();
+  printf("integer tests passed\n");
+  return 0;
+}
@@ -0,0 +1,43 @@
+#!/bin/bash
+
+#
+# Copyright 2021 Signal Messenger, LLC.
+# SPDX-License-Identifier: AGPL-3.0-only
+#
+
+# Compiles the C tests in this directory against a built libsignal_ffi.a and runs them.
+#
+# The library must be built with the "testing" feature (swift/build_ffi.sh --testing), which
+# provides the signal_testing_* functions declared in signal_ffi_testing.h.
+#
+# Usage: run_c_tests.sh path/to/libsignal_ffi.a
+
+set -euo pipefail
+
+if [ $# -ne 1 ]; then
+  echo "usage: $(basename "$0") path/to/libsignal_ffi.a" >&2
+  exit 2
+fi
+
+SCRIPT_DIR=$(dirname "$0")
+HEADER_DIR="${SCRIPT_DIR}"/../../../../swift/Sources/SignalFfi
+LIBRARY=$1
+
+case "$(uname)" in
+  Darwin)
+    SYSTEM_LIBS=(-framework Security -framework Foundation)
+    ;;
+  *)
+    SYSTEM_LIBS=(-lpthread -ldl -lm)
+    ;;
+esac
+
+BUILD_DIR=$(mktemp -d)
+trap 'rm -rf "${BUILD_DIR}"' EXIT
+
+for test_source in "${SCRIPT_DIR}"/*.c; do
+  test_binary="${BUILD_DIR}/$(basename "${test_source}" .c)"
+  cc -std=c11 -D_POSIX_C_SOURCE=200809L -Wall -Werror -I "${HEADER_DIR}" \
+    -o "${test_binary}" "${test_source}" "${LIBRARY}" -pthread "${SYSTEM_LIBS[@]}"
+  "${test_binary}"
+done
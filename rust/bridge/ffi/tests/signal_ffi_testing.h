@@ -0,0 +1,78 @@
+/*
+Copyright 2021 Signal Messenger, LLC.
+SPDX-License-Identifier: AGPL-3.0-only
+*/
+
+/* Declares the signal_testing_* entry points, which are only built into libsignal_ffi with its
+ * "testing" feature and so are left out of signal_ffi.h. They are written to match what cbindgen
+ * would generate; swift/build_ffi.sh --verify-ffi checks them against a testing build. */
+
+#ifndef SIGNAL_FFI_TESTING_H_
+#define SIGNAL_FFI_TESTING_H_
+
+#include "signal_ffi.h"
+
+void signal_testing_future_success(void (*done)(SignalFfiError*, uint32_t, void*),
+                                   void *ctx,
+                                   uint32_t input);
+
+void signal_testing_future_failure(void (*done)(SignalFfiError*, uint32_t, void*),
+                                   void *ctx,
+                                   uint32_t input);
+
+void signal_testing_future_pending(void (*done)(SignalFfiError*, uint32_t, void*),
+                                   void *ctx,
+                                   uint32_t input,
+                                   uint32_t delay_ms);
+
+SignalFfiError *signal_testing_bool_from_int(bool *out, uint32_t value);
+
+SignalFfiError *signal_testing_count_utf8_bytes(uint32_t *out, const char *text);
+
+SIGNAL_DEPRECATED("only for testing") SignalFfiError *signal_testing_deprecated_echo(uint32_t *out,
+                                                                                     uint32_t value);
+
+SignalFfiError *signal_testing_describe_bridged_functions(const char **out);
+
+SignalFfiError *signal_testing_invoke_callback(uint32_t *out,
+                                              uint32_t (*callback)(uint32_t),
+                                              uint32_t value);
+
+SignalFfiError *signal_testing_is_even(bool *out, uint32_t value);
+
+SignalFfiError *signal_testing_return_buffer_list(const unsigned char **out,
+                                                  size_t *out_len,
+                                                  size_t *out_count,
+                                                  uint32_t count);
+
+SignalFfiError *signal_testing_return_optional_buffer(const unsigned char **out,
+                                                      size_t *out_len,
+                                                      uint32_t len);
+
+SignalFfiError *signal_testing_return_optional_string(const char **out, const char *text);
+
+SignalFfiError *signal_testing_return_tuple(const unsigned char **out_0,
+                                            size_t *out_0_len,
+                                            uint32_t *out_1,
+                                            bool *out_2,
+                                            uint32_t value);
+
+SignalFfiError *signal_testing_session_store_has_session(bool *out,
+                                                        const SignalProtocolAddress *address,
+                                                        const SignalSessionStore *store);
+
+SignalFfiError *signal_testing_string_from_utf8(const char **out,
+                                                const unsigned char *bytes,
+                                                size_t bytes_len);
+
+SignalFfiError *signal_testing_u64_from_bytes(uint64_t *out,
+                                              const unsigned char *bytes,
+                                              size_t bytes_len);
+
+SignalFfiError *signal_testing_u64_to_bytes(const unsigned char **out,
+                                            size_t *out_len,
+                                            uint64_t value);
+
+SignalFfiError *signal_testing_uuid_to_string(const char **out, const uint8_t (*uuid)[16]);
+
+#endif /* SIGNAL_FFI_TESTING_H_ */
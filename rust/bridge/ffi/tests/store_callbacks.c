@@ -0,0 +1,110 @@
+/*
+Copyright 2021 Signal Messenger, LLC.
+SPDX-License-Identifier: AGPL-3.0-only
+*/
+
+/* Checks that stores passed to bridge_fn entry points are called through their callbacks, and
+ * that a failing callback is reported as a SignalErrorCode_CallbackError.
+ *
+ * Build and run with rust/bridge/ffi/tests/run_c_tests.sh. */
+
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#include "signal_ffi_testing.h"
+
+#define CHECK(condition)                                                                  \
+  do {                                                                                    \
+    if (!(condition)) {                                                                   \
+      fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__, __LINE__, #condition);      \
+      exit(1);                                                                            \
+    }                                                                                     \
+  } while (0)
+
+typedef struct {
+  int load_count;
+  const SignalProtocolAddress *last_address;
+  int result;
+} FakeSessionStore;
+
+static int load_session(void *store_ctx,
+                        SignalSessionRecord **recordp,
+                        const SignalProtocolAddress *address,
+                        void *ctx) {
+  FakeSessionStore *store = store_ctx;
+  store->load_count += 1;
+  store->last_address = address;
+  *recordp = NULL;
+  return store->result;
+}
+
+static int store_session(void *store_ctx,
+                         const SignalProtocolAddress *address,
+                         const SignalSessionRecord *record,
+                         void *ctx) {
+  CHECK(!"not used by these tests");
+  return 1;
+}
+
+static SignalProtocolAddress *new_address(void) {
+  SignalProtocolAddress *address = NULL;
+  CHECK(signal_address_new(&address, "+14155550100", 1) == NULL);
+  return address;
+}
+
+static void test_no_session(void) {
+  FakeSessionStore fake = {0};
+  SignalSessionStore store = {&fake, load_session, store_session};
+  SignalProtocolAddress *address = new_address();
+
+  bool has_session = true;
+  CHECK(signal_testing_session_store_has_session(&has_session, address, &store) == NULL);
+  CHECK(!has_session);
+  CHECK(fake.load_count == 1);
+  CHECK(fake.last_address == address);
+
+  signal_address_destroy(address);
+}
+
+static void test_callback_failure(void) {
+  FakeSessionStore fake = {0};
+  fake.result = 7;
+  SignalSessionStore store = {&fake, load_session, store_session};
+  SignalProtocolAddress *address = new_address();
+
+  bool has_session = false;
+  SignalFfiError *error = signal_testing_session_store_has_session(&has_session, address, &store);
+  CHECK(error != NULL);
+  CHECK(fake.load_count == 1);
+  CHECK(signal_error_get_type(error) == SignalErrorCode_CallbackError);
+
+  const char *message = NULL;
+  CHECK(signal_error_get_message(error, &message) == NULL);
+  CHECK(strstr(message, "load_session") != NULL);
+  CHECK(strstr(message, "error code 7") != NULL);
+  signal_free_string(message);
+  signal_error_free(error);
+
+  signal_address_destroy(address);
+}
+
+static void test_null_store(void) {
+  SignalProtocolAddress *address = new_address();
+
+  bool has_session = false;
+  SignalFfiError *error = signal_testing_session_store_has_session(&has_session, address, NULL);
+  CHECK(error != NULL);
+  CHECK(signal_error_get_type(error) == SignalErrorCode_NullParameter);
+  signal_error_free(error);
+
+  signal_address_destroy(address);
+}
+
+int main(void) {
+  test_no_session();
+  test_callback_failure();
+  test_null_store();
+  printf("store callback tests passed\n");
+  return 0;
+}
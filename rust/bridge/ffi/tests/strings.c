@@ -0,0 +1,86 @@
+/*
+Copyright 2021 Signal Messenger, LLC.
+SPDX-License-Identifier: AGPL-3.0-only
+*/
+
+/* Checks the ownership rules for strings returned to C: each is a NUL-terminated UTF-8 string
+ * allocated by Rust, which the caller owns and must release with signal_free_string. Strings that
+ * can't be represented in C (because they contain a NUL character) are reported as errors.
+ *
+ * Build and run with rust/bridge/ffi/tests/run_c_tests.sh. */
+
+#include <stdio.h>
+#include <string.h>
+
+#include "signal_ffi_testing.h"
+
+#define CHECK(condition)                                                                  \
+  do {                                                                                    \
+    if (!(condition)) {                                                                   \
+      fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__, __LINE__, #condition);      \
+      exit(1);                                                                            \
+    }                                                                                     \
+  } while (0)
+
+static void test_returned_string(void) {
+  const unsigned char bytes[] = "h\xc3\xa9llo";
+  const char *out = NULL;
+
+  CHECK(signal_testing_string_from_utf8(&out, bytes, sizeof(bytes) - 1) == NULL);
+  CHECK(out != NULL);
+  CHECK(strcmp(out, "h\xc3\xa9llo") == 0);
+
+  /* The caller owns the string, so it can be modified before being freed. */
+  ((char *)out)[0] = 'H';
+  CHECK(strcmp(out, "H\xc3\xa9llo") == 0);
+  signal_free_string(out);
+}
+
+static void test_empty_string(void) {
+  const char *out = NULL;
+
+  /* An empty result is still a valid allocation, distinct from NULL. */
+  CHECK(signal_testing_string_from_utf8(&out, (const unsigned char *)"", 0) == NULL);
+  CHECK(out != NULL);
+  CHECK(out[0] == '\0');
+  signal_free_string(out);
+}
+
+static void test_free_null(void) {
+  /* Freeing NULL is a no-op, so callers don't need to check optional results. */
+  signal_free_string(NULL);
+}
+
+static void test_interior_nul(void) {
+  const unsigned char bytes[] = {'a', '\0', 'b'};
+  const char *out = NULL;
+
+  SignalFfiError *error = signal_testing_string_from_utf8(&out, bytes, sizeof(bytes));
+  CHECK(error != NULL);
+  CHECK(signal_error_get_type(error) == SignalErrorCode_InternalError);
+  /* Nothing is written to the out-parameter on failure. */
+  CHECK(out == NULL);
+
+  const char *message = NULL;
+  CHECK(signal_error_get_message(error, &message) == NULL);
+  CHECK(strstr(message, "NUL character") != NULL);
+  signal_free_string(message);
+  signal_error_free(error);
+}
+
+static void test_null_out(void) {
+  SignalFfiError *error = signal_testing_string_from_utf8(NULL, (const unsigned char *)"a", 1);
+  CHECK(error != NULL);
+  CHECK(signal_error_get_type(error) == SignalErrorCode_NullParameter);
+  signal_error_free(error);
+}
+
+int main(void) {
+  test_returned_string();
+  test_empty_string();
+  test_free_null();
+  test_interior_nul();
+  test_null_out();
+  printf("string tests passed\n");
+  return 0;
+}
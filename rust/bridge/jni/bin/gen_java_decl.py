@@ -38,7 +38,7 @@ stderr = str(stderr.decode('utf8'))
 ignore_this_warning = re.compile(
     "("
     r"WARN: Can't find .*\. This usually means that this type was incompatible or not found\.|"
-    r"WARN: Missing `\[defines\]` entry for `feature = \"jni\"` in cbindgen config\.|"
+    r"WARN: Missing `\[defines\]` entry for `feature = \"(jni|testing)\"` in cbindgen config\.|"
     r"WARN: Skip libsignal-bridge::_ - \(not `pub`\)\."
     ")")
 
@@ -57,7 +57,7 @@ for l in stderr.split('\n'):
 if unknown_warning:
     sys.exit(1)
 
-java_decl = re.compile(r'([a-zA-Z]+) Java_org_signal_client_internal_Native_([A-Z][a-zA-Z0-9]+)_1([A-Za-z0-9]+)\(JNIEnv .?env, JClass class_(, .*)?\);')
+java_decl = re.compile(r'(?:DEPRECATED(?:\(("(?:[^"\\]|\\.)*")\))? )?([a-zA-Z]+) Java_org_signal_client_internal_(Native|NativeTesting)_([A-Z][a-zA-Z0-9]+)_1([A-Za-z0-9]+)\(JNIEnv .?env, JClass class_(, .*)?\);')
 
 
 def translate_to_java(typ):
@@ -67,8 +67,13 @@ def translate_to_java(typ):
         "jstring": "String",
         "JString": "String",
         "JClass": "Class",
+        "JByteBuffer": "ByteBuffer",
         "jbyteArray": "byte[]",
         "jlongArray": "long[]",
+        # Only used for Vec<Box<[u8]>> so far.
+        "jobjectArray": "byte[][]",
+        "JavaTuple": "Object[]",
+        "JavaStringArray": "String[]",
         "ObjectHandle": "long",
         "jint": "int",
         "jlong": "long",
@@ -85,8 +90,16 @@ def translate_to_java(typ):
     raise Exception("Don't know what to do with a", typ)
 
 
-cur_type = None
-decls = []
+# TESTING_ functions are only built with the "testing" feature (see cbindgen.toml), so they're
+# declared in a separate class that lives with the tests instead of in Native.java.
+outputs = [
+    ('Native', 'Native.java.in', '../../../../java/java/src/main/java/org/signal/internal/Native.java'),
+    ('NativeTesting', 'NativeTesting.java.in',
+     '../../../../java/tests/src/test/java/org/signal/client/internal/NativeTesting.java'),
+]
+
+cur_types = {}
+decls = {class_name: [] for (class_name, _, _) in outputs}
 
 for line in stdout.split('\n'):
     if line == '':
@@ -96,12 +109,13 @@ for line in stdout.split('\n'):
     if match is None:
         raise Exception("Could not understand", line)
 
-    (ret_type, this_type, method_name, args) = match.groups()
+    (deprecation_note, ret_type, class_name, this_type, method_name, args) = match.groups()
+    class_decls = decls[class_name]
 
     # Add newlines between groups of functions for readability
-    if cur_type is None or this_type != cur_type:
-        decls.append("")
-        cur_type = this_type
+    if cur_types.get(class_name) != this_type:
+        class_decls.append("")
+        cur_types[class_name] = this_type
 
     java_fn_name = '%s_%s' % (this_type, method_name)
     java_ret_type = translate_to_java(ret_type)
@@ -113,28 +127,42 @@ for line in stdout.split('\n'):
             java_arg_type = translate_to_java(arg_type)
             java_args.append('%s %s' % (java_arg_type, arg_name))
 
-    decls.append("  public static native %s %s(%s);" % (java_ret_type, java_fn_name, ", ".join(java_args)))
-
-template_file = open(os.path.join(our_abs_dir, 'Native.java.in')).read()
-
-contents = template_file.replace('\n  // INSERT DECLS HERE', "\n".join(decls))
-
-native_java = os.path.join(our_abs_dir, '../../../../java/java/src/main/java/org/signal/internal/Native.java')
-
-if not os.access(native_java, os.F_OK):
-    raise Exception("Didn't find Native.java where it was expected")
-
-if not mode:
-    with open(native_java, 'w') as fh:
-        fh.write(contents)
-elif mode == '--verify':
-    with open(native_java) as fh:
-        current_contents = fh.readlines()
-    diff = difflib.unified_diff(current_contents, contents.splitlines(keepends=True))
-    first_line = next(diff, None)
-    if first_line:
-        sys.stdout.write(first_line)
-        sys.stdout.writelines(diff)
-        sys.exit("error: Native.java not up to date; re-run %s!" % sys.argv[0])
-else:
-    raise Exception("mode not properly validated")
+    # Functions marked #[deprecated] in Rust are prefixed with DEPRECATED (see cbindgen.toml).
+    if line.startswith('DEPRECATED'):
+        if deprecation_note:
+            note = re.sub(r'\\(.)', r'\1', deprecation_note[1:-1]).replace('*/', '*\\/')
+            class_decls.append("  /** @deprecated %s */" % note)
+        class_decls.append("  @Deprecated")
+
+    class_decls.append("  public static native %s %s(%s);" % (java_ret_type, java_fn_name, ", ".join(java_args)))
+
+out_of_date = False
+
+for (class_name, template_name, output_path) in outputs:
+    template_file = open(os.path.join(our_abs_dir, template_name)).read()
+
+    contents = template_file.replace('\n  // INSERT DECLS HERE', "\n".join(decls[class_name]))
+
+    java_file = os.path.join(our_abs_dir, output_path)
+
+    if not os.access(java_file, os.F_OK):
+        raise Exception("Didn't find %s.java where it was expected" % class_name)
+
+    if not mode:
+        with open(java_file, 'w') as fh:
+            fh.write(contents)
+    elif mode == '--verify':
+        with open(java_file) as fh:
+            current_contents = fh.readlines()
+        diff = difflib.unified_diff(current_contents, contents.splitlines(keepends=True))
+        first_line = next(diff, None)
+        if first_line:
+            sys.stdout.write(first_line)
+            sys.stdout.writelines(diff)
+            print("error: %s.java not up to date" % class_name, file=sys.stderr)
+            out_of_date = True
+    else:
+        raise Exception("mode not properly validated")
+
+if out_of_date:
+    sys.exit("error: re-run %s!" % sys.argv[0])
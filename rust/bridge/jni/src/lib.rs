@@ -14,6 +14,8 @@ use libsignal_bridge::jni::*;
 use libsignal_protocol::*;
 
 pub mod logging;
+#[cfg(feature = "testing")]
+mod testing;
 
 type JavaCiphertextMessage = jobject;
 
@@ -28,7 +30,7 @@ pub unsafe extern "C" fn Java_org_signal_client_internal_Native_IdentityKeyPair_
         let key = IdentityKeyPair::try_from(data.as_ref())?;
 
         let public_key_handle = box_object(Ok(*key.identity_key().public_key()))?;
-        let private_key_handle = box_object(Ok(*key.private_key()))?;
+        let private_key_handle = box_object(Ok(key.private_key().clone()))?;
         let tuple = [public_key_handle, private_key_handle];
 
         let result = env.new_long_array(2)?;
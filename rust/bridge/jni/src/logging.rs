@@ -3,12 +3,21 @@
 // SPDX-License-Identifier: AGPL-3.0-only
 //
 
-use jni::objects::{GlobalRef, JClass, JObject, JValue};
-use jni::sys::jint;
+use crossbeam_queue::SegQueue;
+use jni::objects::{GlobalRef, JClass, JObject, JString, JValue};
+use jni::sys::{jint, jobjectArray};
 use jni::{JNIEnv, JavaVM};
-use std::any::Any;
-use std::panic::{catch_unwind, AssertUnwindSafe};
+use libsignal_bridge::jni::{describe_panic, has_pinned_arrays};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::panic::{catch_unwind, AssertUnwindSafe, PanicInfo};
 use std::process::abort;
+use std::sync::atomic::{AtomicPtr, AtomicU32, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, Once, RwLock};
+use std::time::Instant;
+
+/// A Java `String[]`.
+type JavaStringArray = jobjectArray;
 
 // Keep this in sync with SignalProtocolLogger.java, as well as the list below.
 #[derive(Clone, Copy)]
@@ -59,72 +68,632 @@ impl From<JavaLogLevel> for log::Level {
     }
 }
 
+/// Formatted records waiting to be delivered to Java in a single call.
+struct LogBuffer {
+    records: SegQueue<(JavaLogLevel, String)>,
+    /// The number of records to collect before delivering them.
+    capacity: usize,
+    /// Held while delivering records, so that batches reach Java in order.
+    delivery_lock: Mutex<()>,
+}
+
+/// The tag passed to the Java logger with every record.
+const LOG_TAG: &str = "libsignal-client";
+
+/// How many strings a [`StringPool`] keeps.
+const STRING_POOL_SLOTS: usize = 8;
+
+/// Java strings that are passed to the logger over and over, like the tag and structured logging
+/// keys, kept as global references so that logging a record doesn't allocate new ones.
+///
+/// The pool keeps the most recently used strings, up to its capacity, so a key that stops being
+/// logged is eventually released. Other strings are allocated fresh each time.
+struct StringPool {
+    capacity: usize,
+    /// The pooled strings, least recently used first.
+    slots: Mutex<Vec<(String, GlobalRef)>>,
+}
+
+impl StringPool {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            slots: Mutex::new(Vec::with_capacity(capacity)),
+        }
+    }
+
+    /// Returns a local reference to a Java string with the contents of `s`.
+    ///
+    /// The lock is never held while calling into Java, which may block for a GC and would stall
+    /// every other thread that's logging.
+    fn get<'a>(&self, env: &JNIEnv<'a>, s: &str) -> jni::errors::Result<JObject<'a>> {
+        let pooled = {
+            let mut slots = self.slots.lock().expect("not poisoned");
+            slots
+                .iter()
+                .position(|(contents, _)| contents == s)
+                .map(|i| {
+                    let slot = slots.remove(i);
+                    let string = slot.1.clone();
+                    slots.push(slot);
+                    string
+                })
+        };
+        if let Some(string) = pooled {
+            return env.new_local_ref::<JObject>(string.as_obj().into_inner().into());
+        }
+
+        let string = env.new_string(s)?.into();
+        if self.capacity == 0 {
+            return Ok(string);
+        }
+        let global = env.new_global_ref(string)?;
+        let unused = {
+            let mut slots = self.slots.lock().expect("not poisoned");
+            if slots.iter().any(|(contents, _)| contents == s) {
+                // Another thread pooled the same string first.
+                Some(global)
+            } else {
+                slots.push((s.to_string(), global));
+                if slots.len() > self.capacity {
+                    Some(slots.remove(0).1)
+                } else {
+                    None
+                }
+            }
+        };
+        // Deleting a global reference calls into Java too.
+        drop(unused);
+        Ok(string)
+    }
+}
+
 struct JniLogger {
     vm: JavaVM,
     logger_class: GlobalRef,
+    strings: StringPool,
+    /// If present, only records whose targets start with one of these prefixes are logged.
+    target_prefixes: Option<Vec<String>>,
+    /// If present, records are delivered in batches rather than one at a time. A warning or error
+    /// delivers the batch right away, along with everything logged before it.
+    ///
+    /// Loggers are never dropped, so records still waiting at exit are only delivered by
+    /// [`log::Log::flush`], which Native.java calls from a shutdown hook.
+    buffer: Option<LogBuffer>,
+    /// The most verbose [`log::LevelFilter`] to deliver, as a `usize`.
+    ///
+    /// This is checked instead of [`log::max_level`], which other crates in the same process may
+    /// also set, and can be changed at any time with `Logger_SetMaxLevel`.
+    max_level: AtomicUsize,
 }
 
 impl JniLogger {
-    fn new(env: JNIEnv, logger_class: JClass) -> jni::errors::Result<Self> {
+    /// Creates a new logger; a `buffer_size` of 0 delivers each record as soon as it's logged.
+    fn new(
+        env: JNIEnv,
+        logger_class: JClass,
+        target_prefixes: Option<Vec<String>>,
+        buffer_size: usize,
+    ) -> jni::errors::Result<Self> {
+        let buffer = if buffer_size == 0 {
+            None
+        } else {
+            Some(LogBuffer {
+                records: SegQueue::new(),
+                capacity: buffer_size,
+                delivery_lock: Mutex::new(()),
+            })
+        };
         Ok(Self {
             vm: env.get_java_vm()?,
             logger_class: env.new_global_ref(logger_class)?,
+            strings: StringPool::new(STRING_POOL_SLOTS),
+            target_prefixes,
+            buffer,
+            max_level: AtomicUsize::new(log::max_level() as usize),
         })
     }
 
+    fn set_max_level(&self, level: log::LevelFilter) {
+        self.max_level.store(level as usize, Ordering::Relaxed);
+        // The log macros check the global level before getting here, so make sure it lets through
+        // everything this logger wants, without lowering it for anyone else.
+        if level > log::max_level() {
+            log::set_max_level(level);
+        }
+    }
+
+    fn is_target_enabled(&self, target: &str) -> bool {
+        match &self.target_prefixes {
+            None => true,
+            Some(prefixes) => prefixes.iter().any(|prefix| target.starts_with(prefix)),
+        }
+    }
+
     fn log_impl(&self, record: &log::Record) -> jni::errors::Result<()> {
-        let env = self.vm.attach_current_thread()?;
         let level: JavaLogLevel = record.level().into();
-        let message = format!(
-            "{}:{}: {}",
-            record.file().unwrap_or("<unknown>"),
-            record.line().unwrap_or(0),
-            record.args(),
-        );
-        let args: [JValue; 3] = [
-            level.into(),
-            env.new_string("libsignal-client")?.into(),
-            env.new_string(message)?.into(),
-        ];
-        let result = env.call_static_method(
-            &self.logger_class,
-            "log",
-            "(ILjava/lang/String;Ljava/lang/String;)V",
-            &args,
-        );
-
-        let throwable = env.exception_occurred()?;
-        if **throwable == *JObject::null() {
-            result?;
-        } else {
+        let message = format_message(record);
+        let key_values = collect_key_values(record);
+
+        if let Some(buffer) = &self.buffer {
+            // Batches have no room for structure, so fall back to the same format as Log.java.
+            let mut message = message;
+            for (key, value) in key_values {
+                message.push_str(&format!(" {}={}", key, value));
+            }
+            buffer.records.push((level, message));
+            // Android can kill the app without running shutdown hooks, so don't leave warnings and
+            // errors waiting for a full batch; they're the records most likely to explain why.
+            if buffer.records.len() >= buffer.capacity || record.level() <= log::Level::Warn {
+                self.flush_buffer()?;
+            }
+            return Ok(());
+        }
+
+        // Calling into Java with an array pinned could deadlock, and there's nowhere to keep the
+        // record until it's released, so drop it.
+        if has_pinned_arrays() {
+            return Ok(());
+        }
+
+        let env = self.vm.attach_current_thread()?;
+
+        // Logging often happens in the middle of a long native call, so release this record's
+        // local references right away instead of leaving them for when it returns.
+        with_local_frame(&env, || {
+            let result = if key_values.is_empty() {
+                let args: [JValue; 3] = [
+                    level.into(),
+                    self.strings.get(&env, LOG_TAG)?.into(),
+                    env.new_string(message)?.into(),
+                ];
+                env.call_static_method(
+                    &self.logger_class,
+                    "log",
+                    "(ILjava/lang/String;Ljava/lang/String;)V",
+                    &args,
+                )
+            } else {
+                let (keys, values): (Vec<_>, Vec<_>) = key_values.into_iter().unzip();
+                let args: [JValue; 5] = [
+                    level.into(),
+                    self.strings.get(&env, LOG_TAG)?.into(),
+                    env.new_string(message)?.into(),
+                    new_string_array(&env, &keys, Some(&self.strings))?.into(),
+                    new_string_array(&env, &values, None)?.into(),
+                ];
+                env.call_static_method(
+                    &self.logger_class,
+                    "logStructured",
+                    "(ILjava/lang/String;Ljava/lang/String;[Ljava/lang/String;[Ljava/lang/String;)V",
+                    &args,
+                )
+            };
+
+            ignore_java_exception(&env, result)
+        })
+    }
+
+    /// Delivers any buffered records to Java in a single call.
+    ///
+    /// While the current thread has arrays pinned, this does nothing, leaving the records for the
+    /// next flush.
+    fn flush_buffer(&self) -> jni::errors::Result<()> {
+        let buffer = match &self.buffer {
+            Some(buffer) => buffer,
+            None => return Ok(()),
+        };
+        if has_pinned_arrays() {
+            return Ok(());
+        }
+        let _guard = buffer.delivery_lock.lock().expect("not poisoned");
+
+        let mut levels = Vec::new();
+        let mut messages = Vec::new();
+        while let Some((level, message)) = buffer.records.pop() {
+            levels.push(jint::from(level));
+            messages.push(message);
+        }
+        if messages.is_empty() {
+            return Ok(());
+        }
+
+        let env = self.vm.attach_current_thread()?;
+        with_local_frame(&env, || {
+            let level_array = env.new_int_array(levels.len() as jint)?;
+            env.set_int_array_region(level_array, 0, &levels)?;
+            let args: [JValue; 3] = [
+                JObject::from(level_array).into(),
+                self.strings.get(&env, LOG_TAG)?.into(),
+                new_string_array(&env, &messages, None)?.into(),
+            ];
+            let result = env.call_static_method(
+                &self.logger_class,
+                "log",
+                "([ILjava/lang/String;[Ljava/lang/String;)V",
+                &args,
+            );
+
+            ignore_java_exception(&env, result)
+        })
+    }
+}
+
+impl JniLogger {
+    /// Logs a panic's message and location at `Error` level, ignoring target filters, and
+    /// delivers it to Java right away.
+    fn log_panic(&self, info: &PanicInfo) -> jni::errors::Result<()> {
+        // Calling into Java with an array pinned could deadlock, so leave it to the previous hook.
+        if has_pinned_arrays() {
+            return Ok(());
+        }
+
+        // The panic may be on a thread Java has never seen, like one running a future. Attach it
+        // as a daemon so that it doesn't keep the JVM from exiting; it's detached when it exits.
+        let env = self.vm.attach_current_thread_as_daemon()?;
+        // Set aside any exception that's already pending, since Java can't be called until it's
+        // cleared.
+        let pending = env.exception_occurred()?;
+        if !pending.is_null() {
             env.exception_clear()?;
         }
-        Ok(())
+
+        let thread = std::thread::current();
+        let location = info.location();
+        let result = self
+            .log_impl(
+                &log::Record::builder()
+                    .level(log::Level::Error)
+                    .target(module_path!())
+                    .args(format_args!(
+                        "thread '{}' panicked: {}",
+                        thread.name().unwrap_or("<unnamed>"),
+                        describe_panic(info.payload())
+                    ))
+                    .file(location.map(|location| location.file()))
+                    .line(location.map(|location| location.line()))
+                    .build(),
+            )
+            .and_then(|()| self.flush_buffer());
+
+        if !pending.is_null() {
+            env.throw(pending)?;
+        }
+        result
+    }
+}
+
+/// Logs every panic through each of `registry`'s loggers before running the previous panic hook.
+///
+/// Panics in bridge functions are still turned into exceptions by `run_ffi_safe`, but those only
+/// have the panic message, not where it came from.
+fn install_panic_hook(registry: &'static LoggerRegistry) {
+    let previous_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        for logger in registry.loggers().iter() {
+            if logger.inner.log_panic(info).is_err() {
+                // Drop the error; it's not like we can log it!
+            }
+        }
+        previous_hook(info);
+    }));
+}
+
+fn format_message(record: &log::Record) -> String {
+    format!(
+        "{}:{}: {}",
+        record.file().unwrap_or("<unknown>"),
+        record.line().unwrap_or(0),
+        record.args(),
+    )
+}
+
+/// Clears any exception thrown by a call to the Java logger, so that logging never fails because
+/// of a misbehaving logger.
+fn ignore_java_exception<T>(
+    env: &JNIEnv,
+    result: jni::errors::Result<T>,
+) -> jni::errors::Result<()> {
+    let throwable = env.exception_occurred()?;
+    if **throwable == *JObject::null() {
+        result?;
+    } else {
+        env.exception_clear()?;
+    }
+    Ok(())
+}
+
+/// Runs `f` in a new local reference frame, releasing any local references it creates.
+fn with_local_frame(
+    env: &JNIEnv,
+    f: impl FnOnce() -> jni::errors::Result<()>,
+) -> jni::errors::Result<()> {
+    // Enough for the arguments of any call to the Java logger.
+    const LOCAL_FRAME_CAPACITY: i32 = 8;
+    env.with_local_frame(LOCAL_FRAME_CAPACITY, || f().map(|()| JObject::null()))?;
+    Ok(())
+}
+
+/// Collects the key-value pairs attached to `record` as strings, in order.
+fn collect_key_values(record: &log::Record) -> Vec<(String, String)> {
+    struct Collector(Vec<(String, String)>);
+
+    impl<'kvs> log::kv::Visitor<'kvs> for Collector {
+        fn visit_pair(
+            &mut self,
+            key: log::kv::Key<'kvs>,
+            value: log::kv::Value<'kvs>,
+        ) -> Result<(), log::kv::Error> {
+            self.0.push((key.to_string(), value.to_string()));
+            Ok(())
+        }
+    }
+
+    let mut collector = Collector(Vec::new());
+    // Our visitor never fails, so neither will this.
+    let _ = record.key_values().visit(&mut collector);
+    collector.0
+}
+
+/// Reads a Java `String[]`, treating `null` as `None`.
+fn read_string_array(
+    env: &JNIEnv,
+    array: JavaStringArray,
+) -> jni::errors::Result<Option<Vec<String>>> {
+    if array.is_null() {
+        return Ok(None);
+    }
+    let len = env.get_array_length(array)?;
+    let strings = (0..len)
+        .map(|i| {
+            let string = JString::from(env.get_object_array_element(array, i)?);
+            let result = env.get_string(string)?.into();
+            env.delete_local_ref(string.into())?;
+            Ok(result)
+        })
+        .collect::<jni::errors::Result<_>>()?;
+    Ok(Some(strings))
+}
+
+/// Creates a Java `String[]`, taking the strings from `pool` if given.
+fn new_string_array<'a>(
+    env: &JNIEnv<'a>,
+    strings: &[String],
+    pool: Option<&StringPool>,
+) -> jni::errors::Result<JObject<'a>> {
+    let array = env.new_object_array(strings.len() as jint, "java/lang/String", JObject::null())?;
+    for (i, string) in (0..).zip(strings) {
+        let string = match pool {
+            Some(pool) => pool.get(env, string)?,
+            None => env.new_string(string)?.into(),
+        };
+        env.set_object_array_element(array, i, string)?;
+        env.delete_local_ref(string)?;
     }
+    Ok(JObject::from(array))
 }
 
 impl log::Log for JniLogger {
-    fn enabled(&self, _metadata: &log::Metadata) -> bool {
-        true
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() as usize <= self.max_level.load(Ordering::Relaxed)
+            && self.is_target_enabled(metadata.target())
     }
 
     fn log(&self, record: &log::Record) {
+        // The log macros only check the level, so check again before calling into Java.
+        if !self.enabled(record.metadata()) {
+            return;
+        }
         if self.log_impl(record).is_err() {
             // Drop the error; it's not like we can log it!
         }
     }
 
-    fn flush(&self) {}
+    fn flush(&self) {
+        if self.flush_buffer().is_err() {
+            // Drop the error; it's not like we can log it!
+        }
+    }
 }
 
-// See https://github.com/rust-lang/rfcs/issues/1389
-fn describe_panic(any: &Box<dyn Any + Send>) -> String {
-    if let Some(msg) = any.downcast_ref::<&str>() {
-        msg.to_string()
-    } else if let Some(msg) = any.downcast_ref::<String>() {
-        msg.to_string()
-    } else {
-        "(break on rust_panic to debug)".to_string()
+/// How much a single logging site has logged recently, as a token bucket.
+struct SiteState {
+    tokens: f64,
+    last_refill: Instant,
+    suppressed: u64,
+}
+
+/// Wraps a [`JniLogger`] to limit how often each logging site (file and line) can call into Java.
+///
+/// Each site may log `burst_size` messages at once, refilled at `messages_per_sec`. Further
+/// messages are dropped, and the next message from the same site to get through notes how many
+/// were suppressed.
+struct LogSampler {
+    inner: JniLogger,
+    sites: Mutex<HashMap<(Cow<'static, str>, u32), SiteState>>,
+    /// The number of messages per second allowed from each logging site, or 0 for no limit.
+    ///
+    /// Set from Java with `Logger_SetSampling`.
+    messages_per_sec: AtomicU32,
+    /// The number of messages a logging site can emit at once before being limited.
+    burst_size: AtomicU32,
+}
+
+impl LogSampler {
+    fn new(inner: JniLogger) -> Self {
+        Self {
+            inner,
+            sites: Mutex::new(HashMap::new()),
+            messages_per_sec: AtomicU32::new(0),
+            burst_size: AtomicU32::new(0),
+        }
+    }
+
+    fn set_sampling(&self, messages_per_sec: u32, burst_size: u32) {
+        self.burst_size.store(burst_size, Ordering::Relaxed);
+        self.messages_per_sec
+            .store(messages_per_sec, Ordering::Relaxed);
+    }
+
+    /// Decides whether `record` should be logged, returning the number of messages suppressed
+    /// from its site since the last one that was, or `None` if this one should be suppressed too.
+    fn sample(&self, record: &log::Record) -> Option<u64> {
+        let messages_per_sec = self.messages_per_sec.load(Ordering::Relaxed);
+        if messages_per_sec == 0 {
+            return Some(0);
+        }
+        let burst_size = f64::from(self.burst_size.load(Ordering::Relaxed).max(1));
+
+        let file = match record.file_static() {
+            Some(file) => Cow::Borrowed(file),
+            None => Cow::Owned(record.file().unwrap_or("<unknown>").to_string()),
+        };
+        let now = Instant::now();
+
+        let mut sites = self.sites.lock().expect("not poisoned");
+        let site = sites
+            .entry((file, record.line().unwrap_or(0)))
+            .or_insert(SiteState {
+                tokens: burst_size,
+                last_refill: now,
+                suppressed: 0,
+            });
+
+        let elapsed = now.duration_since(site.last_refill);
+        site.tokens =
+            burst_size.min(site.tokens + elapsed.as_secs_f64() * f64::from(messages_per_sec));
+        site.last_refill = now;
+
+        if site.tokens < 1.0 {
+            site.suppressed += 1;
+            return None;
+        }
+        site.tokens -= 1.0;
+        Some(std::mem::take(&mut site.suppressed))
+    }
+}
+
+impl log::Log for LogSampler {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &log::Record) {
+        // Don't count records that would be filtered out anyway.
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        match self.sample(record) {
+            None => {}
+            Some(0) => self.inner.log(record),
+            Some(suppressed) => self.inner.log(
+                &log::Record::builder()
+                    .metadata(record.metadata().clone())
+                    .args(format_args!(
+                        "{} (suppressed {} messages)",
+                        record.args(),
+                        suppressed
+                    ))
+                    .module_path(record.module_path())
+                    .file(record.file())
+                    .line(record.line())
+                    .key_values(record.key_values())
+                    .build(),
+            ),
+        }
+    }
+
+    fn flush(&self) {
+        self.inner.flush()
+    }
+}
+
+/// The loggers registered with `Logger_Initialize`, each delivering records to a different Java
+/// class with its own filters and sampling.
+///
+/// `Logger_SetMaxLevel` and `Logger_SetSampling` take the Java class too, and only change the
+/// logger registered for it.
+///
+/// This is what's installed with [`log::set_logger`], which can only be done once per process.
+/// Loggers are only ever added, and are leaked so that they can be used without holding the lock.
+struct LoggerRegistry {
+    loggers: RwLock<Arc<Vec<&'static LogSampler>>>,
+}
+
+impl LoggerRegistry {
+    fn new() -> Self {
+        Self {
+            loggers: RwLock::new(Arc::new(Vec::new())),
+        }
+    }
+
+    /// Returns the registered loggers.
+    ///
+    /// The lock is released before returning, so a logger calling back into Rust can't deadlock
+    /// with a concurrent registration.
+    fn loggers(&self) -> Arc<Vec<&'static LogSampler>> {
+        self.loggers.read().expect("not poisoned").clone()
+    }
+
+    /// Returns the logger registered for `logger_class`, if there is one.
+    fn find(
+        &self,
+        env: &JNIEnv,
+        logger_class: JClass,
+    ) -> jni::errors::Result<Option<&'static LogSampler>> {
+        for &logger in self.loggers().iter() {
+            if env.is_same_object(logger.inner.logger_class.as_obj(), logger_class)? {
+                return Ok(Some(logger));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Adds `logger`, unless a logger for the same Java class is already registered.
+    ///
+    /// Returns whether `logger` was added.
+    fn register(&self, env: &JNIEnv, logger: LogSampler) -> jni::errors::Result<bool> {
+        let mut loggers = self.loggers.write().expect("not poisoned");
+        for existing in loggers.iter() {
+            if env.is_same_object(
+                existing.inner.logger_class.as_obj(),
+                logger.inner.logger_class.as_obj(),
+            )? {
+                return Ok(false);
+            }
+        }
+        let mut new_loggers = Vec::clone(&loggers);
+        new_loggers.push(Box::leak(Box::new(logger)));
+        *loggers = Arc::new(new_loggers);
+        Ok(true)
+    }
+}
+
+impl log::Log for LoggerRegistry {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        self.loggers().iter().any(|logger| logger.enabled(metadata))
+    }
+
+    fn log(&self, record: &log::Record) {
+        for logger in self.loggers().iter() {
+            logger.log(record);
+        }
+    }
+
+    fn flush(&self) {
+        for logger in self.loggers().iter() {
+            logger.flush();
+        }
+    }
+}
+
+/// Throws an `IllegalArgumentException` for an argument passed in from Java that can't be used.
+fn throw_illegal_argument(env: &JNIEnv, message: &str) {
+    if env
+        .throw_new("java/lang/IllegalArgumentException", message)
+        .is_err()
+    {
+        // Drop the error; we're about to return to Java either way.
     }
 }
 
@@ -132,28 +701,52 @@ fn describe_panic(any: &Box<dyn Any + Send>) -> String {
 ///
 /// This is important for logging failures because we might want to log during the normal
 /// `run_ffi_safe`. This should *not* be used normally because we don't want to crash the app!
-fn abort_on_panic(f: impl FnOnce()) {
+fn abort_on_panic<T>(f: impl FnOnce() -> T) -> T {
     catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|e| {
-        eprintln!("fatal error: {}", describe_panic(&e));
+        eprintln!("fatal error: {}", describe_panic(&*e));
         abort();
+    })
+}
+
+/// The registry installed by the first call to `Logger_Initialize`, or null if there isn't one yet.
+static INSTALLED_REGISTRY: AtomicPtr<LoggerRegistry> = AtomicPtr::new(std::ptr::null_mut());
+
+/// Returns the installed registry, installing a new one as the global logger if needed.
+///
+/// Returns `None` if some other logger was installed first.
+fn installed_registry() -> Option<&'static LoggerRegistry> {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        let registry: &'static LoggerRegistry = Box::leak(Box::new(LoggerRegistry::new()));
+        if log::set_logger(registry).is_ok() {
+            install_panic_hook(registry);
+            INSTALLED_REGISTRY.store(
+                registry as *const LoggerRegistry as *mut _,
+                Ordering::Release,
+            );
+        }
     });
+    // Registries are leaked once installed, so the pointer is valid for the rest of the process.
+    unsafe { INSTALLED_REGISTRY.load(Ordering::Acquire).as_ref() }
 }
 
-fn set_max_level_from_java_level(max_level: jint) {
+/// Converts a level from SignalProtocolLogger.java, or returns a message explaining why it can't be
+/// used.
+fn level_filter_from_java_level(max_level: jint) -> Result<log::LevelFilter, &'static str> {
     // Keep this in sync with SignalProtocolLogger.java.
     let level = match max_level {
         // The jni crate uses trace! in its own implementation.
-        2 => panic!("invalid log level (must be DEBUG or higher for libsignal-client)"),
+        2 => return Err("invalid log level (must be DEBUG or higher for libsignal-client)"),
         3 => JavaLogLevel::Debug,
         4 => JavaLogLevel::Info,
         5 => JavaLogLevel::Warn,
         6 => JavaLogLevel::Error,
         7 => JavaLogLevel::Assert,
-        _ => panic!("invalid log level (see SignalProtocolLogger)"),
+        _ => return Err("invalid log level (see SignalProtocolLogger)"),
     };
     assert!(jint::from(level) == max_level);
 
-    log::set_max_level(log::Level::from(level).to_level_filter());
+    Ok(log::Level::from(level).to_level_filter())
 }
 
 #[no_mangle]
@@ -162,30 +755,354 @@ pub unsafe extern "C" fn Java_org_signal_client_internal_Native_Logger_1Initiali
     _class: JClass,
     max_level: jint,
     logger_class: JClass,
+    target_prefixes: JavaStringArray,
+    buffer_size: jint,
 ) {
     abort_on_panic(|| {
-        let logger = JniLogger::new(env, logger_class).expect("could not initialize logging");
+        if buffer_size < 0 {
+            throw_illegal_argument(&env, "buffer size must not be negative");
+            return;
+        }
+        let level = match level_filter_from_java_level(max_level) {
+            Ok(level) => level,
+            Err(message) => {
+                throw_illegal_argument(&env, message);
+                return;
+            }
+        };
+        let target_prefixes =
+            read_string_array(&env, target_prefixes).expect("could not read target prefixes");
+        let logger = JniLogger::new(env, logger_class, target_prefixes, buffer_size as usize)
+            .expect("could not initialize logging");
 
-        match log::set_logger(Box::leak(Box::new(logger))) {
-            Ok(_) => {
-                set_max_level_from_java_level(max_level);
+        logger.set_max_level(level);
+
+        let registry = match installed_registry() {
+            Some(registry) => registry,
+            None => {
+                log::warn!(
+                    "another logger is already installed; ignoring libsignal-client logging"
+                );
+                return;
+            }
+        };
+        let is_first = registry.loggers().is_empty();
+        if registry
+            .register(&env, LogSampler::new(logger))
+            .expect("could not register logger")
+        {
+            if is_first {
                 log::info!(
                     "Initializing libsignal-client version:{}",
                     env!("CARGO_PKG_VERSION")
                 );
             }
-            Err(_) => {
-                log::warn!("logging already initialized for libsignal-client; ignoring later call");
-            }
+        } else {
+            log::warn!("logging already initialized for this logger class; ignoring later call");
         }
     });
 }
 
+/// Returns the logger registered for `logger_class`, throwing an `IllegalArgumentException` if
+/// there isn't one.
+fn registered_logger(env: &JNIEnv, logger_class: JClass) -> Option<&'static LogSampler> {
+    // Registries are leaked once installed, so the pointer is valid for the rest of the process.
+    let registry = unsafe { INSTALLED_REGISTRY.load(Ordering::Acquire).as_ref() };
+    let logger = match registry {
+        Some(registry) => registry
+            .find(env, logger_class)
+            .expect("could not compare logger classes"),
+        None => None,
+    };
+    if logger.is_none() {
+        throw_illegal_argument(env, "no logger is registered for this class");
+    }
+    logger
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn Java_org_signal_client_internal_Native_Logger_1SetMaxLevel(
-    _env: JNIEnv,
+    env: JNIEnv,
     _class: JClass,
+    logger_class: JClass,
     max_level: jint,
 ) {
-    abort_on_panic(|| set_max_level_from_java_level(max_level));
+    abort_on_panic(|| {
+        let level = match level_filter_from_java_level(max_level) {
+            Ok(level) => level,
+            Err(message) => {
+                throw_illegal_argument(&env, message);
+                return;
+            }
+        };
+        if let Some(logger) = registered_logger(&env, logger_class) {
+            logger.inner.set_max_level(level);
+        }
+    });
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Java_org_signal_client_internal_Native_Logger_1Flush(
+    _env: JNIEnv,
+    _class: JClass,
+) {
+    abort_on_panic(|| log::logger().flush());
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn Java_org_signal_client_internal_Native_Logger_1SetSampling(
+    env: JNIEnv,
+    _class: JClass,
+    logger_class: JClass,
+    messages_per_sec: jint,
+    burst_size: jint,
+) {
+    abort_on_panic(|| {
+        if messages_per_sec < 0 || burst_size < 0 {
+            throw_illegal_argument(&env, "sampling parameters must not be negative");
+            return;
+        }
+        if let Some(logger) = registered_logger(&env, logger_class) {
+            logger.set_sampling(messages_per_sec as u32, burst_size as u32);
+        }
+    });
+}
+
+/// Hand-written `TESTING_` entry points for the logger, only built with the "testing" feature.
+#[cfg(feature = "testing")]
+mod testing {
+    use super::*;
+    use jni::sys::{jboolean, jbyteArray, jlong};
+    use libsignal_bridge::jni::JniPinnedSlice;
+    use std::time::Duration;
+
+    /// Logs `count` messages through a new logger that delivers them in batches of `buffer_size`,
+    /// then flushes the logger, which should deliver any that are left.
+    #[no_mangle]
+    pub unsafe extern "C" fn Java_org_signal_client_internal_NativeTesting_TESTING_1LogBuffered(
+        env: JNIEnv,
+        _class: JClass,
+        logger_class: JClass,
+        buffer_size: jint,
+        count: jint,
+    ) {
+        abort_on_panic(|| {
+            let logger = JniLogger::new(env, logger_class, None, buffer_size as usize)
+                .expect("could not create logger");
+            for i in 0..count {
+                log::Log::log(
+                    &logger,
+                    &log::Record::builder()
+                        .level(log::Level::Info)
+                        .target("libsignal_jni::logging")
+                        .args(format_args!("buffered message {}", i))
+                        .file(Some(file!()))
+                        .line(Some(line!()))
+                        .build(),
+                );
+            }
+            log::Log::flush(&logger);
+        });
+    }
+
+    /// Logs an info message, a warning, and another info message through a new logger that
+    /// delivers them in batches of `buffer_size`, without flushing. Only the last message should
+    /// still be waiting.
+    #[no_mangle]
+    pub unsafe extern "C" fn Java_org_signal_client_internal_NativeTesting_TESTING_1LogBufferedWarning(
+        env: JNIEnv,
+        _class: JClass,
+        logger_class: JClass,
+        buffer_size: jint,
+    ) {
+        abort_on_panic(|| {
+            let logger = JniLogger::new(env, logger_class, None, buffer_size as usize)
+                .expect("could not create logger");
+            let levels = [log::Level::Info, log::Level::Warn, log::Level::Info];
+            for (i, &level) in levels.iter().enumerate() {
+                log::Log::log(
+                    &logger,
+                    &log::Record::builder()
+                        .level(level)
+                        .target("libsignal_jni::logging")
+                        .args(format_args!("buffered message {}", i))
+                        .file(Some(file!()))
+                        .line(Some(line!()))
+                        .build(),
+                );
+            }
+        });
+    }
+
+    /// Logs a message and flushes through a new logger while `data` is pinned, then flushes again
+    /// once it's released. Buffered records should arrive after the release; unbuffered ones are
+    /// dropped.
+    #[no_mangle]
+    pub unsafe extern "C" fn Java_org_signal_client_internal_NativeTesting_TESTING_1LogWhilePinned(
+        env: JNIEnv,
+        _class: JClass,
+        logger_class: JClass,
+        buffer_size: jint,
+        data: jbyteArray,
+    ) {
+        abort_on_panic(|| {
+            let logger = JniLogger::new(env, logger_class, None, buffer_size as usize)
+                .expect("could not create logger");
+            {
+                let _pinned = JniPinnedSlice::new(&env, data).expect("could not pin array");
+                log::Log::log(
+                    &logger,
+                    &log::Record::builder()
+                        .level(log::Level::Info)
+                        .target("libsignal_jni::logging")
+                        .args(format_args!("logged while pinned"))
+                        .file(Some(file!()))
+                        .line(Some(line!()))
+                        .build(),
+                );
+                log::Log::flush(&logger);
+            }
+            log::Log::flush(&logger);
+        });
+    }
+
+    /// Logs `count` messages from a single site, spread evenly over `duration_ms` milliseconds, to
+    /// test sampling.
+    #[no_mangle]
+    pub unsafe extern "C" fn Java_org_signal_client_internal_NativeTesting_TESTING_1LogFlood(
+        _env: JNIEnv,
+        _class: JClass,
+        count: jint,
+        duration_ms: jint,
+    ) {
+        abort_on_panic(|| {
+            let start = Instant::now();
+            let duration = Duration::from_millis(duration_ms as u64);
+            for i in 0..count {
+                log::info!("flood message {}", i);
+
+                let next = start + duration.mul_f64(f64::from(i + 1) / f64::from(count));
+                if let Some(delay) = next.checked_duration_since(Instant::now()) {
+                    std::thread::sleep(delay);
+                }
+            }
+        });
+    }
+
+    /// Logs `message` at the given level (as in SignalProtocolLogger.java) through the installed
+    /// logger, the same way the log macros in the rest of the library do.
+    #[no_mangle]
+    pub unsafe extern "C" fn Java_org_signal_client_internal_NativeTesting_TESTING_1LogAtLevel(
+        env: JNIEnv,
+        _class: JClass,
+        level: jint,
+        message: JString,
+    ) {
+        abort_on_panic(|| {
+            let level = level_filter_from_java_level(level)
+                .expect("valid level")
+                .to_level()
+                .expect("not Off");
+            let message: String = env.get_string(message).expect("valid message").into();
+            log::log!(level, "{}", message);
+        });
+    }
+
+    /// Logs a fixed message with key-value pairs, to test structured logging.
+    #[no_mangle]
+    pub unsafe extern "C" fn Java_org_signal_client_internal_NativeTesting_TESTING_1LogKeyValues(
+        _env: JNIEnv,
+        _class: JClass,
+    ) {
+        abort_on_panic(|| {
+            let key_values = [("count", "2"), ("name", "value with spaces")];
+            log::logger().log(
+                &log::Record::builder()
+                    .level(log::Level::Info)
+                    .target("libsignal_jni::logging")
+                    .args(format_args!("structured logging test"))
+                    .file(Some(file!()))
+                    .line(Some(line!()))
+                    .key_values(&key_values)
+                    .build(),
+            );
+        });
+    }
+
+    /// Logs `count` records with the given target through a new logger restricted to
+    /// `target_prefixes`, returning the elapsed time in nanoseconds.
+    ///
+    /// Used to benchmark filtering. The installed logger and its configuration are not affected.
+    #[no_mangle]
+    pub unsafe extern "C" fn Java_org_signal_client_internal_NativeTesting_TESTING_1BenchmarkLogFilter(
+        env: JNIEnv,
+        _class: JClass,
+        logger_class: JClass,
+        target_prefixes: JavaStringArray,
+        target: JString,
+        count: jint,
+    ) -> jlong {
+        abort_on_panic(|| {
+            let target_prefixes =
+                read_string_array(&env, target_prefixes).expect("could not read target prefixes");
+            let target: String = env.get_string(target).expect("valid target").into();
+            let logger = JniLogger::new(env, logger_class, target_prefixes, 0)
+                .expect("could not create logger");
+
+            let start = Instant::now();
+            for i in 0..count {
+                log::Log::log(
+                    &logger,
+                    &log::Record::builder()
+                        .level(log::Level::Info)
+                        .target(&target)
+                        .args(format_args!("benchmark message {}", i))
+                        .file(Some(file!()))
+                        .line(Some(line!()))
+                        .build(),
+                );
+            }
+            start.elapsed().as_nanos() as jlong
+        })
+    }
+
+    /// Logs `count` structured records through a new logger, returning the elapsed time in
+    /// nanoseconds.
+    ///
+    /// Used to measure how much the logger's [`StringPool`] saves; if `pooled` is false, every
+    /// string is allocated fresh instead.
+    #[no_mangle]
+    pub unsafe extern "C" fn Java_org_signal_client_internal_NativeTesting_TESTING_1BenchmarkLogStrings(
+        env: JNIEnv,
+        _class: JClass,
+        logger_class: JClass,
+        pooled: jboolean,
+        count: jint,
+    ) -> jlong {
+        abort_on_panic(|| {
+            let mut logger =
+                JniLogger::new(env, logger_class, None, 0).expect("could not create logger");
+            if pooled == 0 {
+                logger.strings = StringPool::new(0);
+            }
+
+            let start = Instant::now();
+            for i in 0..count {
+                let index = i.to_string();
+                let key_values = [("index", index.as_str()), ("kind", "benchmark")];
+                log::Log::log(
+                    &logger,
+                    &log::Record::builder()
+                        .level(log::Level::Info)
+                        .target("libsignal_jni::logging")
+                        .args(format_args!("benchmark message"))
+                        .file(Some(file!()))
+                        .line(Some(line!()))
+                        .key_values(&key_values)
+                        .build(),
+                );
+            }
+            start.elapsed().as_nanos() as jlong
+        })
+    }
 }
@@ -0,0 +1,44 @@
+//
+// Copyright 2021 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+//! Hand-written `TESTING_` entry points, only built with the "testing" feature.
+
+use jni::objects::JClass;
+use jni::sys::{jboolean, jbyteArray, jint, jlong};
+use jni::JNIEnv;
+use std::time::Instant;
+
+use libsignal_bridge::jni::*;
+
+/// Loads `data` as a `&[u8]` argument `count` times, either pinned or through
+/// `GetByteArrayElements`, returning the elapsed time in nanoseconds.
+///
+/// Used to benchmark [`JniPinnedSlice`]. Note that arrays no longer than
+/// [`BYTE_ARRAY_PIN_THRESHOLD`] are never pinned.
+#[no_mangle]
+pub unsafe extern "C" fn Java_org_signal_client_internal_NativeTesting_TESTING_1BenchmarkByteArrayArg(
+    env: JNIEnv,
+    _class: JClass,
+    data: jbyteArray,
+    pinned: jboolean,
+    count: jint,
+) -> jlong {
+    run_ffi_safe(&env, || {
+        let start = Instant::now();
+        for _ in 0..count {
+            let mut stored = <&[u8]>::borrow(&env, data)?;
+            let slice = if pinned != 0 {
+                <&[u8]>::load_from_pinned(&env, &mut stored)?
+            } else {
+                <&[u8]>::load_from(&env, &mut stored)?
+            };
+            // Make sure the contents are actually read.
+            if let Some(first) = slice.first() {
+                std::ptr::read_volatile(first);
+            }
+        }
+        Ok(start.elapsed().as_nanos() as jlong)
+    })
+}
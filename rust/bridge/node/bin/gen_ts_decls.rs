@@ -0,0 +1,359 @@
+//
+// Copyright 2020-2021 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+//! Generates the TypeScript declarations for the Node bridge.
+//!
+//! Usage: `cargo run --bin gen-ts-decls [-- --verify]`
+//!
+//! Declarations are collected from the `ts:` doc comments left by `bridge_fn` and friends (as well
+//! as any written by hand) by expanding the bridge crates' macros. Doc comments directly above an
+//! annotation, like those copied by `bridge_fn(forward_docs = true)`, become the declaration's
+//! JSDoc comment. With `--verify`, nothing is written; instead, the tool exits with an error if any
+//! checked-in file is out of date.
+
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{exit, Command};
+
+/// Each TypeScript module that declarations can be placed in with `ts_module`, and the file (in
+/// the repository's `node` directory) that its declarations are written to.
+///
+/// Each file starts with the contents of `bin/<file name>.in`. `Testing` holds the `TESTING_`
+/// functions, which only the tests can use.
+const MODULES: &[(&str, &str)] = &[
+    ("Native", "libsignal_client.d.ts"),
+    ("Testing", "test/NativeTesting.d.ts"),
+];
+
+/// The module for declarations that don't specify one; see `bridge_fn`'s `ts_module`.
+const DEFAULT_MODULE: &str = "Native";
+
+fn split_once<'a>(s: &'a str, separator: &str) -> Option<(&'a str, &'a str)> {
+    let index = s.find(separator)?;
+    Some((&s[..index], &s[index + separator.len()..]))
+}
+
+/// Extracts the text of a doc comment from a line of macro-expanded Rust, along with whether it
+/// is still an escaped string literal.
+///
+/// Doc comments appear either as `/// ...` or as doc attributes (`#[doc = "..."]`); the attribute
+/// is sometimes wrapped onto two lines, so the `#[doc =` part is optional.
+fn split_doc(line: &str) -> Option<(&str, bool)> {
+    let line = line.trim();
+    match line.strip_prefix("///") {
+        Some(comment) => Some((comment, false)),
+        None => {
+            let attr = line.strip_prefix("#[doc").unwrap_or(line).trim_start();
+            let attr = attr.strip_prefix('=').unwrap_or(attr).trim_start();
+            Some((attr.strip_prefix('"')?.strip_suffix("\"]")?, true))
+        }
+    }
+}
+
+/// Whether `line` is the first half of a doc attribute wrapped onto two lines.
+fn is_wrapped_doc_start(line: &str) -> bool {
+    line.trim() == "#[doc ="
+}
+
+/// Extracts the text of a doc comment from a line of macro-expanded Rust (see [`split_doc`]).
+fn parse_doc(line: &str) -> Option<Cow<'_, str>> {
+    let (text, is_literal) = split_doc(line)?;
+    if !is_literal || !text.contains('\\') {
+        return Some(Cow::Borrowed(text));
+    }
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => unescaped.push('\n'),
+            Some('t') => unescaped.push('\t'),
+            Some(escaped) => unescaped.push(escaped),
+            None => unescaped.push('\\'),
+        }
+    }
+    Some(Cow::Owned(unescaped))
+}
+
+/// Extracts the module and declaration from a line of macro-expanded Rust, if it has a `ts:`
+/// annotation.
+fn parse_annotation(line: &str) -> Option<(&str, &str)> {
+    let (annotation, _) = split_doc(line)?;
+    let annotation = annotation.trim_start();
+
+    if let Some(decl) = annotation.strip_prefix("ts: ") {
+        return Some((DEFAULT_MODULE, decl));
+    }
+    let (module, decl) = split_once(annotation.strip_prefix("ts(")?, "): ")?;
+    Some((module, decl))
+}
+
+/// Terminates a function declaration with a semicolon.
+///
+/// The `ts:` annotations already use TypeScript types (see `ts_type` in the bridge macros), so
+/// anything else (like a class declaration) is passed through unchanged.
+fn finish_decl(decl: &str) -> String {
+    if decl.starts_with("export function ") && !decl.ends_with(';') {
+        format!("{};", decl)
+    } else {
+        decl.to_string()
+    }
+}
+
+/// Formats a declaration, preceded by its doc comment lines (if any) as a JSDoc block.
+fn format_decl(decl: &str, docs: &[String]) -> String {
+    if docs.is_empty() {
+        return decl.to_string();
+    }
+    let mut result = String::from("/**\n");
+    for line in docs {
+        // Rust doc comments usually start with a space after the `///`.
+        let line = line.strip_prefix(' ').unwrap_or(line).replace("*/", "*\\/");
+        if line.trim().is_empty() {
+            result.push_str(" *\n");
+        } else {
+            result.push_str(&format!(" * {}\n", line));
+        }
+    }
+    result.push_str(" */\n");
+    result.push_str(decl);
+    result
+}
+
+/// A declaration and the doc comment lines found directly above its annotation.
+type Decl = (String, Vec<String>);
+
+/// Expands the macros in the crate at `crate_dir` and collects its declarations by module.
+fn collect_decls(
+    crate_dir: &Path,
+    features: &str,
+    decls: &mut BTreeMap<String, Vec<Decl>>,
+) -> Result<(), String> {
+    let output = Command::new(std::env::var_os("CARGO").unwrap_or_else(|| "cargo".into()))
+        .current_dir(crate_dir)
+        .args(&[
+            "rustc",
+            "-q",
+            "--profile=check",
+            "--features",
+            features,
+            "--message-format=short",
+            "--",
+            "-Zunstable-options",
+            "--pretty=expanded",
+        ])
+        .output()
+        .map_err(|e| format!("failed to run cargo: {}", e))?;
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let errors: Vec<_> = stderr
+        .lines()
+        .filter(|line| !line.is_empty())
+        .filter(|line| !(line.starts_with("warning: ") && line.ends_with(" emitted")))
+        .collect();
+    if !errors.is_empty() || !output.status.success() {
+        return Err(errors.join("\n"));
+    }
+
+    let mut docs = vec![];
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        if let Some((module, decl)) = parse_annotation(line) {
+            decls
+                .entry(module.to_string())
+                .or_default()
+                .push((finish_decl(decl), std::mem::take(&mut docs)));
+        } else if let Some(doc) = parse_doc(line) {
+            docs.push(doc.into_owned());
+        } else if !is_wrapped_doc_start(line) {
+            docs.clear();
+        }
+    }
+    Ok(())
+}
+
+fn main() {
+    let verify = match std::env::args().nth(1).as_deref() {
+        None => false,
+        Some("--verify") if std::env::args().len() == 2 => true,
+        _ => {
+            eprintln!("usage: gen-ts-decls [--verify]");
+            exit(2);
+        }
+    };
+
+    let our_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let output_dir = our_dir.join("../../../node");
+
+    let mut decls = BTreeMap::new();
+    for (crate_dir, features) in &[
+        (our_dir.clone(), "testing"),
+        (our_dir.join("../shared"), "node,testing"),
+    ] {
+        if let Err(e) = collect_decls(crate_dir, features, &mut decls) {
+            eprintln!("{}", e);
+            eprintln!("Exiting with error");
+            exit(1);
+        }
+    }
+
+    if let Some(unknown) = decls
+        .keys()
+        .find(|module| !MODULES.iter().any(|(known, _)| known == module))
+    {
+        eprintln!(
+            "error: unknown TypeScript module {} (add it to MODULES in {})",
+            unknown,
+            file!()
+        );
+        exit(1);
+    }
+
+    let mut out_of_date = false;
+    for (module, file_name) in MODULES {
+        let template_name = Path::new(file_name)
+            .file_name()
+            .expect("module files have names")
+            .to_string_lossy();
+        let template_path = our_dir.join("bin").join(format!("{}.in", template_name));
+        let mut contents = fs::read_to_string(&template_path).unwrap_or_else(|e| {
+            eprintln!("error: cannot read {}: {}", template_path.display(), e);
+            exit(1);
+        });
+        let mut module_decls = decls.remove(*module).unwrap_or_default();
+        module_decls.sort();
+        let module_decls: Vec<_> = module_decls
+            .iter()
+            .map(|(decl, docs)| format_decl(decl, docs))
+            .collect();
+        contents.push('\n');
+        contents.push_str(&module_decls.join("\n"));
+        contents.push('\n');
+
+        let output_path = output_dir.join(file_name);
+        if !output_path.exists() {
+            eprintln!("error: didn't find {} where it was expected", file_name);
+            exit(1);
+        }
+
+        if verify {
+            let current_contents = fs::read_to_string(&output_path).unwrap_or_default();
+            if current_contents != contents {
+                report_first_difference(&current_contents, &contents);
+                eprintln!(
+                    "error: {} not up to date; re-run `cargo run --bin gen-ts-decls`!",
+                    file_name
+                );
+                out_of_date = true;
+            }
+        } else if let Err(e) = fs::write(&output_path, contents) {
+            eprintln!("error: cannot write {}: {}", output_path.display(), e);
+            exit(1);
+        }
+    }
+
+    if out_of_date {
+        exit(1);
+    }
+}
+
+fn report_first_difference(current: &str, expected: &str) {
+    let mut current_lines = current.lines();
+    let mut expected_lines = expected.lines();
+    for line_number in 1.. {
+        match (current_lines.next(), expected_lines.next()) {
+            (None, None) => break,
+            (current, expected) if current == expected => continue,
+            (current, expected) => {
+                eprintln!("first difference at line {}:", line_number);
+                eprintln!("- {}", current.unwrap_or("<end of file>"));
+                eprintln!("+ {}", expected.unwrap_or("<end of file>"));
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_finish_decl() {
+        assert_eq!(
+            finish_decl(
+                "export function Foo_Split(foo: Wrapper<Foo>): { data: Buffer, count: number }"
+            ),
+            "export function Foo_Split(foo: Wrapper<Foo>): { data: Buffer, count: number };"
+        );
+        assert_eq!(
+            finish_decl("export function Foo_New(): Foo;"),
+            "export function Foo_New(): Foo;"
+        );
+        assert_eq!(
+            finish_decl("export class Foo { readonly __type: unique symbol; }"),
+            "export class Foo { readonly __type: unique symbol; }"
+        );
+    }
+
+    #[test]
+    fn test_parse_annotation() {
+        assert_eq!(
+            parse_annotation("    /// ts: export function Foo(): void"),
+            Some(("Native", "export function Foo(): void"))
+        );
+        assert_eq!(
+            parse_annotation(r#"    #[doc = "ts: export function Foo(): void"]"#),
+            Some(("Native", "export function Foo(): void"))
+        );
+        assert_eq!(
+            parse_annotation(r#"        "ts(Testing): export function Foo(): void"]"#),
+            Some(("Testing", "export function Foo(): void"))
+        );
+        assert_eq!(parse_annotation("/// Some other documentation."), None);
+        assert_eq!(
+            parse_annotation(r#"#[doc = "Some other documentation."]"#),
+            None
+        );
+    }
+
+    #[test]
+    fn test_parse_doc() {
+        assert_eq!(
+            parse_doc("    /// Some documentation.").as_deref(),
+            Some(" Some documentation.")
+        );
+        assert_eq!(
+            parse_doc(r#"    #[doc = " Says \"hi\" \\o/"]"#).as_deref(),
+            Some(r#" Says "hi" \o/"#)
+        );
+        assert_eq!(parse_doc(r#"#[doc = ""]"#).as_deref(), Some(""));
+        assert_eq!(parse_doc("#[allow(non_snake_case)]"), None);
+        assert!(is_wrapped_doc_start("    #[doc ="));
+    }
+
+    #[test]
+    fn test_format_decl() {
+        assert_eq!(
+            format_decl("export function Foo(): void;", &[]),
+            "export function Foo(): void;"
+        );
+        assert_eq!(
+            format_decl(
+                "export function Foo(): void;",
+                &[
+                    " Does foo.".to_string(),
+                    "".to_string(),
+                    " Never returns `*/`.".to_string()
+                ]
+            ),
+            "/**\n * Does foo.\n *\n * Never returns `*\\/`.\n */\nexport function Foo(): void;"
+        );
+    }
+}
@@ -0,0 +1,165 @@
+//
+// Copyright 2021 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+use neon::prelude::*;
+use std::future::Future;
+use std::panic::UnwindSafe;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{self, Poll, Waker};
+
+use crate::*;
+
+const CANCEL_SLOT: &str = "cancel";
+
+#[derive(Default)]
+struct CancellationState {
+    cancelled: AtomicBool,
+    wakers: Mutex<Vec<Waker>>,
+}
+
+/// Lets a Rust future find out that the JavaScript promise it's producing is no longer wanted.
+///
+/// Tokens are cheap to clone; all clones share the same state. Cancellation is advisory: a future
+/// that never checks its token will run to completion as usual.
+///
+/// See [cancellable_promise()].
+#[derive(Clone, Default)]
+pub struct CancellationToken(Arc<CancellationState>);
+
+impl CancellationToken {
+    /// Creates a new token that has not been cancelled.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns `true` once [cancel](Self::cancel) has been called on this token or any of its clones.
+    pub fn is_cancelled(&self) -> bool {
+        self.0.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Marks the token as cancelled, and wakes up any futures that are waiting on it.
+    pub fn cancel(&self) {
+        self.0.cancelled.store(true, Ordering::SeqCst);
+        let wakers = std::mem::take(&mut *self.0.wakers.lock().expect("not poisoned"));
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+
+    /// Produces a future that completes when the token is cancelled.
+    pub fn cancelled(&self) -> Cancelled {
+        Cancelled(self.clone())
+    }
+
+    fn register(&self, waker: &Waker) {
+        let mut wakers = self.0.wakers.lock().expect("not poisoned");
+        if !wakers.iter().any(|existing| existing.will_wake(waker)) {
+            wakers.push(waker.clone());
+        }
+    }
+}
+
+/// A future that completes when a [CancellationToken] is cancelled.
+///
+/// Produced by [CancellationToken::cancelled].
+pub struct Cancelled(CancellationToken);
+
+impl Future for Cancelled {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut task::Context) -> Poll<()> {
+        if self.0.is_cancelled() {
+            return Poll::Ready(());
+        }
+        self.0.register(cx.waker());
+        // Check again in case the token was cancelled before the waker was registered.
+        if self.0.is_cancelled() {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+/// Wraps a future so that it gets polled again as soon as its token is cancelled.
+struct WakeOnCancel<Fut> {
+    token: CancellationToken,
+    future: Pin<Box<Fut>>,
+}
+
+impl<Fut: Future> Future for WakeOnCancel<Fut> {
+    type Output = Fut::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut task::Context) -> Poll<Fut::Output> {
+        if !self.token.is_cancelled() {
+            self.token.register(cx.waker());
+        }
+        self.future.as_mut().poll(cx)
+    }
+}
+
+/// A [CancellationToken] held by the JavaScript `cancel()` function.
+struct BoxedToken(CancellationToken);
+
+impl Finalize for BoxedToken {}
+
+/// A JavaScript-compatible function that cancels the token passed as its first (bound) argument.
+fn cancel_promise(mut cx: FunctionContext) -> JsResult<JsUndefined> {
+    let token = cx.argument::<JsBox<BoxedToken>>(0)?;
+    token.0.cancel();
+    Ok(cx.undefined())
+}
+
+/// Like [promise()], but the resulting Promise has a `cancel()` method that signals the future.
+///
+/// `make_future` is passed a [CancellationToken] to check or wait on. Calling `cancel()` on the
+/// Promise cancels the token and polls the future again (on the microtask queue, like any other
+/// wakeup), so a future waiting on [CancellationToken::cancelled] sees the signal right away.
+///
+/// Cancellation does not settle the Promise by itself. The future decides what to do: usually
+/// it settles with an error, but it may also finish its work and settle normally.
+///
+/// ```no_run
+/// # use neon::prelude::*;
+/// # use signal_neon_futures::*;
+/// #
+/// fn js_wait_for_cancellation(mut cx: FunctionContext) -> JsResult<JsObject> {
+///     cancellable_promise(&mut cx, |token| async move {
+///         token.cancelled().await;
+///         settle_promise(|cx| Ok(cx.string("cancelled")))
+///     })
+/// }
+/// ```
+pub fn cancellable_promise<'a, V, F, Fut>(
+    cx: &mut FunctionContext<'a>,
+    make_future: impl FnOnce(CancellationToken) -> Fut,
+) -> JsResult<'a, JsObject>
+where
+    V: neon::types::Value,
+    F: for<'b> FnOnce(&mut TaskContext<'b>) -> JsResult<'b, V> + Send + UnwindSafe + 'static,
+    Fut: Future<Output = Result<F, PersistentException>> + UnwindSafe + 'static,
+{
+    let token = CancellationToken::new();
+    let future = WakeOnCancel {
+        token: token.clone(),
+        future: Box::pin(make_future(token.clone())),
+    };
+    let promise = promise(cx, future)?;
+
+    let cancel_fn = JsFunction::new(cx, cancel_promise)?;
+    let undefined = cx.undefined();
+    let boxed_token = cx.boxed(BoxedToken(token));
+    let bound_cancel_fn = call_method(
+        cx,
+        cancel_fn,
+        "bind",
+        vec![undefined.upcast(), boxed_token.upcast()],
+    )?;
+    promise.set(cx, CANCEL_SLOT, bound_cancel_fn)?;
+
+    Ok(promise)
+}
@@ -25,6 +25,9 @@
 mod executor;
 pub use executor::{ContextEx, EventQueueEx};
 
+mod cancellation;
+pub use cancellation::{cancellable_promise, CancellationToken, Cancelled};
+
 mod exception;
 pub use exception::PersistentException;
 
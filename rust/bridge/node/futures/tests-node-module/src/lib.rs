@@ -64,9 +64,18 @@ fn increment_promise(mut cx: FunctionContext) -> JsResult<JsObject> {
     })
 }
 
+// function waitForCancellation(): Promise<string> & { cancel(): void }
+fn wait_for_cancellation(mut cx: FunctionContext) -> JsResult<JsObject> {
+    cancellable_promise(&mut cx, |token| async move {
+        token.cancelled().await;
+        settle_promise(|cx| Ok(cx.string("cancelled")))
+    })
+}
+
 register_module!(mut cx, {
     cx.export_function("incrementAsync", increment_async)?;
     cx.export_function("incrementPromise", increment_promise)?;
+    cx.export_function("waitForCancellation", wait_for_cancellation)?;
 
     cx.export_function("doubleNameFromStore", double_name_from_store)?;
     cx.export_function(
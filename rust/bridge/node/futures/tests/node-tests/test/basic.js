@@ -97,6 +97,25 @@ describe('native', () => {
     });
   });
 
+  describe('cancellation', () => {
+    it('signals the future when cancelled', async () => {
+      const promise = native.waitForCancellation();
+      promise.cancel();
+      const result = await Promise.race([
+        promise,
+        new Promise(resolve => setImmediate(() => resolve('not cancelled'))),
+      ]);
+      assert.equal(result, 'cancelled');
+    });
+
+    it('can be cancelled more than once', async () => {
+      const promise = native.waitForCancellation();
+      promise.cancel();
+      promise.cancel();
+      assert.equal(await promise, 'cancelled');
+    });
+  });
+
   describe('panic recovery', () => {
     it('handles pre-await panics', async () => {
       const promise = native.panicPreAwait(Promise.resolve(6));
@@ -4,35 +4,95 @@
 //
 
 use heck::SnakeCase;
-use proc_macro2::TokenStream as TokenStream2;
+use proc_macro2::{TokenStream as TokenStream2, TokenTree};
 use quote::*;
 use syn::spanned::Spanned;
 use syn::*;
-use syn_mid::{FnArg, Pat, PatType, Signature};
+use syn_mid::{FnArg, PatType, Signature};
 use unzip3::Unzip3;
 
-use crate::ResultKind;
+use crate::{
+    box_bytearray_result, buffer_result_shape, bytearray_result_shape, generic_arg_if_named,
+    has_env_param, param_ident, tuple_result_elements, ResultKind,
+};
 
-pub(crate) fn bridge_fn(name: String, sig: &Signature, result_kind: ResultKind) -> TokenStream2 {
+pub(crate) fn bridge_fn(
+    name: String,
+    sig: &Signature,
+    result_kind: ResultKind,
+    ffi_async: bool,
+) -> TokenStream2 {
     let name = format_ident!("signal_{}", name);
 
-    let (output_args, env_arg, output_processing) = match (result_kind, &sig.output) {
-        (ResultKind::Regular, ReturnType::Default) => (quote!(), quote!(), quote!()),
+    if ffi_async {
+        return bridge_fn_async(name, sig, result_kind);
+    }
+
+    let (output_args, output_processing) = match (result_kind, &sig.output) {
+        (ResultKind::Regular, ReturnType::Default) => (quote!(), quote!()),
+        (ResultKind::Regular, ReturnType::Type(_, ref ty)) if is_buffer_list(ty) => (
+            quote!(
+                out: *mut *const libc::c_uchar,
+                out_len: *mut libc::size_t,
+                out_count: *mut libc::size_t, // note the trailing comma
+            ),
+            quote!(ffi::write_bytearray_list_to(
+                out, out_len, out_count, __result
+            )?),
+        ),
+        (ResultKind::Regular, ReturnType::Type(_, ref ty))
+            if bytearray_result_shape(ty).is_some() =>
+        {
+            let shape = bytearray_result_shape(ty).expect("just checked");
+            let box_result = box_bytearray_result(shape);
+            (
+                quote!(
+                    out: *mut *const libc::c_uchar,
+                    out_len: *mut libc::size_t, // note the trailing comma
+                ),
+                bytearray_output_processing(shape, box_result),
+            )
+        }
+        (ResultKind::Regular, ReturnType::Type(_, ref ty)) if handle_list_element(ty).is_some() => {
+            let elem = handle_list_element(ty).expect("just checked");
+            (
+                quote!(
+                    out: *mut *mut ffi_result_type!(#elem),
+                    out_count: *mut libc::size_t, // note the trailing comma
+                ),
+                quote!(ffi::write_handle_list_to(out, out_count, __result)?),
+            )
+        }
+        (ResultKind::Regular, ReturnType::Type(_, ref ty))
+            if tuple_result_elements(ty).is_some() =>
+        {
+            let (is_result, elements) = tuple_result_elements(ty).expect("just checked");
+            tuple_output(is_result, &elements)
+        }
         (ResultKind::Regular, ReturnType::Type(_, ref ty)) => (
             quote!(out: *mut ffi_result_type!(#ty),), // note the trailing comma
-            quote!(),
             quote!(ffi::write_result_to(out, __result)?),
         ),
-        (ResultKind::Void, ReturnType::Default) => (quote!(), quote!(), quote!()),
-        (ResultKind::Void, ReturnType::Type(_, _)) => (quote!(), quote!(), quote!(__result?;)),
-        (ResultKind::Buffer, ReturnType::Type(_, _)) => (
+        (ResultKind::Void, ReturnType::Default) => (quote!(), quote!()),
+        (ResultKind::Void, ReturnType::Type(_, _)) => (quote!(), quote!(__result?;)),
+        (ResultKind::Buffer, ReturnType::Type(_, ref ty)) => (
             quote!(
                 out: *mut *const libc::c_uchar,
                 out_len: *mut libc::size_t, // note the trailing comma
             ),
-            quote!(ffi::Env,), // note the trailing comma
-            quote!(ffi::write_bytearray_to(out, out_len, __result?)?),
+            bytearray_output_processing(buffer_result_shape(ty), quote!()),
         ),
+        (ResultKind::Bool, ReturnType::Type(_, _)) => (
+            quote!(out: *mut bool,), // note the trailing comma
+            quote!(ffi::write_result_to(out, __result)?),
+        ),
+        (ResultKind::Bool, ReturnType::Default) => {
+            return Error::new(
+                sig.paren_token.span,
+                "missing result type for bridge_fn_bool",
+            )
+            .to_compile_error()
+        }
         (ResultKind::Buffer, ReturnType::Default) => {
             return Error::new(
                 sig.paren_token.span,
@@ -48,11 +108,153 @@ pub(crate) fn bridge_fn(name: String, sig: &Signature, result_kind: ResultKind)
         }
     });
 
-    let (input_names, input_args, input_processing): (Vec<_>, Vec<_>, Vec<_>) = sig
-        .inputs
+    let env_arg = env_arg(sig);
+    let (input_names, input_args, input_processing) = bridge_fn_inputs(sig);
+
+    let orig_name = sig.ident.clone();
+    let arity = count_params(quote!(#output_args #(#input_args),*));
+
+    quote! {
+        #[no_mangle]
+        pub unsafe extern "C" fn #name(
+            #output_args
+            #(#input_args),*
+        ) -> *mut ffi::SignalFfiError {
+            ffi::run_ffi_safe(|| {
+                #(#input_processing);*;
+                let __result = #orig_name(#env_arg #(#input_names),*);
+                #await_if_needed;
+                #output_processing;
+                Ok(())
+            })
+        }
+
+        ffi_register!(#name, #arity);
+    }
+}
+
+/// Generates an entry point for `bridge_fn(ffi_async = true)`, which reports its result through a
+/// completion callback instead of output parameters.
+fn bridge_fn_async(name: Ident, sig: &Signature, result_kind: ResultKind) -> TokenStream2 {
+    if sig.asyncness.is_none() {
+        return Error::new(sig.fn_token.span, "ffi_async requires an async function")
+            .to_compile_error();
+    }
+
+    let (callback_type, run_fn) = match (result_kind, &sig.output) {
+        (ResultKind::Regular, ReturnType::Default) | (ResultKind::Void, _) => (
+            quote!(extern "C" fn(*mut ffi::SignalFfiError, *mut libc::c_void)),
+            quote!(ffi::run_void_future_with_callback),
+        ),
+        (ResultKind::Regular, ReturnType::Type(_, ty))
+            if is_buffer_list(ty)
+                || bytearray_result_shape(ty).is_some()
+                || handle_list_element(ty).is_some()
+                || tuple_result_elements(ty).is_some() =>
+        {
+            return Error::new(
+                ty.span(),
+                "ffi_async does not support results with multiple output parameters",
+            )
+            .to_compile_error();
+        }
+        (ResultKind::Regular, ReturnType::Type(_, ty)) => (
+            quote!(
+                extern "C" fn(*mut ffi::SignalFfiError, ffi_result_type!(#ty), *mut libc::c_void)
+            ),
+            quote!(ffi::run_future_with_callback),
+        ),
+        (ResultKind::Bool, _) => (
+            quote!(extern "C" fn(*mut ffi::SignalFfiError, bool, *mut libc::c_void)),
+            quote!(ffi::run_future_with_callback),
+        ),
+        (ResultKind::Buffer, _) => {
+            return Error::new(
+                sig.fn_token.span,
+                "ffi_async is not supported for E::Buffer results",
+            )
+            .to_compile_error();
+        }
+    };
+
+    let env_arg = env_arg(sig);
+    let (input_names, input_args, input_processing) = bridge_fn_inputs(sig);
+
+    let orig_name = sig.ident.clone();
+    // The completion callback and its context come first.
+    let arity = 2 + count_params(quote!(#(#input_args),*));
+
+    quote! {
+        #[no_mangle]
+        pub unsafe extern "C" fn #name(
+            done: #callback_type,
+            ctx: *mut libc::c_void,
+            #(#input_args),*
+        ) {
+            #run_fn(done, ctx, || {
+                #(#input_processing);*;
+                Ok(#orig_name(#env_arg #(#input_names),*))
+            })
+        }
+
+        ffi_register!(#name, #arity);
+    }
+}
+
+/// Counts the parameters in a comma-separated list of C parameters, as generated for an entry point.
+///
+/// Commas inside groups (like a callback's parameter list) or angle brackets don't separate
+/// parameters.
+fn count_params(params: TokenStream2) -> usize {
+    let mut count = 0;
+    let mut depth = 0;
+    let mut in_param = false;
+    let mut after_dash = false;
+    for token in params {
+        let is_dash = matches!(&token, TokenTree::Punct(p) if p.as_char() == '-');
+        match &token {
+            TokenTree::Punct(p) if p.as_char() == ',' && depth == 0 => {
+                if in_param {
+                    count += 1;
+                }
+                in_param = false;
+            }
+            TokenTree::Punct(p) if p.as_char() == '<' => {
+                depth += 1;
+                in_param = true;
+            }
+            // Skip the `>` in a `->`.
+            TokenTree::Punct(p) if p.as_char() == '>' && !after_dash => {
+                depth -= 1;
+                in_param = true;
+            }
+            _ => in_param = true,
+        }
+        after_dash = is_dash;
+    }
+    if in_param {
+        count += 1;
+    }
+    count
+}
+
+/// Generates the `Env` argument for the function, if it takes one.
+fn env_arg(sig: &Signature) -> TokenStream2 {
+    if has_env_param(sig) {
+        quote!(ffi::Env,) // note the trailing comma
+    } else {
+        quote!()
+    }
+}
+
+/// Generates the C parameters for each of the function's arguments, along with the code to
+/// convert them to their Rust types.
+fn bridge_fn_inputs(sig: &Signature) -> (Vec<Ident>, Vec<TokenStream2>, Vec<TokenStream2>) {
+    sig.inputs
         .iter()
-        .skip(if result_kind.has_env() { 1 } else { 0 })
-        .map(|arg| match arg {
+        .enumerate()
+        .skip(if has_env_param(sig) { 1 } else { 0 })
+        .map(|(index, arg)| match arg {
             FnArg::Receiver(tokens) => (
                 Ident::new("self", tokens.self_token.span),
                 Error::new(tokens.self_token.span, "cannot have 'self' parameter")
@@ -61,68 +263,277 @@ pub(crate) fn bridge_fn(name: String, sig: &Signature, result_kind: ResultKind)
             ),
             FnArg::Typed(PatType {
                 attrs,
-                pat: box Pat::Ident(name),
+                pat,
                 colon_token,
-                ty:
-                    ty
-                    @
-                    box Type::Reference(TypeReference {
-                        elem: box Type::Slice(_),
-                        ..
-                    }),
-            }) => {
-                let size_arg = format_ident!("{}_len", name.ident);
-                (
-                    name.ident.clone(),
-                    quote!(
-                        #(#attrs)* #name #colon_token ffi_arg_type!(#ty),
-                        #size_arg: libc::size_t
-                    ),
+                ty,
+            }) => match param_ident(pat, index) {
+                Some(name) if is_sized_arg(ty) => {
+                    let size_arg = format_ident!("{}_len", name);
+                    (
+                        name.clone(),
+                        quote!(
+                            #(#attrs)* #name #colon_token ffi_arg_type!(#ty),
+                            #size_arg: libc::size_t
+                        ),
+                        quote!(
+                            let #name = <#ty as ffi::SizedArgTypeInfo>::convert_from(#name, #size_arg)?
+                        ),
+                    )
+                }
+                Some(name) if is_callback_arg(ty) => (
+                    name.clone(),
+                    match &**ty {
+                        Type::BareFn(TypeBareFn { abi: None, .. }) => Error::new(
+                            ty.span(),
+                            "callbacks must be declared extern \"C\" to be called from C",
+                        )
+                        .to_compile_error(),
+                        _ => quote!(#(#attrs)* #name #colon_token Option<#ty>),
+                    },
                     quote!(
-                        let #name = <#ty as ffi::SizedArgTypeInfo>::convert_from(#name, #size_arg)?
+                        let #name =
+                            <ffi::FfiCallback<#ty> as ffi::SimpleArgTypeInfo>::convert_from(#name)?
+                                .get()
                     ),
-                )
+                ),
+                Some(name) => (
+                    name.clone(),
+                    quote!(#(#attrs)* #name #colon_token ffi_arg_type!(#ty)),
+                    quote! {
+                        let mut #name = <#ty as ffi::ArgTypeInfo>::borrow(#name)?;
+                        let #name = <#ty as ffi::ArgTypeInfo>::load_from(&mut #name)?
+                    },
+                ),
+                None => (
+                    Ident::new("unexpected", pat.span()),
+                    Error::new(pat.span(), "cannot use patterns in paramater").to_compile_error(),
+                    quote!(),
+                ),
+            },
+        })
+        .unzip3()
+}
+
+/// Generates code to write a buffer `__result` with the given [`buffer_result_shape`] to the `out`
+/// and `out_len` parameters, after running `conversion`.
+fn bytearray_output_processing(
+    (is_result, is_optional): (bool, bool),
+    conversion: TokenStream2,
+) -> TokenStream2 {
+    let value = if is_result {
+        quote!(__result?)
+    } else {
+        quote!(__result)
+    };
+    let write_fn = if is_optional {
+        quote!(ffi::write_optional_bytearray_to)
+    } else {
+        quote!(ffi::write_bytearray_to)
+    };
+    quote! {
+        #conversion
+        #write_fn(out, out_len, #value)?
+    }
+}
+
+/// Generates one output parameter per element of a tuple `__result` (a pointer/length pair for
+/// buffers), along with the code to fill them in.
+///
+/// `is_result` and `elements` come from [`tuple_result_elements`].
+fn tuple_output(is_result: bool, elements: &[&Type]) -> (TokenStream2, TokenStream2) {
+    let value = if is_result {
+        quote!(__result?)
+    } else {
+        quote!(__result)
+    };
+    let names: Vec<_> = (0..elements.len())
+        .map(|i| format_ident!("__result_{}", i))
+        .collect();
+
+    let (output_args, output_writes): (Vec<_>, Vec<_>) = elements
+        .iter()
+        .zip(&names)
+        .zip(0usize..)
+        .map(|((ty, name), i)| {
+            let out = format_ident!("out_{}", i);
+            match bytearray_result_shape(ty) {
+                Some((_, is_optional)) => {
+                    let out_len = format_ident!("out_{}_len", i);
+                    let write_fn = if is_optional {
+                        quote!(ffi::write_optional_bytearray_to)
+                    } else {
+                        quote!(ffi::write_bytearray_to)
+                    };
+                    (
+                        quote!(
+                            #out: *mut *const libc::c_uchar,
+                            #out_len: *mut libc::size_t, // note the trailing comma
+                        ),
+                        quote!(#write_fn(#out, #out_len, #name)?),
+                    )
+                }
+                None => (
+                    quote!(#out: *mut ffi_result_type!(#ty),), // note the trailing comma
+                    quote!(ffi::write_result_to(#out, #name)?),
+                ),
             }
-            FnArg::Typed(PatType {
-                attrs,
-                pat: box Pat::Ident(name),
-                colon_token,
-                ty,
-            }) => (
-                name.ident.clone(),
-                quote!(#(#attrs)* #name #colon_token ffi_arg_type!(#ty)),
-                quote! {
-                    let mut #name = <#ty as ffi::ArgTypeInfo>::borrow(#name)?;
-                    let #name = <#ty as ffi::ArgTypeInfo>::load_from(&mut #name)?
-                },
-            ),
-            FnArg::Typed(PatType { pat, .. }) => (
-                Ident::new("unexpected", pat.span()),
-                Error::new(pat.span(), "cannot use patterns in paramater").to_compile_error(),
-                quote!(),
-            ),
         })
-        .unzip3();
+        .unzip();
 
-    let orig_name = sig.ident.clone();
+    (
+        quote!(#(#output_args)*),
+        quote! {
+            let (#(#names),*) = #value;
+            #(#output_writes);*
+        },
+    )
+}
 
-    quote! {
-        #[no_mangle]
-        pub unsafe extern "C" fn #name(
-            #output_args
-            #(#input_args),*
-        ) -> *mut ffi::SignalFfiError {
-            ffi::run_ffi_safe(|| {
-                #(#input_processing);*;
-                let __result = #orig_name(#env_arg #(#input_names),*);
-                #await_if_needed;
-                #output_processing;
-                Ok(())
+/// Returns true if `ty` is passed as a base+length pair: `&[T]`, `&mut [T]`, or `Option<&[T]>`.
+fn is_sized_arg(ty: &Type) -> bool {
+    fn is_slice_ref(ty: &Type) -> bool {
+        matches!(
+            ty,
+            Type::Reference(TypeReference {
+                elem: box Type::Slice(_),
+                ..
             })
+        )
+    }
+
+    is_slice_ref(ty) || generic_arg_if_named(ty, "Option").map_or(false, is_slice_ref)
+}
+
+/// Returns true if `ty` is a function pointer, or an alias for one whose name ends in `CallbackFn`.
+///
+/// These are passed through as nullable function pointers; see `ffi::FfiCallback`.
+fn is_callback_arg(ty: &Type) -> bool {
+    match ty {
+        Type::BareFn(_) => true,
+        Type::Path(TypePath { qself: None, path }) => path.segments.last().map_or(false, |last| {
+            last.arguments.is_empty() && last.ident.to_string().ends_with("CallbackFn")
+        }),
+        _ => false,
+    }
+}
+
+/// Checks whether `ty` is `Vec<Box<[u8]>>`, optionally wrapped in a `Result`.
+///
+/// These results are returned through three output parameters instead of one: a flattened buffer,
+/// its length, and the number of buffers in the list.
+fn is_buffer_list(ty: &Type) -> bool {
+    let success_ty = generic_arg_if_named(ty, "Result").unwrap_or(ty);
+    match generic_arg_if_named(success_ty, "Vec").and_then(|elem| generic_arg_if_named(elem, "Box"))
+    {
+        Some(Type::Slice(TypeSlice { elem, .. })) => {
+            matches!(&**elem, Type::Path(elem_path) if elem_path.path.is_ident("u8"))
         }
+        _ => false,
     }
 }
 
+/// If `ty` is a `Vec` of bridged handles, optionally wrapped in a `Result`, returns the element
+/// type.
+///
+/// These results are returned through two output parameters: a Rust-owned array of handles, and
+/// the number of handles in it. Lists of buffers are handled by [`is_buffer_list`] instead.
+fn handle_list_element(ty: &Type) -> Option<&Type> {
+    let success_ty = generic_arg_if_named(ty, "Result").unwrap_or(ty);
+    match generic_arg_if_named(success_ty, "Vec")? {
+        elem @ Type::Path(TypePath { qself: None, path })
+            if path.get_ident().map_or(false, |ident| ident != "u8") =>
+        {
+            Some(elem)
+        }
+        _ => None,
+    }
+}
+
+/// Derives the FFI name for a function (without the `signal_` prefix).
+///
+/// The mapping is part of the C API, so it must not change for existing names; see the tests.
 pub(crate) fn name_from_ident(ident: &Ident) -> String {
     ident.to_string().to_snake_case()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_name_from_ident() {
+        for (name, expected) in &[
+            ("PublicKey_Compare", "public_key_compare"),
+            (
+                "SessionRecord_GetRemoteRegistrationId",
+                "session_record_get_remote_registration_id",
+            ),
+            // Acronyms are kept together, whether or not they're followed by another word.
+            ("HKDF_DeriveSecrets", "hkdf_derive_secrets"),
+            ("ECPublicKey_Serialize", "ec_public_key_serialize"),
+            ("HKDF", "hkdf"),
+            // Digits stay with the preceding word.
+            ("Aes256GcmSiv_New", "aes256_gcm_siv_new"),
+            ("SHA256Hash", "sha256_hash"),
+            ("E164", "e164"),
+            // Names that are already snake_case are unchanged.
+            ("process_prekey_bundle", "process_prekey_bundle"),
+            ("TESTING_ReturnTuple", "testing_return_tuple"),
+            // A lowercase suffix after an acronym splits in the wrong place; these need an
+            // explicit `ffi = "..."` name.
+            ("UUIDv4", "uui_dv4"),
+        ] {
+            assert_eq!(
+                name_from_ident(&Ident::new(name, proc_macro2::Span::call_site())),
+                *expected,
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn test_count_params() {
+        assert_eq!(count_params(quote!()), 0);
+        assert_eq!(count_params(quote!(out: *mut bool,)), 1);
+        assert_eq!(
+            count_params(quote!(
+                out: *mut *const libc::c_uchar,
+                out_len: *mut libc::size_t,
+                data: *const libc::c_uchar,
+                data_len: libc::size_t
+            )),
+            4
+        );
+        assert_eq!(
+            count_params(quote!(
+                callback: Option<unsafe extern "C" fn(u32, u32) -> u32>,
+                map: HashMap<u32, u32>,
+                value: ffi_arg_type!(Foo<A, B>)
+            )),
+            3
+        );
+    }
+
+    #[test]
+    fn test_handle_list_element() {
+        for (ty, expected) in &[
+            (quote!(Vec<PreKeyRecord>), Some(quote!(PreKeyRecord))),
+            (
+                quote!(Result<Vec<PreKeyRecord>>),
+                Some(quote!(PreKeyRecord)),
+            ),
+            (quote!(Vec<u8>), None),
+            (quote!(Vec<Box<[u8]>>), None),
+            (quote!(PreKeyRecord), None),
+        ] {
+            let ty: Type = parse2(ty.clone()).expect("valid type");
+            assert_eq!(
+                handle_list_element(&ty).map(|elem| elem.to_token_stream().to_string()),
+                expected.as_ref().map(|elem| elem.to_string()),
+                "{}",
+                ty.to_token_stream()
+            );
+        }
+    }
+}
@@ -5,23 +5,69 @@
 
 use proc_macro2::TokenStream as TokenStream2;
 use quote::*;
+use syn::ext::IdentExt;
 use syn::spanned::Spanned;
 use syn::*;
-use syn_mid::{FnArg, Pat, PatType, Signature};
-use unzip3::Unzip3;
+use syn_mid::{FnArg, PatType, Signature};
 
-use crate::ResultKind;
+use crate::{
+    box_bytearray_result, bytearray_result_shape, generic_arg_if_named, has_env_param, param_ident,
+    ResultKind,
+};
 
-pub(crate) fn bridge_fn(name: String, sig: &Signature, result_kind: ResultKind) -> TokenStream2 {
-    let name = format_ident!("Java_org_signal_client_internal_Native_{}", name);
+pub(crate) fn bridge_fn(
+    name: String,
+    sig: &Signature,
+    result_kind: ResultKind,
+    jni_async: bool,
+    jni_direct_buffers: bool,
+) -> TokenStream2 {
+    let name = format_ident!(
+        "Java_org_signal_client_internal_{}_{}",
+        class_name(sig),
+        name
+    );
+    let has_env = has_env_param(sig);
 
-    let (env_arg, output) = match (result_kind, &sig.output) {
-        (ResultKind::Regular, ReturnType::Default) => (quote!(), quote!()),
+    if jni_async {
+        if sig.asyncness.is_none() {
+            return Error::new(sig.fn_token.span, "jni_async requires an async function")
+                .to_compile_error();
+        }
+        if has_env {
+            return Error::new(
+                sig.fn_token.span,
+                "jni_async does not support Env parameters",
+            )
+            .to_compile_error();
+        }
+    }
+
+    let box_result = match (result_kind, &sig.output) {
+        (ResultKind::Regular, ReturnType::Type(_, ty)) => {
+            bytearray_result_shape(ty).map(box_bytearray_result)
+        }
+        _ => None,
+    };
+
+    let output = match (result_kind, &sig.output) {
+        (ResultKind::Regular, ReturnType::Default) => quote!(),
+        (ResultKind::Regular, ReturnType::Type(_, _)) if box_result.is_some() => {
+            quote!(-> jni::jbyteArray)
+        }
         (ResultKind::Regular, ReturnType::Type(_, ref ty)) => {
-            (quote!(), quote!(-> jni_result_type!(#ty)))
+            quote!(-> jni_result_type!(#ty))
+        }
+        (ResultKind::Void, _) => quote!(),
+        (ResultKind::Buffer, ReturnType::Type(_, _)) => quote!(-> jni::jbyteArray),
+        (ResultKind::Bool, ReturnType::Type(_, _)) => quote!(-> jni::jboolean),
+        (ResultKind::Bool, ReturnType::Default) => {
+            return Error::new(
+                sig.paren_token.span,
+                "missing result type for bridge_fn_bool",
+            )
+            .to_compile_error()
         }
-        (ResultKind::Void, _) => (quote!(), quote!()),
-        (ResultKind::Buffer, ReturnType::Type(_, _)) => (quote!(&env,), quote!(-> jni::jbyteArray)),
         (ResultKind::Buffer, ReturnType::Default) => {
             return Error::new(
                 sig.paren_token.span,
@@ -31,45 +77,133 @@ pub(crate) fn bridge_fn(name: String, sig: &Signature, result_kind: ResultKind)
         }
     };
 
+    let env_arg = if has_env { quote!(&env,) } else { quote!() };
+
     let await_if_needed = sig.asyncness.map(|_| {
         quote! {
             let __result = expect_ready(__result);
         }
     });
 
-    let (input_names, input_args, input_processing): (Vec<_>, Vec<_>, Vec<_>) = sig
+    // Large byte arrays can be pinned instead of copied, but only if nothing else calls into Java
+    // while they're pinned: no stores (which call back into Java), no JNIEnv for the function
+    // itself, and no other arguments still to be loaded. The pinned arguments are loaded last, and
+    // released before the result is converted.
+    let pin_buffers = !jni_async
+        && !has_env
+        && !sig.inputs.iter().any(|arg| match arg {
+            FnArg::Typed(PatType { ty, .. }) => is_trait_object_ref(ty),
+            FnArg::Receiver(_) => false,
+        });
+
+    let mut input_names = vec![];
+    let mut input_args = vec![];
+    let mut input_borrowing = vec![];
+    let mut input_loading = vec![];
+    let mut input_pinning = vec![];
+    for (index, arg) in sig
         .inputs
         .iter()
-        .skip(if result_kind.has_env() { 1 } else { 0 })
-        .map(|arg| match arg {
-            FnArg::Receiver(tokens) => (
-                Ident::new("self", tokens.self_token.span),
-                Error::new(tokens.self_token.span, "cannot have 'self' parameter")
-                    .to_compile_error(),
-                quote!(),
-            ),
+        .enumerate()
+        .skip(if has_env { 1 } else { 0 })
+    {
+        let (attrs, pat, colon_token, ty) = match arg {
+            FnArg::Receiver(tokens) => {
+                input_names.push(Ident::new("self", tokens.self_token.span));
+                input_args.push(
+                    Error::new(tokens.self_token.span, "cannot have 'self' parameter")
+                        .to_compile_error(),
+                );
+                continue;
+            }
             FnArg::Typed(PatType {
                 attrs,
-                pat: box Pat::Ident(name),
+                pat,
                 colon_token,
                 ty,
-            }) => (
-                name.ident.clone(),
-                quote!(#(#attrs)* #name #colon_token jni_arg_type!(#ty)),
-                quote! {
-                    let mut #name = <#ty as jni::ArgTypeInfo>::borrow(&env, #name)?;
-                    let #name = <#ty as jni::ArgTypeInfo>::load_from(&env, &mut #name)?
-                },
-            ),
-            FnArg::Typed(PatType { pat, .. }) => (
-                Ident::new("unexpected", pat.span()),
-                Error::new(pat.span(), "cannot use patterns in paramater").to_compile_error(),
-                quote!(),
-            ),
-        })
-        .unzip3();
+            }) => (attrs, pat, colon_token, ty),
+        };
+        let name = match param_ident(pat, index) {
+            Some(name) => name,
+            None => {
+                input_names.push(Ident::new("unexpected", pat.span()));
+                input_args.push(
+                    Error::new(pat.span(), "cannot use patterns in paramater").to_compile_error(),
+                );
+                continue;
+            }
+        };
+        input_names.push(name.clone());
+
+        if jni_direct_buffers && is_byte_slice_arg(ty) {
+            input_args.push(quote!(#(#attrs)* #name #colon_token jni::JByteBuffer));
+            input_borrowing.push(quote! {
+                let #name = <#ty as jni::DirectBufferArgTypeInfo>::convert_from(&env, #name)?
+            });
+        } else if is_byte_slice_arg(ty) {
+            // Passed as a byte[]. Borrowing checks for null (unless optional) and fetches the
+            // length; short arrays are copied right away, and longer ones when loaded.
+            input_args.push(quote!(#(#attrs)* #name #colon_token jni::jbyteArray));
+            input_borrowing.push(quote! {
+                let mut #name = <#ty as jni::ArgTypeInfo>::borrow(&env, #name)?
+            });
+            let load = if pin_buffers {
+                quote!(load_from_pinned)
+            } else {
+                quote!(load_from)
+            };
+            let loading = quote! {
+                let #name = <#ty as jni::ArgTypeInfo>::#load(&env, &mut #name)?
+            };
+            if pin_buffers {
+                input_pinning.push(loading);
+            } else {
+                input_loading.push(loading);
+            }
+        } else {
+            input_args.push(quote!(#(#attrs)* #name #colon_token jni_arg_type!(#ty)));
+            input_borrowing.push(quote! {
+                let mut #name = <#ty as jni::ArgTypeInfo>::borrow(&env, #name)?
+            });
+            input_loading.push(quote! {
+                let #name = <#ty as jni::ArgTypeInfo>::load_from(&env, &mut #name)?
+            });
+        }
+    }
+    input_loading.extend(input_pinning);
 
     let orig_name = sig.ident.clone();
+    let arity = input_args.len();
+
+    if jni_async {
+        let async_box_result = box_result.map(|box_result| {
+            quote! {
+                let __future = async move {
+                    let __result = __future.await;
+                    #box_result
+                    __result
+                };
+            }
+        });
+        return quote! {
+            #[no_mangle]
+            pub unsafe extern "C" fn #name(
+                env: jni::JNIEnv,
+                _class: jni::JClass,
+                #(#input_args),*
+            ) -> jni::JavaCompletableFuture {
+                jni::run_ffi_safe(&env, || {
+                    #(#input_borrowing);*;
+                    #(#input_loading);*;
+                    let __future = #orig_name(#(#input_names),*);
+                    #async_box_result
+                    jni::run_future_on_executor(&env, __future)
+                })
+            }
+
+            jni_register!(#name, #arity);
+        };
+    }
 
     quote! {
         #[no_mangle]
@@ -79,15 +213,118 @@ pub(crate) fn bridge_fn(name: String, sig: &Signature, result_kind: ResultKind)
             #(#input_args),*
         ) #output {
             jni::run_ffi_safe(&env, || {
-                #(#input_processing);*;
-                let __result = #orig_name(#env_arg #(#input_names),*);
-                #await_if_needed;
+                let __result = {
+                    #(#input_borrowing);*;
+                    #(#input_loading);*;
+                    let __result = #orig_name(#env_arg #(#input_names),*);
+                    #await_if_needed;
+                    __result
+                };
+                #box_result
                 jni::ResultTypeInfo::convert_into(__result, &env)
             })
         }
+
+        jni_register!(#name, #arity);
+    }
+}
+
+/// Returns true if `ty` is `&[u8]` or `Option<&[u8]>`, which are passed as a `byte[]` (or a direct
+/// `java.nio.ByteBuffer`).
+fn is_byte_slice_arg(ty: &Type) -> bool {
+    fn is_byte_slice_ref(ty: &Type) -> bool {
+        match ty {
+            Type::Reference(TypeReference {
+                mutability: None,
+                elem: box Type::Slice(TypeSlice { elem, .. }),
+                ..
+            }) => matches!(&**elem, Type::Path(elem_path) if elem_path.path.is_ident("u8")),
+            _ => false,
+        }
     }
+
+    is_byte_slice_ref(ty) || generic_arg_if_named(ty, "Option").map_or(false, is_byte_slice_ref)
 }
 
+/// Returns true if `ty` is a reference to a trait object, like the stores.
+fn is_trait_object_ref(ty: &Type) -> bool {
+    matches!(
+        ty,
+        Type::Reference(TypeReference {
+            elem: box Type::TraitObject(_),
+            ..
+        })
+    )
+}
+
+/// Derives the JNI name for a function (without the `Java_..._Native_` prefix), which is the
+/// function's name escaped with [`mangle_name`].
 pub(crate) fn name_from_ident(ident: &Ident) -> String {
-    ident.to_string().replace("_", "_1")
+    mangle_name(&ident.unraw().to_string())
+}
+
+/// The Java class whose static method exposes `sig`'s entry point.
+///
+/// `TESTING_` functions are only built for the bindings' own tests, so they go in a separate class
+/// that is not part of the library.
+fn class_name(sig: &Signature) -> &'static str {
+    if sig.ident.to_string().starts_with("TESTING_") {
+        "NativeTesting"
+    } else {
+        "Native"
+    }
+}
+
+/// Escapes a Java method name for use in a JNI symbol, as described in the [JNI spec][].
+///
+/// ASCII letters and digits are kept. `_`, `;`, and `[` become `_1`, `_2`, and `_3`, and any other
+/// character becomes `_0xxxx` for each of its UTF-16 code units, in lowercase hex.
+///
+/// [JNI spec]: https://docs.oracle.com/javase/8/docs/technotes/guides/jni/spec/design.html#resolving_native_method_names
+fn mangle_name(name: &str) -> String {
+    let mut result = String::with_capacity(name.len());
+    for c in name.chars() {
+        match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' => result.push(c),
+            '_' => result.push_str("_1"),
+            ';' => result.push_str("_2"),
+            '[' => result.push_str("_3"),
+            _ => {
+                for unit in c.encode_utf16(&mut [0; 2]) {
+                    result.push_str(&format!("_0{:04x}", unit));
+                }
+            }
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_name_from_ident() {
+        // Expected names are from the headers `javac -h` generates for the same methods.
+        for (name, expected) in &[
+            ("PublicKey_Compare", "PublicKey_1Compare"),
+            ("Aes256_GcmSiv_New", "Aes256_1GcmSiv_1New"),
+            ("HKDF_DeriveSecrets", "HKDF_1DeriveSecrets"),
+            ("TESTING_ReturnTuple", "TESTING_1ReturnTuple"),
+            ("Foo__Bar_", "Foo_1_1Bar_1"),
+            ("Foo_1", "Foo_11"),
+            ("Größe_Get", "Gr_000f6_000dfe_1Get"),
+            ("r#type", "type"),
+        ] {
+            let ident: Ident = parse_str(name).expect("valid identifier");
+            assert_eq!(name_from_ident(&ident), *expected, "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_mangle_name() {
+        assert_eq!(mangle_name("a;b[c"), "a_2b_3c");
+        // Characters outside the BMP are escaped as a surrogate pair.
+        assert_eq!(mangle_name("x\u{1D518}"), "x_0d835_0dd18");
+    }
 }
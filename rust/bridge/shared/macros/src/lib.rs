@@ -3,12 +3,12 @@
 // SPDX-License-Identifier: AGPL-3.0-only
 //
 
-//! Generates C, Java, and Node entry points for Rust functions.
+//! Generates C, Java, Node, and WebAssembly entry points for Rust functions.
 //!
 //! The goal of the `bridge_fn` family of macros is to define a cross-language glue layer using
 //! strongly-typed Rust code. You can write a normal top-level Rust function exposing a particular
-//! operation, and additional functions will be generated for FFI, JNI, and Node bindings, mapping
-//! types automatically.
+//! operation, and additional functions will be generated for FFI, JNI, Node, and WASM bindings,
+//! mapping types automatically.
 //!
 //! It is explicitly *not* a goal for this layer to generate perfect C, Java, or TypeScript APIs.
 //! Rather, it should generate safe interfaces to *Rust* APIs, on top of which idiomatic Swift,
@@ -51,7 +51,7 @@
 //! export function SenderKeyMessage_New(
 //!     keyId: number,
 //!     iteration: number,
-//!     ciphertext: Buffer,
+//!     ciphertext: Uint8Like,
 //!     pk: Wrapper<PrivateKey>
 //! ): SenderKeyMessage;
 //! ```
@@ -64,34 +64,146 @@
 //! defined using the [`async-trait`][] crate. Like the synchronous implementations of all three
 //! bridges, **panics will be caught** and translated to JavaScript exceptions.
 //!
-//! The FFI and JNI bridges do not support asynchronous execution; an `async` function is invoked
-//! and `expect`ed to complete immediately without blocking.
+//! Arguments to an async function are saved before the Promise is created (using
+//! `node::AsyncArgTypeInfo`) and finalized after it settles, whether it succeeds or fails. This
+//! includes store arguments like `&mut dyn SessionStore`, which need no special annotation: the
+//! JavaScript store object is kept alive for the duration of the future, each `&mut` access calls
+//! back into JavaScript, and so any mutations are visible once the Promise resolves. The
+//! corresponding TypeScript parameter type is the store class itself:
+//!
+//! ```ignore
+//! #[bridge_fn(ffi = false, jni = false)]
+//! async fn SessionStore_Touch(address: &ProtocolAddress, store: &mut dyn SessionStore) -> Result<()> {
+//!     // ...
+//! }
+//! ```
+//!
+//! ```typescript
+//! export function SessionStore_Touch(
+//!     address: Wrapper<ProtocolAddress>,
+//!     store: SessionStore
+//! ): Promise<void>;
+//! ```
+//!
+//! An async function can also take a `CancellationToken` parameter (see
+//! `node::SupportsCancellation`), which is not passed from JavaScript. Instead, the returned
+//! Promise gets a `cancel()` method that cancels the token, and its TypeScript result type becomes
+//! `CancellablePromise<T>`. Cancelling only signals the function; it is still up to the function
+//! to settle the Promise. Such functions must disable the FFI and JNI bridges.
+//!
+//! By default, the FFI and JNI bridges invoke an `async` function and `expect` it to complete
+//! immediately without blocking. For JNI, an `async` function can instead be marked
+//! `bridge_fn(jni_async = true)`. Such a function returns a Java `CompletableFuture` and runs on a
+//! thread pool shared by all such functions, which means its arguments cannot borrow from the JNI
+//! environment (so, no `&[u8]` or store parameters). The pool's threads attach themselves to the
+//! JVM as daemons to deliver results; errors and panics are mapped to exceptions as usual and
+//! delivered to Java through `completeExceptionally`.
+//!
+//! Similarly, for FFI, an `async` function marked `bridge_fn(ffi_async = true)` reports its result
+//! through a completion callback, which is passed as the first two arguments along with a context
+//! pointer:
+//!
+//! ```c
+//! void signal_foo_bar(void (*done)(SignalFfiError *error, uint32_t result, void *ctx),
+//!                     void *ctx,
+//!                     uint32_t input);
+//! ```
+//!
+//! The callback is called exactly once, with either a null error and the result, or an error
+//! (which the callback now owns) and a zero placeholder value. Functions with no result (including
+//! those returning `Result<()>`) omit the result parameter. If the future completes the first
+//! time it's polled, the callback is called before the entry point returns; otherwise, the future
+//! runs on a shared thread pool, with the same restrictions on arguments as `jni_async`. Results that
+//! need more than one output parameter (buffers and tuples) are not supported.
 //!
 //! [`async-trait`]: https://crates.io/crates/async-trait
 //!
+//! # Store parameters
+//!
+//! A parameter of type `&mut dyn FooStore` is passed as a store implemented in the calling
+//! language, and wrapped in an adapter chosen by the trait's name: a `SignalFooStore` struct of
+//! callbacks for FFI (`ffi::FfiFooStoreStruct`), a Java object implementing `FooStore` for JNI
+//! (`jni::JniFooStore`), and a JavaScript `FooStore` object for Node (`node::NodeFooStore`, in
+//! async functions only). Each adapter implements the trait by calling back into the store, so
+//! there is nothing else to write for a new store-taking function. A failing callback becomes a
+//! `SignalProtocolError::ApplicationCallbackError`, reported as `SignalErrorCode_CallbackError` for
+//! FFI. For JNI, the exception thrown by the store is rethrown as is; for Node, the thrown error's
+//! message is included in the `Error` the function throws.
+//!
+//! Supporting a new store trait means adding the adapters for each bridge, and a `store!` line
+//! to each bridge's `convert.rs`.
+//!
+//! # Callbacks for FFI
+//!
+//! A parameter whose type is a function pointer, like `unsafe extern "C" fn(u32) -> u32`, or a
+//! type alias whose name ends in `CallbackFn`, is passed from C as a function pointer with the
+//! same signature. C callers can always pass `NULL`, so the generated entry point takes an
+//! `Option` and fails with `SignalFfiError::NullPointer` before calling the Rust function (see
+//! `ffi::FfiCallback`). Function pointers without an explicit ABI are rejected, since C can't
+//! call them. Such functions must disable the JNI and Node bridges.
+//!
+//! # Direct buffers for JNI
+//!
+//! By default, `&[u8]` and `Option<&[u8]>` arguments are passed from Java as `byte[]`, which the
+//! JVM may copy. A function marked `bridge_fn(jni_direct_buffers = true)` instead takes each of
+//! these arguments as a `java.nio.ByteBuffer`, which must be a direct buffer (from
+//! `ByteBuffer.allocateDirect`). The Rust slice refers to the buffer's remaining bytes, between its
+//! position and its limit, without copying them. This is worthwhile for large inputs only.
+//!
+//! Without direct buffers, arrays longer than 4 KiB (`jni::BYTE_ARRAY_PIN_THRESHOLD`) are pinned
+//! with `GetPrimitiveArrayCritical` instead of being copied, as long as nothing else calls into
+//! Java while they're pinned. So functions that take stores or an `Env`, as well as `jni_async`
+//! functions, always copy. The pinned arrays are released before the result is
+//! converted. A function that pins an array can stall the garbage collector until it returns, so
+//! it should not block or run for a long time.
+//!
+//! # WASM
+//!
+//! The WASM bridge exposes the same functions as the Node bridge, under the same names, as
+//! `#[wasm_bindgen]` exports of the `libsignal-wasm` crate. Arguments and results are converted by
+//! `wasm::ArgTypeInfo` and `wasm::ResultTypeInfo` much as for Node: byte slices are accepted as any
+//! `Uint8Array` or `ArrayBuffer` and copied, buffers are returned as `Uint8Array`s, `u64` is a
+//! `bigint`, tuples become objects named by `node_result_fields`, and errors are thrown as `Error`s
+//! with the same `name`s. Handles are passed as plain numbers rather than `Wrapper` objects, and
+//! must be released with the generated `Foo_Destroy` function.
+//!
+//! An `async` function returns a `Promise`, like it does for Node, and its store arguments call
+//! back into the same JavaScript store methods. Unlike Node, panics are not caught, since they
+//! abort on `wasm32`, and `CancellationToken` parameters are not supported.
+//!
 //! # Naming conventions
 //!
 //! By default, `bridge_fn` tries to pick a good name for each exposed entry point:
 //!
-//! - FFI: Convert the function's name to `lower_snake_case` and prepend `signal_`.
-//! - JNI: Escape any underscores in the function's name per the [JNI spec][], then prepend
-//!  `Java_org_signal_client_internal_Native_` to expose the function as a static method of the
-//!  class `org.signal.client.internal.Native`.
+//! - FFI: Convert the function's name to `lower_snake_case` and prepend `signal_`. A run of
+//!   capitals is kept together as one word, and digits stay with the word before them:
+//!   `HKDF_DeriveSecrets` becomes `signal_hkdf_derive_secrets`, `ECPublicKey_Serialize` becomes
+//!   `signal_ec_public_key_serialize`, and `Aes256GcmSiv_New` becomes `signal_aes256_gcm_siv_new`.
+//! - JNI: Escape the function's name per the [JNI spec][] (`_` becomes `_1`, and non-ASCII
+//!   characters become `_0xxxx`), then prepend `Java_org_signal_client_internal_Native_` to
+//!   expose the function as a static method of the class `org.signal.client.internal.Native`.
 //! - Node: Use the original function's name.
+//! - WASM: Use the Node name, including a replaced one, unless `wasm` is given separately.
 //!
 //! As such, the recommended naming scheme for `bridge_fn` functions is `ObjectOrGroup_Operation`.
 //!
-//! Any of these names can be replaced by specifying an argument to the `bridge_fn` attribute:
+//! Any of these names can be replaced independently by specifying an argument to the `bridge_fn`
+//! attribute; the others keep their default names:
 //!
 //! ```ignore
-//! #[bridge_fn(ffi = "magic_alakazam", jni = "Magic_1Alakazam")]
+//! #[bridge_fn(ffi = "magic_alakazam", jni = "Magic_1Alakazam", node = "magicAlakazam")]
 //! fn Abracadabra() {
 //!   // ...
 //! }
 //! ```
 //!
+//! Since changing an exposed name breaks existing callers, use an explicit name rather than
+//! renaming the function when the derived one comes out wrong (e.g. `UUIDv4` would become
+//! `uui_dv4`).
+//!
 //! A replaced name does not undergo any transformation, but is still prefixed with the required
-//! "namespace" for FFI and JNI.
+//! "namespace" for FFI and JNI. A replaced Node name is also used in the generated TypeScript
+//! declaration. Unrecognized options are rejected.
 //!
 //! [JNI spec]: https://docs.oracle.com/javase/8/docs/technotes/guides/jni/spec/design.html#resolving_native_method_names
 //!
@@ -99,8 +211,102 @@
 //!
 //! Do not use `cfg(feature = "abc")` to restrict a `bridge_fn` to certain bridges (e.g. "just
 //! FFI"). This interacts poorly with commands like `cargo clippy --workspace`, which try to
-//! validate all packages by enabling all three bridges at once. Instead, you can write e.g.
-//! `bridge_fn(jni = false)` to keep from exposing a particular function to Java.
+//! validate all packages by enabling every bridge at once. Instead, you can write e.g.
+//! `bridge_fn(jni = false)` to keep from exposing a particular function to Java. No entry point
+//! is generated at all for a disabled bridge: no `#[no_mangle]` symbol, no JNI method, and no Node
+//! registration. This composes with name overrides (e.g. `bridge_fn(ffi = "custom_name", node =
+//! false)`). Disabling every bridge is an error. Since WASM follows Node by default, `node =
+//! false` disables it as well, unless it has its own `wasm = "..."` name.
+//!
+//! # Result types
+//!
+//! `bridge_fn` looks at the function's return type (syntactically, so not through type aliases)
+//! to decide how to return the result:
+//!
+//! - No return type, `()`, or `Result<()>`: nothing is returned on success. Errors are still
+//!   reported, so C callers only get the error pointer, Java methods are `void`, and TypeScript
+//!   functions return `void`.
+//! - `bool` or `Result<bool>`: a `bool` output parameter for C, a `jboolean` for Java, and a
+//!   `boolean` for TypeScript.
+//! - `E::Buffer`, optionally wrapped in `Result` and/or `Option`, where `E` is the function's
+//!   `Env` type (see below): a buffer created by the bridge itself, with the same representation
+//!   as the byte buffers described under "Returning buffers".
+//! - Anything else: a value converted with the bridges' `ResultTypeInfo` traits, with special
+//!   cases for byte buffers and tuples described below.
+//!
+//! The older `bridge_fn_void`, `bridge_fn_buffer`, and `bridge_fn_bool` macros force one of the
+//! first three behaviors regardless of the return type. They are deprecated, and will be removed.
+//!
+//! # Env parameters
+//!
+//! Any `bridge_fn` can take an `Env` as its first parameter, to allocate bridge-specific values
+//! such as buffers. The macro recognizes it syntactically: its type must be `impl Env` or a type
+//! parameter bounded by `Env`, as in `fn Foo_Bar<E: Env>(env: E, ...)`. It isn't passed by the
+//! caller; each bridge supplies its own (`ffi::Env`, the `JNIEnv`, or the Neon context, which is
+//! `node::AsyncEnv` for an `async` function). `jni_async` functions cannot take an `Env`.
+//!
+//! # Returning buffers
+//!
+//! A `bridge_fn` can return `Vec<u8>`, `Box<[u8]>`, or `[u8; N]`, optionally wrapped in `Result`
+//! and/or `Option`. These are recognized syntactically and returned as a pointer/length pair of
+//! output parameters for FFI, a `byte[]` for JNI, and a `Buffer` for Node (`null` for `None` in
+//! all three).
+//!
+//! # Returning multiple values
+//!
+//! A `bridge_fn` can also return a 2- or 3-tuple, optionally wrapped in `Result`, to avoid making
+//! several calls for one operation. For FFI, each element gets its own output parameter (`out_0`,
+//! `out_1`, ...; a pointer/length pair for buffer elements). For JNI, the elements are returned
+//! as an `Object[]`, with primitives boxed. For Node, the elements become the fields of a plain
+//! object, whose names must be given with `node_result_fields`:
+//!
+//! ```ignore
+//! #[bridge_fn(node_result_fields = "ciphertext, messageType")]
+//! fn SessionCipher_EncryptMessage(/* ... */) -> Result<(Vec<u8>, u8)> {
+//!     // ...
+//! }
+//! ```
+//!
+//! ```typescript
+//! export function SessionCipher_EncryptMessage(
+//!     /* ... */
+//! ): { ciphertext: Buffer, messageType: number };
+//! ```
+//!
+//! # TypeScript declarations
+//!
+//! Each Node entry point carries its TypeScript declaration as a `ts:` doc comment. Argument names
+//! are converted to `camelCase`, and types are translated as shown above: numbers, booleans,
+//! strings, and `Buffer`s are passed directly, handles are passed as `Wrapper<Foo>` and returned as
+//! `Foo`, byte slices are accepted as any `Uint8Array` or `ArrayBuffer` (`Uint8Like`), `Option<T>`
+//! becomes `T | null`, and errors are thrown rather than returned. `u64` is
+//! always a `bigint`, since a `number` can't represent every value exactly (for JNI, it is a
+//! `long` with the same bits). A type with no TypeScript equivalent is a compile-time error. `cargo run --bin gen-ts-decls` (in
+//! `rust/bridge/node`) collects these comments and writes `node/libsignal_client.d.ts`; with
+//! `--verify`, it fails instead if the checked-in file is out of date.
+//!
+//! Declarations belong to the `Native` module (`libsignal_client.d.ts`) unless a different one is
+//! given with `ts_module`, in which case the comment is written as `ts(Module):`. Each module must
+//! also be listed in `gen-ts-decls`, which decides where its declarations are written.
+//!
+//! Functions named `TESTING_*` are only built with the bridge's `testing` feature, for the
+//! bindings' own tests. Their JNI entry points belong to `org.signal.client.internal.NativeTesting`
+//! instead of `Native`, and their TypeScript declarations default to the `Testing` module.
+//!
+//! # Forwarding doc comments
+//!
+//! The generated entry points are undocumented by default. With `bridge_fn(forward_docs = true)`,
+//! the function's `///` comments are copied onto each of them. Rust links like ``[`Foo`]`` would
+//! not resolve there (or in TypeScript), so they are replaced by their text, and link reference
+//! definitions are dropped. For Node, the comments go directly above the `ts:` annotation, and
+//! `gen-ts-decls` emits them as the declaration's JSDoc comment.
+//!
+//! # Deprecation
+//!
+//! A `#[deprecated]` attribute on a `bridge_fn` is copied onto its generated entry points. cbindgen
+//! marks deprecated FFI functions with `SIGNAL_DEPRECATED(note)`, which compilers that support it
+//! turn into a deprecation warning, and `gen_java_decl.py` marks the Java methods `@Deprecated`.
+//! For Node, the note becomes an `@deprecated` JSDoc tag on the TypeScript declaration.
 //!
 //! # Adding new argument and result types
 //!
@@ -109,20 +315,27 @@
 //!
 //! 1. Argument and result types for FFI and JNI are determined by macros `ffi_arg_type`,
 //!    `ffi_result_type`, `jni_arg_type`, and `jni_result_type`. You may need to add your new type
-//!    there. JNI and Node types also undergo some additional transformation in the scripts
-//!    `gen_java_decl.py` and `gen_ts_decl.py`, which you may need to tweak as well.
+//!    there. JNI types also undergo some additional transformation in the generator
+//!    `gen_java_decl.py`, and Node types are translated to TypeScript by `ts_type` in `node.rs`;
+//!    you may need to tweak those as well.
+//!
+//!    Before generating any entry points, `bridge_fn` rejects types that no bridge could support,
+//!    such as generic types other than `Option`, `Result`, `Vec`, and `Box`, with an error at the
+//!    offending type. A new generic wrapper type must also be added to `SUPPORTED_GENERIC_TYPES`.
 //!
 //! 2. Argument types conform to one or more of the following bridge-specific traits:
 //!
 //!     - `ffi::ArgTypeInfo` or `ffi::SizedArgTypeInfo`
 //!     - `jni::ArgTypeInfo`
 //!     - `node::ArgTypeInfo` and/or `node::AsyncArgTypeInfo`
+//!     - `wasm::ArgTypeInfo` and/or `wasm::AsyncArgTypeInfo`
 //!
 //!     Similarly, result types conform to one or more of the following:
 //!
 //!     - `ffi::ResultTypeInfo`
 //!     - `jni::ResultTypeInfo`
 //!     - `node::ResultTypeInfo`
+//!     - `wasm::ResultTypeInfo`
 //!
 //!    These traits define how to convert between the bridge type and the Rust type used in the
 //!    function as written. See each individual trait for more info on how to add a new type.
@@ -136,20 +349,28 @@
 //!   particularly when trying to do so on the syntactic representation of the AST that macros are
 //!   restricted to.
 //!
-//! - There is no support for multiple return values, even though some of the FFI entry points
-//!   use multiple output parameters. These functions must be implemented manually.
+//! - Parameters must be plain identifiers or `_`. A `_` parameter is still passed from each
+//!   bridge and converted as usual (under a synthesized name like `__unused_1`, which shows up as
+//!   `unused1` in TypeScript), then ignored by the function. Other patterns are rejected.
+//!
+//! - Tuple results are only recognized at the top level (possibly inside a `Result`), and each
+//!   element must be a type the bridges can already return on its own.
 
 #![feature(box_patterns)]
 
 use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
 use quote::*;
+use syn::parse::Parser;
 use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
 use syn::*;
 use syn_mid::ItemFn;
 
 mod ffi;
 mod jni;
 mod node;
+mod wasm;
 
 fn value_for_meta_key<'a>(
     meta_values: &'a Punctuated<MetaNameValue, Token![,]>,
@@ -178,57 +399,651 @@ fn name_for_meta_key(
     }
 }
 
-#[derive(Clone, Copy)]
+fn flag_for_meta_key(
+    meta_values: &Punctuated<MetaNameValue, Token![,]>,
+    key: &str,
+) -> Result<bool> {
+    match value_for_meta_key(meta_values, key) {
+        Some(Lit::Bool(LitBool { value, .. })) => Ok(*value),
+        Some(value) => Err(Error::new(value.span(), "value must be a boolean literal")),
+        None => Ok(false),
+    }
+}
+
+/// Collects the function's doc comments for `bridge_fn(forward_docs = true)`, one string per line.
+///
+/// Rust links like ``[`Foo`]`` or `[Foo](crate::Foo)` don't resolve from the generated functions
+/// (or in TypeScript), so they're replaced by their text, and link reference definitions are
+/// dropped.
+fn forwarded_docs(attrs: &[Attribute]) -> Vec<String> {
+    let mut docs = attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("doc"))
+        .filter_map(|attr| match attr.parse_meta() {
+            Ok(Meta::NameValue(MetaNameValue {
+                lit: Lit::Str(doc), ..
+            })) => Some(doc.value()),
+            _ => None,
+        })
+        .filter(|line| !is_link_reference_definition(line))
+        .map(|line| strip_doc_links(&line))
+        .collect::<Vec<_>>();
+    // Don't leave a blank line where the reference definitions were.
+    while docs.last().map_or(false, |line| line.trim().is_empty()) {
+        docs.pop();
+    }
+    docs
+}
+
+/// Returns the note given in a `#[deprecated]` attribute, as either `#[deprecated = "..."]` or
+/// `#[deprecated(note = "...")]`.
+fn deprecation_note(attr: &Attribute) -> Option<String> {
+    match attr.parse_meta().ok()? {
+        Meta::NameValue(MetaNameValue {
+            lit: Lit::Str(note),
+            ..
+        }) => Some(note.value()),
+        Meta::List(list) => list.nested.iter().find_map(|nested| match nested {
+            NestedMeta::Meta(Meta::NameValue(MetaNameValue {
+                path,
+                lit: Lit::Str(note),
+                ..
+            })) if path.is_ident("note") => Some(note.value()),
+            _ => None,
+        }),
+        _ => None,
+    }
+}
+
+/// Whether `line` looks like a Markdown link reference definition, such as `[Foo]: crate::Foo`.
+fn is_link_reference_definition(line: &str) -> bool {
+    let line = line.trim_start();
+    line.starts_with('[')
+        && line
+            .find("]:")
+            .map_or(false, |end| !line[1..end].contains(']'))
+}
+
+/// Replaces each Markdown link in `line` with its text: `[text]`, `[text](target)`, and
+/// `[text][reference]` all become `text`. Brackets inside inline code (like `` `&[u8]` ``) are left
+/// alone.
+fn strip_doc_links(line: &str) -> String {
+    let mut result = String::with_capacity(line.len());
+    let mut in_code = false;
+    let mut rest = line;
+    while let Some(next) = rest.find(|c| c == '`' || (c == '[' && !in_code)) {
+        result.push_str(&rest[..next]);
+        if rest[next..].starts_with('`') {
+            in_code = !in_code;
+            result.push('`');
+            rest = &rest[next + 1..];
+            continue;
+        }
+        // The link text may have inline code of its own, as in ``[`&[u8]`]``.
+        let mut in_link_code = false;
+        let link_end = rest[next..].find(|c| match c {
+            '`' => {
+                in_link_code = !in_link_code;
+                false
+            }
+            ']' => !in_link_code,
+            _ => false,
+        });
+        let end = match link_end {
+            Some(offset) => next + offset,
+            None => {
+                result.push('[');
+                rest = &rest[next + 1..];
+                continue;
+            }
+        };
+        result.push_str(&rest[next + 1..end]);
+        rest = &rest[end + 1..];
+        let closing = match rest.chars().next() {
+            Some('(') => ')',
+            Some('[') => ']',
+            _ => continue,
+        };
+        rest = rest
+            .find(closing)
+            .map_or("", |target_end| &rest[target_end + 1..]);
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Returns the name for the parameter at `index` in the generated entry points.
+///
+/// Plain identifiers are kept as is. A `_` parameter gets a unique synthesized name, so that its
+/// argument is still declared, converted, and passed to the original function, which ignores it.
+/// Any other pattern is unsupported.
+fn param_ident(pat: &syn_mid::Pat, index: usize) -> Option<Ident> {
+    match pat {
+        syn_mid::Pat::Ident(name) => Some(name.ident.clone()),
+        syn_mid::Pat::Wild(wild) => Some(format_ident!(
+            "__unused_{}",
+            index,
+            span = wild.underscore_token.span
+        )),
+        _ => None,
+    }
+}
+
+/// Checks whether the function's first parameter is an `Env`, which each bridge supplies itself
+/// rather than converting it from a caller's argument.
+///
+/// This is a syntactic check: the parameter's type must be `impl Env` or one of the function's
+/// type parameters bounded by `Env` (inline or in a `where` clause), as in
+/// `fn Foo<E: Env>(env: E, ...)`. The parameter's name doesn't matter.
+fn has_env_param(sig: &syn_mid::Signature) -> bool {
+    fn is_env_bound(bound: &TypeParamBound) -> bool {
+        match bound {
+            TypeParamBound::Trait(TraitBound { path, .. }) => path
+                .segments
+                .last()
+                .map_or(false, |segment| segment.ident == "Env"),
+            TypeParamBound::Lifetime(_) => false,
+        }
+    }
+
+    let ty = match sig.inputs.first() {
+        Some(syn_mid::FnArg::Typed(syn_mid::PatType { ty, .. })) => &**ty,
+        _ => return false,
+    };
+    match ty {
+        Type::ImplTrait(TypeImplTrait { bounds, .. }) => bounds.iter().any(is_env_bound),
+        Type::Path(TypePath { qself: None, path }) => {
+            let param_name = match path.get_ident() {
+                Some(name) => name,
+                None => return false,
+            };
+            let inline_bounds = sig.generics.type_params().filter_map(|param| {
+                if &param.ident == param_name {
+                    Some(&param.bounds)
+                } else {
+                    None
+                }
+            });
+            let where_bounds = sig
+                .generics
+                .where_clause
+                .iter()
+                .flat_map(|clause| clause.predicates.iter())
+                .filter_map(|predicate| match predicate {
+                    WherePredicate::Type(PredicateType {
+                        bounded_ty: Type::Path(bounded),
+                        bounds,
+                        ..
+                    }) if bounded.qself.is_none() && bounded.path.is_ident(param_name) => {
+                        Some(bounds)
+                    }
+                    _ => None,
+                });
+            inline_bounds
+                .chain(where_bounds)
+                .any(|bounds| bounds.iter().any(is_env_bound))
+        }
+        _ => false,
+    }
+}
+
+/// If `ty` is a path type whose last segment is `name<T, ...>`, returns `T`.
+///
+/// This is a syntactic check, so it won't see through type aliases.
+fn generic_arg_if_named<'a>(ty: &'a Type, name: &str) -> Option<&'a Type> {
+    match ty {
+        Type::Path(TypePath { qself: None, path }) => match path.segments.last() {
+            Some(PathSegment {
+                ident,
+                arguments: PathArguments::AngleBracketed(generic_args),
+            }) if ident == name => match generic_args.args.first() {
+                Some(GenericArgument::Type(inner)) => Some(inner),
+                _ => None,
+            },
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Checks whether a buffer result type is wrapped in `Result` and/or `Option`, as in
+/// `Result<Option<E::Buffer>>`.
+fn buffer_result_shape(ty: &Type) -> (bool, bool) {
+    match generic_arg_if_named(ty, "Result") {
+        Some(success_ty) => (true, generic_arg_if_named(success_ty, "Option").is_some()),
+        None => (false, generic_arg_if_named(ty, "Option").is_some()),
+    }
+}
+
+/// Checks whether `ty` is a byte buffer that a regular `bridge_fn` can return directly:
+/// `Vec<u8>`, `Box<[u8]>`, or `[u8; N]`, optionally wrapped in `Result` and/or `Option`.
+///
+/// If so, returns the same shape as [`buffer_result_shape`].
+fn bytearray_result_shape(ty: &Type) -> Option<(bool, bool)> {
+    fn is_u8(ty: &Type) -> bool {
+        matches!(ty, Type::Path(path) if path.path.is_ident("u8"))
+    }
+
+    let success_ty = generic_arg_if_named(ty, "Result").unwrap_or(ty);
+    let buffer_ty = generic_arg_if_named(success_ty, "Option").unwrap_or(success_ty);
+    let is_bytearray = match buffer_ty {
+        Type::Array(TypeArray { elem, .. }) => is_u8(elem),
+        _ => {
+            generic_arg_if_named(buffer_ty, "Vec").map_or(false, is_u8)
+                || matches!(
+                    generic_arg_if_named(buffer_ty, "Box"),
+                    Some(Type::Slice(TypeSlice { elem, .. })) if is_u8(elem)
+                )
+        }
+    };
+    if is_bytearray {
+        Some(buffer_result_shape(ty))
+    } else {
+        None
+    }
+}
+
+/// Generates a statement converting a byte buffer `__result` to a `Box<[u8]>`, keeping any
+/// `Result` or `Option` wrapper, so that each bridge only has to handle one buffer type.
+///
+/// `shape` is the result of [`bytearray_result_shape`].
+fn box_bytearray_result(shape: (bool, bool)) -> TokenStream2 {
+    match shape {
+        (true, true) => quote!(let __result = __result.map(|r| r.map(Box::<[u8]>::from));),
+        (true, false) | (false, true) => quote!(let __result = __result.map(Box::<[u8]>::from);),
+        (false, false) => quote!(let __result = Box::<[u8]>::from(__result);),
+    }
+}
+
+/// Checks whether `ty` is a tuple, optionally wrapped in `Result`.
+///
+/// If so, returns whether it was wrapped in a `Result`, along with the tuple's element types.
+fn tuple_result_elements(ty: &Type) -> Option<(bool, Vec<&Type>)> {
+    let (is_result, success_ty) = match generic_arg_if_named(ty, "Result") {
+        Some(success_ty) => (true, success_ty),
+        None => (false, ty),
+    };
+    match success_ty {
+        Type::Tuple(TypeTuple { elems, .. }) if !elems.is_empty() => {
+            Some((is_result, elems.iter().collect()))
+        }
+        _ => None,
+    }
+}
+
+/// The generic types that bridge_fn arguments and results may be wrapped in.
+const SUPPORTED_GENERIC_TYPES: &[&str] = &["Option", "Result", "Vec", "Box"];
+
+/// A summary of the types accepted by the bridges, for error messages.
+const SUPPORTED_TYPES_DESCRIPTION: &str = "integers, bool, String, &str, byte slices and arrays, \
+    bridge handles (Foo, &Foo, &mut Foo, &[&Foo]), store trait objects (&mut dyn FooStore), \
+    and Option, Result, Vec, or Box of those";
+
+/// Finds the part of `ty` that no bridge could possibly support, if any.
+///
+/// This is a syntactic check meant to catch mistakes early with a readable error, rather than a
+/// wall of unsatisfied trait bounds from inside the expansion. Types that pass may still lack the
+/// necessary `ArgTypeInfo` or `ResultTypeInfo` impls; in particular, any non-generic path might
+/// be a bridge handle.
+fn unsupported_part_of_type(ty: &Type) -> Option<&Type> {
+    match ty {
+        Type::Path(TypePath { qself: None, path }) => {
+            for segment in &path.segments {
+                if let PathArguments::AngleBracketed(generic_args) = &segment.arguments {
+                    if !SUPPORTED_GENERIC_TYPES
+                        .iter()
+                        .any(|name| segment.ident == name)
+                    {
+                        return Some(ty);
+                    }
+                    let nested = generic_args.args.iter().find_map(|arg| match arg {
+                        GenericArgument::Type(inner) => unsupported_part_of_type(inner),
+                        _ => None,
+                    });
+                    if nested.is_some() {
+                        return nested;
+                    }
+                }
+            }
+            None
+        }
+        Type::Reference(TypeReference { elem, .. }) => match &**elem {
+            Type::TraitObject(_) => None,
+            elem => unsupported_part_of_type(elem),
+        },
+        Type::Slice(TypeSlice { elem, .. })
+        | Type::Array(TypeArray { elem, .. })
+        | Type::Group(TypeGroup { elem, .. })
+        | Type::Paren(TypeParen { elem, .. }) => unsupported_part_of_type(elem),
+        Type::Tuple(TypeTuple { elems, .. }) => elems.iter().find_map(unsupported_part_of_type),
+        Type::BareFn(_) => None,
+        _ => Some(ty),
+    }
+}
+
+/// Formats a type the way it would be written by hand, e.g. `HashMap<String, u32>` rather than
+/// the token stream's `HashMap < String , u32 >`.
+fn type_to_string(ty: &Type) -> String {
+    let mut result = ty.to_token_stream().to_string();
+    for (spaced, tight) in &[
+        (" <", "<"),
+        ("< ", "<"),
+        (" >", ">"),
+        (" ,", ","),
+        (" :: ", "::"),
+        (":: ", "::"),
+        ("& ", "&"),
+        ("[ ", "["),
+        (" ]", "]"),
+        (" ;", ";"),
+        ("( ", "("),
+        (" )", ")"),
+    ] {
+        result = result.replace(spaced, tight);
+    }
+    result
+}
+
+/// Checks the argument and result types of a bridge_fn with [`unsupported_part_of_type`],
+/// reporting each problem at the user's own type.
+fn check_supported_types(sig: &syn_mid::Signature) -> Result<()> {
+    let arg_types = sig
+        .inputs
+        .iter()
+        .skip(if has_env_param(sig) { 1 } else { 0 })
+        .filter_map(|arg| match arg {
+            syn_mid::FnArg::Typed(syn_mid::PatType { ty, .. }) => Some((&**ty, "argument")),
+            // Reported separately by each bridge.
+            syn_mid::FnArg::Receiver(_) => None,
+        });
+    let result_type = match &sig.output {
+        ReturnType::Type(_, ty) => Some((&**ty, "result")),
+        ReturnType::Default => None,
+    };
+
+    let mut errors = arg_types.chain(result_type).filter_map(|(ty, position)| {
+        unsupported_part_of_type(ty).map(|unsupported| {
+            Error::new(
+                unsupported.span(),
+                format!(
+                    "type `{}` is not supported as a bridge_fn {}; supported types are {}",
+                    type_to_string(unsupported),
+                    position,
+                    SUPPORTED_TYPES_DESCRIPTION
+                ),
+            )
+        })
+    });
+    match errors.next() {
+        Some(mut error) => {
+            errors.for_each(|next| error.combine(next));
+            Err(error)
+        }
+        None => Ok(()),
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 enum ResultKind {
     Regular,
     Buffer,
     Void,
+    Bool,
 }
 
 impl ResultKind {
-    fn has_env(self) -> bool {
-        match self {
-            Self::Regular | Self::Void => false,
-            Self::Buffer => true,
+    /// Picks the result kind for a `bridge_fn` from its return type, syntactically:
+    ///
+    /// - no return type, `()`, or `Result<()>`: `Void`
+    /// - `bool` or `Result<bool>`: `Bool`
+    /// - `E::Buffer`, optionally wrapped in `Result` and/or `Option`: `Buffer`
+    /// - anything else, including byte buffers like `Vec<u8>`: `Regular`
+    fn for_output(output: &ReturnType) -> Self {
+        let ty = match output {
+            ReturnType::Default => return Self::Void,
+            ReturnType::Type(_, ty) => &**ty,
+        };
+        let success_ty = generic_arg_if_named(ty, "Result").unwrap_or(ty);
+        let buffer_ty = generic_arg_if_named(success_ty, "Option").unwrap_or(success_ty);
+        match (success_ty, buffer_ty) {
+            (Type::Tuple(TypeTuple { elems, .. }), _) if elems.is_empty() => Self::Void,
+            (Type::Path(path), _) if path.path.is_ident("bool") => Self::Bool,
+            (_, Type::Path(TypePath { qself: None, path }))
+                if path.segments.len() == 2
+                    && path
+                        .segments
+                        .last()
+                        .map_or(false, |last| last.ident == "Buffer") =>
+            {
+                Self::Buffer
+            }
+            _ => Self::Regular,
         }
     }
 }
 
-fn bridge_fn_impl(attr: TokenStream, item: TokenStream, result_kind: ResultKind) -> TokenStream {
-    let function = parse_macro_input!(item as ItemFn);
+/// The options accepted by all the `bridge_fn` attributes.
+const BRIDGE_FN_OPTIONS: &[&str] = &[
+    "ffi",
+    "jni",
+    "node",
+    "wasm",
+    "jni_async",
+    "jni_direct_buffers",
+    "ffi_async",
+    "node_result_fields",
+    "ts_module",
+    "forward_docs",
+];
 
-    let item_names =
-        parse_macro_input!(attr with Punctuated<MetaNameValue, Token![,]>::parse_terminated);
-    let ffi_name = match name_for_meta_key(&item_names, "ffi", cfg!(feature = "ffi"), || {
-        ffi::name_from_ident(&function.sig.ident)
+/// Expands a `bridge_fn`, using `result_kind` if given and otherwise picking one with
+/// [`ResultKind::for_output`].
+fn bridge_fn_impl(
+    attr: TokenStream,
+    item: TokenStream,
+    result_kind: Option<ResultKind>,
+) -> TokenStream {
+    match expand_bridge_fn(attr.into(), item.into(), result_kind) {
+        Ok(tokens) => tokens.into(),
+        Err(error) => error.to_compile_error().into(),
+    }
+}
+
+fn expand_bridge_fn(
+    attr: TokenStream2,
+    item: TokenStream2,
+    result_kind: Option<ResultKind>,
+) -> Result<TokenStream2> {
+    let function: ItemFn = parse2(item)?;
+    let result_kind = result_kind.unwrap_or_else(|| ResultKind::for_output(&function.sig.output));
+
+    let item_names = Punctuated::<MetaNameValue, Token![,]>::parse_terminated.parse2(attr)?;
+    if let Some(unknown) = item_names.iter().find(|meta| {
+        !BRIDGE_FN_OPTIONS
+            .iter()
+            .any(|option| meta.path.is_ident(option))
     }) {
-        Ok(name) => name,
-        Err(error) => return error.to_compile_error().into(),
+        return Err(Error::new(
+            unknown.path.span(),
+            format!(
+                "unknown bridge_fn option (expected one of: {})",
+                BRIDGE_FN_OPTIONS.join(", ")
+            ),
+        ));
+    }
+
+    let explicitly_disabled = |key| {
+        matches!(
+            value_for_meta_key(&item_names, key),
+            Some(Lit::Bool(LitBool { value: false, .. }))
+        )
     };
-    let jni_name = match name_for_meta_key(&item_names, "jni", cfg!(feature = "jni"), || {
+    // WASM follows Node unless it's given its own name (or disabled) explicitly.
+    let wasm_enabled = match value_for_meta_key(&item_names, "wasm") {
+        Some(_) => !explicitly_disabled("wasm"),
+        None => !explicitly_disabled("node"),
+    };
+    if ["ffi", "jni", "node"]
+        .iter()
+        .all(|key| explicitly_disabled(key))
+        && !wasm_enabled
+    {
+        return Err(Error::new(
+            item_names.span(),
+            "bridge_fn must be exposed to at least one bridge",
+        ));
+    }
+
+    let ffi_name = name_for_meta_key(&item_names, "ffi", cfg!(feature = "ffi"), || {
+        ffi::name_from_ident(&function.sig.ident)
+    })?;
+    let jni_name = name_for_meta_key(&item_names, "jni", cfg!(feature = "jni"), || {
         jni::name_from_ident(&function.sig.ident)
-    }) {
-        Ok(name) => name,
-        Err(error) => return error.to_compile_error().into(),
+    })?;
+    let node_name = name_for_meta_key(&item_names, "node", cfg!(feature = "node"), || {
+        node::name_from_ident(&function.sig.ident)
+    })?;
+    let wasm_key = match value_for_meta_key(&item_names, "wasm") {
+        Some(_) => "wasm",
+        None => "node",
     };
-    let node_name = match name_for_meta_key(&item_names, "node", cfg!(feature = "node"), || {
+    let wasm_name = name_for_meta_key(&item_names, wasm_key, cfg!(feature = "wasm"), || {
         node::name_from_ident(&function.sig.ident)
-    }) {
-        Ok(name) => name,
-        Err(error) => return error.to_compile_error().into(),
+    })?;
+
+    let jni_async = flag_for_meta_key(&item_names, "jni_async")?;
+    let jni_direct_buffers = flag_for_meta_key(&item_names, "jni_direct_buffers")?;
+    let ffi_async = flag_for_meta_key(&item_names, "ffi_async")?;
+    let docs = if flag_for_meta_key(&item_names, "forward_docs")? {
+        forwarded_docs(&function.attrs)
+    } else {
+        vec![]
     };
+    let ts_module = match value_for_meta_key(&item_names, "ts_module") {
+        Some(Lit::Str(module)) => match parse_str::<Ident>(&module.value()) {
+            Ok(_) => Some(module.value()),
+            Err(_) => {
+                return Err(Error::new(
+                    module.span(),
+                    "ts_module must be a TypeScript identifier",
+                ))
+            }
+        },
+        Some(value) => {
+            return Err(Error::new(
+                value.span(),
+                "ts_module must be a string literal",
+            ))
+        }
+        // TESTING_ functions aren't part of the library, so they aren't declared alongside it.
+        None if function.sig.ident.to_string().starts_with("TESTING_") => {
+            Some("Testing".to_string())
+        }
+        None => None,
+    };
+    // Without an Env, a buffer result can only be an ordinary byte buffer, which the bridges
+    // already handle for a regular bridge_fn.
+    let result_kind = match (result_kind, &function.sig.output) {
+        (ResultKind::Buffer, ReturnType::Type(_, ty)) if !has_env_param(&function.sig) => {
+            if bytearray_result_shape(ty).is_none() {
+                return Err(Error::new(
+                    ty.span(),
+                    "a buffer result without an Env parameter must be Vec<u8>, Box<[u8]>, or \
+                     [u8; N]",
+                ));
+            }
+            ResultKind::Regular
+        }
+        _ => result_kind,
+    };
+
+    let node_result_fields = node_result_fields(
+        &item_names,
+        &function.sig.output,
+        result_kind,
+        !explicitly_disabled("node") || wasm_enabled,
+    )?;
 
     let ffi_feature = ffi_name.as_ref().map(|_| quote!(feature = "ffi"));
     let jni_feature = jni_name.as_ref().map(|_| quote!(feature = "jni"));
     let node_feature = node_name.as_ref().map(|_| quote!(feature = "node"));
-    let maybe_features = [ffi_feature, jni_feature, node_feature];
+    let wasm_feature = wasm_name.as_ref().map(|_| quote!(feature = "wasm"));
+    let maybe_features = [ffi_feature, jni_feature, node_feature, wasm_feature];
     let feature_list = maybe_features.iter().flatten();
 
-    let ffi_fn = ffi_name.map(|name| ffi::bridge_fn(name, &function.sig, result_kind));
-    let jni_fn = jni_name.map(|name| jni::bridge_fn(name, &function.sig, result_kind));
-    let node_fn = node_name.map(|name| node::bridge_fn(name, &function.sig, result_kind));
+    // Skip generating entry points for unsupported types; they would only add a pile of trait
+    // errors pointing into the expansion.
+    if let Err(error) = check_supported_types(&function.sig) {
+        let error = error.to_compile_error();
+        return Ok(quote!(
+            #[allow(non_snake_case)]
+            #[cfg(any(#(#feature_list,)*))]
+            #function
+
+            #error
+        ));
+    }
+
+    // Deprecating the function deprecates all of its entry points. They still call the function,
+    // which shouldn't produce warnings of its own.
+    let deprecated: Vec<&Attribute> = function
+        .attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("deprecated"))
+        .collect();
+    let deprecation = if deprecated.is_empty() {
+        quote!()
+    } else {
+        quote!(#(#deprecated)* #[allow(deprecated)])
+    };
+    // For TypeScript, the deprecation is only visible through a JSDoc tag.
+    let mut node_docs = docs.clone();
+    if let Some(attr) = deprecated.first() {
+        node_docs.push(match deprecation_note(attr) {
+            Some(note) => format!(" @deprecated {}", note),
+            None => " @deprecated".to_string(),
+        });
+    }
+
+    // The Node bridge puts the docs right above the "ts:" annotation instead, so that gen-ts-decls
+    // can find them.
+    let doc_attrs = quote!(#(#[doc = #docs])*);
+    let ffi_fn = ffi_name.map(|name| {
+        let ffi_fn = ffi::bridge_fn(name, &function.sig, result_kind, ffi_async);
+        quote!(#deprecation #doc_attrs #ffi_fn)
+    });
+    let jni_fn = jni_name.map(|name| {
+        let jni_fn = jni::bridge_fn(
+            name,
+            &function.sig,
+            result_kind,
+            jni_async,
+            jni_direct_buffers,
+        );
+        quote!(#deprecation #doc_attrs #jni_fn)
+    });
+    let node_fn = node_name.map(|name| {
+        let node_fn = node::bridge_fn(
+            name,
+            &function.sig,
+            result_kind,
+            node_result_fields.as_deref(),
+            ts_module.as_deref(),
+            &node_docs,
+        );
+        quote!(#deprecation #node_fn)
+    });
+    let wasm_fn = wasm_name.map(|name| {
+        let wasm_fn = wasm::bridge_fn(
+            name,
+            &function.sig,
+            result_kind,
+            node_result_fields.as_deref(),
+        );
+        quote!(#deprecation #doc_attrs #wasm_fn)
+    });
 
-    quote!(
+    Ok(quote!(
         #[allow(non_snake_case)]
         #[cfg(any(#(#feature_list,)*))]
         #function
@@ -238,13 +1053,74 @@ fn bridge_fn_impl(attr: TokenStream, item: TokenStream, result_kind: ResultKind)
         #jni_fn
 
         #node_fn
-    )
-    .into()
+
+        #wasm_fn
+    ))
+}
+
+/// Parses the `node_result_fields` option, checking it against the function's result type.
+///
+/// Returns `None` if the function does not return a tuple.
+fn node_result_fields(
+    meta_values: &Punctuated<MetaNameValue, Token![,]>,
+    output: &ReturnType,
+    result_kind: ResultKind,
+    node_enabled: bool,
+) -> Result<Option<Vec<String>>> {
+    let tuple_elements = match (result_kind, output) {
+        (ResultKind::Regular, ReturnType::Type(_, ty)) => tuple_result_elements(ty),
+        _ => None,
+    };
+    if let Some((_, elements)) = &tuple_elements {
+        if !(2..=3).contains(&elements.len()) {
+            return Err(Error::new(
+                output.span(),
+                "bridge_fn can only return tuples of 2 or 3 elements",
+            ));
+        }
+    }
+
+    match (
+        value_for_meta_key(meta_values, "node_result_fields"),
+        tuple_elements,
+    ) {
+        (Some(Lit::Str(fields)), Some((_, elements))) => {
+            let names: Vec<String> = fields
+                .value()
+                .split(',')
+                .map(|name| name.trim().to_string())
+                .collect();
+            if names.len() != elements.len() || names.iter().any(String::is_empty) {
+                return Err(Error::new(
+                    fields.span(),
+                    format!(
+                        "expected {} comma-separated field names to match the result tuple",
+                        elements.len()
+                    ),
+                ));
+            }
+            Ok(Some(names))
+        }
+        (Some(Lit::Str(fields)), None) => Err(Error::new(
+            fields.span(),
+            "node_result_fields can only be used with a tuple result",
+        )),
+        (Some(value), _) => Err(Error::new(
+            value.span(),
+            "node_result_fields must be a string literal",
+        )),
+        (None, Some(_)) if node_enabled => Err(Error::new(
+            output.span(),
+            "tuple results need node_result_fields to name their fields for Node",
+        )),
+        (None, _) => Ok(None),
+    }
 }
 
-/// Generates C, Java, and Node entry points for a Rust function that returns a value.
+/// Generates C, Java, Node, and WASM entry points for a Rust function.
 ///
-/// See the [crate-level documentation](crate) for more information.
+/// How the result is returned depends on the function's return type; see "Result types" in the
+/// [crate-level documentation](crate), which has more information.
 ///
 /// # Example
 ///
@@ -259,17 +1135,15 @@ fn bridge_fn_impl(attr: TokenStream, item: TokenStream, result_kind: ResultKind)
 /// ```
 #[proc_macro_attribute]
 pub fn bridge_fn(attr: TokenStream, item: TokenStream) -> TokenStream {
-    bridge_fn_impl(attr, item, ResultKind::Regular)
+    bridge_fn_impl(attr, item, None)
 }
 
 /// Generates C, Java, and Node entry points for a Rust function that returns a buffer,
 /// avoiding unnecessary copies of the return value.
 ///
-/// Unlike a normal `bridge_fn`, functions annotated with `bridge_fn_buffer` take an extra initial
-/// parameter of type `E: Env` and have a return type containing `E::Buffer`. All other parameters
-/// behave the same as they do in a normal `bridge_fn`.
-///
-/// See the [crate-level documentation](crate) for more information.
+/// Deprecated: a plain `bridge_fn` whose return type contains `E::Buffer` behaves the same way.
+/// Unlike `bridge_fn`, this always treats the result as a buffer, so a function without an `Env`
+/// parameter must return `Vec<u8>`, `Box<[u8]>`, or `[u8; N]`.
 ///
 /// # Example
 ///
@@ -283,17 +1157,16 @@ pub fn bridge_fn(attr: TokenStream, item: TokenStream) -> TokenStream {
 ///   e.buffer(result)
 /// }
 /// ```
+#[deprecated(note = "use `bridge_fn`, which picks the result kind from the return type")]
 #[proc_macro_attribute]
 pub fn bridge_fn_buffer(attr: TokenStream, item: TokenStream) -> TokenStream {
-    bridge_fn_impl(attr, item, ResultKind::Buffer)
+    bridge_fn_impl(attr, item, Some(ResultKind::Buffer))
 }
 
 /// Generates C, Java, and Node entry points for a Rust function that returns `Result<(), _>`.
 ///
-/// Because the C bindings conventions use out-parameters for successful return values,
-/// a case of "no result on success" must be annotated specially.
-///
-/// See the [crate-level documentation](crate) for more information.
+/// Deprecated: a plain `bridge_fn` returning `()` or `Result<()>` behaves the same way. Unlike
+/// `bridge_fn`, this discards any other success value.
 ///
 /// # Example
 ///
@@ -306,7 +1179,1131 @@ pub fn bridge_fn_buffer(attr: TokenStream, item: TokenStream) -> TokenStream {
 ///   // ...
 /// }
 /// ```
+#[deprecated(note = "use `bridge_fn`, which picks the result kind from the return type")]
 #[proc_macro_attribute]
 pub fn bridge_fn_void(attr: TokenStream, item: TokenStream) -> TokenStream {
-    bridge_fn_impl(attr, item, ResultKind::Void)
+    bridge_fn_impl(attr, item, Some(ResultKind::Void))
+}
+
+/// Generates C, Java, and Node entry points for a Rust function that returns `bool` or
+/// `Result<bool, _>`.
+///
+/// Deprecated: a plain `bridge_fn` with either return type behaves the same way.
+///
+/// # Example
+///
+/// ```ignore
+/// // Produces a C function named "signal_post_key_is_valid"
+/// // and a JNI function named "PostKey_1IsValid" (with JNI "_1" mangling for an underscore),
+/// // with the Node entry point disabled.
+/// #[bridge_fn_bool(node = false)]
+/// fn PostKey_IsValid(post_key: &PostKey) -> Result<bool> {
+///   // ...
+/// }
+/// ```
+#[deprecated(note = "use `bridge_fn`, which picks the result kind from the return type")]
+#[proc_macro_attribute]
+pub fn bridge_fn_bool(attr: TokenStream, item: TokenStream) -> TokenStream {
+    bridge_fn_impl(attr, item, Some(ResultKind::Bool))
+}
+
+#[cfg(all(test, feature = "ffi", feature = "jni", feature = "node"))]
+mod tests {
+    use super::*;
+
+    fn expand(attr: TokenStream2) -> String {
+        let item = quote!(
+            fn SessionCipher_Encrypt(message: &[u8]) -> Result<u32> {}
+        );
+        expand_bridge_fn(attr, item, None)
+            .expect("valid bridge_fn")
+            .to_string()
+    }
+
+    const DEFAULT_FFI: &str = "fn signal_session_cipher_encrypt (";
+    const DEFAULT_JNI: &str = "fn Java_org_signal_client_internal_Native_SessionCipher_1Encrypt (";
+    const DEFAULT_NODE: &str = "node_register ! (SessionCipher_Encrypt , 1usize)";
+
+    const CUSTOM_FFI: &str = "fn signal_session_encrypt (";
+    const CUSTOM_JNI: &str = "fn Java_org_signal_client_internal_Native_Cipher_1Encrypt (";
+    const CUSTOM_NODE: &str = "node_register ! (sessionCipherEncrypt , 1usize)";
+
+    #[test]
+    fn test_name_overrides() {
+        let ffi_overrides = [
+            (quote!(), DEFAULT_FFI),
+            (quote!(ffi = "session_encrypt",), CUSTOM_FFI),
+        ];
+        let jni_overrides = [
+            (quote!(), DEFAULT_JNI),
+            (quote!(jni = "Cipher_1Encrypt",), CUSTOM_JNI),
+        ];
+        let node_overrides = [
+            (quote!(), DEFAULT_NODE),
+            (quote!(node = "sessionCipherEncrypt",), CUSTOM_NODE),
+        ];
+
+        for (ffi_attr, ffi_expected) in &ffi_overrides {
+            for (jni_attr, jni_expected) in &jni_overrides {
+                for (node_attr, node_expected) in &node_overrides {
+                    let attr = quote!(#ffi_attr #jni_attr #node_attr);
+                    let expanded = expand(attr.clone());
+                    for expected in &[ffi_expected, jni_expected, node_expected] {
+                        assert!(
+                            expanded.contains(*expected),
+                            "missing {:?} for attribute ({})",
+                            expected,
+                            attr
+                        );
+                    }
+                    for unexpected in &[
+                        DEFAULT_FFI,
+                        DEFAULT_JNI,
+                        DEFAULT_NODE,
+                        CUSTOM_FFI,
+                        CUSTOM_JNI,
+                        CUSTOM_NODE,
+                    ] {
+                        if ![ffi_expected, jni_expected, node_expected].contains(&unexpected) {
+                            assert!(
+                                !expanded.contains(*unexpected),
+                                "unexpected {:?} for attribute ({})",
+                                unexpected,
+                                attr
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_node_name_override_in_ts_decl() {
+        let expanded = expand(quote!(node = "sessionCipherEncrypt"));
+        assert!(expanded
+            .contains("\"ts: export function sessionCipherEncrypt(message: Uint8Like): number\""));
+        assert!(!expanded.contains("ts: export function SessionCipher_Encrypt"));
+    }
+
+    #[test]
+    fn test_ts_types() {
+        let expand_node = |item: TokenStream2| {
+            expand_bridge_fn(quote!(ffi = false, jni = false), item, None)
+                .expect("valid bridge_fn")
+                .to_string()
+        };
+
+        let expanded = expand_node(quote!(
+            fn Foo_Update(
+                foo: &mut Foo,
+                other_foo: Option<&Foo>,
+                name: &str,
+                count: Option<u32>,
+                is_final: bool,
+            ) -> Result<Vec<Box<[u8]>>> {
+            }
+        ));
+        assert!(expanded.contains(
+            "\"ts: export function Foo_Update(foo: Wrapper<Foo>, otherFoo: Wrapper<Foo> | null, \
+             name: string, count: number | null, isFinal: boolean): Buffer[]\""
+        ));
+
+        let expanded = expand_node(quote!(
+            async fn Foo_Load(id: u64, store: &mut dyn FooStore) -> Result<Option<Foo>> {}
+        ));
+        assert!(expanded.contains(
+            "\"ts: export function Foo_Load(id: bigint, store: FooStore): \
+             Promise<Foo | null>\""
+        ));
+
+        // Types substituted by macro_rules are wrapped in invisible groups.
+        let foo_group = proc_macro2::Group::new(proc_macro2::Delimiter::None, quote!(Foo));
+        let expanded = expand_node(quote!(
+            fn Foo_Get(foo: &#foo_group) -> Option<#foo_group> {}
+        ));
+        assert!(expanded.contains("\"ts: export function Foo_Get(foo: Wrapper<Foo>): Foo | null\""));
+
+        for item in &[
+            quote!(
+                fn Foo_Get(foo: &Foo, size: usize) {}
+            ),
+            quote!(
+                fn Foo_Get(foo: &Foo) -> HashMap<u32, u32> {}
+            ),
+            quote!(
+                fn Foo_Get(foo: (u32, u32)) {}
+            ),
+        ] {
+            let expanded = expand_node(item.clone());
+            assert!(expanded.contains("compile_error"), "{}", item);
+            assert!(!expanded.contains("ts: export function"), "{}", item);
+        }
+    }
+
+    #[test]
+    fn test_bytearray_results() {
+        let cases = [
+            (quote!(Vec<u8>), "Buffer"),
+            (quote!(Box<[u8]>), "Buffer"),
+            (quote!([u8; 32]), "Buffer"),
+            (quote!(Result<Vec<u8>>), "Buffer"),
+            (quote!(Option<Box<[u8]>>), "Buffer | null"),
+            (quote!(Result<Option<[u8; 32]>>), "Buffer | null"),
+        ];
+        for (result_ty, ts_type) in &cases {
+            let item = quote!(
+                fn Foo_GetBytes(foo: &Foo) -> #result_ty {}
+            );
+            let expanded = expand_bridge_fn(quote!(), item, None)
+                .expect("valid bridge_fn")
+                .to_string();
+            assert!(
+                expanded.contains("out_len : * mut libc :: size_t"),
+                "missing FFI length for {}",
+                result_ty
+            );
+            assert!(
+                expanded.contains(") -> jni :: jbyteArray {"),
+                "wrong JNI result type for {}",
+                result_ty
+            );
+            assert!(
+                expanded.contains(&format!(
+                    "\"ts: export function Foo_GetBytes(foo: Wrapper<Foo>): {}\"",
+                    ts_type
+                )),
+                "wrong TypeScript result type for {}",
+                result_ty
+            );
+            assert!(expanded.contains("Box :: < [u8] > :: from"));
+        }
+    }
+
+    #[test]
+    fn test_non_bytearray_results() {
+        for result_ty in &[
+            quote!(Vec<u32>),
+            quote!(Box<Foo>),
+            quote!([u32; 4]),
+            quote!(u8),
+        ] {
+            let item = quote!(
+                fn Foo_Get(foo: &Foo) -> #result_ty {}
+            );
+            let expanded = expand_bridge_fn(quote!(), item, None)
+                .expect("valid bridge_fn")
+                .to_string();
+            assert!(!expanded.contains("out_len"), "{}", result_ty);
+            assert!(
+                !expanded.contains("Box :: < [u8] > :: from"),
+                "{}",
+                result_ty
+            );
+        }
+    }
+
+    #[test]
+    fn test_bool_results() {
+        for result_ty in &[quote!(bool), quote!(Result<bool, SignalProtocolError>)] {
+            let item = quote!(
+                fn Foo_IsValid(foo: &Foo) -> #result_ty {}
+            );
+            let expanded = expand_bridge_fn(quote!(), item, None)
+                .expect("valid bridge_fn")
+                .to_string();
+            assert!(!expanded.contains("compile_error"), "{}", expanded);
+            assert!(
+                expanded.contains(
+                    "fn signal_foo_is_valid (out : * mut bool , foo : ffi_arg_type ! (& Foo))"
+                ),
+                "{}",
+                expanded
+            );
+            assert!(
+                expanded.contains("ffi :: write_result_to (out , __result) ?"),
+                "{}",
+                expanded
+            );
+            assert!(
+                expanded.contains("foo : jni_arg_type ! (& Foo)) -> jni :: jboolean"),
+                "{}",
+                expanded
+            );
+            assert!(
+                expanded
+                    .contains("\"ts: export function Foo_IsValid(foo: Wrapper<Foo>): boolean\""),
+                "{}",
+                expanded
+            );
+        }
+    }
+
+    #[test]
+    fn test_tuple_results() {
+        let item = quote!(
+            fn Foo_Split(foo: &Foo) -> Result<(Vec<u8>, u32)> {}
+        );
+        let expanded = expand_bridge_fn(quote!(node_result_fields = "data, count"), item, None)
+            .expect("valid bridge_fn")
+            .to_string();
+        assert!(expanded.contains(
+            "out_0 : * mut * const libc :: c_uchar , out_0_len : * mut libc :: size_t , \
+             out_1 : * mut ffi_result_type ! (u32) ,"
+        ));
+        assert!(expanded.contains("let (__result_0 , __result_1) = __result ? ;"));
+        assert!(expanded.contains("-> jni_result_type ! (Result < (Vec < u8 > , u32) >)"));
+        assert!(expanded.contains("node :: NamedFields { names : & [\"data\" , \"count\"]"));
+        assert!(expanded.contains(
+            "\"ts: export function Foo_Split(foo: Wrapper<Foo>): { data: Buffer, count: number }\""
+        ));
+
+        let item = quote!(
+            fn Foo_Split3(foo: &Foo) -> (Option<Box<[u8]>>, u64, bool) {}
+        );
+        let expanded = expand_bridge_fn(quote!(node_result_fields = "data, id, ok"), item, None)
+            .expect("valid bridge_fn")
+            .to_string();
+        assert!(expanded.contains(
+            "out_0 : * mut * const libc :: c_uchar , out_0_len : * mut libc :: size_t , \
+             out_1 : * mut ffi_result_type ! (u64) , out_2 : * mut ffi_result_type ! (bool) ,"
+        ));
+        assert!(expanded.contains("let (__result_0 , __result_1 , __result_2) = __result ;"));
+        assert!(expanded.contains("ffi :: write_optional_bytearray_to (out_0 , out_0_len"));
+        assert!(expanded.contains("-> jni_result_type ! ((Option < Box < [u8] > > , u64 , bool))"));
+        assert!(expanded.contains("node :: NamedFields { names : & [\"data\" , \"id\" , \"ok\"]"));
+        assert!(expanded.contains(
+            "\"ts: export function Foo_Split3(foo: Wrapper<Foo>): \
+             { data: Buffer | null, id: bigint, ok: boolean }\""
+        ));
+    }
+
+    #[test]
+    fn test_tuple_result_errors() {
+        let expand_tuple = |attr: TokenStream2, result_ty: TokenStream2| {
+            let item = quote!(
+                fn Foo_Split(foo: &Foo) -> #result_ty {}
+            );
+            expand_bridge_fn(attr, item, None).map(|tokens| tokens.to_string())
+        };
+
+        // Node needs field names, but the other bridges don't.
+        assert!(expand_tuple(quote!(), quote!((u32, bool))).is_err());
+        assert!(expand_tuple(quote!(node = false), quote!((u32, bool))).is_ok());
+
+        for (attr, result_ty) in &[
+            (quote!(node_result_fields = "a, b"), quote!((u32, bool, u8))),
+            (quote!(node_result_fields = "a, "), quote!((u32, bool))),
+            (quote!(node_result_fields = true), quote!((u32, bool))),
+            (quote!(node_result_fields = "a"), quote!(u32)),
+            (quote!(node = false), quote!((u32,))),
+            (quote!(node = false), quote!((u32, u32, u32, u32))),
+        ] {
+            assert!(
+                expand_tuple(attr.clone(), result_ty.clone()).is_err(),
+                "{} -> {}",
+                attr,
+                result_ty
+            );
+        }
+    }
+
+    #[test]
+    fn test_disabled_bridges() {
+        let expanded = expand(quote!(ffi = false, node = false));
+        assert!(!expanded.contains(DEFAULT_FFI));
+        assert!(expanded.contains(DEFAULT_JNI));
+        assert!(!expanded.contains("node_register"));
+    }
+
+    #[test]
+    fn test_custom_names_with_node_disabled() {
+        let expanded = expand(quote!(
+            ffi = "custom_name",
+            jni = "CustomName",
+            node = false
+        ));
+        assert!(expanded.contains("fn signal_custom_name ("));
+        assert!(expanded.contains("fn Java_org_signal_client_internal_Native_CustomName ("));
+        assert!(!expanded.contains("node_register"));
+        assert!(!expanded.contains("ts: export function"));
+        assert!(!expanded.contains("feature = \"node\""));
+    }
+
+    #[test]
+    #[cfg(feature = "wasm")]
+    fn test_wasm_follows_node() {
+        const DEFAULT_WASM: &str = "wasm_bindgen (js_name = \"SessionCipher_Encrypt\")";
+
+        let expanded = expand(quote!());
+        assert!(expanded.contains(DEFAULT_WASM));
+        assert!(expanded.contains("pub fn wasm_SessionCipher_Encrypt (message : wasm :: JsValue)"));
+
+        let expanded = expand(quote!(node = "sessionCipherEncrypt"));
+        assert!(expanded.contains("wasm_bindgen (js_name = \"sessionCipherEncrypt\")"));
+
+        let expanded = expand(quote!(node = false));
+        assert!(!expanded.contains("wasm_bindgen"));
+        assert!(!expanded.contains("feature = \"wasm\""));
+
+        let expanded = expand(quote!(node = false, wasm = "encrypt"));
+        assert!(expanded.contains("wasm_bindgen (js_name = \"encrypt\")"));
+        assert!(!expanded.contains("node_register"));
+
+        let expanded = expand(quote!(wasm = false));
+        assert!(!expanded.contains("wasm_bindgen"));
+        assert!(expanded.contains(DEFAULT_NODE));
+
+        let item = quote!(
+            fn SessionCipher_Encrypt(message: &[u8]) -> Result<u32> {}
+        );
+        assert!(expand_bridge_fn(
+            quote!(ffi = false, jni = false, node = false, wasm = "encrypt"),
+            item,
+            None
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn test_all_bridges_disabled() {
+        let item = quote!(
+            fn SessionCipher_Encrypt(message: &[u8]) -> Result<u32> {}
+        );
+        let error = expand_bridge_fn(quote!(ffi = false, jni = false, node = false), item, None)
+            .expect_err("should reject disabling every bridge");
+        assert_eq!(
+            error.to_string(),
+            "bridge_fn must be exposed to at least one bridge"
+        );
+    }
+
+    #[test]
+    fn test_jni_direct_buffers() {
+        let item = quote!(
+            fn Foo_Decrypt(foo: &Foo, ctext: &[u8], ad: Option<&[u8]>, out: &mut [u8]) {}
+        );
+        let expanded = expand_bridge_fn(quote!(jni_direct_buffers = true), item.clone(), None)
+            .expect("valid bridge_fn")
+            .to_string();
+        assert!(expanded.contains("ctext : jni :: JByteBuffer"));
+        assert!(expanded.contains("ad : jni :: JByteBuffer"));
+        assert!(expanded.contains("out : jni_arg_type ! (& mut [u8])"));
+        assert!(expanded.contains("foo : jni_arg_type ! (& Foo)"));
+        // FFI is unaffected.
+        assert!(expanded.contains("ctext_len : libc :: size_t"));
+
+        let expanded = expand_bridge_fn(quote!(), item, None)
+            .expect("valid bridge_fn")
+            .to_string();
+        assert!(!expanded.contains("JByteBuffer"));
+    }
+
+    #[test]
+    fn test_jni_byte_slice_args() {
+        let expanded = expand_bridge_fn(
+            quote!(ffi = false, node = false),
+            quote!(
+                fn Foo_Bar(foo: &Foo, data: &[u8], more: Option<&[u8]>, out: &mut [u8]) {}
+            ),
+            None,
+        )
+        .expect("valid bridge_fn")
+        .to_string();
+        assert!(expanded.contains(
+            "foo : jni_arg_type ! (& Foo) , data : jni :: jbyteArray , more : jni :: jbyteArray , out : jni_arg_type ! (& mut [u8])"
+        ));
+        assert!(expanded.contains(
+            "let mut data = < & [u8] as jni :: ArgTypeInfo > :: borrow (& env , data) ?"
+        ));
+        assert!(expanded.contains(
+            "let mut more = < Option < & [u8] > as jni :: ArgTypeInfo > :: borrow (& env , more) ?"
+        ));
+        assert!(!expanded.contains("jni_arg_type ! (& [u8])"));
+        assert!(!expanded.contains("jni_arg_type ! (Option < & [u8] >)"));
+    }
+
+    #[test]
+    fn test_jni_pinned_buffers() {
+        let expanded = expand_bridge_fn(
+            quote!(ffi = false, node = false),
+            quote!(
+                fn Foo_Decrypt(ctext: &[u8], foo: &Foo, ad: Option<&[u8]>, text: String) {}
+            ),
+            None,
+        )
+        .expect("valid bridge_fn")
+        .to_string();
+        // Byte slices are pinned after everything else is loaded.
+        let last_load = expanded
+            .rfind("ArgTypeInfo > :: load_from (")
+            .expect("has loads");
+        for (arg, ty) in &[("ctext", "& [u8]"), ("ad", "Option < & [u8] >")] {
+            let pin = format!(
+                "let {} = < {} as jni :: ArgTypeInfo > :: load_from_pinned",
+                arg, ty
+            );
+            assert!(expanded.find(&pin).expect("pinned") > last_load);
+        }
+
+        // Functions that might call back into Java never pin.
+        let expanded = expand_bridge_fn(
+            quote!(ffi = false, node = false),
+            quote!(
+                fn Foo_Process(ctext: &[u8], store: &mut dyn SessionStore) {}
+            ),
+            None,
+        )
+        .expect("valid bridge_fn")
+        .to_string();
+        assert!(!expanded.contains("load_from_pinned"));
+
+        let expanded = expand_bridge_fn(
+            quote!(ffi = false, node = false),
+            quote!(
+                fn Foo_Process<E: Env>(env: E, ctext: &[u8]) -> Result<E::Buffer> {}
+            ),
+            Some(ResultKind::Buffer),
+        )
+        .expect("valid bridge_fn")
+        .to_string();
+        assert!(!expanded.contains("load_from_pinned"));
+    }
+
+    #[test]
+    fn test_ffi_async() {
+        let expand_async = |item: TokenStream2, result_kind| {
+            expand_bridge_fn(
+                quote!(ffi_async = true, jni = false, node = false),
+                item,
+                result_kind,
+            )
+            .expect("valid bridge_fn")
+            .to_string()
+        };
+
+        let expanded = expand_async(
+            quote!(
+                async fn Foo_Bar(input: u32) -> Result<u32> {}
+            ),
+            None,
+        );
+        assert!(expanded.contains(
+            "done : extern \"C\" fn (* mut ffi :: SignalFfiError , ffi_result_type ! (Result < u32 >) , * mut libc :: c_void) , ctx : * mut libc :: c_void"
+        ));
+        assert!(expanded.contains("ffi :: run_future_with_callback (done , ctx"));
+        assert!(!expanded.contains("expect_ready"));
+
+        let expanded = expand_async(
+            quote!(
+                async fn Foo_Bar(input: u32) -> Result<u32> {}
+            ),
+            Some(ResultKind::Void),
+        );
+        assert!(expanded.contains(
+            "done : extern \"C\" fn (* mut ffi :: SignalFfiError , * mut libc :: c_void)"
+        ));
+        assert!(expanded.contains("ffi :: run_void_future_with_callback (done , ctx"));
+
+        for item in &[
+            quote!(
+                fn Foo_Bar(input: u32) -> Result<u32> {}
+            ),
+            quote!(
+                async fn Foo_Bar(input: u32) -> Result<Vec<u8>> {}
+            ),
+            quote!(
+                async fn Foo_Bar(input: u32) -> (u32, bool) {}
+            ),
+        ] {
+            let expanded = expand_async(item.clone(), None);
+            assert!(expanded.contains("compile_error"), "{}", expanded);
+        }
+    }
+
+    #[test]
+    fn test_ffi_callbacks() {
+        let expanded = expand_bridge_fn(
+            quote!(jni = false, node = false),
+            quote!(
+                fn Foo_Bar(callback: unsafe extern "C" fn(u32) -> u32, log: LogCallbackFn) {}
+            ),
+            Some(ResultKind::Void),
+        )
+        .expect("valid bridge_fn")
+        .to_string();
+        assert!(expanded.contains(
+            "callback : Option < unsafe extern \"C\" fn (u32) -> u32 > , log : Option < LogCallbackFn >"
+        ));
+        assert!(expanded.contains(
+            "< ffi :: FfiCallback < unsafe extern \"C\" fn (u32) -> u32 > as ffi :: SimpleArgTypeInfo > :: convert_from (callback) ? . get ()"
+        ));
+        assert!(expanded.contains(
+            "< ffi :: FfiCallback < LogCallbackFn > as ffi :: SimpleArgTypeInfo > :: convert_from (log) ? . get ()"
+        ));
+        assert!(!expanded.contains("compile_error"));
+
+        let expanded = expand_bridge_fn(
+            quote!(jni = false, node = false),
+            quote!(
+                fn Foo_Bar(callback: fn(u32) -> u32) {}
+            ),
+            Some(ResultKind::Void),
+        )
+        .expect("valid bridge_fn")
+        .to_string();
+        assert!(expanded.contains("compile_error"), "{}", expanded);
+    }
+
+    #[test]
+    fn test_registered_arity() {
+        let expanded = expand(quote!());
+        // The result, then the message and its length.
+        assert!(expanded.contains("ffi_register ! (signal_session_cipher_encrypt , 3usize)"));
+        assert!(expanded.contains(
+            "jni_register ! (Java_org_signal_client_internal_Native_SessionCipher_1Encrypt , 1usize)"
+        ));
+
+        let expanded = expand_bridge_fn(
+            quote!(ffi_async = true),
+            quote!(
+                async fn Foo_Bar(token: CancellationToken, input: u32) -> Result<u32> {}
+            ),
+            None,
+        )
+        .expect("valid bridge_fn")
+        .to_string();
+        // The completion callback and its context, then both arguments.
+        assert!(expanded.contains("ffi_register ! (signal_foo_bar , 4usize)"));
+        assert!(expanded
+            .contains("jni_register ! (Java_org_signal_client_internal_Native_Foo_1Bar , 2usize)"));
+        // JavaScript callers cancel the returned promise instead of passing a token.
+        assert!(expanded.contains("node_register ! (Foo_Bar , 1usize)"));
+
+        let expanded = expand_bridge_fn(
+            quote!(),
+            quote!(
+                fn Foo_Bar(env: impl Env, input: &[u8]) {}
+            ),
+            Some(ResultKind::Void),
+        )
+        .expect("valid bridge_fn")
+        .to_string();
+        assert!(expanded.contains("ffi_register ! (signal_foo_bar , 2usize)"));
+        assert!(expanded
+            .contains("jni_register ! (Java_org_signal_client_internal_Native_Foo_1Bar , 1usize)"));
+        assert!(expanded.contains("node_register ! (Foo_Bar , 1usize)"));
+    }
+
+    #[test]
+    fn test_node_cancellation() {
+        let expand_node = |item: TokenStream2| {
+            expand_bridge_fn(quote!(ffi = false, jni = false), item, None)
+                .expect("valid bridge_fn")
+                .to_string()
+        };
+
+        let expanded = expand_node(quote!(
+            async fn Foo_Wait(input: u32, token: CancellationToken) -> Result<u32> {}
+        ));
+        assert!(expanded.contains(
+            "\"ts: export function Foo_Wait(input: number): CancellablePromise<number>\""
+        ));
+        assert!(expanded.contains("signal_neon_futures :: cancellable_promise"));
+        assert!(expanded.contains(
+            "let token = < CancellationToken as node :: SupportsCancellation > :: from_cancellation_token"
+        ));
+        // The token doesn't take up a JavaScript argument.
+        assert!(expanded
+            .contains("argument :: << u32 as node :: AsyncArgTypeInfo > :: ArgType > (0i32)"));
+        assert!(!expanded.contains("(1i32)"));
+
+        let expanded = expand_node(quote!(
+            async fn Foo_Wait(input: u32) -> Result<u32> {}
+        ));
+        assert!(expanded.contains("signal_neon_futures :: promise"));
+        assert!(!expanded.contains("cancellable_promise"));
+
+        let expanded = expand_node(quote!(
+            fn Foo_Wait(input: u32, token: CancellationToken) -> Result<u32> {}
+        ));
+        assert!(expanded.contains("compile_error"), "{}", expanded);
+    }
+
+    #[test]
+    fn test_ts_module() {
+        let expanded = expand(quote!(ts_module = "Testing"));
+        assert!(expanded.contains(
+            "\"ts(Testing): export function SessionCipher_Encrypt(message: Uint8Like): number\""
+        ));
+
+        // The default module is never spelled out.
+        let expanded = expand(quote!(ts_module = "Native"));
+        assert!(expanded.contains("\"ts: export function SessionCipher_Encrypt("));
+
+        for attr in &[quote!(ts_module = "not a module"), quote!(ts_module = true)] {
+            let item = quote!(
+                fn SessionCipher_Encrypt(message: &[u8]) -> Result<u32> {}
+            );
+            assert!(expand_bridge_fn(attr.clone(), item, None).is_err());
+        }
+    }
+
+    #[test]
+    fn test_testing_names() {
+        let item = quote!(
+            fn TESTING_Echo(value: u32) -> u32 {}
+        );
+        let expanded = expand_bridge_fn(quote!(), item.clone(), None)
+            .expect("valid bridge_fn")
+            .to_string();
+        assert!(expanded.contains("fn signal_testing_echo ("));
+        assert!(
+            expanded.contains("fn Java_org_signal_client_internal_NativeTesting_TESTING_1Echo (")
+        );
+        assert!(expanded
+            .contains("\"ts(Testing): export function TESTING_Echo(value: number): number\""));
+
+        // An explicit module still wins.
+        let expanded = expand_bridge_fn(quote!(ts_module = "Native"), item, None)
+            .expect("valid bridge_fn")
+            .to_string();
+        assert!(expanded.contains("\"ts: export function TESTING_Echo(value: number): number\""));
+    }
+
+    #[test]
+    fn test_wildcard_params() {
+        let item = quote!(
+            fn Foo_Bar(input: u32, _: &[u8], _: &Foo) -> Result<u32> {}
+        );
+        let expanded = expand_bridge_fn(quote!(), item, None)
+            .expect("valid bridge_fn")
+            .to_string();
+        assert!(!expanded.contains("compile_error"), "{}", expanded);
+        // Each wildcard still gets its own argument in every bridge.
+        assert!(expanded
+            .contains("__unused_1 : ffi_arg_type ! (& [u8]) , __unused_1_len : libc :: size_t"));
+        assert!(expanded
+            .contains("__unused_1 : jni :: jbyteArray , __unused_2 : jni_arg_type ! (& Foo)"));
+        assert!(expanded.contains("Foo_Bar (input , __unused_1 , __unused_2)"));
+        assert!(expanded
+            .contains("\"ts: export function Foo_Bar(input: number, unused1: Uint8Like, unused2: Wrapper<Foo>): number\""));
+
+        for item in &[
+            quote!(
+                fn Foo_Bar((a, b): (u32, u32)) {}
+            ),
+            quote!(
+                fn Foo_Bar(Wrapper(a): Wrapper) {}
+            ),
+        ] {
+            let expanded = expand_bridge_fn(quote!(), item.clone(), Some(ResultKind::Void))
+                .expect("errors are reported in the expansion")
+                .to_string();
+            assert!(
+                expanded.contains("cannot use patterns in paramater"),
+                "{}",
+                expanded
+            );
+        }
+    }
+
+    #[test]
+    fn test_env_params() {
+        let has_env = |item: TokenStream2| {
+            let function: ItemFn = parse2(item).expect("valid function");
+            has_env_param(&function.sig)
+        };
+        assert!(has_env(quote!(
+            fn Foo_Bar<E: Env>(env: E, input: u32) {}
+        )));
+        assert!(has_env(quote!(
+            fn Foo_Bar<E>(_: E)
+            where
+                E: crate::support::Env,
+            {
+            }
+        )));
+        assert!(has_env(quote!(
+            fn Foo_Bar(env: impl Env) {}
+        )));
+        assert!(!has_env(quote!(
+            fn Foo_Bar<E: Env>(input: u32, env: E) {}
+        )));
+        assert!(!has_env(quote!(
+            fn Foo_Bar<T: Clone>(env: T) {}
+        )));
+        assert!(!has_env(quote!(
+            fn Foo_Bar(env: Env) {}
+        )));
+
+        // Any kind of bridge_fn can take an Env, which each bridge passes itself.
+        let expanded = expand_bridge_fn(
+            quote!(),
+            quote!(
+                fn Foo_Bar<E: Env>(env: E, input: u32) -> Result<u32> {}
+            ),
+            None,
+        )
+        .expect("valid bridge_fn")
+        .to_string();
+        assert!(!expanded.contains("compile_error"), "{}", expanded);
+        assert!(expanded.contains("Foo_Bar (ffi :: Env , input)"));
+        assert!(expanded.contains("Foo_Bar (& env , input)"));
+        assert!(expanded.contains("Foo_Bar (& mut cx , input)"));
+        assert!(expanded.contains("\"ts: export function Foo_Bar(input: number): number\""));
+
+        let expanded = expand_bridge_fn(
+            quote!(jni = false, ffi = false),
+            quote!(
+                async fn Foo_Bar<E: Env>(env: E, input: u32) -> Result<()> {}
+            ),
+            Some(ResultKind::Void),
+        )
+        .expect("valid bridge_fn")
+        .to_string();
+        assert!(expanded.contains("Foo_Bar (node :: AsyncEnv , input)"));
+
+        // A forced buffer result without an Env is an ordinary byte buffer.
+        let expanded = expand_bridge_fn(
+            quote!(),
+            quote!(
+                fn Foo_Bar(input: &[u8]) -> Result<Vec<u8>> {}
+            ),
+            Some(ResultKind::Buffer),
+        )
+        .expect("valid bridge_fn")
+        .to_string();
+        assert!(!expanded.contains("compile_error"), "{}", expanded);
+        assert!(expanded.contains("Foo_Bar (input)"));
+        assert!(expanded.contains("Box :: < [u8] > :: from"));
+
+        let error = expand_bridge_fn(
+            quote!(),
+            quote!(
+                fn Foo_Bar(input: &[u8]) -> Result<E::Buffer> {}
+            ),
+            Some(ResultKind::Buffer),
+        )
+        .expect_err("needs an Env");
+        assert_eq!(
+            error.to_string(),
+            "a buffer result without an Env parameter must be Vec<u8>, Box<[u8]>, or [u8; N]"
+        );
+
+        let expanded = expand_bridge_fn(
+            quote!(jni_async = true, ffi = false, node = false),
+            quote!(
+                async fn Foo_Bar<E: Env>(env: E, input: u32) -> Result<u32> {}
+            ),
+            None,
+        )
+        .expect("errors are reported in the expansion")
+        .to_string();
+        assert!(
+            expanded.contains("jni_async does not support Env parameters"),
+            "{}",
+            expanded
+        );
+    }
+
+    #[test]
+    fn test_result_kind_detection() {
+        let kind = |item: TokenStream2| {
+            let function: ItemFn = parse2(item).expect("valid function");
+            ResultKind::for_output(&function.sig.output)
+        };
+        assert_eq!(
+            kind(quote!(
+                fn Foo_Bar() {}
+            )),
+            ResultKind::Void
+        );
+        assert_eq!(
+            kind(quote!(
+                fn Foo_Bar() -> () {}
+            )),
+            ResultKind::Void
+        );
+        assert_eq!(
+            kind(quote!(
+                fn Foo_Bar() -> Result<()> {}
+            )),
+            ResultKind::Void
+        );
+        assert_eq!(
+            kind(quote!(
+                fn Foo_Bar() -> Result<(), SignalProtocolError> {}
+            )),
+            ResultKind::Void
+        );
+        assert_eq!(
+            kind(quote!(
+                fn Foo_Bar() -> bool {}
+            )),
+            ResultKind::Bool
+        );
+        assert_eq!(
+            kind(quote!(
+                fn Foo_Bar() -> Result<bool> {}
+            )),
+            ResultKind::Bool
+        );
+        assert_eq!(
+            kind(quote!(
+                fn Foo_Bar<E: Env>(env: E) -> E::Buffer {}
+            )),
+            ResultKind::Buffer
+        );
+        assert_eq!(
+            kind(quote!(
+                fn Foo_Bar<E: Env>(env: E) -> Result<Option<E::Buffer>> {}
+            )),
+            ResultKind::Buffer
+        );
+        for item in &[
+            quote!(
+                fn Foo_Bar() -> u32 {}
+            ),
+            quote!(
+                fn Foo_Bar() -> Result<Vec<u8>> {}
+            ),
+            quote!(
+                fn Foo_Bar() -> Option<bool> {}
+            ),
+            quote!(
+                fn Foo_Bar() -> Result<(u32, u32)> {}
+            ),
+            quote!(
+                fn Foo_Bar() -> Result<Foo> {}
+            ),
+        ] {
+            assert_eq!(kind(item.clone()), ResultKind::Regular, "{}", item);
+        }
+
+        // The detected kinds generate the same entry points as the old macros.
+        for (item, old_kind) in &[
+            (
+                quote!(
+                    fn Foo_Bar(input: &Foo) -> Result<()> {}
+                ),
+                ResultKind::Void,
+            ),
+            (
+                quote!(
+                    fn Foo_Bar(input: &Foo) -> Result<bool> {}
+                ),
+                ResultKind::Bool,
+            ),
+            (
+                quote!(
+                    fn Foo_Bar<E: Env>(env: E, input: &Foo) -> Result<E::Buffer> {}
+                ),
+                ResultKind::Buffer,
+            ),
+        ] {
+            let detected = expand_bridge_fn(quote!(), item.clone(), None)
+                .expect("valid bridge_fn")
+                .to_string();
+            let forced = expand_bridge_fn(quote!(), item.clone(), Some(*old_kind))
+                .expect("valid bridge_fn")
+                .to_string();
+            assert_eq!(detected, forced);
+        }
+
+        let expanded = expand_bridge_fn(
+            quote!(),
+            quote!(
+                fn Foo_Bar(input: &Foo) -> Result<()> {}
+            ),
+            None,
+        )
+        .expect("valid bridge_fn")
+        .to_string();
+        assert!(expanded.contains("pub unsafe extern \"C\" fn signal_foo_bar (input :"));
+        assert!(expanded.contains("__result ? ;"));
+    }
+
+    #[test]
+    fn test_unsupported_types() {
+        let check = |item: TokenStream2| {
+            let function: ItemFn = parse2(item).expect("valid function");
+            check_supported_types(&function.sig)
+        };
+
+        for item in &[
+            quote!(
+                fn Foo_Bar(a: u32, b: &[u8], c: Option<&Foo>, d: &[&Foo], e: &mut dyn FooStore) {}
+            ),
+            quote!(
+                fn Foo_Bar(a: Option<String>) -> Result<Vec<Box<[u8]>>> {}
+            ),
+            quote!(
+                fn Foo_Bar(callback: unsafe extern "C" fn(u32)) -> Result<(Vec<u8>, u32)> {}
+            ),
+            quote!(
+                fn Foo_Bar(a: [u8; 16]) -> Result<Option<E::Buffer>> {}
+            ),
+        ] {
+            assert!(check(item.clone()).is_ok(), "{}", item);
+        }
+
+        let error = check(quote!(
+            fn Foo_Bar(map: HashMap<String, u32>) {}
+        ))
+        .expect_err("should reject HashMap");
+        assert!(
+            error.to_string().starts_with(
+                "type `HashMap<String, u32>` is not supported as a bridge_fn argument; \
+                 supported types are"
+            ),
+            "{}",
+            error
+        );
+
+        // The innermost unsupported type is reported, not the whole thing.
+        let error = check(quote!(
+            fn Foo_Bar() -> Result<Option<Rc<Foo>>> {}
+        ))
+        .expect_err("should reject Rc");
+        assert!(
+            error
+                .to_string()
+                .starts_with("type `Rc<Foo>` is not supported as a bridge_fn result"),
+            "{}",
+            error
+        );
+
+        let error = check(quote!(
+            fn Foo_Bar(a: impl Into<u32>, b: *const u8) {}
+        ))
+        .expect_err("should reject impl Trait and raw pointers");
+        assert_eq!(error.into_iter().count(), 2);
+
+        // The function is still emitted, but no entry points are.
+        let expanded = expand_bridge_fn(
+            quote!(),
+            quote!(
+                fn Foo_Bar(map: HashMap<String, u32>) {}
+            ),
+            Some(ResultKind::Void),
+        )
+        .expect("errors are reported in the expansion")
+        .to_string();
+        assert!(expanded.contains("compile_error"));
+        assert!(expanded.contains("fn Foo_Bar"));
+        assert!(!expanded.contains("ffi_arg_type"));
+    }
+
+    #[test]
+    fn test_forward_docs() {
+        let item = quote!(
+            /// Encrypts `message` with [`SessionCipher`].
+            ///
+            /// See [the spec](https://signal.org/docs/) and [`&[u8]` slices][slices].
+            ///
+            /// [slices]: https://doc.rust-lang.org/std/primitive.slice.html
+            fn SessionCipher_Encrypt(message: &[u8]) -> Result<u32> {}
+        );
+        let expanded = expand_bridge_fn(quote!(forward_docs = true), item.clone(), None)
+            .expect("valid bridge_fn")
+            .to_string();
+
+        let docs = [
+            "# [doc = \" Encrypts `message` with `SessionCipher`.\"]",
+            "# [doc = \"\"]",
+            "# [doc = \" See the spec and `&[u8]` slices.\"]",
+        ]
+        .join(" ");
+        for entry_point in &[DEFAULT_FFI, DEFAULT_JNI] {
+            let with_docs = format!(
+                "{} # [no_mangle] pub unsafe extern \"C\" {}",
+                docs, entry_point
+            );
+            assert!(expanded.contains(&with_docs), "{}", expanded);
+        }
+        assert!(expanded.contains(&format!(
+            "# [allow (non_snake_case)] {} # [doc = \"ts: export function",
+            docs
+        )));
+        assert_eq!(expanded.matches("[slices]:").count(), 1);
+        assert!(!expanded.contains(&format!("{} # [doc = \"\"]", docs)));
+
+        // The original function always keeps its docs, but they're only forwarded if requested.
+        let expanded = expand_bridge_fn(quote!(), item, None)
+            .expect("valid bridge_fn")
+            .to_string();
+        assert_eq!(expanded.matches("Encrypts `message`").count(), 1);
+    }
+
+    #[test]
+    fn test_deprecated() {
+        let expand_deprecated = |attr: TokenStream2| {
+            let item = quote!(
+                #attr
+                fn SessionCipher_Encrypt(message: &[u8]) -> Result<u32> {}
+            );
+            expand_bridge_fn(quote!(), item, None)
+                .expect("valid bridge_fn")
+                .to_string()
+        };
+
+        let expanded = expand_deprecated(quote!(#[deprecated(note = "use SessionCipher_Seal")]));
+        let attrs = "# [deprecated (note = \"use SessionCipher_Seal\")] # [allow (deprecated)]";
+        assert!(expanded.contains(&format!(
+            "{} # [no_mangle] pub unsafe extern \"C\" {}",
+            attrs, DEFAULT_FFI
+        )));
+        assert!(expanded.contains(&format!(
+            "{} # [no_mangle] pub unsafe extern \"C\" {}",
+            attrs, DEFAULT_JNI
+        )));
+        assert!(expanded.contains(&format!(
+            "{} # [allow (non_snake_case)] # [doc = \" @deprecated use SessionCipher_Seal\"] \
+             # [doc = \"ts: export function",
+            attrs
+        )));
+
+        let expanded = expand_deprecated(quote!(#[deprecated = "going away"]));
+        assert!(expanded.contains("# [doc = \" @deprecated going away\"] # [doc = \"ts:"));
+
+        let expanded = expand_deprecated(quote!(#[deprecated]));
+        assert!(expanded.contains("# [doc = \" @deprecated\"] # [doc = \"ts:"));
+        let bridge_count = if cfg!(feature = "wasm") { 4 } else { 3 };
+        assert_eq!(
+            expanded.matches("# [allow (deprecated)]").count(),
+            bridge_count
+        );
+
+        let expanded = expand_deprecated(quote!());
+        assert!(!expanded.contains("deprecated"));
+    }
+
+    #[test]
+    fn test_strip_doc_links() {
+        assert_eq!(strip_doc_links(" Plain text."), " Plain text.");
+        assert_eq!(strip_doc_links(" See [`Foo::bar`]."), " See `Foo::bar`.");
+        assert_eq!(strip_doc_links(" A [link](crate::Foo)."), " A link.");
+        assert_eq!(strip_doc_links(" A [link][ref] [x]"), " A link x");
+        assert_eq!(
+            strip_doc_links(" Takes `&[u8]` and `[u8; 32]`."),
+            " Takes `&[u8]` and `[u8; 32]`."
+        );
+        assert_eq!(
+            strip_doc_links(" Unbalanced [bracket"),
+            " Unbalanced [bracket"
+        );
+        assert!(is_link_reference_definition("[Foo]: crate::Foo"));
+        assert!(!is_link_reference_definition(" [Foo] is a thing: really"));
+    }
+
+    #[test]
+    fn test_unknown_option() {
+        let item = quote!(
+            fn SessionCipher_Encrypt(message: &[u8]) -> Result<u32> {}
+        );
+        let error = expand_bridge_fn(quote!(nod = "Foo"), item, None)
+            .expect_err("should reject unknown option");
+        assert!(error.to_string().starts_with("unknown bridge_fn option"));
+    }
 }
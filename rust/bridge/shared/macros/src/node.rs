@@ -3,20 +3,25 @@
 // SPDX-License-Identifier: AGPL-3.0-only
 //
 
+use heck::MixedCase;
 use proc_macro2::Span;
 use proc_macro2::TokenStream as TokenStream2;
 use quote::*;
 use std::fmt::Display;
 use syn::spanned::Spanned;
 use syn::*;
-use syn_mid::{FnArg, Pat, PatType, Signature};
+use syn_mid::{FnArg, PatType, Signature};
 
-use crate::ResultKind;
+use crate::{
+    box_bytearray_result, buffer_result_shape, bytearray_result_shape, generic_arg_if_named,
+    has_env_param, param_ident, tuple_result_elements, ResultKind,
+};
 
 fn bridge_fn_body(
     orig_name: &Ident,
-    input_args: &[(&Ident, &Type)],
-    result_kind: ResultKind,
+    input_args: &[(Ident, &Type)],
+    has_env: bool,
+    result_conversion: Option<TokenStream2>,
 ) -> TokenStream2 {
     let input_borrowing = input_args.iter().zip(0..).map(|((name, ty), i)| {
         let name_arg = format_ident!("{}_arg", name);
@@ -36,27 +41,40 @@ fn bridge_fn_body(
         }
     });
 
-    let env_arg = if result_kind.has_env() {
-        quote!(&mut cx,)
-    } else {
-        quote!()
-    };
+    let env_arg = if has_env { quote!(&mut cx,) } else { quote!() };
     let input_names = input_args.iter().map(|(name, _ty)| name);
 
     quote! {
         #(#input_borrowing)*
         #(#input_loading)*
         let __result = #orig_name(#env_arg #(#input_names),*);
+        #result_conversion
         Ok(node::ResultTypeInfo::convert_into(__result, &mut cx)?.upcast())
     }
 }
 
 fn bridge_fn_async_body(
     orig_name: &Ident,
-    input_args: &[(&Ident, &Type)],
-    result_kind: ResultKind,
+    input_args: &[(Ident, &Type)],
+    has_env: bool,
+    result_conversion: Option<TokenStream2>,
 ) -> TokenStream2 {
-    let input_saving = input_args.iter().zip(0..).map(|((name, ty), i)| {
+    // Cancellation tokens come from the promise rather than from the JavaScript arguments.
+    let (token_args, js_args): (Vec<_>, Vec<_>) = input_args
+        .iter()
+        .partition(|(_name, ty)| is_cancellation_token(ty));
+
+    // Saved arguments are finalized through this queue if they're dropped before the promise
+    // settles.
+    let queue_creation = if js_args.is_empty() {
+        quote!()
+    } else {
+        quote! {
+            let __queue = std::sync::Arc::new(neon::context::Context::queue(&mut *cx.borrow_mut()));
+        }
+    };
+
+    let input_saving = js_args.iter().zip(0..).map(|((name, ty), i)| {
         let name_arg = format_ident!("{}_arg", name);
         let name_stored = format_ident!("{}_stored", name);
         let name_guard = format_ident!("{}_guard", name);
@@ -64,14 +82,17 @@ fn bridge_fn_async_body(
             // First, load each argument and save it in a context-independent form.
             let #name_arg = cx.borrow_mut().argument::<<#ty as node::AsyncArgTypeInfo>::ArgType>(#i)?;
             let #name_stored = <#ty as node::AsyncArgTypeInfo>::save_async_arg(&mut cx.borrow_mut(), #name_arg)?;
-            // Make sure we Finalize any arguments we've loaded if there's an error.
+            // From here on, the saved argument is finalized even if it's dropped without settling
+            // the promise.
+            let #name_stored = node::FinalizeOnDrop::new(#name_stored, __queue.clone());
+            // Make sure we Finalize any arguments we've loaded right away if there's an error.
             let mut #name_guard = scopeguard::guard(#name_stored, |#name_stored| {
                 neon::prelude::Finalize::finalize(#name_stored, &mut *cx.borrow_mut())
             });
         }
     });
 
-    let input_unwrapping = input_args.iter().map(|(name, _ty)| {
+    let input_unwrapping = js_args.iter().map(|(name, _ty)| {
         let name_stored = format_ident!("{}_stored", name);
         let name_guard = format_ident!("{}_guard", name);
         quote! {
@@ -80,22 +101,22 @@ fn bridge_fn_async_body(
         }
     });
 
-    let input_loading = input_args.iter().map(|(name, ty)| {
+    let input_loading = js_args.iter().map(|(name, ty)| {
         let name_stored = format_ident!("{}_stored", name);
         quote! {
             // Inside the future, we load the expected types from the stored values.
-            let #name = <#ty as node::AsyncArgTypeInfo>::load_async_arg(&mut #name_stored);
+            let #name = <#ty as node::AsyncArgTypeInfo>::load_async_arg(&mut *#name_stored);
         }
     });
 
-    let env_arg = if result_kind.has_env() {
+    let env_arg = if has_env {
         quote!(node::AsyncEnv,)
     } else {
         quote!()
     };
     let input_names = input_args.iter().map(|(name, _ty)| name);
 
-    let input_finalization = input_args.iter().map(|(name, _ty)| {
+    let input_finalization = js_args.iter().map(|(name, _ty)| {
         let name_stored = format_ident!("{}_stored", name);
         quote! {
             // Clean up all the stored values at the end.
@@ -103,69 +124,155 @@ fn bridge_fn_async_body(
         }
     });
 
+    let future = quote! {
+        std::panic::AssertUnwindSafe(async move {
+            // Catch panics here rather than letting them escape the future, so that the stored
+            // arguments are still finalized below.
+            let __result = futures::FutureExt::catch_unwind(std::panic::AssertUnwindSafe(async {
+                #(#input_loading)*
+                let __result = #orig_name(#env_arg #(#input_names),*).await;
+                #result_conversion
+                __result
+            }))
+            .await
+            .map_err(node::UnexpectedPanic::new);
+            signal_neon_futures::settle_promise(move |cx| {
+                let mut cx = scopeguard::guard(cx, |cx| {
+                    #(#input_finalization)*
+                });
+                match __result {
+                    Ok(__result) => Ok(node::ResultTypeInfo::convert_into(__result, *cx)?
+                        .upcast::<neon::types::JsValue>()),
+                    Err(__panic) => node::SignalNodeError::throw(__panic, *cx),
+                }
+            })
+        })
+    };
+
+    let promise = if token_args.is_empty() {
+        quote!(signal_neon_futures::promise(&mut cx.into_inner(), #future))
+    } else {
+        let token_loading = token_args.iter().map(|(name, ty)| {
+            quote! {
+                let #name = <#ty as node::SupportsCancellation>::from_cancellation_token(
+                    __cancellation_token.clone()
+                );
+            }
+        });
+        quote! {
+            signal_neon_futures::cancellable_promise(
+                &mut cx.into_inner(),
+                move |__cancellation_token| {
+                    #(#token_loading)*
+                    #future
+                }
+            )
+        }
+    };
+
     quote! {
         // Use a RefCell so that the early-exit cleanup functions can reference the context
         // without taking ownership.
         let cx = std::cell::RefCell::new(cx);
+        #queue_creation
         #(#input_saving)*
         #(#input_unwrapping)*
-        Ok(signal_neon_futures::promise(
-            &mut cx.into_inner(),
-            std::panic::AssertUnwindSafe(async move {
-                #(#input_loading)*
-                let __result = #orig_name(#env_arg #(#input_names),*).await;
-                signal_neon_futures::settle_promise(move |cx| {
-                    let mut cx = scopeguard::guard(cx, |cx| {
-                        #(#input_finalization)*
-                    });
-                    node::ResultTypeInfo::convert_into(__result, *cx)
-                })
-            })
-        )?.upcast())
+        Ok(#promise?.upcast())
     }
 }
 
-pub(crate) fn bridge_fn(name: String, sig: &Signature, result_kind: ResultKind) -> TokenStream2 {
+/// `result_fields` names the fields of the object returned for a tuple result, and must be
+/// present if and only if the function returns a tuple.
+///
+/// `ts_module` is the TypeScript module the declaration belongs to, if not [`DEFAULT_TS_MODULE`].
+///
+/// `docs` are forwarded doc comment lines, placed directly above the `ts:` annotation so that they
+/// become the declaration's JSDoc comment.
+pub(crate) fn bridge_fn(
+    name: String,
+    sig: &Signature,
+    result_kind: ResultKind,
+    result_fields: Option<&[String]>,
+    ts_module: Option<&str>,
+    docs: &[String],
+) -> TokenStream2 {
     let name_with_prefix = format_ident!("node_{}", name);
     let name_without_prefix = Ident::new(&name, Span::call_site());
 
-    let result_type_format = if sig.asyncness.is_some() {
-        |ty: &dyn Display| format!("Promise<{}>", ty)
-    } else {
-        |ty: &dyn Display| format!("{}", ty)
+    let is_cancellable = sig.inputs.iter().any(|arg| match arg {
+        FnArg::Typed(PatType { ty, .. }) => is_cancellation_token(ty),
+        FnArg::Receiver(_) => false,
+    });
+    if is_cancellable && sig.asyncness.is_none() {
+        return Error::new(
+            sig.ident.span(),
+            "CancellationToken parameters are only supported for async functions",
+        )
+        .to_compile_error();
+    }
+
+    let result_type_format = match (sig.asyncness, is_cancellable) {
+        (Some(_), true) => |ty: &dyn Display| format!("CancellablePromise<{}>", ty),
+        (Some(_), false) => |ty: &dyn Display| format!("Promise<{}>", ty),
+        (None, _) => |ty: &dyn Display| format!("{}", ty),
+    };
+    let bytearray_shape = match (result_kind, &sig.output) {
+        (ResultKind::Regular, ReturnType::Type(_, ty)) => bytearray_result_shape(ty),
+        _ => None,
     };
+    let named_tuple: Option<(bool, Vec<(&String, &Type)>)> =
+        match (result_kind, &sig.output, result_fields) {
+            (ResultKind::Regular, ReturnType::Type(_, ty), Some(fields)) => {
+                tuple_result_elements(ty)
+                    .map(|(is_result, elements)| (is_result, fields.iter().zip(elements).collect()))
+            }
+            _ => None,
+        };
+
     let result_type_str = match (result_kind, &sig.output) {
-        (ResultKind::Regular, ReturnType::Default) => result_type_format(&"()"),
-        (ResultKind::Regular, ReturnType::Type(_, ty)) => result_type_format(&quote!(#ty)),
-        (ResultKind::Void, _) => result_type_format(&"()"),
-        (ResultKind::Buffer, ReturnType::Type(_, _)) => result_type_format(&"Buffer"),
-        (ResultKind::Buffer, ReturnType::Default) => {
-            return Error::new(
-                sig.paren_token.span,
-                "missing result type for bridge_fn_buffer",
-            )
-            .to_compile_error()
+        (ResultKind::Regular, ReturnType::Default) => Ok(result_type_format(&"void")),
+        (ResultKind::Regular, ReturnType::Type(_, _)) if named_tuple.is_some() => {
+            let (_, fields) = named_tuple.as_ref().expect("just checked");
+            object_ts_type(fields).map(|ty| result_type_format(&ty))
+        }
+        (ResultKind::Regular, ReturnType::Type(_, ty)) => {
+            ts_type(ty).map(|ty| result_type_format(&ty))
         }
+        (ResultKind::Void, _) => Ok(result_type_format(&"void")),
+        (ResultKind::Buffer, ReturnType::Type(_, ty)) => {
+            let (_, is_optional) = buffer_result_shape(ty);
+            Ok(result_type_format(&buffer_ts_type(is_optional)))
+        }
+        (ResultKind::Bool, ReturnType::Type(_, _)) => Ok(result_type_format(&"boolean")),
+        (ResultKind::Bool, ReturnType::Default) => Err(Error::new(
+            sig.paren_token.span,
+            "missing result type for bridge_fn_bool",
+        )),
+        (ResultKind::Buffer, ReturnType::Default) => Err(Error::new(
+            sig.paren_token.span,
+            "missing result type for bridge_fn_buffer",
+        )),
+    };
+    let result_type_str = match result_type_str {
+        Ok(ty) => ty,
+        Err(error) => return error.to_compile_error(),
     };
 
+    let has_env = has_env_param(sig);
     let input_args: Result<Vec<_>> = sig
         .inputs
         .iter()
-        .skip(if result_kind.has_env() { 1 } else { 0 })
-        .map(|arg| match arg {
+        .enumerate()
+        .skip(if has_env { 1 } else { 0 })
+        .map(|(index, arg)| match arg {
             FnArg::Receiver(tokens) => Err(Error::new(
                 tokens.self_token.span,
                 "cannot have 'self' parameter",
             )),
-            FnArg::Typed(PatType {
-                attrs: _,
-                pat: box Pat::Ident(name),
-                colon_token: _,
-                ty,
-            }) => Ok((&name.ident, &**ty)),
-            FnArg::Typed(PatType { pat, .. }) => {
-                Err(Error::new(pat.span(), "cannot use patterns in parameter"))
-            }
+            FnArg::Typed(PatType { pat, ty, .. }) => match param_ident(pat, index) {
+                Some(name) => Ok((name, &**ty)),
+                None => Err(Error::new(pat.span(), "cannot use patterns in parameter")),
+            },
         })
         .collect();
 
@@ -174,25 +281,41 @@ pub(crate) fn bridge_fn(name: String, sig: &Signature, result_kind: ResultKind)
         Err(error) => return error.to_compile_error(),
     };
 
+    let result_conversion = bytearray_shape
+        .map(box_bytearray_result)
+        .or_else(|| named_tuple.map(|(is_result, fields)| name_tuple_fields(is_result, &fields)));
     let body = match sig.asyncness {
-        Some(_) => bridge_fn_async_body(&sig.ident, &input_args, result_kind),
-        None => bridge_fn_body(&sig.ident, &input_args, result_kind),
+        Some(_) => bridge_fn_async_body(&sig.ident, &input_args, has_env, result_conversion),
+        None => bridge_fn_body(&sig.ident, &input_args, has_env, result_conversion),
     };
 
-    let node_annotation = format!(
-        "ts: export function {}({}): {}",
+    let ts_args: Result<Vec<_>> = input_args
+        .iter()
+        .filter(|(_name, ty)| !is_cancellation_token(ty))
+        .map(|(name, ty)| Ok(format!("{}: {}", ts_arg_name(name), ts_type(ty)?)))
+        .collect();
+    let ts_args = match ts_args {
+        Ok(args) => args,
+        Err(error) => return error.to_compile_error(),
+    };
+    let arity = ts_args.len();
+    let ts_decl = format!(
+        "export function {}({}): {}",
         name_without_prefix,
-        sig.inputs
-            .iter()
-            .skip(if result_kind.has_env() { 1 } else { 0 })
-            .map(|arg| quote!(#arg).to_string())
-            .collect::<Vec<_>>()
-            .join(", "),
+        ts_args.join(", "),
         result_type_str
     );
 
+    // Declarations in the default module keep the original, unqualified "ts:" form.
+    let ts_module = ts_module.filter(|module| *module != DEFAULT_TS_MODULE);
+    let node_annotation = match ts_module {
+        Some(module) => format!("ts({}): {}", module, ts_decl),
+        None => format!("ts: {}", ts_decl),
+    };
+
     quote! {
         #[allow(non_snake_case)]
+        #(#[doc = #docs])*
         #[doc = #node_annotation]
         pub fn #name_with_prefix(
             mut cx: node::FunctionContext,
@@ -200,10 +323,218 @@ pub(crate) fn bridge_fn(name: String, sig: &Signature, result_kind: ResultKind)
             #body
         }
 
-        node_register!(#name_without_prefix);
+        node_register!(#name_without_prefix, #arity);
+    }
+}
+
+/// Whether `ty` names `CancellationToken`, which async functions can take to find out when the
+/// JavaScript caller cancels the returned promise (see `node::SupportsCancellation`).
+pub(crate) fn is_cancellation_token(ty: &Type) -> bool {
+    match ty {
+        Type::Path(TypePath { qself: None, path }) => path
+            .segments
+            .last()
+            .map_or(false, |segment| segment.ident == "CancellationToken"),
+        _ => false,
+    }
+}
+
+/// Generates a statement wrapping a tuple `__result` in `node::NamedFields`, keeping any `Result`
+/// wrapper, so that it is returned to JavaScript as an object.
+fn name_tuple_fields(is_result: bool, fields: &[(&String, &Type)]) -> TokenStream2 {
+    let names = fields.iter().map(|(name, _ty)| name);
+    let named_fields = quote!(node::NamedFields {
+        names: &[#(#names),*],
+        values,
+    });
+    if is_result {
+        quote!(let __result = __result.map(|values| #named_fields);)
+    } else {
+        quote!(let values = __result; let __result = #named_fields;)
+    }
+}
+
+/// Formats the TypeScript object type for a tuple result with the given field names.
+fn object_ts_type(fields: &[(&String, &Type)]) -> Result<String> {
+    let fields: Result<Vec<_>> = fields
+        .iter()
+        .map(|(name, ty)| Ok(format!("{}: {}", name, ts_type(ty)?)))
+        .collect();
+    Ok(format!("{{ {} }}", fields?.join(", ")))
+}
+
+/// Translates an argument or result type to the TypeScript type that JavaScript sees.
+///
+/// Borrowed handles (`&Foo`) are passed as `Wrapper<Foo>` (and arrays of them, `&[&Foo]`, as
+/// `Wrapper<Foo>[]`), but returned as plain `Foo`; stores
+/// (`&mut dyn FooStore`) keep their names. `Result` is transparent, since errors are thrown.
+/// Anything else without an obvious equivalent is an error, rather than a guess.
+fn ts_type(ty: &Type) -> Result<String> {
+    fn is_u8(ty: &Type) -> bool {
+        matches!(ty, Type::Path(path) if path.path.is_ident("u8"))
+    }
+
+    /// Looks through the invisible groups left by `macro_rules` substitutions like `&$typ`.
+    fn ungroup(ty: &Type) -> &Type {
+        match ty {
+            Type::Group(TypeGroup { elem, .. }) => ungroup(elem),
+            _ => ty,
+        }
+    }
+
+    /// Handle types are plain capitalized names, like `PublicKey`.
+    fn handle_name(path: &syn::Path) -> Option<String> {
+        path.get_ident()
+            .map(Ident::to_string)
+            .filter(|name| name.starts_with(|c: char| c.is_ascii_uppercase()))
+    }
+
+    let translated = match ty {
+        Type::Group(TypeGroup { elem, .. }) | Type::Paren(TypeParen { elem, .. }) => {
+            return ts_type(elem)
+        }
+        Type::Tuple(TypeTuple { elems, .. }) if elems.is_empty() => Some("void".to_string()),
+        Type::Array(TypeArray { elem, .. }) if is_u8(elem) => Some("Buffer".to_string()),
+        Type::Reference(TypeReference {
+            mutability, elem, ..
+        }) => match (mutability, ungroup(elem)) {
+            // Byte arguments can be any Uint8Array (like a Buffer) or ArrayBuffer; see
+            // IsUint8Like in the bridge's node/convert.rs.
+            (None, Type::Slice(TypeSlice { elem, .. })) if is_u8(elem) => {
+                Some("Uint8Like".to_string())
+            }
+            (None, Type::Slice(TypeSlice { elem, .. })) => {
+                return Ok(format!("{}[]", ts_type(elem)?));
+            }
+            (None, Type::Path(TypePath { qself: None, path })) if path.is_ident("str") => {
+                Some("string".to_string())
+            }
+            (Some(_), Type::TraitObject(TypeTraitObject { bounds, .. })) => match bounds.first() {
+                Some(TypeParamBound::Trait(TraitBound { path, .. })) => path
+                    .segments
+                    .last()
+                    .map(|segment| segment.ident.to_string()),
+                _ => None,
+            },
+            (_, Type::Path(TypePath { qself: None, path })) => {
+                handle_name(path).map(|name| format!("Wrapper<{}>", name))
+            }
+            _ => None,
+        },
+        Type::Path(TypePath { qself: None, path }) => {
+            if let Some(inner) = generic_arg_if_named(ty, "Option") {
+                return Ok(format!("{} | null", ts_type(inner)?));
+            }
+            if let Some(inner) = generic_arg_if_named(ty, "Result") {
+                return ts_type(inner);
+            }
+            if bytearray_result_shape(ty).is_some() {
+                // Only Vec<u8> and Box<[u8]> are left after checking for Option and Result.
+                return Ok("Buffer".to_string());
+            }
+            if let Some(inner) = generic_arg_if_named(ty, "Vec") {
+                return Ok(format!("{}[]", ts_type(inner)?));
+            }
+            match path.get_ident().map(Ident::to_string).as_deref() {
+                Some("u8") | Some("u32") | Some("i32") => Some("number".to_string()),
+                // See the conversions for u64 in the bridge's node/convert.rs.
+                Some("u64") => Some("bigint".to_string()),
+                Some("bool") => Some("boolean".to_string()),
+                Some("String") => Some("string".to_string()),
+                Some("Uuid") => Some("Buffer".to_string()),
+                _ => handle_name(path),
+            }
+        }
+        _ => None,
+    };
+
+    translated.ok_or_else(|| {
+        Error::new(
+            ty.span(),
+            "no known TypeScript equivalent for this type (see ts_type in node.rs)",
+        )
+    })
+}
+
+/// Formats a parameter name the way JavaScript code would spell it, e.g. `messageBody`.
+fn ts_arg_name(name: &Ident) -> String {
+    name.to_string().to_mixed_case()
+}
+
+fn buffer_ts_type(is_optional: bool) -> &'static str {
+    if is_optional {
+        "Buffer | null"
+    } else {
+        "Buffer"
     }
 }
 
+/// The TypeScript module for declarations that don't specify `ts_module`, which ends up in
+/// `libsignal_client.d.ts`.
+pub(crate) const DEFAULT_TS_MODULE: &str = "Native";
+
 pub(crate) fn name_from_ident(ident: &Ident) -> String {
     ident.to_string()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ts_annotations() {
+        let sync_fn: syn_mid::ItemFn = parse_quote!(
+            fn Foo_GetBar(foo: &Foo, index: u32) -> Result<String> {}
+        );
+        let async_fn: syn_mid::ItemFn = parse_quote!(
+            async fn Foo_Process(data: &[u8], store: &mut dyn FooStore) {}
+        );
+        let buffer_fn: syn_mid::ItemFn = parse_quote!(
+            fn Foo_GetData<E: Env>(env: E, foo: &Foo) -> Result<Option<E::Buffer>> {}
+        );
+        for (name, sig, result_kind, ts_module, annotation) in &[
+            (
+                "Foo_GetBar",
+                &sync_fn.sig,
+                ResultKind::Regular,
+                None,
+                "ts: export function Foo_GetBar(foo: Wrapper<Foo>, index: number): string",
+            ),
+            (
+                "Foo_Process",
+                &async_fn.sig,
+                ResultKind::Void,
+                Some("Native"),
+                "ts: export function Foo_Process(data: Uint8Like, store: FooStore): Promise<void>",
+            ),
+            (
+                "Foo_GetData",
+                &buffer_fn.sig,
+                ResultKind::Buffer,
+                Some("Testing"),
+                "ts(Testing): export function Foo_GetData(foo: Wrapper<Foo>): Buffer | null",
+            ),
+        ] {
+            let tokens = bridge_fn(name.to_string(), sig, *result_kind, None, *ts_module, &[]);
+            let expected = format!("{:?}", annotation);
+            assert!(
+                tokens.to_string().contains(&expected),
+                "{} not found in {}",
+                expected,
+                tokens
+            );
+        }
+    }
+
+    #[test]
+    fn test_ts_type_handle_array() {
+        let ty: Type = parse_quote!(&[&Foo]);
+        assert_eq!(ts_type(&ty).expect("valid"), "Wrapper<Foo>[]");
+        let ty: Type = parse_quote!(&[u8]);
+        assert_eq!(ts_type(&ty).expect("valid"), "Uint8Like");
+        let ty: Type = parse_quote!(Option<&[u8]>);
+        assert_eq!(ts_type(&ty).expect("valid"), "Uint8Like | null");
+        let ty: Type = parse_quote!(&[f64]);
+        assert!(ts_type(&ty).is_err());
+    }
+}
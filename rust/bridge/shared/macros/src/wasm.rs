@@ -0,0 +1,192 @@
+//
+// Copyright 2021 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+use proc_macro2::TokenStream as TokenStream2;
+use quote::*;
+use syn::spanned::Spanned;
+use syn::*;
+use syn_mid::{FnArg, PatType, Signature};
+
+use crate::node::is_cancellation_token;
+use crate::{
+    box_bytearray_result, bytearray_result_shape, has_env_param, param_ident,
+    tuple_result_elements, ResultKind,
+};
+
+fn bridge_fn_body(
+    orig_name: &Ident,
+    input_args: &[(Ident, &Type)],
+    has_env: bool,
+    result_conversion: Option<TokenStream2>,
+) -> TokenStream2 {
+    let input_borrowing = input_args.iter().map(|(name, ty)| {
+        let name_stored = format_ident!("{}_stored", name);
+        quote! {
+            // First, "borrow" the contents of each JavaScript argument.
+            let mut #name_stored = <#ty as wasm::ArgTypeInfo>::borrow(&#name)?;
+        }
+    });
+
+    let input_loading = input_args.iter().map(|(name, ty)| {
+        let name_stored = format_ident!("{}_stored", name);
+        quote! {
+            // Then load the expected types from the stored values.
+            let #name = <#ty as wasm::ArgTypeInfo>::load_from(&mut #name_stored);
+        }
+    });
+
+    let env_arg = if has_env {
+        quote!(wasm::Env,)
+    } else {
+        quote!()
+    };
+    let input_names = input_args.iter().map(|(name, _ty)| name);
+
+    quote! {
+        #(#input_borrowing)*
+        #(#input_loading)*
+        let __result = #orig_name(#env_arg #(#input_names),*);
+        #result_conversion
+        wasm::ResultTypeInfo::convert_into(__result)
+    }
+}
+
+fn bridge_fn_async_body(
+    orig_name: &Ident,
+    input_args: &[(Ident, &Type)],
+    has_env: bool,
+    result_conversion: Option<TokenStream2>,
+) -> TokenStream2 {
+    let input_saving = input_args.iter().map(|(name, ty)| {
+        let name_stored = format_ident!("{}_stored", name);
+        quote! {
+            // First, save each argument in a form that can outlive this call.
+            let mut #name_stored = <#ty as wasm::AsyncArgTypeInfo>::save_async_arg(&#name)?;
+        }
+    });
+
+    let input_loading = input_args.iter().map(|(name, ty)| {
+        let name_stored = format_ident!("{}_stored", name);
+        quote! {
+            // Inside the future, we load the expected types from the stored values.
+            let #name = <#ty as wasm::AsyncArgTypeInfo>::load_async_arg(&mut #name_stored);
+        }
+    });
+
+    let env_arg = if has_env {
+        quote!(wasm::Env,)
+    } else {
+        quote!()
+    };
+    let input_names = input_args.iter().map(|(name, _ty)| name);
+
+    // Panics abort on wasm32, so unlike Node there's nothing to catch here. The saved arguments
+    // are dropped along with the future, whether or not it completes.
+    quote! {
+        #(#input_saving)*
+        Ok(wasm::promise(async move {
+            #(#input_loading)*
+            let __result = #orig_name(#env_arg #(#input_names),*).await;
+            #result_conversion
+            wasm::ResultTypeInfo::convert_into(__result)
+        }))
+    }
+}
+
+/// `result_fields` names the fields of the object returned for a tuple result, exactly as for
+/// Node, and must be present if and only if the function returns a tuple.
+///
+/// Every parameter and the result are declared to wasm-bindgen as plain `JsValue`s, so that all
+/// conversions go through `wasm::ArgTypeInfo` and `wasm::ResultTypeInfo` rather than
+/// wasm-bindgen's own traits.
+pub(crate) fn bridge_fn(
+    name: String,
+    sig: &Signature,
+    result_kind: ResultKind,
+    result_fields: Option<&[String]>,
+) -> TokenStream2 {
+    let name_with_prefix = format_ident!("wasm_{}", name);
+
+    if let Some(token) = sig.inputs.iter().find_map(|arg| match arg {
+        FnArg::Typed(PatType { ty, .. }) if is_cancellation_token(ty) => Some(ty),
+        _ => None,
+    }) {
+        return Error::new(
+            token.span(),
+            "CancellationToken parameters are not supported for WASM; use wasm = false",
+        )
+        .to_compile_error();
+    }
+
+    let has_env = has_env_param(sig);
+    let input_args: Result<Vec<_>> = sig
+        .inputs
+        .iter()
+        .enumerate()
+        .skip(if has_env { 1 } else { 0 })
+        .map(|(index, arg)| match arg {
+            FnArg::Receiver(tokens) => Err(Error::new(
+                tokens.self_token.span,
+                "cannot have 'self' parameter",
+            )),
+            FnArg::Typed(PatType { pat, ty, .. }) => match param_ident(pat, index) {
+                Some(name) => Ok((name, &**ty)),
+                None => Err(Error::new(pat.span(), "cannot use patterns in parameter")),
+            },
+        })
+        .collect();
+
+    let input_args = match input_args {
+        Ok(args) => args,
+        Err(error) => return error.to_compile_error(),
+    };
+
+    let bytearray_shape = match (result_kind, &sig.output) {
+        (ResultKind::Regular, ReturnType::Type(_, ty)) => bytearray_result_shape(ty),
+        _ => None,
+    };
+    let named_tuple = match (result_kind, &sig.output, result_fields) {
+        (ResultKind::Regular, ReturnType::Type(_, ty), Some(fields)) => {
+            tuple_result_elements(ty).map(|(is_result, _elements)| (is_result, fields))
+        }
+        _ => None,
+    };
+
+    let result_conversion = bytearray_shape
+        .map(box_bytearray_result)
+        .or_else(|| named_tuple.map(|(is_result, fields)| name_tuple_fields(is_result, fields)));
+    let body = match sig.asyncness {
+        Some(_) => bridge_fn_async_body(&sig.ident, &input_args, has_env, result_conversion),
+        None => bridge_fn_body(&sig.ident, &input_args, has_env, result_conversion),
+    };
+
+    let params = input_args
+        .iter()
+        .map(|(name, _ty)| quote!(#name: wasm::JsValue));
+
+    // Unlike the C and JNI entry points, these are plain Rust functions as far as Clippy can tell,
+    // so they inherit the arity of the original function.
+    quote! {
+        #[allow(non_snake_case, clippy::too_many_arguments)]
+        #[wasm_bindgen::prelude::wasm_bindgen(js_name = #name)]
+        pub fn #name_with_prefix(#(#params),*) -> wasm::WasmResult<wasm::JsValue> {
+            #body
+        }
+    }
+}
+
+/// Generates a statement wrapping a tuple `__result` in `wasm::NamedFields`, keeping any `Result`
+/// wrapper, so that it is returned to JavaScript as an object.
+fn name_tuple_fields(is_result: bool, fields: &[String]) -> TokenStream2 {
+    let named_fields = quote!(wasm::NamedFields {
+        names: &[#(#fields),*],
+        values,
+    });
+    if is_result {
+        quote!(let __result = __result.map(|values| #named_fields);)
+    } else {
+        quote!(let values = __result; let __result = #named_fields;)
+    }
+}
@@ -0,0 +1,13 @@
+//
+// Copyright 2021 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+use libsignal_bridge_macros::*;
+
+#[bridge_fn]
+fn Foo_Count(counts: std::collections::HashMap<String, u32>) -> u32 {
+    counts.len() as u32
+}
+
+fn main() {}
@@ -0,0 +1,13 @@
+//
+// Copyright 2021 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+use libsignal_bridge_macros::*;
+
+#[bridge_fn]
+fn Foo_Share(value: u32) -> Option<std::rc::Rc<u32>> {
+    Some(std::rc::Rc::new(value))
+}
+
+fn main() {}
@@ -18,32 +18,42 @@ fn Aes256GcmSiv_New(key: &[u8]) -> Result<Aes256GcmSiv> {
     aes_gcm_siv::Aes256GcmSiv::new(&key)
 }
 
-#[bridge_fn_buffer]
-fn Aes256GcmSiv_Encrypt<T: Env>(
-    env: T,
+#[bridge_fn]
+fn Aes256GcmSiv_Encrypt(
     aes_gcm_siv: &Aes256GcmSiv,
     ptext: &[u8],
     nonce: &[u8],
     associated_data: &[u8],
-) -> Result<T::Buffer> {
+) -> Result<Vec<u8>> {
     let mut buf = Vec::with_capacity(ptext.len() + 16);
     buf.extend_from_slice(ptext);
 
     let gcm_tag = aes_gcm_siv.encrypt(&mut buf, &nonce, &associated_data)?;
     buf.extend_from_slice(&gcm_tag);
 
-    Ok(env.buffer(buf))
+    Ok(buf)
 }
 
-#[bridge_fn_buffer]
-fn Aes256GcmSiv_Decrypt<T: Env>(
-    env: T,
+#[bridge_fn]
+fn Aes256GcmSiv_Decrypt(
     aes_gcm_siv: &Aes256GcmSiv,
     ctext: &[u8],
     nonce: &[u8],
     associated_data: &[u8],
-) -> Result<T::Buffer> {
+) -> Result<Vec<u8>> {
     let mut buf = ctext.to_vec();
     aes_gcm_siv.decrypt_with_appended_tag(&mut buf, &nonce, &associated_data)?;
-    Ok(env.buffer(buf))
+    Ok(buf)
+}
+
+/// Like `Aes256GcmSiv_Decrypt`, but takes direct `ByteBuffer`s in Java to avoid copying large
+/// ciphertexts.
+#[bridge_fn(ffi = false, node = false, jni_direct_buffers = true)]
+fn Aes256GcmSiv_DecryptDirect(
+    aes_gcm_siv: &Aes256GcmSiv,
+    ctext: &[u8],
+    nonce: &[u8],
+    associated_data: &[u8],
+) -> Result<Vec<u8>> {
+    Aes256GcmSiv_Decrypt(aes_gcm_siv, ctext, nonce, associated_data)
 }
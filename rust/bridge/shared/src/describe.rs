@@ -0,0 +1,142 @@
+//
+// Copyright 2021 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+//! A registry of every entry point generated for the bridges, so that each language's test suite
+//! can check its own declarations against what was actually built.
+//!
+//! Only built with the "describe" feature, and for this crate's own tests, which check the FFI
+//! and JNI entry points against the C header and `Native.java`. Otherwise, the `*_register!`
+//! macros record nothing beyond what the bridges themselves need.
+
+#[cfg(test)]
+use std::collections::HashSet;
+use std::fmt;
+
+/// The bridge an entry point was generated for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Platform {
+    Ffi,
+    Jni,
+    Node,
+}
+
+impl Platform {
+    /// The name used for this platform in the bridges' feature flags.
+    pub fn name(self) -> &'static str {
+        match self {
+            Platform::Ffi => "ffi",
+            Platform::Jni => "jni",
+            Platform::Node => "node",
+        }
+    }
+}
+
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A single generated entry point.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BridgedFn {
+    /// The name the entry point is exported under: the C symbol for FFI and JNI, and the property
+    /// of the module's exports for Node.
+    pub name: &'static str,
+    /// The number of parameters the entry point takes, as declared in the other language.
+    ///
+    /// For FFI this includes any output parameters; for JNI it leaves out the `JNIEnv` and class.
+    pub arity: usize,
+    pub platform: Platform,
+}
+
+impl fmt::Display for BridgedFn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} {}", self.platform, self.name, self.arity)
+    }
+}
+
+#[doc(hidden)]
+#[linkme::distributed_slice]
+pub static BRIDGED_FNS: [BridgedFn] = [..];
+
+/// Returns every entry point generated for the enabled bridges, sorted by platform and then name.
+pub fn describe_all() -> Vec<BridgedFn> {
+    let mut result = BRIDGED_FNS.to_vec();
+    result.sort_by_key(|f| (f.platform, f.name));
+    result
+}
+
+/// Returns the names of every entry point generated for `platform`, for checking against the
+/// declarations on the other side of the bridge.
+#[cfg(test)]
+pub(crate) fn generated_functions(platform: Platform) -> Vec<&'static str> {
+    describe_all()
+        .into_iter()
+        .filter(|f| f.platform == platform)
+        .map(|f| f.name)
+        .collect()
+}
+
+/// Returns every identifier immediately followed by an open parenthesis in `source`, which covers
+/// the functions declared in a C header or a Java class (along with a few false positives).
+#[cfg(test)]
+pub(crate) fn declared_functions(source: &str) -> HashSet<&str> {
+    source
+        .split('(')
+        .filter_map(|before| {
+            before
+                .rsplit(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .next()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_describe_all() {
+        let all = describe_all();
+        assert!(!all.is_empty());
+        assert!(
+            all.windows(2)
+                .all(|pair| (pair[0].platform, pair[0].name) < (pair[1].platform, pair[1].name)),
+            "entries should be sorted and unique"
+        );
+
+        #[cfg(feature = "ffi")]
+        assert!(all.contains(&BridgedFn {
+            name: "signal_publickey_deserialize",
+            arity: 3, // output, data and length
+            platform: Platform::Ffi,
+        }));
+        #[cfg(all(feature = "ffi", feature = "testing"))]
+        assert!(all.contains(&BridgedFn {
+            name: "signal_testing_return_tuple",
+            arity: 5, // buffer and length, value, is_even, input
+            platform: Platform::Ffi,
+        }));
+        #[cfg(feature = "jni")]
+        assert!(all.contains(&BridgedFn {
+            name: "Java_org_signal_client_internal_Native_SessionRecord_1Deserialize",
+            arity: 1,
+            platform: Platform::Jni,
+        }));
+        #[cfg(all(feature = "jni", feature = "testing"))]
+        assert!(all.contains(&BridgedFn {
+            name: "Java_org_signal_client_internal_NativeTesting_TESTING_1SumBytes",
+            arity: 2,
+            platform: Platform::Jni,
+        }));
+        #[cfg(all(feature = "node", feature = "testing"))]
+        assert!(all.contains(&BridgedFn {
+            name: "TESTING_FirstByte",
+            arity: 1,
+            platform: Platform::Node,
+        }));
+    }
+}
@@ -6,6 +6,7 @@
 use libc::{c_char, c_uchar, c_void};
 use libsignal_protocol::*;
 use paste::paste;
+use std::convert::TryFrom;
 use std::ffi::CStr;
 use std::{borrow::Cow, ops::Deref};
 
@@ -182,6 +183,24 @@ impl SizedArgTypeInfo for &mut [u8] {
     }
 }
 
+/// A `NULL` pointer is used to represent `None` here, in which case the length must be zero.
+///
+/// A non-`NULL` pointer with a length of zero produces `Some` empty slice, so callers can
+/// distinguish an absent buffer from an empty one.
+impl SizedArgTypeInfo for Option<&[u8]> {
+    type ArgType = *const c_uchar;
+    fn convert_from(input: Self::ArgType, input_len: usize) -> SignalFfiResult<Self> {
+        if input.is_null() {
+            if input_len != 0 {
+                return Err(SignalFfiError::NullPointer);
+            }
+            return Ok(None);
+        }
+
+        unsafe { Ok(Some(std::slice::from_raw_parts(input, input_len))) }
+    }
+}
+
 /// `u32::MAX` (`UINT_MAX`, `~0u`) is used to represent `None` here.
 impl SimpleArgTypeInfo for Option<u32> {
     type ArgType = u32;
@@ -194,6 +213,18 @@ impl SimpleArgTypeInfo for Option<u32> {
     }
 }
 
+/// Copies a UUID out of a non-`NULL` pointer to 16 bytes.
+impl SimpleArgTypeInfo for crate::support::Uuid {
+    type ArgType = *const [u8; 16];
+    #[allow(clippy::not_unsafe_ptr_arg_deref)]
+    fn convert_from(foreign: *const [u8; 16]) -> SignalFfiResult<Self> {
+        match unsafe { foreign.as_ref() } {
+            Some(bytes) => Ok(Self::from_bytes(*bytes)),
+            None => Err(SignalFfiError::NullPointer),
+        }
+    }
+}
+
 /// Converts a non-`NULL` C string to a Rust String.
 impl SimpleArgTypeInfo for String {
     type ArgType = *const c_char;
@@ -222,6 +253,80 @@ impl SimpleArgTypeInfo for Option<String> {
     }
 }
 
+/// Borrows a non-`NULL` C string as a Rust string slice, without copying it.
+impl<'a> ArgTypeInfo<'a> for &'a str {
+    type ArgType = *const c_char;
+    type StoredType = *const c_char;
+    fn borrow(foreign: *const c_char) -> SignalFfiResult<Self::StoredType> {
+        if foreign.is_null() {
+            return Err(SignalFfiError::NullPointer);
+        }
+        Ok(foreign)
+    }
+    fn load_from(stored: &'a mut Self::StoredType) -> SignalFfiResult<Self> {
+        unsafe { CStr::from_ptr(*stored) }
+            .to_str()
+            .map_err(|_| SignalFfiError::InvalidUtf8String)
+    }
+}
+
+/// A function pointer passed in from C, such as a callback to look up a value.
+///
+/// `F` should be an `extern "C" fn` type. C callers can pass `NULL` for any function pointer, so
+/// the FFI form is an `Option<F>` (which has the same representation as `F`), and loading a `NULL`
+/// callback fails with [`SignalFfiError::NullPointer`]. Use `Option<FfiCallback<F>>` for callbacks
+/// that are allowed to be absent.
+///
+/// `bridge_fn` parameters declared as function pointers (or `CallbackFn` aliases) are converted
+/// through this type automatically, and passed to the Rust function as plain `F`s.
+#[derive(Clone, Copy)]
+pub struct FfiCallback<F: Copy>(F);
+
+impl<F: Copy> FfiCallback<F> {
+    /// Returns the (non-`NULL`) function pointer.
+    pub fn get(self) -> F {
+        self.0
+    }
+}
+
+impl<F: Copy> Deref for FfiCallback<F> {
+    type Target = F;
+    fn deref(&self) -> &F {
+        &self.0
+    }
+}
+
+impl<F: Copy> SimpleArgTypeInfo for FfiCallback<F> {
+    type ArgType = Option<F>;
+    fn convert_from(foreign: Option<F>) -> SignalFfiResult<Self> {
+        foreign.map(FfiCallback).ok_or(SignalFfiError::NullPointer)
+    }
+}
+
+/// A `NULL` function pointer is used to represent `None` here.
+impl<F: Copy> SimpleArgTypeInfo for Option<FfiCallback<F>> {
+    type ArgType = Option<F>;
+    fn convert_from(foreign: Option<F>) -> SignalFfiResult<Self> {
+        Ok(foreign.map(FfiCallback))
+    }
+}
+
+/// Borrows a possibly-`NULL` C string as a Rust string slice (or `None`), without copying it.
+impl<'a> ArgTypeInfo<'a> for Option<&'a str> {
+    type ArgType = *const c_char;
+    type StoredType = *const c_char;
+    fn borrow(foreign: *const c_char) -> SignalFfiResult<Self::StoredType> {
+        Ok(foreign)
+    }
+    fn load_from(stored: &'a mut Self::StoredType) -> SignalFfiResult<Self> {
+        if stored.is_null() {
+            Ok(None)
+        } else {
+            <&str>::load_from(stored).map(Some)
+        }
+    }
+}
+
 impl SimpleArgTypeInfo for Context {
     type ArgType = *mut c_void;
     fn convert_from(foreign: *mut c_void) -> SignalFfiResult<Self> {
@@ -255,14 +360,11 @@ store!(SenderKeyStore);
 store!(SessionStore);
 store!(SignedPreKeyStore);
 
-impl<T: ResultTypeInfo> ResultTypeInfo for Result<T, SignalProtocolError> {
-    type ResultType = T::ResultType;
-    fn convert_into(self) -> SignalFfiResult<Self::ResultType> {
-        T::convert_into(self?)
-    }
-}
-
-impl<T: ResultTypeInfo> ResultTypeInfo for Result<T, aes_gcm_siv::Error> {
+/// Any error that can be converted to a [`SignalFfiError`] can be returned from a `bridge_fn`.
+impl<T: ResultTypeInfo, E> ResultTypeInfo for Result<T, E>
+where
+    SignalFfiError: From<E>,
+{
     type ResultType = T::ResultType;
     fn convert_into(self) -> SignalFfiResult<Self::ResultType> {
         T::convert_into(self?)
@@ -285,11 +387,17 @@ impl ResultTypeInfo for Option<String> {
     }
 }
 
-/// Allocates and returns a new Rust-owned C string.
+/// Allocates and returns a new Rust-owned C string, which must be freed with
+/// `signal_free_string`.
+///
+/// C strings can't contain NUL characters, so a string with one is reported as an
+/// [`SignalProtocolError::InternalError`] rather than being truncated.
 impl ResultTypeInfo for &str {
     type ResultType = *const libc::c_char;
     fn convert_into(self) -> SignalFfiResult<Self::ResultType> {
-        let cstr = CString::new(self).expect("No NULL characters in string being returned to C");
+        let cstr = CString::new(self).map_err(|_| {
+            SignalProtocolError::InternalError("returned string contains a NUL character")
+        })?;
         Ok(cstr.into_raw())
     }
 }
@@ -312,6 +420,28 @@ impl ResultTypeInfo for Option<u32> {
     }
 }
 
+/// Flattens a list of buffers into a single Rust-owned buffer, returned along with the number of
+/// buffers.
+///
+/// Each buffer is preceded by its length as a 32-bit big-endian integer.
+impl ResultTypeInfo for Vec<Box<[u8]>> {
+    type ResultType = (Box<[u8]>, usize);
+    fn convert_into(self) -> SignalFfiResult<Self::ResultType> {
+        let total_len = self.iter().map(|buffer| 4 + buffer.len()).sum();
+        let mut flattened = Vec::with_capacity(total_len);
+        for buffer in &self {
+            let len = u32::try_from(buffer.len()).map_err(|_| {
+                SignalFfiError::Signal(SignalProtocolError::InvalidArgument(
+                    "buffer too large to return".to_string(),
+                ))
+            })?;
+            flattened.extend_from_slice(&len.to_be_bytes());
+            flattened.extend_from_slice(buffer);
+        }
+        Ok((flattened.into_boxed_slice(), self.len()))
+    }
+}
+
 /// A dummy type used to implement [`crate::support::Env`].
 pub(crate) struct Env;
 
@@ -344,6 +474,8 @@ macro_rules! ffi_bridge_handle {
                 }
             }
         }
+        // As with any `*mut` in C, the caller is responsible for not aliasing this argument with
+        // any other argument to the same call, or using it concurrently from another thread.
         impl ffi::SimpleArgTypeInfo for &mut $typ {
             type ArgType = *mut $typ;
             #[allow(clippy::not_unsafe_ptr_arg_deref)]
@@ -351,6 +483,13 @@ macro_rules! ffi_bridge_handle {
                 unsafe { ffi::native_handle_cast_mut(foreign) }
             }
         }
+        impl ffi::SizedArgTypeInfo for &[&$typ] {
+            type ArgType = *const *const $typ;
+            #[allow(clippy::not_unsafe_ptr_arg_deref)]
+            fn convert_from(foreign: *const *const $typ, size: usize) -> ffi::SignalFfiResult<Self> {
+                unsafe { ffi::native_handle_slice_cast(foreign, size) }
+            }
+        }
         impl ffi::ResultTypeInfo for $typ {
             type ResultType = *mut $typ;
             fn convert_into(self) -> ffi::SignalFfiResult<Self::ResultType> {
@@ -366,6 +505,12 @@ macro_rules! ffi_bridge_handle {
                 }
             }
         }
+        impl ffi::ResultTypeInfo for Vec<$typ> {
+            type ResultType = Box<[*mut $typ]>;
+            fn convert_into(self) -> ffi::SignalFfiResult<Self::ResultType> {
+                Ok(self.into_iter().map(|obj| Box::into_raw(Box::new(obj))).collect())
+            }
+        }
         ffi_bridge_destroy!($typ as $ffi_name);
     };
     ( $typ:ty as $ffi_name:ident ) => {
@@ -381,6 +526,8 @@ macro_rules! ffi_bridge_handle {
                     ffi::box_object::<$typ>(new_obj, Ok(obj.clone()))
                 })
             }
+
+            ffi_register!([<signal_ $ffi_name _clone>], 2);
         }
     };
     ( $typ:ty $(, clone = $_:tt)? ) => {
@@ -432,11 +579,17 @@ macro_rules! ffi_arg_type {
     (usize) => (libc::size_t);
     (&[u8]) => (*const libc::c_uchar);
     (&mut [u8]) => (*mut libc::c_uchar);
+    (Option<&[u8]>) => (*const libc::c_uchar);
     (String) => (*const libc::c_char);
     (Option<String>) => (*const libc::c_char);
+    (&str) => (*const libc::c_char);
     (Option<&str>) => (*const libc::c_char);
+    (Uuid) => (*const [u8; 16]);
     (Context) => (*mut libc::c_void);
+    (FfiCallback<$typ:ty>) => (Option<$typ>);
+    (Option<FfiCallback<$typ:ty>>) => (Option<$typ>);
     (&mut dyn $typ:ty) => (*const paste!(ffi::[<Ffi $typ Struct>]));
+    (&[& $typ:ty]) => (*const *const $typ);
     (& $typ:ty) => (*const $typ);
     (&mut $typ:ty) => (*mut $typ);
     (Option<& $typ:ty>) => (*const $typ);
@@ -470,3 +623,273 @@ macro_rules! ffi_result_type {
     (Option<$typ:ty>) => (*mut $typ);
     ( $typ:ty ) => (*mut $typ);
 }
+
+#[test]
+fn test_optional_u32_round_trip() {
+    let foreign = Some(5u32).convert_into().expect("success");
+    assert!(matches!(Option::<u32>::convert_from(foreign), Ok(Some(5))));
+
+    let foreign = Option::<u32>::None.convert_into().expect("success");
+    assert_eq!(foreign, u32::MAX);
+    assert!(matches!(Option::<u32>::convert_from(foreign), Ok(None)));
+}
+
+#[test]
+fn test_optional_string_round_trip() {
+    let foreign = Some("abc").convert_into().expect("success");
+    let result = Option::<String>::convert_from(foreign);
+    unsafe { drop(CString::from_raw(foreign as *mut c_char)) };
+    assert_eq!(result.expect("success").as_deref(), Some("abc"));
+
+    let foreign = Option::<&str>::None.convert_into().expect("success");
+    assert!(foreign.is_null());
+    assert!(matches!(Option::<String>::convert_from(foreign), Ok(None)));
+}
+
+#[test]
+fn test_string_result() {
+    let foreign = String::from("h\u{e9}llo").convert_into().expect("success");
+    let result = unsafe { CString::from_raw(foreign as *mut c_char) };
+    assert_eq!(result.to_str(), Ok("h\u{e9}llo"));
+
+    assert!(matches!(
+        "a\0b".convert_into(),
+        Err(SignalFfiError::Signal(SignalProtocolError::InternalError(
+            _
+        )))
+    ));
+    assert!(matches!(
+        Some(String::from("a\0b")).convert_into(),
+        Err(SignalFfiError::Signal(SignalProtocolError::InternalError(
+            _
+        )))
+    ));
+}
+
+#[test]
+fn test_str_arg() {
+    let valid = CString::new("abc").expect("no NUL bytes");
+    let mut stored = <&str as ArgTypeInfo>::borrow(valid.as_ptr()).expect("not NULL");
+    assert!(matches!(<&str>::load_from(&mut stored), Ok("abc")));
+
+    // An overlong encoding of U+0000, which is not valid UTF-8.
+    let invalid = CString::new(vec![0xC0, 0x80]).expect("no NUL bytes");
+    let mut stored = <&str as ArgTypeInfo>::borrow(invalid.as_ptr()).expect("not NULL");
+    assert!(matches!(
+        <&str>::load_from(&mut stored),
+        Err(SignalFfiError::InvalidUtf8String)
+    ));
+
+    assert!(matches!(
+        <&str as ArgTypeInfo>::borrow(std::ptr::null()),
+        Err(SignalFfiError::NullPointer)
+    ));
+}
+
+#[test]
+fn test_optional_str_arg() {
+    let valid = CString::new("abc").expect("no NUL bytes");
+    let mut stored = <Option<&str> as ArgTypeInfo>::borrow(valid.as_ptr()).expect("always OK");
+    assert!(matches!(
+        <Option<&str>>::load_from(&mut stored),
+        Ok(Some("abc"))
+    ));
+
+    let mut stored = <Option<&str> as ArgTypeInfo>::borrow(std::ptr::null()).expect("always OK");
+    assert!(matches!(<Option<&str>>::load_from(&mut stored), Ok(None)));
+
+    let invalid = CString::new(vec![0xC0, 0x80]).expect("no NUL bytes");
+    let mut stored = <Option<&str> as ArgTypeInfo>::borrow(invalid.as_ptr()).expect("always OK");
+    assert!(matches!(
+        <Option<&str>>::load_from(&mut stored),
+        Err(SignalFfiError::InvalidUtf8String)
+    ));
+}
+
+#[test]
+fn test_optional_slice_arg() {
+    let buffer = [1u8, 2, 3];
+    assert!(matches!(
+        <Option<&[u8]>>::convert_from(buffer.as_ptr(), buffer.len()),
+        Ok(Some(&[1, 2, 3]))
+    ));
+    assert!(matches!(
+        <Option<&[u8]>>::convert_from(buffer.as_ptr(), 0),
+        Ok(Some(&[]))
+    ));
+    assert!(matches!(
+        <Option<&[u8]>>::convert_from(std::ptr::null(), 0),
+        Ok(None)
+    ));
+    assert!(matches!(
+        <Option<&[u8]>>::convert_from(std::ptr::null(), 3),
+        Err(SignalFfiError::NullPointer)
+    ));
+}
+
+#[test]
+fn test_callback_arg() {
+    extern "C" fn double(value: u32) -> u32 {
+        value * 2
+    }
+    type DoubleCallbackFn = extern "C" fn(u32) -> u32;
+
+    let callback = FfiCallback::<DoubleCallbackFn>::convert_from(Some(double)).expect("not NULL");
+    assert_eq!(callback(21), 42);
+    assert!(matches!(
+        FfiCallback::<DoubleCallbackFn>::convert_from(None),
+        Err(SignalFfiError::NullPointer)
+    ));
+
+    assert!(matches!(
+        Option::<FfiCallback<DoubleCallbackFn>>::convert_from(Some(double)),
+        Ok(Some(_))
+    ));
+    assert!(matches!(
+        Option::<FfiCallback<DoubleCallbackFn>>::convert_from(None),
+        Ok(None)
+    ));
+}
+
+#[cfg(feature = "testing")]
+#[test]
+fn test_callback_bridge_fn() {
+    unsafe extern "C" fn increment(value: u32) -> u32 {
+        value + 1
+    }
+
+    let mut out = 0;
+    let error =
+        unsafe { crate::testing::signal_testing_invoke_callback(&mut out, Some(increment), 41) };
+    assert!(error.is_null());
+    assert_eq!(out, 42);
+
+    let error = unsafe { crate::testing::signal_testing_invoke_callback(&mut out, None, 41) };
+    assert!(!error.is_null());
+    let error = unsafe { Box::from_raw(error) };
+    assert!(matches!(*error, SignalFfiError::NullPointer));
+}
+
+#[test]
+fn test_optional_handle_round_trip() {
+    let address = ProtocolAddress::new("addr".to_string(), 1);
+    let foreign = Some(address.clone()).convert_into().expect("success");
+    match Option::<&ProtocolAddress>::convert_from(foreign) {
+        Ok(Some(loaded)) => assert_eq!(*loaded, address),
+        _ => panic!("expected Some"),
+    }
+    unsafe { drop(Box::from_raw(foreign)) };
+
+    let foreign = Option::<ProtocolAddress>::None
+        .convert_into()
+        .expect("success");
+    assert!(foreign.is_null());
+    assert!(matches!(
+        Option::<&ProtocolAddress>::convert_from(foreign),
+        Ok(None)
+    ));
+}
+
+#[cfg(feature = "testing")]
+#[test]
+fn test_buffer_list_round_trip() {
+    use std::convert::TryInto;
+
+    for &count in &[0u32, 1, 1500] {
+        let mut out = std::ptr::null();
+        let mut out_len = 0;
+        let mut out_count = 0;
+        let error = unsafe {
+            crate::testing::signal_testing_return_buffer_list(
+                &mut out,
+                &mut out_len,
+                &mut out_count,
+                count,
+            )
+        };
+        assert!(error.is_null());
+        assert_eq!(out_count, count as usize);
+
+        let flattened =
+            unsafe { Box::from_raw(std::slice::from_raw_parts_mut(out as *mut u8, out_len)) };
+        let mut remaining = &flattened[..];
+        for i in 0..count {
+            let (len, rest) = remaining.split_at(4);
+            let len = u32::from_be_bytes(len.try_into().expect("4 bytes")) as usize;
+            let (buffer, rest) = rest.split_at(len);
+            assert_eq!(buffer, &vec![i as u8; (i % 4) as usize][..]);
+            remaining = rest;
+        }
+        assert!(remaining.is_empty());
+    }
+}
+
+#[cfg(feature = "testing")]
+#[test]
+fn test_optional_buffer_result() {
+    let call = |len: u32| {
+        let mut out = std::ptr::null();
+        let mut out_len = usize::MAX;
+        let error = unsafe {
+            crate::testing::signal_testing_return_optional_buffer(&mut out, &mut out_len, len)
+        };
+        assert!(error.is_null());
+        (out, out_len)
+    };
+
+    let (out, out_len) = call(u32::MAX);
+    assert!(out.is_null());
+    assert_eq!(out_len, 0);
+
+    for &len in &[0u32, 1, 5] {
+        let (out, out_len) = call(len);
+        assert!(!out.is_null(), "empty buffers must not be returned as NULL");
+        assert_eq!(out_len, len as usize);
+        let buffer =
+            unsafe { Box::from_raw(std::slice::from_raw_parts_mut(out as *mut u8, out_len)) };
+        assert_eq!(&buffer[..], &vec![len as u8; len as usize][..]);
+    }
+}
+
+#[cfg(feature = "testing")]
+#[test]
+fn test_tuple_result() {
+    let mut buffer = std::ptr::null();
+    let mut buffer_len = usize::MAX;
+    let mut value = 0;
+    let mut is_even = true;
+    let error = unsafe {
+        crate::testing::signal_testing_return_tuple(
+            &mut buffer,
+            &mut buffer_len,
+            &mut value,
+            &mut is_even,
+            0x01020305,
+        )
+    };
+    assert!(error.is_null());
+    let buffer = unsafe {
+        Box::from_raw(std::slice::from_raw_parts_mut(
+            buffer as *mut u8,
+            buffer_len,
+        ))
+    };
+    assert_eq!(&buffer[..], &[1, 2, 3, 5]);
+    assert_eq!(value, 0x01020305);
+    assert!(!is_even);
+}
+
+#[test]
+fn test_uuid_arg() {
+    use crate::support::Uuid;
+
+    let bytes = [0xab; 16];
+    assert_eq!(
+        Uuid::convert_from(&bytes).expect("valid"),
+        Uuid::from_bytes(bytes)
+    );
+    assert!(matches!(
+        Uuid::convert_from(std::ptr::null()),
+        Err(SignalFfiError::NullPointer)
+    ));
+}
@@ -9,6 +9,8 @@ use std::fmt;
 use aes_gcm_siv::Error as AesGcmSivError;
 use libsignal_protocol::*;
 
+use crate::support::ErrorChain;
+
 /// The top-level error type (opaquely) returned to C clients when something goes wrong.
 #[derive(Debug)]
 pub enum SignalFfiError {
@@ -24,7 +26,7 @@ pub enum SignalFfiError {
 impl fmt::Display for SignalFfiError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            SignalFfiError::Signal(s) => write!(f, "{}", s),
+            SignalFfiError::Signal(s) => write!(f, "{}", ErrorChain(s)),
             SignalFfiError::AesGcmSiv(c) => {
                 write!(f, "AES-GCM-SIV operation failed: {}", c)
             }
@@ -0,0 +1,223 @@
+//
+// Copyright 2021 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+use futures::executor::ThreadPool;
+use futures::FutureExt;
+use libc::c_void;
+use std::future::Future;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::atomic::{AtomicPtr, Ordering};
+use std::sync::Once;
+use std::task::{Context, Poll};
+
+use super::*;
+
+/// Provides a placeholder value to pass to a completion callback along with an error.
+pub trait FfiDummyValue {
+    fn dummy_value() -> Self;
+}
+
+macro_rules! zero_dummy_value {
+    ($($typ:ty),+) => {
+        $(impl FfiDummyValue for $typ {
+            fn dummy_value() -> Self {
+                0
+            }
+        })+
+    };
+}
+
+zero_dummy_value!(u8, i32, u32, u64);
+
+impl FfiDummyValue for bool {
+    fn dummy_value() -> Self {
+        false
+    }
+}
+
+impl<T> FfiDummyValue for *mut T {
+    fn dummy_value() -> Self {
+        std::ptr::null_mut()
+    }
+}
+
+impl<T> FfiDummyValue for *const T {
+    fn dummy_value() -> Self {
+        std::ptr::null()
+    }
+}
+
+/// The result of an async `bridge_fn` with no value to report: either `()`, or a `Result` whose
+/// success value is ignored (as with the deprecated `bridge_fn_void`).
+pub trait VoidResultTypeInfo {
+    fn into_void_result(self) -> SignalFfiResult<()>;
+}
+
+impl VoidResultTypeInfo for () {
+    fn into_void_result(self) -> SignalFfiResult<()> {
+        Ok(())
+    }
+}
+
+impl<T, E> VoidResultTypeInfo for Result<T, E>
+where
+    SignalFfiError: From<E>,
+{
+    fn into_void_result(self) -> SignalFfiResult<()> {
+        self?;
+        Ok(())
+    }
+}
+
+/// The C context pointer passed back to a completion callback.
+///
+/// The caller of an async entry point is responsible for making sure the context can be used from
+/// whichever thread the callback ends up being invoked on.
+struct CallbackContext(*mut c_void);
+
+unsafe impl Send for CallbackContext {}
+
+/// Reports the result of a future exactly once.
+///
+/// If a `Completion` is dropped without being used, it reports an error instead, so the callback
+/// is still invoked if the future can't be run at all.
+struct Completion<R> {
+    report: Option<Box<dyn FnOnce(SignalFfiResult<R>) + Send>>,
+}
+
+impl<R> Completion<R> {
+    fn new(report: impl FnOnce(SignalFfiResult<R>) + Send + 'static) -> Self {
+        Self {
+            report: Some(Box::new(report)),
+        }
+    }
+
+    fn complete(mut self, result: SignalFfiResult<R>) {
+        let report = self.report.take().expect("only completed once");
+        report(result)
+    }
+}
+
+impl<R> Drop for Completion<R> {
+    fn drop(&mut self) {
+        if let Some(report) = self.report.take() {
+            report(Err(SignalFfiError::Signal(
+                SignalProtocolError::InternalError("future was dropped before it completed"),
+            )))
+        }
+    }
+}
+
+/// Runs the future produced by `make_future`, then passes its result to `done` along with `ctx`.
+///
+/// This is the implementation of `bridge_fn(ffi_async = true)`. `done` is called exactly once,
+/// with either a null error and the result, or a non-null error (owned by the callee) and a
+/// placeholder value. Errors from `make_future` are reported the same way, as are panics.
+///
+/// The future is polled once before this function returns; if it's already complete, `done` is
+/// called synchronously. Otherwise, it finishes on a shared thread pool (see [`executor`]), and
+/// `done` is called from one of the pool's threads.
+pub fn run_future_with_callback<F, Fut, R>(
+    done: extern "C" fn(*mut SignalFfiError, R::ResultType, *mut c_void),
+    ctx: *mut c_void,
+    make_future: F,
+) where
+    F: FnOnce() -> SignalFfiResult<Fut>,
+    Fut: Future<Output = R> + Send + 'static,
+    R: ResultTypeInfo + 'static,
+    R::ResultType: FfiDummyValue,
+{
+    let ctx = CallbackContext(ctx);
+    let completion =
+        Completion::new(
+            move |result: SignalFfiResult<R>| match result.and_then(R::convert_into) {
+                Ok(value) => done(std::ptr::null_mut(), value, ctx.0),
+                Err(e) => done(
+                    Box::into_raw(Box::new(e)),
+                    R::ResultType::dummy_value(),
+                    ctx.0,
+                ),
+            },
+        );
+    run_future(make_future, completion, Ok)
+}
+
+/// Like [`run_future_with_callback`], but for functions with no result value to report.
+pub fn run_void_future_with_callback<F, Fut>(
+    done: extern "C" fn(*mut SignalFfiError, *mut c_void),
+    ctx: *mut c_void,
+    make_future: F,
+) where
+    F: FnOnce() -> SignalFfiResult<Fut>,
+    Fut: Future + Send + 'static,
+    Fut::Output: VoidResultTypeInfo,
+{
+    let ctx = CallbackContext(ctx);
+    let completion = Completion::new(move |result: SignalFfiResult<()>| match result {
+        Ok(()) => done(std::ptr::null_mut(), ctx.0),
+        Err(e) => done(Box::into_raw(Box::new(e)), ctx.0),
+    });
+    run_future(
+        make_future,
+        completion,
+        VoidResultTypeInfo::into_void_result,
+    )
+}
+
+fn run_future<F, Fut, R>(
+    make_future: F,
+    completion: Completion<R>,
+    into_result: fn(Fut::Output) -> SignalFfiResult<R>,
+) where
+    F: FnOnce() -> SignalFfiResult<Fut>,
+    Fut: Future + Send + 'static,
+    R: 'static,
+{
+    let future = match catch_unwind(AssertUnwindSafe(make_future)) {
+        Ok(Ok(future)) => future,
+        Ok(Err(e)) => return completion.complete(Err(e)),
+        Err(panic) => return completion.complete(Err(SignalFfiError::UnexpectedPanic(panic))),
+    };
+
+    let mut future = Box::pin(AssertUnwindSafe(future).catch_unwind().map(
+        move |result| match result {
+            Ok(output) => into_result(output),
+            Err(panic) => Err(SignalFfiError::UnexpectedPanic(panic)),
+        },
+    ));
+
+    // Give the future a chance to finish right away, so that synchronous work stays synchronous.
+    // If it doesn't, the executor will poll it again with a real waker.
+    let mut cx = Context::from_waker(futures::task::noop_waker_ref());
+    if let Poll::Ready(result) = future.as_mut().poll(&mut cx) {
+        return completion.complete(result);
+    }
+
+    // If there's no pool to run it on, dropping the Completion reports an error.
+    if let Some(pool) = executor() {
+        pool.spawn_ok(async move { completion.complete(future.await) });
+    }
+}
+
+/// Returns the thread pool that runs futures that don't complete right away.
+///
+/// It's shared by every async entry point, so that a burst of calls doesn't start a thread for each
+/// one, and created on first use with one thread per CPU. Returns `None` if it couldn't be
+/// created.
+fn executor() -> Option<&'static ThreadPool> {
+    static CREATE: Once = Once::new();
+    static POOL: AtomicPtr<ThreadPool> = AtomicPtr::new(std::ptr::null_mut());
+    CREATE.call_once(|| {
+        match ThreadPool::builder()
+            .name_prefix("libsignal-ffi-future-")
+            .create()
+        {
+            Ok(pool) => POOL.store(Box::into_raw(Box::new(pool)), Ordering::Release),
+            Err(e) => log::error!("failed to start a thread pool for futures: {}", e),
+        }
+    });
+    // The pool is leaked once created, so the pointer is valid for the rest of the process.
+    unsafe { POOL.load(Ordering::Acquire).as_ref() }
+}
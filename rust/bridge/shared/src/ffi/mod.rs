@@ -14,6 +14,9 @@ pub use convert::*;
 mod error;
 pub use error::*;
 
+mod future;
+pub use future::*;
+
 mod storage;
 pub use storage::*;
 
@@ -61,6 +64,32 @@ pub unsafe fn native_handle_cast<T>(handle: *const T) -> Result<&'static T, Sign
     Ok(&*(handle))
 }
 
+/// Borrows an array of handles, as passed for `&[&T]` arguments.
+///
+/// As with byte slices, a `NULL` array is only allowed if the length is zero. A `NULL` element is
+/// reported as an [`InvalidArgument`](SignalProtocolError::InvalidArgument) error naming its
+/// index.
+pub unsafe fn native_handle_slice_cast<'a, T>(
+    handles: *const *const T,
+    len: usize,
+) -> Result<&'a [&'a T], SignalFfiError> {
+    if handles.is_null() {
+        if len != 0 {
+            return Err(SignalFfiError::NullPointer);
+        }
+        return Ok(&[]);
+    }
+
+    let handles = std::slice::from_raw_parts(handles, len);
+    if let Some(index) = handles.iter().position(|handle| handle.is_null()) {
+        return Err(SignalFfiError::Signal(
+            SignalProtocolError::InvalidArgument(format!("null handle at index {}", index)),
+        ));
+    }
+    // Non-null `*const T` and `&T` have the same representation.
+    Ok(&*(handles as *const [*const T] as *const [&T]))
+}
+
 pub unsafe fn native_handle_cast_mut<T>(handle: *mut T) -> Result<&'static mut T, SignalFfiError> {
     if handle.is_null() {
         return Err(SignalFfiError::NullPointer);
@@ -98,6 +127,89 @@ pub unsafe fn write_bytearray_to<T: Into<Box<[u8]>>>(
     Ok(())
 }
 
+/// Like [`write_bytearray_to`], but writes a `NULL` pointer and a length of zero for `None`.
+///
+/// This allows callers to distinguish "no value" from an empty buffer, which will still have a
+/// non-`NULL` pointer.
+pub unsafe fn write_optional_bytearray_to<T: Into<Box<[u8]>>>(
+    out: *mut *const c_uchar,
+    out_len: *mut size_t,
+    value: Option<T>,
+) -> Result<(), SignalFfiError> {
+    match value {
+        Some(value) => write_bytearray_to(out, out_len, value),
+        None => {
+            if out.is_null() || out_len.is_null() {
+                return Err(SignalFfiError::NullPointer);
+            }
+            *out = std::ptr::null();
+            *out_len = 0;
+            Ok(())
+        }
+    }
+}
+
+/// Writes a list of buffers as a single flattened buffer, along with the number of buffers.
+///
+/// See the [`ResultTypeInfo`] implementation for `Vec<Box<[u8]>>` for the flattened format.
+pub unsafe fn write_bytearray_list_to<T: ResultTypeInfo<ResultType = (Box<[u8]>, usize)>>(
+    out: *mut *const c_uchar,
+    out_len: *mut size_t,
+    out_count: *mut size_t,
+    value: T,
+) -> Result<(), SignalFfiError> {
+    if out_count.is_null() {
+        return Err(SignalFfiError::NullPointer);
+    }
+
+    let (flattened, count) = value.convert_into()?;
+    write_bytearray_to(out, out_len, flattened)?;
+    *out_count = count;
+
+    Ok(())
+}
+
+/// Writes a list of handles as a Rust-owned array, along with the number of handles.
+///
+/// The caller takes ownership of each handle, and must free the array itself with
+/// `signal_free_handle_list`.
+pub unsafe fn write_handle_list_to<T, R: ResultTypeInfo<ResultType = Box<[*mut T]>>>(
+    out: *mut *mut *mut T,
+    out_count: *mut size_t,
+    value: R,
+) -> Result<(), SignalFfiError> {
+    if out.is_null() || out_count.is_null() {
+        return Err(SignalFfiError::NullPointer);
+    }
+
+    let handles = value.convert_into()?;
+    *out_count = handles.len();
+    *out = Box::into_raw(handles) as *mut *mut T;
+
+    Ok(())
+}
+
+/// Used in the implementation of `bridge_fn` to keep track of all generated entry points.
+///
+/// `$arity` is the number of parameters the entry point takes in C, as listed by
+/// `describe_all` when the "describe" feature is enabled.
+///
+/// Not intended to be invoked directly.
+macro_rules! ffi_register {
+    ( $name:ident, $arity:expr ) => {
+        paste! {
+            #[cfg(any(test, feature = "describe"))]
+            #[allow(non_upper_case_globals)]
+            #[linkme::distributed_slice(crate::describe::BRIDGED_FNS)]
+            static [<describe_ $name>]: crate::describe::BridgedFn = crate::describe::BridgedFn {
+                name: stringify!($name),
+                arity: $arity,
+                platform: crate::describe::Platform::Ffi,
+            };
+        }
+    };
+}
+
 /// Used by [`bridge_handle`](crate::support::bridge_handle).
 ///
 /// Not intended to be invoked directly.
@@ -116,6 +228,40 @@ macro_rules! ffi_bridge_destroy {
                     Ok(())
                 })
             }
+
+            #[cfg(feature = "ffi")]
+            ffi_register!([<signal_ $ffi_name _destroy>], 1);
+        }
+    };
+}
+
+/// Implementation of the `serialize` flag of [`bridge_handle`](crate::support::bridge_handle) for
+/// FFI.
+macro_rules! ffi_bridge_serialize {
+    ( $typ:ty as false $(, serialize = $_:tt)? ) => {};
+    ( $typ:ty as $ffi_name:ident $(, serialize = false)? ) => {};
+    ( $typ:ty as $ffi_name:ident, serialize = true ) => {
+        paste! {
+            #[no_mangle]
+            pub unsafe extern "C" fn [<signal_ $ffi_name _serialize>](
+                out: *mut *const libc::c_uchar,
+                out_len: *mut libc::size_t,
+                obj: *const $typ,
+            ) -> *mut ffi::SignalFfiError {
+                ffi::run_ffi_safe(|| {
+                    let obj = ffi::native_handle_cast::<$typ>(obj)?;
+                    let result = TransformHelper(<$typ>::serialize(obj));
+                    let bytes = result.ok_if_needed()?.into_vec_if_needed().0;
+                    ffi::write_bytearray_to(out, out_len, bytes)
+                })
+            }
+
+            ffi_register!([<signal_ $ffi_name _serialize>], 3);
+        }
+    };
+    ( $typ:ty $(, serialize = $_:tt)? ) => {
+        paste! {
+            ffi_bridge_serialize!($typ as [<$typ:snake>] $(, serialize = $_)?);
         }
     };
 }
@@ -140,6 +286,9 @@ macro_rules! ffi_bridge_deserialize {
                     ffi::box_object(p, $typ::$fn(data))
                 })
             }
+
+            #[cfg(feature = "ffi")]
+            ffi_register!([<signal_ $ffi_name _deserialize>], 3);
         }
     };
     ( $typ:ident::$fn:path ) => {
@@ -148,3 +297,131 @@ macro_rules! ffi_bridge_deserialize {
         }
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::describe::{declared_functions, generated_functions, Platform};
+
+    const HEADER: &str = include_str!("../../../../../swift/Sources/SignalFfi/signal_ffi.h");
+    /// Declares the `signal_testing_*` functions, which are only built with the "testing" feature.
+    const TESTING_HEADER: &str = include_str!("../../../ffi/tests/signal_ffi_testing.h");
+
+    #[test]
+    fn test_all_entry_points_declared_in_header() {
+        let generated = generated_functions(Platform::Ffi);
+        assert!(!generated.is_empty());
+        let mut declared = declared_functions(HEADER);
+        declared.extend(declared_functions(TESTING_HEADER));
+
+        let missing: Vec<&str> = generated
+            .into_iter()
+            .filter(|name| !declared.contains(name))
+            .collect();
+        assert!(
+            missing.is_empty(),
+            "missing from signal_ffi.h (regenerate it with swift/build_ffi.sh --generate-ffi): {:?}",
+            missing
+        );
+    }
+
+    #[test]
+    fn test_deprecated_entry_points_marked_in_header() {
+        // cbindgen writes the note with `deprecated_with_note` from cbindgen.toml.
+        assert!(TESTING_HEADER.contains(
+            "SIGNAL_DEPRECATED(\"only for testing\") SignalFfiError *signal_testing_deprecated_echo("
+        ));
+    }
+
+    #[test]
+    fn test_bridge_deserialize_names() {
+        let generated = generated_functions(Platform::Ffi);
+        for name in &[
+            // Default names are derived from the type.
+            "signal_pre_key_signal_message_deserialize",
+            "signal_sender_key_message_deserialize",
+            "signal_session_record_deserialize",
+            // Overridden names replace the type name but keep the suffix.
+            "signal_message_deserialize",
+            "signal_publickey_deserialize",
+        ] {
+            assert!(generated.contains(name), "missing {}", name);
+        }
+    }
+
+    #[test]
+    fn test_bridge_deserialize_round_trip() {
+        use crate::protocol::{
+            signal_publickey_deserialize, signal_publickey_destroy, signal_publickey_serialize,
+        };
+
+        let key = KeyPair::generate(&mut rand::rngs::OsRng).public_key;
+        let serialized = key.serialize();
+
+        unsafe {
+            let mut handle: *mut PublicKey = std::ptr::null_mut();
+            let error =
+                signal_publickey_deserialize(&mut handle, serialized.as_ptr(), serialized.len());
+            assert!(error.is_null());
+            assert_eq!(*handle, key);
+
+            let mut out: *const c_uchar = std::ptr::null();
+            let mut out_len: size_t = 0;
+            let error = signal_publickey_serialize(&mut out, &mut out_len, handle);
+            assert!(error.is_null());
+            let round_tripped =
+                Box::from_raw(std::slice::from_raw_parts_mut(out as *mut u8, out_len));
+            assert_eq!(&round_tripped[..], &serialized[..]);
+
+            assert!(signal_publickey_destroy(handle).is_null());
+        }
+    }
+
+    #[test]
+    fn test_handle_slice_arg() {
+        let keys: Vec<PublicKey> = (0..3)
+            .map(|_| KeyPair::generate(&mut rand::rngs::OsRng).public_key)
+            .collect();
+        let mut handles: Vec<*const PublicKey> = keys.iter().map(|key| key as *const _).collect();
+
+        let loaded = <&[&PublicKey]>::convert_from(handles.as_ptr(), handles.len()).expect("valid");
+        assert_eq!(loaded.len(), keys.len());
+        assert!(loaded.iter().zip(&keys).all(|(a, b)| *a == b));
+
+        let empty = <&[&PublicKey]>::convert_from(std::ptr::null(), 0).expect("valid");
+        assert!(empty.is_empty());
+        assert!(matches!(
+            <&[&PublicKey]>::convert_from(std::ptr::null(), 1),
+            Err(SignalFfiError::NullPointer)
+        ));
+
+        handles[1] = std::ptr::null();
+        match <&[&PublicKey]>::convert_from(handles.as_ptr(), handles.len()) {
+            Err(SignalFfiError::Signal(SignalProtocolError::InvalidArgument(message))) => {
+                assert_eq!(message, "null handle at index 1")
+            }
+            _ => panic!("null element accepted"),
+        }
+    }
+
+    #[test]
+    fn test_bridge_deserialize_malformed_input() {
+        use crate::protocol::signal_publickey_deserialize;
+
+        let malformed = [0xffu8; 33];
+        unsafe {
+            let mut handle: *mut PublicKey = std::ptr::null_mut();
+            let error =
+                signal_publickey_deserialize(&mut handle, malformed.as_ptr(), malformed.len());
+            assert!(handle.is_null());
+            assert!(matches!(
+                *Box::from_raw(error),
+                SignalFfiError::Signal(SignalProtocolError::BadKeyType(0xff))
+            ));
+
+            let error = signal_publickey_deserialize(&mut handle, std::ptr::null(), 0);
+            assert!(handle.is_null());
+            assert!(matches!(*Box::from_raw(error), SignalFfiError::NullPointer));
+        }
+    }
+}
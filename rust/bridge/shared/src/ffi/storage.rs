@@ -116,7 +116,8 @@ impl IdentityKeyStore for &FfiIdentityKeyStoreStruct {
         identity: &IdentityKey,
         direction: Direction,
         ctx: Context,
-    ) -> Result<bool, SignalProtocolError> {
+    ) -> Result<IdentityTrust, SignalProtocolError> {
+        let saved_ctx = ctx;
         let ctx = ctx.unwrap_or(std::ptr::null_mut());
         let direction = match direction {
             Direction::Sending => FfiDirection::Sending,
@@ -130,14 +131,22 @@ impl IdentityKeyStore for &FfiIdentityKeyStoreStruct {
             ctx,
         );
 
-        match result {
-            0 => Ok(false),
-            1 => Ok(true),
-            r => Err(SignalProtocolError::ApplicationCallbackError(
-                "is_trusted_identity",
-                Box::new(CallbackError::check(r).unwrap()),
-            )),
-        }
+        let trusted = match result {
+            0 => false,
+            1 => true,
+            r => {
+                return Err(SignalProtocolError::ApplicationCallbackError(
+                    "is_trusted_identity",
+                    Box::new(CallbackError::check(r).unwrap()),
+                ))
+            }
+        };
+        let saved = if trusted {
+            None
+        } else {
+            self.get_identity(address, saved_ctx).await.ok().flatten()
+        };
+        Ok(IdentityTrust::from_saved_key(trusted, identity, saved))
     }
 
     async fn get_identity(
@@ -3,12 +3,15 @@
 // SPDX-License-Identifier: AGPL-3.0-only
 //
 
-use jni::objects::{AutoArray, JString, ReleaseMode};
-use jni::sys::{jbyte, JNI_FALSE, JNI_TRUE};
+use jni::objects::{
+    AutoArray, AutoPrimitiveArray, JByteBuffer, JObject, JString, JValue, ReleaseMode,
+};
+use jni::sys::{jbyte, jsize, JNI_FALSE, JNI_TRUE};
 use jni::JNIEnv;
-use libsignal_protocol::*;
 use paste::paste;
 use std::borrow::Cow;
+use std::cell::Cell;
+use std::convert::TryFrom;
 use std::ops::Deref;
 
 use super::*;
@@ -50,6 +53,19 @@ pub trait ArgTypeInfo<'storage, 'context: 'storage>: Sized {
     fn borrow(env: &'context JNIEnv, foreign: Self::ArgType) -> SignalJniResult<Self::StoredType>;
     /// Loads the Rust value from the data that's been `stored` by [`borrow()`](Self::borrow()).
     fn load_from(env: &JNIEnv, stored: &'storage mut Self::StoredType) -> SignalJniResult<Self>;
+    /// Like [`load_from()`](Self::load_from()), but may pin Java data in place (see
+    /// [`JniPinnedSlice`]) rather than copying it.
+    ///
+    /// Once an argument has been loaded this way, no other JNI calls may be made until its storage
+    /// is dropped, not even to borrow or load other arguments. `bridge_fn` only does this for
+    /// functions that can't call back into Java, and releases the arguments before converting the
+    /// result.
+    fn load_from_pinned(
+        env: &JNIEnv,
+        stored: &'storage mut Self::StoredType,
+    ) -> SignalJniResult<Self> {
+        Self::load_from(env, stored)
+    }
 }
 
 /// A simpler interface for [`ArgTypeInfo`] for when no local storage is needed.
@@ -151,14 +167,14 @@ impl<'a> SimpleArgTypeInfo<'a> for Option<u32> {
     }
 }
 
-/// Supports values `0..=Long.MAX_VALUE`.
+/// Reinterprets the bits of a `long`, so that Java code can pass any `u64` value.
 ///
-/// Negative `long` values are *not* reinterpreted as large `u64` values.
-/// Note that this is different from the implementation of [`ResultTypeInfo`] for `u64`.
+/// Negative `long` values become values above `Long.MAX_VALUE`, the same as
+/// `Long.toUnsignedString` would show them.
 impl<'a> SimpleArgTypeInfo<'a> for u64 {
     type ArgType = jlong;
     fn convert_from(_env: &JNIEnv, foreign: jlong) -> SignalJniResult<Self> {
-        jlong_to_u64(foreign)
+        Ok(foreign as u64)
     }
 }
 
@@ -170,10 +186,47 @@ impl<'a> SimpleArgTypeInfo<'a> for u8 {
     }
 }
 
+/// Copies a UUID out of a `byte[]`, which must be exactly 16 bytes long.
+///
+/// Any other length is rejected with `IllegalArgumentException`, and a `null` array with
+/// [`SignalJniError::NullHandle`] (`NullPointerException`).
+impl<'a> SimpleArgTypeInfo<'a> for crate::support::Uuid {
+    type ArgType = jbyteArray;
+    fn convert_from(env: &JNIEnv, foreign: jbyteArray) -> SignalJniResult<Self> {
+        if foreign.is_null() {
+            return Err(SignalJniError::NullHandle);
+        }
+        Ok(Self::from_slice(&env.convert_byte_array(foreign)?)?)
+    }
+}
+
+/// Decodes a Java string from its modified UTF-8 form.
+///
+/// Strings with unpaired surrogates (which have no UTF-8 representation) or NUL characters (which
+/// can't be passed on to C) are rejected with [`SignalProtocolError::InvalidArgument`]
+/// (`IllegalArgumentException`) rather than being converted lossily.
+fn decode_java_string(env: &JNIEnv, foreign: JString) -> SignalJniResult<String> {
+    if foreign.is_null() {
+        return Err(SignalJniError::NullHandle);
+    }
+    let java_str = env.get_string(foreign)?;
+    let decoded = cesu8::from_java_cesu8(java_str.to_bytes()).map_err(|_| {
+        SignalProtocolError::InvalidArgument("string contains an unpaired surrogate".to_string())
+    })?;
+    if decoded.contains('\0') {
+        return Err(SignalProtocolError::InvalidArgument(
+            "string contains a NUL character".to_string(),
+        )
+        .into());
+    }
+    Ok(decoded.into_owned())
+}
+
+/// See [`decode_java_string`] for the error cases.
 impl<'a> SimpleArgTypeInfo<'a> for String {
     type ArgType = JString<'a>;
     fn convert_from(env: &JNIEnv, foreign: JString<'a>) -> SignalJniResult<Self> {
-        Ok(env.get_string(foreign)?.into())
+        decode_java_string(env, foreign)
     }
 }
 
@@ -188,30 +241,158 @@ impl<'a> SimpleArgTypeInfo<'a> for Option<String> {
     }
 }
 
-/// A wrapper around [`jni::objects::AutoArray`] that also stores the array's length.
+/// Decodes a Java string up front, so that the Rust function can borrow it as a `&str`.
+///
+/// See [`decode_java_string`] for the error cases.
+impl<'storage, 'context: 'storage> ArgTypeInfo<'storage, 'context> for &'storage str {
+    type ArgType = JString<'context>;
+    type StoredType = String;
+    fn borrow(env: &'context JNIEnv, foreign: Self::ArgType) -> SignalJniResult<Self::StoredType> {
+        decode_java_string(env, foreign)
+    }
+    fn load_from(_env: &JNIEnv, stored: &'storage mut Self::StoredType) -> SignalJniResult<Self> {
+        Ok(stored)
+    }
+}
+
+/// `byte[]` arguments longer than this are pinned rather than copied by
+/// [`ArgTypeInfo::load_from_pinned`].
+///
+/// Pinning an array can hold up the garbage collector, which isn't worth it for short arrays.
+pub const BYTE_ARRAY_PIN_THRESHOLD: usize = 4 * 1024;
+
+/// A `byte[]` pinned in place with `GetPrimitiveArrayCritical`, and released with
+/// `ReleasePrimitiveArrayCritical` when dropped.
+///
+/// While any array is pinned, the JVM may be unable to garbage-collect, so the current thread must
+/// not make any other JNI calls (besides pinning more arrays) or wait on another Java thread.
+/// Keep the pinned window short.
+pub struct JniPinnedSlice<'a> {
+    jni_array: AutoPrimitiveArray<'a, 'a>,
+    len: usize,
+}
+
+thread_local! {
+    /// The number of [`JniPinnedSlice`]s alive on this thread.
+    static PINNED_ARRAY_COUNT: Cell<usize> = Cell::new(0);
+}
+
+/// Returns true if the current thread has any arrays pinned, and so must not call into Java.
+///
+/// This is for code that can run at arbitrary points, like a panic hook or a logger.
+pub fn has_pinned_arrays() -> bool {
+    PINNED_ARRAY_COUNT.with(|count| count.get() != 0)
+}
+
+impl<'a> JniPinnedSlice<'a> {
+    pub fn new(env: &'a JNIEnv<'a>, array: jbyteArray) -> SignalJniResult<Self> {
+        let len = env.get_array_length(array)?;
+        assert!(len >= 0);
+        Self::with_len(env, array, len as usize)
+    }
+
+    /// Pins `array` without checking its length, which would be a JNI call of its own.
+    fn with_len(env: &'a JNIEnv<'a>, array: jbyteArray, len: usize) -> SignalJniResult<Self> {
+        let jni_array = env.get_primitive_array_critical(array, ReleaseMode::NoCopyBack)?;
+        PINNED_ARRAY_COUNT.with(|count| count.set(count.get() + 1));
+        Ok(Self { jni_array, len })
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.jni_array.as_ptr() as *const u8, self.len) }
+    }
+}
+
+impl Drop for JniPinnedSlice<'_> {
+    fn drop(&mut self) {
+        // The array itself is released right after this, when `jni_array` is dropped.
+        PINNED_ARRAY_COUNT.with(|count| count.set(count.get() - 1));
+    }
+}
+
+/// Local storage for a `byte[]` argument.
+///
+/// Arrays up to [`BYTE_ARRAY_PIN_THRESHOLD`] bytes are copied as soon as they're borrowed, so that
+/// releasing them never needs a JNI call (which would not be allowed while another argument is
+/// pinned). Longer arrays are fetched when loaded, either pinned or with `GetByteArrayElements`.
 pub struct AutoByteSlice<'a> {
-    jni_array: AutoArray<'a, 'a, jbyte>,
+    env: &'a JNIEnv<'a>,
+    array: jbyteArray,
     len: usize,
+    contents: ByteSliceContents<'a>,
+}
+
+enum ByteSliceContents<'a> {
+    Copied(Vec<u8>),
+    NotLoaded,
+    Elements(AutoArray<'a, 'a, jbyte>),
+    Pinned(JniPinnedSlice<'a>),
+}
+
+impl<'a> AutoByteSlice<'a> {
+    fn new(env: &'a JNIEnv<'a>, array: jbyteArray) -> SignalJniResult<Self> {
+        if array.is_null() {
+            return Err(SignalJniError::NullHandle);
+        }
+        let len = env.get_array_length(array)?;
+        assert!(len >= 0);
+        let len = len as usize;
+        let contents = if len <= BYTE_ARRAY_PIN_THRESHOLD {
+            ByteSliceContents::Copied(env.convert_byte_array(array)?)
+        } else {
+            ByteSliceContents::NotLoaded
+        };
+        Ok(Self {
+            env,
+            array,
+            len,
+            contents,
+        })
+    }
+
+    fn load(&mut self, pin: bool) -> SignalJniResult<&[u8]> {
+        if let ByteSliceContents::NotLoaded = self.contents {
+            self.contents = if pin {
+                ByteSliceContents::Pinned(JniPinnedSlice::with_len(self.env, self.array, self.len)?)
+            } else {
+                ByteSliceContents::Elements(
+                    self.env
+                        .get_byte_array_elements(self.array, ReleaseMode::NoCopyBack)?,
+                )
+            };
+        }
+        Ok(match &self.contents {
+            ByteSliceContents::Copied(bytes) => bytes,
+            ByteSliceContents::NotLoaded => unreachable!("loaded above"),
+            ByteSliceContents::Elements(jni_array) => unsafe {
+                std::slice::from_raw_parts(jni_array.as_ptr() as *const u8, self.len)
+            },
+            ByteSliceContents::Pinned(pinned) => pinned.as_slice(),
+        })
+    }
 }
 
+/// A `null` array is rejected with [`SignalJniError::NullHandle`] (`NullPointerException`).
+///
+/// Long arrays are pinned by [`load_from_pinned`](ArgTypeInfo::load_from_pinned); see
+/// [`AutoByteSlice`].
 impl<'storage, 'context: 'storage> ArgTypeInfo<'storage, 'context> for &'storage [u8] {
     type ArgType = jbyteArray;
     type StoredType = AutoByteSlice<'context>;
     fn borrow(env: &'context JNIEnv, foreign: Self::ArgType) -> SignalJniResult<Self::StoredType> {
-        let len = env.get_array_length(foreign)?;
-        assert!(len >= 0);
-        Ok(AutoByteSlice {
-            jni_array: env.get_byte_array_elements(foreign, ReleaseMode::NoCopyBack)?,
-            len: len as usize,
-        })
+        AutoByteSlice::new(env, foreign)
     }
     fn load_from(
         _env: &JNIEnv,
         stored: &'storage mut Self::StoredType,
     ) -> SignalJniResult<&'storage [u8]> {
-        Ok(unsafe {
-            std::slice::from_raw_parts(stored.jni_array.as_ptr() as *const u8, stored.len)
-        })
+        stored.load(false)
+    }
+    fn load_from_pinned(
+        _env: &JNIEnv,
+        stored: &'storage mut Self::StoredType,
+    ) -> SignalJniResult<&'storage [u8]> {
+        stored.load(true)
     }
 }
 
@@ -234,6 +415,70 @@ impl<'storage, 'context: 'storage> ArgTypeInfo<'storage, 'context> for Option<&'
             .map(|s| <&'storage [u8]>::load_from(env, s))
             .transpose()
     }
+    fn load_from_pinned(
+        env: &JNIEnv,
+        stored: &'storage mut Self::StoredType,
+    ) -> SignalJniResult<Option<&'storage [u8]>> {
+        stored
+            .as_mut()
+            .map(|s| <&'storage [u8]>::load_from_pinned(env, s))
+            .transpose()
+    }
+}
+
+/// Converts `java.nio.ByteBuffer` arguments to byte slices without copying.
+///
+/// This is used instead of [`ArgTypeInfo`] for `&[u8]` and `Option<&[u8]>` arguments to functions
+/// marked `bridge_fn(jni_direct_buffers = true)`. The buffer must be a direct buffer; the
+/// resulting slice covers its remaining bytes, from its position up to its limit.
+pub trait DirectBufferArgTypeInfo<'a>: Sized {
+    fn convert_from(env: &'a JNIEnv, foreign: JByteBuffer) -> SignalJniResult<Self>;
+}
+
+impl<'a> DirectBufferArgTypeInfo<'a> for &'a [u8] {
+    fn convert_from(env: &'a JNIEnv, foreign: JByteBuffer) -> SignalJniResult<Self> {
+        if foreign.is_null() {
+            return Err(SignalJniError::NullHandle);
+        }
+
+        let get_index = |method| -> SignalJniResult<usize> {
+            match call_method_checked(env, foreign, method, "()I", &[])? {
+                JValue::Int(i) => Ok(i as usize),
+                other => Err(SignalJniError::UnexpectedJniResultType(
+                    method,
+                    other.type_name(),
+                )),
+            }
+        };
+        let position = get_index("position")?;
+        let limit = get_index("limit")?;
+
+        // Non-direct buffers have no stable address to borrow.
+        let contents = env.get_direct_buffer_address(foreign).map_err(|_| {
+            SignalProtocolError::InvalidArgument("ByteBuffer must be direct".to_string())
+        })?;
+        let len = contents.len();
+        contents
+            .get(position..limit)
+            .map(|slice| &*slice)
+            .ok_or_else(|| {
+                SignalProtocolError::InvalidArgument(format!(
+                    "invalid ByteBuffer range {}..{} (capacity {})",
+                    position, limit, len
+                ))
+                .into()
+            })
+    }
+}
+
+impl<'a> DirectBufferArgTypeInfo<'a> for Option<&'a [u8]> {
+    fn convert_from(env: &'a JNIEnv, foreign: JByteBuffer) -> SignalJniResult<Self> {
+        if foreign.is_null() {
+            Ok(None)
+        } else {
+            <&'a [u8]>::convert_from(env, foreign).map(Some)
+        }
+    }
 }
 
 macro_rules! store {
@@ -304,13 +549,13 @@ impl ResultTypeInfo for Option<u32> {
     }
 }
 
-/// Reinterprets the bits of the `u64` as a Java `long`.
+/// Reinterprets the bits as a `long`, like the implementation of [`ArgTypeInfo`] for `u64`.
 ///
-/// Note that this is different from the implementation of [`ArgTypeInfo`] for `u64`.
+/// Values above `Long.MAX_VALUE` come out negative; Java code should treat the result as
+/// unsigned (e.g. with `Long.compareUnsigned`).
 impl ResultTypeInfo for u64 {
     type ResultType = jlong;
     fn convert_into(self, _env: &JNIEnv) -> SignalJniResult<Self::ResultType> {
-        // Note that we don't check bounds here.
         Ok(self as jlong)
     }
 }
@@ -346,21 +591,79 @@ impl ResultTypeInfo for Option<&str> {
     }
 }
 
-impl<T: ResultTypeInfo> ResultTypeInfo for Result<T, SignalProtocolError> {
-    type ResultType = T::ResultType;
+impl ResultTypeInfo for Box<[u8]> {
+    type ResultType = jbyteArray;
     fn convert_into(self, env: &JNIEnv) -> SignalJniResult<Self::ResultType> {
-        T::convert_into(self?, env)
+        Ok(env.byte_array_from_slice(&self)?)
     }
 }
 
-impl<T: ResultTypeInfo> ResultTypeInfo for Result<T, aes_gcm_siv::Error> {
-    type ResultType = T::ResultType;
+impl ResultTypeInfo for Vec<u8> {
+    type ResultType = jbyteArray;
     fn convert_into(self, env: &JNIEnv) -> SignalJniResult<Self::ResultType> {
-        T::convert_into(self?, env)
+        Ok(env.byte_array_from_slice(&self)?)
+    }
+}
+
+impl ResultTypeInfo for Option<Box<[u8]>> {
+    type ResultType = jbyteArray;
+    fn convert_into(self, env: &JNIEnv) -> SignalJniResult<Self::ResultType> {
+        match self {
+            Some(buffer) => buffer.convert_into(env),
+            None => Ok(std::ptr::null_mut()),
+        }
     }
 }
 
-impl<T: ResultTypeInfo> ResultTypeInfo for SignalJniResult<T> {
+/// Returns a Java `byte[][]`.
+impl ResultTypeInfo for Vec<Box<[u8]>> {
+    type ResultType = jobjectArray;
+    fn convert_into(self, env: &JNIEnv) -> SignalJniResult<Self::ResultType> {
+        let len = jsize::try_from(self.len())
+            .map_err(|_| SignalJniError::IntegerOverflow(format!("{} to jsize", self.len())))?;
+        let array = env.new_object_array(len, "[B", JObject::null())?;
+        for (i, buffer) in (0..).zip(self.iter()) {
+            let element = env.byte_array_from_slice(buffer)?;
+            env.set_object_array_element(array, i, element)?;
+            // Don't let the local references pile up for long lists.
+            env.delete_local_ref(element.into())?;
+        }
+        Ok(array)
+    }
+}
+
+/// A Java `Object[]` holding the elements of a tuple result.
+pub type JavaTuple = jobjectArray;
+
+/// Returns a Java `Object[]`, with primitive elements boxed (see [`IntoJavaObject`]).
+macro_rules! tuple_result {
+    ($($element:ident $index:tt),+) => {
+        impl<$($element),+> ResultTypeInfo for ($($element,)+)
+        where
+            $($element: ResultTypeInfo, $element::ResultType: IntoJavaObject),+
+        {
+            type ResultType = JavaTuple;
+            fn convert_into(self, env: &JNIEnv) -> SignalJniResult<Self::ResultType> {
+                let elements = [$(self.$index.convert_into(env)?.into_java_object(env)?),+];
+                let array =
+                    env.new_object_array(elements.len() as jsize, "java/lang/Object", JObject::null())?;
+                for (i, element) in (0..).zip(elements.iter()) {
+                    env.set_object_array_element(array, i, *element)?;
+                }
+                Ok(array)
+            }
+        }
+    };
+}
+
+tuple_result!(A 0, B 1);
+tuple_result!(A 0, B 1, C 2);
+
+/// Any error that can be converted to a [`SignalJniError`] can be returned from a `bridge_fn`.
+impl<T: ResultTypeInfo, E> ResultTypeInfo for Result<T, E>
+where
+    SignalJniError: From<E>,
+{
     type ResultType = T::ResultType;
     fn convert_into(self, env: &JNIEnv) -> SignalJniResult<Self::ResultType> {
         T::convert_into(self?, env)
@@ -417,6 +720,9 @@ macro_rules! jni_bridge_handle {
                 }
             }
         }
+        // Java code can hold several references to the same wrapper object, and so the same
+        // handle, so the exclusivity of `&mut` can't be checked here. Wrapper classes must not
+        // hand out their handles, and must not pass the same handle to one call twice.
         impl<'a> jni::SimpleArgTypeInfo<'a> for &mut $typ {
             type ArgType = jni::ObjectHandle;
             fn convert_from(
@@ -426,6 +732,25 @@ macro_rules! jni_bridge_handle {
                 Ok(unsafe { jni::native_handle_cast(foreign) }?)
             }
         }
+        impl<'storage, 'context: 'storage> jni::ArgTypeInfo<'storage, 'context>
+            for &'storage [&'storage $typ]
+        {
+            type ArgType = jni::jlongArray;
+            type StoredType = Vec<&'static $typ>;
+            #[allow(clippy::not_unsafe_ptr_arg_deref)]
+            fn borrow(
+                env: &'context jni::JNIEnv,
+                foreign: Self::ArgType,
+            ) -> jni::SignalJniResult<Self::StoredType> {
+                unsafe { jni::native_handle_array_cast(env, foreign) }
+            }
+            fn load_from(
+                _env: &jni::JNIEnv,
+                stored: &'storage mut Self::StoredType,
+            ) -> jni::SignalJniResult<Self> {
+                Ok(&stored[..])
+            }
+        }
         impl jni::ResultTypeInfo for $typ {
             type ResultType = jni::ObjectHandle;
             fn convert_into(self, _env: &jni::JNIEnv) -> jni::SignalJniResult<Self::ResultType> {
@@ -441,6 +766,12 @@ macro_rules! jni_bridge_handle {
                 }
             }
         }
+        impl jni::ResultTypeInfo for Vec<$typ> {
+            type ResultType = jni::jlongArray;
+            fn convert_into(self, env: &jni::JNIEnv) -> jni::SignalJniResult<Self::ResultType> {
+                jni::box_object_array(env, self)
+            }
+        }
         jni_bridge_destroy!($typ as $jni_name);
     };
     ( $typ:ty ) => {
@@ -498,15 +829,24 @@ macro_rules! jni_arg_type {
     (Option<String>) => {
         jni::JString
     };
+    (&str) => {
+        jni::JString
+    };
     (&[u8]) => {
         jni::jbyteArray
     };
     (Option<&[u8]>) => {
         jni::jbyteArray
     };
+    (Uuid) => {
+        jni::jbyteArray
+    };
     (&mut dyn $typ:ty) => {
         paste!(jni::[<Java $typ>])
     };
+    (&[& $typ:ty]) => {
+        jni::jlongArray
+    };
     (& $typ:ty) => {
         jni::ObjectHandle
     };
@@ -538,6 +878,15 @@ macro_rules! jni_result_type {
     (Result<Option<&$typ:tt> $(, $_:ty)?>) => {
         jni_result_type!(&$typ)
     };
+    // Note the space between the closing angle brackets, which keeps them separate tokens to
+    // match the output of the bridge_fn macros.
+    (Result<Vec<Box<[u8]> > $(, $_:ty)?>) => {
+        jni::jobjectArray
+    };
+    // A tuple is a single token tree, so this also covers tuples inside Results.
+    (($($_:tt)+)) => {
+        jni::JavaTuple
+    };
     (Result<$typ:tt<$($args:tt),+> $(, $_:ty)?>) => {
         jni_result_type!($typ<$($args)+>)
     };
@@ -575,6 +924,13 @@ macro_rules! jni_result_type {
     (Vec<u8>) => {
         jni::jbyteArray
     };
+    (Vec<Box<[u8]> >) => {
+        jni::jobjectArray
+    };
+    // Any other list is a list of bridged handles.
+    (Vec<$typ:ident>) => {
+        jni::jlongArray
+    };
     ( $typ:ty ) => {
         jni::ObjectHandle
     };
@@ -11,6 +11,7 @@ use aes_gcm_siv::Error as AesGcmSivError;
 use libsignal_protocol::*;
 
 use super::*;
+use crate::support::{describe_panic, ErrorChain};
 
 /// The top-level error type for when something goes wrong.
 #[derive(Debug)]
@@ -28,7 +29,7 @@ pub enum SignalJniError {
 impl fmt::Display for SignalJniError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            SignalJniError::Signal(s) => write!(f, "{}", s),
+            SignalJniError::Signal(s) => write!(f, "{}", ErrorChain(s)),
             SignalJniError::AesGcmSiv(s) => write!(f, "{}", s),
             SignalJniError::Jni(s) => write!(f, "JNI error {}", s),
             SignalJniError::NullHandle => write!(f, "null handle"),
@@ -39,10 +40,9 @@ impl fmt::Display for SignalJniError {
             SignalJniError::IntegerOverflow(m) => {
                 write!(f, "integer overflow during conversion of {}", m)
             }
-            SignalJniError::UnexpectedPanic(e) => match e.downcast_ref::<&'static str>() {
-                Some(s) => write!(f, "unexpected panic: {}", s),
-                None => write!(f, "unknown unexpected panic"),
-            },
+            SignalJniError::UnexpectedPanic(e) => {
+                write!(f, "unexpected panic: {}", describe_panic(&**e))
+            }
         }
     }
 }
@@ -0,0 +1,217 @@
+//
+// Copyright 2021 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+use futures::executor::ThreadPool;
+use futures::FutureExt;
+use jni::objects::{GlobalRef, JObject, JValue};
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicPtr, Ordering};
+use std::sync::{Mutex, Once};
+
+use super::*;
+
+/// A Java `java.util.concurrent.CompletableFuture`, as returned by `jni_async` bridge functions.
+///
+/// `CompletableFuture` is only available on Android API level 24 and newer.
+pub type JavaCompletableFuture = jobject;
+
+/// Converts a JNI result value into a Java object that can complete a `CompletableFuture`.
+///
+/// Primitive results are boxed (`jint` becomes `java.lang.Integer`, and so on); object results
+/// are passed through unchanged, and `()` becomes `null`.
+pub trait IntoJavaObject {
+    fn into_java_object<'a>(self, env: &JNIEnv<'a>) -> SignalJniResult<JObject<'a>>;
+}
+
+impl IntoJavaObject for jobject {
+    fn into_java_object<'a>(self, _env: &JNIEnv<'a>) -> SignalJniResult<JObject<'a>> {
+        Ok(JObject::from(self))
+    }
+}
+
+impl IntoJavaObject for jlong {
+    fn into_java_object<'a>(self, env: &JNIEnv<'a>) -> SignalJniResult<JObject<'a>> {
+        Ok(env.new_object("java/lang/Long", "(J)V", &[JValue::from(self)])?)
+    }
+}
+
+impl IntoJavaObject for jint {
+    fn into_java_object<'a>(self, env: &JNIEnv<'a>) -> SignalJniResult<JObject<'a>> {
+        Ok(env.new_object("java/lang/Integer", "(I)V", &[JValue::from(self)])?)
+    }
+}
+
+impl IntoJavaObject for jboolean {
+    fn into_java_object<'a>(self, env: &JNIEnv<'a>) -> SignalJniResult<JObject<'a>> {
+        Ok(env.new_object("java/lang/Boolean", "(Z)V", &[JValue::Bool(self)])?)
+    }
+}
+
+impl IntoJavaObject for () {
+    fn into_java_object<'a>(self, _env: &JNIEnv<'a>) -> SignalJniResult<JObject<'a>> {
+        Ok(JObject::null())
+    }
+}
+
+/// Runs `future` to completion on a shared thread pool, returning a `CompletableFuture` for its
+/// result.
+///
+/// This returns as soon as the future is queued, so the calling Java thread is never blocked
+/// waiting for it, even if it doesn't complete right away. This is the JNI counterpart of
+/// `signal_neon_futures::promise`, which the Node bridge uses to return a Promise.
+///
+/// The pool's threads are attached to the JVM as daemons the first time they complete a future.
+/// Errors are translated to Java exceptions the same way as in [`run_ffi_safe`] and passed to
+/// `completeExceptionally`, as are panics. If the `CompletableFuture` has already been completed
+/// or cancelled from the Java side, the result is silently dropped.
+///
+/// If there's no pool to run the future on, the `CompletableFuture` is completed exceptionally
+/// right away. If a pool thread can't be attached to the JVM, it can't call into Java at all, so
+/// the `CompletableFuture` is instead completed exceptionally by the next call to this function.
+pub fn run_future_on_executor<F>(env: &JNIEnv, future: F) -> SignalJniResult<JavaCompletableFuture>
+where
+    F: Future + Send + 'static,
+    F::Output: ResultTypeInfo,
+    <F::Output as ResultTypeInfo>::ResultType: IntoJavaObject,
+{
+    let completable_future =
+        env.new_object("java/util/concurrent/CompletableFuture", "()V", &[])?;
+
+    let executor = match executor() {
+        Some(executor) => executor,
+        None => {
+            complete_future::<F::Output>(
+                env,
+                completable_future,
+                Ok(Err(SignalJniError::Signal(
+                    SignalProtocolError::InternalError("no thread pool to run futures on"),
+                ))),
+            );
+            return Ok(completable_future.into_inner());
+        }
+    };
+    executor.complete_undelivered(env);
+
+    let completable_future_ref = env.new_global_ref(completable_future)?;
+    let jvm = env.get_java_vm()?;
+    executor.pool.spawn_ok(async move {
+        let result = AssertUnwindSafe(future).catch_unwind().await;
+        match jvm.attach_current_thread_as_daemon() {
+            Ok(env) => complete_future(&env, completable_future_ref.as_obj(), result.map(Ok)),
+            Err(e) => {
+                log::error!("failed to attach future thread to the JVM: {}", e);
+                executor
+                    .undelivered
+                    .lock()
+                    .expect("not poisoned")
+                    .push((completable_future_ref, e));
+            }
+        }
+    });
+
+    Ok(completable_future.into_inner())
+}
+
+/// The thread pool that runs futures for `jni_async` functions.
+struct Executor {
+    pool: ThreadPool,
+    /// `CompletableFuture`s whose results couldn't be delivered because a pool thread couldn't be
+    /// attached to the JVM.
+    undelivered: Mutex<Vec<(GlobalRef, jni::errors::Error)>>,
+}
+
+impl Executor {
+    /// Completes any undelivered `CompletableFuture`s exceptionally from the current thread.
+    fn complete_undelivered(&self, env: &JNIEnv) {
+        let undelivered = std::mem::take(&mut *self.undelivered.lock().expect("not poisoned"));
+        for (completable_future, error) in undelivered {
+            complete_future::<()>(
+                env,
+                completable_future.as_obj(),
+                Ok(Err(SignalJniError::Jni(error))),
+            );
+        }
+    }
+}
+
+/// Returns the executor for `jni_async` functions.
+///
+/// It's shared by every async entry point, so that a burst of calls doesn't start a thread for each
+/// one, and created on first use with one thread per CPU. Returns `None` if it couldn't be
+/// created.
+fn executor() -> Option<&'static Executor> {
+    static CREATE: Once = Once::new();
+    static EXECUTOR: AtomicPtr<Executor> = AtomicPtr::new(std::ptr::null_mut());
+    CREATE.call_once(|| {
+        match ThreadPool::builder()
+            .name_prefix("libsignal-jni-future-")
+            .create()
+        {
+            Ok(pool) => EXECUTOR.store(
+                Box::into_raw(Box::new(Executor {
+                    pool,
+                    undelivered: Mutex::new(Vec::new()),
+                })),
+                Ordering::Release,
+            ),
+            Err(e) => log::error!("failed to start a thread pool for futures: {}", e),
+        }
+    });
+    // The executor is leaked once created, so the pointer is valid for the rest of the process.
+    unsafe { EXECUTOR.load(Ordering::Acquire).as_ref() }
+}
+
+/// Completes `completable_future` with `result`, or with the error it holds or was converted to.
+fn complete_future<R>(
+    env: &JNIEnv,
+    completable_future: JObject,
+    result: std::thread::Result<SignalJniResult<R>>,
+) where
+    R: ResultTypeInfo,
+    R::ResultType: IntoJavaObject,
+{
+    let value = match result {
+        Ok(value) => value
+            .and_then(|value| value.convert_into(env))
+            .and_then(|value| value.into_java_object(env)),
+        Err(panic) => Err(SignalJniError::UnexpectedPanic(panic)),
+    };
+
+    let completion = match value {
+        Ok(value) => call_method_checked(
+            env,
+            completable_future,
+            "complete",
+            "(Ljava/lang/Object;)Z",
+            &[JValue::Object(value)],
+        ),
+        Err(error) => {
+            // Reuse the usual error mapping by throwing the exception and then catching it again.
+            throw_error(env, error);
+            match env.exception_occurred() {
+                Ok(throwable) if !throwable.is_null() => {
+                    let _ = env.exception_clear();
+                    call_method_checked(
+                        env,
+                        completable_future,
+                        "completeExceptionally",
+                        "(Ljava/lang/Throwable;)Z",
+                        &[JValue::Object(throwable.into())],
+                    )
+                }
+                Ok(_) => {
+                    log::error!("failed to produce an exception to complete a Java future");
+                    return;
+                }
+                Err(e) => Err(e.into()),
+            }
+        }
+    };
+
+    if let Err(e) = completion {
+        log::error!("failed to complete Java future: {}", e);
+    }
+}
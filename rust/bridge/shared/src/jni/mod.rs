@@ -13,8 +13,10 @@ use libsignal_protocol::*;
 use std::convert::TryFrom;
 use std::error::Error;
 
-pub(crate) use jni::objects::{JClass, JString};
-pub(crate) use jni::sys::{jboolean, jbyteArray, jint, jlong, jstring};
+pub(crate) use jni::objects::{JByteBuffer, JClass, JString};
+pub(crate) use jni::sys::{
+    jboolean, jbyteArray, jint, jlong, jlongArray, jobjectArray, jsize, jstring,
+};
 pub(crate) use jni::JNIEnv;
 
 #[macro_use]
@@ -24,10 +26,13 @@ pub use convert::*;
 mod error;
 pub use error::*;
 
+mod future;
+pub use future::*;
+
 mod storage;
 pub use storage::*;
 
-pub use crate::support::expect_ready;
+pub use crate::support::{describe_panic, expect_ready};
 
 /// The type of boxed Rust values, as surfaced in JavaScript.
 pub type ObjectHandle = jlong;
@@ -72,6 +77,38 @@ fn throw_error(env: &JNIEnv, error: SignalJniError) {
             return;
         }
 
+        SignalJniError::Signal(SignalProtocolError::IdentityKeyChanged(
+            ref addr,
+            ref old_key,
+            ref new_key,
+        )) => {
+            let throwable = (|| -> SignalJniResult<JObject> {
+                let key_class = "org/whispersystems/libsignal/IdentityKey";
+                let old_key = jobject_from_serialized(env, key_class, &old_key.serialize())?;
+                let new_key = jobject_from_serialized(env, key_class, &new_key.serialize())?;
+                Ok(env.new_object(
+                    "org/whispersystems/libsignal/IdentityKeyChangedException",
+                    "(Ljava/lang/String;Lorg/whispersystems/libsignal/IdentityKey;Lorg/whispersystems/libsignal/IdentityKey;)V",
+                    &[
+                        JValue::from(JObject::from(env.new_string(addr.name())?)),
+                        JValue::from(old_key),
+                        JValue::from(new_key),
+                    ],
+                )?)
+            })();
+
+            match throwable {
+                Err(e) => log::error!("failed to create exception for {}: {}", error, e),
+                Ok(throwable) => {
+                    let result = env.throw(JThrowable::from(throwable));
+                    if let Err(e) = result {
+                        log::error!("failed to throw exception for {}: {}", error, e);
+                    }
+                }
+            }
+            return;
+        }
+
         SignalJniError::Signal(SignalProtocolError::FingerprintVersionMismatch(theirs, ours)) => {
             let throwable = env.new_object(
                 "org/whispersystems/libsignal/fingerprint/FingerprintVersionMismatchException",
@@ -109,9 +146,11 @@ fn throw_error(env: &JNIEnv, error: SignalJniError) {
             "java/lang/IllegalArgumentException"
         }
 
-        SignalJniError::UnexpectedPanic(_)
-        | SignalJniError::BadJniParameter(_)
-        | SignalJniError::UnexpectedJniResultType(_, _) => "java/lang/AssertionError",
+        SignalJniError::UnexpectedPanic(_) => "org/whispersystems/libsignal/UnexpectedPanicError",
+
+        SignalJniError::BadJniParameter(_) | SignalJniError::UnexpectedJniResultType(_, _) => {
+            "java/lang/AssertionError"
+        }
 
         SignalJniError::IntegerOverflow(_)
         | SignalJniError::Jni(_)
@@ -138,6 +177,10 @@ fn throw_error(env: &JNIEnv, error: SignalJniError) {
 
         SignalJniError::Signal(SignalProtocolError::NoKeyTypeIdentifier)
         | SignalJniError::Signal(SignalProtocolError::SignatureValidationFailed)
+        | SignalJniError::Signal(SignalProtocolError::InvalidSignedPreKeySignature)
+        | SignalJniError::Signal(SignalProtocolError::InvalidRegistrationId(_))
+        | SignalJniError::Signal(SignalProtocolError::InvalidDeviceId(_))
+        | SignalJniError::Signal(SignalProtocolError::InconsistentOneTimePreKey)
         | SignalJniError::Signal(SignalProtocolError::BadKeyType(_))
         | SignalJniError::Signal(SignalProtocolError::BadKeyLength(_, _))
         | SignalJniError::AesGcmSiv(AesGcmSivError::InvalidKeySize) => {
@@ -148,11 +191,17 @@ fn throw_error(env: &JNIEnv, error: SignalJniError) {
             "org/whispersystems/libsignal/NoSessionException"
         }
 
+        SignalJniError::Signal(SignalProtocolError::UnknownSessionVersion(_)) => {
+            "org/whispersystems/libsignal/UnknownSessionVersionException"
+        }
+
         SignalJniError::Signal(SignalProtocolError::InvalidMessage(_))
+        | SignalJniError::Signal(SignalProtocolError::TooManySkippedKeys(_, _))
         | SignalJniError::Signal(SignalProtocolError::CiphertextMessageTooShort(_))
         | SignalJniError::Signal(SignalProtocolError::InvalidCiphertext)
         | SignalJniError::Signal(SignalProtocolError::InvalidProtobufEncoding)
         | SignalJniError::Signal(SignalProtocolError::ProtobufDecodingError(_))
+        | SignalJniError::Signal(SignalProtocolError::ProtobufTooLarge(_, _))
         | SignalJniError::Signal(SignalProtocolError::InvalidSealedSenderMessage(_))
         | SignalJniError::AesGcmSiv(AesGcmSivError::InvalidTag) => {
             "org/whispersystems/libsignal/InvalidMessageException"
@@ -172,7 +221,12 @@ fn throw_error(env: &JNIEnv, error: SignalJniError) {
             "org/signal/libsignal/metadata/SelfSendException"
         }
 
+        SignalJniError::Signal(SignalProtocolError::CertificateExpired { .. }) => {
+            "org/signal/libsignal/metadata/certificate/InvalidCertificateException"
+        }
+
         SignalJniError::Signal(SignalProtocolError::UntrustedIdentity(_))
+        | SignalJniError::Signal(SignalProtocolError::IdentityKeyChanged(..))
         | SignalJniError::Signal(SignalProtocolError::FingerprintVersionMismatch(_, _)) => {
             unreachable!("already handled in prior match")
         }
@@ -248,6 +302,26 @@ pub fn box_object<T>(t: Result<T, SignalProtocolError>) -> Result<ObjectHandle,
     }
 }
 
+/// Boxes each value in `values`, returning their handles as a Java `long[]`.
+///
+/// If the array can't be created, the values are dropped rather than leaked.
+pub fn box_object_array<T>(env: &JNIEnv, values: Vec<T>) -> Result<jlongArray, SignalJniError> {
+    let len = jsize::try_from(values.len())
+        .map_err(|_| SignalJniError::IntegerOverflow(format!("{} to jsize", values.len())))?;
+    let array = env.new_long_array(len)?;
+    let handles = values
+        .into_iter()
+        .map(|value| Box::into_raw(Box::new(value)) as ObjectHandle)
+        .collect::<Vec<_>>();
+    if let Err(e) = env.set_long_array_region(array, 0, &handles) {
+        for handle in handles {
+            drop(unsafe { Box::from_raw(handle as *mut T) });
+        }
+        return Err(e.into());
+    }
+    Ok(array)
+}
+
 pub unsafe fn native_handle_cast<T>(
     handle: ObjectHandle,
 ) -> Result<&'static mut T, SignalJniError> {
@@ -263,6 +337,35 @@ pub unsafe fn native_handle_cast<T>(
     Ok(&mut *(handle as *mut T))
 }
 
+/// Borrows the values referenced by an array of handles, as passed for `&[&T]` arguments.
+///
+/// A zero handle is reported as an [`InvalidArgument`](SignalProtocolError::InvalidArgument) error
+/// naming its index.
+pub unsafe fn native_handle_array_cast<T>(
+    env: &JNIEnv,
+    handles: jlongArray,
+) -> Result<Vec<&'static T>, SignalJniError> {
+    if handles.is_null() {
+        return Err(SignalJniError::NullHandle);
+    }
+
+    let len = env.get_array_length(handles)?;
+    let mut raw_handles = vec![0; len as usize];
+    env.get_long_array_region(handles, 0, &mut raw_handles)?;
+    raw_handles
+        .into_iter()
+        .enumerate()
+        .map(|(index, handle)| {
+            if handle == 0 {
+                return Err(SignalJniError::Signal(
+                    SignalProtocolError::InvalidArgument(format!("null handle at index {}", index)),
+                ));
+            }
+            Ok(&*(handle as *const T))
+        })
+        .collect()
+}
+
 pub fn jint_to_u32(v: jint) -> Result<u32, SignalJniError> {
     if v < 0 {
         return Err(SignalJniError::IntegerOverflow(format!("{} to u32", v)));
@@ -449,6 +552,27 @@ pub fn get_object_with_serialization(
     }
 }
 
+/// Used in the implementation of `bridge_fn` to keep track of all generated entry points.
+///
+/// `$arity` is the number of parameters the entry point takes in Java, as listed by
+/// `describe_all` when the "describe" feature is enabled.
+///
+/// Not intended to be invoked directly.
+macro_rules! jni_register {
+    ( $name:ident, $arity:expr ) => {
+        paste! {
+            #[cfg(any(test, feature = "describe"))]
+            #[allow(non_upper_case_globals)]
+            #[linkme::distributed_slice(crate::describe::BRIDGED_FNS)]
+            static [<describe_ $name>]: crate::describe::BridgedFn = crate::describe::BridgedFn {
+                name: stringify!($name),
+                arity: $arity,
+                platform: crate::describe::Platform::Jni,
+            };
+        }
+    };
+}
+
 /// Used by [`bridge_handle`](crate::support::bridge_handle).
 ///
 /// Not intended to be invoked directly.
@@ -465,6 +589,39 @@ macro_rules! jni_bridge_destroy {
                     let _boxed_value = Box::from_raw(handle as *mut $typ);
                 }
             }
+
+            jni_register!([<Java_org_signal_client_internal_Native_ $jni_name _1Destroy>], 1);
+        }
+    };
+}
+
+/// Implementation of the `serialize` flag of [`bridge_handle`](crate::support::bridge_handle) for
+/// JNI.
+macro_rules! jni_bridge_serialize {
+    ( $typ:ty as false $(, serialize = $_:tt)? ) => {};
+    ( $typ:ty as $jni_name:ident $(, serialize = false)? ) => {};
+    ( $typ:ty as $jni_name:ident, serialize = true ) => {
+        paste! {
+            #[no_mangle]
+            pub unsafe extern "C" fn [<Java_org_signal_client_internal_Native_ $jni_name _1Serialize>](
+                env: jni::JNIEnv,
+                _class: jni::JClass,
+                handle: jni::ObjectHandle,
+            ) -> jni::jbyteArray {
+                jni::run_ffi_safe(&env, || {
+                    let obj = <&$typ as jni::SimpleArgTypeInfo>::convert_from(&env, handle)?;
+                    let result = TransformHelper(<$typ>::serialize(obj));
+                    let bytes = Vec::from(result.ok_if_needed()?.into_vec_if_needed().0);
+                    jni::ResultTypeInfo::convert_into(bytes, &env)
+                })
+            }
+
+            jni_register!([<Java_org_signal_client_internal_Native_ $jni_name _1Serialize>], 1);
+        }
+    };
+    ( $typ:ty $(, serialize = $_:tt)? ) => {
+        paste! {
+            jni_bridge_serialize!($typ as $typ $(, serialize = $_)?);
         }
     };
 }
@@ -481,13 +638,78 @@ macro_rules! jni_bridge_deserialize {
                 data: jni::jbyteArray,
             ) -> jni::ObjectHandle {
                 jni::run_ffi_safe(&env, || {
-                    let data = env.convert_byte_array(data)?;
-                    jni::box_object($typ::$fn(data.as_ref()))
+                    let mut data = <&[u8] as jni::ArgTypeInfo>::borrow(&env, data)?;
+                    let data = <&[u8] as jni::ArgTypeInfo>::load_from_pinned(&env, &mut data)?;
+                    jni::box_object($typ::$fn(data))
                 })
             }
+
+            jni_register!([<Java_org_signal_client_internal_Native_ $jni_name _1Deserialize>], 1);
         }
     };
     ( $typ:ident::$fn:path ) => {
         jni_bridge_deserialize!($typ::$fn as $typ);
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::describe::{declared_functions, generated_functions, Platform};
+
+    const NATIVE_JAVA: &str =
+        include_str!("../../../../../java/java/src/main/java/org/signal/internal/Native.java");
+    /// Declares the `TESTING_` entry points, which are only built with the "testing" feature.
+    const NATIVE_TESTING_JAVA: &str = include_str!(
+        "../../../../../java/tests/src/test/java/org/signal/client/internal/NativeTesting.java"
+    );
+
+    #[test]
+    fn test_all_entry_points_declared_in_native_java() {
+        let generated = generated_functions(Platform::Jni);
+        assert!(!generated.is_empty());
+        let mut declared = declared_functions(NATIVE_JAVA);
+        declared.extend(declared_functions(NATIVE_TESTING_JAVA));
+
+        let missing: Vec<&str> = generated
+            .into_iter()
+            .filter(|name| {
+                let java_name = name
+                    .strip_prefix("Java_org_signal_client_internal_Native_")
+                    .or_else(|| name.strip_prefix("Java_org_signal_client_internal_NativeTesting_"))
+                    .expect("all entry points are in the Native or NativeTesting class")
+                    .replace("_1", "_");
+                !declared.contains(java_name.as_str())
+            })
+            .collect();
+        assert!(
+            missing.is_empty(),
+            "missing from Native.java or NativeTesting.java (regenerate them with \
+             rust/bridge/jni/bin/gen_java_decl.py): {:?}",
+            missing
+        );
+    }
+
+    #[test]
+    fn test_deprecated_entry_points_marked_in_native_java() {
+        // gen_java_decl.py turns cbindgen's `DEPRECATED(note)` prefix into these.
+        assert!(NATIVE_TESTING_JAVA.contains(
+            "  /** @deprecated only for testing */\n  @Deprecated\n  \
+             public static native int TESTING_DeprecatedEcho("
+        ));
+    }
+
+    #[test]
+    fn test_bridge_deserialize_names() {
+        let generated = generated_functions(Platform::Jni);
+        for name in &[
+            "Java_org_signal_client_internal_Native_SignalMessage_1Deserialize",
+            "Java_org_signal_client_internal_Native_SessionRecord_1Deserialize",
+        ] {
+            assert!(generated.contains(name), "missing {}", name);
+        }
+        // PublicKey disables its generated JNI entry point in favor of a hand-written one.
+        assert!(
+            !generated.contains(&"Java_org_signal_client_internal_Native_PublicKey_1Deserialize")
+        );
+    }
+}
@@ -229,8 +229,14 @@ impl<'a> IdentityKeyStore for JniIdentityKeyStore<'a> {
         identity: &IdentityKey,
         direction: Direction,
         _ctx: Context,
-    ) -> Result<bool, SignalProtocolError> {
-        Ok(self.do_is_trusted_identity(address, identity, direction)?)
+    ) -> Result<IdentityTrust, SignalProtocolError> {
+        let trusted = self.do_is_trusted_identity(address, identity, direction)?;
+        let saved = if trusted {
+            None
+        } else {
+            self.do_get_identity(address).ok().flatten()
+        };
+        Ok(IdentityTrust::from_saved_key(trusted, identity, saved))
     }
 
     async fn get_identity(
@@ -5,8 +5,8 @@
 
 #![allow(clippy::missing_safety_doc)]
 
-#[cfg(not(any(feature = "ffi", feature = "jni", feature = "node")))]
-compile_error!("Feature \"ffi\", \"jni\", or \"node\" must be enabled for this crate.");
+#[cfg(not(any(feature = "ffi", feature = "jni", feature = "node", feature = "wasm")))]
+compile_error!("Feature \"ffi\", \"jni\", \"node\", or \"wasm\" must be enabled for this crate.");
 
 #[cfg(feature = "ffi")]
 #[macro_use]
@@ -20,8 +20,21 @@ pub mod jni;
 #[macro_use]
 pub mod node;
 
+#[cfg(feature = "wasm")]
+#[macro_use]
+pub mod wasm;
+
 #[macro_use]
 mod support;
 
+#[cfg(any(test, feature = "describe"))]
+pub mod describe;
+#[cfg(any(test, feature = "describe"))]
+pub use describe::describe_all;
+
 pub mod aes_gcm_siv;
+pub mod poksho;
 pub mod protocol;
+
+#[cfg(feature = "testing")]
+pub mod testing;
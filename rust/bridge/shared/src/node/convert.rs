@@ -6,10 +6,11 @@
 use neon::prelude::*;
 use paste::paste;
 use std::borrow::Cow;
+use std::cell::{Ref, RefCell, RefMut};
 use std::collections::hash_map::DefaultHasher;
 use std::convert::{TryFrom, TryInto};
 use std::hash::Hasher;
-use std::ops::{Deref, RangeInclusive};
+use std::ops::{Deref, DerefMut, RangeInclusive};
 use std::slice;
 
 use super::*;
@@ -213,28 +214,142 @@ fn can_convert_js_number_to_int(value: f64, valid_range: RangeInclusive<f64>) ->
     value.is_finite() && value.fract() == 0.0 && valid_range.contains(&value)
 }
 
-// 2**53 - 1, the maximum "safe" integer representable in an f64.
-// https://developer.mozilla.org/en-US/docs/Web/JavaScript/Reference/Global_Objects/Number/MAX_SAFE_INTEGER
-const MAX_SAFE_JS_INTEGER: f64 = 9007199254740991.0;
+/// Looks up a function on the JavaScript global object, such as `BigInt`.
+fn global_function<'a>(cx: &mut impl Context<'a>, name: &str) -> JsResult<'a, JsFunction> {
+    let global = cx.global();
+    global.get(cx, name)?.downcast_or_throw(cx)
+}
+
+/// Returns `Object.prototype.toString.call(value)`, such as `[object BigInt]`.
+///
+/// This is how to identify values Neon doesn't have a type for.
+fn object_tag<'a>(cx: &mut impl Context<'a>, value: Handle<'a, JsValue>) -> NeonResult<String> {
+    let object = global_function(cx, "Object")?;
+    let prototype: Handle<JsObject> = object.get(cx, "prototype")?.downcast_or_throw(cx)?;
+    let to_string: Handle<JsFunction> = prototype.get(cx, "toString")?.downcast_or_throw(cx)?;
+    let tag: Handle<JsString> = to_string
+        .call(cx, value, Vec::<Handle<JsValue>>::new())?
+        .downcast_or_throw(cx)?;
+    Ok(tag.value(cx))
+}
+
+/// Checks whether `value` is a `bigint`.
+///
+/// Neon doesn't have a type for BigInts, so this uses [`object_tag`], which produces
+/// `[object BigInt]` only for `bigint` values.
+fn is_bigint<'a>(cx: &mut impl Context<'a>, value: Handle<'a, JsValue>) -> NeonResult<bool> {
+    Ok(object_tag(cx, value)? == "[object BigInt]")
+}
 
-/// Converts non-negative numbers up to [`Number.MAX_SAFE_INTEGER`][].
+/// Converts `bigint`s in the range of a `u64`.
 ///
-/// [`Number.MAX_SAFE_INTEGER`]: https://developer.mozilla.org/en-US/docs/Web/JavaScript/Reference/Global_Objects/Number/MAX_SAFE_INTEGER
+/// Numbers are rejected with a `TypeError`, even integral ones, so that every `u64` is a `bigint`
+/// in both directions and no value can silently lose precision.
 impl SimpleArgTypeInfo for u64 {
-    type ArgType = JsNumber;
+    type ArgType = JsValue;
+    fn convert_from(cx: &mut FunctionContext, foreign: Handle<Self::ArgType>) -> NeonResult<Self> {
+        if !is_bigint(cx, foreign)? {
+            return cx.throw_type_error("expected a bigint");
+        }
+        let to_string = global_function(cx, "String")?;
+        let undefined = cx.undefined();
+        let digits: Handle<JsString> = to_string
+            .call(cx, undefined, vec![foreign])?
+            .downcast_or_throw(cx)?;
+        let digits = digits.value(cx);
+        match digits.parse() {
+            Ok(value) => Ok(value),
+            Err(_) => cx.throw_range_error(format!("cannot convert {} to u64", digits)),
+        }
+    }
+}
+
+/// Copies a UUID out of a `Buffer`, which must be exactly 16 bytes long.
+///
+/// Any other length is rejected with a `TypeError`.
+impl SimpleArgTypeInfo for crate::support::Uuid {
+    type ArgType = JsBuffer;
     fn convert_from(cx: &mut FunctionContext, foreign: Handle<Self::ArgType>) -> NeonResult<Self> {
-        let value = foreign.value(cx);
-        if !can_convert_js_number_to_int(value, 0.0..=MAX_SAFE_JS_INTEGER) {
-            return cx.throw_range_error(format!("cannot convert {} to u64", value));
+        let result = cx.borrow(&foreign, |buf| Self::from_slice(buf.as_slice()));
+        match result {
+            Ok(uuid) => Ok(uuid),
+            Err(e) => cx.throw_type_error(e.to_string()),
         }
-        Ok(value as u64)
     }
 }
 
+/// Checks whether `value` contains a UTF-16 surrogate that isn't part of a pair.
+///
+/// Neon only reads strings as UTF-8, with unpaired surrogates already replaced by U+FFFD
+/// REPLACEMENT CHARACTER, so this asks JavaScript instead. In a regular expression with the `u`
+/// flag, a pair of surrogates is a single character, so a class of surrogates only matches ones
+/// that are unpaired.
+fn has_unpaired_surrogate<'a>(
+    cx: &mut impl Context<'a>,
+    value: Handle<JsString>,
+) -> NeonResult<bool> {
+    let reg_exp = global_function(cx, "RegExp")?;
+    let pattern = cx.string("[\\uD800-\\uDFFF]");
+    let flags = cx.string("u");
+    let reg_exp = reg_exp.construct(cx, vec![pattern, flags])?;
+    let test: Handle<JsFunction> = reg_exp.get(cx, "test")?.downcast_or_throw(cx)?;
+    let result: Handle<JsBoolean> = test.call(cx, reg_exp, vec![value])?.downcast_or_throw(cx)?;
+    Ok(result.value(cx))
+}
+
+/// Copies a JavaScript string into a Rust `String`.
+///
+/// JavaScript strings can contain unpaired surrogates, which have no UTF-8 representation, and
+/// strings containing NUL characters can't be passed on to C. Both are rejected with a
+/// `TypeError`.
+fn convert_js_string(cx: &mut FunctionContext, foreign: Handle<JsString>) -> NeonResult<String> {
+    let value = foreign.value(cx);
+    if value.contains('\0') {
+        return cx.throw_type_error("string contains a NUL character");
+    }
+    // Only a string that converted with a replacement character can have had a surrogate replaced.
+    if value.contains('\u{FFFD}') && has_unpaired_surrogate(cx, foreign)? {
+        return cx.throw_type_error("string contains an unpaired surrogate");
+    }
+    Ok(value)
+}
+
+/// See [`convert_js_string`].
 impl SimpleArgTypeInfo for String {
     type ArgType = JsString;
     fn convert_from(cx: &mut FunctionContext, foreign: Handle<Self::ArgType>) -> NeonResult<Self> {
-        Ok(foreign.value(cx))
+        convert_js_string(cx, foreign)
+    }
+}
+
+/// Copies a JavaScript string so that the Rust function can borrow it as a `&str`.
+///
+/// See [`convert_js_string`].
+impl<'storage, 'context: 'storage> ArgTypeInfo<'storage, 'context> for &'storage str {
+    type ArgType = JsString;
+    type StoredType = String;
+    fn borrow(
+        cx: &mut FunctionContext<'context>,
+        foreign: Handle<'context, Self::ArgType>,
+    ) -> NeonResult<Self::StoredType> {
+        convert_js_string(cx, foreign)
+    }
+    fn load_from(stored: &'storage mut Self::StoredType) -> Self {
+        stored
+    }
+}
+
+impl<'storage> AsyncArgTypeInfo<'storage> for &'storage str {
+    type ArgType = JsString;
+    type StoredType = DefaultFinalize<String>;
+    fn save_async_arg(
+        cx: &mut FunctionContext,
+        foreign: Handle<Self::ArgType>,
+    ) -> NeonResult<Self::StoredType> {
+        Ok(DefaultFinalize(convert_js_string(cx, foreign)?))
+    }
+    fn load_async_arg(stored: &'storage mut Self::StoredType) -> Self {
+        &stored.0
     }
 }
 
@@ -315,6 +430,145 @@ fn calculate_checksum_for_immutable_buffer(buffer: &[u8]) -> u64 {
     hasher.finish()
 }
 
+/// The JavaScript types that own their bytes, which can be borrowed without copying.
+///
+/// `&[u8]` arguments also accept any `Uint8Array`, by borrowing from the `ArrayBuffer` it views;
+/// see [`uint8_like_bytes`].
+pub trait IsUint8Like: Value {
+    /// Returns the start and length of the bytes in `handle`, with a null start if there are none.
+    fn byte_range<'a>(cx: &mut impl Context<'a>, handle: Handle<Self>) -> (*const u8, usize);
+}
+
+macro_rules! impl_is_uint8_like {
+    ($typ:ty) => {
+        impl IsUint8Like for $typ {
+            fn byte_range<'a>(
+                cx: &mut impl Context<'a>,
+                handle: Handle<Self>,
+            ) -> (*const u8, usize) {
+                cx.borrow(&handle, |buf| {
+                    if buf.len() == 0 {
+                        (std::ptr::null(), 0)
+                    } else {
+                        let slice: &[u8] = buf.as_slice();
+                        (slice.as_ptr(), slice.len())
+                    }
+                })
+            }
+        }
+    };
+}
+
+impl_is_uint8_like!(JsBuffer);
+impl_is_uint8_like!(JsArrayBuffer);
+
+/// Borrows the bytes of `value` if it's a `T`, returning their start and length.
+fn owned_bytes<'a, T: IsUint8Like>(
+    cx: &mut impl Context<'a>,
+    value: Handle<'a, JsObject>,
+) -> Option<(*const u8, usize)> {
+    let handle = value.downcast::<T, _>(cx).ok()?;
+    Some(T::byte_range(cx, handle))
+}
+
+/// Calls the getter for `key` defined on `%TypedArray%.prototype`, the prototype shared by all
+/// typed arrays, with `value` as `this`.
+///
+/// These getters read the typed array's internal slots, so unlike ordinary property lookups they
+/// can't be overridden by `value` itself.
+fn call_typed_array_getter<'a>(
+    cx: &mut impl Context<'a>,
+    value: Handle<'a, JsObject>,
+    key: Handle<'a, JsValue>,
+) -> JsResult<'a, JsValue> {
+    let object = global_function(cx, "Object")?;
+    let get_prototype_of: Handle<JsFunction> =
+        object.get(cx, "getPrototypeOf")?.downcast_or_throw(cx)?;
+    let get_own_property_descriptor: Handle<JsFunction> = object
+        .get(cx, "getOwnPropertyDescriptor")?
+        .downcast_or_throw(cx)?;
+    let uint8_array_prototype = global_function(cx, "Uint8Array")?.get(cx, "prototype")?;
+    let typed_array_prototype = get_prototype_of.call(cx, object, vec![uint8_array_prototype])?;
+    let descriptor: Handle<JsObject> = get_own_property_descriptor
+        .call(cx, object, vec![typed_array_prototype, key])?
+        .downcast_or_throw(cx)?;
+    let getter: Handle<JsFunction> = descriptor.get(cx, "get")?.downcast_or_throw(cx)?;
+    getter.call(cx, value, Vec::<Handle<JsValue>>::new())
+}
+
+/// Checks whether `value` is a `Uint8Array`, including a `Buffer`.
+///
+/// Neon doesn't have a type for typed arrays, and [`object_tag`] can be fooled by an object with
+/// its own `Symbol.toStringTag`. Instead, this uses the getter for
+/// `%TypedArray%.prototype[Symbol.toStringTag]`, which produces `"Uint8Array"` only for real
+/// `Uint8Array`s; it's the same test that `napi_is_typedarray` makes, plus the array type.
+fn is_uint8_array<'a>(cx: &mut impl Context<'a>, value: Handle<'a, JsObject>) -> NeonResult<bool> {
+    let to_string_tag = global_function(cx, "Symbol")?.get(cx, "toStringTag")?;
+    let tag = call_typed_array_getter(cx, value, to_string_tag)?;
+    Ok(match tag.downcast::<JsString, _>(cx) {
+        Ok(tag) => tag.value(cx) == "Uint8Array",
+        Err(_) => false,
+    })
+}
+
+/// Reads the `byteOffset` or `byteLength` of a typed array.
+fn byte_index_property<'a>(
+    cx: &mut impl Context<'a>,
+    object: Handle<'a, JsObject>,
+    name: &str,
+) -> NeonResult<usize> {
+    let key = cx.string(name).upcast();
+    let value: Handle<JsNumber> =
+        call_typed_array_getter(cx, object, key)?.downcast_or_throw(cx)?;
+    let value = value.value(cx);
+    if !can_convert_js_number_to_int(value, 0.0..=usize::MAX as f64) {
+        return cx.throw_range_error(format!("invalid {} {}", name, value));
+    }
+    Ok(value as usize)
+}
+
+/// Finds the bytes of a `Buffer`, an `ArrayBuffer`, or any other `Uint8Array`, without copying.
+///
+/// Returns the object that owns the bytes along with their start and length (with a null start if
+/// there are none). Anything else is rejected with a `TypeError`.
+///
+/// Node-API's buffer support, which covers the common case of a `Buffer` without calling back
+/// into JavaScript, accepts other typed arrays too in some versions of Node. Otherwise, a
+/// `Uint8Array` is identified with [`is_uint8_array`] and borrowed from its `buffer`, which is the
+/// owner returned. The view's `byteOffset` and `byteLength` are still checked against that
+/// buffer, since a caller can replace the global functions used to look them up.
+fn uint8_like_bytes<'a>(
+    cx: &mut impl Context<'a>,
+    value: Handle<'a, JsObject>,
+) -> NeonResult<(Handle<'a, JsObject>, *const u8, usize)> {
+    if let Some((start, len)) =
+        owned_bytes::<JsBuffer>(cx, value).or_else(|| owned_bytes::<JsArrayBuffer>(cx, value))
+    {
+        return Ok((value, start, len));
+    }
+    if !is_uint8_array(cx, value)? {
+        return cx.throw_type_error("expected a Buffer, Uint8Array, or ArrayBuffer");
+    }
+
+    let buffer_key = cx.string("buffer").upcast();
+    let buffer: Handle<JsArrayBuffer> =
+        call_typed_array_getter(cx, value, buffer_key)?.downcast_or_throw(cx)?;
+    let offset = byte_index_property(cx, value, "byteOffset")?;
+    let len = byte_index_property(cx, value, "byteLength")?;
+    let (buffer_start, buffer_len) = JsArrayBuffer::byte_range(cx, buffer);
+    match offset.checked_add(len) {
+        Some(end) if end <= buffer_len => {}
+        _ => return cx.throw_range_error("Uint8Array extends past the end of its buffer"),
+    }
+    let start = if len == 0 {
+        std::ptr::null()
+    } else {
+        // Safe because the range was checked against the buffer just above.
+        unsafe { buffer_start.add(offset) }
+    };
+    Ok((buffer.upcast(), start, len))
+}
+
 /// A wrapper around `&[u8]` that also stores a checksum, to be validated on Drop.
 pub struct AssumedImmutableBuffer<'a> {
     buffer: &'a [u8],
@@ -322,24 +576,24 @@ pub struct AssumedImmutableBuffer<'a> {
 }
 
 impl<'a> AssumedImmutableBuffer<'a> {
-    /// Loads and checksums a slice from `handle`.
-    ///
-    /// [A JsBuffer owns its storage][napi], so it's safe to assume the buffer won't get
-    /// deallocated. What's unsafe is assuming that no one else will modify the buffer while we
-    /// have a reference to it, which is why we checksum it. (We can't stop the Rust compiler from
-    /// potentially optimizing out that checksum, though.)
+    /// Loads and checksums a slice from `handle` (see [`uint8_like_bytes`]).
     ///
-    /// [napi]: https://nodejs.org/api/n-api.html#n_api_napi_get_buffer_info
-    fn new<'b>(cx: &mut impl Context<'b>, handle: Handle<'a, JsBuffer>) -> Self {
-        let buffer = cx.borrow(&handle, |buf| {
-            if buf.len() == 0 {
-                &[]
-            } else {
-                unsafe { extend_lifetime::<'_, 'a, [u8]>(buf.as_slice()) }
-            }
-        });
+    /// The bytes can't be deallocated while the slice is in use: the owner is kept alive by the
+    /// current handle scope, and no JavaScript code runs during a synchronous call, so nothing can
+    /// transfer (and so detach) the `ArrayBuffer` that holds them. What's unsafe is assuming that
+    /// no one else will modify the buffer while we have a reference to it, since another thread
+    /// could write to a `SharedArrayBuffer`; that's why we checksum it. (We can't stop the Rust
+    /// compiler from potentially optimizing out that checksum, though.)
+    fn new(cx: &mut impl Context<'a>, handle: Handle<'a, JsObject>) -> NeonResult<Self> {
+        let (_owner, start, len) = uint8_like_bytes(cx, handle)?;
+        let buffer = if start.is_null() {
+            &[]
+        } else {
+            // The owner is kept alive by the current handle scope, which lasts as long as 'a.
+            unsafe { slice::from_raw_parts(start, len) }
+        };
         let hash = calculate_checksum_for_immutable_buffer(buffer);
-        Self { buffer, hash }
+        Ok(Self { buffer, hash })
     }
 }
 
@@ -352,107 +606,49 @@ impl Drop for AssumedImmutableBuffer<'_> {
     }
 }
 
-/// Loads from a JsBuffer, assuming it won't be mutated while in use.
-/// See [`AssumedImmutableBuffer`].
+/// Loads from a `Buffer`, `Uint8Array`, or `ArrayBuffer`, assuming it won't be mutated while in
+/// use. See [`AssumedImmutableBuffer`].
 impl<'storage, 'context: 'storage> ArgTypeInfo<'storage, 'context> for &'storage [u8] {
-    type ArgType = JsBuffer;
+    type ArgType = JsObject;
     type StoredType = AssumedImmutableBuffer<'context>;
     fn borrow(
-        cx: &mut FunctionContext,
+        cx: &mut FunctionContext<'context>,
         foreign: Handle<'context, Self::ArgType>,
     ) -> NeonResult<Self::StoredType> {
-        Ok(AssumedImmutableBuffer::new(cx, foreign))
+        AssumedImmutableBuffer::new(cx, foreign)
     }
     fn load_from(stored: &'storage mut Self::StoredType) -> Self {
         stored.buffer
     }
 }
 
-/// A wrapper around a persisted JavaScript buffer and a pointer/length pair.
+/// Copies the bytes of a `Buffer`, `Uint8Array`, or `ArrayBuffer` (see [`uint8_like_bytes`]).
 ///
-/// Like [`AssumedImmutableBuffer`], `PersistentAssumedImmutableBuffer` also stores a checksum,
-/// to be validated on Finalize.
-///
-/// A `PersistentAssumedImmutableBuffer` **cannot be dropped**; instead, it must be explicitly
-/// finalized in a JavaScript context, as it contains a [`neon::handle::Root`].
-pub struct PersistentAssumedImmutableBuffer {
-    owner: Root<JsBuffer>,
-    buffer_start: *const u8,
-    buffer_len: usize,
-    hash: u64,
-}
-
-impl PersistentAssumedImmutableBuffer {
-    /// Establishes a GC root for `buffer`, then loads and checksums a slice from it.
-    ///
-    /// [A JsBuffer owns its storage][napi], so it's safe to assume the buffer won't get
-    /// deallocated. What's unsafe is assuming that no one else will modify the buffer while we
-    /// have a reference to it, which is why we checksum it. (We can't stop the Rust compiler from
-    /// potentially optimizing out that checksum, though.)
-    ///
-    /// [napi]: https://nodejs.org/api/n-api.html#n_api_napi_get_buffer_info
-    fn new<'a>(cx: &mut impl Context<'a>, buffer: Handle<JsBuffer>) -> Self {
-        let owner = buffer.root(cx);
-        let (buffer_start, buffer_len, hash) = cx.borrow(&buffer, |buf| {
-            (
-                if buf.len() == 0 {
-                    std::ptr::null()
-                } else {
-                    buf.as_slice().as_ptr()
-                },
-                buf.len(),
-                calculate_checksum_for_immutable_buffer(buf.as_slice()),
-            )
-        });
-        Self {
-            owner,
-            buffer_start,
-            buffer_len,
-            hash,
-        }
-    }
-}
-
-impl Deref for PersistentAssumedImmutableBuffer {
-    type Target = [u8];
-    fn deref(&self) -> &[u8] {
-        if self.buffer_start.is_null() {
-            &[]
-        } else {
-            // See `new()` for the safety guarantee.
-            unsafe { slice::from_raw_parts(self.buffer_start, self.buffer_len) }
-        }
-    }
-}
-
-// PersistentAssumedImmutableBuffer is not automatically Send because it contains a pointer.
-// We're already assuming (and checking) that the contents of the buffer won't be modified
-// while in use, and we know it won't be deallocated (see above).
-unsafe impl Send for PersistentAssumedImmutableBuffer {}
-
-/// Logs an error (but does not panic) if the buffer's contents have changed.
-impl Finalize for PersistentAssumedImmutableBuffer {
-    fn finalize<'a, C: Context<'a>>(self, cx: &mut C) {
-        if self.hash != calculate_checksum_for_immutable_buffer(&*self) {
-            log::error!("buffer modified while in use");
-        }
-        self.owner.finalize(cx)
-    }
-}
-
-/// Persists the JsBuffer, assuming it won't be mutated while in use.
-/// See [`PersistentAssumedImmutableBuffer`].
+/// Unlike a synchronous call, an async call can't borrow the bytes, because JavaScript code runs
+/// before it finishes. That code could write to them, or free them by transferring the
+/// `ArrayBuffer` that holds them (say, with `postMessage`), which detaches it. A `Buffer` is no
+/// exception, since it's a view into an `ArrayBuffer` too.
 impl<'a> AsyncArgTypeInfo<'a> for &'a [u8] {
-    type ArgType = JsBuffer;
-    type StoredType = PersistentAssumedImmutableBuffer;
+    type ArgType = JsObject;
+    type StoredType = DefaultFinalize<Vec<u8>>;
     fn save_async_arg(
         cx: &mut FunctionContext,
         foreign: Handle<Self::ArgType>,
     ) -> NeonResult<Self::StoredType> {
-        Ok(PersistentAssumedImmutableBuffer::new(cx, foreign))
+        // uint8_like_bytes needs a handle in cx's scope; going through a Root produces one.
+        let buffer = foreign.root(cx).into_inner(cx);
+        let (_owner, start, len) = uint8_like_bytes(cx, buffer)?;
+        let bytes = if start.is_null() {
+            vec![]
+        } else {
+            // The owner is kept alive by the current handle scope, and no JavaScript code runs
+            // before the copy is made.
+            unsafe { slice::from_raw_parts(start, len) }.to_vec()
+        };
+        Ok(DefaultFinalize(bytes))
     }
     fn load_async_arg(stored: &'a mut Self::StoredType) -> Self {
-        &*stored
+        &stored.0
     }
 }
 
@@ -489,20 +685,14 @@ impl<'a> ResultTypeInfo<'a> for bool {
     }
 }
 
-/// Converts non-negative values up to [`Number.MAX_SAFE_INTEGER`][].
-///
-/// [`Number.MAX_SAFE_INTEGER`]: https://developer.mozilla.org/en-US/docs/Web/JavaScript/Reference/Global_Objects/Number/MAX_SAFE_INTEGER
+/// Produces a `bigint`, like the implementation of [`SimpleArgTypeInfo`] for `u64`.
 impl<'a> ResultTypeInfo<'a> for u64 {
-    type ResultType = JsNumber;
+    type ResultType = JsValue;
     fn convert_into(self, cx: &mut impl Context<'a>) -> NeonResult<Handle<'a, Self::ResultType>> {
-        let result = self as f64;
-        if result > MAX_SAFE_JS_INTEGER {
-            cx.throw_range_error(format!(
-                "precision loss during conversion of {} to f64",
-                self
-            ))?;
-        }
-        Ok(cx.number(self as f64))
+        let bigint = global_function(cx, "BigInt")?;
+        let undefined = cx.undefined();
+        let digits = cx.string(self.to_string());
+        bigint.call(cx, undefined, vec![digits.upcast()])
     }
 }
 
@@ -547,26 +737,76 @@ impl<'a> ResultTypeInfo<'a> for Vec<u8> {
     }
 }
 
-impl<'a, T: ResultTypeInfo<'a>> ResultTypeInfo<'a>
-    for Result<T, libsignal_protocol::SignalProtocolError>
-{
-    type ResultType = T::ResultType;
+impl<'a> ResultTypeInfo<'a> for Box<[u8]> {
+    type ResultType = JsBuffer;
     fn convert_into(self, cx: &mut impl Context<'a>) -> NeonResult<Handle<'a, Self::ResultType>> {
-        match self {
-            Ok(value) => value.convert_into(cx),
-            // FIXME: Use a dedicated Error type?
-            Err(err) => cx.throw_error(err.to_string()),
-        }
+        self.into_vec().convert_into(cx)
+    }
+}
+
+impl<'a> ResultTypeInfo<'a> for Vec<Box<[u8]>> {
+    type ResultType = JsArray;
+    fn convert_into(self, cx: &mut impl Context<'a>) -> NeonResult<Handle<'a, Self::ResultType>> {
+        convert_list_into(cx, self)
+    }
+}
+
+/// Converts each value in `values`, returning them as a JavaScript array.
+///
+/// Used for lists of buffers and, by [`node_bridge_handle`], lists of boxed values.
+pub fn convert_list_into<'a, T: ResultTypeInfo<'a>>(
+    cx: &mut impl Context<'a>,
+    values: Vec<T>,
+) -> NeonResult<Handle<'a, JsArray>> {
+    let array_len = match u32::try_from(values.len()) {
+        Ok(l) => l,
+        Err(_) => return cx.throw_error("Cannot return very large object to JS environment"),
+    };
+
+    let array = JsArray::new(cx, array_len);
+    for (i, value) in (0..array_len).zip(values.into_iter()) {
+        let value = value.convert_into(cx)?;
+        array.set(cx, i, value)?;
     }
+    Ok(array)
+}
+
+/// A tuple result to be returned to JavaScript as an object, with one field per element.
+///
+/// Generated by `bridge_fn` for functions that return tuples; see `node_result_fields`.
+pub struct NamedFields<T> {
+    pub names: &'static [&'static str],
+    pub values: T,
 }
 
-impl<'a, T: ResultTypeInfo<'a>> ResultTypeInfo<'a> for Result<T, aes_gcm_siv::Error> {
+macro_rules! named_fields_result {
+    ($($element:ident $index:tt),+) => {
+        impl<'a, $($element: ResultTypeInfo<'a>),+> ResultTypeInfo<'a> for NamedFields<($($element,)+)> {
+            type ResultType = JsObject;
+            fn convert_into(
+                self,
+                cx: &mut impl Context<'a>,
+            ) -> NeonResult<Handle<'a, Self::ResultType>> {
+                let object = cx.empty_object();
+                $(
+                    let value = self.values.$index.convert_into(cx)?;
+                    object.set(cx, self.names[$index], value)?;
+                )+
+                Ok(object)
+            }
+        }
+    };
+}
+
+named_fields_result!(A 0, B 1);
+named_fields_result!(A 0, B 1, C 2);
+
+impl<'a, T: ResultTypeInfo<'a>, E: SignalNodeError> ResultTypeInfo<'a> for Result<T, E> {
     type ResultType = T::ResultType;
     fn convert_into(self, cx: &mut impl Context<'a>) -> NeonResult<Handle<'a, Self::ResultType>> {
         match self {
             Ok(value) => value.convert_into(cx),
-            // FIXME: Use a dedicated Error type?
-            Err(err) => cx.throw_error(err.to_string()),
+            Err(err) => err.throw(cx),
         }
     }
 }
@@ -687,6 +927,187 @@ impl<T: Send + Sync + 'static> Finalize for PersistentBoxedValue<T> {
     }
 }
 
+/// Loads the `RefCell` used by [`bridge_handle`](crate::support::bridge_handle)'s `mut = true`
+/// mode from its JavaScript wrapper.
+///
+/// # Safety
+///
+/// The returned reference is only valid as long as `wrapper` is reachable; the caller must keep
+/// it alive (using a [`Handle`] in the current context or a [`Root`]) for as long as the
+/// reference is used.
+pub(crate) unsafe fn boxed_ref_cell<'a, 'b, T: Send + 'static>(
+    cx: &mut impl Context<'a>,
+    wrapper: Handle<JsObject>,
+) -> NeonResult<&'b RefCell<T>> {
+    let value_box: Handle<DefaultJsBox<RefCell<T>>> = wrapper
+        .get(cx, NATIVE_HANDLE_PROPERTY)?
+        .downcast_or_throw(cx)?;
+    // The RefCell can't move because we can't know how many JS references there are referring to
+    // the JsBox, so its lifetime is that of the wrapper object.
+    Ok(extend_lifetime(&***value_box))
+}
+
+/// Loads each element of a JavaScript array with `load`, as for `&[&Foo]` arguments.
+///
+/// Throws a `TypeError` naming the index of the first element that isn't an object or that `load`
+/// rejects by returning `None`.
+pub(crate) fn load_array_elements<'a, T>(
+    cx: &mut FunctionContext<'a>,
+    array: Handle<'a, JsArray>,
+    mut load: impl FnMut(&mut FunctionContext<'a>, Handle<'a, JsObject>) -> NeonResult<Option<T>>,
+) -> NeonResult<Vec<T>> {
+    let elements = array.to_vec(cx)?;
+    let mut result = Vec::with_capacity(elements.len());
+    for (index, element) in elements.into_iter().enumerate() {
+        let loaded = match element.downcast::<JsObject, _>(cx) {
+            Ok(wrapper) => load(cx, wrapper)?,
+            Err(_) => None,
+        };
+        match loaded {
+            Some(value) => result.push(value),
+            None => return cx.throw_type_error(format!("invalid handle at index {}", index)),
+        }
+    }
+    Ok(result)
+}
+
+/// Safely persists every element of a JavaScript array of wrappers, for `&[&Foo]` arguments to
+/// async functions.
+///
+/// Each element is persisted as a `P`, such as a [`PersistentBoxedValue`] or a
+/// [`PersistentBorrow`]. Like those, a `PersistentArray` **cannot be dropped**; instead, it must be
+/// explicitly finalized in a JavaScript context, which finalizes each element.
+pub struct PersistentArray<P: Finalize, T: 'static> {
+    elements: Vec<P>,
+    value_ptrs: Vec<*const T>,
+}
+
+impl<P: Finalize, T: 'static> PersistentArray<P, T> {
+    /// Persists each element of `array` with `persist`, which should throw if an element isn't the
+    /// right kind of wrapper.
+    ///
+    /// `project` picks out the value to pass to Rust from a persisted element; it must not move for
+    /// as long as the element is persisted.
+    pub(crate) fn new<'a>(
+        cx: &mut FunctionContext<'a>,
+        array: Handle<JsArray>,
+        persist: impl Fn(&mut FunctionContext<'a>, Handle<'a, JsObject>) -> NeonResult<P>,
+        project: impl Fn(&P) -> &T,
+    ) -> NeonResult<Self> {
+        // load_array_elements needs a handle in cx's scope; going through a Root produces one.
+        let array = array.root(cx).into_inner(cx);
+        let wrappers = load_array_elements(cx, array, |_, wrapper| Ok(Some(wrapper)))?;
+        let mut elements = Vec::with_capacity(wrappers.len());
+        for wrapper in wrappers {
+            match persist(cx, wrapper) {
+                Ok(element) => elements.push(element),
+                Err(e) => {
+                    // The elements persisted so far can't be dropped either.
+                    for element in elements {
+                        element.finalize(cx);
+                    }
+                    return Err(e);
+                }
+            }
+        }
+        let value_ptrs = elements
+            .iter()
+            .map(|element| project(element) as *const T)
+            .collect();
+        Ok(Self {
+            elements,
+            value_ptrs,
+        })
+    }
+
+    pub(crate) fn as_slice(&self) -> &[&T] {
+        // Non-null *const T and &T have the same representation, and every pointer refers to a
+        // value kept alive (and unmoved) by the corresponding element of `self.elements`.
+        unsafe { &*(self.value_ptrs.as_slice() as *const [*const T] as *const [&T]) }
+    }
+}
+
+impl<P: Finalize, T: 'static> Finalize for PersistentArray<P, T> {
+    fn finalize<'a, C: Context<'a>>(self, cx: &mut C) {
+        for element in self.elements {
+            element.finalize(cx);
+        }
+    }
+}
+
+/// Safely persists a borrow of a `RefCell`-boxed Rust value (as used by `bridge_handle!(..., mut
+/// = true)`) by treating its JavaScript wrapper as a GC root.
+///
+/// `B` is either a [`std::cell::Ref`] or a [`std::cell::RefMut`]. Holding the borrow for the
+/// whole lifetime of the `PersistentBorrow` means that any other attempt to mutate the same
+/// object while an async operation is in progress will fail rather than being silently
+/// overwritten.
+///
+/// Like [`PersistentBoxedValue`], a `PersistentBorrow` **cannot be dropped**; instead, it must be
+/// explicitly finalized in a JavaScript context, which releases the borrow and then the root.
+pub struct PersistentBorrow<B: 'static> {
+    owner: Root<JsObject>,
+    borrow: B,
+}
+
+impl<T: Send + 'static> PersistentBorrow<Ref<'static, T>> {
+    /// Persists `wrapper` and immutably borrows its boxed value.
+    pub(crate) fn new_shared<'a>(
+        cx: &mut impl Context<'a>,
+        wrapper: Handle<JsObject>,
+    ) -> NeonResult<Self> {
+        // Safe because the wrapper is rooted below for as long as the borrow is alive.
+        let cell: &'static RefCell<T> = unsafe { boxed_ref_cell(cx, wrapper)? };
+        let borrow = match cell.try_borrow() {
+            Ok(borrow) => borrow,
+            Err(_) => return cx.throw_error("object is being modified by another operation"),
+        };
+        // We must create the root after all failable operations.
+        let owner = wrapper.root(cx);
+        Ok(Self { owner, borrow })
+    }
+}
+
+impl<T: Send + 'static> PersistentBorrow<RefMut<'static, T>> {
+    /// Persists `wrapper` and mutably borrows its boxed value.
+    pub(crate) fn new_exclusive<'a>(
+        cx: &mut impl Context<'a>,
+        wrapper: Handle<JsObject>,
+    ) -> NeonResult<Self> {
+        // Safe because the wrapper is rooted below for as long as the borrow is alive.
+        let cell: &'static RefCell<T> = unsafe { boxed_ref_cell(cx, wrapper)? };
+        let borrow = match cell.try_borrow_mut() {
+            Ok(borrow) => borrow,
+            Err(_) => return cx.throw_error("object is already in use by another operation"),
+        };
+        // We must create the root after all failable operations.
+        let owner = wrapper.root(cx);
+        Ok(Self { owner, borrow })
+    }
+}
+
+impl<B: Deref + 'static> Deref for PersistentBorrow<B> {
+    type Target = B::Target;
+    fn deref(&self) -> &Self::Target {
+        &*self.borrow
+    }
+}
+
+impl<B: DerefMut + 'static> DerefMut for PersistentBorrow<B> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut *self.borrow
+    }
+}
+
+impl<B: 'static> Finalize for PersistentBorrow<B> {
+    fn finalize<'a, C: Context<'a>>(self, cx: &mut C) {
+        // Release the borrow before the wrapper (and thus the RefCell) can be collected.
+        let Self { owner, borrow } = self;
+        drop(borrow);
+        owner.finalize(cx)
+    }
+}
+
 /// Implementation of [`bridge_handle`](crate::support::bridge_handle) for Node.
 macro_rules! node_bridge_handle {
     ( $typ:ty as false ) => {};
@@ -708,8 +1129,38 @@ macro_rules! node_bridge_handle {
             }
         }
 
+        impl<'storage, 'context: 'storage> node::ArgTypeInfo<'storage, 'context>
+            for &'storage [&'storage $typ]
+        {
+            type ArgType = node::JsArray;
+            type StoredType = (
+                Vec<node::Handle<'context, node::DefaultJsBox<$typ>>>,
+                Vec<&'storage $typ>,
+            );
+            fn borrow(
+                cx: &mut node::FunctionContext<'context>,
+                foreign: node::Handle<'context, Self::ArgType>,
+            ) -> node::NeonResult<Self::StoredType> {
+                let boxes = node::load_array_elements(cx, foreign, |cx, wrapper| {
+                    Ok(node::Object::get(*wrapper, cx, node::NATIVE_HANDLE_PROPERTY)?
+                        .downcast::<node::DefaultJsBox<$typ>, _>(cx)
+                        .ok())
+                })?;
+                Ok((boxes, Vec::new()))
+            }
+            fn load_from(
+                stored: &'storage mut Self::StoredType,
+            ) -> Self {
+                let (boxes, loaded) = stored;
+                let boxes: &'storage Vec<_> = boxes;
+                *loaded = boxes.iter().map(|value_box| &****value_box).collect();
+                let loaded: &'storage Vec<_> = loaded;
+                &loaded[..]
+            }
+        }
+
         paste! {
-            #[doc = "ts: interface " $typ " { readonly __type: unique symbol; }"]
+            #[doc = "ts: export interface " $typ " { readonly __type: unique symbol; }"]
             impl<'a> node::ResultTypeInfo<'a> for $typ {
                 type ResultType = node::JsValue;
                 fn convert_into(
@@ -719,6 +1170,15 @@ macro_rules! node_bridge_handle {
                     node::return_boxed_object(cx, Ok(self))
                 }
             }
+            impl<'a> node::ResultTypeInfo<'a> for Vec<$typ> {
+                type ResultType = node::JsArray;
+                fn convert_into(
+                    self,
+                    cx: &mut impl node::Context<'a>,
+                ) -> node::NeonResult<node::Handle<'a, Self::ResultType>> {
+                    node::convert_list_into(cx, self)
+                }
+            }
         }
 
         impl<'storage> node::AsyncArgTypeInfo<'storage> for &'storage $typ {
@@ -736,6 +1196,30 @@ macro_rules! node_bridge_handle {
                 &*stored
             }
         }
+
+        impl<'storage> node::AsyncArgTypeInfo<'storage> for &'storage [&'storage $typ] {
+            type ArgType = node::JsArray;
+            type StoredType = node::PersistentArray<
+                node::PersistentBoxedValue<node::DefaultFinalize<$typ>>,
+                $typ,
+            >;
+            fn save_async_arg(
+                cx: &mut node::FunctionContext,
+                foreign: node::Handle<Self::ArgType>,
+            ) -> node::NeonResult<Self::StoredType> {
+                node::PersistentArray::new(
+                    cx,
+                    foreign,
+                    |cx, wrapper| node::PersistentBoxedValue::new(cx, wrapper),
+                    |element| &***element,
+                )
+            }
+            fn load_async_arg(
+                stored: &'storage mut Self::StoredType,
+            ) -> Self {
+                stored.as_slice()
+            }
+        }
     };
     ( $typ:ty as $node_name:ident, mut = true ) => {
         impl<'storage, 'context: 'storage> node::ArgTypeInfo<'storage, 'context>
@@ -743,25 +1227,21 @@ macro_rules! node_bridge_handle {
         {
             type ArgType = node::JsObject;
             type StoredType = (
-                node::Handle<'context, node::DefaultJsBox<std::cell::RefCell<$typ>>>,
+                node::Handle<'context, Self::ArgType>,
                 std::cell::Ref<'context, $typ>,
             );
             fn borrow(
                 cx: &mut node::FunctionContext<'context>,
                 foreign: node::Handle<'context, Self::ArgType>,
             ) -> node::NeonResult<Self::StoredType> {
-                let boxed_value: node::Handle<'context, node::DefaultJsBox<std::cell::RefCell<$typ>>> =
-                    node::Object::get(*foreign, cx, node::NATIVE_HANDLE_PROPERTY)?.downcast_or_throw(cx)?;
-                let cell: &std::cell::RefCell<_> = &***boxed_value;
                 // FIXME: Workaround for https://github.com/neon-bindings/neon/issues/678
                 // The lifetime of the boxed RefCell is necessarily longer than the lifetime of any handles referring to it, i.e. longer than 'context.
                 // However, Deref'ing a Handle can only give us a Ref whose lifetime matches a *particular* handle.
                 // Therefore, we unsafely (in the compiler sense) extend the lifetime to be the lifetime of the context, as given by the Handle.
-                // (We also know the RefCell can't move because we can't know how many JS references there are referring to the JsBox.)
-                let cell_with_extended_lifetime: &'context std::cell::RefCell<_> = unsafe {
-                    node::extend_lifetime(cell)
+                let cell: &'context std::cell::RefCell<$typ> = unsafe {
+                    node::boxed_ref_cell(cx, foreign)?
                 };
-                Ok((boxed_value, cell_with_extended_lifetime.borrow()))
+                Ok((foreign, cell.borrow()))
             }
             fn load_from(
                 stored: &'storage mut Self::StoredType,
@@ -773,21 +1253,20 @@ macro_rules! node_bridge_handle {
         impl<'storage, 'context: 'storage> node::ArgTypeInfo<'storage, 'context>
             for &'storage mut $typ
         {
-            type ArgType = node::DefaultJsBox<std::cell::RefCell<$typ>>;
+            type ArgType = node::JsObject;
             type StoredType = (
                 node::Handle<'context, Self::ArgType>,
                 std::cell::RefMut<'context, $typ>,
             );
             fn borrow(
-                _cx: &mut node::FunctionContext,
+                cx: &mut node::FunctionContext<'context>,
                 foreign: node::Handle<'context, Self::ArgType>,
             ) -> node::NeonResult<Self::StoredType> {
-                let cell: &std::cell::RefCell<_> = &***foreign;
                 // See above.
-                let cell_with_extended_lifetime: &'context std::cell::RefCell<_> = unsafe {
-                    node::extend_lifetime(cell)
+                let cell: &'context std::cell::RefCell<$typ> = unsafe {
+                    node::boxed_ref_cell(cx, foreign)?
                 };
-                Ok((foreign, cell_with_extended_lifetime.borrow_mut()))
+                Ok((foreign, cell.borrow_mut()))
             }
             fn load_from(
                 stored: &'storage mut Self::StoredType,
@@ -796,8 +1275,103 @@ macro_rules! node_bridge_handle {
             }
         }
 
+        impl<'storage, 'context: 'storage> node::ArgTypeInfo<'storage, 'context>
+            for &'storage [&'storage $typ]
+        {
+            type ArgType = node::JsArray;
+            type StoredType = (
+                Vec<std::cell::Ref<'context, $typ>>,
+                Vec<&'storage $typ>,
+            );
+            fn borrow(
+                cx: &mut node::FunctionContext<'context>,
+                foreign: node::Handle<'context, Self::ArgType>,
+            ) -> node::NeonResult<Self::StoredType> {
+                let boxes = node::load_array_elements(cx, foreign, |cx, wrapper| {
+                    Ok(node::Object::get(*wrapper, cx, node::NATIVE_HANDLE_PROPERTY)?
+                        .downcast::<node::DefaultJsBox<std::cell::RefCell<$typ>>, _>(cx)
+                        .ok())
+                })?;
+                // See above. Each handle to a box lives as long as the context, so the boxes do
+                // too.
+                let borrows = boxes
+                    .iter()
+                    .map(|value_box| {
+                        let cell: &'context std::cell::RefCell<$typ> = unsafe {
+                            node::extend_lifetime(&****value_box)
+                        };
+                        cell.borrow()
+                    })
+                    .collect();
+                Ok((borrows, Vec::new()))
+            }
+            fn load_from(
+                stored: &'storage mut Self::StoredType,
+            ) -> Self {
+                let (borrows, loaded) = stored;
+                let borrows: &'storage Vec<_> = borrows;
+                *loaded = borrows.iter().map(|borrow| &**borrow).collect();
+                let loaded: &'storage Vec<_> = loaded;
+                &loaded[..]
+            }
+        }
+
+        impl<'storage> node::AsyncArgTypeInfo<'storage> for &'storage $typ {
+            type ArgType = node::JsObject;
+            type StoredType = node::PersistentBorrow<std::cell::Ref<'static, $typ>>;
+            fn save_async_arg(
+                cx: &mut node::FunctionContext,
+                foreign: node::Handle<Self::ArgType>,
+            ) -> node::NeonResult<Self::StoredType> {
+                node::PersistentBorrow::new_shared(cx, foreign)
+            }
+            fn load_async_arg(
+                stored: &'storage mut Self::StoredType,
+            ) -> Self {
+                &*stored
+            }
+        }
+
+        impl<'storage> node::AsyncArgTypeInfo<'storage> for &'storage [&'storage $typ] {
+            type ArgType = node::JsArray;
+            type StoredType =
+                node::PersistentArray<node::PersistentBorrow<std::cell::Ref<'static, $typ>>, $typ>;
+            fn save_async_arg(
+                cx: &mut node::FunctionContext,
+                foreign: node::Handle<Self::ArgType>,
+            ) -> node::NeonResult<Self::StoredType> {
+                node::PersistentArray::new(
+                    cx,
+                    foreign,
+                    |cx, wrapper| node::PersistentBorrow::new_shared(cx, wrapper),
+                    |element| &**element,
+                )
+            }
+            fn load_async_arg(
+                stored: &'storage mut Self::StoredType,
+            ) -> Self {
+                stored.as_slice()
+            }
+        }
+
+        impl<'storage> node::AsyncArgTypeInfo<'storage> for &'storage mut $typ {
+            type ArgType = node::JsObject;
+            type StoredType = node::PersistentBorrow<std::cell::RefMut<'static, $typ>>;
+            fn save_async_arg(
+                cx: &mut node::FunctionContext,
+                foreign: node::Handle<Self::ArgType>,
+            ) -> node::NeonResult<Self::StoredType> {
+                node::PersistentBorrow::new_exclusive(cx, foreign)
+            }
+            fn load_async_arg(
+                stored: &'storage mut Self::StoredType,
+            ) -> Self {
+                &mut *stored
+            }
+        }
+
         paste! {
-            #[doc = "ts: interface " $typ " { readonly __type: unique symbol; }"]
+            #[doc = "ts: export interface " $typ " { readonly __type: unique symbol; }"]
             impl<'a> node::ResultTypeInfo<'a> for $typ {
                 type ResultType = node::JsValue;
                 fn convert_into(
@@ -807,6 +1381,15 @@ macro_rules! node_bridge_handle {
                     node::return_boxed_object(cx, Ok(std::cell::RefCell::new(self)))
                 }
             }
+            impl<'a> node::ResultTypeInfo<'a> for Vec<$typ> {
+                type ResultType = node::JsArray;
+                fn convert_into(
+                    self,
+                    cx: &mut impl node::Context<'a>,
+                ) -> node::NeonResult<node::Handle<'a, Self::ResultType>> {
+                    node::convert_list_into(cx, self)
+                }
+            }
         }
     };
     ( $typ:ty $(, mut = $_:tt)?) => {
@@ -5,8 +5,11 @@
 
 use super::*;
 
+use std::any::Any;
 use std::fmt;
 
+use crate::support::describe_panic;
+
 /// Represents an error returned by a callback.
 #[derive(Debug)]
 struct CallbackError {
@@ -31,3 +34,79 @@ impl std::error::Error for CallbackError {}
 pub fn js_error_to_rust(func: &'static str, err: String) -> SignalProtocolError {
     SignalProtocolError::ApplicationCallbackError(func, Box::new(CallbackError::new(err)))
 }
+
+/// Converts a Rust error into a JavaScript exception.
+///
+/// The thrown value is a JavaScript `Error` whose `message` describes the Rust error (by default,
+/// with its `Display` output) and whose `name` identifies the kind of error, so that JavaScript
+/// code can distinguish errors coming from different Rust crates.
+pub trait SignalNodeError: Sized + fmt::Display {
+    /// The `name` property of the thrown JavaScript `Error`.
+    fn js_error_name(&self) -> &'static str;
+
+    /// The `message` property of the thrown JavaScript `Error`.
+    fn js_error_message(&self) -> String {
+        self.to_string()
+    }
+
+    /// Throws `self` as a JavaScript `Error`.
+    fn throw<'a, T: Value>(self, cx: &mut impl Context<'a>) -> JsResult<'a, T> {
+        let error = cx.error(self.js_error_message())?;
+        let name = cx.string(self.js_error_name());
+        error.set(cx, "name", name)?;
+        cx.throw(error)
+    }
+}
+
+impl SignalNodeError for SignalProtocolError {
+    fn js_error_name(&self) -> &'static str {
+        match self {
+            SignalProtocolError::IdentityKeyChanged(..) => "IdentityKeyChanged",
+            SignalProtocolError::UnknownSessionVersion(_) => "UnknownSessionVersion",
+            _ => "SignalProtocolError",
+        }
+    }
+
+    fn js_error_message(&self) -> String {
+        crate::support::ErrorChain(self).to_string()
+    }
+}
+
+impl SignalNodeError for aes_gcm_siv::Error {
+    fn js_error_name(&self) -> &'static str {
+        match self {
+            aes_gcm_siv::Error::InvalidKeySize => "InvalidKeySize",
+            aes_gcm_siv::Error::InvalidNonceSize => "InvalidNonceSize",
+            aes_gcm_siv::Error::InvalidInputSize => "InvalidInputSize",
+            aes_gcm_siv::Error::InvalidTag => "InvalidTag",
+        }
+    }
+}
+
+/// A panic caught in Rust code before it could unwind into JavaScript.
+///
+/// Thrown as an `Error` named `LibSignalInternalError`, so that apps can report it as a bug rather
+/// than mistaking it for a problem with their inputs.
+#[derive(Debug)]
+pub struct UnexpectedPanic(String);
+
+impl UnexpectedPanic {
+    /// Describes the payload of a caught panic.
+    ///
+    /// Only the description is kept, so that the error can be sent back to the JavaScript thread.
+    pub fn new(payload: Box<dyn Any + Send>) -> Self {
+        Self(describe_panic(&*payload))
+    }
+}
+
+impl fmt::Display for UnexpectedPanic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unexpected panic: {}", self.0)
+    }
+}
+
+impl SignalNodeError for UnexpectedPanic {
+    fn js_error_name(&self) -> &'static str {
+        "LibSignalInternalError"
+    }
+}
@@ -5,7 +5,10 @@
 
 use libsignal_protocol::*;
 use std::convert::TryFrom;
-use std::ops::Deref;
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread::ThreadId;
 
 pub(crate) use neon::context::Context;
 pub(crate) use neon::prelude::*;
@@ -51,6 +54,153 @@ impl<T> Deref for DefaultFinalize<T> {
 
 pub type DefaultJsBox<T> = JsBox<DefaultFinalize<T>>;
 
+static LIVE_SAVED_ARGUMENTS: AtomicUsize = AtomicUsize::new(0);
+
+/// Returns how many arguments saved by async `bridge_fn`s haven't been released yet.
+///
+/// Used by the binding tests to check that calls interrupted by shutdown don't hold on to their
+/// arguments.
+pub fn live_saved_argument_count() -> usize {
+    LIVE_SAVED_ARGUMENTS.load(Ordering::SeqCst)
+}
+
+/// An argument saved for an async `bridge_fn`, tied to the JavaScript thread it was saved on.
+///
+/// Most saved arguments aren't `Send`, since they hold borrows and pointers into JavaScript
+/// objects. A `SavedArgument` can still be passed through an [`EventQueue`], because the value
+/// inside is only ever used or finalized on its original thread, and is never dropped.
+struct SavedArgument<T: Finalize> {
+    value: Option<T>,
+    thread: ThreadId,
+}
+
+// Safe because every access to `value` checks `thread` first, and `drop` never drops it.
+unsafe impl<T: Finalize> Send for SavedArgument<T> {}
+
+impl<T: Finalize> SavedArgument<T> {
+    fn new(value: T) -> Self {
+        LIVE_SAVED_ARGUMENTS.fetch_add(1, Ordering::SeqCst);
+        Self {
+            value: Some(value),
+            thread: std::thread::current().id(),
+        }
+    }
+
+    fn is_on_original_thread(&self) -> bool {
+        std::thread::current().id() == self.thread
+    }
+
+    fn get(&mut self) -> &mut T {
+        assert!(
+            self.is_on_original_thread(),
+            "saved argument used off the JavaScript thread"
+        );
+        self.value.as_mut().expect("not yet finalized")
+    }
+
+    fn finalize<'a, C: Context<'a>>(mut self, cx: &mut C) {
+        assert!(
+            self.is_on_original_thread(),
+            "saved argument finalized off the JavaScript thread"
+        );
+        if let Some(value) = self.value.take() {
+            value.finalize(cx);
+            LIVE_SAVED_ARGUMENTS.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+}
+
+impl<T: Finalize> Drop for SavedArgument<T> {
+    fn drop(&mut self) {
+        if let Some(value) = self.value.take() {
+            // We only get here if the value couldn't be sent back to be finalized, because the
+            // event queue has shut down along with the whole JavaScript environment. Node deletes
+            // the environment's remaining references and finalizes its boxed values itself, and
+            // some of those may already be gone, so dropping `value` (and releasing, say, the
+            // borrow of a boxed `RefCell`) could touch freed memory. Leave everything to Node.
+            std::mem::forget(value);
+            LIVE_SAVED_ARGUMENTS.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+}
+
+/// Owns an argument saved for an async `bridge_fn`, and makes sure it is finalized however the
+/// call ends.
+///
+/// Finalizing the wrapper finalizes the value right away. If the wrapper is dropped instead (say,
+/// because the promise couldn't be created, or the future was dropped before it settled),
+/// finalization is scheduled on the JavaScript thread through `queue`. If the queue has already
+/// shut down, the environment is being torn down and Node releases the value's references itself.
+pub struct FinalizeOnDrop<T: Finalize + 'static> {
+    saved: Option<SavedArgument<T>>,
+    queue: Arc<EventQueue>,
+}
+
+impl<T: Finalize + 'static> FinalizeOnDrop<T> {
+    pub fn new(value: T, queue: Arc<EventQueue>) -> Self {
+        Self {
+            saved: Some(SavedArgument::new(value)),
+            queue,
+        }
+    }
+}
+
+impl<T: Finalize + 'static> Deref for FinalizeOnDrop<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        let saved = self.saved.as_ref().expect("not yet finalized");
+        assert!(
+            saved.is_on_original_thread(),
+            "saved argument used off the JavaScript thread"
+        );
+        saved.value.as_ref().expect("not yet finalized")
+    }
+}
+
+impl<T: Finalize + 'static> DerefMut for FinalizeOnDrop<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.saved.as_mut().expect("not yet finalized").get()
+    }
+}
+
+impl<T: Finalize + 'static> Finalize for FinalizeOnDrop<T> {
+    fn finalize<'a, C: Context<'a>>(mut self, cx: &mut C) {
+        if let Some(saved) = self.saved.take() {
+            saved.finalize(cx)
+        }
+    }
+}
+
+impl<T: Finalize + 'static> Drop for FinalizeOnDrop<T> {
+    fn drop(&mut self) {
+        if let Some(saved) = self.saved.take() {
+            // If the queue has shut down, the closure is dropped without running, and `saved`
+            // with it.
+            self.queue.send(move |mut cx| {
+                saved.finalize(&mut cx);
+                Ok(())
+            });
+        }
+    }
+}
+
+pub use signal_neon_futures::CancellationToken;
+
+/// A parameter of an async `bridge_fn` that is filled in from the returned promise's
+/// [`CancellationToken`] rather than from the JavaScript arguments.
+///
+/// The token is cancelled when the promise's `cancel()` method is called.
+pub trait SupportsCancellation {
+    fn from_cancellation_token(token: CancellationToken) -> Self;
+}
+
+impl SupportsCancellation for CancellationToken {
+    fn from_cancellation_token(token: CancellationToken) -> Self {
+        token
+    }
+}
+
 pub fn return_boxed_object<'a, T: 'static + Send>(
     cx: &mut impl Context<'a>,
     value: Result<T, SignalProtocolError>,
@@ -109,15 +259,60 @@ pub(crate) fn with_buffer_contents<R>(
 
 /// Used in the implementation of `bridge_fn` to keep track of all generated entry points.
 ///
+/// `$arity` is the number of parameters the entry point takes in JavaScript, as listed by
+/// `describe_all` when the "describe" feature is enabled.
+///
 /// Not intended to be invoked directly.
 macro_rules! node_register {
-    ( $name:ident ) => {
+    ( $name:ident, $arity:expr ) => {
         paste! {
             #[no_mangle] // necessary because we are linking as a cdylib
-            #[allow(non_upper_case_globals)]
+            // The entry point may be deprecated, but registering it isn't a use.
+            #[allow(non_upper_case_globals, deprecated)]
             #[linkme::distributed_slice(node::LIBSIGNAL_FNS)]
             static [<signal_register_node_ $name>]: (&str, node::JsFn) =
                 (stringify!($name), [<node_ $name>]);
+
+            #[cfg(any(test, feature = "describe"))]
+            #[allow(non_upper_case_globals)]
+            #[linkme::distributed_slice(crate::describe::BRIDGED_FNS)]
+            static [<signal_describe_node_ $name>]: crate::describe::BridgedFn =
+                crate::describe::BridgedFn {
+                    name: stringify!($name),
+                    arity: $arity,
+                    platform: crate::describe::Platform::Node,
+                };
+        }
+    };
+}
+
+/// Implementation of the `serialize` flag of [`bridge_handle`](crate::support::bridge_handle) for
+/// Node.
+macro_rules! node_bridge_serialize {
+    ( $typ:ty as false $(, serialize = $_:tt)? ) => {};
+    ( $typ:ty as $node_name:ident $(, serialize = false)? ) => {};
+    ( $typ:ty as $node_name:ident, serialize = true ) => {
+        paste! {
+            #[allow(non_snake_case)]
+            #[doc = "ts: export function " $node_name "_Serialize(obj: Wrapper<" $node_name ">): Buffer"]
+            pub fn [<node_ $node_name _Serialize>](
+                mut cx: node::FunctionContext
+            ) -> node::JsResult<node::JsValue> {
+                let obj_arg = cx.argument::<<&$typ as node::ArgTypeInfo>::ArgType>(0)?;
+                let mut obj_stored = <&$typ as node::ArgTypeInfo>::borrow(&mut cx, obj_arg)?;
+                let obj = <&$typ as node::ArgTypeInfo>::load_from(&mut obj_stored);
+                let result = TransformHelper(<$typ>::serialize(obj))
+                    .ok_if_needed()
+                    .map(|result| Vec::from(result.into_vec_if_needed().0));
+                Ok(node::ResultTypeInfo::convert_into(result, &mut cx)?.upcast())
+            }
+
+            node_register!([<$node_name _Serialize>], 1);
+        }
+    };
+    ( $typ:ty $(, serialize = $_:tt)? ) => {
+        paste! {
+            node_bridge_serialize!($typ as $typ $(, serialize = $_)?);
         }
     };
 }
@@ -138,7 +333,7 @@ macro_rules! node_bridge_deserialize {
                 node::return_boxed_object(&mut cx, obj)
             }
 
-            node_register!([<$node_name _Deserialize>]);
+            node_register!([<$node_name _Deserialize>], 1);
         }
     };
     ( $typ:ident::$fn:path ) => {
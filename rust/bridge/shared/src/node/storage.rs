@@ -7,6 +7,7 @@ use super::*;
 
 use async_trait::async_trait;
 use signal_neon_futures::*;
+use std::cell::RefCell;
 use std::sync::Arc;
 
 pub struct NodePreKeyStore {
@@ -257,8 +258,8 @@ impl NodeSessionStore {
             Ok(result)
         })
         .then(|cx, result| match result {
-            Ok(value) => match value.downcast::<DefaultJsBox<SessionRecord>, _>(cx) {
-                Ok(obj) => Ok(Some((***obj).clone())),
+            Ok(value) => match value.downcast::<DefaultJsBox<RefCell<SessionRecord>>, _>(cx) {
+                Ok(obj) => Ok(Some((***obj).borrow().clone())),
                 Err(_) => {
                     if value.is_a::<JsNull, _>(cx) || value.is_a::<JsUndefined, _>(cx) {
                         Ok(None)
@@ -363,7 +364,7 @@ impl NodeIdentityKeyStore {
         })
         .then(|cx, result| match result {
             Ok(value) => match value.downcast::<DefaultJsBox<PrivateKey>, _>(cx) {
-                Ok(obj) => Ok(***obj),
+                Ok(obj) => Ok((***obj).clone()),
                 Err(_) => Err("result must be an object".to_owned()),
             },
             Err(error) => Err(error
@@ -549,10 +550,21 @@ impl IdentityKeyStore for NodeIdentityKeyStore {
         identity: &IdentityKey,
         direction: libsignal_protocol::Direction,
         _ctx: libsignal_protocol::Context,
-    ) -> Result<bool, SignalProtocolError> {
-        self.do_is_trusted(address.clone(), *identity.public_key(), direction)
+    ) -> Result<IdentityTrust, SignalProtocolError> {
+        let trusted = self
+            .do_is_trusted(address.clone(), *identity.public_key(), direction)
             .await
-            .map_err(|s| js_error_to_rust("isTrustedIdentity", s))
+            .map_err(|s| js_error_to_rust("isTrustedIdentity", s))?;
+        let saved = if trusted {
+            None
+        } else {
+            self.do_get_identity(address.clone())
+                .await
+                .ok()
+                .flatten()
+                .map(IdentityKey::new)
+        };
+        Ok(IdentityTrust::from_saved_key(trusted, identity, saved))
     }
 }
 
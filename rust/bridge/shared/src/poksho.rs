@@ -0,0 +1,50 @@
+//
+// Copyright 2021 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+use ::poksho;
+use curve25519_dalek::ristretto::{CompressedRistretto, RistrettoPoint};
+use curve25519_dalek::scalar::Scalar;
+use libsignal_bridge_macros::*;
+use libsignal_protocol::error::{Result, SignalProtocolError};
+
+use crate::support::*;
+use crate::*;
+
+/// Computes `Σ s_i * P_i` for the 32-byte canonical scalars in `scalars` and the 32-byte
+/// compressed Ristretto points in `points`, returning the compressed result.
+///
+/// Not constant-time; see [`poksho::multi_scalar_mul`].
+#[bridge_fn]
+fn Poksho_MultiScalarMul(scalars: &[u8], points: &[u8]) -> Result<Vec<u8>> {
+    if scalars.len() % 32 != 0 || points.len() != scalars.len() {
+        return Err(SignalProtocolError::InvalidArgument(format!(
+            "expected the same number of 32-byte scalars and points, got {} and {} bytes",
+            scalars.len(),
+            points.len()
+        )));
+    }
+
+    let scalars = scalars
+        .chunks_exact(32)
+        .map(|bytes| {
+            poksho::scalar_from_slice_canonical(bytes).map_err(|_| {
+                SignalProtocolError::InvalidArgument("non-canonical scalar".to_string())
+            })
+        })
+        .collect::<Result<Vec<Scalar>>>()?;
+    let points = points
+        .chunks_exact(32)
+        .map(|bytes| {
+            CompressedRistretto::from_slice(bytes)
+                .decompress()
+                .ok_or_else(|| SignalProtocolError::InvalidArgument("invalid point".to_string()))
+        })
+        .collect::<Result<Vec<RistrettoPoint>>>()?;
+
+    Ok(poksho::multi_scalar_mul(&scalars, &points)
+        .compress()
+        .as_bytes()
+        .to_vec())
+}
@@ -12,28 +12,43 @@ use std::convert::TryFrom;
 use crate::support::*;
 use crate::*;
 
-bridge_handle!(CiphertextMessage, clone = false, jni = false);
+bridge_handle!(
+    CiphertextMessage,
+    clone = false,
+    serialize = true,
+    jni = false
+);
 bridge_handle!(Fingerprint, jni = NumericFingerprintGenerator);
 bridge_handle!(PreKeyBundle);
 bridge_handle!(PreKeyRecord);
 bridge_handle!(PreKeySignalMessage);
-bridge_handle!(PrivateKey, ffi = privatekey, jni = ECPrivateKey);
+bridge_handle!(
+    PrivateKey,
+    serialize = true,
+    ffi = privatekey,
+    jni = ECPrivateKey
+);
 bridge_handle!(ProtocolAddress, ffi = address);
-bridge_handle!(PublicKey, ffi = publickey, jni = ECPublicKey);
+bridge_handle!(
+    PublicKey,
+    serialize = true,
+    ffi = publickey,
+    jni = ECPublicKey
+);
 bridge_handle!(SenderCertificate);
 bridge_handle!(SenderKeyDistributionMessage);
 bridge_handle!(SenderKeyMessage);
 bridge_handle!(SenderKeyName);
 bridge_handle!(SenderKeyRecord);
 bridge_handle!(ServerCertificate);
-bridge_handle!(SessionRecord, mut = true);
+bridge_handle!(SessionRecord, mut = true, serialize = true);
 bridge_handle!(SignalMessage, ffi = message);
 bridge_handle!(SignedPreKeyRecord);
 bridge_handle!(UnidentifiedSenderMessage, ffi = false, node = false);
 bridge_handle!(UnidentifiedSenderMessageContent, clone = false);
 bridge_handle!(SealedSenderDecryptionResult, ffi = false, jni = false);
 
-#[bridge_fn_buffer(ffi = false)]
+#[bridge_fn]
 fn HKDF_DeriveSecrets<E: Env>(
     env: E,
     output_length: u32,
@@ -51,7 +66,7 @@ fn HKDF_DeriveSecrets<E: Env>(
 }
 
 // Alternate implementation to fill an existing buffer.
-#[bridge_fn_void(jni = false, node = false)]
+#[bridge_fn(jni = false, node = false)]
 fn HKDF_Derive(
     output: &mut [u8],
     version: u32,
@@ -79,11 +94,6 @@ fn ECPublicKey_Deserialize(data: &[u8], offset: u32) -> Result<PublicKey> {
     PublicKey::deserialize(&data[offset..])
 }
 
-bridge_get_bytearray!(
-    PublicKey::serialize as Serialize,
-    ffi = "publickey_serialize",
-    jni = "ECPublicKey_1Serialize"
-);
 bridge_get_bytearray!(
     PublicKey::public_key_bytes,
     ffi = "publickey_get_public_key_bytes",
@@ -106,16 +116,54 @@ fn ECPublicKey_Verify(key: &PublicKey, message: &[u8], signature: &[u8]) -> Resu
     key.verify_signature(&message, &signature)
 }
 
+/// Verifies a batch of signatures made with `key`, returning `true` only if all of them are valid.
+///
+/// `messages` holds each message prefixed with its length as a big-endian `u32`, and `signatures`
+/// holds the 64-byte signatures back to back, in the same order.
+#[bridge_fn(ffi = "publickey_verify_batch", node = "PublicKey_VerifyBatch")]
+fn ECPublicKey_VerifyBatch(key: &PublicKey, messages: &[u8], signatures: &[u8]) -> Result<bool> {
+    const SIGNATURE_LENGTH: usize = 64;
+    if signatures.len() % SIGNATURE_LENGTH != 0 {
+        return Err(SignalProtocolError::InvalidArgument(format!(
+            "signatures must be {} bytes each",
+            SIGNATURE_LENGTH
+        )));
+    }
+
+    let mut messages_and_signatures = Vec::with_capacity(signatures.len() / SIGNATURE_LENGTH);
+    let mut remaining = messages;
+    for signature in signatures.chunks_exact(SIGNATURE_LENGTH) {
+        let truncated = || SignalProtocolError::InvalidArgument("truncated message".to_string());
+        if remaining.len() < 4 {
+            return Err(truncated());
+        }
+        let (length, rest) = remaining.split_at(4);
+        let length = u32::from_be_bytes(<[u8; 4]>::try_from(length).expect("correct length"));
+        let length = length as usize;
+        if rest.len() < length {
+            return Err(truncated());
+        }
+        let (message, rest) = rest.split_at(length);
+        messages_and_signatures.push((message, signature));
+        remaining = rest;
+    }
+    if !remaining.is_empty() {
+        return Err(SignalProtocolError::InvalidArgument(
+            "more messages than signatures".to_string(),
+        ));
+    }
+
+    let mut csprng = rand::rngs::OsRng;
+    Ok(key
+        .verify_signature_batch(&messages_and_signatures, &mut csprng)?
+        .is_none())
+}
+
 bridge_deserialize!(
     PrivateKey::deserialize,
     ffi = privatekey,
     jni = ECPrivateKey
 );
-bridge_get_bytearray!(
-    PrivateKey::serialize as Serialize,
-    ffi = "privatekey_serialize",
-    jni = "ECPrivateKey_1Serialize"
-);
 
 #[bridge_fn(ffi = "privatekey_generate", node = "PrivateKey_Generate")]
 fn ECPrivateKey_Generate() -> PrivateKey {
@@ -129,30 +177,25 @@ fn ECPrivateKey_GetPublicKey(k: &PrivateKey) -> Result<PublicKey> {
     k.public_key()
 }
 
-#[bridge_fn_buffer(ffi = "privatekey_sign", node = "PrivateKey_Sign")]
-fn ECPrivateKey_Sign<T: Env>(env: T, key: &PrivateKey, message: &[u8]) -> Result<T::Buffer> {
+#[bridge_fn(ffi = "privatekey_sign", node = "PrivateKey_Sign")]
+fn ECPrivateKey_Sign(key: &PrivateKey, message: &[u8]) -> Result<Box<[u8]>> {
     let mut rng = rand::rngs::OsRng;
-    let sig = key.calculate_signature(&message, &mut rng)?;
-    Ok(env.buffer(sig.into_vec()))
+    key.calculate_signature(&message, &mut rng)
 }
 
-#[bridge_fn_buffer(ffi = "privatekey_agree", node = "PrivateKey_Agree")]
-fn ECPrivateKey_Agree<T: Env>(
-    env: T,
-    private_key: &PrivateKey,
-    public_key: &PublicKey,
-) -> Result<T::Buffer> {
-    let dh_secret = private_key.calculate_agreement(&public_key)?;
-    Ok(env.buffer(dh_secret.into_vec()))
+#[bridge_fn(ffi = "privatekey_agree", node = "PrivateKey_Agree")]
+fn ECPrivateKey_Agree(private_key: &PrivateKey, public_key: &PublicKey) -> Result<Box<[u8]>> {
+    private_key.calculate_agreement(&public_key)
 }
 
-#[bridge_fn_buffer(ffi = "identitykeypair_serialize")]
+#[bridge_fn(ffi = "identitykeypair_serialize")]
 fn IdentityKeyPair_Serialize<T: Env>(
     env: T,
     public_key: &PublicKey,
     private_key: &PrivateKey,
 ) -> Result<T::Buffer> {
-    let identity_key_pair = IdentityKeyPair::new(IdentityKey::new(*public_key), *private_key);
+    let identity_key_pair =
+        IdentityKeyPair::new(IdentityKey::new(*public_key), private_key.clone());
     Ok(env.buffer(identity_key_pair.serialize().into_vec()))
 }
 
@@ -198,7 +241,7 @@ fn NumericFingerprintGenerator_New(
     )
 }
 
-#[bridge_fn_buffer(jni = "NumericFingerprintGenerator_1GetScannableEncoding")]
+#[bridge_fn(jni = "NumericFingerprintGenerator_1GetScannableEncoding")]
 fn Fingerprint_ScannableEncoding<E: Env>(env: E, obj: &Fingerprint) -> Result<E::Buffer> {
     Ok(env.buffer(obj.scannable.serialize()?))
 }
@@ -215,8 +258,8 @@ fn ScannableFingerprint_Compare(fprint1: &[u8], fprint2: &[u8]) -> Result<bool>
 
 bridge_deserialize!(SignalMessage::try_from, ffi = message);
 
-#[bridge_fn_buffer(ffi = false, node = false)]
-fn SignalMessage_GetSenderRatchetKey<E: Env>(env: E, m: &SignalMessage) -> E::Buffer {
+#[bridge_fn(ffi = false, jni = "SignalMessage_1GetSenderRatchetKey", node = false)]
+fn SignalMessage_GetSenderRatchetKeySerialized<E: Env>(env: E, m: &SignalMessage) -> E::Buffer {
     env.buffer(m.sender_ratchet_key().serialize().into_vec())
 }
 
@@ -262,10 +305,12 @@ fn SignalMessage_VerifyMac(
     )
 }
 
-#[bridge_fn(ffi = "message_get_sender_ratchet_key", jni = false, node = false)]
-fn Message_GetSenderRatchetKey(m: &SignalMessage) -> PublicKey {
-    *m.sender_ratchet_key()
-}
+bridge_get!(
+    SignalMessage::sender_ratchet_key -> PublicKey,
+    ffi = "message_get_sender_ratchet_key",
+    jni = false,
+    node = false
+);
 
 #[bridge_fn]
 fn PreKeySignalMessage_New(
@@ -288,20 +333,18 @@ fn PreKeySignalMessage_New(
     )
 }
 
-#[bridge_fn(jni = false, node = false)]
-fn PreKeySignalMessage_GetBaseKey(m: &PreKeySignalMessage) -> PublicKey {
-    *m.base_key()
-}
+bridge_get!(PreKeySignalMessage::base_key -> PublicKey, jni = false, node = false);
 
 #[bridge_fn(jni = false, node = false)]
 fn PreKeySignalMessage_GetIdentityKey(m: &PreKeySignalMessage) -> PublicKey {
     *m.identity_key().public_key()
 }
 
-#[bridge_fn(jni = false, node = false)]
-fn PreKeySignalMessage_GetSignalMessage(m: &PreKeySignalMessage) -> SignalMessage {
-    m.message().clone()
-}
+bridge_get!(
+    PreKeySignalMessage::message as GetSignalMessage -> SignalMessage,
+    jni = false,
+    node = false
+);
 
 bridge_deserialize!(PreKeySignalMessage::try_from);
 bridge_get_bytearray!(
@@ -309,12 +352,12 @@ bridge_get_bytearray!(
     jni = "PreKeySignalMessage_1GetSerialized"
 );
 
-#[bridge_fn_buffer(ffi = false, jni = "PreKeySignalMessage_1GetBaseKey", node = false)]
+#[bridge_fn(ffi = false, jni = "PreKeySignalMessage_1GetBaseKey", node = false)]
 fn PreKeySignalMessage_GetBaseKeySerialized<E: Env>(env: E, m: &PreKeySignalMessage) -> E::Buffer {
     env.buffer(m.base_key().serialize().into_vec())
 }
 
-#[bridge_fn_buffer(ffi = false, jni = "PreKeySignalMessage_1GetIdentityKey", node = false)]
+#[bridge_fn(ffi = false, jni = "PreKeySignalMessage_1GetIdentityKey", node = false)]
 fn PreKeySignalMessage_GetIdentityKeySerialized<E: Env>(
     env: E,
     m: &PreKeySignalMessage,
@@ -322,7 +365,7 @@ fn PreKeySignalMessage_GetIdentityKeySerialized<E: Env>(
     env.buffer(m.identity_key().serialize().into_vec())
 }
 
-#[bridge_fn_buffer(
+#[bridge_fn(
     ffi = false,
     jni = "PreKeySignalMessage_1GetSignalMessage",
     node = false
@@ -364,10 +407,23 @@ fn SenderKeyMessage_VerifySignature(skm: &SenderKeyMessage, pubkey: &PublicKey)
     skm.verify_signature(pubkey)
 }
 
+/// Returns the IV followed by the cipher key for the given iteration of a sender chain.
+#[bridge_fn]
+fn SenderKey_DeriveMessageKey<E: Env>(
+    env: E,
+    chain_key: &[u8],
+    iteration: u32,
+) -> Result<E::Buffer> {
+    let key = derive_sender_message_key(chain_key, iteration)?;
+    let mut result = key.iv()?;
+    result.extend_from_slice(&key.cipher_key()?);
+    Ok(env.buffer(result))
+}
+
 bridge_deserialize!(SenderKeyDistributionMessage::try_from);
 bridge_get_bytearray!(SenderKeyDistributionMessage::chain_key);
 
-#[bridge_fn_buffer(
+#[bridge_fn(
     ffi = false,
     jni = "SenderKeyDistributionMessage_1GetSignatureKey",
     node = false
@@ -396,12 +452,11 @@ fn SenderKeyDistributionMessage_New(
     SenderKeyDistributionMessage::new(key_id, iteration, &chainkey, *pk)
 }
 
-#[bridge_fn(jni = false, node = false)]
-fn SenderKeyDistributionMessage_GetSignatureKey(
-    m: &SenderKeyDistributionMessage,
-) -> Result<PublicKey> {
-    Ok(*m.signing_key()?)
-}
+bridge_get!(
+    SenderKeyDistributionMessage::signing_key as GetSignatureKey -> PublicKey,
+    jni = false,
+    node = false
+);
 
 #[bridge_fn]
 fn PreKeyBundle_New(
@@ -414,11 +469,15 @@ fn PreKeyBundle_New(
     signed_prekey_signature: &[u8],
     identity_key: &PublicKey,
 ) -> Result<PreKeyBundle> {
-    let identity_key = IdentityKey::new(*identity_key);
-
-    let prekey = match (prekey, prekey_id) {
-        (None, None) => None,
-        (Some(k), Some(id)) => Some((id, *k)),
+    let builder = PreKeyBundleBuilder::new()
+        .registration_id(registration_id)
+        .device_id(device_id)
+        .signed_pre_key(signed_prekey_id, *signed_prekey, signed_prekey_signature)
+        .identity_key(IdentityKey::new(*identity_key));
+
+    let builder = match (prekey, prekey_id) {
+        (None, None) => builder,
+        (Some(k), Some(id)) => builder.pre_key(id, *k),
         _ => {
             return Err(SignalProtocolError::InvalidArgument(
                 "Must supply both or neither of prekey and prekey_id".to_owned(),
@@ -426,15 +485,7 @@ fn PreKeyBundle_New(
         }
     };
 
-    PreKeyBundle::new(
-        registration_id,
-        device_id,
-        prekey,
-        signed_prekey_id,
-        *signed_prekey,
-        signed_prekey_signature.to_vec(),
-        identity_key,
-    )
+    builder.build()
 }
 
 #[bridge_fn]
@@ -469,7 +520,7 @@ fn SignedPreKeyRecord_New(
     priv_key: &PrivateKey,
     signature: &[u8],
 ) -> SignedPreKeyRecord {
-    let keypair = KeyPair::new(*pub_key, *priv_key);
+    let keypair = KeyPair::new(*pub_key, priv_key.clone());
     SignedPreKeyRecord::new(id, timestamp, &keypair, &signature)
 }
 
@@ -484,10 +535,20 @@ bridge_get!(PreKeyRecord::private_key -> PrivateKey);
 
 #[bridge_fn]
 fn PreKeyRecord_New(id: u32, pub_key: &PublicKey, priv_key: &PrivateKey) -> PreKeyRecord {
-    let keypair = KeyPair::new(*pub_key, *priv_key);
+    let keypair = KeyPair::new(*pub_key, priv_key.clone());
     PreKeyRecord::new(id, &keypair)
 }
 
+/// Generates `count` pre-keys with consecutive IDs starting at `start_id`.
+///
+/// The records are returned as handles rather than serialized, so their private keys stay in
+/// Rust memory.
+#[bridge_fn]
+fn PreKeyRecord_GenerateBatch(start_id: u32, count: u32) -> Result<Vec<PreKeyRecord>> {
+    let mut rng = rand::rngs::OsRng;
+    generate_pre_keys(start_id, count, &mut rng)
+}
+
 bridge_get!(SenderKeyName::group_id -> String);
 
 #[bridge_fn]
@@ -559,10 +620,7 @@ fn SenderCertificate_Validate(
     cert.validate(key, time)
 }
 
-#[bridge_fn]
-fn SenderCertificate_GetServerCertificate(cert: &SenderCertificate) -> Result<ServerCertificate> {
-    Ok(cert.signer()?.clone())
-}
+bridge_get!(SenderCertificate::signer as GetServerCertificate -> ServerCertificate);
 
 #[bridge_fn]
 fn SenderCertificate_New(
@@ -595,12 +653,7 @@ bridge_get_bytearray!(
 );
 bridge_get_bytearray!(UnidentifiedSenderMessageContent::contents);
 
-#[bridge_fn]
-fn UnidentifiedSenderMessageContent_GetSenderCert(
-    m: &UnidentifiedSenderMessageContent,
-) -> Result<SenderCertificate> {
-    Ok(m.sender()?.clone())
-}
+bridge_get!(UnidentifiedSenderMessageContent::sender as GetSenderCert -> SenderCertificate);
 
 #[bridge_fn]
 fn UnidentifiedSenderMessageContent_GetMsgType(m: &UnidentifiedSenderMessageContent) -> Result<u8> {
@@ -694,8 +747,6 @@ fn CiphertextMessage_Type(msg: &CiphertextMessage) -> u8 {
     msg.message_type() as u8
 }
 
-bridge_get_bytearray!(CiphertextMessage::serialize as Serialize, jni = false);
-
 #[bridge_fn(ffi = false, node = false)]
 fn SessionRecord_NewFresh() -> SessionRecord {
     SessionRecord::new_fresh()
@@ -716,15 +767,25 @@ fn SessionRecord_GetSessionVersion(s: &SessionRecord) -> Result<u32> {
     }
 }
 
-#[bridge_fn_void]
+#[bridge_fn]
 fn SessionRecord_ArchiveCurrentState(session_record: &mut SessionRecord) -> Result<()> {
     session_record.archive_current_state()
 }
 
-bridge_get!(SessionRecord::has_current_session_state as HasCurrentState -> bool, jni = false, node = false);
+#[bridge_fn(ffi = false, node = false)]
+fn SessionRecord_SetMaxSkippedMessageKeys(
+    session_record: &mut SessionRecord,
+    limit: u32,
+) -> Result<()> {
+    session_record.set_max_skipped_message_keys(limit)
+}
+
+#[bridge_fn(jni = false)]
+fn SessionRecord_HasCurrentState(obj: &SessionRecord) -> bool {
+    obj.has_current_session_state()
+}
 
 bridge_deserialize!(SessionRecord::deserialize);
-bridge_get_bytearray!(SessionRecord::serialize as Serialize);
 bridge_get_bytearray!(SessionRecord::alice_base_key, ffi = false, node = false);
 bridge_get_bytearray!(
     SessionRecord::local_identity_key_bytes as GetLocalIdentityKeyPublic,
@@ -732,9 +793,7 @@ bridge_get_bytearray!(
     node = false
 );
 bridge_get_optional_bytearray!(
-    SessionRecord::remote_identity_key_bytes as GetRemoteIdentityKeyPublic,
-    ffi = false,
-    node = false
+    SessionRecord::remote_identity_key_bytes as GetRemoteIdentityKeyPublic
 );
 bridge_get!(SessionRecord::local_registration_id -> u32);
 bridge_get!(SessionRecord::remote_registration_id -> u32);
@@ -756,7 +815,7 @@ bridge_get_bytearray!(
     ffi = false,
     node = false
 );
-#[bridge_fn_buffer(ffi = false, node = false)]
+#[bridge_fn(ffi = false, node = false)]
 fn SessionRecord_GetReceiverChainKeyValue<E: Env>(
     env: E,
     session_state: &SessionRecord,
@@ -778,10 +837,10 @@ fn SessionRecord_InitializeAliceSession(
 ) -> Result<SessionRecord> {
     let our_identity_key_pair = IdentityKeyPair::new(
         IdentityKey::new(*identity_key_public),
-        *identity_key_private,
+        identity_key_private.clone(),
     );
 
-    let our_base_key_pair = KeyPair::new(*base_public, *base_private);
+    let our_base_key_pair = KeyPair::new(*base_public, base_private.clone());
 
     let their_identity_key = IdentityKey::new(*their_identity_key);
 
@@ -812,12 +871,13 @@ fn SessionRecord_InitializeBobSession(
 ) -> Result<SessionRecord> {
     let our_identity_key_pair = IdentityKeyPair::new(
         IdentityKey::new(*identity_key_public),
-        *identity_key_private,
+        identity_key_private.clone(),
     );
 
-    let our_signed_pre_key_pair = KeyPair::new(*signed_prekey_public, *signed_prekey_private);
+    let our_signed_pre_key_pair =
+        KeyPair::new(*signed_prekey_public, signed_prekey_private.clone());
 
-    let our_ratchet_key_pair = KeyPair::new(*eph_public, *eph_private);
+    let our_ratchet_key_pair = KeyPair::new(*eph_public, eph_private.clone());
 
     let their_identity_key = IdentityKey::new(*their_identity_key);
 
@@ -835,7 +895,7 @@ fn SessionRecord_InitializeBobSession(
 
 // End SessionRecord testing functions
 
-#[bridge_fn_void(ffi = "process_prekey_bundle", jni = false, node = false)]
+#[bridge_fn(ffi = "process_prekey_bundle", jni = false, node = false)]
 async fn ProcessPreKeyBundle(
     bundle: &PreKeyBundle,
     protocol_address: &ProtocolAddress,
@@ -855,7 +915,7 @@ async fn ProcessPreKeyBundle(
     .await
 }
 
-#[bridge_fn_void(ffi = false)]
+#[bridge_fn(ffi = false)]
 async fn SessionBuilder_ProcessPreKeyBundle(
     bundle: &PreKeyBundle,
     protocol_address: &ProtocolAddress,
@@ -909,7 +969,7 @@ async fn SessionCipher_EncryptMessage(
     .await
 }
 
-#[bridge_fn_buffer(jni = false, node = false)]
+#[bridge_fn(jni = false, node = false)]
 async fn DecryptMessage<E: Env>(
     env: E,
     message: &SignalMessage,
@@ -931,7 +991,7 @@ async fn DecryptMessage<E: Env>(
     Ok(env.buffer(ptext))
 }
 
-#[bridge_fn_buffer(ffi = false)]
+#[bridge_fn(ffi = false)]
 async fn SessionCipher_DecryptSignalMessage<E: Env>(
     env: E,
     message: &SignalMessage,
@@ -952,7 +1012,7 @@ async fn SessionCipher_DecryptSignalMessage<E: Env>(
     Ok(env.buffer(ptext))
 }
 
-#[bridge_fn_buffer(jni = false, node = false)]
+#[bridge_fn(jni = false, node = false)]
 async fn DecryptPreKeyMessage<E: Env>(
     env: E,
     message: &PreKeySignalMessage,
@@ -978,7 +1038,7 @@ async fn DecryptPreKeyMessage<E: Env>(
     Ok(env.buffer(ptext))
 }
 
-#[bridge_fn_buffer(ffi = false)]
+#[bridge_fn(ffi = false)]
 async fn SessionCipher_DecryptPreKeySignalMessage<E: Env>(
     env: E,
     message: &PreKeySignalMessage,
@@ -1003,7 +1063,7 @@ async fn SessionCipher_DecryptPreKeySignalMessage<E: Env>(
 
     Ok(env.buffer(ptext))
 }
-#[bridge_fn_buffer(jni = false, node = false)]
+#[bridge_fn(jni = false, node = false)]
 async fn SealedSessionCipherEncrypt<E: Env>(
     env: E,
     destination: &ProtocolAddress,
@@ -1027,7 +1087,7 @@ async fn SealedSessionCipherEncrypt<E: Env>(
     Ok(env.buffer(ctext))
 }
 
-#[bridge_fn_buffer(ffi = false, node = "SealedSender_EncryptMessage")]
+#[bridge_fn(ffi = false, node = "SealedSender_EncryptMessage")]
 async fn SealedSessionCipher_Encrypt<E: Env>(
     env: E,
     destination: &ProtocolAddress,
@@ -1051,6 +1111,53 @@ async fn SealedSessionCipher_Encrypt<E: Env>(
     Ok(env.buffer(ctext))
 }
 
+#[bridge_fn(
+    ffi = "sealed_sender_multi_recipient_encrypt",
+    jni = false,
+    node = false
+)]
+async fn SealedSenderMultiRecipientEncrypt<E: Env>(
+    env: E,
+    recipients: &[&ProtocolAddress],
+    recipient_sessions: &[&SessionRecord],
+    content: &UnidentifiedSenderMessageContent,
+    identity_key_store: &mut dyn IdentityKeyStore,
+    ctx: Context,
+) -> Result<E::Buffer> {
+    let mut rng = rand::rngs::OsRng;
+    let ctext = sealed_sender_multi_recipient_encrypt(
+        recipients,
+        recipient_sessions,
+        content,
+        identity_key_store,
+        ctx,
+        &mut rng,
+    )
+    .await?;
+    Ok(env.buffer(ctext))
+}
+
+#[bridge_fn(ffi = false, wasm = false)]
+async fn SealedSender_MultiRecipientEncrypt<E: Env>(
+    env: E,
+    recipients: &[&ProtocolAddress],
+    recipient_sessions: &[&SessionRecord],
+    content: &UnidentifiedSenderMessageContent,
+    identity_store: &mut dyn IdentityKeyStore,
+) -> Result<E::Buffer> {
+    let mut rng = rand::rngs::OsRng;
+    let ctext = sealed_sender_multi_recipient_encrypt(
+        recipients,
+        recipient_sessions,
+        content,
+        identity_store,
+        None,
+        &mut rng,
+    )
+    .await?;
+    Ok(env.buffer(ctext))
+}
+
 #[bridge_fn(jni = false, node = false)]
 async fn SealedSessionCipherDecryptToUsmc(
     ctext: &[u8],
@@ -1124,7 +1231,7 @@ async fn SenderKeyDistributionMessage_Create(
     create_sender_key_distribution_message(sender_key_name, store, &mut csprng, None).await
 }
 
-#[bridge_fn_void(
+#[bridge_fn(
     ffi = "process_sender_key_distribution_message",
     jni = false,
     node = false
@@ -1144,7 +1251,7 @@ async fn ProcessSenderKeyDistributionMessage(
     .await
 }
 
-#[bridge_fn_void(
+#[bridge_fn(
     ffi = false,
     jni = "GroupSessionBuilder_1ProcessSenderKeyDistributionMessage"
 )]
@@ -1162,7 +1269,7 @@ async fn SenderKeyDistributionMessage_Process(
     .await
 }
 
-#[bridge_fn_buffer(ffi = "group_encrypt_message", jni = false, node = false)]
+#[bridge_fn(ffi = "group_encrypt_message", jni = false, node = false)]
 async fn GroupEncryptMessage<E: Env>(
     env: E,
     sender_key_name: &SenderKeyName,
@@ -1175,7 +1282,7 @@ async fn GroupEncryptMessage<E: Env>(
     Ok(env.buffer(ctext))
 }
 
-#[bridge_fn_buffer(ffi = false, jni = "GroupCipher_1EncryptMessage")]
+#[bridge_fn(ffi = false, jni = "GroupCipher_1EncryptMessage")]
 async fn GroupCipher_Encrypt<E: Env>(
     env: E,
     sender_key_name: &SenderKeyName,
@@ -1187,7 +1294,7 @@ async fn GroupCipher_Encrypt<E: Env>(
     Ok(env.buffer(ctext))
 }
 
-#[bridge_fn_buffer(ffi = "group_decrypt_message", jni = false, node = false)]
+#[bridge_fn(ffi = "group_decrypt_message", jni = false, node = false)]
 async fn GroupDecryptMessage<E: Env>(
     env: E,
     sender_key_name: &SenderKeyName,
@@ -1199,7 +1306,7 @@ async fn GroupDecryptMessage<E: Env>(
     Ok(env.buffer(ptext))
 }
 
-#[bridge_fn_buffer(ffi = false, jni = "GroupCipher_1DecryptMessage")]
+#[bridge_fn(ffi = false, jni = "GroupCipher_1DecryptMessage")]
 async fn GroupCipher_Decrypt<E: Env>(
     env: E,
     sender_key_name: &SenderKeyName,
@@ -1209,3 +1316,78 @@ async fn GroupCipher_Decrypt<E: Env>(
     let ptext = group_decrypt(message, store, sender_key_name, None).await?;
     Ok(env.buffer(ptext))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The public key for the private key `[0x42; 32]`.
+    const PUBLIC_KEY: &str = "05132c442be010fbd57e72603328aa76e71fccc1503aae219327d14d9c9993f472";
+    /// Signatures of "hello" and "" with the private key `[0x42; 32]`.
+    const SIGNATURES: &str = concat!(
+        "a9ba224caf0fb8281616fb0a299eaac57a8d922fca7088fcffaf23a0ffc44163",
+        "808c9a5db9c345e0d19531428dd772cd2cee4b48164fe2b9563507519378160a",
+        "328b5eb3962611729de7df7cdad3bce962eda085ef99ac0d29dd84c527eaa948",
+        "a4cfc2230f194caaddd4dd492584c94353d2758013c197fe76163ef35fcea905",
+    );
+    /// "hello" and "", each prefixed with its length.
+    const MESSAGES: &str = concat!("00000005", "68656c6c6f", "00000000");
+
+    fn verify_batch(messages: &str, signatures: &str) -> Result<bool> {
+        let key = PublicKey::deserialize(&hex::decode(PUBLIC_KEY).expect("valid hex"))?;
+        ECPublicKey_VerifyBatch(
+            &key,
+            &hex::decode(messages).expect("valid hex"),
+            &hex::decode(signatures).expect("valid hex"),
+        )
+    }
+
+    fn assert_invalid_argument(result: Result<bool>, expected: &str) {
+        match result {
+            Err(SignalProtocolError::InvalidArgument(message)) => assert_eq!(message, expected),
+            other => panic!("expected InvalidArgument({:?}), got {:?}", expected, other),
+        }
+    }
+
+    #[test]
+    fn test_verify_batch_known_answers() {
+        assert!(verify_batch(MESSAGES, SIGNATURES).expect("valid"));
+        assert!(verify_batch("", "").expect("valid"));
+
+        // Swapping the signatures breaks both.
+        let (hello, empty) = SIGNATURES.split_at(128);
+        assert!(!verify_batch(MESSAGES, &format!("{}{}", empty, hello)).expect("valid"));
+        // So does changing a message without changing its length.
+        let jello = MESSAGES.replace("6865", "6a65");
+        assert!(!verify_batch(&jello, SIGNATURES).expect("valid"));
+    }
+
+    #[test]
+    fn test_verify_batch_malformed_lengths() {
+        // A partial signature.
+        assert_invalid_argument(
+            verify_batch(MESSAGES, &SIGNATURES[..SIGNATURES.len() - 2]),
+            "signatures must be 64 bytes each",
+        );
+        // A partial length prefix.
+        assert_invalid_argument(
+            verify_batch("000000", &SIGNATURES[..128]),
+            "truncated message",
+        );
+        // A length prefix longer than the rest of the buffer.
+        assert_invalid_argument(
+            verify_batch("0000000668656c6c6f", &SIGNATURES[..128]),
+            "truncated message",
+        );
+        // Fewer messages than signatures.
+        assert_invalid_argument(
+            verify_batch("0000000568656c6c6f", SIGNATURES),
+            "truncated message",
+        );
+        // More messages than signatures.
+        assert_invalid_argument(
+            verify_batch(MESSAGES, &SIGNATURES[..128]),
+            "more messages than signatures",
+        );
+    }
+}
@@ -5,7 +5,9 @@
 
 use futures::pin_mut;
 use futures::task::noop_waker_ref;
+use libsignal_protocol::SignalProtocolError;
 use std::borrow::Cow;
+use std::fmt;
 use std::future::Future;
 use std::task::{self, Poll};
 
@@ -25,9 +27,105 @@ pub fn expect_ready<F: Future>(future: F) -> F::Output {
     }
 }
 
+/// Extracts the message from a panic payload, if it has one.
+///
+/// `panic!` payloads are either `&'static str` or `String`, depending on whether the panic message
+/// had any formatting arguments. See https://github.com/rust-lang/rfcs/issues/1389.
+#[allow(dead_code)] // not used in FFI-only builds
+pub fn describe_panic(any: &(dyn std::any::Any + std::marker::Send)) -> String {
+    if let Some(msg) = any.downcast_ref::<&str>() {
+        msg.to_string()
+    } else if let Some(msg) = any.downcast_ref::<String>() {
+        msg.to_string()
+    } else {
+        "(break on rust_panic to debug)".to_string()
+    }
+}
+
+/// Displays an error followed by each of its [sources](std::error::Error::source), separated by
+/// colons.
+///
+/// The other languages only see a single message, so the bridges use this instead of the error's
+/// own `Display`, which leaves out the underlying cause.
+pub(crate) struct ErrorChain<'a>(pub &'a dyn std::error::Error);
+
+impl fmt::Display for ErrorChain<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)?;
+        let mut source = self.0.source();
+        while let Some(error) = source {
+            write!(f, ": {}", error)?;
+            source = error.source();
+        }
+        Ok(())
+    }
+}
+
+/// A UUID passed across the bridges as 16 raw bytes, in RFC 4122 (big-endian) order.
+///
+/// Each bridge checks the length of the incoming bytes before producing a `Uuid`, so `bridge_fn`s
+/// that take IDs don't have to validate a `&[u8]` themselves.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Uuid(uuid::Uuid);
+
+impl Uuid {
+    pub fn from_bytes(bytes: [u8; 16]) -> Self {
+        Self(uuid::Uuid::from_bytes(bytes))
+    }
+
+    /// Fails with [`SignalProtocolError::InvalidArgument`] unless `bytes` is exactly 16 bytes long.
+    pub fn from_slice(bytes: &[u8]) -> Result<Self, SignalProtocolError> {
+        let bytes: [u8; 16] = std::convert::TryInto::try_into(bytes).map_err(|_| {
+            SignalProtocolError::InvalidArgument(format!(
+                "expected a 16-byte UUID, got {} bytes",
+                bytes.len()
+            ))
+        })?;
+        Ok(Self::from_bytes(bytes))
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 16] {
+        self.0.as_bytes()
+    }
+}
+
+impl From<uuid::Uuid> for Uuid {
+    fn from(uuid: uuid::Uuid) -> Self {
+        Self(uuid)
+    }
+}
+
+impl From<Uuid> for uuid::Uuid {
+    fn from(uuid: Uuid) -> Self {
+        uuid.0
+    }
+}
+
+#[test]
+fn test_uuid_from_slice() {
+    let bytes: Vec<u8> = (0..17).collect();
+    let uuid = Uuid::from_slice(&bytes[..16]).expect("valid");
+    assert_eq!(
+        uuid::Uuid::from(uuid).to_string(),
+        "00010203-0405-0607-0809-0a0b0c0d0e0f"
+    );
+
+    for &len in &[0, 15, 17] {
+        match Uuid::from_slice(&bytes[..len]) {
+            Err(SignalProtocolError::InvalidArgument(message)) => {
+                assert_eq!(
+                    message,
+                    format!("expected a 16-byte UUID, got {} bytes", len)
+                );
+            }
+            other => panic!("unexpected result for {} bytes: {:?}", len, other),
+        }
+    }
+}
+
 /// Used for returning newly-allocated buffers as efficiently as possible.
 ///
-/// Functions marked `#[bridge_fn_buffer]` must have an `Env` as their first parameter.
+/// Any `bridge_fn` can take an `Env` as its first parameter; each bridge supplies its own.
 pub(crate) trait Env {
     type Buffer;
     fn buffer<'a, T: Into<Cow<'a, [u8]>>>(self, input: T) -> Self::Buffer;
@@ -37,44 +135,62 @@ pub(crate) trait Env {
 ///
 /// Full form:
 ///
-/// ```no_run
+/// ```ignore
 /// # #[macro_use] extern crate libsignal_bridge;
 /// # struct Foo;
-/// bridge_handle!(Foo, clone = true, mut = true, ffi = "foo", jni = "Foo", node = "Foo");
+/// # impl Foo {
+/// #     fn serialize(&self) -> Vec<u8> {
+/// #         vec![]
+/// #     }
+/// # }
+/// bridge_handle!(
+///     Foo, clone = true, mut = true, serialize = true, ffi = foo, jni = Foo, node = Foo
+/// );
 /// ```
 ///
 /// This has several effects for a type `Foo`:
 ///
 /// - `Foo` and `Option<Foo>` become valid result types for `bridge_fn`s (conforming to the
-///   `ResultTypeInfo` traits for all three bridges).
+///   `ResultTypeInfo` traits for every bridge).
 ///
 /// - `&Foo` and `Option<&Foo>` become valid argument types (conforming to the `ArgTypeInfo` traits
-///   for all three bridges).
+///   for every bridge).
 ///
-/// - If `mut = true` is passed to `bridge_handle`, `&mut Foo` becomes a valid argument type for all
-///   three bridges as well. This may include extra overhead to check Rust's exclusive borrow rules,
+/// - If `mut = true` is passed to `bridge_handle`, `&mut Foo` becomes a valid argument type for
+///   every bridge as well. This may include extra overhead to check Rust's exclusive borrow rules,
 ///   even for immutable accesses.
 ///
 /// - If `mut = true` is *not* passed to `bridge_handle`, `&Foo` and `Option<&Foo>` become valid
 ///   argument types for async functions as well (conforming to [`node::AsyncArgTypeInfo`]).
 ///   (Note that you can't write `mut = false` because I was lazy with the macros.)
 ///
-/// - "Destroy" functions are generated for FFI and JNI based on the name of the type:
-///   `signal_foo_destroy` and `Native.Foo_Destroy`.
+/// - `&[&Foo]` becomes a valid argument type for synchronous functions on every bridge, and for
+///   async functions on Node (see [Representation](#representation)). Empty arrays are allowed; a
+///   missing or invalid element is reported as an error that names its index.
+///
+/// - "Destroy" functions are generated for FFI, JNI, and WASM based on the name of the type:
+///   `signal_foo_destroy`, `Native.Foo_Destroy`, and `Foo_Destroy`.
 ///
 /// - If `clone = true` is passed to `bridge_handle`, a `signal_foo_clone` function will be
 ///   generated for the FFI bridge as well. `Foo` must adopt `Clone`.
 ///
+/// - If `serialize = true` is passed to `bridge_handle`, a "serialize" function will be generated
+///   for each bridge as well, using the same naming: `signal_foo_serialize`,
+///   `Native.Foo_Serialize`, and `Foo_Serialize`. `Foo` must have a `serialize(&self)` method
+///   returning bytes, as described for [`bridge_get_bytearray`].
+///
 /// # Representation
 ///
 /// Each bridge represents a boxed Rust value differently:
 ///
 /// - FFI: boxed values are opaque structs with manual memory management (`SignalFoo *`).
 ///   Note that the pointer may not refer directly to a `Foo` on the Rust side; that's an
-///   implementation detail. (For example, it could point to a type tag.)
+///   implementation detail. (For example, it could point to a type tag.) Arrays of handles are
+///   passed as a `const SignalFoo *const *` and a length.
 ///
 /// - JNI: boxed values are bare `long` values with manual memory management. (The Java code on the
-///   other side of the bridge is expected to wrap these in strong class types.)
+///   other side of the bridge is expected to wrap these in strong class types.) Arrays of handles
+///   are passed as a `long[]`.
 ///
 /// - Node: boxed values use Neon's [`JsBox`][] type, but this is only used for return values.
 ///   Arguments are always in the form of a JavaScript object with a `_nativeHandle` property that
@@ -82,17 +198,32 @@ pub(crate) trait Env {
 ///   that's an implementation detail.
 ///
 ///   For TypeScript's benefit, each boxed type gets its own unique `interface Foo`, and the
-///   arguments are of the form `Wrapper<Foo>`.
+///   arguments are of the form `Wrapper<Foo>` (or `Wrapper<Foo>[]` for arrays).
+///
+/// - WASM: boxed values are numeric handles with manual memory management, like JNI's, released
+///   with `Foo_Destroy`. A handle of the wrong type is rejected with a `TypeError`. Arrays of
+///   handles are passed as JavaScript arrays. WASM uses the Node names throughout, so `node = false`
+///   disables the WASM functions as well.
 ///
 /// [`JsBox`]: https://docs.rs/neon/0.7.1-napi/neon/types/struct.JsBox.html
 macro_rules! bridge_handle {
-    ($typ:ty $(, clone = $_clone:tt)? $(, mut = $_mut:tt)? $(, ffi = $ffi_name:ident)? $(, jni = $jni_name:ident)? $(, node = $node_name:ident)?) => {
+    ($typ:ty $(, clone = $_clone:tt)? $(, mut = $_mut:tt)? $(, serialize = $_serialize:tt)? $(, ffi = $ffi_name:ident)? $(, jni = $jni_name:ident)? $(, node = $node_name:ident)?) => {
         #[cfg(feature = "ffi")]
         ffi_bridge_handle!($typ $(as $ffi_name)? $(, clone = $_clone)?);
+        #[cfg(feature = "ffi")]
+        ffi_bridge_serialize!($typ $(as $ffi_name)? $(, serialize = $_serialize)?);
         #[cfg(feature = "jni")]
         jni_bridge_handle!($typ $(as $jni_name)?);
+        #[cfg(feature = "jni")]
+        jni_bridge_serialize!($typ $(as $jni_name)? $(, serialize = $_serialize)?);
         #[cfg(feature = "node")]
         node_bridge_handle!($typ $(as $node_name)? $(, mut = $_mut)?);
+        #[cfg(feature = "node")]
+        node_bridge_serialize!($typ $(as $node_name)? $(, serialize = $_serialize)?);
+        #[cfg(feature = "wasm")]
+        wasm_bridge_handle!($typ $(as $node_name)? $(, mut = $_mut)?);
+        #[cfg(feature = "wasm")]
+        wasm_bridge_serialize!($typ $(as $node_name)? $(, serialize = $_serialize)?);
     };
 }
 
@@ -100,7 +231,7 @@ macro_rules! bridge_handle {
 ///
 /// Example:
 ///
-/// ```no_run
+/// ```ignore
 /// # #[macro_use] extern crate libsignal_bridge_macros;
 /// # struct Foo;
 /// # impl Foo {
@@ -115,8 +246,9 @@ macro_rules! bridge_handle {
 /// The underlying method is expected to take a single `&[u8]` parameter and return
 /// `Result<Self, _>`.
 ///
-/// The `ffi`, `jni`, and `node` parameters control the name of the **type**; the resulting function
-/// will always be suffixed with `_Deserialize` or `_deserialize` as appropriate. Unlike
+/// The `ffi`, `jni`, and `node` parameters control the name of the **type** (WASM uses the `node`
+/// name); the resulting function will always be suffixed with `_Deserialize` or `_deserialize` as
+/// appropriate. Unlike
 /// `bridge_fn`, these parameters are identifiers, not string literals, and there is no way to
 /// disable a particular bridge.
 macro_rules! bridge_deserialize {
@@ -127,6 +259,8 @@ macro_rules! bridge_deserialize {
         jni_bridge_deserialize!($typ::$fn $(as $jni_name)?);
         #[cfg(feature = "node")]
         node_bridge_deserialize!($typ::$fn $(as $node_name)?);
+        #[cfg(feature = "wasm")]
+        wasm_bridge_deserialize!($typ::$fn $(as $node_name)?);
     }
 }
 
@@ -134,7 +268,7 @@ macro_rules! bridge_deserialize {
 ///
 /// Example:
 ///
-/// ```no_run
+/// ```ignore
 /// # #[macro_use] extern crate libsignal_bridge_macros;
 /// # struct Foo;
 /// # impl Foo {
@@ -154,7 +288,7 @@ macro_rules! bridge_deserialize {
 macro_rules! bridge_get_bytearray {
     ($typ:ident :: $method:ident as $name:ident $(, $param:ident = $val:tt)*) => {
         paste! {
-            #[bridge_fn_buffer($($param = $val),*)]
+            #[bridge_fn($($param = $val),*)]
             fn [<$typ _ $name>]<E: Env>(env: E, obj: &$typ) -> Result<E::Buffer> {
                 let result = TransformHelper($typ::$method(obj));
                 Ok(env.buffer(result.ok_if_needed()?.into_vec_if_needed().0))
@@ -172,7 +306,7 @@ macro_rules! bridge_get_bytearray {
 ///
 /// Example:
 ///
-/// ```no_run
+/// ```ignore
 /// # #[macro_use] extern crate libsignal_bridge_macros;
 /// # struct Foo;
 /// # impl Foo {
@@ -192,7 +326,7 @@ macro_rules! bridge_get_bytearray {
 macro_rules! bridge_get_optional_bytearray {
     ($typ:ident :: $method:ident as $name:ident $(, $param:ident = $val:tt)*) => {
         paste! {
-            #[bridge_fn_buffer($($param = $val),*)]
+            #[bridge_fn($($param = $val),*)]
             fn [<$typ _ $name>]<E: Env>(env: E, obj: &$typ) -> Result<Option<E::Buffer>> {
                 let result = $typ::$method(obj);
                 let result_without_errors = TransformHelper(result).ok_if_needed()?.0;
@@ -215,7 +349,7 @@ macro_rules! bridge_get_optional_bytearray {
 ///
 /// Full form:
 ///
-/// ```no_run
+/// ```ignore
 /// # #[macro_use] extern crate libsignal_bridge;
 /// # struct Foo;
 /// impl Foo {
@@ -233,7 +367,7 @@ macro_rules! bridge_get_optional_bytearray {
 ///
 /// Roughly equivalent to
 ///
-/// ```no_run
+/// ```ignore
 /// # #[macro_use] extern crate libsignal_bridge_macros;
 /// # struct Foo;
 /// # impl Foo {
@@ -249,14 +383,15 @@ macro_rules! bridge_get_optional_bytearray {
 /// ```
 ///
 /// Automatically handles converting from the underlying type (using `into()`) and wrapping in
-/// `Ok` if the underlying result is non-failable.
+/// `Ok` if the underlying result is non-failable. A getter that returns a reference, like
+/// `fn key(&self) -> &PublicKey`, is cloned to produce an owned result.
 macro_rules! bridge_get {
     ($typ:ident :: $method:ident as $name:ident -> $result:ty $(, $param:ident = $val:tt)* ) => {
         paste! {
             #[bridge_fn($($param = $val),*)]
             fn [<$typ _ $name>](obj: &$typ) -> Result<$result> {
                 let result = TransformHelper($typ::$method(obj));
-                Ok(result.ok_if_needed()?.option_map_into().into())
+                Ok(result.ok_if_needed()?.cloned_if_needed().option_map_into().into())
             }
         }
     };
@@ -46,6 +46,16 @@ impl<T> TransformHelper<Option<T>> {
     }
 }
 
+impl<'a, T: Clone> TransformHelper<&'a T> {
+    /// Transforms `TransformHelper<&T>` into a `TransformHelper<T>` by cloning the referenced
+    /// value, and leaves other TransformHelper values unchanged.
+    ///
+    /// References to unsized types like `&str` and `&[u8]` are left alone.
+    pub(crate) fn cloned_if_needed(self) -> TransformHelper<T> {
+        TransformHelper(self.0.clone())
+    }
+}
+
 impl<T> TransformHelper<Box<[T]>> {
     /// Transforms `TransformHelper<Box<[T]>>` into a `TransformHelper<Vec<T>>`
     /// and leaves other TransformHelper values unchanged.
@@ -54,6 +64,25 @@ impl<T> TransformHelper<Box<[T]>> {
     }
 }
 
+#[allow(dead_code)] // for bridge functions returning lists; none use TransformHelper yet
+impl<T, E> TransformHelper<Result<Vec<T>, E>> {
+    /// Transforms `TransformHelper<Result<Vec<T>, E>>` into a `Result<TransformHelper<Vec<T>>, E>`
+    /// and wraps other TransformHelper values in `Ok`.
+    ///
+    /// This is equivalent to [TransformHelper::ok_if_needed] followed by
+    /// [TransformHelper::into_vec_if_needed], without needing to get the order right.
+    pub(crate) fn into_result_vec_if_needed(self) -> Result<TransformHelper<Vec<T>>, E> {
+        self.0.map(TransformHelper)
+    }
+
+    /// Transforms `TransformHelper<Result<Vec<T>, E>>` into a `Result<TransformHelper<Vec<U>>, E>`
+    /// by converting each element, and wraps other TransformHelper values in `Ok`.
+    pub(crate) fn result_vec_map_into<U: From<T>>(self) -> Result<TransformHelper<Vec<U>>, E> {
+        self.0
+            .map(|values| TransformHelper(values.into_iter().map(U::from).collect()))
+    }
+}
+
 pub(crate) trait TransformHelperImpl: Sized {
     fn ok_if_needed(self) -> Result<Self, libsignal_protocol::SignalProtocolError> {
         Ok(self)
@@ -61,9 +90,20 @@ pub(crate) trait TransformHelperImpl: Sized {
     fn option_map_into(self) -> Self {
         self
     }
+    fn cloned_if_needed(self) -> Self {
+        self
+    }
     fn into_vec_if_needed(self) -> Self {
         self
     }
+    #[allow(dead_code)]
+    fn into_result_vec_if_needed(self) -> Result<Self, libsignal_protocol::SignalProtocolError> {
+        Ok(self)
+    }
+    #[allow(dead_code)]
+    fn result_vec_map_into(self) -> Result<Self, libsignal_protocol::SignalProtocolError> {
+        Ok(self)
+    }
 }
 impl<T> TransformHelperImpl for TransformHelper<T> {}
 
@@ -115,3 +155,73 @@ fn test_option_map_into() {
         Option::<u64>::None
     ));
 }
+
+#[test]
+fn test_cloned_if_needed() {
+    let value = String::from("abc");
+    assert!(matches!(
+        TransformHelper(&value).cloned_if_needed(),
+        TransformHelper(s) if s == "abc"
+    ));
+    assert!(matches!(
+        TransformHelper("abc").cloned_if_needed(),
+        TransformHelper("abc")
+    ));
+    assert!(matches!(
+        TransformHelper(1u32).cloned_if_needed(),
+        TransformHelper(1u32)
+    ));
+
+    assert!(matches!(
+        TransformHelper(Result::<&String, bool>::Ok(&value))
+            .ok_if_needed()
+            .map(|result| result.cloned_if_needed().into::<String>()),
+        Ok(s) if s == "abc"
+    ));
+}
+
+#[test]
+fn test_into_result_vec_if_needed() {
+    assert!(matches!(
+        TransformHelper(0).into_result_vec_if_needed(),
+        Ok(TransformHelper(0))
+    ));
+    assert!(matches!(
+        TransformHelper(Result::<Vec<i32>, bool>::Ok(vec![])).into_result_vec_if_needed(),
+        Ok(TransformHelper(v)) if v.is_empty()
+    ));
+    assert!(matches!(
+        TransformHelper(Result::<Vec<i32>, bool>::Ok(vec![1, 2])).into_result_vec_if_needed(),
+        Ok(TransformHelper(v)) if v == [1, 2]
+    ));
+    assert!(matches!(
+        TransformHelper(Result::<Vec<i32>, bool>::Err(false)).into_result_vec_if_needed(),
+        Err(false)
+    ));
+}
+
+#[test]
+fn test_result_vec_map_into() {
+    assert!(matches!(
+        TransformHelper(0u32).result_vec_map_into(),
+        Ok(TransformHelper(0u32))
+    ));
+    assert!(matches!(
+        TransformHelper(Result::<Vec<u32>, bool>::Ok(vec![])).result_vec_map_into::<u64>(),
+        Ok(TransformHelper(v)) if v.is_empty()
+    ));
+    assert!(matches!(
+        TransformHelper(Result::<Vec<u32>, bool>::Ok(vec![1, 2])).result_vec_map_into::<u64>(),
+        Ok(TransformHelper(v)) if v == [1u64, 2u64]
+    ));
+    assert!(matches!(
+        TransformHelper(Result::<Vec<u32>, bool>::Err(false)).result_vec_map_into::<u64>(),
+        Err(false)
+    ));
+
+    let boxed: Vec<Box<[u8]>> = vec![Box::new([1]), Box::new([])];
+    assert!(matches!(
+        TransformHelper(Result::<_, bool>::Ok(boxed)).result_vec_map_into::<Vec<u8>>(),
+        Ok(TransformHelper(v)) if v == [vec![1], vec![]]
+    ));
+}
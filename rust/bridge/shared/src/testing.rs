@@ -0,0 +1,244 @@
+//
+// Copyright 2021 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+//! Entry points that exist only to test the bridging layer itself.
+
+use libsignal_bridge_macros::*;
+use libsignal_protocol::error::Result;
+use libsignal_protocol::{ProtocolAddress, SessionStore};
+
+use crate::support::*;
+use crate::*;
+
+#[bridge_fn(node = false, jni_async = true, ffi_async = true)]
+async fn TESTING_FutureSuccess(input: u32) -> u32 {
+    input * 2
+}
+
+#[bridge_fn(node = false, jni_async = true, ffi_async = true)]
+async fn TESTING_FutureFailure(input: u32) -> Result<u32> {
+    Err(
+        libsignal_protocol::error::SignalProtocolError::InvalidArgument(format!(
+            "failed with input {}",
+            input
+        )),
+    )
+}
+
+/// Returns `input * 2` after `delay_ms` milliseconds, without completing the first time it's
+/// polled.
+///
+/// For JNI, this checks that the calling thread isn't blocked while the future is pending.
+#[bridge_fn(node = false, jni_async = true, ffi_async = true)]
+async fn TESTING_FuturePending(input: u32, delay_ms: u32) -> u32 {
+    let (sender, receiver) = futures::channel::oneshot::channel();
+    std::thread::spawn(move || {
+        std::thread::sleep(std::time::Duration::from_millis(delay_ms.into()));
+        let _ = sender.send(input * 2);
+    });
+    receiver
+        .await
+        .expect("sender is never dropped without sending")
+}
+
+/// Returns the ID of the thread that finishes the future, after `delay_ms` milliseconds.
+///
+/// For JNI, this checks that outstanding futures share the executor's threads.
+#[bridge_fn(ffi = false, node = false, jni_async = true)]
+async fn TESTING_FutureThreadId(delay_ms: u32) -> String {
+    TESTING_FuturePending(0, delay_ms).await;
+    format!("{:?}", std::thread::current().id())
+}
+
+#[bridge_fn]
+fn TESTING_ReturnBufferList(count: u32) -> Result<Vec<Box<[u8]>>> {
+    Ok((0..count)
+        .map(|i| vec![i as u8; (i % 4) as usize].into_boxed_slice())
+        .collect())
+}
+
+/// Returns `len` copies of the byte `len`, or `None` if `len` is `None`.
+///
+/// Used to check that each bridge distinguishes a missing buffer from an empty one.
+#[bridge_fn]
+fn TESTING_ReturnOptionalBuffer(len: Option<u32>) -> Result<Option<Vec<u8>>> {
+    Ok(len.map(|len| vec![len as u8; len as usize]))
+}
+
+/// Returns `value` three ways at once, to check that each bridge keeps tuple elements in order.
+#[bridge_fn(node_result_fields = "buffer, value, isEven")]
+fn TESTING_ReturnTuple(value: u32) -> Result<(Vec<u8>, u32, bool)> {
+    Ok((value.to_be_bytes().to_vec(), value, value % 2 == 0))
+}
+
+/// Returns `value` unchanged, to check that each binding marks deprecated entry points.
+#[bridge_fn]
+#[deprecated(note = "only for testing")]
+fn TESTING_DeprecatedEcho(value: u32) -> u32 {
+    value
+}
+
+/// Returns whether `value` is even, to check how each bridge returns a plain `bool`.
+#[bridge_fn]
+fn TESTING_IsEven(value: u32) -> bool {
+    value % 2 == 0
+}
+
+/// Converts 0 or 1 to a `bool`, to check how each bridge returns a `Result<bool>`.
+#[bridge_fn]
+fn TESTING_BoolFromInt(value: u32) -> Result<bool> {
+    match value {
+        0 => Ok(false),
+        1 => Ok(true),
+        _ => Err(
+            libsignal_protocol::error::SignalProtocolError::InvalidArgument(format!(
+                "expected 0 or 1, got {}",
+                value
+            )),
+        ),
+    }
+}
+
+/// Returns whether `store` has a session for `address`, to check that each bridge passes stores
+/// through (and reports their callbacks' failures) without any glue of its own.
+#[bridge_fn]
+async fn TESTING_SessionStoreHasSession(
+    address: &ProtocolAddress,
+    store: &mut dyn SessionStore,
+) -> Result<bool> {
+    Ok(store.load_session(address, None).await?.is_some())
+}
+
+/// Loads the session for `address` from `store`, then panics with `message`, to check that the
+/// Node bridge rejects the promise (and still releases its arguments) instead of crashing.
+#[bridge_fn(ffi = false, jni = false)]
+async fn TESTING_PanicInFuture(
+    address: &ProtocolAddress,
+    store: &mut dyn SessionStore,
+    message: String,
+) -> Result<()> {
+    store.load_session(address, None).await?;
+    panic!("{}", message)
+}
+
+/// Loads the session for `address` from `store`, then returns the sum of the bytes in `data`, to
+/// check that the Node bridge copies byte arguments, which JavaScript code could otherwise detach
+/// while the store is being called.
+#[bridge_fn(ffi = false, jni = false)]
+async fn TESTING_SumBytesAfterLoadingSession(
+    address: &ProtocolAddress,
+    store: &mut dyn SessionStore,
+    data: &[u8],
+) -> Result<u32> {
+    store.load_session(address, None).await?;
+    Ok(data.iter().map(|&b| u32::from(b)).sum())
+}
+
+/// Returns how many arguments saved by Node async functions haven't been released yet, to check
+/// that calls interrupted by shutdown don't hold on to them.
+#[cfg(feature = "node")]
+#[bridge_fn(ffi = false, jni = false, wasm = false)]
+fn TESTING_LiveSavedArgumentCount() -> u32 {
+    crate::node::live_saved_argument_count() as u32
+}
+
+/// Returns the length of `text` in UTF-8, to check how each bridge converts strings.
+#[bridge_fn]
+fn TESTING_CountUtf8Bytes(text: &str) -> u32 {
+    text.len() as u32
+}
+
+/// Returns `text` unchanged, to check that owned and optional strings survive the round trip.
+#[bridge_fn]
+fn TESTING_ReturnOptionalString(text: Option<String>) -> Option<String> {
+    text
+}
+
+/// Decodes `bytes` as UTF-8, to check how each bridge returns strings (including ones that
+/// contain NUL characters).
+#[bridge_fn]
+fn TESTING_StringFromUtf8(bytes: &[u8]) -> Result<String> {
+    String::from_utf8(bytes.to_vec()).map_err(|_| {
+        libsignal_protocol::error::SignalProtocolError::InvalidArgument("invalid UTF-8".to_string())
+    })
+}
+
+/// Decodes a big-endian `u64`, to check how each bridge returns large integers.
+#[bridge_fn]
+fn TESTING_U64FromBytes(bytes: &[u8]) -> Result<u64> {
+    let bytes: [u8; 8] = std::convert::TryInto::try_into(bytes).map_err(|_| {
+        libsignal_protocol::error::SignalProtocolError::InvalidArgument(
+            "expected 8 bytes".to_string(),
+        )
+    })?;
+    Ok(u64::from_be_bytes(bytes))
+}
+
+/// Encodes `value` as a big-endian `u64`, to check how each bridge accepts large integers.
+#[bridge_fn]
+fn TESTING_U64ToBytes(value: u64) -> Vec<u8> {
+    value.to_be_bytes().to_vec()
+}
+
+/// Formats `uuid` in its usual hyphenated form, to check how each bridge accepts UUIDs.
+#[bridge_fn]
+fn TESTING_UuidToString(uuid: Uuid) -> String {
+    uuid::Uuid::from(uuid).to_string()
+}
+
+/// Returns the sum of the bytes in `data` and `more`, to check that long arrays survive being
+/// pinned by the JNI bridge and borrowed in place by the Node bridge.
+#[bridge_fn(ffi = false)]
+fn TESTING_SumBytes(data: &[u8], more: Option<&[u8]>) -> u32 {
+    data.iter()
+        .chain(more.unwrap_or_default())
+        .map(|&b| u32::from(b))
+        .sum()
+}
+
+/// Returns the first byte of `data`, without looking at the rest, so that calls with large
+/// buffers measure only the cost of passing the argument.
+#[bridge_fn(ffi = false, jni = false)]
+fn TESTING_FirstByte(data: &[u8]) -> Option<u8> {
+    data.first().copied()
+}
+
+/// Returns the result of calling `callback` on `value`, to check that function pointers can be
+/// passed in from C.
+#[bridge_fn(jni = false, node = false)]
+fn TESTING_InvokeCallback(callback: unsafe extern "C" fn(u32) -> u32, value: u32) -> u32 {
+    unsafe { callback(value) }
+}
+
+/// Panics with `message`, which should show up in the error surfaced to the caller.
+#[bridge_fn(ffi = false, node = false)]
+fn TESTING_PanicWithMessage(message: String) {
+    panic!("{}", message)
+}
+
+/// Panics with `message` on a new thread, which is not attached to the JVM, and waits for it.
+#[bridge_fn(ffi = false, node = false)]
+fn TESTING_PanicOnNewThread(message: String) {
+    std::thread::spawn(move || panic!("{}", message))
+        .join()
+        .expect_err("thread panicked");
+}
+
+/// Lists every entry point generated for the bridges, one per line as "platform name arity" (see
+/// [`describe_all`](crate::describe::describe_all)), so that each language's tests can check
+/// their declarations against it.
+///
+/// Empty unless the bridges were built with the "describe" feature.
+#[bridge_fn]
+fn TESTING_DescribeBridgedFunctions() -> String {
+    #[cfg(feature = "describe")]
+    let lines: Vec<String> = crate::describe_all()
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+    #[cfg(not(feature = "describe"))]
+    let lines: Vec<String> = vec![];
+    lines.join("\n")
+}
@@ -0,0 +1,752 @@
+//
+// Copyright 2021 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+use paste::paste;
+use std::any::{Any, TypeId};
+use std::cell::{Ref, RefCell, RefMut};
+use std::mem::ManuallyDrop;
+use std::ops::{Deref, DerefMut, RangeInclusive};
+use std::rc::Rc;
+use wasm_bindgen::JsCast;
+
+use super::*;
+
+/// Converts arguments from their JavaScript form to their Rust form.
+///
+/// `ArgTypeInfo` has two required methods: `borrow` and `load_from`. The use site looks like this:
+///
+/// ```no_run
+/// # use libsignal_bridge::wasm::*;
+/// # struct Foo;
+/// # impl SimpleArgTypeInfo for Foo {
+/// #     fn convert_from(_: &JsValue) -> WasmResult<Self> {
+/// #         Ok(Foo)
+/// #     }
+/// # }
+/// # fn test(js_arg: &JsValue) -> WasmResult<()> {
+/// let mut js_arg_borrowed = Foo::borrow(js_arg)?;
+/// let rust_arg = Foo::load_from(&mut js_arg_borrowed);
+/// #     Ok(())
+/// # }
+/// ```
+///
+/// This mirrors `node::ArgTypeInfo`, but since WASM memory is separate from JavaScript's, the
+/// stored value never borrows from the JavaScript argument itself.
+///
+/// If the Rust type can be directly loaded from a `JsValue` with no local storage needed,
+/// implement [`SimpleArgTypeInfo`] instead.
+pub trait ArgTypeInfo<'storage>: Sized {
+    /// Local storage for the argument.
+    type StoredType: 'storage;
+    /// "Borrows" the data in `foreign`, usually by copying it into WASM memory.
+    fn borrow(foreign: &JsValue) -> WasmResult<Self::StoredType>;
+    /// Loads the Rust value from the data that's been `stored` by [`borrow()`](Self::borrow()).
+    fn load_from(stored: &'storage mut Self::StoredType) -> Self;
+}
+
+/// Converts arguments from their JavaScript form and saves them for use in an `async` function.
+///
+/// `AsyncArgTypeInfo` works very similarly to `ArgTypeInfo`, but with the added restriction that
+/// the stored type is `'static` so that it can be used in an `async` block. Unlike Node, no
+/// JavaScript context is needed to clean it up; it's simply dropped along with the future.
+///
+/// If the Rust type can be directly loaded from a `JsValue` with no extra local storage needed,
+/// implement [`SimpleArgTypeInfo`] instead.
+pub trait AsyncArgTypeInfo<'storage>: Sized {
+    /// Local storage for the argument that can outlive the current call.
+    type StoredType: 'static;
+    /// Saves the data in `foreign` so that it can be used in an `async` context.
+    fn save_async_arg(foreign: &JsValue) -> WasmResult<Self::StoredType>;
+    /// Loads the Rust value from the data that's been `stored` by
+    /// [`save_async_arg()`](Self::save_async_arg()).
+    fn load_async_arg(stored: &'storage mut Self::StoredType) -> Self;
+}
+
+/// A simpler interface for [`ArgTypeInfo`] and [`AsyncArgTypeInfo`] for when no separate local
+/// storage is needed.
+pub trait SimpleArgTypeInfo: Sized + 'static {
+    /// Converts the data in `foreign` to the Rust type.
+    fn convert_from(foreign: &JsValue) -> WasmResult<Self>;
+}
+
+impl<'a, T> ArgTypeInfo<'a> for T
+where
+    T: SimpleArgTypeInfo,
+{
+    type StoredType = Option<Self>;
+    fn borrow(foreign: &JsValue) -> WasmResult<Self::StoredType> {
+        Ok(Some(Self::convert_from(foreign)?))
+    }
+    fn load_from(stored: &'a mut Self::StoredType) -> Self {
+        stored.take().expect("should only be loaded once")
+    }
+}
+
+impl<'a, T> AsyncArgTypeInfo<'a> for T
+where
+    T: SimpleArgTypeInfo,
+{
+    type StoredType = Option<Self>;
+    fn save_async_arg(foreign: &JsValue) -> WasmResult<Self::StoredType> {
+        Ok(Some(Self::convert_from(foreign)?))
+    }
+    fn load_async_arg(stored: &'a mut Self::StoredType) -> Self {
+        stored.take().expect("should only be loaded once")
+    }
+}
+
+/// Converts result values from their Rust form to their JavaScript form.
+///
+/// `ResultTypeInfo` is used to implement the `bridge_fn` macro, but can also be used outside it.
+pub trait ResultTypeInfo: Sized {
+    /// Converts the data in `self` to a JavaScript value, similar to `try_into()`.
+    fn convert_into(self) -> WasmResult<JsValue>;
+}
+
+/// Returns `true` if `value` represents an integer within the given range.
+fn can_convert_js_number_to_int(value: f64, valid_range: RangeInclusive<f64>) -> bool {
+    value.is_finite() && value.fract() == 0.0 && valid_range.contains(&value)
+}
+
+/// Looks up a function on the JavaScript global object, such as `BigInt`.
+fn global_function(name: &str) -> WasmResult<js_sys::Function> {
+    js_sys::Reflect::get(&js_sys::global(), &JsValue::from_str(name))?
+        .dyn_into()
+        .map_err(|_| type_error(format!("{} is not a function", name)))
+}
+
+/// Returns `Object.prototype.toString.call(value)`, such as `[object BigInt]`.
+///
+/// This is how to identify values js-sys doesn't have a type for.
+fn object_tag(value: &JsValue) -> WasmResult<String> {
+    let object = global_function("Object")?;
+    let prototype = js_sys::Reflect::get(&object, &JsValue::from_str("prototype"))?;
+    let to_string: js_sys::Function =
+        js_sys::Reflect::get(&prototype, &JsValue::from_str("toString"))?
+            .dyn_into()
+            .map_err(|_| type_error("Object.prototype.toString is not a function"))?;
+    to_string
+        .call0(value)?
+        .as_string()
+        .ok_or_else(|| type_error("Object.prototype.toString did not return a string"))
+}
+
+/// Converts `bigint`s in the range of a `u64`.
+///
+/// Numbers are rejected with a `TypeError`, even integral ones, exactly as for Node.
+impl SimpleArgTypeInfo for u64 {
+    fn convert_from(foreign: &JsValue) -> WasmResult<Self> {
+        if object_tag(foreign)? != "[object BigInt]" {
+            return Err(type_error("expected a bigint"));
+        }
+        let digits = global_function("String")?
+            .call1(&JsValue::UNDEFINED, foreign)?
+            .as_string()
+            .ok_or_else(|| type_error("expected a bigint"))?;
+        digits
+            .parse()
+            .map_err(|_| range_error(format!("cannot convert {} to u64", digits)))
+    }
+}
+
+/// Copies a UUID out of a `Uint8Array` or `ArrayBuffer`, which must be exactly 16 bytes long.
+///
+/// Any other length is rejected with a `TypeError`.
+impl SimpleArgTypeInfo for crate::support::Uuid {
+    fn convert_from(foreign: &JsValue) -> WasmResult<Self> {
+        Self::from_slice(&copy_uint8_like(foreign)?).map_err(|e| type_error(e.to_string()))
+    }
+}
+
+/// Copies a JavaScript string into a Rust `String`.
+///
+/// Strings containing unpaired surrogates, which wasm-bindgen would replace with U+FFFD REPLACEMENT
+/// CHARACTER, or NUL characters are rejected with a `TypeError`, to match the other bridges.
+fn convert_js_string(foreign: &JsValue) -> WasmResult<String> {
+    let string = foreign
+        .dyn_ref::<js_sys::JsString>()
+        .ok_or_else(|| type_error("expected a string"))?;
+    let value = String::from(string);
+    if value.contains('\0') {
+        return Err(type_error("string contains a NUL character"));
+    }
+    // Only a string that converted with a replacement character can have had a surrogate replaced.
+    if value.contains('\u{FFFD}') && !string.is_valid_utf16() {
+        return Err(type_error("string contains an unpaired surrogate"));
+    }
+    Ok(value)
+}
+
+/// See [`convert_js_string`].
+impl SimpleArgTypeInfo for String {
+    fn convert_from(foreign: &JsValue) -> WasmResult<Self> {
+        convert_js_string(foreign)
+    }
+}
+
+/// Copies a JavaScript string so that the Rust function can borrow it as a `&str`.
+///
+/// See [`convert_js_string`].
+impl<'storage> ArgTypeInfo<'storage> for &'storage str {
+    type StoredType = String;
+    fn borrow(foreign: &JsValue) -> WasmResult<Self::StoredType> {
+        convert_js_string(foreign)
+    }
+    fn load_from(stored: &'storage mut Self::StoredType) -> Self {
+        stored
+    }
+}
+
+impl<'storage> AsyncArgTypeInfo<'storage> for &'storage str {
+    type StoredType = String;
+    fn save_async_arg(foreign: &JsValue) -> WasmResult<Self::StoredType> {
+        convert_js_string(foreign)
+    }
+    fn load_async_arg(stored: &'storage mut Self::StoredType) -> Self {
+        stored
+    }
+}
+
+/// Converts `null` to `None`, passing through all other values.
+impl<'storage, T> ArgTypeInfo<'storage> for Option<T>
+where
+    T: ArgTypeInfo<'storage>,
+{
+    type StoredType = Option<T::StoredType>;
+    fn borrow(foreign: &JsValue) -> WasmResult<Self::StoredType> {
+        if foreign.is_null() {
+            return Ok(None);
+        }
+        T::borrow(foreign).map(Some)
+    }
+    fn load_from(stored: &'storage mut Self::StoredType) -> Self {
+        stored.as_mut().map(T::load_from)
+    }
+}
+
+/// Converts `null` to `None`, passing through all other values.
+impl<'storage, T> AsyncArgTypeInfo<'storage> for Option<T>
+where
+    T: AsyncArgTypeInfo<'storage>,
+{
+    type StoredType = Option<T::StoredType>;
+    fn save_async_arg(foreign: &JsValue) -> WasmResult<Self::StoredType> {
+        if foreign.is_null() {
+            return Ok(None);
+        }
+        T::save_async_arg(foreign).map(Some)
+    }
+    fn load_async_arg(stored: &'storage mut Self::StoredType) -> Self {
+        stored.as_mut().map(T::load_async_arg)
+    }
+}
+
+/// Copies the bytes of a `Uint8Array` (including a Node `Buffer`) or an `ArrayBuffer` into WASM
+/// memory.
+///
+/// Anything else is rejected with a `TypeError`. Copying means the Rust function never sees later
+/// changes to the JavaScript buffer, even during an `async` call.
+fn copy_uint8_like(foreign: &JsValue) -> WasmResult<Vec<u8>> {
+    if let Some(array) = foreign.dyn_ref::<js_sys::Uint8Array>() {
+        Ok(array.to_vec())
+    } else if let Some(buffer) = foreign.dyn_ref::<js_sys::ArrayBuffer>() {
+        Ok(js_sys::Uint8Array::new(buffer).to_vec())
+    } else {
+        Err(type_error("expected a Uint8Array or ArrayBuffer"))
+    }
+}
+
+/// Copies a `Uint8Array` or `ArrayBuffer`; see [`copy_uint8_like`].
+impl<'storage> ArgTypeInfo<'storage> for &'storage [u8] {
+    type StoredType = Vec<u8>;
+    fn borrow(foreign: &JsValue) -> WasmResult<Self::StoredType> {
+        copy_uint8_like(foreign)
+    }
+    fn load_from(stored: &'storage mut Self::StoredType) -> Self {
+        stored
+    }
+}
+
+/// Copies a `Uint8Array` or `ArrayBuffer`; see [`copy_uint8_like`].
+impl<'storage> AsyncArgTypeInfo<'storage> for &'storage [u8] {
+    type StoredType = Vec<u8>;
+    fn save_async_arg(foreign: &JsValue) -> WasmResult<Self::StoredType> {
+        copy_uint8_like(foreign)
+    }
+    fn load_async_arg(stored: &'storage mut Self::StoredType) -> Self {
+        stored
+    }
+}
+
+macro_rules! store {
+    ($name:ident) => {
+        paste! {
+            impl<'a> AsyncArgTypeInfo<'a> for &'a mut dyn libsignal_protocol::$name {
+                type StoredType = [<Wasm $name>];
+                fn save_async_arg(foreign: &JsValue) -> WasmResult<Self::StoredType> {
+                    Self::StoredType::new(foreign)
+                }
+                fn load_async_arg(stored: &'a mut Self::StoredType) -> Self {
+                    stored
+                }
+            }
+        }
+    };
+}
+
+store!(IdentityKeyStore);
+store!(PreKeyStore);
+store!(SenderKeyStore);
+store!(SessionStore);
+store!(SignedPreKeyStore);
+
+impl ResultTypeInfo for bool {
+    fn convert_into(self) -> WasmResult<JsValue> {
+        Ok(JsValue::from_bool(self))
+    }
+}
+
+/// Produces a `bigint`, like the implementation of [`SimpleArgTypeInfo`] for `u64`.
+impl ResultTypeInfo for u64 {
+    fn convert_into(self) -> WasmResult<JsValue> {
+        global_function("BigInt")?.call1(&JsValue::UNDEFINED, &JsValue::from_str(&self.to_string()))
+    }
+}
+
+impl ResultTypeInfo for String {
+    fn convert_into(self) -> WasmResult<JsValue> {
+        self.deref().convert_into()
+    }
+}
+
+impl ResultTypeInfo for &str {
+    fn convert_into(self) -> WasmResult<JsValue> {
+        Ok(JsValue::from_str(self))
+    }
+}
+
+/// Converts `None` to `null`, passing through all other values.
+impl<T: ResultTypeInfo> ResultTypeInfo for Option<T> {
+    fn convert_into(self) -> WasmResult<JsValue> {
+        match self {
+            Some(value) => value.convert_into(),
+            None => Ok(JsValue::NULL),
+        }
+    }
+}
+
+/// Copies the bytes into a new `Uint8Array`.
+impl ResultTypeInfo for Vec<u8> {
+    fn convert_into(self) -> WasmResult<JsValue> {
+        Ok(js_sys::Uint8Array::from(&self[..]).into())
+    }
+}
+
+impl ResultTypeInfo for Box<[u8]> {
+    fn convert_into(self) -> WasmResult<JsValue> {
+        self.into_vec().convert_into()
+    }
+}
+
+impl ResultTypeInfo for Vec<Box<[u8]>> {
+    fn convert_into(self) -> WasmResult<JsValue> {
+        convert_list_into(self)
+    }
+}
+
+/// Converts each value in `values`, returning them as a JavaScript array.
+///
+/// Used for lists of buffers and, by [`wasm_bridge_handle`], lists of handles.
+pub fn convert_list_into<T: ResultTypeInfo>(values: Vec<T>) -> WasmResult<JsValue> {
+    let array = js_sys::Array::new();
+    for value in values {
+        array.push(&value.convert_into()?);
+    }
+    Ok(array.into())
+}
+
+/// A tuple result to be returned to JavaScript as an object, with one field per element.
+///
+/// Generated by `bridge_fn` for functions that return tuples, with the same field names as for
+/// Node; see `node_result_fields`.
+pub struct NamedFields<T> {
+    pub names: &'static [&'static str],
+    pub values: T,
+}
+
+macro_rules! named_fields_result {
+    ($($element:ident $index:tt),+) => {
+        impl<$($element: ResultTypeInfo),+> ResultTypeInfo for NamedFields<($($element,)+)> {
+            fn convert_into(self) -> WasmResult<JsValue> {
+                let object = js_sys::Object::new();
+                $(
+                    let value = self.values.$index.convert_into()?;
+                    js_sys::Reflect::set(&object, &JsValue::from_str(self.names[$index]), &value)?;
+                )+
+                Ok(object.into())
+            }
+        }
+    };
+}
+
+named_fields_result!(A 0, B 1);
+named_fields_result!(A 0, B 1, C 2);
+
+impl<T: ResultTypeInfo, E: SignalWasmError> ResultTypeInfo for Result<T, E> {
+    fn convert_into(self) -> WasmResult<JsValue> {
+        match self {
+            Ok(value) => value.convert_into(),
+            Err(err) => Err(err.into_js_error()),
+        }
+    }
+}
+
+impl<T: ResultTypeInfo> ResultTypeInfo for WasmResult<T> {
+    fn convert_into(self) -> WasmResult<JsValue> {
+        self?.convert_into()
+    }
+}
+
+impl ResultTypeInfo for () {
+    fn convert_into(self) -> WasmResult<JsValue> {
+        Ok(JsValue::UNDEFINED)
+    }
+}
+
+impl ResultTypeInfo for JsValue {
+    fn convert_into(self) -> WasmResult<JsValue> {
+        Ok(self)
+    }
+}
+
+macro_rules! full_range_integer {
+    ($typ:ty) => {
+        #[doc = "Converts all valid integer values for the type."]
+        impl SimpleArgTypeInfo for $typ {
+            fn convert_from(foreign: &JsValue) -> WasmResult<Self> {
+                let value = foreign
+                    .as_f64()
+                    .ok_or_else(|| type_error("expected a number"))?;
+                if !can_convert_js_number_to_int(value, 0.0..=<$typ>::MAX.into()) {
+                    return Err(range_error(format!(
+                        "cannot convert {} to {}",
+                        value,
+                        stringify!($typ),
+                    )));
+                }
+                Ok(value as $typ)
+            }
+        }
+        #[doc = "Converts all valid integer values for the type."]
+        impl ResultTypeInfo for $typ {
+            fn convert_into(self) -> WasmResult<JsValue> {
+                Ok(JsValue::from_f64(self.into()))
+            }
+        }
+    };
+}
+
+full_range_integer!(u8);
+full_range_integer!(u32);
+full_range_integer!(i32);
+
+/// A boxed Rust value referred to by a handle, tagged with its type.
+///
+/// Handles are plain numbers, so JavaScript can pass any handle (or any number at all) where a
+/// particular type is expected. The tag lets the bridge reject a live handle of the wrong type
+/// with a `TypeError` instead of misreading it. Like FFI pointers, a number that isn't a live
+/// handle at all can't be detected.
+#[repr(C)]
+pub struct HandleBox<T: 'static> {
+    // Must be first, so that it can be read without knowing `T`.
+    type_id: TypeId,
+    value: T,
+}
+
+impl<T: 'static> Deref for HandleBox<T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+/// Boxes `value` for JavaScript, returning its handle.
+///
+/// The handle is a number that owns one reference to the value, which JavaScript releases with
+/// the type's `Destroy` function (see [`destroy_handle`]). Arguments of `async` functions take
+/// their own references, so a value stays alive until every operation using it has finished.
+pub fn new_handle<T: 'static>(value: T) -> JsValue {
+    let handle = Rc::new(HandleBox {
+        type_id: TypeId::of::<T>(),
+        value,
+    });
+    JsValue::from_f64(Rc::into_raw(handle) as usize as f64)
+}
+
+/// Checks that `foreign` is a handle to a `T`, and returns the pointer it represents.
+///
+/// # Safety
+///
+/// `foreign` must be a number that isn't a handle (such as 0), or a live handle of any type
+/// produced by [`new_handle`].
+unsafe fn handle_ptr<T: 'static>(foreign: &JsValue) -> WasmResult<*const HandleBox<T>> {
+    let ptr = match foreign.as_f64() {
+        Some(value) if can_convert_js_number_to_int(value, 1.0..=usize::MAX as f64) => {
+            value as usize as *const HandleBox<T>
+        }
+        _ => return Err(type_error("expected a handle")),
+    };
+    // Every HandleBox starts with its type tag, whatever its value type.
+    if *(ptr as *const TypeId) != TypeId::of::<T>() {
+        return Err(type_error(format!(
+            "expected a handle to {}",
+            std::any::type_name::<T>()
+        )));
+    }
+    Ok(ptr)
+}
+
+/// Takes a new reference to the value behind a handle, without giving up JavaScript's reference.
+///
+/// # Safety
+///
+/// See [`handle_ptr`].
+pub unsafe fn clone_handle<T: 'static>(foreign: &JsValue) -> WasmResult<Rc<HandleBox<T>>> {
+    let handle = ManuallyDrop::new(Rc::from_raw(handle_ptr::<T>(foreign)?));
+    Ok(Rc::clone(&handle))
+}
+
+/// Releases JavaScript's reference to the value behind a handle.
+///
+/// # Safety
+///
+/// See [`handle_ptr`]. Afterwards, the handle is no longer live.
+pub unsafe fn destroy_handle<T: 'static>(foreign: &JsValue) -> WasmResult<()> {
+    drop(Rc::from_raw(handle_ptr::<T>(foreign)?));
+    Ok(())
+}
+
+/// Loads each element of a JavaScript array with `load`, as for `&[&Foo]` arguments.
+///
+/// Rejects anything but an array with a `TypeError`, as well as any element `load` rejects, naming
+/// its index.
+pub(crate) fn load_array_elements<T>(
+    foreign: &JsValue,
+    mut load: impl FnMut(&JsValue) -> WasmResult<T>,
+) -> WasmResult<Vec<T>> {
+    if !js_sys::Array::is_array(foreign) {
+        return Err(type_error("expected an array"));
+    }
+    let array: &js_sys::Array = foreign.unchecked_ref();
+    (0..array.length())
+        .map(|index| {
+            load(&array.get(index))
+                .map_err(|_| type_error(format!("invalid handle at index {}", index)))
+        })
+        .collect()
+}
+
+/// Holds a borrow of a `RefCell`-boxed Rust value (as used by `bridge_handle!(..., mut = true)`),
+/// along with a reference to the handle's value that keeps the `RefCell` alive.
+///
+/// `B` is either a [`std::cell::Ref`] or a [`std::cell::RefMut`]. As with Node, holding the borrow
+/// for an entire `async` operation means that any other attempt to mutate the same object in the
+/// meantime fails rather than being silently overwritten.
+pub struct BorrowedHandle<B: 'static> {
+    // Declared first, so that the borrow is released before the value can be.
+    borrow: B,
+    _owner: Rc<dyn Any>,
+}
+
+impl<T: 'static> BorrowedHandle<Ref<'static, T>> {
+    /// Immutably borrows the value behind `foreign`.
+    ///
+    /// # Safety
+    ///
+    /// See [`handle_ptr`].
+    pub unsafe fn new_shared(foreign: &JsValue) -> WasmResult<Self> {
+        let owner = clone_handle::<RefCell<T>>(foreign)?;
+        // The RefCell lives as long as `owner`, which outlives the borrow (see above).
+        let cell: &'static RefCell<T> = &*(&**owner as *const RefCell<T>);
+        let borrow = cell
+            .try_borrow()
+            .map_err(|_| plain_error("object is being modified by another operation"))?;
+        Ok(Self {
+            borrow,
+            _owner: owner,
+        })
+    }
+}
+
+impl<T: 'static> BorrowedHandle<RefMut<'static, T>> {
+    /// Mutably borrows the value behind `foreign`.
+    ///
+    /// # Safety
+    ///
+    /// See [`handle_ptr`].
+    pub unsafe fn new_exclusive(foreign: &JsValue) -> WasmResult<Self> {
+        let owner = clone_handle::<RefCell<T>>(foreign)?;
+        // See new_shared.
+        let cell: &'static RefCell<T> = &*(&**owner as *const RefCell<T>);
+        let borrow = cell
+            .try_borrow_mut()
+            .map_err(|_| plain_error("object is already in use by another operation"))?;
+        Ok(Self {
+            borrow,
+            _owner: owner,
+        })
+    }
+}
+
+impl<B: Deref + 'static> Deref for BorrowedHandle<B> {
+    type Target = B::Target;
+    fn deref(&self) -> &Self::Target {
+        &self.borrow
+    }
+}
+
+impl<B: DerefMut + 'static> DerefMut for BorrowedHandle<B> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.borrow
+    }
+}
+
+/// Implementation of [`bridge_handle`](crate::support::bridge_handle) for WASM.
+macro_rules! wasm_bridge_handle {
+    ( $typ:ty as false $(, mut = $_:tt)? ) => {};
+    ( $typ:ty as $wasm_name:ident ) => {
+        impl<'storage> wasm::ArgTypeInfo<'storage> for &'storage $typ {
+            type StoredType = std::rc::Rc<wasm::HandleBox<$typ>>;
+            fn borrow(foreign: &wasm::JsValue) -> wasm::WasmResult<Self::StoredType> {
+                unsafe { wasm::clone_handle(foreign) }
+            }
+            fn load_from(stored: &'storage mut Self::StoredType) -> Self {
+                &***stored
+            }
+        }
+
+        impl<'storage> wasm::ArgTypeInfo<'storage> for &'storage [&'storage $typ] {
+            type StoredType = (
+                Vec<std::rc::Rc<wasm::HandleBox<$typ>>>,
+                Vec<&'storage $typ>,
+            );
+            fn borrow(foreign: &wasm::JsValue) -> wasm::WasmResult<Self::StoredType> {
+                let handles = wasm::load_array_elements(foreign, |element| unsafe {
+                    wasm::clone_handle(element)
+                })?;
+                Ok((handles, Vec::new()))
+            }
+            fn load_from(stored: &'storage mut Self::StoredType) -> Self {
+                let (handles, loaded) = stored;
+                let handles: &'storage Vec<_> = handles;
+                *loaded = handles.iter().map(|handle| &***handle).collect();
+                let loaded: &'storage Vec<_> = loaded;
+                &loaded[..]
+            }
+        }
+
+        impl<'storage> wasm::AsyncArgTypeInfo<'storage> for &'storage $typ {
+            type StoredType = std::rc::Rc<wasm::HandleBox<$typ>>;
+            fn save_async_arg(foreign: &wasm::JsValue) -> wasm::WasmResult<Self::StoredType> {
+                unsafe { wasm::clone_handle(foreign) }
+            }
+            fn load_async_arg(stored: &'storage mut Self::StoredType) -> Self {
+                &***stored
+            }
+        }
+
+        impl wasm::ResultTypeInfo for $typ {
+            fn convert_into(self) -> wasm::WasmResult<wasm::JsValue> {
+                Ok(wasm::new_handle(self))
+            }
+        }
+        impl wasm::ResultTypeInfo for Vec<$typ> {
+            fn convert_into(self) -> wasm::WasmResult<wasm::JsValue> {
+                wasm::convert_list_into(self)
+            }
+        }
+
+        wasm_bridge_destroy!($typ as $wasm_name);
+    };
+    ( $typ:ty as $wasm_name:ident, mut = true ) => {
+        impl<'storage> wasm::ArgTypeInfo<'storage> for &'storage $typ {
+            type StoredType = wasm::BorrowedHandle<std::cell::Ref<'static, $typ>>;
+            fn borrow(foreign: &wasm::JsValue) -> wasm::WasmResult<Self::StoredType> {
+                unsafe { wasm::BorrowedHandle::new_shared(foreign) }
+            }
+            fn load_from(stored: &'storage mut Self::StoredType) -> Self {
+                &*stored
+            }
+        }
+
+        impl<'storage> wasm::ArgTypeInfo<'storage> for &'storage mut $typ {
+            type StoredType = wasm::BorrowedHandle<std::cell::RefMut<'static, $typ>>;
+            fn borrow(foreign: &wasm::JsValue) -> wasm::WasmResult<Self::StoredType> {
+                unsafe { wasm::BorrowedHandle::new_exclusive(foreign) }
+            }
+            fn load_from(stored: &'storage mut Self::StoredType) -> Self {
+                &mut *stored
+            }
+        }
+
+        impl<'storage> wasm::ArgTypeInfo<'storage> for &'storage [&'storage $typ] {
+            type StoredType = (
+                Vec<wasm::BorrowedHandle<std::cell::Ref<'static, $typ>>>,
+                Vec<&'storage $typ>,
+            );
+            fn borrow(foreign: &wasm::JsValue) -> wasm::WasmResult<Self::StoredType> {
+                let borrows = wasm::load_array_elements(foreign, |element| unsafe {
+                    wasm::BorrowedHandle::new_shared(element)
+                })?;
+                Ok((borrows, Vec::new()))
+            }
+            fn load_from(stored: &'storage mut Self::StoredType) -> Self {
+                let (borrows, loaded) = stored;
+                let borrows: &'storage Vec<_> = borrows;
+                *loaded = borrows.iter().map(|borrow| &**borrow).collect();
+                let loaded: &'storage Vec<_> = loaded;
+                &loaded[..]
+            }
+        }
+
+        impl<'storage> wasm::AsyncArgTypeInfo<'storage> for &'storage $typ {
+            type StoredType = wasm::BorrowedHandle<std::cell::Ref<'static, $typ>>;
+            fn save_async_arg(foreign: &wasm::JsValue) -> wasm::WasmResult<Self::StoredType> {
+                unsafe { wasm::BorrowedHandle::new_shared(foreign) }
+            }
+            fn load_async_arg(stored: &'storage mut Self::StoredType) -> Self {
+                &*stored
+            }
+        }
+
+        impl<'storage> wasm::AsyncArgTypeInfo<'storage> for &'storage mut $typ {
+            type StoredType = wasm::BorrowedHandle<std::cell::RefMut<'static, $typ>>;
+            fn save_async_arg(foreign: &wasm::JsValue) -> wasm::WasmResult<Self::StoredType> {
+                unsafe { wasm::BorrowedHandle::new_exclusive(foreign) }
+            }
+            fn load_async_arg(stored: &'storage mut Self::StoredType) -> Self {
+                &mut *stored
+            }
+        }
+
+        impl wasm::ResultTypeInfo for $typ {
+            fn convert_into(self) -> wasm::WasmResult<wasm::JsValue> {
+                Ok(wasm::new_handle(std::cell::RefCell::new(self)))
+            }
+        }
+        impl wasm::ResultTypeInfo for Vec<$typ> {
+            fn convert_into(self) -> wasm::WasmResult<wasm::JsValue> {
+                wasm::convert_list_into(self)
+            }
+        }
+
+        wasm_bridge_destroy!(std::cell::RefCell<$typ> as $wasm_name);
+    };
+    ( $typ:ty $(, mut = $_:tt)? ) => {
+        paste! {
+            wasm_bridge_handle!($typ as $typ $(, mut = $_)?);
+        }
+    };
+}
@@ -0,0 +1,107 @@
+//
+// Copyright 2021 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+use super::*;
+
+use std::fmt;
+use wasm_bindgen::JsCast;
+
+/// Represents an error returned by a callback.
+#[derive(Debug)]
+struct CallbackError {
+    message: String,
+}
+
+impl CallbackError {
+    fn new(message: String) -> CallbackError {
+        Self { message }
+    }
+}
+
+impl fmt::Display for CallbackError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "callback error {}", self.message)
+    }
+}
+
+impl std::error::Error for CallbackError {}
+
+/// Converts a JavaScript error message to a [`SignalProtocolError::ApplicationCallbackError`].
+pub fn js_error_to_rust(func: &'static str, err: String) -> SignalProtocolError {
+    SignalProtocolError::ApplicationCallbackError(func, Box::new(CallbackError::new(err)))
+}
+
+/// Converts a Rust error into a JavaScript exception.
+///
+/// Thrown errors look exactly like the ones from Node: a JavaScript `Error` whose `message`
+/// describes the Rust error and whose `name` identifies the kind of error.
+pub trait SignalWasmError: Sized + fmt::Display {
+    /// The `name` property of the thrown JavaScript `Error`.
+    fn js_error_name(&self) -> &'static str;
+
+    /// The `message` property of the thrown JavaScript `Error`.
+    fn js_error_message(&self) -> String {
+        self.to_string()
+    }
+
+    /// Converts `self` to a JavaScript `Error`, ready to be thrown.
+    fn into_js_error(self) -> JsValue {
+        let error = js_sys::Error::new(&self.js_error_message());
+        error.set_name(self.js_error_name());
+        error.into()
+    }
+}
+
+impl SignalWasmError for SignalProtocolError {
+    fn js_error_name(&self) -> &'static str {
+        match self {
+            SignalProtocolError::IdentityKeyChanged(..) => "IdentityKeyChanged",
+            SignalProtocolError::UnknownSessionVersion(_) => "UnknownSessionVersion",
+            _ => "SignalProtocolError",
+        }
+    }
+
+    fn js_error_message(&self) -> String {
+        crate::support::ErrorChain(self).to_string()
+    }
+}
+
+impl SignalWasmError for aes_gcm_siv::Error {
+    fn js_error_name(&self) -> &'static str {
+        match self {
+            aes_gcm_siv::Error::InvalidKeySize => "InvalidKeySize",
+            aes_gcm_siv::Error::InvalidNonceSize => "InvalidNonceSize",
+            aes_gcm_siv::Error::InvalidInputSize => "InvalidInputSize",
+            aes_gcm_siv::Error::InvalidTag => "InvalidTag",
+        }
+    }
+}
+
+/// A JavaScript `TypeError`, for arguments of the wrong type.
+pub(crate) fn type_error(message: impl AsRef<str>) -> JsValue {
+    js_sys::TypeError::new(message.as_ref()).into()
+}
+
+/// A JavaScript `RangeError`, for numeric arguments out of range.
+pub(crate) fn range_error(message: impl AsRef<str>) -> JsValue {
+    js_sys::RangeError::new(message.as_ref()).into()
+}
+
+/// A plain JavaScript `Error`.
+pub(crate) fn plain_error(message: impl AsRef<str>) -> JsValue {
+    js_sys::Error::new(message.as_ref()).into()
+}
+
+/// Describes a value thrown (or a `Promise` rejected) by JavaScript code, for use with
+/// [`js_error_to_rust`].
+pub(crate) fn error_message(error: &JsValue) -> String {
+    if let Some(error) = error.dyn_ref::<js_sys::Error>() {
+        String::from(error.to_string())
+    } else if let Some(message) = error.as_string() {
+        message
+    } else {
+        format!("{:?}", error)
+    }
+}
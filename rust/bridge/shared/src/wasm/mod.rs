@@ -0,0 +1,120 @@
+//
+// Copyright 2021 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+//! Support for the WebAssembly bridge, built on `wasm-bindgen`.
+//!
+//! Every `bridge_fn` exposed to Node is also exported to JavaScript from the `libsignal-wasm`
+//! crate under the same name. Unlike Node, WASM memory is separate from JavaScript's, so byte
+//! buffers are always copied in both directions, and boxed Rust values are passed as numeric
+//! handles (see [`new_handle`]) rather than as garbage-collected objects.
+
+use libsignal_protocol::*;
+use std::borrow::Cow;
+use std::future::Future;
+
+pub use wasm_bindgen::JsValue;
+
+#[macro_use]
+mod convert;
+pub use convert::*;
+
+mod error;
+pub use error::*;
+
+mod storage;
+pub use storage::*;
+
+/// The result of a WASM entry point; the error is thrown in JavaScript.
+pub type WasmResult<T> = Result<T, JsValue>;
+
+/// Runs `future` on the JavaScript event loop, returning a `Promise` for its result.
+///
+/// Used in the implementation of `async` `bridge_fn`s.
+pub fn promise(future: impl Future<Output = WasmResult<JsValue>> + 'static) -> JsValue {
+    wasm_bindgen_futures::future_to_promise(future).into()
+}
+
+/// The [`crate::support::Env`] for WASM `bridge_fn`s, both synchronous and `async`.
+///
+/// Buffers have to be copied into JavaScript memory anyway, so they're returned as plain
+/// `Vec<u8>`s.
+pub struct Env;
+
+impl crate::support::Env for Env {
+    type Buffer = Vec<u8>;
+    fn buffer<'b, T: Into<Cow<'b, [u8]>>>(self, input: T) -> Self::Buffer {
+        input.into().into_owned()
+    }
+}
+
+/// Used in the implementation of [`bridge_handle`](crate::support::bridge_handle) to expose a
+/// `Foo_Destroy` function, which releases the handle JavaScript owns.
+///
+/// `$inner` is the type inside the handle: the bridged type itself, or a `RefCell` of it for
+/// `mut = true` types.
+///
+/// Not intended to be invoked directly.
+macro_rules! wasm_bridge_destroy {
+    ( $inner:ty as $wasm_name:ident ) => {
+        paste! {
+            #[allow(non_snake_case)]
+            #[wasm_bindgen::prelude::wasm_bindgen(js_name = [<$wasm_name _Destroy>])]
+            pub fn [<wasm_ $wasm_name _Destroy>](handle: wasm::JsValue) -> wasm::WasmResult<()> {
+                // Any async operations still using the value keep their own reference to it.
+                unsafe { wasm::destroy_handle::<$inner>(&handle) }
+            }
+        }
+    };
+}
+
+/// Implementation of the `serialize` flag of [`bridge_handle`](crate::support::bridge_handle) for
+/// WASM.
+macro_rules! wasm_bridge_serialize {
+    ( $typ:ty as false $(, serialize = $_:tt)? ) => {};
+    ( $typ:ty as $wasm_name:ident $(, serialize = false)? ) => {};
+    ( $typ:ty as $wasm_name:ident, serialize = true ) => {
+        paste! {
+            #[allow(non_snake_case)]
+            #[wasm_bindgen::prelude::wasm_bindgen(js_name = [<$wasm_name _Serialize>])]
+            pub fn [<wasm_ $wasm_name _Serialize>](
+                obj: wasm::JsValue,
+            ) -> wasm::WasmResult<wasm::JsValue> {
+                let mut obj_stored = <&$typ as wasm::ArgTypeInfo>::borrow(&obj)?;
+                let obj = <&$typ as wasm::ArgTypeInfo>::load_from(&mut obj_stored);
+                let result = TransformHelper(<$typ>::serialize(obj))
+                    .ok_if_needed()
+                    .map(|result| Vec::from(result.into_vec_if_needed().0));
+                wasm::ResultTypeInfo::convert_into(result)
+            }
+        }
+    };
+    ( $typ:ty $(, serialize = $_:tt)? ) => {
+        paste! {
+            wasm_bridge_serialize!($typ as $typ $(, serialize = $_)?);
+        }
+    };
+}
+
+/// Implementation of [`bridge_deserialize`](crate::support::bridge_deserialize) for WASM.
+macro_rules! wasm_bridge_deserialize {
+    ( $typ:ident::$fn:path as false ) => {};
+    ( $typ:ident::$fn:path as $wasm_name:ident ) => {
+        paste! {
+            #[allow(non_snake_case, clippy::redundant_closure)]
+            #[wasm_bindgen::prelude::wasm_bindgen(js_name = [<$wasm_name _Deserialize>])]
+            pub fn [<wasm_ $wasm_name _Deserialize>](
+                buffer: wasm::JsValue,
+            ) -> wasm::WasmResult<wasm::JsValue> {
+                let mut buffer_stored = <&[u8] as wasm::ArgTypeInfo>::borrow(&buffer)?;
+                let buffer = <&[u8] as wasm::ArgTypeInfo>::load_from(&mut buffer_stored);
+                let obj: Result<$typ> = $typ::$fn(buffer);
+                wasm::ResultTypeInfo::convert_into(obj)
+            }
+        }
+    };
+    ( $typ:ident::$fn:path ) => {
+        wasm_bridge_deserialize!($typ::$fn as $typ);
+    };
+}
@@ -0,0 +1,440 @@
+//
+// Copyright 2021 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+//! Stores backed by JavaScript objects, with the same methods as the Node stores.
+//!
+//! Each method may return a value or a `Promise`. Records and keys passed to the store are new
+//! handles that the store owns, and must eventually release with the matching `Destroy` function.
+//! Handles the store returns are copied, so the store keeps ownership of those.
+
+use super::*;
+
+use async_trait::async_trait;
+use std::convert::TryFrom;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+
+/// Checks that a store argument is an object, whose methods are looked up on each call.
+fn check_store_object(foreign: &JsValue) -> WasmResult<JsValue> {
+    if !foreign.is_object() {
+        return Err(type_error("expected a store object"));
+    }
+    Ok(foreign.clone())
+}
+
+/// Calls `store_object[name](...args)`, and waits for the result if it's a `Promise`.
+async fn call_method(
+    store_object: &JsValue,
+    name: &str,
+    args: Vec<JsValue>,
+) -> Result<JsValue, String> {
+    let method: js_sys::Function = js_sys::Reflect::get(store_object, &JsValue::from_str(name))
+        .map_err(|e| error_message(&e))?
+        .dyn_into()
+        .map_err(|_| format!("{} is not a function", name))?;
+    let args: js_sys::Array = args.into_iter().collect();
+    let result = method
+        .apply(store_object, &args)
+        .map_err(|e| error_message(&e))?;
+    JsFuture::from(js_sys::Promise::resolve(&result))
+        .await
+        .map_err(|e| error_message(&e))
+}
+
+/// Converts an argument for a store method.
+fn store_arg(value: impl ResultTypeInfo) -> Result<JsValue, String> {
+    value.convert_into().map_err(|e| error_message(&e))
+}
+
+/// Copies the value behind a handle returned by a store method.
+///
+/// # Safety
+///
+/// The store must return live handles; see [`clone_handle`].
+unsafe fn load_result<T: Clone + 'static>(value: &JsValue) -> Result<T, String> {
+    clone_handle::<T>(value)
+        .map(|handle| (**handle).clone())
+        .map_err(|_| "result must be an object".to_owned())
+}
+
+/// Like [`load_result`], but returns `None` for `null` and `undefined`.
+///
+/// # Safety
+///
+/// See [`load_result`].
+unsafe fn load_optional_result<T: Clone + 'static>(value: &JsValue) -> Result<Option<T>, String> {
+    if value.is_null() || value.is_undefined() {
+        return Ok(None);
+    }
+    load_result(value).map(Some)
+}
+
+/// Checks that a store method that shouldn't return anything didn't.
+fn expect_undefined(value: JsValue, name: &str) -> Result<(), String> {
+    if value.is_undefined() {
+        Ok(())
+    } else {
+        Err(format!("unexpected result from {}", name))
+    }
+}
+
+/// Loads a boolean returned by a store method.
+fn expect_bool(value: JsValue, name: &str) -> Result<bool, String> {
+    value
+        .as_bool()
+        .ok_or_else(|| format!("unexpected result from {}", name))
+}
+
+pub struct WasmPreKeyStore {
+    store_object: JsValue,
+}
+
+impl WasmPreKeyStore {
+    pub(crate) fn new(store: &JsValue) -> WasmResult<Self> {
+        Ok(Self {
+            store_object: check_store_object(store)?,
+        })
+    }
+
+    async fn do_get_pre_key(&self, id: u32) -> Result<PreKeyRecord, String> {
+        let result = call_method(&self.store_object, "_getPreKey", vec![store_arg(id)?]).await?;
+        unsafe { load_result(&result) }
+    }
+
+    async fn do_save_pre_key(&self, id: u32, record: PreKeyRecord) -> Result<(), String> {
+        let args = vec![store_arg(id)?, store_arg(record)?];
+        let result = call_method(&self.store_object, "_savePreKey", args).await?;
+        expect_undefined(result, "_savePreKey")
+    }
+
+    async fn do_remove_pre_key(&self, id: u32) -> Result<(), String> {
+        let result = call_method(&self.store_object, "_removePreKey", vec![store_arg(id)?]).await?;
+        expect_undefined(result, "_removePreKey")
+    }
+}
+
+#[async_trait(?Send)]
+impl PreKeyStore for WasmPreKeyStore {
+    async fn get_pre_key(
+        &self,
+        pre_key_id: u32,
+        _ctx: libsignal_protocol::Context,
+    ) -> Result<PreKeyRecord, SignalProtocolError> {
+        self.do_get_pre_key(pre_key_id)
+            .await
+            .map_err(|s| js_error_to_rust("getPreKey", s))
+    }
+
+    async fn save_pre_key(
+        &mut self,
+        pre_key_id: u32,
+        record: &PreKeyRecord,
+        _ctx: libsignal_protocol::Context,
+    ) -> Result<(), SignalProtocolError> {
+        self.do_save_pre_key(pre_key_id, record.clone())
+            .await
+            .map_err(|s| js_error_to_rust("savePreKey", s))
+    }
+
+    async fn remove_pre_key(
+        &mut self,
+        pre_key_id: u32,
+        _ctx: libsignal_protocol::Context,
+    ) -> Result<(), SignalProtocolError> {
+        self.do_remove_pre_key(pre_key_id)
+            .await
+            .map_err(|s| js_error_to_rust("removePreKey", s))
+    }
+}
+
+pub struct WasmSignedPreKeyStore {
+    store_object: JsValue,
+}
+
+impl WasmSignedPreKeyStore {
+    pub(crate) fn new(store: &JsValue) -> WasmResult<Self> {
+        Ok(Self {
+            store_object: check_store_object(store)?,
+        })
+    }
+
+    async fn do_get_signed_pre_key(&self, id: u32) -> Result<SignedPreKeyRecord, String> {
+        let args = vec![store_arg(id)?];
+        let result = call_method(&self.store_object, "_getSignedPreKey", args).await?;
+        unsafe { load_result(&result) }
+    }
+
+    async fn do_save_signed_pre_key(
+        &self,
+        id: u32,
+        record: SignedPreKeyRecord,
+    ) -> Result<(), String> {
+        let args = vec![store_arg(id)?, store_arg(record)?];
+        let result = call_method(&self.store_object, "_saveSignedPreKey", args).await?;
+        expect_undefined(result, "_saveSignedPreKey")
+    }
+}
+
+#[async_trait(?Send)]
+impl SignedPreKeyStore for WasmSignedPreKeyStore {
+    async fn get_signed_pre_key(
+        &self,
+        signed_pre_key_id: u32,
+        _ctx: libsignal_protocol::Context,
+    ) -> Result<SignedPreKeyRecord, SignalProtocolError> {
+        self.do_get_signed_pre_key(signed_pre_key_id)
+            .await
+            .map_err(|s| js_error_to_rust("getSignedPreKey", s))
+    }
+
+    async fn save_signed_pre_key(
+        &mut self,
+        signed_pre_key_id: u32,
+        record: &SignedPreKeyRecord,
+        _ctx: libsignal_protocol::Context,
+    ) -> Result<(), SignalProtocolError> {
+        self.do_save_signed_pre_key(signed_pre_key_id, record.clone())
+            .await
+            .map_err(|s| js_error_to_rust("saveSignedPreKey", s))
+    }
+}
+
+pub struct WasmSessionStore {
+    store_object: JsValue,
+}
+
+impl WasmSessionStore {
+    pub(crate) fn new(store: &JsValue) -> WasmResult<Self> {
+        Ok(Self {
+            store_object: check_store_object(store)?,
+        })
+    }
+
+    async fn do_get_session(&self, name: ProtocolAddress) -> Result<Option<SessionRecord>, String> {
+        let result = call_method(&self.store_object, "_getSession", vec![store_arg(name)?]).await?;
+        if result.is_null() || result.is_undefined() {
+            return Ok(None);
+        }
+        // SessionRecords are mutable, so their handles hold a RefCell.
+        let handle = unsafe { clone_handle::<std::cell::RefCell<SessionRecord>>(&result) }
+            .map_err(|_| "_getSession returned unexpected type".to_owned())?;
+        let record = handle
+            .try_borrow()
+            .map_err(|_| "_getSession returned a record that is being modified".to_owned())?;
+        Ok(Some(record.clone()))
+    }
+
+    async fn do_save_session(
+        &self,
+        name: ProtocolAddress,
+        record: SessionRecord,
+    ) -> Result<(), String> {
+        let args = vec![store_arg(name)?, store_arg(record)?];
+        let result = call_method(&self.store_object, "_saveSession", args).await?;
+        expect_undefined(result, "_saveSession")
+    }
+}
+
+#[async_trait(?Send)]
+impl SessionStore for WasmSessionStore {
+    async fn load_session(
+        &self,
+        name: &ProtocolAddress,
+        _ctx: libsignal_protocol::Context,
+    ) -> Result<Option<SessionRecord>, SignalProtocolError> {
+        self.do_get_session(name.clone())
+            .await
+            .map_err(|s| js_error_to_rust("getSession", s))
+    }
+
+    async fn store_session(
+        &mut self,
+        name: &ProtocolAddress,
+        record: &SessionRecord,
+        _ctx: libsignal_protocol::Context,
+    ) -> Result<(), SignalProtocolError> {
+        self.do_save_session(name.clone(), record.clone())
+            .await
+            .map_err(|s| js_error_to_rust("saveSession", s))
+    }
+}
+
+pub struct WasmIdentityKeyStore {
+    store_object: JsValue,
+}
+
+impl WasmIdentityKeyStore {
+    pub(crate) fn new(store: &JsValue) -> WasmResult<Self> {
+        Ok(Self {
+            store_object: check_store_object(store)?,
+        })
+    }
+
+    async fn do_get_identity_key(&self) -> Result<PrivateKey, String> {
+        let result = call_method(&self.store_object, "_getIdentityKey", vec![]).await?;
+        unsafe { load_result(&result) }
+    }
+
+    async fn do_get_local_registration_id(&self) -> Result<u32, String> {
+        let result = call_method(&self.store_object, "_getLocalRegistrationId", vec![]).await?;
+        result
+            .as_f64()
+            .map(|id| id as u32)
+            .ok_or_else(|| "unexpected result from _getLocalRegistrationId".to_owned())
+    }
+
+    async fn do_get_identity(&self, name: ProtocolAddress) -> Result<Option<PublicKey>, String> {
+        let result =
+            call_method(&self.store_object, "_getIdentity", vec![store_arg(name)?]).await?;
+        unsafe { load_optional_result(&result) }
+    }
+
+    async fn do_save_identity(
+        &self,
+        name: ProtocolAddress,
+        key: PublicKey,
+    ) -> Result<bool, String> {
+        let args = vec![store_arg(name)?, store_arg(key)?];
+        let result = call_method(&self.store_object, "_saveIdentity", args).await?;
+        expect_bool(result, "_saveIdentity")
+    }
+
+    async fn do_is_trusted(
+        &self,
+        name: ProtocolAddress,
+        key: PublicKey,
+        direction: Direction,
+    ) -> Result<bool, String> {
+        let sending = direction == Direction::Sending;
+        let args = vec![store_arg(name)?, store_arg(key)?, store_arg(sending)?];
+        let result = call_method(&self.store_object, "_isTrustedIdentity", args).await?;
+        expect_bool(result, "_isTrustedIdentity")
+    }
+}
+
+#[async_trait(?Send)]
+impl IdentityKeyStore for WasmIdentityKeyStore {
+    async fn get_identity_key_pair(
+        &self,
+        _ctx: libsignal_protocol::Context,
+    ) -> Result<IdentityKeyPair, SignalProtocolError> {
+        let pk = self
+            .do_get_identity_key()
+            .await
+            .map_err(|s| js_error_to_rust("getIdentityPrivateKey", s))?;
+
+        IdentityKeyPair::try_from(pk)
+    }
+
+    async fn get_local_registration_id(
+        &self,
+        _ctx: libsignal_protocol::Context,
+    ) -> Result<u32, SignalProtocolError> {
+        self.do_get_local_registration_id()
+            .await
+            .map_err(|s| js_error_to_rust("getLocalRegistrationId", s))
+    }
+
+    async fn get_identity(
+        &self,
+        address: &ProtocolAddress,
+        _ctx: libsignal_protocol::Context,
+    ) -> Result<Option<IdentityKey>, SignalProtocolError> {
+        Ok(self
+            .do_get_identity(address.clone())
+            .await
+            .map_err(|s| js_error_to_rust("getIdentity", s))?
+            .map(IdentityKey::new))
+    }
+
+    async fn save_identity(
+        &mut self,
+        address: &ProtocolAddress,
+        identity: &IdentityKey,
+        _ctx: libsignal_protocol::Context,
+    ) -> Result<bool, SignalProtocolError> {
+        self.do_save_identity(address.clone(), *identity.public_key())
+            .await
+            .map_err(|s| js_error_to_rust("saveIdentity", s))
+    }
+
+    async fn is_trusted_identity(
+        &self,
+        address: &ProtocolAddress,
+        identity: &IdentityKey,
+        direction: libsignal_protocol::Direction,
+        _ctx: libsignal_protocol::Context,
+    ) -> Result<IdentityTrust, SignalProtocolError> {
+        let trusted = self
+            .do_is_trusted(address.clone(), *identity.public_key(), direction)
+            .await
+            .map_err(|s| js_error_to_rust("isTrustedIdentity", s))?;
+        let saved = if trusted {
+            None
+        } else {
+            self.do_get_identity(address.clone())
+                .await
+                .ok()
+                .flatten()
+                .map(IdentityKey::new)
+        };
+        Ok(IdentityTrust::from_saved_key(trusted, identity, saved))
+    }
+}
+
+pub struct WasmSenderKeyStore {
+    store_object: JsValue,
+}
+
+impl WasmSenderKeyStore {
+    pub(crate) fn new(store: &JsValue) -> WasmResult<Self> {
+        Ok(Self {
+            store_object: check_store_object(store)?,
+        })
+    }
+
+    async fn do_get_sender_key(
+        &self,
+        name: SenderKeyName,
+    ) -> Result<Option<SenderKeyRecord>, String> {
+        let args = vec![store_arg(name)?];
+        let result = call_method(&self.store_object, "_getSenderKey", args).await?;
+        unsafe { load_optional_result(&result) }
+    }
+
+    async fn do_save_sender_key(
+        &self,
+        name: SenderKeyName,
+        record: SenderKeyRecord,
+    ) -> Result<(), String> {
+        let args = vec![store_arg(name)?, store_arg(record)?];
+        let result = call_method(&self.store_object, "_saveSenderKey", args).await?;
+        expect_undefined(result, "_saveSenderKey")
+    }
+}
+
+#[async_trait(?Send)]
+impl SenderKeyStore for WasmSenderKeyStore {
+    async fn load_sender_key(
+        &mut self,
+        sender_key_name: &SenderKeyName,
+        _ctx: libsignal_protocol::Context,
+    ) -> Result<Option<SenderKeyRecord>, SignalProtocolError> {
+        self.do_get_sender_key(sender_key_name.clone())
+            .await
+            .map_err(|s| js_error_to_rust("getSenderKey", s))
+    }
+
+    async fn store_sender_key(
+        &mut self,
+        sender_key_name: &SenderKeyName,
+        record: &SenderKeyRecord,
+        _ctx: libsignal_protocol::Context,
+    ) -> Result<(), SignalProtocolError> {
+        self.do_save_sender_key(sender_key_name.clone(), record.clone())
+            .await
+            .map_err(|s| js_error_to_rust("saveSenderKey", s))
+    }
+}
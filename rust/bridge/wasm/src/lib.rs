@@ -0,0 +1,11 @@
+//
+// Copyright 2021 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+//! The WebAssembly build of libsignal-client.
+//!
+//! All of the entry points are generated in `libsignal-bridge` with its `wasm` feature; this crate
+//! only links them into a module for `wasm-pack`.
+
+pub use libsignal_bridge::*;
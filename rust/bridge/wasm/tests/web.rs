@@ -0,0 +1,110 @@
+//
+// Copyright 2021 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+//! Run with `wasm-pack test --headless --chrome` (or `--firefox`, or `--node`).
+
+#![cfg(target_arch = "wasm32")]
+
+use signal_wasm::protocol::*;
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_test::*;
+
+wasm_bindgen_test_configure!(run_in_browser);
+
+fn bytes(value: &[u8]) -> JsValue {
+    js_sys::Uint8Array::from(value).into()
+}
+
+fn error_name(error: JsValue) -> String {
+    error
+        .dyn_into::<js_sys::Error>()
+        .expect("thrown value is an Error")
+        .name()
+        .into()
+}
+
+#[wasm_bindgen_test]
+fn sign_and_verify() {
+    let private_key = wasm_PrivateKey_Generate().unwrap();
+    let public_key = wasm_PrivateKey_GetPublicKey(private_key.clone()).unwrap();
+    let signature = wasm_PrivateKey_Sign(private_key.clone(), bytes(b"message")).unwrap();
+    assert!(signature.is_instance_of::<js_sys::Uint8Array>());
+
+    let valid =
+        wasm_PublicKey_Verify(public_key.clone(), bytes(b"message"), signature.clone()).unwrap();
+    assert_eq!(valid.as_bool(), Some(true));
+    let valid = wasm_PublicKey_Verify(public_key.clone(), bytes(b"massage"), signature).unwrap();
+    assert_eq!(valid.as_bool(), Some(false));
+
+    wasm_PublicKey_Destroy(public_key).unwrap();
+    wasm_PrivateKey_Destroy(private_key).unwrap();
+}
+
+#[wasm_bindgen_test]
+fn strings_and_numbers_round_trip() {
+    let address =
+        wasm_ProtocolAddress_New(JsValue::from_str("alice"), JsValue::from_f64(2.0)).unwrap();
+    assert_eq!(
+        wasm_ProtocolAddress_Name(address.clone())
+            .unwrap()
+            .as_string(),
+        Some("alice".to_owned())
+    );
+    assert_eq!(
+        wasm_ProtocolAddress_DeviceId(address.clone())
+            .unwrap()
+            .as_f64(),
+        Some(2.0)
+    );
+    wasm_ProtocolAddress_Destroy(address).unwrap();
+
+    let error =
+        wasm_ProtocolAddress_New(JsValue::from_str("alice"), JsValue::from_f64(-1.0)).unwrap_err();
+    assert_eq!(error_name(error), "RangeError");
+}
+
+#[wasm_bindgen_test]
+fn unpaired_surrogates_are_rejected() {
+    let text = js_sys::JsString::from_char_code1(0xd800);
+    let error = wasm_ProtocolAddress_New(text.into(), JsValue::from_f64(1.0)).unwrap_err();
+    assert_eq!(error_name(error), "TypeError");
+}
+
+#[wasm_bindgen_test]
+fn errors_have_names() {
+    let error = wasm_PublicKey_Deserialize(bytes(&[0xff; 4])).unwrap_err();
+    assert_eq!(error_name(error), "SignalProtocolError");
+}
+
+#[wasm_bindgen_test]
+fn handles_are_type_checked() {
+    let address =
+        wasm_ProtocolAddress_New(JsValue::from_str("alice"), JsValue::from_f64(1.0)).unwrap();
+    let error = wasm_PrivateKey_GetPublicKey(address.clone()).unwrap_err();
+    assert_eq!(error_name(error), "TypeError");
+    wasm_ProtocolAddress_Destroy(address).unwrap();
+
+    let error = wasm_PrivateKey_GetPublicKey(JsValue::from_f64(0.0)).unwrap_err();
+    assert_eq!(error_name(error), "TypeError");
+}
+
+#[wasm_bindgen_test]
+fn lists_of_handles() {
+    let records =
+        wasm_PreKeyRecord_GenerateBatch(JsValue::from_f64(5.0), JsValue::from_f64(2.0)).unwrap();
+    let records = js_sys::Array::from(&records);
+    assert_eq!(records.length(), 2);
+    for (record, expected_id) in records.iter().zip(5..) {
+        assert_eq!(
+            wasm_PreKeyRecord_GetId(record.clone()).unwrap().as_f64(),
+            Some(f64::from(expected_id))
+        );
+        wasm_PreKeyRecord_Destroy(record).unwrap();
+    }
+
+    let error = wasm_PreKeyRecord_GenerateBatch(JsValue::from_f64(5.0), JsValue::from_f64(65536.0))
+        .unwrap_err();
+    assert_eq!(error_name(error), "SignalProtocolError");
+}
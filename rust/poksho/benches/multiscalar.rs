@@ -0,0 +1,43 @@
+//
+// Copyright 2021 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use curve25519_dalek::ristretto::RistrettoPoint;
+use curve25519_dalek::scalar::Scalar;
+use curve25519_dalek::traits::Identity;
+
+fn inputs(n: usize) -> (Vec<Scalar>, Vec<RistrettoPoint>) {
+    let scalars = (0..n)
+        .map(|i| Scalar::hash_from_bytes::<sha2::Sha512>(&(2 * i as u64).to_le_bytes()))
+        .collect();
+    let points = (0..n)
+        .map(|i| RistrettoPoint::hash_from_bytes::<sha2::Sha512>(&(2 * i as u64 + 1).to_le_bytes()))
+        .collect();
+    (scalars, points)
+}
+
+pub fn multi_scalar_mul(c: &mut Criterion) {
+    let mut group = c.benchmark_group("multi_scalar_mul");
+
+    for &n in &[64, 256] {
+        let (scalars, points) = inputs(n);
+
+        group.bench_function(BenchmarkId::new("multi_scalar_mul", n), |b| {
+            b.iter(|| poksho::multi_scalar_mul(&scalars, &points))
+        });
+        group.bench_function(BenchmarkId::new("naive", n), |b| {
+            b.iter(|| {
+                scalars
+                    .iter()
+                    .zip(&points)
+                    .fold(RistrettoPoint::identity(), |sum, (s, p)| sum + s * p)
+            })
+        });
+    }
+}
+
+criterion_group!(benches, multi_scalar_mul);
+
+criterion_main!(benches);
@@ -13,3 +13,18 @@ pub enum PokshoError {
     VerificationFailure,              // Proof verification failed
     ProofCreationVerificationFailure, // Proof verification failed during proof creation, indicating bad inputs or faulty computation
 }
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum HexError {
+    OddLength,          // The hex string has an odd number of characters
+    InvalidLength,      // The hex string doesn't decode to the expected number of bytes
+    InvalidCharacter,   // The hex string contains a character other than 0-9, a-f, or A-F
+    NonCanonicalScalar, // The decoded bytes are not the canonical encoding of a scalar
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ScalarError {
+    InvalidLength, // The input is not exactly the number of bytes the scalar encoding requires
+    NonCanonical,  // The input is not the canonical encoding of a scalar
+    Zero,          // The input encodes the zero scalar, which the caller disallowed
+}
@@ -5,6 +5,7 @@
 
 pub mod args;
 pub mod errors;
+pub mod multiscalar;
 pub mod proof;
 pub mod scalar;
 pub mod shoapi;
@@ -15,9 +16,13 @@ mod simple_types;
 pub mod statement;
 
 pub use args::{PointArgs, ScalarArgs};
-pub use errors::PokshoError;
+pub use errors::{HexError, PokshoError, ScalarError};
+pub use multiscalar::multi_scalar_mul;
 pub use proof::Proof;
-pub use scalar::{scalar_from_slice_canonical, scalar_from_slice_wide};
+pub use scalar::{
+    nonzero_scalar_from_slice_canonical, scalar_ct_eq, scalar_ct_ne, scalar_from_hex,
+    scalar_from_slice_canonical, scalar_from_slice_wide, scalar_to_hex,
+};
 pub use shoapi::ShoApi;
 pub use shohmacsha256::ShoHmacSha256;
 pub use shosha256::ShoSha256;
@@ -0,0 +1,79 @@
+//
+// Copyright 2021 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+use curve25519_dalek::ristretto::RistrettoPoint;
+use curve25519_dalek::scalar::Scalar;
+use curve25519_dalek::traits::VartimeMultiscalarMul;
+
+/// Computes `Σ scalars[i] * points[i]`.
+///
+/// This is much faster than multiplying each point separately and adding up the results, using
+/// Pippenger's algorithm for large inputs (and Straus's for small ones). Like those algorithms,
+/// it is **not constant-time**, so it must only be used with public scalars, as when verifying
+/// a proof.
+///
+/// Panics if `scalars` and `points` have different lengths.
+pub fn multi_scalar_mul(scalars: &[Scalar], points: &[RistrettoPoint]) -> RistrettoPoint {
+    assert_eq!(
+        scalars.len(),
+        points.len(),
+        "must have the same number of scalars and points"
+    );
+    RistrettoPoint::vartime_multiscalar_mul(scalars, points)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use curve25519_dalek::constants::RISTRETTO_BASEPOINT_POINT;
+    use curve25519_dalek::traits::Identity;
+
+    fn naive_multi_scalar_mul(scalars: &[Scalar], points: &[RistrettoPoint]) -> RistrettoPoint {
+        scalars
+            .iter()
+            .zip(points)
+            .fold(RistrettoPoint::identity(), |sum, (s, p)| sum + s * p)
+    }
+
+    /// Deterministic, distinct inputs, so that failures are reproducible.
+    fn inputs(n: usize) -> (Vec<Scalar>, Vec<RistrettoPoint>) {
+        let scalars = (0..n)
+            .map(|i| Scalar::hash_from_bytes::<sha2::Sha512>(&(2 * i as u64).to_le_bytes()))
+            .collect();
+        let points = (0..n)
+            .map(|i| {
+                RistrettoPoint::hash_from_bytes::<sha2::Sha512>(&(2 * i as u64 + 1).to_le_bytes())
+            })
+            .collect();
+        (scalars, points)
+    }
+
+    #[test]
+    fn test_multi_scalar_mul() {
+        assert_eq!(multi_scalar_mul(&[], &[]), RistrettoPoint::identity());
+        assert_eq!(
+            multi_scalar_mul(&[Scalar::from(3u8)], &[RISTRETTO_BASEPOINT_POINT]),
+            RISTRETTO_BASEPOINT_POINT + RISTRETTO_BASEPOINT_POINT + RISTRETTO_BASEPOINT_POINT
+        );
+
+        // Cover both sides of dalek's switch from Straus to Pippenger (at 190 points).
+        for &n in &[1, 2, 63, 64, 189, 190, 256] {
+            let (scalars, points) = inputs(n);
+            assert_eq!(
+                multi_scalar_mul(&scalars, &points),
+                naive_multi_scalar_mul(&scalars, &points),
+                "n = {}",
+                n
+            );
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "same number of scalars and points")]
+    fn test_multi_scalar_mul_length_mismatch() {
+        let (scalars, points) = inputs(2);
+        multi_scalar_mul(&scalars, &points[..1]);
+    }
+}
@@ -19,10 +19,10 @@ impl Proof {
         if !(2..=257).contains(&num_scalars) || num_scalars * 32 != bytes.len() {
             return None;
         }
-        let challenge = scalar_from_slice_canonical(&bytes[0..32])?;
+        let challenge = scalar_from_slice_canonical(&bytes[0..32]).ok()?;
         let mut response = Vec::<Scalar>::with_capacity(num_scalars - 1);
         for i in 1..num_scalars {
-            response.push(scalar_from_slice_canonical(&bytes[32 * i..(32 * i) + 32])?);
+            response.push(scalar_from_slice_canonical(&bytes[32 * i..(32 * i) + 32]).ok()?);
         }
         Some(Proof {
             challenge,
@@ -3,18 +3,264 @@
 // SPDX-License-Identifier: AGPL-3.0-only
 //
 
+use crate::errors::{HexError, ScalarError};
 use curve25519_dalek::scalar::Scalar;
+use subtle::ConstantTimeEq;
 
-// Because Rust can't create array references from slices (yet)
-pub fn scalar_from_slice_wide(bytes: &[u8]) -> Scalar {
+/// Reduces 64 bytes (such as hash output) to a scalar.
+///
+/// Returns `ScalarError::InvalidLength` rather than panicking if `bytes` is not exactly 64 bytes.
+pub fn scalar_from_slice_wide(bytes: &[u8]) -> Result<Scalar, ScalarError> {
     let mut scalar_bytes = [0u8; 64];
+    if bytes.len() != scalar_bytes.len() {
+        return Err(ScalarError::InvalidLength);
+    }
     scalar_bytes.copy_from_slice(bytes);
-    Scalar::from_bytes_mod_order_wide(&scalar_bytes)
+    Ok(Scalar::from_bytes_mod_order_wide(&scalar_bytes))
 }
 
-// Because Rust can't create array references from slices (yet)
-pub fn scalar_from_slice_canonical(bytes: &[u8]) -> Option<Scalar> {
+/// Decodes a scalar from its 32-byte canonical encoding.
+///
+/// Returns `ScalarError::InvalidLength` rather than panicking if `bytes` is not exactly 32 bytes,
+/// so this is safe to call directly on wire input.
+pub fn scalar_from_slice_canonical(bytes: &[u8]) -> Result<Scalar, ScalarError> {
     let mut scalar_bytes = [0u8; 32];
+    if bytes.len() != scalar_bytes.len() {
+        return Err(ScalarError::InvalidLength);
+    }
     scalar_bytes.copy_from_slice(bytes);
-    Scalar::from_canonical_bytes(scalar_bytes)
+    Scalar::from_canonical_bytes(scalar_bytes).ok_or(ScalarError::NonCanonical)
+}
+
+/// Like [`scalar_from_slice_canonical`], but also rejects the zero scalar.
+///
+/// The comparison against zero is constant-time; only the final accept/reject decision is
+/// observable.
+pub fn nonzero_scalar_from_slice_canonical(bytes: &[u8]) -> Result<Scalar, ScalarError> {
+    let scalar = scalar_from_slice_canonical(bytes)?;
+    if scalar_ct_eq(&scalar, &Scalar::zero()) {
+        return Err(ScalarError::Zero);
+    }
+    Ok(scalar)
+}
+
+/// Decodes a scalar from 64 hex characters (either case), in the same little-endian byte order
+/// as `scalar_from_slice_canonical`.
+pub fn scalar_from_hex(s: &str) -> Result<Scalar, HexError> {
+    fn nibble(c: u8) -> Result<u8, HexError> {
+        match c {
+            b'0'..=b'9' => Ok(c - b'0'),
+            b'a'..=b'f' => Ok(c - b'a' + 10),
+            b'A'..=b'F' => Ok(c - b'A' + 10),
+            _ => Err(HexError::InvalidCharacter),
+        }
+    }
+
+    let s = s.as_bytes();
+    if s.len() % 2 != 0 {
+        return Err(HexError::OddLength);
+    }
+    if s.len() != 64 {
+        return Err(HexError::InvalidLength);
+    }
+    let mut bytes = [0u8; 32];
+    for (byte, pair) in bytes.iter_mut().zip(s.chunks(2)) {
+        *byte = (nibble(pair[0])? << 4) | nibble(pair[1])?;
+    }
+    scalar_from_slice_canonical(&bytes).map_err(|_| HexError::NonCanonicalScalar)
+}
+
+/// Encodes a scalar as 64 lowercase hex characters, the inverse of `scalar_from_hex`.
+pub fn scalar_to_hex(s: &Scalar) -> String {
+    s.as_bytes().iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Compares two scalars in constant time.
+///
+/// `Scalar`'s `==` is currently constant-time too, since curve25519-dalek implements `PartialEq`
+/// through `ConstantTimeEq`, but that is an implementation detail rather than part of its API.
+/// Use this (or [`scalar_ct_ne`]) whenever either side might be secret (keys, nonces, proof
+/// responses), so that the call site states the requirement and keeps it if `Scalar` changes.
+pub fn scalar_ct_eq(a: &Scalar, b: &Scalar) -> bool {
+    a.ct_eq(b).into()
+}
+
+/// Compares two scalars for inequality in constant time; see [`scalar_ct_eq`].
+pub fn scalar_ct_ne(a: &Scalar, b: &Scalar) -> bool {
+    !scalar_ct_eq(a, b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scalar_hex_round_trip() {
+        let one_hex = "0100000000000000000000000000000000000000000000000000000000000000";
+        assert_eq!(scalar_from_hex(one_hex), Ok(Scalar::one()));
+        assert_eq!(scalar_to_hex(&Scalar::one()), one_hex);
+
+        // The largest canonical scalar, l - 1.
+        let l_minus_one_hex = "ecd3f55c1a631258d69cf7a2def9de1400000000000000000000000000000010";
+        assert_eq!(
+            scalar_from_hex(l_minus_one_hex),
+            Ok(Scalar::zero() - Scalar::one())
+        );
+        assert_eq!(
+            scalar_to_hex(&(Scalar::zero() - Scalar::one())),
+            l_minus_one_hex
+        );
+    }
+
+    #[test]
+    fn test_scalar_from_hex_case() {
+        let lower = "a08f6b34a282dd4c7cfc40b918f224a6b631ca5f6480a10b42bd1408602a7e00";
+        let upper = lower.to_uppercase();
+        let mixed = "A08f6B34a282DD4c7cfc40b918f224a6b631ca5f6480a10b42bd1408602a7E00";
+        let expected = scalar_from_hex(lower).unwrap();
+        assert_eq!(scalar_from_hex(&upper), Ok(expected));
+        assert_eq!(scalar_from_hex(mixed), Ok(expected));
+        assert_eq!(scalar_to_hex(&expected), lower);
+    }
+
+    #[test]
+    fn test_scalar_from_hex_test_vector() {
+        // The challenge scalar at the start of the signature in sign::tests::test_signature.
+        let challenge_hex = "a08f6b34a282dd4c7cfc40b918f224a6b631ca5f6480a10b42bd1408602a7e00";
+        let challenge_bytes = [
+            0xa0, 0x8f, 0x6b, 0x34, 0xa2, 0x82, 0xdd, 0x4c, 0x7c, 0xfc, 0x40, 0xb9, 0x18, 0xf2,
+            0x24, 0xa6, 0xb6, 0x31, 0xca, 0x5f, 0x64, 0x80, 0xa1, 0x0b, 0x42, 0xbd, 0x14, 0x08,
+            0x60, 0x2a, 0x7e, 0x00,
+        ];
+        assert_eq!(
+            scalar_from_hex(challenge_hex),
+            Ok(scalar_from_slice_canonical(&challenge_bytes).unwrap())
+        );
+    }
+
+    #[test]
+    fn test_scalar_from_hex_errors() {
+        assert_eq!(scalar_from_hex("0"), Err(HexError::OddLength));
+        assert_eq!(scalar_from_hex(&"0".repeat(63)), Err(HexError::OddLength));
+        assert_eq!(scalar_from_hex(""), Err(HexError::InvalidLength));
+        assert_eq!(
+            scalar_from_hex(&"00".repeat(33)),
+            Err(HexError::InvalidLength)
+        );
+        assert_eq!(
+            scalar_from_hex(&"0g".repeat(32)),
+            Err(HexError::InvalidCharacter)
+        );
+        // l itself is not canonical.
+        assert_eq!(
+            scalar_from_hex("edd3f55c1a631258d69cf7a2def9de1400000000000000000000000000000010"),
+            Err(HexError::NonCanonicalScalar)
+        );
+    }
+
+    #[test]
+    fn test_scalar_from_slice_wide_length() {
+        let mut one_bytes = [0u8; 64];
+        one_bytes[0] = 1;
+        assert_eq!(scalar_from_slice_wide(&one_bytes), Ok(Scalar::one()));
+        // 2^256 reduced mod l, to make sure the upper half is used.
+        let mut wide_bytes = [0u8; 64];
+        wide_bytes[32] = 1;
+        assert_eq!(
+            scalar_from_slice_wide(&wide_bytes),
+            Ok(Scalar::from_bytes_mod_order_wide(&wide_bytes))
+        );
+
+        assert_eq!(scalar_from_slice_wide(&[]), Err(ScalarError::InvalidLength));
+        assert_eq!(
+            scalar_from_slice_wide(&[0u8; 63]),
+            Err(ScalarError::InvalidLength)
+        );
+        assert_eq!(
+            scalar_from_slice_wide(&[0u8; 65]),
+            Err(ScalarError::InvalidLength)
+        );
+    }
+
+    #[test]
+    fn test_scalar_from_slice_canonical_length() {
+        let one_bytes = Scalar::one().to_bytes();
+        assert_eq!(scalar_from_slice_canonical(&one_bytes), Ok(Scalar::one()));
+        assert_eq!(
+            scalar_from_slice_canonical(&[]),
+            Err(ScalarError::InvalidLength)
+        );
+        assert_eq!(
+            scalar_from_slice_canonical(&one_bytes[..31]),
+            Err(ScalarError::InvalidLength)
+        );
+        assert_eq!(
+            scalar_from_slice_canonical(&[0u8; 33]),
+            Err(ScalarError::InvalidLength)
+        );
+        assert_eq!(
+            scalar_from_slice_canonical(&[0xffu8; 32]),
+            Err(ScalarError::NonCanonical)
+        );
+    }
+
+    #[test]
+    fn test_nonzero_scalar_from_slice_canonical() {
+        let one_bytes = Scalar::one().to_bytes();
+        assert_eq!(
+            nonzero_scalar_from_slice_canonical(&one_bytes),
+            Ok(Scalar::one())
+        );
+        assert_eq!(
+            nonzero_scalar_from_slice_canonical(&[0u8; 32]),
+            Err(ScalarError::Zero)
+        );
+        assert_eq!(
+            nonzero_scalar_from_slice_canonical(&[0u8; 31]),
+            Err(ScalarError::InvalidLength)
+        );
+        assert_eq!(
+            nonzero_scalar_from_slice_canonical(&[0u8; 64]),
+            Err(ScalarError::InvalidLength)
+        );
+        // l itself reduces to zero, but is rejected as non-canonical first.
+        let l_bytes = [
+            0xed, 0xd3, 0xf5, 0x5c, 0x1a, 0x63, 0x12, 0x58, 0xd6, 0x9c, 0xf7, 0xa2, 0xde, 0xf9,
+            0xde, 0x14, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x10,
+        ];
+        assert_eq!(
+            nonzero_scalar_from_slice_canonical(&l_bytes),
+            Err(ScalarError::NonCanonical)
+        );
+    }
+
+    #[test]
+    fn test_scalar_ct_eq() {
+        // Run under Miri too (`cargo +nightly miri test -p poksho scalar_ct`), which will catch
+        // any undefined behavior in the comparison.
+        let zero = Scalar::zero();
+        let one = Scalar::one();
+        let l_minus_one = Scalar::zero() - Scalar::one();
+        // Differs from l - 1 only in its first byte.
+        let l_minus_two = l_minus_one - Scalar::one();
+
+        for s in &[zero, one, l_minus_one, l_minus_two] {
+            assert!(scalar_ct_eq(s, s));
+            assert!(!scalar_ct_ne(s, s));
+            assert!(scalar_ct_eq(s, &Scalar::from_bits(s.to_bytes())));
+        }
+
+        for (a, b) in &[
+            (zero, one),
+            (one, l_minus_one),
+            (l_minus_one, l_minus_two),
+            (zero, l_minus_one),
+        ] {
+            assert!(!scalar_ct_eq(a, b));
+            assert!(!scalar_ct_eq(b, a));
+            assert!(scalar_ct_ne(a, b));
+            assert!(scalar_ct_ne(b, a));
+        }
+    }
 }
@@ -3,6 +3,15 @@
 // SPDX-License-Identifier: AGPL-3.0-only
 //
 
+/// A stateful hash object: a hash function that can absorb input and squeeze output repeatedly.
+///
+/// Each `absorb_and_ratchet` and `squeeze_and_ratchet` call updates the object's state, so every
+/// output depends on the label, on all the input absorbed so far, and on all the output squeezed so
+/// far. This makes a SHO a convenient domain-separated KDF or PRF: start with a label unique to the
+/// purpose, absorb the inputs (keys, context) one at a time, then squeeze as much output as needed.
+///
+/// The construction is fully deterministic. The same label and the same sequence of calls always
+/// produce the same output bytes, which the implementations' tests pin down.
 pub trait ShoApi
 where
     Self: Sized,
@@ -28,4 +37,15 @@ where
     // pub fn squeeze(&mut self, _outlen: usize) -> Vec<u8>;
 
     fn squeeze_and_ratchet(&mut self, outlen: usize) -> Vec<u8>;
+
+    /// Like `squeeze_and_ratchet`, but fills a fixed-size array such as `[u8; 32]`.
+    ///
+    /// `sho.squeeze_to_array::<[u8; N]>()` produces the same bytes as `sho.squeeze_and_ratchet(N)`.
+    /// Any `Default` byte container works, which for arrays means sizes up to 32.
+    fn squeeze_to_array<A: Default + AsMut<[u8]>>(&mut self) -> A {
+        let mut output = A::default();
+        let bytes = output.as_mut();
+        bytes.copy_from_slice(&self.squeeze_and_ratchet(bytes.len()));
+        output
+    }
 }
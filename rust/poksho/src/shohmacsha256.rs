@@ -149,4 +149,36 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn test_squeeze_to_array_vectors() {
+        let mut sho = ShoHmacSha256::new(b"LibSignal_Test_KDF");
+        sho.absorb_and_ratchet(b"first input");
+        sho.absorb_and_ratchet(b"");
+        sho.absorb_and_ratchet(&[0xa5u8; 100]);
+        let out: [u8; 32] = sho.squeeze_to_array();
+        assert_eq!(
+            hex::encode(out),
+            "94d745a6152528e193adabc841cf5edf67ca5e8622748e4bebd65166349fd093"
+        );
+        let out: [u8; 16] = sho.squeeze_to_array();
+        assert_eq!(hex::encode(out), "405099cc1eca7ca77c9d125964d86e08");
+
+        sho.absorb_and_ratchet(b"second input");
+        let out = sho.squeeze_and_ratchet(64);
+        assert_eq!(
+            hex::encode(out),
+            "2831fcd64f80e945d37e761401948025aa3b6819073d10a989a0f9e866cc188d\
+             c1b7c29bafe35c9ec6cfe474df9fa2fdb113e3d06e23b0978431e51f7f218f9a"
+        );
+
+        // The array is the same as the equivalent Vec output.
+        let mut sho = ShoHmacSha256::new(b"asd");
+        sho.absorb_and_ratchet(b"asdasd");
+        let mut sho_clone = sho.clone();
+        assert_eq!(
+            &sho.squeeze_to_array::<[u8; 31]>()[..],
+            &sho_clone.squeeze_and_ratchet(31)[..]
+        );
+    }
 }
@@ -156,4 +156,36 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn test_squeeze_to_array_vectors() {
+        let mut sho = ShoSha256::new(b"LibSignal_Test_KDF");
+        sho.absorb_and_ratchet(b"first input");
+        sho.absorb_and_ratchet(b"");
+        sho.absorb_and_ratchet(&[0xa5u8; 100]);
+        let out: [u8; 32] = sho.squeeze_to_array();
+        assert_eq!(
+            hex::encode(out),
+            "f04e056f712e70736fe54182a57af95c193c2eaeb8be70f083c620e2bbe7db68"
+        );
+        let out: [u8; 16] = sho.squeeze_to_array();
+        assert_eq!(hex::encode(out), "e7464eb54730a1e3243e6099cadfec75");
+
+        sho.absorb_and_ratchet(b"second input");
+        let out = sho.squeeze_and_ratchet(64);
+        assert_eq!(
+            hex::encode(out),
+            "ae965e0fa4e11cc3a71a6c8869ab0a0245af5043545e507f31a98dbf41c2b9f5\
+             622301666b4ef5b7519acd5c4397efc5b4df3eba4cfe361da94dfe6083f75954"
+        );
+
+        // The array is the same as the equivalent Vec output.
+        let mut sho = ShoSha256::new(b"asd");
+        sho.absorb_and_ratchet(b"asdasd");
+        let mut sho_clone = sho.clone();
+        assert_eq!(
+            &sho.squeeze_to_array::<[u8; 31]>()[..],
+            &sho_clone.squeeze_and_ratchet(31)[..]
+        );
+    }
 }
@@ -165,6 +165,17 @@ impl Statement {
         self.equations.push(Equation { lhs, rhs });
     }
 
+    /// Builder-style variant of [`add`](Statement::add), for declaring a whole statement in one
+    /// expression.
+    ///
+    /// All equations in a statement are proven together under a single Fiat-Shamir challenge.
+    ///
+    /// Panics on invalid input.
+    pub fn with_equation(mut self, lhs_str: &str, rhs_pairs: &[(&str, &str)]) -> Self {
+        self.add(lhs_str, rhs_pairs);
+        self
+    }
+
     pub fn prove(
         &self,
         scalar_args: &ScalarArgs,
@@ -201,9 +212,10 @@ impl Statement {
 
         let mut nonce = self.g1_new();
         for i in 0..g1.len() {
-            nonce.push(scalar_from_slice_wide(
-                &blinding_scalar_bytes[i * 64..(i + 1) * 64],
-            ))
+            nonce.push(
+                scalar_from_slice_wide(&blinding_scalar_bytes[i * 64..(i + 1) * 64])
+                    .map_err(|_| ProofCreationVerificationFailure)?,
+            )
         }
 
         // Commitment from nonce by applying homomorphism F: commitment = F(nonce)
@@ -214,7 +226,8 @@ impl Statement {
             sho.absorb(&point.compress().to_bytes());
         }
         sho.absorb_and_ratchet(message);
-        let challenge = scalar_from_slice_wide(&sho.squeeze_and_ratchet(64));
+        let challenge = scalar_from_slice_wide(&sho.squeeze_and_ratchet(64))
+            .map_err(|_| ProofCreationVerificationFailure)?;
 
         // Response
         let mut response = self.g1_new();
@@ -275,7 +288,8 @@ impl Statement {
             sho.absorb(&point.compress().to_bytes());
         }
         sho.absorb_and_ratchet(message); // M
-        let challenge = scalar_from_slice_wide(&sho.squeeze_and_ratchet(64));
+        let challenge = scalar_from_slice_wide(&sho.squeeze_and_ratchet(64))
+            .map_err(|_| VerificationFailure)?;
 
         // Check challenge (const time)
         if challenge == proof.challenge {
@@ -445,6 +459,91 @@ mod tests {
         assert!(s.to_bytes() == vec![1, 1, 2, 0, 0, 1, 2]);
     }
 
+    #[test]
+    fn test_two_equation_statement() {
+        let H = Scalar::from_bytes_mod_order_wide(&[1u8; 64]) * RISTRETTO_BASEPOINT_POINT;
+        let a = Scalar::from_bytes_mod_order_wide(&[2u8; 64]);
+        let randomness = [7u8; 32];
+        let message = b"two equations";
+
+        // Discrete-log equality: the same "a" relates A to G and B to H.
+        let st = Statement::new()
+            .with_equation("A", &[("a", "G")])
+            .with_equation("B", &[("a", "H")]);
+        assert!(st.to_bytes() == vec![2, 1, 1, 0, 0, 2, 1, 0, 3]);
+
+        let mut scalar_args = ScalarArgs::new();
+        scalar_args.add("a", a);
+        let mut point_args = PointArgs::new();
+        point_args.add("A", a * RISTRETTO_BASEPOINT_POINT);
+        point_args.add("B", a * H);
+        point_args.add("H", H);
+
+        let proof = st
+            .prove(&scalar_args, &point_args, message, &randomness)
+            .unwrap();
+        // One challenge plus one response per scalar.
+        assert_eq!(proof.len(), 64);
+        st.verify_proof(&proof, &point_args, message).unwrap();
+
+        // A witness that only satisfies the first equation can't produce a proof.
+        let mut bad_point_args = point_args.clone();
+        bad_point_args.add("B", a * RISTRETTO_BASEPOINT_POINT);
+        assert!(matches!(
+            st.prove(&scalar_args, &bad_point_args, message, &randomness),
+            Err(ProofCreationVerificationFailure)
+        ));
+        assert!(matches!(
+            st.verify_proof(&proof, &bad_point_args, message),
+            Err(VerificationFailure)
+        ));
+    }
+
+    #[test]
+    fn test_tampered_proof_rejected() {
+        let H = Scalar::from_bytes_mod_order_wide(&[3u8; 64]) * RISTRETTO_BASEPOINT_POINT;
+        let a = Scalar::from_bytes_mod_order_wide(&[4u8; 64]);
+        let b = Scalar::from_bytes_mod_order_wide(&[5u8; 64]);
+        let randomness = [9u8; 32];
+        let message = b"tampering";
+
+        let st = Statement::new()
+            .with_equation("A", &[("a", "G"), ("b", "H")])
+            .with_equation("B", &[("b", "G")]);
+
+        let mut scalar_args = ScalarArgs::new();
+        scalar_args.add("a", a);
+        scalar_args.add("b", b);
+        let mut point_args = PointArgs::new();
+        point_args.add("A", a * RISTRETTO_BASEPOINT_POINT + b * H);
+        point_args.add("B", b * RISTRETTO_BASEPOINT_POINT);
+        point_args.add("H", H);
+
+        let proof = st
+            .prove(&scalar_args, &point_args, message, &randomness)
+            .unwrap();
+        st.verify_proof(&proof, &point_args, message).unwrap();
+
+        // Flipping a low bit keeps every scalar canonical, so these all reach the challenge check.
+        for i in (0..proof.len()).step_by(32) {
+            let mut tampered = proof.clone();
+            tampered[i] ^= 1;
+            assert!(matches!(
+                st.verify_proof(&tampered, &point_args, message),
+                Err(VerificationFailure)
+            ));
+        }
+
+        // Swapping the two responses is also rejected.
+        let mut swapped = proof.clone();
+        swapped[32..64].copy_from_slice(&proof[64..96]);
+        swapped[64..96].copy_from_slice(&proof[32..64]);
+        assert!(matches!(
+            st.verify_proof(&swapped, &point_args, message),
+            Err(VerificationFailure)
+        ));
+    }
+
     #[test]
     #[allow(clippy::needless_range_loop, clippy::redundant_clone)]
     fn test_complex_statement() {
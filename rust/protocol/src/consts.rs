@@ -3,8 +3,35 @@
 // SPDX-License-Identifier: AGPL-3.0-only
 //
 
+/// The default limit on how many message keys a single incoming message may skip over; see
+/// `SessionRecord::set_max_skipped_message_keys`.
 pub const MAX_FORWARD_JUMPS: usize = 25_000;
 pub const MAX_MESSAGE_KEYS: usize = 2000;
 pub const MAX_RECEIVER_CHAINS: usize = 5;
 pub const ARCHIVED_STATES_MAX_LENGTH: usize = 40;
 pub const MAX_SENDER_KEY_STATES: usize = 5;
+
+// Limits on the size of serialized protobufs, checked before decoding so that a malformed input
+// can't make prost allocate arbitrarily large buffers. Each is comfortably above the largest
+// legitimate encoding of the types it applies to.
+
+/// The largest serialized protobuf this crate will decode, whatever its type.
+///
+/// This is sized for `SessionRecord`s: with [`ARCHIVED_STATES_MAX_LENGTH`] archived sessions,
+/// each with [`MAX_RECEIVER_CHAINS`] chains holding [`MAX_MESSAGE_KEYS`] skipped keys (about 90
+/// bytes apiece), a record can legitimately approach 40 MiB.
+pub const MAX_PROTO_BYTES: usize = 64 * 1024 * 1024;
+/// Limit for `SenderKeyRecord`s and their states: [`MAX_SENDER_KEY_STATES`] states with
+/// [`MAX_MESSAGE_KEYS`] skipped keys (about 40 bytes apiece) comes to under 0.5 MiB.
+pub const MAX_SENDER_KEY_RECORD_PROTO_BYTES: usize = 4 * 1024 * 1024;
+/// The largest plaintext `message_encrypt` and `group_encrypt` will accept.
+pub const MAX_MESSAGE_PLAINTEXT_BYTES: usize = 1024 * 1024;
+/// Limit for messages received over the wire, including sealed sender envelopes.
+///
+/// This leaves room above [`MAX_MESSAGE_PLAINTEXT_BYTES`] for padding, MACs, and signatures,
+/// plus a sender certificate (at most [`MAX_SMALL_PROTO_BYTES`]) and the sealed sender framing,
+/// so that anything the encrypt paths produce can be decoded again.
+pub const MAX_MESSAGE_PROTO_BYTES: usize = MAX_MESSAGE_PLAINTEXT_BYTES + 64 * 1024;
+/// Limit for small structures with no repeated fields: keys, pre-keys, certificates, and
+/// fingerprints.
+pub const MAX_SMALL_PROTO_BYTES: usize = 16 * 1024;
@@ -5,9 +5,11 @@
 
 use crate::{error::Result, SignalProtocolError};
 
+use aes::cipher::generic_array::GenericArray;
 use aes::cipher::stream::{NewStreamCipher, SyncStreamCipher};
 use aes::Aes256;
-use block_modes::{block_padding::Pkcs7, BlockMode, Cbc};
+use block_modes::block_padding::{Padding, Pkcs7};
+use block_modes::{BlockMode, Cbc};
 use ctr::Ctr128;
 use hmac::{Hmac, Mac, NewMac};
 use sha2::Sha256;
@@ -61,6 +63,59 @@ pub fn aes_256_cbc_decrypt(ctext: &[u8], key: &[u8], iv: &[u8]) -> Result<Vec<u8
         .map_err(|_| SignalProtocolError::InvalidCiphertext)?)
 }
 
+/// Decrypts AES-256-CBC ciphertext with PKCS#7 padding a piece at a time.
+///
+/// Produces the same plaintext as [`aes_256_cbc_decrypt`], spread across the calls to
+/// [`update`](Self::update) and [`finalize`](Self::finalize).
+pub struct Aes256CbcDecryptor {
+    mode: Cbc<Aes256, Pkcs7>,
+    /// Ciphertext that hasn't been decrypted yet: a partial block, or the most recent full block,
+    /// which may turn out to be the last one and so has to be unpadded.
+    pending: Vec<u8>,
+}
+
+impl Aes256CbcDecryptor {
+    const BLOCK_SIZE: usize = 16;
+
+    pub fn new(key: &[u8], iv: &[u8]) -> Result<Self> {
+        match Cbc::<Aes256, Pkcs7>::new_var(key, iv) {
+            Ok(mode) => Ok(Self {
+                mode,
+                pending: Vec::with_capacity(Self::BLOCK_SIZE),
+            }),
+            Err(block_modes::InvalidKeyIvLength) => Err(
+                SignalProtocolError::InvalidCipherCryptographicParameters(key.len(), iv.len()),
+            ),
+        }
+    }
+
+    /// Decrypts as much of the ciphertext so far as possible, always holding back the last block.
+    pub fn update(&mut self, ctext: &[u8]) -> Vec<u8> {
+        self.pending.extend_from_slice(ctext);
+        let ready_len = self.pending.len().saturating_sub(1) / Self::BLOCK_SIZE * Self::BLOCK_SIZE;
+        let mut ptext: Vec<u8> = self.pending.drain(..ready_len).collect();
+        for block in ptext.chunks_exact_mut(Self::BLOCK_SIZE) {
+            self.mode
+                .decrypt_blocks(std::slice::from_mut(GenericArray::from_mut_slice(block)));
+        }
+        ptext
+    }
+
+    /// Decrypts and unpads the last block.
+    pub fn finalize(mut self) -> Result<Vec<u8>> {
+        if self.pending.len() != Self::BLOCK_SIZE {
+            return Err(SignalProtocolError::InvalidCiphertext);
+        }
+        self.mode
+            .decrypt_blocks(std::slice::from_mut(GenericArray::from_mut_slice(
+                &mut self.pending,
+            )));
+        Ok(Pkcs7::unpad(&self.pending)
+            .map_err(|_| SignalProtocolError::InvalidCiphertext)?
+            .to_vec())
+    }
+}
+
 pub fn hmac_sha256(key: &[u8], input: &[u8]) -> Result<[u8; 32]> {
     let mut hmac = Hmac::<Sha256>::new_varkey(key).expect("HMAC-SHA256 should accept any size key");
     hmac.update(input);
@@ -127,6 +182,34 @@ mod test {
         assert_eq!(hex::encode(recovered), "b0736294a124482a4159");
     }
 
+    #[test]
+    fn aes_cbc_streaming_test() {
+        let key = hex::decode("4e22eb16d964779994222e82192ce9f747da72dc4abe49dfdeeb71d0ffe3796e")
+            .unwrap();
+        let iv = hex::decode("6f8a557ddc0a140c878063a6d5f31d3d").unwrap();
+
+        for ptext_len in &[0, 1, 15, 16, 17, 100] {
+            let ptext: Vec<u8> = (0..*ptext_len).map(|i| i as u8).collect();
+            let ctext = super::aes_256_cbc_encrypt(&ptext, &key, &iv).unwrap();
+
+            for chunk_size in &[1, 7, 16, 33, ctext.len()] {
+                let mut decryptor = super::Aes256CbcDecryptor::new(&key, &iv).unwrap();
+                let mut recovered = vec![];
+                for chunk in ctext.chunks(*chunk_size) {
+                    recovered.extend(decryptor.update(chunk));
+                }
+                recovered.extend(decryptor.finalize().unwrap());
+                assert_eq!(hex::encode(&ptext), hex::encode(recovered));
+            }
+        }
+
+        // truncated:
+        let ctext = super::aes_256_cbc_encrypt(&[0u8; 20], &key, &iv).unwrap();
+        let mut decryptor = super::Aes256CbcDecryptor::new(&key, &iv).unwrap();
+        assert_eq!(decryptor.update(&ctext[..ctext.len() - 1]).len(), 16);
+        assert!(decryptor.finalize().is_err());
+    }
+
     #[test]
     fn aes_ctr_test() {
         let key = hex::decode("603DEB1015CA71BE2B73AEF0857D77811F352C073B6108D72D9810A30914DFF4")
@@ -14,6 +14,7 @@ use std::fmt;
 use arrayref::array_ref;
 use rand::{CryptoRng, Rng};
 use subtle::ConstantTimeEq;
+use zeroize::Zeroizing;
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub enum KeyType {
@@ -113,6 +114,62 @@ impl PublicKey {
         }
     }
 
+    /// Verifies several signatures made with this key, returning the index of the first invalid
+    /// one, or `None` if they are all valid.
+    ///
+    /// See [`verify_signatures`](Self::verify_signatures).
+    pub fn verify_signature_batch<R: CryptoRng + Rng>(
+        &self,
+        messages_and_signatures: &[(&[u8], &[u8])],
+        csprng: &mut R,
+    ) -> Result<Option<usize>> {
+        let items: Vec<_> = messages_and_signatures
+            .iter()
+            .map(|(message, signature)| (self, *message, *signature))
+            .collect();
+        Self::verify_signatures(&items, csprng)
+    }
+
+    /// Verifies several signatures, possibly made with different keys, returning the index of the
+    /// first invalid one, or `None` if they are all valid.
+    ///
+    /// The signatures are first checked together, which is much faster than checking each one
+    /// with [`verify_signature`](Self::verify_signature). Only if that fails are they checked one
+    /// at a time to find the invalid one.
+    ///
+    /// The combined check can be fooled by a signer who deliberately adds small-order components
+    /// to their key or signature. Use `verify_signature` when every party must reach the same
+    /// verdict about such a signature.
+    pub fn verify_signatures<R: CryptoRng + Rng>(
+        items: &[(&PublicKey, &[u8], &[u8])],
+        csprng: &mut R,
+    ) -> Result<Option<usize>> {
+        let mut djb_items = Vec::with_capacity(items.len());
+        for (key, message, signature) in items {
+            match key.key {
+                PublicKeyData::DjbPublicKey(ref pub_key) => {
+                    if signature.len() != 64 {
+                        break;
+                    }
+                    djb_items.push((pub_key, *message, array_ref![signature, 0, 64]));
+                }
+            }
+        }
+
+        if djb_items.len() == items.len()
+            && curve25519::KeyPair::verify_signature_batch(&djb_items, csprng)
+        {
+            return Ok(None);
+        }
+
+        for (i, (key, message, signature)) in items.iter().enumerate() {
+            if !key.verify_signature(message, signature)? {
+                return Ok(Some(i));
+            }
+        }
+        Ok(None)
+    }
+
     fn key_data(&self) -> &[u8] {
         match self.key {
             PublicKeyData::DjbPublicKey(ref k) => k.as_ref(),
@@ -173,12 +230,17 @@ impl fmt::Debug for PublicKey {
     }
 }
 
-#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq)]
 enum PrivateKeyData {
-    DjbPrivateKey([u8; 32]),
+    DjbPrivateKey(Zeroizing<[u8; 32]>),
 }
 
-#[derive(Clone, Copy, Eq, PartialEq)]
+/// A private key, whose bytes are zeroed when it is dropped.
+///
+/// This type is deliberately not `Copy`, so that each copy of the key material is explicit and
+/// gets zeroed in turn.
+#[derive(Clone, Eq, PartialEq)]
+#[must_use]
 pub struct PrivateKey {
     key: PrivateKeyData,
 }
@@ -188,7 +250,7 @@ impl PrivateKey {
         if value.len() != 32 {
             Err(SignalProtocolError::BadKeyLength(KeyType::Djb, value.len()))
         } else {
-            let mut key = [0u8; 32];
+            let mut key = Zeroizing::new([0u8; 32]);
             key.copy_from_slice(&value[..32]);
             // Clamp:
             key[0] &= 0xF8;
@@ -201,22 +263,22 @@ impl PrivateKey {
     }
 
     pub fn serialize(&self) -> Vec<u8> {
-        match self.key {
+        match &self.key {
             PrivateKeyData::DjbPrivateKey(v) => v.to_vec(),
         }
     }
 
     pub fn public_key(&self) -> Result<PublicKey> {
-        match self.key {
+        match &self.key {
             PrivateKeyData::DjbPrivateKey(private_key) => {
-                let public_key = curve25519::derive_public_key(&private_key);
+                let public_key = curve25519::derive_public_key(private_key);
                 Ok(PublicKey::new(PublicKeyData::DjbPublicKey(public_key)))
             }
         }
     }
 
     pub fn key_type(&self) -> KeyType {
-        match self.key {
+        match &self.key {
             PrivateKeyData::DjbPrivateKey(_) => KeyType::Djb,
         }
     }
@@ -226,18 +288,18 @@ impl PrivateKey {
         message: &[u8],
         csprng: &mut R,
     ) -> Result<Box<[u8]>> {
-        match self.key {
+        match &self.key {
             PrivateKeyData::DjbPrivateKey(k) => {
-                let kp = curve25519::KeyPair::from(k);
+                let kp = curve25519::KeyPair::from(**k);
                 Ok(Box::new(kp.calculate_signature(csprng, message)))
             }
         }
     }
 
     pub fn calculate_agreement(&self, their_key: &PublicKey) -> Result<Box<[u8]>> {
-        match (self.key, their_key.key) {
+        match (&self.key, their_key.key) {
             (PrivateKeyData::DjbPrivateKey(priv_key), PublicKeyData::DjbPublicKey(pub_key)) => {
-                let kp = curve25519::KeyPair::from(priv_key);
+                let kp = curve25519::KeyPair::from(**priv_key);
                 Ok(Box::new(kp.calculate_agreement(&pub_key)))
             }
         }
@@ -258,7 +320,9 @@ impl TryFrom<&[u8]> for PrivateKey {
     }
 }
 
-#[derive(Copy, Clone)]
+/// A public/private key pair; the private key is zeroed when the pair is dropped.
+#[derive(Clone)]
+#[must_use]
 pub struct KeyPair {
     pub public_key: PublicKey,
     pub private_key: PrivateKey,
@@ -269,7 +333,9 @@ impl KeyPair {
         let keypair = curve25519::KeyPair::new(csprng);
 
         let public_key = PublicKey::from(PublicKeyData::DjbPublicKey(*keypair.public_key()));
-        let private_key = PrivateKey::from(PrivateKeyData::DjbPrivateKey(*keypair.private_key()));
+        let private_key = PrivateKey::from(PrivateKeyData::DjbPrivateKey(Zeroizing::new(
+            *keypair.private_key(),
+        )));
 
         Self {
             public_key,
@@ -311,6 +377,24 @@ mod tests {
     use rand::rngs::OsRng;
 
     use super::*;
+    use crate::utils::bytes_after_drop;
+
+    fn private_key_bytes(key: &PrivateKey) -> &[u8] {
+        match &key.key {
+            PrivateKeyData::DjbPrivateKey(bytes) => &bytes[..],
+        }
+    }
+
+    #[test]
+    fn test_private_key_is_zeroed_on_drop() -> Result<()> {
+        let key = KeyPair::generate(&mut OsRng).private_key;
+        assert!(key.serialize().iter().any(|&b| b != 0));
+        assert_eq!(bytes_after_drop(key, private_key_bytes), [0; 32]);
+
+        let key = PrivateKey::deserialize(&[0xAA; 32])?;
+        assert_eq!(bytes_after_drop(key, private_key_bytes), [0; 32]);
+        Ok(())
+    }
 
     #[test]
     fn test_large_signatures() {
@@ -370,4 +454,93 @@ mod tests {
             &extra_space_decode.unwrap().serialize()[..]
         );
     }
+
+    #[test]
+    fn test_signature_batches() {
+        let mut csprng = OsRng;
+        let key_pair = KeyPair::generate(&mut csprng);
+        let other_key_pair = KeyPair::generate(&mut csprng);
+        let messages: Vec<Vec<u8>> = (0u8..6).map(|i| vec![i; 100]).collect();
+        let signatures: Vec<Box<[u8]>> = messages
+            .iter()
+            .map(|message| {
+                key_pair
+                    .private_key
+                    .calculate_signature(message, &mut csprng)
+                    .unwrap()
+            })
+            .collect();
+
+        let pairs: Vec<(&[u8], &[u8])> = messages
+            .iter()
+            .zip(&signatures)
+            .map(|(message, signature)| (&message[..], &signature[..]))
+            .collect();
+        assert_eq!(
+            key_pair
+                .public_key
+                .verify_signature_batch(&pairs, &mut csprng)
+                .unwrap(),
+            None
+        );
+        assert_eq!(
+            key_pair
+                .public_key
+                .verify_signature_batch(&[], &mut csprng)
+                .unwrap(),
+            None
+        );
+
+        // A mix of valid and invalid signatures reports the first invalid one.
+        let mut bad_signature = signatures[4].to_vec();
+        bad_signature[33] ^= 0x01;
+        let mut mixed = pairs.clone();
+        mixed[2].0 = b"not the signed message";
+        mixed[4].1 = &bad_signature;
+        assert_eq!(
+            key_pair
+                .public_key
+                .verify_signature_batch(&mixed, &mut csprng)
+                .unwrap(),
+            Some(2)
+        );
+        assert_eq!(
+            key_pair
+                .public_key
+                .verify_signature_batch(&mixed[3..], &mut csprng)
+                .unwrap(),
+            Some(1)
+        );
+
+        // Signatures of the wrong length are invalid rather than an error.
+        let mut truncated = pairs.clone();
+        truncated[5].1 = &signatures[5][..63];
+        assert_eq!(
+            key_pair
+                .public_key
+                .verify_signature_batch(&truncated, &mut csprng)
+                .unwrap(),
+            Some(5)
+        );
+
+        // Signatures from different keys can be checked together.
+        let other_signature = other_key_pair
+            .private_key
+            .calculate_signature(&messages[0], &mut csprng)
+            .unwrap();
+        let mut items: Vec<(&PublicKey, &[u8], &[u8])> = pairs
+            .iter()
+            .map(|(message, signature)| (&key_pair.public_key, *message, *signature))
+            .collect();
+        items.push((&other_key_pair.public_key, &messages[0], &other_signature));
+        assert_eq!(
+            PublicKey::verify_signatures(&items, &mut csprng).unwrap(),
+            None
+        );
+        items[1].0 = &other_key_pair.public_key;
+        assert_eq!(
+            PublicKey::verify_signatures(&items, &mut csprng).unwrap(),
+            Some(1)
+        );
+    }
 }
@@ -3,10 +3,11 @@
 // SPDX-License-Identifier: AGPL-3.0-only
 //
 
-use curve25519_dalek::constants::ED25519_BASEPOINT_TABLE;
-use curve25519_dalek::edwards::EdwardsPoint;
+use curve25519_dalek::constants::{ED25519_BASEPOINT_POINT, ED25519_BASEPOINT_TABLE};
+use curve25519_dalek::edwards::{CompressedEdwardsY, EdwardsPoint};
 use curve25519_dalek::montgomery::MontgomeryPoint;
 use curve25519_dalek::scalar::Scalar;
+use curve25519_dalek::traits::{IsIdentity, VartimeMultiscalarMul};
 use rand::{CryptoRng, Rng};
 use sha2::{Digest, Sha512};
 use subtle::ConstantTimeEq;
@@ -100,12 +101,95 @@ impl KeyPair {
         message: &[u8],
         signature: &[u8; SIGNATURE_LENGTH],
     ) -> bool {
-        let mont_point = MontgomeryPoint(*their_public_key);
-        let ed_pub_key_point =
-            match mont_point.to_edwards((signature[SIGNATURE_LENGTH - 1] & 0b1000_0000_u8) >> 7) {
+        let check = match SignatureCheck::new(their_public_key, message, signature) {
+            Some(x) => x,
+            None => return false,
+        };
+
+        let cap_r_check_point = EdwardsPoint::vartime_double_scalar_mul_basepoint(
+            &check.h,
+            &check.minus_cap_a,
+            &check.s,
+        );
+        let cap_r_check = cap_r_check_point.compress();
+
+        bool::from(cap_r_check.as_bytes().ct_eq(&check.cap_r))
+    }
+
+    /// Checks several XEdDSA signatures at once, returning `true` if they are all valid.
+    ///
+    /// Each signature's verification equation is scaled by a random 128-bit factor, and the sum is
+    /// checked with a single multiscalar multiplication, which is considerably faster than checking
+    /// each signature on its own. If any signature is invalid, the result is `false`, except with
+    /// negligible probability.
+    ///
+    /// The one exception is signatures built to fail [`verify_signature`](Self::verify_signature)
+    /// only because of small-order components in the key or signature. The random factors may
+    /// cancel those out, so such signatures are not reliably rejected here.
+    pub fn verify_signature_batch<R>(
+        signatures: &[(&[u8; PUBLIC_KEY_LENGTH], &[u8], &[u8; SIGNATURE_LENGTH])],
+        csprng: &mut R,
+    ) -> bool
+    where
+        R: CryptoRng + Rng,
+    {
+        let mut basepoint_scalar = Scalar::zero();
+        let mut scalars = Vec::with_capacity(2 * signatures.len() + 1);
+        let mut points = Vec::with_capacity(2 * signatures.len() + 1);
+
+        for (their_public_key, message, signature) in signatures {
+            let check = match SignatureCheck::new(their_public_key, message, signature) {
+                Some(x) => x,
+                None => return false,
+            };
+            let cap_r = match CompressedEdwardsY(check.cap_r).decompress() {
                 Some(x) => x,
                 None => return false,
             };
+            // verify_signature compares against the canonical encoding of R.
+            if cap_r.compress().as_bytes() != &check.cap_r {
+                return false;
+            }
+
+            let z = Scalar::from(csprng.gen::<u128>());
+            basepoint_scalar += z * check.s;
+            scalars.push(z * check.h);
+            points.push(check.minus_cap_a);
+            scalars.push(-z);
+            points.push(cap_r);
+        }
+
+        scalars.push(basepoint_scalar);
+        points.push(ED25519_BASEPOINT_POINT);
+        EdwardsPoint::vartime_multiscalar_mul(scalars, points).is_identity()
+    }
+
+    pub fn public_key(&self) -> &[u8; PUBLIC_KEY_LENGTH] {
+        &self.public_key
+    }
+
+    pub fn private_key(&self) -> &[u8; PRIVATE_KEY_LENGTH] {
+        &self.private_key
+    }
+}
+
+/// The parts of an XEdDSA signature and its public key that go into verifying it.
+struct SignatureCheck {
+    minus_cap_a: EdwardsPoint,
+    cap_r: [u8; 32],
+    h: Scalar,
+    s: Scalar,
+}
+
+impl SignatureCheck {
+    fn new(
+        their_public_key: &[u8; PUBLIC_KEY_LENGTH],
+        message: &[u8],
+        signature: &[u8; SIGNATURE_LENGTH],
+    ) -> Option<Self> {
+        let mont_point = MontgomeryPoint(*their_public_key);
+        let ed_pub_key_point =
+            mont_point.to_edwards((signature[SIGNATURE_LENGTH - 1] & 0b1000_0000_u8) >> 7)?;
         let cap_a = ed_pub_key_point.compress();
         let mut cap_r = [0u8; 32];
         cap_r.copy_from_slice(&signature[..32]);
@@ -113,9 +197,8 @@ impl KeyPair {
         s.copy_from_slice(&signature[32..]);
         s[31] &= 0b0111_1111_u8;
         if (s[31] & 0b1110_0000_u8) != 0 {
-            return false;
+            return None;
         }
-        let minus_cap_a = -ed_pub_key_point;
 
         let mut hash = Sha512::new();
         hash.update(&cap_r);
@@ -123,22 +206,12 @@ impl KeyPair {
         hash.update(&message);
         let h = Scalar::from_hash(hash);
 
-        let cap_r_check_point = EdwardsPoint::vartime_double_scalar_mul_basepoint(
-            &h,
-            &minus_cap_a,
-            &Scalar::from_bits(s),
-        );
-        let cap_r_check = cap_r_check_point.compress();
-
-        bool::from(cap_r_check.as_bytes().ct_eq(&cap_r))
-    }
-
-    pub fn public_key(&self) -> &[u8; PUBLIC_KEY_LENGTH] {
-        &self.public_key
-    }
-
-    pub fn private_key(&self) -> &[u8; PRIVATE_KEY_LENGTH] {
-        &self.private_key
+        Some(Self {
+            minus_cap_a: -ed_pub_key_point,
+            cap_r,
+            h,
+            s: Scalar::from_bits(s),
+        })
     }
 }
 
@@ -289,4 +362,63 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn test_signature_batch() {
+        let mut csprng = OsRng;
+        let key_pairs: Vec<KeyPair> = (0..4).map(|_| KeyPair::new(&mut csprng)).collect();
+        let messages: Vec<[u8; 64]> = (0..8)
+            .map(|_| {
+                let mut message = [0u8; 64];
+                csprng.fill_bytes(&mut message);
+                message
+            })
+            .collect();
+        let signatures: Vec<[u8; SIGNATURE_LENGTH]> = messages
+            .iter()
+            .enumerate()
+            .map(|(i, message)| key_pairs[i % 4].calculate_signature(&mut csprng, message))
+            .collect();
+
+        let batch = |messages: &[[u8; 64]], signatures: &[[u8; SIGNATURE_LENGTH]]| {
+            let items: Vec<_> = messages
+                .iter()
+                .zip(signatures)
+                .enumerate()
+                .map(|(i, (message, signature))| {
+                    (key_pairs[i % 4].public_key(), &message[..], signature)
+                })
+                .collect();
+            KeyPair::verify_signature_batch(&items, &mut OsRng)
+        };
+
+        assert!(batch(&messages, &signatures), "batch check failed");
+        assert!(batch(&[], &[]), "empty batch check failed");
+
+        for i in 0..messages.len() {
+            let mut bad_messages = messages.clone();
+            bad_messages[i][0] ^= 0x01;
+            assert!(
+                !batch(&bad_messages, &signatures),
+                "batch check passed with a bad message at {}",
+                i
+            );
+
+            let mut bad_signatures = signatures.clone();
+            bad_signatures[i][5] ^= 0x01;
+            assert!(
+                !batch(&messages, &bad_signatures),
+                "batch check passed with a bad R at {}",
+                i
+            );
+
+            let mut bad_signatures = signatures.clone();
+            bad_signatures[i][40] ^= 0x01;
+            assert!(
+                !batch(&messages, &bad_signatures),
+                "batch check passed with a bad s at {}",
+                i
+            );
+        }
+    }
 }
@@ -19,6 +19,7 @@ pub enum SignalProtocolError {
     ProtobufDecodingError(prost::DecodeError),
     ProtobufEncodingError(prost::EncodeError),
     InvalidProtobufEncoding,
+    ProtobufTooLarge(usize, usize),
 
     CiphertextMessageTooShort(usize),
     LegacyCiphertextVersion(u8),
@@ -36,11 +37,22 @@ pub enum SignalProtocolError {
     SignatureValidationFailed,
 
     UntrustedIdentity(crate::ProtocolAddress),
+    /// The address, the key previously saved for it, and the new key.
+    IdentityKeyChanged(
+        crate::ProtocolAddress,
+        crate::IdentityKey,
+        crate::IdentityKey,
+    ),
 
     InvalidPreKeyId,
     InvalidSignedPreKeyId,
     InvalidSenderKeyId,
 
+    InvalidRegistrationId(u32),
+    InvalidDeviceId(u32),
+    InconsistentOneTimePreKey,
+    InvalidSignedPreKeySignature,
+
     InvalidRootKeyLength(usize),
     InvalidChainKeyLength(usize),
 
@@ -52,8 +64,10 @@ pub enum SignalProtocolError {
 
     SessionNotFound(String),
     InvalidSessionStructure,
+    UnknownSessionVersion(u8),
 
     DuplicatedMessage(u32, u32),
+    TooManySkippedKeys(usize, usize),
     InvalidMessage(&'static str),
     InternalError(&'static str),
     FfiBindingError(String),
@@ -62,6 +76,10 @@ pub enum SignalProtocolError {
     InvalidSealedSenderMessage(String),
     UnknownSealedSenderVersion(u8),
     SealedSenderSelfSend,
+    CertificateExpired {
+        expired_at: u64,
+        now: u64,
+    },
 }
 
 impl Error for SignalProtocolError {
@@ -90,15 +108,21 @@ impl From<prost::EncodeError> for SignalProtocolError {
 impl fmt::Display for SignalProtocolError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            SignalProtocolError::ProtobufDecodingError(e) => {
-                write!(f, "failed to decode protobuf: {}", e)
+            // The underlying errors are available through `source`.
+            SignalProtocolError::ProtobufDecodingError(_) => {
+                write!(f, "failed to decode protobuf")
             }
-            SignalProtocolError::ProtobufEncodingError(e) => {
-                write!(f, "failed to encode protobuf: {}", e)
+            SignalProtocolError::ProtobufEncodingError(_) => {
+                write!(f, "failed to encode protobuf")
             }
             SignalProtocolError::InvalidProtobufEncoding => {
                 write!(f, "protobuf encoding was invalid")
             }
+            SignalProtocolError::ProtobufTooLarge(len, limit) => write!(
+                f,
+                "protobuf encoding was too large ({} bytes, limit {})",
+                len, limit
+            ),
             SignalProtocolError::InvalidArgument(s) => write!(f, "invalid argument: {}", s),
             SignalProtocolError::InvalidState(func, s) => {
                 write!(f, "invalid state for call to {} to succeed: {}", func, s)
@@ -137,6 +161,21 @@ impl fmt::Display for SignalProtocolError {
             SignalProtocolError::InvalidSignedPreKeyId => {
                 write!(f, "invalid signed prekey identifier")
             }
+            SignalProtocolError::InvalidRegistrationId(id) => {
+                write!(f, "registration ID <{}> is out of range", id)
+            }
+            SignalProtocolError::InvalidDeviceId(id) => {
+                write!(f, "device ID <{}> is out of range", id)
+            }
+            SignalProtocolError::InconsistentOneTimePreKey => {
+                write!(
+                    f,
+                    "one-time prekey ID and public key must be present together"
+                )
+            }
+            SignalProtocolError::InvalidSignedPreKeySignature => {
+                write!(f, "signed prekey signature does not match identity key")
+            }
             SignalProtocolError::InvalidChainKeyLength(l) => {
                 write!(f, "invalid chain key length <{}>", l)
             }
@@ -154,6 +193,9 @@ impl fmt::Display for SignalProtocolError {
             SignalProtocolError::UntrustedIdentity(addr) => {
                 write!(f, "untrusted identity for address {}", addr)
             }
+            SignalProtocolError::IdentityKeyChanged(address, _, _) => {
+                write!(f, "identity key changed for address {}", address)
+            }
             SignalProtocolError::SignatureValidationFailed => {
                 write!(f, "invalid signature detected")
             }
@@ -162,9 +204,17 @@ impl fmt::Display for SignalProtocolError {
                 write!(f, "session with '{}' not found", who)
             }
             SignalProtocolError::InvalidSessionStructure => write!(f, "invalid session structure"),
+            SignalProtocolError::UnknownSessionVersion(v) => {
+                write!(f, "unknown session record version {}", v)
+            }
             SignalProtocolError::DuplicatedMessage(i, c) => {
                 write!(f, "message with old counter {} / {}", i, c)
             }
+            SignalProtocolError::TooManySkippedKeys(jump, limit) => write!(
+                f,
+                "message would skip {} message keys, more than the limit of {}",
+                jump, limit
+            ),
             SignalProtocolError::InvalidMessage(m) => write!(f, "invalid message {}", m),
             SignalProtocolError::InternalError(m) => write!(f, "internal error {}", m),
             SignalProtocolError::InvalidSenderKeyId => write!(f, "invalid send key id"),
@@ -172,8 +222,8 @@ impl fmt::Display for SignalProtocolError {
             SignalProtocolError::FfiBindingError(m) => {
                 write!(f, "error while invoking an ffi callback: {}", m)
             }
-            SignalProtocolError::ApplicationCallbackError(func, c) => {
-                write!(f, "application callback {} failed with {}", func, c)
+            SignalProtocolError::ApplicationCallbackError(func, _) => {
+                write!(f, "application callback {} failed", func)
             }
             SignalProtocolError::InvalidSealedSenderMessage(m) => {
                 write!(f, "invalid sealed sender message {}", m)
@@ -184,6 +234,51 @@ impl fmt::Display for SignalProtocolError {
             SignalProtocolError::SealedSenderSelfSend => {
                 write!(f, "self send of a sealed sender message")
             }
+            SignalProtocolError::CertificateExpired { expired_at, now } => write!(
+                f,
+                "sender certificate expired at {} (validated at {})",
+                expired_at, now
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SessionRecord;
+
+    #[test]
+    fn test_source_chain() {
+        let error = SessionRecord::deserialize(&[0xFF; 3]).expect_err("invalid protobuf");
+        assert!(matches!(
+            error,
+            SignalProtocolError::ProtobufDecodingError(_)
+        ));
+        let source = error.source().expect("has a source");
+        assert!(source.downcast_ref::<prost::DecodeError>().is_some());
+        assert_eq!(error.to_string(), "failed to decode protobuf");
+        assert!(!error.to_string().contains(&source.to_string()));
+
+        #[derive(Debug)]
+        struct StoreError;
+        impl fmt::Display for StoreError {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "store is closed")
+            }
         }
+        impl Error for StoreError {}
+
+        let error =
+            SignalProtocolError::ApplicationCallbackError("load_session", Box::new(StoreError));
+        assert_eq!(
+            error.to_string(),
+            "application callback load_session failed"
+        );
+        let source = error.source().expect("has a source");
+        assert!(source.downcast_ref::<StoreError>().is_some());
+        assert_eq!(source.to_string(), "store is closed");
+
+        assert!(SignalProtocolError::InvalidCiphertext.source().is_none());
     }
 }
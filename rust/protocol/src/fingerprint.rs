@@ -3,6 +3,7 @@
 // SPDX-License-Identifier: AGPL-3.0-only
 //
 
+use crate::consts;
 use crate::proto;
 use crate::{IdentityKey, Result, SignalProtocolError};
 use prost::Message;
@@ -62,6 +63,24 @@ impl DisplayableFingerprint {
     }
 }
 
+/// The outcome of comparing a scanned fingerprint with the one shown on this device.
+///
+/// The mismatch cases say which side's data differs, so that a failed safety number check can be
+/// explained more precisely than "they don't match".
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FingerprintComparison {
+    /// Both fingerprints were computed from the same identities.
+    Match,
+    /// The scanned fingerprint uses a different format version, so its contents weren't compared.
+    VersionMismatch { theirs: u32, ours: u32 },
+    /// The scanned fingerprint was computed with a different identity for this device's user.
+    LocalIdentifierMismatch,
+    /// The scanned fingerprint was computed with a different identity for the other user.
+    RemoteIdentifierMismatch,
+    /// Neither identity in the scanned fingerprint matches.
+    BothIdentifiersMismatch,
+}
+
 #[derive(Debug, Clone)]
 pub struct ScannableFingerprint {
     version: u32,
@@ -79,8 +98,11 @@ impl ScannableFingerprint {
     }
 
     pub fn deserialize(protobuf: &[u8]) -> Result<Self> {
-        let fingerprint = proto::fingerprint::CombinedFingerprints::decode(protobuf)
-            .map_err(|_| SignalProtocolError::FingerprintParsingError)?;
+        let fingerprint = proto::decode_with_limit::<proto::fingerprint::CombinedFingerprints>(
+            protobuf,
+            consts::MAX_SMALL_PROTO_BYTES,
+        )
+        .map_err(|_| SignalProtocolError::FingerprintParsingError)?;
 
         Ok(Self {
             version: fingerprint
@@ -115,41 +137,60 @@ impl ScannableFingerprint {
         Ok(buf)
     }
 
-    pub fn compare(&self, combined: &[u8]) -> Result<bool> {
-        let combined = proto::fingerprint::CombinedFingerprints::decode(combined)
-            .map_err(|_| SignalProtocolError::FingerprintParsingError)?;
+    /// Compares `combined`, a serialized fingerprint scanned from the other device, with this one.
+    ///
+    /// Fails only if `combined` can't be parsed.
+    pub fn compare_detailed(&self, combined: &[u8]) -> Result<FingerprintComparison> {
+        let combined = proto::decode_with_limit::<proto::fingerprint::CombinedFingerprints>(
+            combined,
+            consts::MAX_SMALL_PROTO_BYTES,
+        )
+        .map_err(|_| SignalProtocolError::FingerprintParsingError)?;
 
         let their_version = combined.version.unwrap_or(0);
 
         if their_version != self.version {
-            return Err(SignalProtocolError::FingerprintVersionMismatch(
-                their_version,
-                self.version,
-            ));
-        }
-
-        if combined.local_fingerprint.is_none() || combined.remote_fingerprint.is_none() {
-            return Err(SignalProtocolError::FingerprintParsingError);
+            return Ok(FingerprintComparison::VersionMismatch {
+                theirs: their_version,
+                ours: self.version,
+            });
         }
 
-        let same1 = combined
+        let their_local = combined
             .local_fingerprint
-            .as_ref()
-            .unwrap()
-            .content
-            .as_ref()
-            .ok_or(SignalProtocolError::FingerprintParsingError)?
-            .ct_eq(&self.remote_fingerprint);
-        let same2 = combined
+            .and_then(|fingerprint| fingerprint.content)
+            .ok_or(SignalProtocolError::FingerprintParsingError)?;
+        let their_remote = combined
             .remote_fingerprint
-            .as_ref()
-            .unwrap()
-            .content
-            .as_ref()
-            .ok_or(SignalProtocolError::FingerprintParsingError)?
-            .ct_eq(&self.local_fingerprint);
-
-        Ok(same1.into() && same2.into())
+            .and_then(|fingerprint| fingerprint.content)
+            .ok_or(SignalProtocolError::FingerprintParsingError)?;
+
+        // Their view of us is their remote fingerprint, and vice versa.
+        let local_matches: bool = their_remote.ct_eq(&self.local_fingerprint).into();
+        let remote_matches: bool = their_local.ct_eq(&self.remote_fingerprint).into();
+
+        Ok(match (local_matches, remote_matches) {
+            (true, true) => FingerprintComparison::Match,
+            (false, true) => FingerprintComparison::LocalIdentifierMismatch,
+            (true, false) => FingerprintComparison::RemoteIdentifierMismatch,
+            (false, false) => FingerprintComparison::BothIdentifiersMismatch,
+        })
+    }
+
+    /// Like [`compare_detailed`](Self::compare_detailed), but only reports whether the
+    /// fingerprints match.
+    ///
+    /// A version mismatch is reported as [`SignalProtocolError::FingerprintVersionMismatch`].
+    pub fn compare(&self, combined: &[u8]) -> Result<bool> {
+        match self.compare_detailed(combined)? {
+            FingerprintComparison::Match => Ok(true),
+            FingerprintComparison::VersionMismatch { theirs, ours } => Err(
+                SignalProtocolError::FingerprintVersionMismatch(theirs, ours),
+            ),
+            FingerprintComparison::LocalIdentifierMismatch
+            | FingerprintComparison::RemoteIdentifierMismatch
+            | FingerprintComparison::BothIdentifiersMismatch => Ok(false),
+        }
     }
 }
 
@@ -214,6 +255,57 @@ impl Fingerprint {
     pub fn display_string(&self) -> Result<String> {
         Ok(format!("{}", self.display))
     }
+
+    /// Compares two displayed safety numbers, such as one computed locally and one typed in by
+    /// the user.
+    ///
+    /// Whitespace and invisible formatting characters (like zero-width spaces and direction
+    /// marks, which creep in when copying and pasting) are ignored, and digits from other common
+    /// scripts are treated as their ASCII equivalents. Any other character is an error.
+    pub fn compare_strings(local: &str, remote: &str) -> Result<bool> {
+        let local = normalize_display_string(local)?;
+        let remote = normalize_display_string(remote)?;
+        Ok(local.len() == remote.len() && bool::from(local.as_bytes().ct_eq(remote.as_bytes())))
+    }
+}
+
+/// Strips ignorable characters from a displayed safety number and converts its digits to ASCII,
+/// for [`Fingerprint::compare_strings`].
+fn normalize_display_string(display: &str) -> Result<String> {
+    // The zero of each run of decimal digits accepted besides ASCII: Arabic-Indic, Extended
+    // Arabic-Indic (Persian and Urdu), Devanagari, Bengali, and fullwidth.
+    const DIGIT_ZEROS: &[char] = &['\u{0660}', '\u{06F0}', '\u{0966}', '\u{09E6}', '\u{FF10}'];
+
+    let mut normalized = String::with_capacity(display.len());
+    for c in display.chars() {
+        let digit = c.to_digit(10).or_else(|| {
+            DIGIT_ZEROS
+                .iter()
+                .map(|&zero| (c as u32).wrapping_sub(zero as u32))
+                .find(|&offset| offset < 10)
+        });
+        if let Some(digit) = digit {
+            normalized.push(std::char::from_digit(digit, 10).expect("is a digit"));
+            continue;
+        }
+        match c {
+            c if c.is_whitespace() => {}
+            // Zero-width spaces and joiners, direction marks and embeddings, word joiners, and
+            // byte order marks.
+            '\u{200B}'..='\u{200F}'
+            | '\u{202A}'..='\u{202E}'
+            | '\u{2060}'..='\u{2064}'
+            | '\u{2066}'..='\u{2069}'
+            | '\u{FEFF}' => {}
+            _ => {
+                return Err(SignalProtocolError::InvalidArgument(format!(
+                    "safety number contains unexpected character {:?}",
+                    c
+                )))
+            }
+        }
+    }
+    Ok(normalized)
 }
 
 #[cfg(test)]
@@ -232,9 +324,137 @@ mod test {
     const ALICE_SCANNABLE_FINGERPRINT_V2 : &str = "080212220a201e301a0353dce3dbe7684cb8336e85136cdc0ee96219494ada305d62a7bd61df1a220a20d62cbf73a11592015b6b9f1682ac306fea3aaf3885b84d12bca631e9d4fb3a4d";
     const BOB_SCANNABLE_FINGERPRINT_V2   : & str = "080212220a20d62cbf73a11592015b6b9f1682ac306fea3aaf3885b84d12bca631e9d4fb3a4d1a220a201e301a0353dce3dbe7684cb8336e85136cdc0ee96219494ada305d62a7bd61df";
 
+    const RATCHET_ALICE_IDENTITY: &str =
+        "05b4a8455660ada65b401007f615e654041746432e3339c6875149bceefcb42b4a";
+    const RATCHET_BOB_IDENTITY: &str =
+        "05f1f43874f6966956c2dd473f8fa15adeb71d1cb991b2341692324cefb1c5e626";
+    const KEY_FOR_0X42: &str = "05132c442be010fbd57e72603328aa76e71fccc1503aae219327d14d9c9993f472";
+
     const ALICE_STABLE_ID: &str = "+14152222222";
     const BOB_STABLE_ID: &str = "+14153333333";
 
+    struct FingerprintVector {
+        version: u32,
+        iterations: u32,
+        local_id: &'static str,
+        local_key: &'static str,
+        remote_id: &'static str,
+        remote_key: &'static str,
+        /// The combined displayable fingerprint, the same from both sides.
+        display: &'static str,
+        /// The scannable fingerprint, from the local side.
+        scannable: &'static str,
+    }
+
+    // The vectors from the Java and Swift test suites (NumericFingerprintGeneratorTest and
+    // PublicAPITests.testFingerprint), checked from both sides.
+    const FINGERPRINT_VECTORS: &[FingerprintVector] = &[
+        FingerprintVector {
+            version: 1,
+            iterations: 5200,
+            local_id: ALICE_STABLE_ID,
+            local_key: ALICE_IDENTITY,
+            remote_id: BOB_STABLE_ID,
+            remote_key: BOB_IDENTITY,
+            display: DISPLAYABLE_FINGERPRINT_V1,
+            scannable: ALICE_SCANNABLE_FINGERPRINT_V1,
+        },
+        FingerprintVector {
+            version: 1,
+            iterations: 5200,
+            local_id: BOB_STABLE_ID,
+            local_key: BOB_IDENTITY,
+            remote_id: ALICE_STABLE_ID,
+            remote_key: ALICE_IDENTITY,
+            display: DISPLAYABLE_FINGERPRINT_V1,
+            scannable: BOB_SCANNABLE_FINGERPRINT_V1,
+        },
+        FingerprintVector {
+            version: 2,
+            iterations: 5200,
+            local_id: ALICE_STABLE_ID,
+            local_key: ALICE_IDENTITY,
+            remote_id: BOB_STABLE_ID,
+            remote_key: BOB_IDENTITY,
+            // unchanged vs v1
+            display: DISPLAYABLE_FINGERPRINT_V1,
+            scannable: ALICE_SCANNABLE_FINGERPRINT_V2,
+        },
+        FingerprintVector {
+            version: 2,
+            iterations: 5200,
+            local_id: BOB_STABLE_ID,
+            local_key: BOB_IDENTITY,
+            remote_id: ALICE_STABLE_ID,
+            remote_key: ALICE_IDENTITY,
+            display: DISPLAYABLE_FINGERPRINT_V1,
+            scannable: BOB_SCANNABLE_FINGERPRINT_V2,
+        },
+        // Computed with a separate implementation of the fingerprint algorithm, checked against
+        // the vectors above. The other keys are the identity keys from the ratchet test vectors
+        // (tests/ratchet.rs) and the public key for the private key [0x42; 32].
+        FingerprintVector {
+            version: 1,
+            iterations: 5200,
+            local_id: "+14151111111",
+            local_key: RATCHET_ALICE_IDENTITY,
+            remote_id: "+14152222222",
+            remote_key: RATCHET_BOB_IDENTITY,
+            display: "118678761510232431824451412867163399270923657257402296295603",
+            scannable: "080112220a20360d99b7db42d300d0ffb11fd42cf8c08969f40e3d5d829722c0485d9303e43c1a220a200044937c73579e1d91a55eb4dbb5e992dd60ad2c9cda96ef727c0e907fb38ff6",
+        },
+        FingerprintVector {
+            version: 1,
+            iterations: 5200,
+            local_id: "+14155550100",
+            local_key: RATCHET_BOB_IDENTITY,
+            remote_id: "+14155550199",
+            remote_key: KEY_FOR_0X42,
+            display: "440085737579049686591492539785837114652797317132485147492064",
+            scannable: "080112220a207db313fe8831ef959dbf2934f43649d32039f5135f2a62db8dda85a035c997be1a220a2017fe775ebfe49e293e3f73f6d9a365d404b00ce0066c188ab260cc508f60ab1c",
+        },
+        FingerprintVector {
+            version: 2,
+            iterations: 5200,
+            local_id: "9d0652a3-dcc3-4d11-975f-74d61598733f",
+            local_key: ALICE_IDENTITY,
+            remote_id: "796abedb-ca4e-4f18-8803-1fde5b921f9f",
+            remote_key: RATCHET_BOB_IDENTITY,
+            display: "776408462034749208497258649925829539634122539527091259300331",
+            scannable: "080212220a20ec05f8ddc82a01c17f0c447f256addba527fbf714c516f71aaa9f97de305a0c21a220a200523c708c9a2f32cb8d56e1118868b15dc6d2165b373d9ffb1db1aa10e0b7cd3",
+        },
+        FingerprintVector {
+            version: 2,
+            iterations: 1024,
+            local_id: "+14152222222",
+            local_key: ALICE_IDENTITY,
+            remote_id: "+14152222222",
+            remote_key: KEY_FOR_0X42,
+            display: "012760967231831095207014935229433743537762144674851263373726",
+            scannable: "080212220a20818e10bf2e729a0ed651eb728e97c06ff7808f3d06091ebad98de2e0d35e9ac81a220a20a051d6d5bc048017c1688693d9a47717cc741d30896ce9a1a5624815113dcb12",
+        },
+        FingerprintVector {
+            version: 1,
+            iterations: 2,
+            local_id: "+447700900123",
+            local_key: KEY_FOR_0X42,
+            remote_id: "+14153333333",
+            remote_key: BOB_IDENTITY,
+            display: "085984007114592388148292428923371861519645362394684468795464",
+            scannable: "080112220a2046193e8042c8eb23405c21122cc7b2387c756eec8ad4cdcfaf80473d06084ed11a220a2042463659d685a8868b0718faac5b00bbfca0281e73216abf2cddffcd7b3b71c2",
+        },
+        FingerprintVector {
+            version: 2,
+            iterations: 5200,
+            local_id: "",
+            local_key: RATCHET_ALICE_IDENTITY,
+            remote_id: "+14153333333",
+            remote_key: BOB_IDENTITY,
+            display: "458257569162576843440918079131889025500594267510714372767655",
+            scannable: "080212220a208bcbc2b3664de0ca969dea0347f35ba26b95567fb35c9b8bcf684268de27dddb1a220a20d62cbf73a11592015b6b9f1682ac306fea3aaf3885b84d12bca631e9d4fb3a4d",
+        },
+    ];
+
     #[test]
     fn fingerprint_encodings() -> Result<()> {
         let l = vec![0x12; 32];
@@ -355,6 +575,99 @@ mod test {
         );
     }
 
+    #[test]
+    fn fingerprint_known_answers() -> Result<()> {
+        for (i, vector) in FINGERPRINT_VECTORS.iter().enumerate() {
+            let local_key = IdentityKey::decode(&hex::decode(vector.local_key).unwrap())?;
+            let remote_key = IdentityKey::decode(&hex::decode(vector.remote_key).unwrap())?;
+
+            let local = Fingerprint::new(
+                vector.version,
+                vector.iterations,
+                vector.local_id.as_bytes(),
+                &local_key,
+                vector.remote_id.as_bytes(),
+                &remote_key,
+            )?;
+            let remote = Fingerprint::new(
+                vector.version,
+                vector.iterations,
+                vector.remote_id.as_bytes(),
+                &remote_key,
+                vector.local_id.as_bytes(),
+                &local_key,
+            )?;
+
+            assert_eq!(local.display_string()?, vector.display, "vector {}", i);
+            assert_eq!(remote.display_string()?, vector.display, "vector {}", i);
+            assert_eq!(
+                hex::encode(local.scannable.serialize()?),
+                vector.scannable,
+                "vector {}",
+                i
+            );
+            assert!(
+                remote.scannable.compare(&local.scannable.serialize()?)?,
+                "vector {}",
+                i
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn fingerprint_compare_strings() -> Result<()> {
+        let display = DISPLAYABLE_FINGERPRINT_V1;
+        assert!(Fingerprint::compare_strings(display, display)?);
+
+        let grouped = display
+            .as_bytes()
+            .chunks(5)
+            .map(|group| std::str::from_utf8(group).unwrap())
+            .collect::<Vec<_>>()
+            .join(" ");
+        assert!(Fingerprint::compare_strings(display, &grouped)?);
+        assert!(Fingerprint::compare_strings(
+            display,
+            &format!(
+                "\u{FEFF}\u{200E}{}\n\u{200B}",
+                grouped.replace(' ', "\u{00A0}")
+            )
+        )?);
+
+        // Arabic-Indic and fullwidth digits.
+        let arabic: String = display
+            .chars()
+            .map(|c| std::char::from_u32(0x0660 + c.to_digit(10).unwrap()).unwrap())
+            .collect();
+        assert!(Fingerprint::compare_strings(display, &arabic)?);
+        let fullwidth: String = display
+            .chars()
+            .map(|c| std::char::from_u32(0xFF10 + c.to_digit(10).unwrap()).unwrap())
+            .collect();
+        assert!(Fingerprint::compare_strings(&fullwidth, display)?);
+
+        // Different or missing digits are mismatches.
+        assert!(!Fingerprint::compare_strings(display, &display[1..])?);
+        assert!(!Fingerprint::compare_strings(
+            display,
+            &format!("{}0", &display[..display.len() - 1])
+        )?);
+        assert!(!Fingerprint::compare_strings(display, "")?);
+
+        // Anything else is rejected.
+        assert!(matches!(
+            Fingerprint::compare_strings(display, &format!("{}x", display)),
+            Err(SignalProtocolError::InvalidArgument(_))
+        ));
+        assert!(matches!(
+            Fingerprint::compare_strings(&display.replacen('0', "-", 1), display),
+            Err(SignalProtocolError::InvalidArgument(_))
+        ));
+
+        Ok(())
+    }
+
     #[test]
     fn fingerprint_matching_identifiers() {
         // testMatchingFingerprints
@@ -580,4 +893,63 @@ mod test {
             hex::encode(a_fprint_v2.scannable.serialize().unwrap())
         );
     }
+
+    #[test]
+    fn fingerprint_comparison_details() -> Result<()> {
+        let ours = ScannableFingerprint::new(2, &[0x12; 32], &[0xBA; 32]);
+
+        // Their fingerprint lists their own identity (our remote) first.
+        let scanned = |version: &str, their_local: &str, their_remote: &str| {
+            hex::decode(format!(
+                "{}12220a20{}1a220a20{}",
+                version,
+                their_local.repeat(32),
+                their_remote.repeat(32)
+            ))
+            .expect("valid hex")
+        };
+
+        assert_eq!(
+            ours.compare_detailed(&scanned("0802", "ba", "12"))?,
+            FingerprintComparison::Match
+        );
+        assert_eq!(
+            ours.compare_detailed(&scanned("0802", "ba", "13"))?,
+            FingerprintComparison::LocalIdentifierMismatch
+        );
+        assert_eq!(
+            ours.compare_detailed(&scanned("0802", "bb", "12"))?,
+            FingerprintComparison::RemoteIdentifierMismatch
+        );
+        assert_eq!(
+            ours.compare_detailed(&scanned("0802", "12", "ba"))?,
+            FingerprintComparison::BothIdentifiersMismatch
+        );
+        assert_eq!(
+            ours.compare_detailed(&scanned("0801", "ba", "12"))?,
+            FingerprintComparison::VersionMismatch { theirs: 1, ours: 2 }
+        );
+        assert_eq!(
+            ours.compare_detailed(&scanned("", "ba", "12"))?,
+            FingerprintComparison::VersionMismatch { theirs: 0, ours: 2 }
+        );
+
+        // The bool form reports only matches, and keeps treating version mismatches as errors.
+        assert!(ours.compare(&scanned("0802", "ba", "12"))?);
+        assert!(!ours.compare(&scanned("0802", "ba", "13"))?);
+        assert!(!ours.compare(&scanned("0802", "bb", "12"))?);
+        assert!(matches!(
+            ours.compare(&scanned("0801", "ba", "12")),
+            Err(SignalProtocolError::FingerprintVersionMismatch(1, 2))
+        ));
+
+        // Missing fingerprints are parse errors rather than mismatches.
+        let local_only = hex::decode(format!("080212220a20{}", "ba".repeat(32))).unwrap();
+        assert!(matches!(
+            ours.compare_detailed(&local_only),
+            Err(SignalProtocolError::FingerprintParsingError)
+        ));
+
+        Ok(())
+    }
 }
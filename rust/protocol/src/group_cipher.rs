@@ -23,6 +23,14 @@ pub async fn group_encrypt<R: Rng + CryptoRng>(
     csprng: &mut R,
     ctx: Context,
 ) -> Result<Vec<u8>> {
+    if plaintext.len() > consts::MAX_MESSAGE_PLAINTEXT_BYTES {
+        return Err(SignalProtocolError::InvalidArgument(format!(
+            "message of {} bytes exceeds the limit of {} bytes",
+            plaintext.len(),
+            consts::MAX_MESSAGE_PLAINTEXT_BYTES
+        )));
+    }
+
     let mut record = sender_key_store
         .load_sender_key(&sender_key_id, ctx)
         .await?
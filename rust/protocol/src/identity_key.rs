@@ -3,6 +3,7 @@
 // SPDX-License-Identifier: AGPL-3.0-only
 //
 
+use crate::consts;
 use crate::proto;
 use crate::{KeyPair, PrivateKey, PublicKey, Result, SignalProtocolError};
 
@@ -51,7 +52,7 @@ impl From<PublicKey> for IdentityKey {
     }
 }
 
-#[derive(Copy, Clone)]
+#[derive(Clone)]
 pub struct IdentityKeyPair {
     identity_key: IdentityKey,
     private_key: PrivateKey,
@@ -93,6 +94,7 @@ impl IdentityKeyPair {
         let structure = proto::storage::IdentityKeyPairStructure {
             public_key: self.identity_key.serialize().to_vec(),
             private_key: self.private_key.serialize().to_vec(),
+            schema_version: proto::PROTO_SCHEMA_VERSION,
         };
         let mut result = Vec::new();
         structure.encode(&mut result).unwrap();
@@ -104,7 +106,10 @@ impl TryFrom<&[u8]> for IdentityKeyPair {
     type Error = SignalProtocolError;
 
     fn try_from(value: &[u8]) -> Result<Self> {
-        let structure = proto::storage::IdentityKeyPairStructure::decode(value)?;
+        let structure = proto::decode_with_limit::<proto::storage::IdentityKeyPairStructure>(
+            value,
+            consts::MAX_SMALL_PROTO_BYTES,
+        )?;
         Ok(Self {
             identity_key: IdentityKey::try_from(&structure.public_key[..])?,
             private_key: PrivateKey::deserialize(&structure.private_key)?,
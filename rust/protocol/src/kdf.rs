@@ -55,6 +55,39 @@ impl HKDF {
         self.expand(&prk, info, output_length)
     }
 
+    /// Like [`derive_secrets`](Self::derive_secrets), but fills `output` instead of allocating.
+    ///
+    /// The `info` used is the concatenation of `info_parts`, without building it in memory.
+    pub fn derive_secrets_into(
+        self,
+        input_key_material: &[u8],
+        info_parts: &[&[u8]],
+        output: &mut [u8],
+    ) -> Result<()> {
+        self.derive_salted_secrets_into(
+            input_key_material,
+            &[0u8; Self::HASH_OUTPUT_SIZE],
+            info_parts,
+            output,
+        )
+    }
+
+    /// Like [`derive_salted_secrets`](Self::derive_salted_secrets), but fills `output` instead of
+    /// allocating.
+    ///
+    /// The `info` used is the concatenation of `info_parts`, without building it in memory.
+    pub fn derive_salted_secrets_into(
+        self,
+        input_key_material: &[u8],
+        salt: &[u8],
+        info_parts: &[&[u8]],
+        output: &mut [u8],
+    ) -> Result<()> {
+        let prk = self.extract(salt, input_key_material)?;
+        self.expand_into(&prk, info_parts, output);
+        Ok(())
+    }
+
     fn extract(
         self,
         salt: &[u8],
@@ -69,23 +102,33 @@ impl HKDF {
         info: &[u8],
         output_length: usize,
     ) -> Result<Box<[u8]>> {
-        let iterations = (output_length + Self::HASH_OUTPUT_SIZE - 1) / Self::HASH_OUTPUT_SIZE;
-        let mut result = Vec::<u8>::with_capacity(iterations * Self::HASH_OUTPUT_SIZE);
+        let mut result = vec![0u8; output_length].into_boxed_slice();
+        self.expand_into(prk, &[info], &mut result);
+        Ok(result)
+    }
+
+    fn expand_into(
+        self,
+        prk: &[u8; Self::HASH_OUTPUT_SIZE],
+        info_parts: &[&[u8]],
+        output: &mut [u8],
+    ) {
         let mut mac =
             Hmac::<Sha256>::new_varkey(prk).expect("HMAC-SHA256 should accept any size key");
+        let mut previous_block: Option<[u8; Self::HASH_OUTPUT_SIZE]> = None;
 
-        for i in 0..iterations {
-            if result.len() >= Self::HASH_OUTPUT_SIZE {
-                mac.update(&result[(result.len() - Self::HASH_OUTPUT_SIZE)..]);
+        for (i, chunk) in output.chunks_mut(Self::HASH_OUTPUT_SIZE).enumerate() {
+            if let Some(previous_block) = &previous_block {
+                mac.update(previous_block);
+            }
+            for part in info_parts {
+                mac.update(part);
             }
-            mac.update(info);
             mac.update(&[(i as u8) + self.iteration_start_offset]);
             let d = mac.finalize_reset().into_bytes();
-            result.extend_from_slice(&d[..]);
+            chunk.copy_from_slice(&d[..chunk.len()]);
+            previous_block = Some(d.into());
         }
-
-        result.truncate(output_length);
-        Ok(result.into_boxed_slice())
     }
 }
 
@@ -93,6 +136,88 @@ impl HKDF {
 mod tests {
     use super::*;
 
+    use rand::{Rng, RngCore};
+
+    /// The original implementation of `expand`, which takes the info as a single slice.
+    fn expand_concatenated(
+        hkdf: HKDF,
+        prk: &[u8; HKDF::HASH_OUTPUT_SIZE],
+        info: &[u8],
+        output_length: usize,
+    ) -> Vec<u8> {
+        let iterations = (output_length + HKDF::HASH_OUTPUT_SIZE - 1) / HKDF::HASH_OUTPUT_SIZE;
+        let mut result = Vec::<u8>::with_capacity(iterations * HKDF::HASH_OUTPUT_SIZE);
+        let mut mac = Hmac::<Sha256>::new_varkey(prk).unwrap();
+
+        for i in 0..iterations {
+            if result.len() >= HKDF::HASH_OUTPUT_SIZE {
+                mac.update(&result[(result.len() - HKDF::HASH_OUTPUT_SIZE)..]);
+            }
+            mac.update(info);
+            mac.update(&[(i as u8) + hkdf.iteration_start_offset]);
+            let d = mac.finalize_reset().into_bytes();
+            result.extend_from_slice(&d[..]);
+        }
+
+        result.truncate(output_length);
+        result
+    }
+
+    #[test]
+    fn test_expand_into_matches_concatenated_info() {
+        let mut rng = rand::rngs::OsRng;
+
+        for _ in 0..200 {
+            let hkdf = HKDF::new(rng.gen_range(2, 4)).unwrap();
+            let mut prk = [0u8; HKDF::HASH_OUTPUT_SIZE];
+            rng.fill_bytes(&mut prk);
+
+            let mut info = vec![0u8; rng.gen_range(0, 100)];
+            rng.fill_bytes(&mut info);
+            let mut split_points: Vec<usize> = (0..rng.gen_range(0, 5))
+                .map(|_| rng.gen_range(0, info.len() + 1))
+                .collect();
+            split_points.sort_unstable();
+            let mut info_parts = vec![];
+            let mut start = 0;
+            for &end in &split_points {
+                info_parts.push(&info[start..end]);
+                start = end;
+            }
+            info_parts.push(&info[start..]);
+
+            let output_length = rng.gen_range(0, 8 * HKDF::HASH_OUTPUT_SIZE);
+            let mut output = vec![0u8; output_length];
+            hkdf.expand_into(&prk, &info_parts, &mut output);
+
+            assert_eq!(
+                expand_concatenated(hkdf, &prk, &info, output_length),
+                output,
+                "info split as {:?}",
+                info_parts
+            );
+            assert_eq!(
+                &hkdf.expand(&prk, &info, output_length).unwrap()[..],
+                &output[..]
+            );
+        }
+    }
+
+    #[test]
+    fn test_derive_secrets_into() {
+        let ikm = b"input key material";
+        let expected = HKDF::new(3)
+            .unwrap()
+            .derive_secrets(ikm, b"WhisperMessageKeys", 80)
+            .unwrap();
+        let mut output = [0u8; 80];
+        HKDF::new(3)
+            .unwrap()
+            .derive_secrets_into(ikm, &[b"Whisper", b"Message", b"Keys"], &mut output)
+            .unwrap();
+        assert_eq!(&expected[..], &output[..]);
+    }
+
     #[test]
     fn test_vector_v3() {
         let ikm = [
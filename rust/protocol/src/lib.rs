@@ -31,13 +31,16 @@ pub use {
     address::ProtocolAddress,
     curve::{KeyPair, PrivateKey, PublicKey},
     error::SignalProtocolError,
-    fingerprint::{DisplayableFingerprint, Fingerprint, ScannableFingerprint},
+    fingerprint::{
+        DisplayableFingerprint, Fingerprint, FingerprintComparison, ScannableFingerprint,
+    },
     group_cipher::{
         create_sender_key_distribution_message, group_decrypt, group_encrypt,
         process_sender_key_distribution_message,
     },
     identity_key::{IdentityKey, IdentityKeyPair},
     kdf::HKDF,
+    proto::{parse_schema_version, PROTO_SCHEMA_VERSION},
     protocol::{
         CiphertextMessage, CiphertextMessageType, PreKeySignalMessage,
         SenderKeyDistributionMessage, SenderKeyMessage, SignalMessage,
@@ -48,18 +51,24 @@ pub use {
     },
     sealed_sender::{
         sealed_sender_decrypt, sealed_sender_decrypt_to_usmc, sealed_sender_encrypt,
-        SealedSenderDecryptionResult, SenderCertificate, ServerCertificate,
-        UnidentifiedSenderMessage, UnidentifiedSenderMessageContent,
+        sealed_sender_multi_recipient_encrypt, validate_sender_certificate,
+        SealedSenderDecryptionResult, SealedSenderMultiRecipientMessage, SenderCertificate,
+        ServerCertificate, UnidentifiedSenderMessage, UnidentifiedSenderMessageContent,
     },
-    sender_keys::{SenderKeyName, SenderKeyRecord},
+    sender_keys::{derive_sender_message_key, SenderKeyName, SenderKeyRecord, SenderMessageKey},
     session::{process_prekey, process_prekey_bundle},
     session_cipher::{
         message_decrypt, message_decrypt_prekey, message_decrypt_signal, message_encrypt,
+        StreamingDecryptor,
+    },
+    state::{
+        generate_pre_keys, PreKeyBundle, PreKeyBundleBuilder, PreKeyRecord, SessionRecord,
+        SessionStateDiff, SignedPreKeyRecord, MAX_PRE_KEY_BATCH_SIZE,
     },
-    state::{PreKeyBundle, PreKeyRecord, SessionRecord, SignedPreKeyRecord},
     storage::{
-        Context, Direction, IdentityKeyStore, InMemIdentityKeyStore, InMemPreKeyStore,
-        InMemSenderKeyStore, InMemSessionStore, InMemSignalProtocolStore, InMemSignedPreKeyStore,
-        PreKeyStore, ProtocolStore, SenderKeyStore, SessionStore, SignedPreKeyStore,
+        Context, Direction, EvictionPolicy, IdentityKeyStore, IdentityTrust, InMemIdentityKeyStore,
+        InMemPreKeyStore, InMemSenderKeyStore, InMemSessionStore, InMemSignalProtocolStore,
+        InMemSignedPreKeyStore, LruSessionCache, LruSessionCacheBuilder, PreKeyStore,
+        ProtocolStore, SenderKeyStore, SessionStore, SignedPreKeyStore,
     },
 };
@@ -7,3 +7,95 @@ pub mod fingerprint;
 pub mod sealed_sender;
 pub mod storage;
 pub mod wire;
+
+use crate::consts::MAX_PROTO_BYTES;
+use crate::{Result, SignalProtocolError};
+
+/// The schema version written to the `schema_version` field of each top-level storage record
+/// (session, pre-key, signed pre-key, identity key pair, and sender key records).
+///
+/// Records serialized before the field existed are version 0.
+pub const PROTO_SCHEMA_VERSION: u32 = 1;
+
+/// Bare protobuf encodings always start with a field tag of at least this value (field numbers
+/// start at 1), so records can be prefixed with a format version byte below it, as
+/// `SessionRecord::serialize` does, and still be told apart from records written without one.
+pub(crate) const UNVERSIONED_RECORD_MIN_BYTE: u8 = 1 << 3;
+
+/// Reads the schema version of a serialized storage record without decoding the rest of it.
+///
+/// Session records start with a format version byte of their own, which is skipped.
+///
+/// Returns `None` if the record has no version (because it was written before the field existed)
+/// or if `bytes` is not a valid protobuf encoding.
+pub fn parse_schema_version(bytes: &[u8]) -> Option<u32> {
+    let bytes = match bytes.split_first() {
+        Some((&prefix, rest)) if prefix < UNVERSIONED_RECORD_MIN_BYTE => rest,
+        _ => bytes,
+    };
+    // Unknown fields are skipped, so this only looks at the framing of the rest of the record.
+    let version = <storage::SchemaVersion as prost::Message>::decode(bytes).ok()?;
+    Some(version.schema_version).filter(|&version| version != 0)
+}
+
+/// Decodes a protobuf message, first checking that `bytes` is no longer than `limit`.
+///
+/// Callers should pass the limit for the message type from [`crate::consts`]; the overall
+/// [`MAX_PROTO_BYTES`] applies regardless.
+pub fn decode_with_limit<M: prost::Message + Default>(bytes: &[u8], limit: usize) -> Result<M> {
+    let limit = limit.min(MAX_PROTO_BYTES);
+    if bytes.len() > limit {
+        return Err(SignalProtocolError::ProtobufTooLarge(bytes.len(), limit));
+    }
+    Ok(M::decode(bytes)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{Rng, RngCore};
+
+    #[test]
+    fn test_decode_with_limit() {
+        let message = storage::PreKeyRecordStructure {
+            id: 5,
+            public_key: vec![1; 33],
+            private_key: vec![2; 32],
+            schema_version: PROTO_SCHEMA_VERSION,
+        };
+        let mut encoded = vec![];
+        prost::Message::encode(&message, &mut encoded).expect("can always encode to a Vec");
+
+        let decoded: storage::PreKeyRecordStructure =
+            decode_with_limit(&encoded, encoded.len()).expect("within the limit");
+        assert_eq!(decoded, message);
+        assert!(matches!(
+            decode_with_limit::<storage::PreKeyRecordStructure>(&encoded, encoded.len() - 1),
+            Err(SignalProtocolError::ProtobufTooLarge(len, limit))
+                if len == encoded.len() && limit == encoded.len() - 1
+        ));
+    }
+
+    #[test]
+    fn test_decode_with_limit_rejects_random_oversized_inputs() {
+        let mut rng = rand::rngs::OsRng;
+        for _ in 0..100 {
+            let limit = rng.gen_range(0, 4096);
+            let mut bytes = vec![0u8; limit + rng.gen_range(1, 4096)];
+            rng.fill_bytes(&mut bytes);
+            assert!(matches!(
+                decode_with_limit::<wire::SignalMessage>(&bytes, limit),
+                Err(SignalProtocolError::ProtobufTooLarge(len, l)) if len == bytes.len() && l == limit
+            ));
+        }
+    }
+
+    #[test]
+    fn test_decode_with_limit_caps_at_max_proto_bytes() {
+        let bytes = vec![0u8; MAX_PROTO_BYTES + 1];
+        assert!(matches!(
+            decode_with_limit::<storage::RecordStructure>(&bytes, usize::MAX),
+            Err(SignalProtocolError::ProtobufTooLarge(_, MAX_PROTO_BYTES))
+        ));
+    }
+}
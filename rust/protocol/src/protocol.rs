@@ -3,6 +3,7 @@
 // SPDX-License-Identifier: AGPL-3.0-only
 //
 
+use crate::consts;
 use crate::proto;
 use crate::{IdentityKey, PrivateKey, PublicKey, Result, SignalProtocolError};
 
@@ -13,6 +14,7 @@ use prost::Message;
 use rand::{CryptoRng, Rng};
 use sha2::Sha256;
 use subtle::ConstantTimeEq;
+use zeroize::Zeroize;
 
 pub const CIPHERTEXT_MESSAGE_CURRENT_VERSION: u8 = 3;
 
@@ -65,7 +67,7 @@ pub struct SignalMessage {
 }
 
 impl SignalMessage {
-    const MAC_LENGTH: usize = 8;
+    pub(crate) const MAC_LENGTH: usize = 8;
 
     pub fn new(
         message_version: u8,
@@ -160,6 +162,22 @@ impl SignalMessage {
         mac_key: &[u8],
         message: &[u8],
     ) -> Result<[u8; Self::MAC_LENGTH]> {
+        let mut mac = Self::start_mac(sender_identity_key, receiver_identity_key, mac_key)?;
+        mac.update(message);
+        let mut result = [0u8; Self::MAC_LENGTH];
+        result.copy_from_slice(&mac.finalize().into_bytes()[..Self::MAC_LENGTH]);
+        Ok(result)
+    }
+
+    /// Sets up the MAC for a message between the given identities, ready for the serialized
+    /// message (without the MAC itself) to be added.
+    ///
+    /// The result should be truncated to [`MAC_LENGTH`](Self::MAC_LENGTH).
+    pub(crate) fn start_mac(
+        sender_identity_key: &IdentityKey,
+        receiver_identity_key: &IdentityKey,
+        mac_key: &[u8],
+    ) -> Result<Hmac<Sha256>> {
         if mac_key.len() != 32 {
             return Err(SignalProtocolError::InvalidMacKeyLength(mac_key.len()));
         }
@@ -172,10 +190,15 @@ impl SignalMessage {
 
         mac.update(sender_identity_key.public_key().serialize().as_ref());
         mac.update(receiver_identity_key.public_key().serialize().as_ref());
-        mac.update(message);
-        let mut result = [0u8; Self::MAC_LENGTH];
-        result.copy_from_slice(&mac.finalize().into_bytes()[..Self::MAC_LENGTH]);
-        Ok(result)
+        Ok(mac)
+    }
+}
+
+/// The ciphertext is zeroed along with the serialized message, which includes it.
+impl Drop for SignalMessage {
+    fn drop(&mut self) {
+        self.ciphertext.zeroize();
+        self.serialized.zeroize();
     }
 }
 
@@ -205,8 +228,10 @@ impl TryFrom<&[u8]> for SignalMessage {
             ));
         }
 
-        let proto_structure =
-            proto::wire::SignalMessage::decode(&value[1..value.len() - SignalMessage::MAC_LENGTH])?;
+        let proto_structure = proto::decode_with_limit::<proto::wire::SignalMessage>(
+            &value[1..value.len() - SignalMessage::MAC_LENGTH],
+            consts::MAX_MESSAGE_PROTO_BYTES,
+        )?;
 
         let sender_ratchet_key = proto_structure
             .ratchet_key
@@ -345,7 +370,10 @@ impl TryFrom<&[u8]> for PreKeySignalMessage {
             ));
         }
 
-        let proto_structure = proto::wire::PreKeySignalMessage::decode(&value[1..])?;
+        let proto_structure = proto::decode_with_limit::<proto::wire::PreKeySignalMessage>(
+            &value[1..],
+            consts::MAX_MESSAGE_PROTO_BYTES,
+        )?;
         if proto_structure.signed_pre_key_id.is_none()
             || proto_structure.base_key.is_none()
             || proto_structure.identity_key.is_none()
@@ -468,8 +496,10 @@ impl TryFrom<&[u8]> for SenderKeyMessage {
                 ciphertext_version,
             ));
         }
-        let proto_structure =
-            proto::wire::SenderKeyMessage::decode(&value[1..value.len() - Self::SIGNATURE_LEN])?;
+        let proto_structure = proto::decode_with_limit::<proto::wire::SenderKeyMessage>(
+            &value[1..value.len() - Self::SIGNATURE_LEN],
+            consts::MAX_MESSAGE_PROTO_BYTES,
+        )?;
 
         let key_id = proto_structure
             .id
@@ -584,7 +614,10 @@ impl TryFrom<&[u8]> for SenderKeyDistributionMessage {
             ));
         }
 
-        let proto_structure = proto::wire::SenderKeyDistributionMessage::decode(&value[1..])?;
+        let proto_structure = proto::decode_with_limit::<proto::wire::SenderKeyDistributionMessage>(
+            &value[1..],
+            consts::MAX_MESSAGE_PROTO_BYTES,
+        )?;
 
         let id = proto_structure
             .id
@@ -37,7 +37,7 @@ pub(crate) fn initialize_alice_session<R: Rng + CryptoRng>(
 
     secrets.extend_from_slice(&[0xFFu8; 32]); // "discontinuity bytes"
 
-    let our_base_private_key = parameters.our_base_key_pair().private_key;
+    let our_base_private_key = &parameters.our_base_key_pair().private_key;
 
     secrets.extend_from_slice(
         &parameters
@@ -79,6 +79,7 @@ pub(crate) fn initialize_alice_session<R: Rng + CryptoRng>(
         local_registration_id: 0,
         needs_refresh: false,
         alice_base_key: vec![],
+        max_skipped_message_keys: 0,
     };
 
     let mut session = SessionState::new(session);
@@ -142,6 +143,7 @@ pub(crate) fn initialize_bob_session(
         local_registration_id: 0,
         needs_refresh: false,
         alice_base_key: vec![],
+        max_skipped_message_keys: 0,
     };
 
     let mut session = SessionState::new(session);
@@ -8,20 +8,24 @@ use arrayref::array_ref;
 use crate::crypto;
 use crate::{PrivateKey, PublicKey, Result, SignalProtocolError, HKDF};
 use std::fmt;
+use zeroize::Zeroizing;
 
+/// The keys for a single message; the cipher and MAC keys are zeroed when dropped.
 pub struct MessageKeys {
-    cipher_key: [u8; 32],
-    mac_key: [u8; 32],
+    cipher_key: Zeroizing<[u8; 32]>,
+    mac_key: Zeroizing<[u8; 32]>,
     iv: [u8; 16],
     counter: u32,
 }
 
 impl MessageKeys {
     pub fn derive_keys(input_key_material: &[u8], kdf: HKDF, counter: u32) -> Result<Self> {
-        let okm = kdf.derive_secrets(input_key_material, b"WhisperMessageKeys", 80)?;
+        // Longer than the arrays Zeroizing supports.
+        let mut okm = Zeroizing::new(vec![0; 80]);
+        kdf.derive_secrets_into(input_key_material, &[b"WhisperMessageKeys"], &mut okm)?;
         Ok(MessageKeys {
-            cipher_key: *array_ref![okm, 0, 32],
-            mac_key: *array_ref![okm, 32, 32],
+            cipher_key: Zeroizing::new(*array_ref![okm, 0, 32]),
+            mac_key: Zeroizing::new(*array_ref![okm, 32, 32]),
             iv: *array_ref![okm, 64, 16],
             counter,
         })
@@ -39,8 +43,8 @@ impl MessageKeys {
         }
 
         Ok(MessageKeys {
-            cipher_key: *array_ref![cipher_key, 0, 32],
-            mac_key: *array_ref![mac_key, 0, 32],
+            cipher_key: Zeroizing::new(*array_ref![cipher_key, 0, 32]),
+            mac_key: Zeroizing::new(*array_ref![mac_key, 0, 32]),
             iv: *array_ref![iv, 0, 16],
             counter,
         })
@@ -67,10 +71,11 @@ impl MessageKeys {
     }
 }
 
+/// A sending or receiving chain key, which is zeroed when dropped.
 #[derive(Clone, Debug)]
 pub struct ChainKey {
     kdf: HKDF,
-    key: [u8; 32],
+    key: Zeroizing<[u8; 32]>,
     index: u32,
 }
 
@@ -85,7 +90,7 @@ impl ChainKey {
 
         Ok(Self {
             kdf,
-            key: *array_ref![key, 0, 32],
+            key: Zeroizing::new(*array_ref![key, 0, 32]),
             index,
         })
     }
@@ -103,28 +108,29 @@ impl ChainKey {
     pub fn next_chain_key(&self) -> Result<Self> {
         Ok(Self {
             kdf: self.kdf,
-            key: self.calculate_base_material(Self::CHAIN_KEY_SEED)?,
+            key: Zeroizing::new(self.calculate_base_material(Self::CHAIN_KEY_SEED)?),
             index: self.index + 1,
         })
     }
 
     pub fn message_keys(&self) -> Result<MessageKeys> {
         MessageKeys::derive_keys(
-            &self.calculate_base_material(Self::MESSAGE_KEY_SEED)?,
+            &*Zeroizing::new(self.calculate_base_material(Self::MESSAGE_KEY_SEED)?),
             self.kdf,
             self.index,
         )
     }
 
     fn calculate_base_material(&self, seed: [u8; 1]) -> Result<[u8; 32]> {
-        crypto::hmac_sha256(&self.key, &seed)
+        crypto::hmac_sha256(&*self.key, &seed)
     }
 }
 
+/// A root key, which is zeroed when dropped.
 #[derive(Clone, Debug)]
 pub struct RootKey {
     kdf: HKDF,
-    key: [u8; 32],
+    key: Zeroizing<[u8; 32]>,
 }
 
 impl RootKey {
@@ -134,7 +140,7 @@ impl RootKey {
         }
         Ok(Self {
             kdf,
-            key: *array_ref![key, 0, 32],
+            key: Zeroizing::new(*array_ref![key, 0, 32]),
         })
     }
 
@@ -148,20 +154,21 @@ impl RootKey {
         our_ratchet_key: &PrivateKey,
     ) -> Result<(RootKey, ChainKey)> {
         let shared_secret = our_ratchet_key.calculate_agreement(their_ratchet_key)?;
-        let derived_secret_bytes = self.kdf.derive_salted_secrets(
+        let mut derived_secret_bytes = Zeroizing::new([0; 64]);
+        self.kdf.derive_salted_secrets_into(
             shared_secret.as_ref(),
-            &self.key,
-            b"WhisperRatchet",
-            64,
+            &*self.key,
+            &[b"WhisperRatchet"],
+            &mut *derived_secret_bytes,
         )?;
         Ok((
             RootKey {
                 kdf: self.kdf,
-                key: *array_ref![derived_secret_bytes, 0, 32],
+                key: Zeroizing::new(*array_ref![derived_secret_bytes, 0, 32]),
             },
             ChainKey {
                 kdf: self.kdf,
-                key: *array_ref![derived_secret_bytes, 32, 32],
+                key: Zeroizing::new(*array_ref![derived_secret_bytes, 32, 32]),
                 index: 0,
             },
         ))
@@ -170,13 +177,14 @@ impl RootKey {
 
 impl fmt::Display for RootKey {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", hex::encode(self.key))
+        write!(f, "{}", hex::encode(*self.key))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::utils::bytes_after_drop;
     use crate::{PrivateKey, PublicKey};
 
     #[test]
@@ -296,4 +304,27 @@ mod tests {
         assert_eq!(&next_chain, next_chain_key.key());
         Ok(())
     }
+
+    #[test]
+    fn test_keys_are_zeroed_on_drop() -> Result<()> {
+        let kdf = HKDF::new(3)?;
+
+        let chain_key = ChainKey::new(kdf, &[0xAA; 32], 0)?;
+        assert_eq!(bytes_after_drop(chain_key, |k| &k.key()[..]), [0; 32]);
+
+        let root_key = RootKey::new(kdf, &[0xAA; 32])?;
+        assert_eq!(bytes_after_drop(root_key, |k| &k.key()[..]), [0; 32]);
+
+        let message_keys = MessageKeys::new(&[0xAA; 32], &[0xBB; 32], &[0xCC; 16], 0)?;
+        assert_eq!(
+            bytes_after_drop(message_keys, |k| &k.cipher_key()[..]),
+            [0; 32]
+        );
+        let message_keys = MessageKeys::new(&[0xAA; 32], &[0xBB; 32], &[0xCC; 16], 0)?;
+        assert_eq!(
+            bytes_after_drop(message_keys, |k| &k.mac_key()[..]),
+            [0; 32]
+        );
+        Ok(())
+    }
 }
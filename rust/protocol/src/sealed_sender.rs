@@ -4,17 +4,20 @@
 //
 
 use crate::{
-    message_encrypt, CiphertextMessageType, Context, IdentityKeyStore, KeyPair,
-    PreKeySignalMessage, PreKeyStore, PrivateKey, ProtocolAddress, PublicKey, Result, SessionStore,
-    SignalMessage, SignalProtocolError, SignedPreKeyStore, HKDF,
+    message_encrypt, CiphertextMessageType, Context, IdentityKeyPair, IdentityKeyStore, KeyPair,
+    PreKeySignalMessage, PreKeyStore, PrivateKey, ProtocolAddress, PublicKey, Result,
+    SessionRecord, SessionStore, SignalMessage, SignalProtocolError, SignedPreKeyStore, HKDF,
 };
 
+use crate::consts;
 use crate::crypto;
 use crate::proto;
 use crate::session_cipher;
 use prost::Message;
 use rand::{CryptoRng, Rng};
+use std::collections::HashSet;
 use std::convert::TryFrom;
+use std::ops::Range;
 use subtle::ConstantTimeEq;
 
 #[derive(Debug, Clone)]
@@ -38,7 +41,10 @@ const REVOKED_SERVER_CERTIFICATE_KEY_IDS: &[u32] = &[0xDEADC357];
 
 impl ServerCertificate {
     pub fn deserialize(data: &[u8]) -> Result<Self> {
-        let pb = proto::sealed_sender::ServerCertificate::decode(data)?;
+        let pb = proto::decode_with_limit::<proto::sealed_sender::ServerCertificate>(
+            data,
+            consts::MAX_SMALL_PROTO_BYTES,
+        )?;
 
         if pb.certificate.is_none() || pb.signature.is_none() {
             return Err(SignalProtocolError::InvalidProtobufEncoding);
@@ -50,8 +56,9 @@ impl ServerCertificate {
         let signature = pb
             .signature
             .ok_or(SignalProtocolError::InvalidProtobufEncoding)?;
-        let certificate_data =
-            proto::sealed_sender::server_certificate::Certificate::decode(certificate.as_ref())?;
+        let certificate_data = proto::decode_with_limit::<
+            proto::sealed_sender::server_certificate::Certificate,
+        >(&certificate, consts::MAX_SMALL_PROTO_BYTES)?;
         let key = PublicKey::try_from(
             &certificate_data
                 .key
@@ -152,15 +159,19 @@ pub struct SenderCertificate {
 
 impl SenderCertificate {
     pub fn deserialize(data: &[u8]) -> Result<Self> {
-        let pb = proto::sealed_sender::SenderCertificate::decode(data)?;
+        let pb = proto::decode_with_limit::<proto::sealed_sender::SenderCertificate>(
+            data,
+            consts::MAX_SMALL_PROTO_BYTES,
+        )?;
         let certificate = pb
             .certificate
             .ok_or(SignalProtocolError::InvalidProtobufEncoding)?;
         let signature = pb
             .signature
             .ok_or(SignalProtocolError::InvalidProtobufEncoding)?;
-        let certificate_data =
-            proto::sealed_sender::sender_certificate::Certificate::decode(certificate.as_ref())?;
+        let certificate_data = proto::decode_with_limit::<
+            proto::sealed_sender::sender_certificate::Certificate,
+        >(&certificate, consts::MAX_SMALL_PROTO_BYTES)?;
 
         let sender_device_id = certificate_data
             .sender_device
@@ -257,23 +268,26 @@ impl SenderCertificate {
     }
 
     pub fn validate(&self, trust_root: &PublicKey, validation_time: u64) -> Result<bool> {
-        if !self.signer.validate(&trust_root)? {
+        if !self.validate_signature(trust_root)? {
             return Ok(false);
         }
 
-        if !self
-            .signer
-            .public_key()?
-            .verify_signature(&self.certificate, &self.signature)?
-        {
+        if validation_time > self.expiration {
             return Ok(false);
         }
 
-        if validation_time > self.expiration {
+        Ok(true)
+    }
+
+    /// Checks the certificate chain up to `trust_root`, ignoring the expiration time.
+    fn validate_signature(&self, trust_root: &PublicKey) -> Result<bool> {
+        if !self.signer.validate(trust_root)? {
             return Ok(false);
         }
 
-        Ok(true)
+        self.signer
+            .public_key()?
+            .verify_signature(&self.certificate, &self.signature)
     }
 
     pub fn signer(&self) -> Result<&ServerCertificate> {
@@ -322,7 +336,9 @@ pub struct UnidentifiedSenderMessageContent {
 
 impl UnidentifiedSenderMessageContent {
     pub fn deserialize(data: &[u8]) -> Result<Self> {
-        let pb = proto::sealed_sender::unidentified_sender_message::Message::decode(data)?;
+        let pb = proto::decode_with_limit::<
+            proto::sealed_sender::unidentified_sender_message::Message,
+        >(data, consts::MAX_MESSAGE_PROTO_BYTES)?;
 
         let msg_type = pb
             .r#type
@@ -370,6 +386,13 @@ impl UnidentifiedSenderMessageContent {
 
         let mut serialized = vec![];
         msg.encode(&mut serialized)?;
+        if serialized.len() > consts::MAX_MESSAGE_PROTO_BYTES {
+            return Err(SignalProtocolError::InvalidArgument(format!(
+                "message content of {} bytes exceeds the limit of {} bytes",
+                serialized.len(),
+                consts::MAX_MESSAGE_PROTO_BYTES
+            )));
+        }
 
         // serialize it
         Ok(Self {
@@ -421,7 +444,10 @@ impl UnidentifiedSenderMessage {
             return Err(SignalProtocolError::UnknownSealedSenderVersion(version));
         }
 
-        let pb = proto::sealed_sender::UnidentifiedSenderMessage::decode(&data[1..])?;
+        let pb = proto::decode_with_limit::<proto::sealed_sender::UnidentifiedSenderMessage>(
+            &data[1..],
+            consts::MAX_MESSAGE_PROTO_BYTES,
+        )?;
 
         let ephemeral_public = pb
             .ephemeral_public
@@ -621,12 +647,359 @@ pub async fn sealed_sender_encrypt<R: Rng + CryptoRng>(
     )
 }
 
+// Multi-recipient ("v2") sealed sender.
+//
+// The content is encrypted once, under keys derived from a random 32-byte secret M, and M is
+// then encrypted separately for each recipient. The sender hands the server a single message:
+//
+//     0x22 || varint(recipient count)
+//          || for each recipient: varint(name length) || name || varint(device ID) || C_i || AT_i
+//          || E || ciphertext
+//
+// and the server forwards each recipient just their own part:
+//
+//     0x22 || C_i || AT_i || E || ciphertext
+//
+// where, for recipient identity key R_i and sender identity key S:
+//
+// - (e, K) = HKDF(M), and E is the public key for the private key e
+// - C_i = M xor HKDF(DH(e, R_i) || E || R_i)
+// - AT_i = HKDF(DH(S, R_i) || E || C_i || S || R_i), truncated to 16 bytes
+// - ciphertext = AES-256-CTR + HMAC-SHA256(K, content)
+//
+// A recipient recovers M from C_i, checks that it produces the same E, decrypts the content, and
+// then uses the sender certificate's key as S to check AT_i. Deriving e from M is what ties each
+// recipient's C_i to the shared ciphertext.
+
+const SEALED_SENDER_V2_VERSION: u8 = 2;
+const SEALED_SENDER_V2_SECRET_LEN: usize = 32;
+const SEALED_SENDER_V2_AUTH_TAG_LEN: usize = 16;
+const SEALED_SENDER_V2_EPHEMERAL_KEY_LEN: usize = 33;
+const SEALED_SENDER_V2_RECIPIENT_HEADER_LEN: usize =
+    SEALED_SENDER_V2_SECRET_LEN + SEALED_SENDER_V2_AUTH_TAG_LEN;
+
+struct SealedSenderV2Keys {
+    ephemeral: KeyPair,
+    derived_values: Box<[u8]>,
+}
+
+impl SealedSenderV2Keys {
+    const LABEL_R_K: &'static [u8] = b"Sealed Sender v2: r, K";
+    const LABEL_DH: &'static [u8] = b"Sealed Sender v2: DH";
+    const LABEL_DH_S: &'static [u8] = b"Sealed Sender v2: DH-sender";
+
+    fn calculate(secret: &[u8]) -> Result<Self> {
+        let derived_values = HKDF::new(3)?.derive_secrets(secret, Self::LABEL_R_K, 96)?;
+        let private_key = PrivateKey::deserialize(&derived_values[0..32])?;
+        let ephemeral = KeyPair::new(private_key.public_key()?, private_key);
+        Ok(Self {
+            ephemeral,
+            derived_values,
+        })
+    }
+
+    fn cipher_key(&self) -> &[u8] {
+        &self.derived_values[32..64]
+    }
+
+    fn mac_key(&self) -> &[u8] {
+        &self.derived_values[64..96]
+    }
+
+    /// Encrypts or decrypts the secret M for one recipient (C_i above).
+    fn apply_recipient_mask(
+        agreement: &[u8],
+        ephemeral: &PublicKey,
+        recipient: &PublicKey,
+        input: &[u8],
+    ) -> Result<Vec<u8>> {
+        let mut ikm = agreement.to_vec();
+        ikm.extend_from_slice(&ephemeral.serialize());
+        ikm.extend_from_slice(&recipient.serialize());
+        let mask = HKDF::new(3)?.derive_secrets(&ikm, Self::LABEL_DH, input.len())?;
+        Ok(input.iter().zip(mask.iter()).map(|(a, b)| a ^ b).collect())
+    }
+
+    /// Computes the tag proving the sender's identity to one recipient (AT_i above).
+    fn recipient_auth_tag(
+        agreement: &[u8],
+        ephemeral: &PublicKey,
+        encrypted_secret: &[u8],
+        sender: &PublicKey,
+        recipient: &PublicKey,
+    ) -> Result<Box<[u8]>> {
+        let mut ikm = agreement.to_vec();
+        ikm.extend_from_slice(&ephemeral.serialize());
+        ikm.extend_from_slice(encrypted_secret);
+        ikm.extend_from_slice(&sender.serialize());
+        ikm.extend_from_slice(&recipient.serialize());
+        HKDF::new(3)?.derive_secrets(&ikm, Self::LABEL_DH_S, SEALED_SENDER_V2_AUTH_TAG_LEN)
+    }
+}
+
+/// Encrypts `usmc` once for all of `destinations`, producing a single message in the
+/// multi-recipient format described above.
+///
+/// Each destination's identity key is taken from the corresponding entry in
+/// `destination_sessions`. Use [`SealedSenderMultiRecipientMessage`] to split the result into the
+/// messages delivered to each recipient, which are decrypted with [`sealed_sender_decrypt`] as
+/// usual.
+pub async fn sealed_sender_multi_recipient_encrypt<R: Rng + CryptoRng>(
+    destinations: &[&ProtocolAddress],
+    destination_sessions: &[&SessionRecord],
+    usmc: &UnidentifiedSenderMessageContent,
+    identity_store: &mut dyn IdentityKeyStore,
+    ctx: Context,
+    rng: &mut R,
+) -> Result<Vec<u8>> {
+    if destinations.len() != destination_sessions.len() {
+        return Err(SignalProtocolError::InvalidArgument(format!(
+            "{} destinations but {} sessions",
+            destinations.len(),
+            destination_sessions.len()
+        )));
+    }
+
+    let our_identity = identity_store.get_identity_key_pair(ctx).await?;
+    if usmc.sender()?.key()? != *our_identity.public_key() {
+        return Err(SignalProtocolError::InvalidArgument(
+            "sender certificate key does not match local identity key".to_string(),
+        ));
+    }
+
+    let mut secret = [0u8; SEALED_SENDER_V2_SECRET_LEN];
+    rng.fill(&mut secret);
+    let keys = SealedSenderV2Keys::calculate(&secret)?;
+    let ephemeral_public = keys.ephemeral.public_key;
+
+    let mut serialized = vec![SEALED_SENDER_V2_VERSION | (SEALED_SENDER_V2_VERSION << 4)];
+    prost::encoding::encode_varint(destinations.len() as u64, &mut serialized);
+
+    let mut seen = HashSet::with_capacity(destinations.len());
+    for (&destination, &session) in destinations.iter().zip(destination_sessions) {
+        if !seen.insert(destination) {
+            return Err(SignalProtocolError::InvalidArgument(format!(
+                "{} is listed more than once",
+                destination
+            )));
+        }
+        let their_identity = if session.has_current_session_state() {
+            session.session_state()?.remote_identity_key()?
+        } else {
+            None
+        }
+        .ok_or_else(|| SignalProtocolError::SessionNotFound(format!("{}", destination)))?;
+        let their_key = their_identity.public_key();
+
+        let encrypted_secret = SealedSenderV2Keys::apply_recipient_mask(
+            &keys.ephemeral.private_key.calculate_agreement(their_key)?,
+            &ephemeral_public,
+            their_key,
+            &secret,
+        )?;
+        let auth_tag = SealedSenderV2Keys::recipient_auth_tag(
+            &our_identity.private_key().calculate_agreement(their_key)?,
+            &ephemeral_public,
+            &encrypted_secret,
+            our_identity.public_key(),
+            their_key,
+        )?;
+
+        prost::encoding::encode_varint(destination.name().len() as u64, &mut serialized);
+        serialized.extend_from_slice(destination.name().as_bytes());
+        prost::encoding::encode_varint(destination.device_id().into(), &mut serialized);
+        serialized.extend_from_slice(&encrypted_secret);
+        serialized.extend_from_slice(&auth_tag);
+    }
+
+    serialized.extend_from_slice(&ephemeral_public.serialize());
+    serialized.extend_from_slice(&crypto::aes256_ctr_hmacsha256_encrypt(
+        usmc.serialized()?,
+        keys.cipher_key(),
+        keys.mac_key(),
+    )?);
+    Ok(serialized)
+}
+
+/// A parsed multi-recipient sealed sender message, as produced by
+/// [`sealed_sender_multi_recipient_encrypt`].
+///
+/// This is what a server uses to produce the message delivered to each recipient.
+#[derive(Debug, Clone)]
+pub struct SealedSenderMultiRecipientMessage {
+    serialized: Vec<u8>,
+    recipients: Vec<ProtocolAddress>,
+    recipient_headers: Vec<Range<usize>>,
+    shared_start: usize,
+}
+
+impl SealedSenderMultiRecipientMessage {
+    pub fn deserialize(data: &[u8]) -> Result<Self> {
+        fn malformed(what: &str) -> SignalProtocolError {
+            SignalProtocolError::InvalidSealedSenderMessage(format!(
+                "multi-recipient message {}",
+                what
+            ))
+        }
+
+        let (&version_byte, mut remaining) =
+            data.split_first().ok_or_else(|| malformed("was empty"))?;
+        let version = version_byte >> 4;
+        if version != SEALED_SENDER_V2_VERSION {
+            return Err(SignalProtocolError::UnknownSealedSenderVersion(version));
+        }
+
+        let count = prost::encoding::decode_varint(&mut remaining)
+            .map_err(|_| malformed("has an invalid recipient count"))?;
+        // Each recipient takes up at least this much, so a bogus count can't cause a huge
+        // allocation.
+        let min_recipient_len = 2 + SEALED_SENDER_V2_RECIPIENT_HEADER_LEN;
+        if count > (remaining.len() / min_recipient_len) as u64 {
+            return Err(malformed("is too short for its recipient count"));
+        }
+
+        let mut recipients = Vec::with_capacity(count as usize);
+        let mut recipient_headers = Vec::with_capacity(count as usize);
+        let mut seen = HashSet::with_capacity(count as usize);
+        for _ in 0..count {
+            let name_len = prost::encoding::decode_varint(&mut remaining)
+                .map_err(|_| malformed("has an invalid recipient name length"))?;
+            if name_len > remaining.len() as u64 {
+                return Err(malformed("is truncated"));
+            }
+            let (name, rest) = remaining.split_at(name_len as usize);
+            let name = std::str::from_utf8(name)
+                .map_err(|_| malformed("has an invalid recipient name"))?;
+            remaining = rest;
+
+            let device_id = prost::encoding::decode_varint(&mut remaining)
+                .ok()
+                .and_then(|id| u32::try_from(id).ok())
+                .ok_or_else(|| malformed("has an invalid device ID"))?;
+            if remaining.len() < SEALED_SENDER_V2_RECIPIENT_HEADER_LEN {
+                return Err(malformed("is truncated"));
+            }
+            let header_start = data.len() - remaining.len();
+            remaining = &remaining[SEALED_SENDER_V2_RECIPIENT_HEADER_LEN..];
+
+            let address = ProtocolAddress::new(name.to_string(), device_id);
+            if !seen.insert(address.clone()) {
+                return Err(malformed("lists a recipient more than once"));
+            }
+            recipients.push(address);
+            recipient_headers
+                .push(header_start..header_start + SEALED_SENDER_V2_RECIPIENT_HEADER_LEN);
+        }
+
+        // The shared part holds the ephemeral key, then the ciphertext with its 10-byte MAC.
+        if remaining.len() < SEALED_SENDER_V2_EPHEMERAL_KEY_LEN + 10 {
+            return Err(malformed("is truncated"));
+        }
+        PublicKey::deserialize(&remaining[..SEALED_SENDER_V2_EPHEMERAL_KEY_LEN])?;
+
+        Ok(Self {
+            serialized: data.to_vec(),
+            recipients,
+            recipient_headers,
+            shared_start: data.len() - remaining.len(),
+        })
+    }
+
+    pub fn recipients(&self) -> Result<&[ProtocolAddress]> {
+        Ok(&self.recipients)
+    }
+
+    /// Returns the message to deliver to `recipient`, which can be decrypted with
+    /// [`sealed_sender_decrypt`].
+    pub fn message_for_recipient(&self, recipient: &ProtocolAddress) -> Result<Vec<u8>> {
+        let index = self
+            .recipients
+            .iter()
+            .position(|r| r == recipient)
+            .ok_or_else(|| {
+                SignalProtocolError::InvalidArgument(format!("{} is not a recipient", recipient))
+            })?;
+        let header = &self.serialized[self.recipient_headers[index].clone()];
+        let shared = &self.serialized[self.shared_start..];
+
+        let mut result = Vec::with_capacity(1 + header.len() + shared.len());
+        result.push(self.serialized[0]);
+        result.extend_from_slice(header);
+        result.extend_from_slice(shared);
+        Ok(result)
+    }
+
+    pub fn serialized(&self) -> Result<&[u8]> {
+        Ok(&self.serialized)
+    }
+}
+
+fn sealed_sender_v2_decrypt_to_usmc(
+    ciphertext: &[u8],
+    our_identity: &IdentityKeyPair,
+) -> Result<UnidentifiedSenderMessageContent> {
+    let shared_start = 1 + SEALED_SENDER_V2_RECIPIENT_HEADER_LEN;
+    if ciphertext.len() < shared_start + SEALED_SENDER_V2_EPHEMERAL_KEY_LEN {
+        return Err(SignalProtocolError::InvalidSealedSenderMessage(
+            "message was too short".to_string(),
+        ));
+    }
+    let encrypted_secret = &ciphertext[1..1 + SEALED_SENDER_V2_SECRET_LEN];
+    let auth_tag = &ciphertext[1 + SEALED_SENDER_V2_SECRET_LEN..shared_start];
+    let (ephemeral_public, encrypted_message) =
+        ciphertext[shared_start..].split_at(SEALED_SENDER_V2_EPHEMERAL_KEY_LEN);
+    let ephemeral_public = PublicKey::deserialize(ephemeral_public)?;
+
+    let secret = SealedSenderV2Keys::apply_recipient_mask(
+        &our_identity
+            .private_key()
+            .calculate_agreement(&ephemeral_public)?,
+        &ephemeral_public,
+        our_identity.public_key(),
+        encrypted_secret,
+    )?;
+    let keys = SealedSenderV2Keys::calculate(&secret)?;
+    if keys.ephemeral.public_key != ephemeral_public {
+        return Err(SignalProtocolError::InvalidSealedSenderMessage(
+            "derived ephemeral key does not match message key".to_string(),
+        ));
+    }
+
+    let message_bytes = crypto::aes256_ctr_hmacsha256_decrypt(
+        encrypted_message,
+        keys.cipher_key(),
+        keys.mac_key(),
+    )?;
+    let usmc = UnidentifiedSenderMessageContent::deserialize(&message_bytes)?;
+
+    let sender_key = usmc.sender()?.key()?;
+    let expected_auth_tag = SealedSenderV2Keys::recipient_auth_tag(
+        &our_identity
+            .private_key()
+            .calculate_agreement(&sender_key)?,
+        &ephemeral_public,
+        encrypted_secret,
+        &sender_key,
+        our_identity.public_key(),
+    )?;
+    if !bool::from(expected_auth_tag.ct_eq(auth_tag)) {
+        return Err(SignalProtocolError::InvalidSealedSenderMessage(
+            "sender certificate key does not match authentication tag".to_string(),
+        ));
+    }
+
+    Ok(usmc)
+}
+
 pub async fn sealed_sender_decrypt_to_usmc(
     ciphertext: &[u8],
     identity_store: &mut dyn IdentityKeyStore,
     ctx: Context,
 ) -> Result<UnidentifiedSenderMessageContent> {
     let our_identity = identity_store.get_identity_key_pair(ctx).await?;
+    if ciphertext.first().map(|b| b >> 4) == Some(SEALED_SENDER_V2_VERSION) {
+        return sealed_sender_v2_decrypt_to_usmc(ciphertext, &our_identity);
+    }
     let usm = UnidentifiedSenderMessage::deserialize(ciphertext)?;
 
     let eph_keys = EphemeralKeys::calculate(
@@ -694,6 +1067,17 @@ impl SealedSenderDecryptionResult {
     }
 }
 
+/// Checks that `cert` has not expired as of `now`.
+///
+/// This does not check the certificate's signatures; see [`SenderCertificate::validate`].
+pub fn validate_sender_certificate(cert: &SenderCertificate, now: u64) -> Result<()> {
+    let expired_at = cert.expiration()?;
+    if now > expired_at {
+        return Err(SignalProtocolError::CertificateExpired { expired_at, now });
+    }
+    Ok(())
+}
+
 #[allow(clippy::too_many_arguments)]
 pub async fn sealed_sender_decrypt(
     ciphertext: &[u8],
@@ -710,11 +1094,12 @@ pub async fn sealed_sender_decrypt(
 ) -> Result<SealedSenderDecryptionResult> {
     let usmc = sealed_sender_decrypt_to_usmc(ciphertext, identity_store, ctx).await?;
 
-    if !usmc.sender()?.validate(trust_root, timestamp)? {
+    if !usmc.sender()?.validate_signature(trust_root)? {
         return Err(SignalProtocolError::InvalidSealedSenderMessage(
             "trust root validation failed".to_string(),
         ));
     }
+    validate_sender_certificate(usmc.sender()?, timestamp)?;
 
     let is_local_uuid = local_uuid == usmc.sender()?.sender_uuid()?;
 
@@ -5,6 +5,7 @@
 
 use crate::consts;
 use crate::crypto::hmac_sha256;
+use crate::proto;
 use crate::proto::storage as storage_proto;
 use crate::{PrivateKey, ProtocolAddress, PublicKey, Result, SignalProtocolError, HKDF};
 
@@ -94,6 +95,17 @@ impl SenderMessageKey {
     }
 }
 
+/// Derives the message key for `iteration` from the sender chain key at that iteration, exactly as
+/// group encryption and decryption do.
+///
+/// Fails with [`SignalProtocolError::InvalidChainKeyLength`] unless `chain_key` is 32 bytes.
+pub fn derive_sender_message_key(chain_key: &[u8], iteration: u32) -> Result<SenderMessageKey> {
+    if chain_key.len() != 32 {
+        return Err(SignalProtocolError::InvalidChainKeyLength(chain_key.len()));
+    }
+    SenderChainKey::new(iteration, chain_key.to_vec())?.sender_message_key()
+}
+
 #[derive(Debug, Clone)]
 pub struct SenderChainKey {
     iteration: u32,
@@ -180,7 +192,10 @@ impl SenderKeyState {
     }
 
     pub fn deserialize(buf: &[u8]) -> Result<Self> {
-        let state = storage_proto::SenderKeyStateStructure::decode(buf)?;
+        let state = proto::decode_with_limit::<storage_proto::SenderKeyStateStructure>(
+            buf,
+            consts::MAX_SENDER_KEY_RECORD_PROTO_BYTES,
+        )?;
         Ok(Self { state })
     }
 
@@ -267,6 +282,91 @@ impl SenderKeyState {
             Ok(None)
         }
     }
+
+    /// Merges in another copy of the same sender key state (with the same key ID).
+    ///
+    /// The newer of the two chain keys is kept. A skipped message key is kept only if neither
+    /// copy has used it yet, so a message decrypted through either copy can't be decrypted again.
+    fn merge(&mut self, other: &SenderKeyState) -> Result<()> {
+        const FUNC: &str = "SenderKeyRecord::merge";
+
+        if self.signing_key_public()? != other.signing_key_public()? {
+            return Err(SignalProtocolError::InvalidState(
+                FUNC,
+                format!(
+                    "conflicting signing keys for sender key ID {}",
+                    self.sender_key_id()?
+                ),
+            ));
+        }
+
+        let our_chain_key = self.sender_chain_key()?;
+        let their_chain_key = other.sender_chain_key()?;
+        if our_chain_key.iteration()? == their_chain_key.iteration()?
+            && our_chain_key.seed()? != their_chain_key.seed()?
+        {
+            return Err(SignalProtocolError::InvalidState(
+                FUNC,
+                format!(
+                    "conflicting chain keys for sender key ID {} at iteration {}",
+                    self.sender_key_id()?,
+                    our_chain_key.iteration()?
+                ),
+            ));
+        }
+
+        // A key is still usable by one copy if that copy still has it, or hasn't reached its
+        // iteration yet.
+        let usable_by = |state: &SenderKeyState, chain_iteration: u32, iteration: u32| {
+            iteration >= chain_iteration
+                || state
+                    .state
+                    .sender_message_keys
+                    .iter()
+                    .any(|key| key.iteration == iteration)
+        };
+
+        let mut message_keys: Vec<storage_proto::sender_key_state_structure::SenderMessageKey> =
+            vec![];
+        for key in self
+            .state
+            .sender_message_keys
+            .iter()
+            .chain(&other.state.sender_message_keys)
+        {
+            if let Some(existing) = message_keys.iter().find(|k| k.iteration == key.iteration) {
+                if existing.seed != key.seed {
+                    return Err(SignalProtocolError::InvalidState(
+                        FUNC,
+                        format!(
+                            "conflicting message keys for sender key ID {} at iteration {}",
+                            self.sender_key_id()?,
+                            key.iteration
+                        ),
+                    ));
+                }
+                continue;
+            }
+            if usable_by(self, our_chain_key.iteration()?, key.iteration)
+                && usable_by(other, their_chain_key.iteration()?, key.iteration)
+            {
+                message_keys.push(key.clone());
+            }
+        }
+        message_keys.sort_by_key(|key| key.iteration);
+        if message_keys.len() > consts::MAX_MESSAGE_KEYS {
+            message_keys.drain(..message_keys.len() - consts::MAX_MESSAGE_KEYS);
+        }
+
+        if their_chain_key.iteration()? > our_chain_key.iteration()? {
+            self.set_sender_chain_key(their_chain_key)?;
+        }
+        if self.signing_key_private().is_err() {
+            self.state.sender_signing_key = other.state.sender_signing_key.clone();
+        }
+        self.state.sender_message_keys = message_keys;
+        Ok(())
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -282,7 +382,10 @@ impl SenderKeyRecord {
     }
 
     pub fn deserialize(buf: &[u8]) -> Result<SenderKeyRecord> {
-        let skr = storage_proto::SenderKeyRecordStructure::decode(buf)?;
+        let skr = proto::decode_with_limit::<storage_proto::SenderKeyRecordStructure>(
+            buf,
+            consts::MAX_SENDER_KEY_RECORD_PROTO_BYTES,
+        )?;
 
         let mut states = VecDeque::with_capacity(skr.sender_key_states.len());
         for state in skr.sender_key_states {
@@ -351,6 +454,45 @@ impl SenderKeyRecord {
         )
     }
 
+    /// Merges in another copy of this record, such as one saved concurrently by another process.
+    ///
+    /// States present in both copies are combined, keeping the newer chain key and whichever
+    /// skipped message keys neither copy has used yet. States only in `other` keep their place in
+    /// `other`'s newest-first order: each goes right after the state that precedes it there, or at
+    /// the front if nothing does, so a key that `other` has just rotated to becomes current. The
+    /// oldest states are then dropped beyond the usual limit.
+    ///
+    /// Fails with [`SignalProtocolError::InvalidState`] if the copies disagree about a state's
+    /// signing key or about a key at the same iteration; `self` is unchanged in that case.
+    pub fn merge(&mut self, other: &SenderKeyRecord) -> Result<()> {
+        let mut states = self.states.clone();
+        // Where the next state only in `other` goes: just after the last of `other`'s states seen.
+        let mut insert_at = 0;
+        for their_state in &other.states {
+            let key_id = their_state.sender_key_id()?;
+            let mut ours = None;
+            for (index, state) in states.iter_mut().enumerate() {
+                if state.sender_key_id()? == key_id {
+                    ours = Some((index, state));
+                    break;
+                }
+            }
+            match ours {
+                Some((index, our_state)) => {
+                    our_state.merge(their_state)?;
+                    insert_at = insert_at.max(index + 1);
+                }
+                None => {
+                    states.insert(insert_at, their_state.clone());
+                    insert_at += 1;
+                }
+            }
+        }
+        states.truncate(consts::MAX_SENDER_KEY_STATES);
+        self.states = states;
+        Ok(())
+    }
+
     pub fn as_protobuf(&self) -> Result<storage_proto::SenderKeyRecordStructure> {
         let mut states = Vec::with_capacity(self.states.len());
         for state in &self.states {
@@ -359,6 +501,7 @@ impl SenderKeyRecord {
 
         Ok(storage_proto::SenderKeyRecordStructure {
             sender_key_states: states,
+            schema_version: proto::PROTO_SCHEMA_VERSION,
         })
     }
 
@@ -368,3 +511,55 @@ impl SenderKeyRecord {
         Ok(buf)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_derive_sender_message_key() -> Result<()> {
+        // (chain key, iteration, seed, iv, cipher key)
+        let vectors = [
+            (
+                "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f",
+                0,
+                "9b4c8120a4823a95f47cde17a244f4507244ee6e3957d1fab9fa29b44d3829b7",
+                "ed1f5e26325b1399f6a34c76e47ff047",
+                "d89f10a08215e845ceb4df3fc59c052ad09e01cd499650025ff83df48ed656e6",
+            ),
+            (
+                "ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff",
+                7,
+                "85d3b07ddafe78925cb78c64e84fae54c2842034d0b88cc6d69fb8d106e3ffc8",
+                "0ec6e65c96f3ead0a620fdbf25eff70d",
+                "be743a648640388f26760b160ded562c22b30144175565514be59f63d7e08f92",
+            ),
+            (
+                "5ed51dcdebc8b7d39749fa4e67c146781fcee86cd26dfe4d41ba579660ce5088",
+                u32::MAX,
+                "37d22ae7b019d51535f0fa8687643b1ced7a63808156a768d9ddf6d56f488852",
+                "4e933819c7d140b5e11f4ed01469a2c2",
+                "c38fd49814714872da8974a77231ac7bbb41cf91304625b836550fedf60e70db",
+            ),
+        ];
+
+        for (chain_key, iteration, seed, iv, cipher_key) in &vectors {
+            let chain_key = hex::decode(chain_key).expect("valid hex");
+            let key = derive_sender_message_key(&chain_key, *iteration)?;
+            assert_eq!(key.iteration()?, *iteration);
+            assert_eq!(hex::encode(key.seed()?), *seed);
+            assert_eq!(hex::encode(key.iv()?), *iv);
+            assert_eq!(hex::encode(key.cipher_key()?), *cipher_key);
+
+            // The same key the chain itself produces.
+            let from_chain = SenderChainKey::new(*iteration, chain_key)?.sender_message_key()?;
+            assert_eq!(from_chain.seed()?, key.seed()?);
+        }
+
+        assert!(matches!(
+            derive_sender_message_key(&[0; 31], 0),
+            Err(SignalProtocolError::InvalidChainKeyLength(31))
+        ));
+        Ok(())
+    }
+}
@@ -4,8 +4,9 @@
 //
 
 use crate::{
-    Context, Direction, IdentityKeyStore, KeyPair, PreKeyBundle, PreKeySignalMessage, PreKeyStore,
-    ProtocolAddress, Result, SessionRecord, SessionStore, SignalProtocolError, SignedPreKeyStore,
+    Context, Direction, IdentityKey, IdentityKeyStore, IdentityTrust, KeyPair, PreKeyBundle,
+    PreKeySignalMessage, PreKeyStore, ProtocolAddress, Result, SessionRecord, SessionStore,
+    SignalProtocolError, SignedPreKeyStore,
 };
 
 use crate::ratchet;
@@ -22,6 +23,29 @@ its reference to the various data stores, instead the functions are
 free standing.
  */
 
+/// Fails unless `trust` says `their_identity_key` may be used for `remote_address`.
+///
+/// If the store has a different key saved for `remote_address`, the peer's identity changed, and
+/// the error carries both keys so that the app can tell the user. Otherwise, it's a plain
+/// [`SignalProtocolError::UntrustedIdentity`].
+pub(crate) fn require_trusted_identity(
+    trust: IdentityTrust,
+    remote_address: &ProtocolAddress,
+    their_identity_key: &IdentityKey,
+) -> Result<()> {
+    match trust {
+        IdentityTrust::Trusted => Ok(()),
+        IdentityTrust::Untrusted => Err(SignalProtocolError::UntrustedIdentity(
+            remote_address.clone(),
+        )),
+        IdentityTrust::Changed { old_key } => Err(SignalProtocolError::IdentityKeyChanged(
+            remote_address.clone(),
+            old_key,
+            *their_identity_key,
+        )),
+    }
+}
+
 pub async fn process_prekey(
     message: &PreKeySignalMessage,
     remote_address: &ProtocolAddress,
@@ -33,19 +57,15 @@ pub async fn process_prekey(
 ) -> Result<Option<PreKeyId>> {
     let their_identity_key = message.identity_key();
 
-    if !identity_store
+    let trust = identity_store
         .is_trusted_identity(
             &remote_address,
             their_identity_key,
             Direction::Receiving,
             ctx,
         )
-        .await?
-    {
-        return Err(SignalProtocolError::UntrustedIdentity(
-            remote_address.clone(),
-        ));
-    }
+        .await?;
+    require_trusted_identity(trust, remote_address, their_identity_key)?;
 
     let unsigned_pre_key_id = process_prekey_v3(
         message,
@@ -99,7 +119,7 @@ async fn process_prekey_v3(
 
     let parameters = BobSignalProtocolParameters::new(
         identity_store.get_identity_key_pair(ctx).await?,
-        our_signed_pre_key_pair, // signed pre key
+        our_signed_pre_key_pair.clone(), // signed pre key
         our_one_time_pre_key_pair,
         our_signed_pre_key_pair, // ratchet key
         *message.identity_key(),
@@ -119,6 +139,10 @@ async fn process_prekey_v3(
     Ok(message.pre_key_id())
 }
 
+/// Starts a new session with the owner of `bundle`.
+///
+/// The only randomness used is the new base key, drawn from `csprng`; tests can pass a seeded RNG
+/// to get reproducible session records.
 pub async fn process_prekey_bundle<R: Rng + CryptoRng>(
     remote_address: &ProtocolAddress,
     session_store: &mut dyn SessionStore,
@@ -129,14 +153,10 @@ pub async fn process_prekey_bundle<R: Rng + CryptoRng>(
 ) -> Result<()> {
     let their_identity_key = bundle.identity_key()?;
 
-    if !identity_store
+    let trust = identity_store
         .is_trusted_identity(&remote_address, their_identity_key, Direction::Sending, ctx)
-        .await?
-    {
-        return Err(SignalProtocolError::UntrustedIdentity(
-            remote_address.clone(),
-        ));
-    }
+        .await?;
+    require_trusted_identity(trust, remote_address, their_identity_key)?;
 
     if !their_identity_key.public_key().verify_signature(
         &bundle.signed_pre_key_public()?.serialize(),
@@ -158,6 +178,7 @@ pub async fn process_prekey_bundle<R: Rng + CryptoRng>(
 
     let our_identity_key_pair = identity_store.get_identity_key_pair(ctx).await?;
 
+    let our_base_public_key = our_base_key_pair.public_key;
     let parameters = AliceSignalProtocolParameters::new(
         our_identity_key_pair,
         our_base_key_pair,
@@ -178,12 +199,12 @@ pub async fn process_prekey_bundle<R: Rng + CryptoRng>(
     session.set_unacknowledged_pre_key_message(
         their_one_time_prekey_id,
         bundle.signed_pre_key_id()?,
-        &our_base_key_pair.public_key,
+        &our_base_public_key,
     )?;
 
     session.set_local_registration_id(identity_store.get_local_registration_id(ctx).await?)?;
     session.set_remote_registration_id(bundle.registration_id()?)?;
-    session.set_alice_base_key(&our_base_key_pair.public_key.serialize())?;
+    session.set_alice_base_key(&our_base_public_key.serialize())?;
 
     identity_store
         .save_identity(&remote_address, their_identity_key, ctx)
@@ -4,18 +4,25 @@
 //
 
 use crate::{
-    CiphertextMessage, Context, Direction, IdentityKeyStore, KeyPair, PreKeySignalMessage,
-    PreKeyStore, ProtocolAddress, PublicKey, Result, SessionRecord, SessionStore, SignalMessage,
-    SignalProtocolError, SignedPreKeyStore,
+    CiphertextMessage, Context, Direction, IdentityKeyStore, IdentityTrust, KeyPair,
+    PreKeySignalMessage, PreKeyStore, ProtocolAddress, PublicKey, Result, SessionRecord,
+    SessionStore, SignalMessage, SignalProtocolError, SignedPreKeyStore,
 };
 
-use crate::consts::MAX_FORWARD_JUMPS;
+use crate::consts;
 use crate::crypto;
+use crate::proto;
+use crate::protocol::CIPHERTEXT_MESSAGE_CURRENT_VERSION;
 use crate::ratchet::{ChainKey, MessageKeys};
 use crate::session;
 use crate::state::SessionState;
 
+use std::convert::TryFrom;
+
+use hmac::{Hmac, Mac};
 use rand::{CryptoRng, Rng};
+use sha2::Sha256;
+use subtle::ConstantTimeEq;
 
 pub async fn message_encrypt(
     ptext: &[u8],
@@ -24,6 +31,14 @@ pub async fn message_encrypt(
     identity_store: &mut dyn IdentityKeyStore,
     ctx: Context,
 ) -> Result<CiphertextMessage> {
+    if ptext.len() > consts::MAX_MESSAGE_PLAINTEXT_BYTES {
+        return Err(SignalProtocolError::InvalidArgument(format!(
+            "message of {} bytes exceeds the limit of {} bytes",
+            ptext.len(),
+            consts::MAX_MESSAGE_PLAINTEXT_BYTES
+        )));
+    }
+
     let mut session_record = session_store
         .load_session(&remote_address, ctx)
         .await?
@@ -92,15 +107,15 @@ pub async fn message_encrypt(
     session_state.set_sender_chain_key(&chain_key.next_chain_key()?)?;
 
     // XXX why is this check after everything else?!!
-    if !identity_store
+    let trust = identity_store
         .is_trusted_identity(
             &remote_address,
             &their_identity_key,
             Direction::Sending,
             ctx,
         )
-        .await?
-    {
+        .await?;
+    if trust != IdentityTrust::Trusted {
         log::warn!(
             "Identity key {} is not trusted for remote address {}",
             their_identity_key
@@ -109,10 +124,8 @@ pub async fn message_encrypt(
                 .map_or_else(|e| format!("<error: {}>", e), hex::encode),
             remote_address,
         );
-        return Err(SignalProtocolError::UntrustedIdentity(
-            remote_address.clone(),
-        ));
     }
+    session::require_trusted_identity(trust, remote_address, &their_identity_key)?;
 
     // XXX this could be combined with the above call to the identity store (in a new API)
     identity_store
@@ -226,21 +239,42 @@ pub async fn message_decrypt_signal<R: Rng + CryptoRng>(
     let ptext =
         decrypt_message_with_record(&remote_address, &mut session_record, ciphertext, csprng)?;
 
+    save_decrypted_session(
+        remote_address,
+        &session_record,
+        session_store,
+        identity_store,
+        ctx,
+    )
+    .await?;
+
+    Ok(ptext)
+}
+
+/// Saves a session after successfully decrypting a SignalMessage with it, as long as the sender's
+/// identity is still trusted.
+async fn save_decrypted_session(
+    remote_address: &ProtocolAddress,
+    session_record: &SessionRecord,
+    session_store: &mut dyn SessionStore,
+    identity_store: &mut dyn IdentityKeyStore,
+    ctx: Context,
+) -> Result<()> {
     // Why are we performing this check after decryption instead of before?
     let their_identity_key = session_record
         .session_state()?
         .remote_identity_key()?
         .ok_or(SignalProtocolError::InvalidSessionStructure)?;
 
-    if !identity_store
+    let trust = identity_store
         .is_trusted_identity(
             &remote_address,
             &their_identity_key,
             Direction::Receiving,
             ctx,
         )
-        .await?
-    {
+        .await?;
+    if trust != IdentityTrust::Trusted {
         log::warn!(
             "Identity key {} is not trusted for remote address {}",
             their_identity_key
@@ -249,10 +283,8 @@ pub async fn message_decrypt_signal<R: Rng + CryptoRng>(
                 .map_or_else(|e| format!("<error: {}>", e), hex::encode),
             remote_address,
         );
-        return Err(SignalProtocolError::UntrustedIdentity(
-            remote_address.clone(),
-        ));
     }
+    session::require_trusted_identity(trust, remote_address, &their_identity_key)?;
 
     identity_store
         .save_identity(&remote_address, &their_identity_key, ctx)
@@ -262,7 +294,379 @@ pub async fn message_decrypt_signal<R: Rng + CryptoRng>(
         .store_session(&remote_address, &session_record, ctx)
         .await?;
 
-    Ok(ptext)
+    Ok(())
+}
+
+/// Decrypts a serialized [`SignalMessage`] incrementally, for payloads too large to comfortably
+/// hold in memory at once.
+///
+/// Pass the serialized message to [`update`](Self::update) in order, in chunks of any size, then
+/// call [`finalize`](Self::finalize) to check the message's MAC and save the session. Plaintext is
+/// returned as soon as it is decrypted, which means **it has not been authenticated** until
+/// `finalize` succeeds; if `finalize` fails, all plaintext returned so far must be discarded.
+///
+/// The advanced ratchet state is kept in the decryptor between calls and only saved to the store
+/// by a successful `finalize`. Unlike [`message_decrypt_signal`], only one session state is
+/// tried: the first one (starting with the current state) that already has a receiver chain for
+/// the message's ratchet key, or else the current state.
+///
+/// Errors:
+///
+/// - Once any call to `update` fails, the decryptor cannot be used again, and later calls fail
+///   with [`SignalProtocolError::InvalidState`].
+/// - Data after the end of the message, including any chunk passed to `update` once the message
+///   is complete, is rejected with [`SignalProtocolError::InvalidMessage`].
+/// - If `finalize` is called before the whole message has been received (a truncated final
+///   chunk), it fails with [`SignalProtocolError::CiphertextMessageTooShort`].
+/// - Chunks passed out of order are indistinguishable from a tampered message, and fail the MAC
+///   check in `finalize` with [`SignalProtocolError::InvalidCiphertext`].
+///
+/// The ciphertext must be the last field in the message, as in messages created by
+/// [`SignalMessage::new`].
+pub struct StreamingDecryptor<'a> {
+    remote_address: ProtocolAddress,
+    session_store: &'a mut dyn SessionStore,
+    identity_store: &'a mut dyn IdentityKeyStore,
+    ctx: Context,
+    record: SessionRecord,
+    /// Used for our next sending chain if the message starts a new receiver chain.
+    new_ephemeral: KeyPair,
+    stage: StreamingStage,
+    received_len: usize,
+}
+
+enum StreamingStage {
+    /// Buffering the version byte and the protobuf fields that precede the ciphertext.
+    Header(Vec<u8>),
+    /// Decrypting the ciphertext, then collecting the MAC.
+    Body(Box<StreamingBody>),
+    /// An earlier call to `update` failed.
+    Failed,
+}
+
+struct StreamingBody {
+    state: SessionState,
+    /// The index of `state` among the previous session states, if it isn't the current one.
+    previous_state_index: Option<usize>,
+    mac: Hmac<Sha256>,
+    cipher: crypto::Aes256CbcDecryptor,
+    ciphertext_remaining: usize,
+    their_mac: Vec<u8>,
+}
+
+impl<'a> StreamingDecryptor<'a> {
+    /// The most header data that will be buffered while looking for the start of the ciphertext.
+    const MAX_HEADER_LEN: usize = 1024;
+
+    /// Protobuf field number of `SignalMessage.ciphertext`.
+    const CIPHERTEXT_FIELD_NUMBER: u64 = 4;
+
+    /// Starts decrypting a message from `remote_address`, whose session must already exist.
+    pub async fn new<R: Rng + CryptoRng>(
+        remote_address: &ProtocolAddress,
+        session_store: &'a mut dyn SessionStore,
+        identity_store: &'a mut dyn IdentityKeyStore,
+        csprng: &mut R,
+        ctx: Context,
+    ) -> Result<StreamingDecryptor<'a>> {
+        let record = session_store
+            .load_session(remote_address, ctx)
+            .await?
+            .ok_or_else(|| SignalProtocolError::SessionNotFound(format!("{}", remote_address)))?;
+
+        Ok(Self {
+            remote_address: remote_address.clone(),
+            session_store,
+            identity_store,
+            ctx,
+            record,
+            new_ephemeral: KeyPair::generate(csprng),
+            stage: StreamingStage::Header(vec![]),
+            received_len: 0,
+        })
+    }
+
+    /// Processes the next chunk of the serialized message, returning any plaintext it completes.
+    ///
+    /// The returned plaintext is not authenticated until [`finalize`](Self::finalize) succeeds.
+    pub fn update(&mut self, chunk: &[u8]) -> Result<Vec<u8>> {
+        // Leave the decryptor marked as failed unless this chunk is processed successfully.
+        let stage = std::mem::replace(&mut self.stage, StreamingStage::Failed);
+        self.received_len += chunk.len();
+
+        let header;
+        let (mut body, ciphertext) = match stage {
+            StreamingStage::Header(mut buffered) => {
+                buffered.extend_from_slice(chunk);
+                match Self::find_ciphertext(&buffered)? {
+                    None => {
+                        self.stage = StreamingStage::Header(buffered);
+                        return Ok(vec![]);
+                    }
+                    Some((field_start, start, len)) => {
+                        let body = self.start_body(&buffered, field_start, start, len)?;
+                        header = buffered;
+                        (body, &header[start..])
+                    }
+                }
+            }
+            StreamingStage::Body(body) => (body, chunk),
+            StreamingStage::Failed => {
+                return Err(SignalProtocolError::InvalidState(
+                    "StreamingDecryptor::update",
+                    "an earlier chunk could not be decrypted".to_string(),
+                ))
+            }
+        };
+
+        let ciphertext_len = ciphertext.len().min(body.ciphertext_remaining);
+        let (ciphertext, rest) = ciphertext.split_at(ciphertext_len);
+        body.ciphertext_remaining -= ciphertext_len;
+        body.mac.update(ciphertext);
+        let ptext = body.cipher.update(ciphertext);
+
+        if body.their_mac.len() + rest.len() > SignalMessage::MAC_LENGTH {
+            return Err(SignalProtocolError::InvalidMessage(
+                "data after the end of a streamed message",
+            ));
+        }
+        body.their_mac.extend_from_slice(rest);
+
+        self.stage = StreamingStage::Body(body);
+        Ok(ptext)
+    }
+
+    /// Checks the message's MAC, returning the rest of the plaintext and saving the session if it
+    /// is valid.
+    pub async fn finalize(self) -> Result<Vec<u8>> {
+        let body = match self.stage {
+            StreamingStage::Header(_) => {
+                return Err(SignalProtocolError::CiphertextMessageTooShort(
+                    self.received_len,
+                ))
+            }
+            StreamingStage::Body(body) => body,
+            StreamingStage::Failed => {
+                return Err(SignalProtocolError::InvalidState(
+                    "StreamingDecryptor::finalize",
+                    "an earlier chunk could not be decrypted".to_string(),
+                ))
+            }
+        };
+        let StreamingBody {
+            mut state,
+            previous_state_index,
+            mac,
+            cipher,
+            ciphertext_remaining,
+            their_mac,
+        } = *body;
+
+        if ciphertext_remaining > 0 || their_mac.len() < SignalMessage::MAC_LENGTH {
+            return Err(SignalProtocolError::CiphertextMessageTooShort(
+                self.received_len,
+            ));
+        }
+
+        let our_mac = mac.finalize().into_bytes();
+        let our_mac = &our_mac[..SignalMessage::MAC_LENGTH];
+        if !bool::from(our_mac.ct_eq(&their_mac)) {
+            log::error!(
+                "Bad Mac! Their Mac: {} Our Mac: {}",
+                hex::encode(&their_mac),
+                hex::encode(our_mac)
+            );
+            return Err(SignalProtocolError::InvalidCiphertext);
+        }
+
+        let ptext = cipher.finalize()?;
+        state.clear_unacknowledged_pre_key_message()?;
+
+        let mut record = self.record;
+        match previous_state_index {
+            None => record.set_session_state(state)?,
+            Some(index) => record.promote_old_session(index, state)?,
+        }
+
+        save_decrypted_session(
+            &self.remote_address,
+            &record,
+            self.session_store,
+            self.identity_store,
+            self.ctx,
+        )
+        .await?;
+
+        Ok(ptext)
+    }
+
+    /// Derives the message keys from the fields before the ciphertext, and sets up the MAC and
+    /// decryption of the rest of the message.
+    fn start_body(
+        &self,
+        header: &[u8],
+        ciphertext_field_start: usize,
+        ciphertext_start: usize,
+        ciphertext_len: usize,
+    ) -> Result<Box<StreamingBody>> {
+        let message_version = header[0] >> 4;
+        let fields = proto::decode_with_limit::<proto::wire::SignalMessage>(
+            &header[1..ciphertext_field_start],
+            consts::MAX_MESSAGE_PROTO_BYTES,
+        )?;
+        let their_ephemeral = fields
+            .ratchet_key
+            .ok_or(SignalProtocolError::InvalidProtobufEncoding)?;
+        let their_ephemeral = PublicKey::deserialize(&their_ephemeral)?;
+        let counter = fields
+            .counter
+            .ok_or(SignalProtocolError::InvalidProtobufEncoding)?;
+
+        let (previous_state_index, mut state) = self.select_state(&their_ephemeral)?;
+
+        if !state.has_sender_chain()? {
+            return Err(SignalProtocolError::InvalidMessage(
+                "No session available to decrypt",
+            ));
+        }
+        if message_version as u32 != state.session_version()? {
+            return Err(SignalProtocolError::UnrecognizedMessageVersion(
+                message_version as u32,
+            ));
+        }
+
+        let new_ephemeral = &self.new_ephemeral;
+        let chain_key =
+            get_or_create_chain_key(&mut state, &their_ephemeral, &self.remote_address, || {
+                new_ephemeral.clone()
+            })?;
+        let message_keys = get_or_create_message_key(
+            &mut state,
+            &their_ephemeral,
+            &self.remote_address,
+            &chain_key,
+            counter,
+        )?;
+
+        let their_identity_key = state
+            .remote_identity_key()?
+            .ok_or(SignalProtocolError::InvalidSessionStructure)?;
+        let mut mac = SignalMessage::start_mac(
+            &their_identity_key,
+            &state.local_identity_key()?,
+            message_keys.mac_key(),
+        )?;
+        mac.update(&header[..ciphertext_start]);
+        let cipher = crypto::Aes256CbcDecryptor::new(message_keys.cipher_key(), message_keys.iv())?;
+
+        Ok(Box::new(StreamingBody {
+            state,
+            previous_state_index,
+            mac,
+            cipher,
+            ciphertext_remaining: ciphertext_len,
+            their_mac: Vec::with_capacity(SignalMessage::MAC_LENGTH),
+        }))
+    }
+
+    /// Picks the session state with a receiver chain for `their_ephemeral`, falling back to the
+    /// current state.
+    fn select_state(&self, their_ephemeral: &PublicKey) -> Result<(Option<usize>, SessionState)> {
+        let current_state = self.record.session_state()?;
+        if current_state
+            .get_receiver_chain_key(their_ephemeral)?
+            .is_none()
+        {
+            for (index, previous) in self.record.previous_session_states()?.enumerate() {
+                if previous.get_receiver_chain_key(their_ephemeral)?.is_some() {
+                    return Ok((Some(index), previous.clone()));
+                }
+            }
+        }
+        Ok((None, current_state.clone()))
+    }
+
+    /// Scans the start of a serialized message for the ciphertext field.
+    ///
+    /// Returns the offsets of the field and of the ciphertext itself, along with the ciphertext's
+    /// length, or `None` if more data is needed.
+    fn find_ciphertext(header: &[u8]) -> Result<Option<(usize, usize, usize)>> {
+        let version_byte = match header.first() {
+            Some(byte) => *byte,
+            None => return Ok(None),
+        };
+        let ciphertext_version = version_byte & 0x0F;
+        if ciphertext_version < CIPHERTEXT_MESSAGE_CURRENT_VERSION {
+            return Err(SignalProtocolError::LegacyCiphertextVersion(
+                ciphertext_version,
+            ));
+        }
+        if ciphertext_version > CIPHERTEXT_MESSAGE_CURRENT_VERSION {
+            return Err(SignalProtocolError::UnrecognizedCiphertextVersion(
+                ciphertext_version,
+            ));
+        }
+
+        let mut offset = 1;
+        while offset < header.len() && offset <= Self::MAX_HEADER_LEN {
+            let field_start = offset;
+            let key = match read_varint(&header[offset..])? {
+                Some((key, key_len)) => {
+                    offset += key_len;
+                    key
+                }
+                None => return Ok(None),
+            };
+            let value_len = match key & 0x7 {
+                // varint
+                0 => match read_varint(&header[offset..])? {
+                    Some((_, len)) => len,
+                    None => return Ok(None),
+                },
+                // 64-bit
+                1 => 8,
+                // length-delimited
+                2 => match read_varint(&header[offset..])? {
+                    Some((len, len_len)) => {
+                        offset += len_len;
+                        let len = usize::try_from(len)
+                            .map_err(|_| SignalProtocolError::InvalidProtobufEncoding)?;
+                        if key >> 3 == Self::CIPHERTEXT_FIELD_NUMBER {
+                            return Ok(Some((field_start, offset, len)));
+                        }
+                        len
+                    }
+                    None => return Ok(None),
+                },
+                // 32-bit
+                5 => 4,
+                _ => return Err(SignalProtocolError::InvalidProtobufEncoding),
+            };
+            offset = offset.saturating_add(value_len);
+        }
+
+        if header.len() > Self::MAX_HEADER_LEN {
+            return Err(SignalProtocolError::InvalidProtobufEncoding);
+        }
+        Ok(None)
+    }
+}
+
+/// Reads a protobuf varint from the start of `input`, returning its value and encoded length, or
+/// `None` if `input` ends first.
+fn read_varint(input: &[u8]) -> Result<Option<(u64, usize)>> {
+    const MAX_VARINT_LEN: usize = 10;
+    let mut value = 0u64;
+    for (i, byte) in input.iter().take(MAX_VARINT_LEN).enumerate() {
+        value |= u64::from(byte & 0x7F) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Ok(Some((value, i + 1)));
+        }
+    }
+    if input.len() >= MAX_VARINT_LEN {
+        return Err(SignalProtocolError::InvalidProtobufEncoding);
+    }
+    Ok(None)
 }
 
 fn create_decryption_failure_log(
@@ -426,6 +830,14 @@ fn decrypt_message_with_record<R: Rng + CryptoRng>(
             "{}",
             create_decryption_failure_log(remote_address, &errs, record, ciphertext)?
         );
+        // If a state would have had to skip too many keys, say so rather than failing generically,
+        // so that the caller can tell the message was valid but too far ahead.
+        if let Some(idx) = errs
+            .iter()
+            .position(|e| matches!(e, SignalProtocolError::TooManySkippedKeys(_, _)))
+        {
+            return Err(errs.swap_remove(idx));
+        }
         Err(SignalProtocolError::InvalidMessage(
             "Message decryption failed",
         ))
@@ -453,7 +865,9 @@ fn decrypt_message_with_state<R: Rng + CryptoRng>(
 
     let their_ephemeral = ciphertext.sender_ratchet_key();
     let counter = ciphertext.counter();
-    let chain_key = get_or_create_chain_key(state, their_ephemeral, remote_address, csprng)?;
+    let chain_key = get_or_create_chain_key(state, their_ephemeral, remote_address, || {
+        KeyPair::generate(csprng)
+    })?;
     let message_keys =
         get_or_create_message_key(state, their_ephemeral, remote_address, &chain_key, counter)?;
 
@@ -482,11 +896,13 @@ fn decrypt_message_with_state<R: Rng + CryptoRng>(
     Ok(ptext)
 }
 
-fn get_or_create_chain_key<R: Rng + CryptoRng>(
+/// Finds the receiver chain for `their_ephemeral`, or performs a DH ratchet step to create one,
+/// using the key pair from `new_ephemeral` for our next sending chain.
+fn get_or_create_chain_key(
     state: &mut SessionState,
     their_ephemeral: &PublicKey,
     remote_address: &ProtocolAddress,
-    csprng: &mut R,
+    new_ephemeral: impl FnOnce() -> KeyPair,
 ) -> Result<ChainKey> {
     if let Some(chain) = state.get_receiver_chain_key(their_ephemeral)? {
         log::debug!("{} has existing receiver chain.", remote_address);
@@ -498,7 +914,7 @@ fn get_or_create_chain_key<R: Rng + CryptoRng>(
     let root_key = state.root_key()?;
     let our_ephemeral = state.sender_ratchet_private_key()?;
     let receiver_chain = root_key.create_chain(their_ephemeral, &our_ephemeral)?;
-    let our_new_ephemeral = KeyPair::generate(csprng);
+    let our_new_ephemeral = new_ephemeral();
     let sender_chain = receiver_chain
         .0
         .create_chain(their_ephemeral, &our_new_ephemeral.private_key)?;
@@ -544,18 +960,17 @@ fn get_or_create_message_key(
     assert!(chain_index <= counter);
 
     let jump = (counter - chain_index) as usize;
+    let limit = state.max_skipped_message_keys();
 
-    if jump > MAX_FORWARD_JUMPS {
+    if jump > limit {
         log::error!(
             "{} Exceeded future message limit: {}, index: {}, counter: {})",
             remote_address,
-            MAX_FORWARD_JUMPS,
+            limit,
             chain_index,
             counter
         );
-        return Err(SignalProtocolError::InvalidMessage(
-            "message from too far into the future",
-        ));
+        return Err(SignalProtocolError::TooManySkippedKeys(jump, limit));
     }
 
     let mut chain_key = chain_key.clone();
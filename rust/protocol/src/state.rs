@@ -8,8 +8,8 @@ mod prekey;
 mod session;
 mod signed_prekey;
 
-pub use bundle::PreKeyBundle;
-pub use prekey::{PreKeyId, PreKeyRecord};
-pub use session::SessionRecord;
+pub use bundle::{PreKeyBundle, PreKeyBundleBuilder};
+pub use prekey::{generate_pre_keys, PreKeyId, PreKeyRecord, MAX_PRE_KEY_BATCH_SIZE};
 pub(crate) use session::SessionState;
+pub use session::{SessionRecord, SessionStateDiff};
 pub use signed_prekey::{SignedPreKeyId, SignedPreKeyRecord};
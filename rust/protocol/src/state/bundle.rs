@@ -4,7 +4,10 @@
 //
 
 use crate::state::{PreKeyId, SignedPreKeyId};
-use crate::{IdentityKey, PublicKey, Result};
+use crate::{IdentityKey, PublicKey, Result, SignalProtocolError};
+
+/// Registration IDs are generated in the range `1..=MAX_REGISTRATION_ID`.
+const MAX_REGISTRATION_ID: u32 = 16380;
 
 #[derive(Debug, Clone)]
 pub struct PreKeyBundle {
@@ -19,6 +22,7 @@ pub struct PreKeyBundle {
 }
 
 impl PreKeyBundle {
+    #[deprecated(note = "use PreKeyBundleBuilder instead")]
     pub fn new(
         registration_id: u32,
         device_id: u32,
@@ -45,6 +49,39 @@ impl PreKeyBundle {
         })
     }
 
+    /// Checks that the bundle is internally consistent, without consulting any stores.
+    ///
+    /// Building a session checks the signature too, but calling this as soon as a bundle is
+    /// received gives a specific error for each kind of problem:
+    ///
+    /// - [`InvalidRegistrationId`](SignalProtocolError::InvalidRegistrationId) if the
+    ///   registration ID is not in `1..=16380`
+    /// - [`InvalidDeviceId`](SignalProtocolError::InvalidDeviceId) if the device ID is 0
+    /// - [`InconsistentOneTimePreKey`](SignalProtocolError::InconsistentOneTimePreKey) if only one
+    ///   of the one-time prekey's ID and public key is present
+    /// - [`InvalidSignedPreKeySignature`](SignalProtocolError::InvalidSignedPreKeySignature) if
+    ///   the signed prekey was not signed by the bundle's identity key
+    pub fn validate(&self) -> Result<()> {
+        if !(1..=MAX_REGISTRATION_ID).contains(&self.registration_id) {
+            return Err(SignalProtocolError::InvalidRegistrationId(
+                self.registration_id,
+            ));
+        }
+        if self.device_id == 0 {
+            return Err(SignalProtocolError::InvalidDeviceId(self.device_id));
+        }
+        if self.pre_key_id.is_some() != self.pre_key_public.is_some() {
+            return Err(SignalProtocolError::InconsistentOneTimePreKey);
+        }
+        if !self.identity_key.public_key().verify_signature(
+            &self.signed_pre_key_public.serialize(),
+            &self.signed_pre_key_signature,
+        )? {
+            return Err(SignalProtocolError::InvalidSignedPreKeySignature);
+        }
+        Ok(())
+    }
+
     pub fn registration_id(&self) -> Result<u32> {
         Ok(self.registration_id)
     }
@@ -77,3 +114,248 @@ impl PreKeyBundle {
         Ok(&self.identity_key)
     }
 }
+
+/// Assembles a [`PreKeyBundle`] from named parts.
+///
+/// The registration ID, device ID, signed prekey, and identity key are required; the one-time
+/// prekey is optional.
+#[derive(Debug, Clone, Default)]
+pub struct PreKeyBundleBuilder {
+    registration_id: Option<u32>,
+    device_id: Option<u32>,
+    pre_key: Option<(PreKeyId, PublicKey)>,
+    signed_pre_key: Option<(SignedPreKeyId, PublicKey, Vec<u8>)>,
+    identity_key: Option<IdentityKey>,
+}
+
+impl PreKeyBundleBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn registration_id(mut self, registration_id: u32) -> Self {
+        self.registration_id = Some(registration_id);
+        self
+    }
+
+    pub fn device_id(mut self, device_id: u32) -> Self {
+        self.device_id = Some(device_id);
+        self
+    }
+
+    pub fn pre_key(mut self, id: PreKeyId, key: PublicKey) -> Self {
+        self.pre_key = Some((id, key));
+        self
+    }
+
+    pub fn signed_pre_key(mut self, id: SignedPreKeyId, key: PublicKey, signature: &[u8]) -> Self {
+        self.signed_pre_key = Some((id, key, signature.to_vec()));
+        self
+    }
+
+    pub fn identity_key(mut self, identity_key: IdentityKey) -> Self {
+        self.identity_key = Some(identity_key);
+        self
+    }
+
+    /// Produces the bundle, failing with
+    /// [`InvalidArgument`](SignalProtocolError::InvalidArgument) if a required part is missing or
+    /// with [`InvalidSignedPreKeySignature`](SignalProtocolError::InvalidSignedPreKeySignature) if
+    /// the signature is not the length of a signature.
+    ///
+    /// The signature itself is not checked here; use [`PreKeyBundle::validate`] for that.
+    pub fn build(self) -> Result<PreKeyBundle> {
+        fn missing(field: &str) -> SignalProtocolError {
+            SignalProtocolError::InvalidArgument(format!("PreKeyBundle is missing {}", field))
+        }
+
+        let registration_id = self
+            .registration_id
+            .ok_or_else(|| missing("a registration ID"))?;
+        let device_id = self.device_id.ok_or_else(|| missing("a device ID"))?;
+        let (signed_pre_key_id, signed_pre_key_public, signed_pre_key_signature) = self
+            .signed_pre_key
+            .ok_or_else(|| missing("a signed prekey"))?;
+        let identity_key = self
+            .identity_key
+            .ok_or_else(|| missing("an identity key"))?;
+
+        if signed_pre_key_signature.len() != 64 {
+            return Err(SignalProtocolError::InvalidSignedPreKeySignature);
+        }
+
+        let (pre_key_id, pre_key_public) = match self.pre_key {
+            None => (None, None),
+            Some((id, key)) => (Some(id), Some(key)),
+        };
+
+        Ok(PreKeyBundle {
+            registration_id,
+            device_id,
+            pre_key_id,
+            pre_key_public,
+            signed_pre_key_id,
+            signed_pre_key_public,
+            signed_pre_key_signature,
+            identity_key,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::OsRng;
+
+    use super::*;
+    use crate::{IdentityKeyPair, KeyPair};
+
+    fn valid_bundle() -> PreKeyBundle {
+        let mut csprng = OsRng;
+        let identity_key_pair = IdentityKeyPair::generate(&mut csprng);
+        let pre_key_pair = KeyPair::generate(&mut csprng);
+        let signed_pre_key_pair = KeyPair::generate(&mut csprng);
+        let signature = identity_key_pair
+            .private_key()
+            .calculate_signature(&signed_pre_key_pair.public_key.serialize(), &mut csprng)
+            .expect("can sign");
+
+        PreKeyBundleBuilder::new()
+            .registration_id(1234)
+            .device_id(1)
+            .pre_key(31337, pre_key_pair.public_key)
+            .signed_pre_key(22, signed_pre_key_pair.public_key, &signature)
+            .identity_key(*identity_key_pair.identity_key())
+            .build()
+            .expect("valid arguments")
+    }
+
+    #[test]
+    fn test_builder() {
+        let bundle = valid_bundle();
+        let builder = PreKeyBundleBuilder::new()
+            .registration_id(bundle.registration_id)
+            .device_id(bundle.device_id)
+            .signed_pre_key(
+                bundle.signed_pre_key_id,
+                bundle.signed_pre_key_public,
+                &bundle.signed_pre_key_signature,
+            )
+            .identity_key(bundle.identity_key);
+
+        let without_pre_key = builder.clone().build().expect("pre key is optional");
+        assert_eq!(without_pre_key.pre_key_id().expect("ok"), None);
+        assert_eq!(without_pre_key.pre_key_public().expect("ok"), None);
+        assert!(without_pre_key.validate().is_ok());
+
+        for incomplete in &[
+            PreKeyBundleBuilder {
+                registration_id: None,
+                ..builder.clone()
+            },
+            PreKeyBundleBuilder {
+                device_id: None,
+                ..builder.clone()
+            },
+            PreKeyBundleBuilder {
+                signed_pre_key: None,
+                ..builder.clone()
+            },
+            PreKeyBundleBuilder {
+                identity_key: None,
+                ..builder.clone()
+            },
+        ] {
+            assert!(matches!(
+                incomplete.clone().build(),
+                Err(SignalProtocolError::InvalidArgument(_))
+            ));
+        }
+
+        let truncated = &bundle.signed_pre_key_signature[..63];
+        assert!(matches!(
+            builder
+                .signed_pre_key(
+                    bundle.signed_pre_key_id,
+                    bundle.signed_pre_key_public,
+                    truncated
+                )
+                .build(),
+            Err(SignalProtocolError::InvalidSignedPreKeySignature)
+        ));
+    }
+
+    #[test]
+    fn test_validate() {
+        let bundle = valid_bundle();
+        assert!(bundle.validate().is_ok());
+
+        let without_pre_key = PreKeyBundle {
+            pre_key_id: None,
+            pre_key_public: None,
+            ..bundle.clone()
+        };
+        assert!(without_pre_key.validate().is_ok());
+
+        for &registration_id in &[0, MAX_REGISTRATION_ID + 1] {
+            let bad = PreKeyBundle {
+                registration_id,
+                ..bundle.clone()
+            };
+            assert!(matches!(
+                bad.validate(),
+                Err(SignalProtocolError::InvalidRegistrationId(id)) if id == registration_id
+            ));
+        }
+        let max_registration_id = PreKeyBundle {
+            registration_id: MAX_REGISTRATION_ID,
+            ..bundle.clone()
+        };
+        assert!(max_registration_id.validate().is_ok());
+
+        let bad = PreKeyBundle {
+            device_id: 0,
+            ..bundle.clone()
+        };
+        assert!(matches!(
+            bad.validate(),
+            Err(SignalProtocolError::InvalidDeviceId(0))
+        ));
+
+        let bad = PreKeyBundle {
+            pre_key_public: None,
+            ..bundle.clone()
+        };
+        assert!(matches!(
+            bad.validate(),
+            Err(SignalProtocolError::InconsistentOneTimePreKey)
+        ));
+        let bad = PreKeyBundle {
+            pre_key_id: None,
+            ..bundle.clone()
+        };
+        assert!(matches!(
+            bad.validate(),
+            Err(SignalProtocolError::InconsistentOneTimePreKey)
+        ));
+
+        let mut signature = bundle.signed_pre_key_signature.clone();
+        signature[0] ^= 1;
+        let bad = PreKeyBundle {
+            signed_pre_key_signature: signature,
+            ..bundle.clone()
+        };
+        assert!(matches!(
+            bad.validate(),
+            Err(SignalProtocolError::InvalidSignedPreKeySignature)
+        ));
+
+        let bad = PreKeyBundle {
+            identity_key: *valid_bundle().identity_key().expect("present"),
+            ..bundle
+        };
+        assert!(matches!(
+            bad.validate(),
+            Err(SignalProtocolError::InvalidSignedPreKeySignature)
+        ));
+    }
+}
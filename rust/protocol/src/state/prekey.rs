@@ -3,12 +3,21 @@
 // SPDX-License-Identifier: AGPL-3.0-only
 //
 
+use crate::consts;
+use crate::proto;
 use crate::proto::storage::PreKeyRecordStructure;
-use crate::{KeyPair, PrivateKey, PublicKey, Result};
+use crate::{KeyPair, PrivateKey, PublicKey, Result, SignalProtocolError};
 use prost::Message;
+use rand::{CryptoRng, Rng};
 
 pub type PreKeyId = u32;
 
+/// The largest number of pre-keys [`generate_pre_keys`] will generate in one call.
+///
+/// Clients upload pre-keys in batches of 100, so this leaves plenty of room while still rejecting
+/// a bad `count`, such as a negative Java `int`, before it allocates billions of records.
+pub const MAX_PRE_KEY_BATCH_SIZE: u32 = 65535;
+
 #[derive(Debug, Clone)]
 pub struct PreKeyRecord {
     pre_key: PreKeyRecordStructure,
@@ -23,13 +32,14 @@ impl PreKeyRecord {
                 id,
                 public_key,
                 private_key,
+                schema_version: proto::PROTO_SCHEMA_VERSION,
             },
         }
     }
 
     pub fn deserialize(data: &[u8]) -> Result<Self> {
         Ok(Self {
-            pre_key: PreKeyRecordStructure::decode(data)?,
+            pre_key: proto::decode_with_limit(data, consts::MAX_SMALL_PROTO_BYTES)?,
         })
     }
 
@@ -51,7 +61,127 @@ impl PreKeyRecord {
 
     pub fn serialize(&self) -> Result<Vec<u8>> {
         let mut buf = vec![];
-        self.pre_key.encode(&mut buf)?;
+        // Records loaded from older serializations are written back out as the current version.
+        PreKeyRecordStructure {
+            schema_version: proto::PROTO_SCHEMA_VERSION,
+            ..self.pre_key.clone()
+        }
+        .encode(&mut buf)?;
         Ok(buf)
     }
 }
+
+/// The IDs assigned by [`generate_pre_keys`]: `count` consecutive IDs starting at `start_id`,
+/// wrapping from `u32::MAX` back to 0.
+fn pre_key_ids(start_id: PreKeyId, count: u32) -> impl Iterator<Item = PreKeyId> {
+    (0..count).map(move |i| start_id.wrapping_add(i))
+}
+
+/// Generates `count` one-time pre-keys with fresh key pairs.
+///
+/// The records have consecutive IDs starting at `start_id`. IDs wrap from `u32::MAX` back to 0,
+/// so callers can keep passing the ID after the last one they uploaded.
+///
+/// Returns [`SignalProtocolError::InvalidArgument`] if `count` is more than
+/// [`MAX_PRE_KEY_BATCH_SIZE`].
+pub fn generate_pre_keys<R: Rng + CryptoRng>(
+    start_id: PreKeyId,
+    count: u32,
+    csprng: &mut R,
+) -> Result<Vec<PreKeyRecord>> {
+    if count > MAX_PRE_KEY_BATCH_SIZE {
+        return Err(SignalProtocolError::InvalidArgument(format!(
+            "cannot generate {} pre-keys at once (the maximum is {})",
+            count, MAX_PRE_KEY_BATCH_SIZE
+        )));
+    }
+    Ok(pre_key_ids(start_id, count)
+        .map(|id| PreKeyRecord::new(id, &KeyPair::generate(csprng)))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use rand::rngs::OsRng;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_generate_pre_keys_wraps() -> Result<()> {
+        let records = generate_pre_keys(u32::MAX - 1, 4, &mut OsRng)?;
+        let ids = records
+            .iter()
+            .map(PreKeyRecord::id)
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(ids, vec![u32::MAX - 1, u32::MAX, 0, 1]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_generate_pre_keys_batch_limit() -> Result<()> {
+        let records = generate_pre_keys(u32::MAX - 99, MAX_PRE_KEY_BATCH_SIZE, &mut OsRng)?;
+        assert_eq!(records.len(), 65535);
+        assert_eq!(
+            records.first().map(PreKeyRecord::id).transpose()?,
+            Some(u32::MAX - 99)
+        );
+        assert_eq!(
+            records.last().map(PreKeyRecord::id).transpose()?,
+            Some(65434)
+        );
+
+        for &count in &[MAX_PRE_KEY_BATCH_SIZE + 1, u32::MAX] {
+            assert!(matches!(
+                generate_pre_keys(1, count, &mut OsRng),
+                Err(SignalProtocolError::InvalidArgument(_))
+            ));
+        }
+        Ok(())
+    }
+
+    proptest! {
+        #[test]
+        fn pre_key_ids_are_unique(start_id: u32, count in 0u32..=65535) {
+            let ids = pre_key_ids(start_id, count).collect::<Vec<_>>();
+            prop_assert_eq!(ids.len(), count as usize);
+            prop_assert_eq!(ids.iter().collect::<HashSet<_>>().len(), ids.len());
+            if let Some(&first) = ids.first() {
+                prop_assert_eq!(first, start_id);
+            }
+            for pair in ids.windows(2) {
+                prop_assert_eq!(pair[1], pair[0].wrapping_add(1));
+            }
+        }
+
+        // Key generation is too slow to run with tens of thousands of keys per case; the ID
+        // assignment itself is covered above.
+        #[test]
+        fn generated_pre_keys_round_trip(start_id: u32, count in 0u32..16) {
+            let records = generate_pre_keys(start_id, count, &mut OsRng).unwrap();
+            prop_assert_eq!(records.len(), count as usize);
+
+            let ids = records.iter().map(|record| record.id().unwrap());
+            prop_assert_eq!(ids.collect::<HashSet<_>>().len(), records.len());
+            let key_pairs = records.iter().map(|record| {
+                (
+                    record.public_key().unwrap().serialize(),
+                    record.private_key().unwrap().serialize(),
+                )
+            });
+            prop_assert_eq!(key_pairs.collect::<HashSet<_>>().len(), records.len());
+
+            for (record, expected_id) in records.iter().zip(pre_key_ids(start_id, count)) {
+                prop_assert_eq!(record.id().unwrap(), expected_id);
+                let serialized = record.serialize().unwrap();
+                let deserialized = PreKeyRecord::deserialize(&serialized).unwrap();
+                prop_assert_eq!(deserialized.id().unwrap(), expected_id);
+                prop_assert_eq!(
+                    deserialized.public_key().unwrap(),
+                    record.public_key().unwrap()
+                );
+                prop_assert_eq!(deserialized.serialize().unwrap(), serialized);
+            }
+        }
+    }
+}
@@ -7,12 +7,30 @@ use crate::ratchet::{ChainKey, MessageKeys, RootKey};
 use crate::{IdentityKey, KeyPair, PrivateKey, PublicKey, Result, SignalProtocolError, HKDF};
 
 use crate::consts;
+use crate::proto;
 use crate::proto::storage::session_structure;
 use crate::proto::storage::{RecordStructure, SessionStructure};
 use crate::state::{PreKeyId, SignedPreKeyId};
 use prost::Message;
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
 
 use std::collections::VecDeque;
+use std::fmt;
+use std::fmt::Write;
+
+/// The version of the format produced by [`SessionRecord::describe`].
+///
+/// Bump this whenever a field is added, removed, or changes meaning.
+const DESCRIBE_FORMAT_VERSION: u32 = 1;
+
+/// The version byte that [`SessionRecord::serialize`] puts in front of the record's protobuf
+/// encoding.
+///
+/// Bump this (and add a decoder in [`SessionRecord::deserialize`]) whenever the stored format
+/// changes in a way older code can't read. Only versions below
+/// [`proto::UNVERSIONED_RECORD_MIN_BYTE`] are available.
+const SESSION_RECORD_VERSION: u8 = 1;
 
 #[derive(Debug, Clone)]
 pub(crate) struct UnacknowledgedPreKeyMessageItems {
@@ -47,6 +65,130 @@ impl UnacknowledgedPreKeyMessageItems {
     }
 }
 
+/// Describes which parts of a session state differ between two snapshots.
+///
+/// Returned by [`SessionRecord::diff_current_session`]. Secrets are compared, but never included
+/// in the diff itself, so it is safe to log.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SessionStateDiff {
+    root_key: bool,
+    sender_chain: bool,
+    receiver_chains: bool,
+    other: bool,
+    archived_sessions: bool,
+}
+
+impl SessionStateDiff {
+    /// Whether the root key changed, as it does on each DH ratchet step.
+    pub fn root_key_changed(&self) -> bool {
+        self.root_key
+    }
+
+    /// Whether the sending chain (its ratchet key, chain key, or index) changed, as it does for
+    /// each encrypted message.
+    pub fn sender_chain_changed(&self) -> bool {
+        self.sender_chain
+    }
+
+    /// Whether any receiver chain was added, removed, or advanced, including the message keys
+    /// saved for out-of-order messages.
+    pub fn receiver_chains_changed(&self) -> bool {
+        self.receiver_chains
+    }
+
+    /// Whether anything else changed, such as identities, registration IDs, or unacknowledged
+    /// pre-key state.
+    pub fn other_fields_changed(&self) -> bool {
+        self.other
+    }
+
+    /// Whether any archived session state was added, removed, or changed.
+    pub fn archived_sessions_changed(&self) -> bool {
+        self.archived_sessions
+    }
+
+    pub fn has_changes(&self) -> bool {
+        self.root_key
+            || self.sender_chain
+            || self.receiver_chains
+            || self.other
+            || self.archived_sessions
+    }
+
+    fn all_changed() -> Self {
+        Self {
+            root_key: true,
+            sender_chain: true,
+            receiver_chains: true,
+            other: true,
+            archived_sessions: false,
+        }
+    }
+}
+
+impl fmt::Display for SessionStateDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let changed: Vec<&str> = [
+            (self.root_key, "root key"),
+            (self.sender_chain, "sender chain"),
+            (self.receiver_chains, "receiver chains"),
+            (self.other, "other fields"),
+            (self.archived_sessions, "archived sessions"),
+        ]
+        .iter()
+        .filter(|(changed, _)| *changed)
+        .map(|(_, name)| *name)
+        .collect();
+        if changed.is_empty() {
+            write!(f, "no changes")
+        } else {
+            write!(f, "changed {}", changed.join(", "))
+        }
+    }
+}
+
+/// Compares two byte strings in constant time, since they may hold key material.
+fn bytes_differ(ours: &[u8], theirs: &[u8]) -> bool {
+    !bool::from(ours.ct_eq(theirs))
+}
+
+/// Compares two chains for [`SessionState::diff`], checking their keys in constant time.
+fn chains_differ(ours: &session_structure::Chain, theirs: &session_structure::Chain) -> bool {
+    // Destructure so that adding a field to the protobuf forces a decision here.
+    let session_structure::Chain {
+        sender_ratchet_key,
+        sender_ratchet_key_private,
+        chain_key,
+        message_keys,
+    } = ours;
+
+    let chain_key_differs = match (chain_key, &theirs.chain_key) {
+        (Some(ours), Some(theirs)) => {
+            ours.index != theirs.index || bytes_differ(&ours.key, &theirs.key)
+        }
+        (None, None) => false,
+        (Some(_), None) | (None, Some(_)) => true,
+    };
+    let message_keys_differ = message_keys.len() != theirs.message_keys.len()
+        || message_keys
+            .iter()
+            .zip(&theirs.message_keys)
+            .any(|(ours, theirs)| {
+                ours.index != theirs.index
+                    || bytes_differ(&ours.cipher_key, &theirs.cipher_key)
+                    || bytes_differ(&ours.mac_key, &theirs.mac_key)
+                    || bytes_differ(&ours.iv, &theirs.iv)
+            });
+
+    bytes_differ(sender_ratchet_key, &theirs.sender_ratchet_key)
+        || bytes_differ(
+            sender_ratchet_key_private,
+            &theirs.sender_ratchet_key_private,
+        )
+        || chain_key_differs
+        || message_keys_differ
+}
+
 #[derive(Clone, Debug)]
 pub(crate) struct SessionState {
     session: SessionStructure,
@@ -428,6 +570,158 @@ impl SessionState {
     pub(crate) fn local_registration_id(&self) -> Result<u32> {
         Ok(self.session.local_registration_id)
     }
+
+    pub(crate) fn max_skipped_message_keys(&self) -> usize {
+        match self.session.max_skipped_message_keys {
+            0 => consts::MAX_FORWARD_JUMPS,
+            limit => limit as usize,
+        }
+    }
+
+    pub(crate) fn set_max_skipped_message_keys(&mut self, limit: u32) {
+        self.session.max_skipped_message_keys = limit;
+    }
+
+    /// Compares this state with `other`, field by field.
+    pub(crate) fn diff(&self, other: &SessionState) -> SessionStateDiff {
+        // Destructure so that adding a field to the protobuf forces a decision here.
+        let SessionStructure {
+            session_version,
+            local_identity_public,
+            remote_identity_public,
+            root_key,
+            previous_counter,
+            sender_chain,
+            receiver_chains,
+            pending_pre_key,
+            remote_registration_id,
+            local_registration_id,
+            needs_refresh,
+            alice_base_key,
+            max_skipped_message_keys,
+        } = &self.session;
+        let theirs = &other.session;
+
+        SessionStateDiff {
+            root_key: bytes_differ(root_key, &theirs.root_key),
+            sender_chain: match (sender_chain, &theirs.sender_chain) {
+                (Some(ours), Some(theirs)) => chains_differ(ours, theirs),
+                (None, None) => false,
+                (Some(_), None) | (None, Some(_)) => true,
+            },
+            receiver_chains: receiver_chains.len() != theirs.receiver_chains.len()
+                || receiver_chains
+                    .iter()
+                    .zip(&theirs.receiver_chains)
+                    .any(|(ours, theirs)| chains_differ(ours, theirs)),
+            other: *session_version != theirs.session_version
+                || *local_identity_public != theirs.local_identity_public
+                || *remote_identity_public != theirs.remote_identity_public
+                || *previous_counter != theirs.previous_counter
+                || *pending_pre_key != theirs.pending_pre_key
+                || *remote_registration_id != theirs.remote_registration_id
+                || *local_registration_id != theirs.local_registration_id
+                || *needs_refresh != theirs.needs_refresh
+                || *alice_base_key != theirs.alice_base_key
+                || *max_skipped_message_keys != theirs.max_skipped_message_keys,
+            // Filled in by SessionRecord::diff_current_session.
+            archived_sessions: false,
+        }
+    }
+
+    /// Writes this state as a JSON object for [`SessionRecord::describe`].
+    ///
+    /// This reads the protobuf fields directly, so that it works even for states that fail to
+    /// deserialize. Only public keys, indices, and IDs are included; the root key appears as a
+    /// fingerprint (a truncated SHA-256 hash), and chain keys, message keys, and private keys are
+    /// left out entirely.
+    fn describe_into(&self, out: &mut String) {
+        fn hex_or_null(bytes: &[u8]) -> String {
+            if bytes.is_empty() {
+                "null".to_string()
+            } else {
+                format!("\"{}\"", hex::encode(bytes))
+            }
+        }
+
+        fn describe_chain(chain: &session_structure::Chain, out: &mut String) {
+            let chain_index = match &chain.chain_key {
+                Some(chain_key) => chain_key.index.to_string(),
+                None => "null".to_string(),
+            };
+            let message_key_indices: Vec<String> = chain
+                .message_keys
+                .iter()
+                .map(|key| key.index.to_string())
+                .collect();
+            write!(
+                out,
+                "{{\"ratchet_key\":{},\"chain_index\":{},\"message_key_indices\":[{}]}}",
+                hex_or_null(&chain.sender_ratchet_key),
+                chain_index,
+                message_key_indices.join(",")
+            )
+            .expect("can write to a String");
+        }
+
+        let session = &self.session;
+        let root_key_fingerprint = if session.root_key.is_empty() {
+            "null".to_string()
+        } else {
+            format!(
+                "\"{}\"",
+                hex::encode(&Sha256::digest(&session.root_key)[..8])
+            )
+        };
+
+        write!(
+            out,
+            "{{\"session_version\":{},\"local_identity_key\":{},\"remote_identity_key\":{},\
+             \"local_registration_id\":{},\"remote_registration_id\":{},\
+             \"alice_base_key\":{},\"root_key_fingerprint\":{},\"previous_counter\":{},\
+             \"needs_refresh\":{},\"sender_chain\":",
+            session.session_version,
+            hex_or_null(&session.local_identity_public),
+            hex_or_null(&session.remote_identity_public),
+            session.local_registration_id,
+            session.remote_registration_id,
+            hex_or_null(&session.alice_base_key),
+            root_key_fingerprint,
+            session.previous_counter,
+            session.needs_refresh,
+        )
+        .expect("can write to a String");
+
+        match &session.sender_chain {
+            Some(chain) => describe_chain(chain, out),
+            None => out.push_str("null"),
+        }
+
+        out.push_str(",\"receiver_chains\":[");
+        for (i, chain) in session.receiver_chains.iter().enumerate() {
+            if i != 0 {
+                out.push(',');
+            }
+            describe_chain(chain, out);
+        }
+        out.push_str("],\"unacknowledged_pre_key\":");
+
+        match &session.pending_pre_key {
+            Some(pending) => write!(
+                out,
+                "{{\"pre_key_id\":{},\"signed_pre_key_id\":{},\"base_key\":{}}}",
+                match pending.pre_key_id {
+                    0 => "null".to_string(),
+                    id => id.to_string(),
+                },
+                pending.signed_pre_key_id,
+                hex_or_null(&pending.base_key)
+            )
+            .expect("can write to a String"),
+            None => out.push_str("null"),
+        }
+        out.push('}');
+    }
 }
 
 impl From<SessionStructure> for SessionState {
@@ -469,8 +763,26 @@ impl SessionRecord {
         }
     }
 
+    /// Reads a record produced by [`serialize`](Self::serialize), or by a version of this crate
+    /// from before records were versioned.
+    ///
+    /// Fails with [`UnknownSessionVersion`](SignalProtocolError::UnknownSessionVersion) if the
+    /// record was written in a newer format than this crate understands.
     pub fn deserialize(bytes: &[u8]) -> Result<Self> {
-        let record = RecordStructure::decode(bytes)?;
+        match bytes.first() {
+            Some(&SESSION_RECORD_VERSION) => Self::deserialize_v1(&bytes[1..]),
+            Some(&version) if version < proto::UNVERSIONED_RECORD_MIN_BYTE => {
+                Err(SignalProtocolError::UnknownSessionVersion(version))
+            }
+            // Unversioned records (including the empty encoding of a fresh record) are in the
+            // version 1 format.
+            _ => Self::deserialize_v1(bytes),
+        }
+    }
+
+    /// Decodes a version 1 record, which is a bare `RecordStructure` protobuf.
+    fn deserialize_v1(bytes: &[u8]) -> Result<Self> {
+        let record = proto::decode_with_limit::<RecordStructure>(bytes, consts::MAX_PROTO_BYTES)?;
 
         let mut previous = VecDeque::with_capacity(record.previous_sessions.len());
         for s in record.previous_sessions {
@@ -484,7 +796,7 @@ impl SessionRecord {
     }
 
     pub fn from_single_session_state(bytes: &[u8]) -> Result<Self> {
-        let session = SessionState::new(SessionStructure::decode(bytes)?);
+        let session = SessionState::new(proto::decode_with_limit(bytes, consts::MAX_PROTO_BYTES)?);
         Ok(Self {
             current_session: Some(session),
             previous_sessions: VecDeque::new(),
@@ -557,7 +869,14 @@ impl SessionRecord {
         self.promote_state(updated_session)
     }
 
-    pub(crate) fn promote_state(&mut self, new_state: SessionState) -> Result<()> {
+    pub(crate) fn promote_state(&mut self, mut new_state: SessionState) -> Result<()> {
+        // Keep any configured limit when a new session replaces the current one.
+        if new_state.session.max_skipped_message_keys == 0 {
+            if let Some(current) = &self.current_session {
+                new_state.session.max_skipped_message_keys =
+                    current.session.max_skipped_message_keys;
+            }
+        }
         self.archive_current_state()?;
         self.current_session = Some(new_state);
         Ok(())
@@ -577,17 +896,87 @@ impl SessionRecord {
         Ok(())
     }
 
+    /// Serializes the record as a version byte (see [`deserialize`](Self::deserialize)) followed
+    /// by its protobuf encoding.
     pub fn serialize(&self) -> Result<Vec<u8>> {
-        let mut buf = vec![];
+        let mut buf = vec![SESSION_RECORD_VERSION];
 
         let record = RecordStructure {
             current_session: self.current_session.as_ref().map(|s| s.into()),
             previous_sessions: self.previous_sessions.iter().map(|s| s.into()).collect(),
+            schema_version: proto::PROTO_SCHEMA_VERSION,
         };
         record.encode(&mut buf)?;
         Ok(buf)
     }
 
+    /// Returns a human-readable JSON description of the current and archived session states, for
+    /// debugging.
+    ///
+    /// This includes public keys, chain and message key indices, registration IDs, and any
+    /// unacknowledged pre-key state, but no secret key material: the root key is represented
+    /// only by a fingerprint. The output has a `format_version` field, but is **not** meant to be
+    /// parsed back into a record; use [`serialize`](Self::serialize) for storage.
+    pub fn describe(&self) -> String {
+        let mut out = format!(
+            "{{\"format_version\":{},\"current_session\":",
+            DESCRIBE_FORMAT_VERSION
+        );
+        match &self.current_session {
+            Some(session) => session.describe_into(&mut out),
+            None => out.push_str("null"),
+        }
+        out.push_str(",\"previous_sessions\":[");
+        for (i, session) in self.previous_sessions.iter().enumerate() {
+            if i != 0 {
+                out.push(',');
+            }
+            session.describe_into(&mut out);
+        }
+        out.push_str("]}");
+        out
+    }
+
+    /// Compares the current session state of this record with that of `other`, and checks
+    /// whether their archived session states match.
+    ///
+    /// Store implementations can use this to skip re-serializing a record that hasn't changed.
+    /// If only one of the records has a current session, everything about it is reported as
+    /// changed.
+    pub fn diff_current_session(&self, other: &SessionRecord) -> SessionStateDiff {
+        let mut diff = match (&self.current_session, &other.current_session) {
+            (Some(ours), Some(theirs)) => ours.diff(theirs),
+            (None, None) => SessionStateDiff::default(),
+            (Some(_), None) | (None, Some(_)) => SessionStateDiff::all_changed(),
+        };
+        diff.archived_sessions = self.previous_sessions.len() != other.previous_sessions.len()
+            || self
+                .previous_sessions
+                .iter()
+                .zip(&other.previous_sessions)
+                .any(|(ours, theirs)| ours.diff(theirs).has_changes());
+        diff
+    }
+
+    /// Limits how many message keys decrypting a single message may skip over (and store for
+    /// later) in the current session.
+    ///
+    /// Decrypting a message further ahead than this fails with
+    /// [`SignalProtocolError::TooManySkippedKeys`]. Passing zero restores the default,
+    /// [`MAX_FORWARD_JUMPS`](consts::MAX_FORWARD_JUMPS). The limit is saved with the record, and
+    /// carried over when a new session (say, from [`process_prekey_bundle`]) replaces this one.
+    ///
+    /// [`process_prekey_bundle`]: crate::process_prekey_bundle
+    pub fn set_max_skipped_message_keys(&mut self, limit: u32) -> Result<()> {
+        self.session_state_mut()?
+            .set_max_skipped_message_keys(limit);
+        Ok(())
+    }
+
+    pub fn max_skipped_message_keys(&self) -> Result<usize> {
+        Ok(self.session_state()?.max_skipped_message_keys())
+    }
+
     pub fn remote_registration_id(&self) -> Result<u32> {
         self.session_state()?.remote_registration_id()
     }
@@ -627,3 +1016,153 @@ impl SessionRecord {
         self.session_state()?.get_sender_chain_key_bytes()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::OsRng;
+
+    use super::*;
+
+    fn session_with_secrets(private_keys: &[&PrivateKey]) -> SessionState {
+        let chain = |public: &PublicKey, private: Option<&PrivateKey>, index: u32| {
+            session_structure::Chain {
+                sender_ratchet_key: public.serialize().to_vec(),
+                sender_ratchet_key_private: private
+                    .map(|key| key.serialize().to_vec())
+                    .unwrap_or_default(),
+                chain_key: Some(session_structure::chain::ChainKey {
+                    index,
+                    key: vec![0xC4; 32],
+                }),
+                message_keys: vec![session_structure::chain::MessageKey {
+                    index: index - 1,
+                    cipher_key: vec![0xC1; 32],
+                    mac_key: vec![0xC2; 32],
+                    iv: vec![0xC3; 16],
+                }],
+            }
+        };
+
+        SessionState::new(SessionStructure {
+            session_version: 3,
+            local_identity_public: private_keys[0]
+                .public_key()
+                .expect("valid")
+                .serialize()
+                .to_vec(),
+            remote_identity_public: vec![],
+            root_key: vec![0xC0; 32],
+            previous_counter: 7,
+            sender_chain: Some(chain(
+                &private_keys[1].public_key().expect("valid"),
+                Some(private_keys[1]),
+                5,
+            )),
+            receiver_chains: vec![chain(
+                &private_keys[2].public_key().expect("valid"),
+                None,
+                9,
+            )],
+            pending_pre_key: Some(session_structure::PendingPreKey {
+                pre_key_id: 0,
+                signed_pre_key_id: 22,
+                base_key: private_keys[2]
+                    .public_key()
+                    .expect("valid")
+                    .serialize()
+                    .to_vec(),
+            }),
+            remote_registration_id: 1234,
+            local_registration_id: 5678,
+            needs_refresh: false,
+            alice_base_key: vec![],
+            max_skipped_message_keys: 0,
+        })
+    }
+
+    #[test]
+    fn test_serialize_versions() -> Result<()> {
+        let mut csprng = OsRng;
+        let keys: Vec<KeyPair> = (0..3).map(|_| KeyPair::generate(&mut csprng)).collect();
+        let private_keys: Vec<&PrivateKey> = keys.iter().map(|pair| &pair.private_key).collect();
+        let record = SessionRecord::new(session_with_secrets(&private_keys));
+
+        let current = record.serialize()?;
+        assert_eq!(current[0], SESSION_RECORD_VERSION);
+        assert_eq!(SessionRecord::deserialize(&current)?.serialize()?, current);
+
+        // Records from before the version byte existed.
+        let unversioned = &current[1..];
+        assert_eq!(
+            SessionRecord::deserialize(unversioned)?.serialize()?,
+            current
+        );
+        assert!(!SessionRecord::deserialize(&[])?.has_current_session_state());
+
+        let mut bumped = current.clone();
+        bumped[0] = SESSION_RECORD_VERSION + 1;
+        assert!(matches!(
+            SessionRecord::deserialize(&bumped),
+            Err(SignalProtocolError::UnknownSessionVersion(v)) if v == SESSION_RECORD_VERSION + 1
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_describe() {
+        let mut csprng = OsRng;
+        let keys: Vec<KeyPair> = (0..3).map(|_| KeyPair::generate(&mut csprng)).collect();
+        let private_keys: Vec<&PrivateKey> = keys.iter().map(|pair| &pair.private_key).collect();
+
+        let mut record = SessionRecord::new(session_with_secrets(&private_keys));
+        record.archive_current_state().expect("can archive");
+        record
+            .set_session_state(session_with_secrets(&private_keys))
+            .expect("can set");
+
+        let description = record.describe();
+        assert!(description.starts_with("{\"format_version\":1,\"current_session\":{"));
+        assert!(description.contains("\"previous_sessions\":[{"));
+        assert!(description.contains(&format!(
+            "\"sender_chain\":{{\"ratchet_key\":\"{}\",\"chain_index\":5,\"message_key_indices\":[4]}}",
+            hex::encode(keys[1].public_key.serialize())
+        )));
+        assert!(description
+            .contains("\"unacknowledged_pre_key\":{\"pre_key_id\":null,\"signed_pre_key_id\":22,"));
+        assert!(description.contains("\"remote_identity_key\":null"));
+
+        let fresh = SessionRecord::new_fresh().describe();
+        assert_eq!(
+            fresh,
+            "{\"format_version\":1,\"current_session\":null,\"previous_sessions\":[]}"
+        );
+    }
+
+    #[test]
+    fn test_describe_has_no_secrets() {
+        let mut csprng = OsRng;
+        let keys: Vec<KeyPair> = (0..3).map(|_| KeyPair::generate(&mut csprng)).collect();
+        let private_keys: Vec<&PrivateKey> = keys.iter().map(|pair| &pair.private_key).collect();
+        let record = SessionRecord::new(session_with_secrets(&private_keys));
+        let description = record.describe();
+
+        let mut secrets: Vec<Vec<u8>> = private_keys
+            .iter()
+            .map(|key| key.serialize().to_vec())
+            .collect();
+        // Root key, message keys, and chain keys.
+        secrets.extend((0xC0..=0xC4).map(|b| vec![b; 16]));
+
+        for secret in secrets {
+            // Any 8 consecutive bytes of a secret is too much.
+            for window in secret.windows(8) {
+                assert!(
+                    !description.contains(&hex::encode(window)),
+                    "found secret bytes {} in {}",
+                    hex::encode(window),
+                    description
+                );
+            }
+        }
+    }
+}
@@ -3,6 +3,8 @@
 // SPDX-License-Identifier: AGPL-3.0-only
 //
 
+use crate::consts;
+use crate::proto;
 use crate::proto::storage::SignedPreKeyRecordStructure;
 use crate::{KeyPair, PrivateKey, PublicKey, Result};
 use prost::Message;
@@ -26,13 +28,14 @@ impl SignedPreKeyRecord {
                 public_key,
                 private_key,
                 signature,
+                schema_version: proto::PROTO_SCHEMA_VERSION,
             },
         }
     }
 
     pub fn deserialize(data: &[u8]) -> Result<Self> {
         Ok(Self {
-            signed_pre_key: SignedPreKeyRecordStructure::decode(data)?,
+            signed_pre_key: proto::decode_with_limit(data, consts::MAX_SMALL_PROTO_BYTES)?,
         })
     }
 
@@ -65,7 +68,12 @@ impl SignedPreKeyRecord {
 
     pub fn serialize(&self) -> Result<Vec<u8>> {
         let mut buf = vec![];
-        self.signed_pre_key.encode(&mut buf)?;
+        // Records loaded from older serializations are written back out as the current version.
+        SignedPreKeyRecordStructure {
+            schema_version: proto::PROTO_SCHEMA_VERSION,
+            ..self.signed_pre_key.clone()
+        }
+        .encode(&mut buf)?;
         Ok(buf)
     }
 }
@@ -4,6 +4,7 @@
 //
 
 mod inmem;
+mod session_cache;
 mod traits;
 
 pub use {
@@ -11,8 +12,9 @@ pub use {
         InMemIdentityKeyStore, InMemPreKeyStore, InMemSenderKeyStore, InMemSessionStore,
         InMemSignalProtocolStore, InMemSignedPreKeyStore,
     },
+    session_cache::{EvictionPolicy, LruSessionCache, LruSessionCacheBuilder},
     traits::{
-        Context, Direction, IdentityKeyStore, PreKeyStore, ProtocolStore, SenderKeyStore,
-        SessionStore, SignedPreKeyStore,
+        Context, Direction, IdentityKeyStore, IdentityTrust, PreKeyStore, ProtocolStore,
+        SenderKeyStore, SessionStore, SignedPreKeyStore,
     },
 };
@@ -35,7 +35,7 @@ impl InMemIdentityKeyStore {
 #[async_trait(?Send)]
 impl traits::IdentityKeyStore for InMemIdentityKeyStore {
     async fn get_identity_key_pair(&self, _ctx: Context) -> Result<IdentityKeyPair> {
-        Ok(self.key_pair)
+        Ok(self.key_pair.clone())
     }
 
     async fn get_local_registration_id(&self, _ctx: Context) -> Result<u32> {
@@ -69,12 +69,13 @@ impl traits::IdentityKeyStore for InMemIdentityKeyStore {
         identity: &IdentityKey,
         _direction: traits::Direction,
         _ctx: Context,
-    ) -> Result<bool> {
+    ) -> Result<traits::IdentityTrust> {
         match self.known_keys.get(address) {
             None => {
-                Ok(true) // first use
+                Ok(traits::IdentityTrust::Trusted) // first use
             }
-            Some(k) => Ok(k == identity),
+            Some(k) if k == identity => Ok(traits::IdentityTrust::Trusted),
+            Some(k) => Ok(traits::IdentityTrust::Changed { old_key: *k }),
         }
     }
 
@@ -313,7 +314,7 @@ impl traits::IdentityKeyStore for InMemSignalProtocolStore {
         identity: &IdentityKey,
         direction: traits::Direction,
         ctx: Context,
-    ) -> Result<bool> {
+    ) -> Result<traits::IdentityTrust> {
         self.identity_store
             .is_trusted_identity(address, identity, direction, ctx)
             .await
@@ -0,0 +1,322 @@
+//
+// Copyright 2021 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+use crate::{ProtocolAddress, Result, SessionRecord, SignalProtocolError};
+
+use crate::storage::traits;
+use crate::storage::Context;
+
+use async_trait::async_trait;
+use lru::LruCache;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// How an [`LruSessionCache`] chooses which session to drop when it is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EvictionPolicy {
+    /// Drop the session that was least recently loaded or stored.
+    LeastRecentlyUsed,
+    /// Among the `candidates` least recently used sessions, drop the one that has sent or received
+    /// the fewest messages (that is, been stored the fewest times) while cached.
+    ///
+    /// This keeps a busy conversation cached through a burst of one-off sessions.
+    LeastActive { candidates: usize },
+}
+
+impl Default for EvictionPolicy {
+    fn default() -> Self {
+        EvictionPolicy::LeastActive { candidates: 8 }
+    }
+}
+
+struct CachedSession {
+    record: SessionRecord,
+    stores: u64,
+}
+
+/// A [`SessionStore`](traits::SessionStore) that keeps at most a fixed number of sessions in
+/// memory, for servers that handle more sessions than they can hold at once.
+///
+/// Clones share the same sessions, so a cache can be handed to many tasks or threads at once.
+/// Sessions dropped from the cache are gone; load them from persistent storage again if needed.
+#[derive(Clone)]
+pub struct LruSessionCache {
+    sessions: Arc<Mutex<LruCache<ProtocolAddress, CachedSession>>>,
+    policy: EvictionPolicy,
+}
+
+impl LruSessionCache {
+    /// Creates a cache holding up to `capacity` sessions, with the default eviction policy.
+    pub fn new(capacity: usize) -> Result<Self> {
+        LruSessionCacheBuilder::new().capacity(capacity).build()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.lock().cap()
+    }
+
+    pub fn eviction_policy(&self) -> EvictionPolicy {
+        self.policy
+    }
+
+    /// The number of sessions currently cached.
+    pub fn session_count(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Returns a copy of the session for `address`, if it is cached.
+    pub fn get(&self, address: &ProtocolAddress) -> Option<SessionRecord> {
+        self.lock()
+            .get(address)
+            .map(|session| session.record.clone())
+    }
+
+    /// Caches `record` as the session for `address`, dropping another session if the cache is full.
+    pub fn insert(&self, address: &ProtocolAddress, record: &SessionRecord) {
+        let mut sessions = self.lock();
+        if let Some(session) = sessions.get_mut(address) {
+            session.record = record.clone();
+            session.stores += 1;
+            return;
+        }
+
+        if sessions.len() == sessions.cap() {
+            if let EvictionPolicy::LeastActive { candidates } = self.policy {
+                // LruCache iterates from most to least recently used.
+                let victim = sessions
+                    .iter()
+                    .rev()
+                    .take(candidates)
+                    .min_by_key(|(_, session)| session.stores)
+                    .map(|(address, _)| address.clone());
+                if let Some(victim) = victim {
+                    sessions.pop(&victim);
+                }
+            }
+        }
+        // With room to spare, this never evicts anything; otherwise it drops the least recently
+        // used session.
+        sessions.put(
+            address.clone(),
+            CachedSession {
+                record: record.clone(),
+                stores: 1,
+            },
+        );
+    }
+
+    /// Drops the session for `address`, returning it if it was cached.
+    pub fn remove(&self, address: &ProtocolAddress) -> Option<SessionRecord> {
+        self.lock().pop(address).map(|session| session.record)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, LruCache<ProtocolAddress, CachedSession>> {
+        // Nothing panics while the lock is held, so the cache is never poisoned.
+        self.sessions.lock().expect("not poisoned")
+    }
+}
+
+#[async_trait(?Send)]
+impl traits::SessionStore for LruSessionCache {
+    async fn load_session(
+        &self,
+        address: &ProtocolAddress,
+        _ctx: Context,
+    ) -> Result<Option<SessionRecord>> {
+        Ok(self.get(address))
+    }
+
+    async fn store_session(
+        &mut self,
+        address: &ProtocolAddress,
+        record: &SessionRecord,
+        _ctx: Context,
+    ) -> Result<()> {
+        self.insert(address, record);
+        Ok(())
+    }
+}
+
+/// Configures an [`LruSessionCache`].
+#[derive(Debug, Default)]
+pub struct LruSessionCacheBuilder {
+    capacity: Option<usize>,
+    policy: EvictionPolicy,
+}
+
+impl LruSessionCacheBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = Some(capacity);
+        self
+    }
+
+    pub fn eviction_policy(mut self, policy: EvictionPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Produces the cache, failing with
+    /// [`InvalidArgument`](SignalProtocolError::InvalidArgument) if the capacity is missing or
+    /// zero, or if a [`LeastActive`](EvictionPolicy::LeastActive) policy has no candidates.
+    pub fn build(self) -> Result<LruSessionCache> {
+        let capacity = match self.capacity {
+            None => {
+                return Err(SignalProtocolError::InvalidArgument(
+                    "LruSessionCache is missing a capacity".to_string(),
+                ))
+            }
+            Some(0) => {
+                return Err(SignalProtocolError::InvalidArgument(
+                    "LruSessionCache must have a nonzero capacity".to_string(),
+                ))
+            }
+            Some(capacity) => capacity,
+        };
+        if self.policy == (EvictionPolicy::LeastActive { candidates: 0 }) {
+            return Err(SignalProtocolError::InvalidArgument(
+                "LruSessionCache eviction needs at least one candidate".to_string(),
+            ));
+        }
+
+        Ok(LruSessionCache {
+            sessions: Arc::new(Mutex::new(LruCache::new(capacity))),
+            policy: self.policy,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::SessionStore;
+    use futures::executor::block_on;
+
+    fn address(i: u32) -> ProtocolAddress {
+        ProtocolAddress::new(format!("+1415555{:04}", i), 1)
+    }
+
+    #[test]
+    fn test_builder() {
+        assert!(LruSessionCacheBuilder::new().build().is_err());
+        assert!(LruSessionCache::new(0).is_err());
+        assert!(LruSessionCacheBuilder::new()
+            .capacity(5)
+            .eviction_policy(EvictionPolicy::LeastActive { candidates: 0 })
+            .build()
+            .is_err());
+
+        let cache = LruSessionCacheBuilder::new()
+            .capacity(5)
+            .eviction_policy(EvictionPolicy::LeastRecentlyUsed)
+            .build()
+            .expect("valid");
+        assert_eq!(cache.capacity(), 5);
+        assert_eq!(cache.eviction_policy(), EvictionPolicy::LeastRecentlyUsed);
+        assert_eq!(cache.session_count(), 0);
+    }
+
+    #[test]
+    fn test_least_recently_used() -> Result<()> {
+        let mut cache = LruSessionCacheBuilder::new()
+            .capacity(2)
+            .eviction_policy(EvictionPolicy::LeastRecentlyUsed)
+            .build()?;
+        let record = SessionRecord::new_fresh();
+
+        block_on(async {
+            cache.store_session(&address(0), &record, None).await?;
+            cache.store_session(&address(1), &record, None).await?;
+            // Loading a session counts as using it.
+            assert!(cache.load_session(&address(0), None).await?.is_some());
+            cache.store_session(&address(2), &record, None).await?;
+
+            assert_eq!(cache.session_count(), 2);
+            assert!(cache.load_session(&address(0), None).await?.is_some());
+            assert!(cache.load_session(&address(1), None).await?.is_none());
+            assert!(cache.load_session(&address(2), None).await?.is_some());
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_least_active() -> Result<()> {
+        // Stores each address the given number of times, from least to most recently used, then
+        // adds one more session and returns which of the originals are still cached.
+        fn remaining_after_eviction(stores: &[u32]) -> Result<Vec<bool>> {
+            let mut cache = LruSessionCacheBuilder::new()
+                .capacity(stores.len())
+                .eviction_policy(EvictionPolicy::LeastActive { candidates: 2 })
+                .build()?;
+            let record = SessionRecord::new_fresh();
+
+            block_on(async {
+                for (i, count) in stores.iter().enumerate() {
+                    for _ in 0..*count {
+                        cache
+                            .store_session(&address(i as u32), &record, None)
+                            .await?;
+                    }
+                }
+                cache.store_session(&address(100), &record, None).await?;
+                assert_eq!(cache.session_count(), stores.len());
+                Ok((0..stores.len())
+                    .map(|i| cache.get(&address(i as u32)).is_some())
+                    .collect())
+            })
+        }
+
+        // The least recently used session has been more active than the next one.
+        assert_eq!(remaining_after_eviction(&[5, 1, 1])?, [true, false, true]);
+        // Only the least recently used sessions are candidates, however inactive the rest are.
+        assert_eq!(remaining_after_eviction(&[5, 3, 1])?, [true, false, true]);
+        // Ties go to the least recently used.
+        assert_eq!(remaining_after_eviction(&[2, 2, 1])?, [false, true, true]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_remove() {
+        let cache = LruSessionCache::new(2).expect("valid");
+        cache.insert(&address(0), &SessionRecord::new_fresh());
+        assert!(cache.remove(&address(0)).is_some());
+        assert!(cache.remove(&address(0)).is_none());
+        assert_eq!(cache.session_count(), 0);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_access() {
+        const TASKS: u32 = 16;
+        const SESSIONS_PER_TASK: u32 = 500;
+        const CAPACITY: usize = 400;
+
+        let cache = LruSessionCache::new(CAPACITY).expect("valid");
+        let tasks: Vec<_> = (0..TASKS)
+            .map(|task| {
+                let cache = cache.clone();
+                // SessionStore's futures aren't Send, so the tasks use the cache directly.
+                tokio::spawn(async move {
+                    let record = SessionRecord::new_fresh();
+                    for i in 0..SESSIONS_PER_TASK {
+                        // Each task has its own addresses, but they all share one session too.
+                        let own = address(task * SESSIONS_PER_TASK + i);
+                        cache.insert(&own, &record);
+                        cache.insert(&address(9999), &record);
+                        let _ = cache.get(&own);
+                        assert!(cache.session_count() <= CAPACITY);
+                    }
+                })
+            })
+            .collect();
+        for task in tasks {
+            task.await.expect("task succeeded");
+        }
+
+        assert_eq!(cache.session_count(), CAPACITY);
+        assert!(cache.get(&address(9999)).is_some());
+    }
+}
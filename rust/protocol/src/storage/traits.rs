@@ -19,6 +19,34 @@ pub enum Direction {
     Receiving,
 }
 
+/// The answer to [`IdentityKeyStore::is_trusted_identity`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IdentityTrust {
+    /// The key may be used.
+    Trusted,
+    /// The key may not be used, and the store has no other key saved for the address.
+    Untrusted,
+    /// The key may not be used because it differs from `old_key`, the one the store has saved for
+    /// the address.
+    Changed { old_key: IdentityKey },
+}
+
+impl IdentityTrust {
+    /// The answer for a store that decides trust as a yes or no, given the key it has saved for
+    /// the address, if any.
+    pub fn from_saved_key(
+        trusted: bool,
+        identity: &IdentityKey,
+        saved: Option<IdentityKey>,
+    ) -> Self {
+        match saved {
+            _ if trusted => Self::Trusted,
+            Some(old_key) if old_key != *identity => Self::Changed { old_key },
+            _ => Self::Untrusted,
+        }
+    }
+}
+
 #[async_trait(?Send)]
 pub trait IdentityKeyStore {
     async fn get_identity_key_pair(&self, ctx: Context) -> Result<IdentityKeyPair>;
@@ -32,13 +60,20 @@ pub trait IdentityKeyStore {
         ctx: Context,
     ) -> Result<bool>;
 
+    /// Decides whether `identity` may be used for `address`.
+    ///
+    /// If it may not because it differs from the key saved for `address`, return
+    /// [`IdentityTrust::Changed`] with the saved key, and the operation fails with
+    /// [`IdentityKeyChanged`](crate::SignalProtocolError::IdentityKeyChanged) carrying both keys.
+    /// Otherwise, an untrusted key fails with
+    /// [`UntrustedIdentity`](crate::SignalProtocolError::UntrustedIdentity).
     async fn is_trusted_identity(
         &self,
         address: &ProtocolAddress,
         identity: &IdentityKey,
         direction: Direction,
         ctx: Context,
-    ) -> Result<bool>;
+    ) -> Result<IdentityTrust>;
 
     async fn get_identity(
         &self,
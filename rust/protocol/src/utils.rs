@@ -81,6 +81,34 @@ pub(crate) fn constant_time_cmp(x: &[u8], y: &[u8]) -> Ordering {
     }
 }
 
+/// Drops `value` in place and returns the bytes that `field` borrowed from it beforehand, to
+/// check that secrets are zeroed on drop.
+///
+/// `field` must borrow from `value` itself rather than from a separate allocation. Only its offset
+/// is kept; the bytes are read back through the storage's own pointer once the drop is done, since
+/// the borrow `field` returned doesn't survive the drop. The storage outlives the drop, so the
+/// read is well-defined, which Miri can check: `cargo +nightly miri test -p libsignal-protocol
+/// zeroed_on_drop`.
+#[cfg(test)]
+#[allow(unsafe_code)]
+pub(crate) fn bytes_after_drop<T>(value: T, field: impl FnOnce(&T) -> &[u8]) -> Vec<u8> {
+    let mut slot = std::mem::MaybeUninit::new(value);
+    let base = slot.as_mut_ptr();
+    let (offset, len) = {
+        let bytes = field(unsafe { &*base });
+        let offset = (bytes.as_ptr() as usize).wrapping_sub(base as usize);
+        assert!(
+            matches!(offset.checked_add(bytes.len()), Some(end) if end <= std::mem::size_of::<T>()),
+            "field must be stored inline in the value"
+        );
+        (offset, bytes.len())
+    };
+    unsafe {
+        std::ptr::drop_in_place(base);
+        std::slice::from_raw_parts((base as *const u8).add(offset), len).to_vec()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
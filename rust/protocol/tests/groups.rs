@@ -245,6 +245,63 @@ fn group_large_messages() -> Result<(), SignalProtocolError> {
     })
 }
 
+#[test]
+fn group_message_size_limit() -> Result<(), SignalProtocolError> {
+    block_on(async {
+        let mut csprng = OsRng;
+
+        let sender_address = ProtocolAddress::new("+14159999111".to_owned(), 1);
+        let group_sender =
+            SenderKeyName::new("summer camp planning committee".to_owned(), sender_address)?;
+
+        let mut alice_store = test_in_memory_protocol_store();
+        let mut bob_store = test_in_memory_protocol_store();
+
+        let sent_distribution_message = create_sender_key_distribution_message(
+            &group_sender,
+            &mut alice_store,
+            &mut csprng,
+            None,
+        )
+        .await?;
+
+        let recv_distribution_message =
+            SenderKeyDistributionMessage::try_from(sent_distribution_message.serialized())?;
+
+        process_sender_key_distribution_message(
+            &group_sender,
+            &recv_distribution_message,
+            &mut bob_store,
+            None,
+        )
+        .await?;
+
+        const MAX_MESSAGE_PLAINTEXT_BYTES: usize = 1024 * 1024; // same value as in library
+
+        let largest = vec![0x42u8; MAX_MESSAGE_PLAINTEXT_BYTES];
+        let alice_ciphertext =
+            group_encrypt(&mut alice_store, &group_sender, &largest, &mut csprng, None).await?;
+        let bob_plaintext =
+            group_decrypt(&alice_ciphertext, &mut bob_store, &group_sender, None).await?;
+        assert_eq!(bob_plaintext, largest);
+
+        let too_large = vec![0x42u8; MAX_MESSAGE_PLAINTEXT_BYTES + 1];
+        assert!(matches!(
+            group_encrypt(
+                &mut alice_store,
+                &group_sender,
+                &too_large,
+                &mut csprng,
+                None
+            )
+            .await,
+            Err(SignalProtocolError::InvalidArgument(_))
+        ));
+
+        Ok(())
+    })
+}
+
 #[test]
 fn group_basic_ratchet() -> Result<(), SignalProtocolError> {
     block_on(async {
@@ -585,3 +642,233 @@ fn group_message_key_limit() -> Result<(), SignalProtocolError> {
         Ok(())
     })
 }
+
+#[test]
+fn group_merge_disjoint_records() -> Result<(), SignalProtocolError> {
+    block_on(async {
+        let mut csprng = OsRng;
+
+        let sender_address = ProtocolAddress::new("+14159999111".to_owned(), 1);
+        let group_sender =
+            SenderKeyName::new("summer camp planning committee".to_owned(), sender_address)?;
+
+        // Two of Alice's sender keys, each processed by a different copy of Bob's store.
+        let mut old_alice_store = test_in_memory_protocol_store();
+        let mut new_alice_store = test_in_memory_protocol_store();
+        let mut bob_store = test_in_memory_protocol_store();
+        let mut other_bob_store = test_in_memory_protocol_store();
+
+        for (alice_store, bob_store) in vec![
+            (&mut old_alice_store, &mut bob_store),
+            (&mut new_alice_store, &mut other_bob_store),
+        ] {
+            let sent_distribution_message = create_sender_key_distribution_message(
+                &group_sender,
+                alice_store,
+                &mut csprng,
+                None,
+            )
+            .await?;
+            let recv_distribution_message =
+                SenderKeyDistributionMessage::try_from(sent_distribution_message.serialized())
+                    .unwrap();
+            process_sender_key_distribution_message(
+                &group_sender,
+                &recv_distribution_message,
+                bob_store,
+                None,
+            )
+            .await?;
+        }
+
+        let mut record = bob_store
+            .load_sender_key(&group_sender, None)
+            .await?
+            .expect("record");
+        let other_record = other_bob_store
+            .load_sender_key(&group_sender, None)
+            .await?
+            .expect("record");
+        record.merge(&other_record)?;
+        bob_store
+            .store_sender_key(&group_sender, &record, None)
+            .await?;
+
+        for (alice_store, message) in vec![
+            (&mut old_alice_store, "from the old key"),
+            (&mut new_alice_store, "from the new key"),
+        ] {
+            let ciphertext = group_encrypt(
+                alice_store,
+                &group_sender,
+                message.as_bytes(),
+                &mut csprng,
+                None,
+            )
+            .await?;
+            assert_eq!(
+                String::from_utf8(
+                    group_decrypt(&ciphertext, &mut bob_store, &group_sender, None).await?
+                )
+                .unwrap(),
+                message
+            );
+        }
+
+        Ok(())
+    })
+}
+
+#[test]
+fn group_merge_overlapping_records() -> Result<(), SignalProtocolError> {
+    block_on(async {
+        let mut csprng = OsRng;
+
+        let sender_address = ProtocolAddress::new("+14159999111".to_owned(), 1);
+        let group_sender =
+            SenderKeyName::new("summer camp planning committee".to_owned(), sender_address)?;
+
+        let mut alice_store = test_in_memory_protocol_store();
+        let mut bob_store = test_in_memory_protocol_store();
+
+        let sent_distribution_message = create_sender_key_distribution_message(
+            &group_sender,
+            &mut alice_store,
+            &mut csprng,
+            None,
+        )
+        .await?;
+        let recv_distribution_message =
+            SenderKeyDistributionMessage::try_from(sent_distribution_message.serialized()).unwrap();
+        process_sender_key_distribution_message(
+            &group_sender,
+            &recv_distribution_message,
+            &mut bob_store,
+            None,
+        )
+        .await?;
+
+        let mut ciphertexts = Vec::with_capacity(4);
+        for i in 0..ciphertexts.capacity() {
+            ciphertexts.push(
+                group_encrypt(
+                    &mut alice_store,
+                    &group_sender,
+                    format!("message {}", i).as_bytes(),
+                    &mut csprng,
+                    None,
+                )
+                .await?,
+            );
+        }
+
+        // Two copies of Bob's state decrypt different messages concurrently.
+        let mut other_bob_store = test_in_memory_protocol_store();
+        let record = bob_store
+            .load_sender_key(&group_sender, None)
+            .await?
+            .expect("record");
+        other_bob_store
+            .store_sender_key(&group_sender, &record, None)
+            .await?;
+
+        group_decrypt(&ciphertexts[2], &mut bob_store, &group_sender, None).await?;
+        group_decrypt(&ciphertexts[0], &mut other_bob_store, &group_sender, None).await?;
+
+        let mut record = bob_store
+            .load_sender_key(&group_sender, None)
+            .await?
+            .expect("record");
+        let other_record = other_bob_store
+            .load_sender_key(&group_sender, None)
+            .await?
+            .expect("record");
+        record.merge(&other_record)?;
+        bob_store
+            .store_sender_key(&group_sender, &record, None)
+            .await?;
+
+        // Messages already decrypted by either copy can't be decrypted again...
+        for i in &[0, 2] {
+            assert!(
+                group_decrypt(&ciphertexts[*i], &mut bob_store, &group_sender, None)
+                    .await
+                    .is_err()
+            );
+        }
+        // ...but the rest still can.
+        for i in &[1, 3] {
+            assert_eq!(
+                String::from_utf8(
+                    group_decrypt(&ciphertexts[*i], &mut bob_store, &group_sender, None).await?
+                )
+                .unwrap(),
+                format!("message {}", i)
+            );
+        }
+
+        Ok(())
+    })
+}
+
+#[test]
+fn group_merge_conflicts_and_limits() -> Result<(), SignalProtocolError> {
+    let mut csprng = OsRng;
+    let signing_key = KeyPair::generate(&mut csprng).public_key;
+
+    let mut record = SenderKeyRecord::new_empty();
+    record.add_sender_key_state(1, 5, &[1; 32], signing_key, None)?;
+
+    let mut conflicting = SenderKeyRecord::new_empty();
+    conflicting.add_sender_key_state(1, 5, &[2; 32], signing_key, None)?;
+    let before = record.serialize()?;
+    assert!(matches!(
+        record.merge(&conflicting),
+        Err(SignalProtocolError::InvalidState(..))
+    ));
+    assert_eq!(record.serialize()?, before);
+
+    let mut newer = SenderKeyRecord::new_empty();
+    newer.add_sender_key_state(1, 7, &[3; 32], signing_key, None)?;
+    record.merge(&newer)?;
+    assert_eq!(
+        record
+            .sender_key_state_for_keyid(1)?
+            .sender_chain_key()?
+            .iteration()?,
+        7
+    );
+
+    // A key the other record has rotated to becomes current, even when this record is full.
+    let mut full = SenderKeyRecord::new_empty();
+    for id in 2..=6 {
+        full.add_sender_key_state(id, 0, &[id as u8; 32], signing_key, None)?;
+    }
+    let mut rotated = SenderKeyRecord::new_empty();
+    for id in 3..=7 {
+        rotated.add_sender_key_state(id, 0, &[id as u8; 32], signing_key, None)?;
+    }
+    full.merge(&rotated)?;
+    assert_eq!(full.sender_key_state()?.sender_key_id()?, 7);
+    for id in 3..=6 {
+        assert!(full.sender_key_state_for_keyid(id).is_ok());
+    }
+    assert!(full.sender_key_state_for_keyid(2).is_err());
+
+    // States only in the other record keep their place in its order, between the shared ones.
+    let mut interleaved = SenderKeyRecord::new_empty();
+    interleaved.add_sender_key_state(8, 0, &[8; 32], signing_key, None)?;
+    interleaved.add_sender_key_state(6, 0, &[6; 32], signing_key, None)?;
+    interleaved.add_sender_key_state(9, 0, &[9; 32], signing_key, None)?;
+    interleaved.add_sender_key_state(7, 0, &[7; 32], signing_key, None)?;
+    full.merge(&interleaved)?;
+    let ids = full
+        .as_protobuf()?
+        .sender_key_states
+        .iter()
+        .map(|state| state.sender_key_id)
+        .collect::<Vec<_>>();
+    assert_eq!(ids, vec![7, 9, 6, 8, 5]);
+
+    Ok(())
+}
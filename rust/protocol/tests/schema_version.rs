@@ -0,0 +1,109 @@
+//
+// Copyright 2021 Signal Messenger, LLC.
+// SPDX-License-Identifier: AGPL-3.0-only
+//
+
+use libsignal_protocol::*;
+use std::convert::TryFrom;
+
+// Records serialized before the schema_version field existed (schema version 0), using the keys
+// from tests/ratchet.rs.
+
+const BOB_IDENTITY_PUBLIC: &str =
+    "05f1f43874f6966956c2dd473f8fa15adeb71d1cb991b2341692324cefb1c5e626";
+const BOB_EPHEMERAL_PUBLIC: &str =
+    "052cb49776b8770205745a3a6e24f579cdb4ba7a89041005928ebbadc9c05ad458";
+const BOB_SIGNED_PREKEY_PUBLIC: &str =
+    "05ac248a8f263be6863576eb0362e28c828f0107a3379d34bab1586bf8c770cd67";
+
+const V0_IDENTITY_KEY_PAIR: &str = "0a2105f1f43874f6966956c2dd473f8fa15adeb71d1cb991b2341692324cefb1c5e62612204875cc69ddf8ea0719ec947d61081135868d5fd801f02c0225e516df2156605e";
+const V0_PRE_KEY_RECORD: &str = "08071221052cb49776b8770205745a3a6e24f579cdb4ba7a89041005928ebbadc9c05ad4581a20a0cab48f7c893fafa9880a28c3b4999d28d6329562d27a4ea4e22e9ff1bdd65a";
+const V0_SIGNED_PRE_KEY_RECORD: &str = "0803122105ac248a8f263be6863576eb0362e28c828f0107a3379d34bab1586bf8c770cd671a20583900131fb727998b7803fe6ac22cc591f342e4e42a8c8d5d78194209b8d25322405555555555555555555555555555555555555555555555555555555555555555555555555555555555555555555555555555555555555555555555555555555529d202964900000000";
+const V0_SESSION_RECORD: &str = "0ad5010803122105f1f43874f6966956c2dd473f8fa15adeb71d1cb991b2341692324cefb1c5e6261a2105b4a8455660ada65b401007f615e654041746432e3339c6875149bceefcb42b4a22203e6130d0a8c8e5b8ad65eb8b7d95fa3ab84710d48d92f34dcda43f9909261bca32690a21052cb49776b8770205745a3a6e24f579cdb4ba7a89041005928ebbadc9c05ad4581220a0cab48f7c893fafa9880a28c3b4999d28d6329562d27a4ea4e22e9ff1bdd65a1a2212209797caca53c989bbe229a40ca7727010eb2604fc14945d77958a0aeda088b44d";
+const V0_SENDER_KEY_RECORD: &str = "0a7108accce9e004122212205ed51dcdebc8b7d39749fa4e67c146781fcee86cd26dfe4d41ba579660ce50881a450a21056b52d02d43ba64ea615e3bf24bf4e72add55cacb0bea46c873e0e9732a3d656e122040b219d62104477a16fc6aa977cd232533a005a821f507cf2799aba2381d476a";
+
+/// Checks that `old` has no schema version, and that re-serializing it records the current one.
+fn assert_upgraded(old: &[u8], reserialized: &[u8]) {
+    assert_eq!(parse_schema_version(old), None);
+    assert_eq!(
+        parse_schema_version(reserialized),
+        Some(PROTO_SCHEMA_VERSION)
+    );
+}
+
+#[test]
+fn test_v0_identity_key_pair() -> Result<(), SignalProtocolError> {
+    let old = hex::decode(V0_IDENTITY_KEY_PAIR).unwrap();
+    let key_pair = IdentityKeyPair::try_from(&old[..])?;
+    assert_eq!(
+        hex::encode(key_pair.identity_key().serialize()),
+        BOB_IDENTITY_PUBLIC
+    );
+    assert_upgraded(&old, &key_pair.serialize());
+    Ok(())
+}
+
+#[test]
+fn test_v0_pre_key_record() -> Result<(), SignalProtocolError> {
+    let old = hex::decode(V0_PRE_KEY_RECORD).unwrap();
+    let record = PreKeyRecord::deserialize(&old)?;
+    assert_eq!(record.id()?, 7);
+    assert_eq!(
+        hex::encode(record.public_key()?.serialize()),
+        BOB_EPHEMERAL_PUBLIC
+    );
+    assert_upgraded(&old, &record.serialize()?);
+    Ok(())
+}
+
+#[test]
+fn test_v0_signed_pre_key_record() -> Result<(), SignalProtocolError> {
+    let old = hex::decode(V0_SIGNED_PRE_KEY_RECORD).unwrap();
+    let record = SignedPreKeyRecord::deserialize(&old)?;
+    assert_eq!(record.id()?, 3);
+    assert_eq!(record.timestamp()?, 1234567890);
+    assert_eq!(record.signature()?, vec![0x55; 64]);
+    assert_eq!(
+        hex::encode(record.public_key()?.serialize()),
+        BOB_SIGNED_PREKEY_PUBLIC
+    );
+    assert_upgraded(&old, &record.serialize()?);
+    Ok(())
+}
+
+#[test]
+fn test_v0_session_record() -> Result<(), SignalProtocolError> {
+    let old = hex::decode(V0_SESSION_RECORD).unwrap();
+    let record = SessionRecord::deserialize(&old)?;
+    assert_eq!(
+        hex::encode(record.local_identity_key_bytes()?),
+        BOB_IDENTITY_PUBLIC
+    );
+    assert_eq!(
+        hex::encode(record.get_sender_chain_key_bytes()?),
+        "9797caca53c989bbe229a40ca7727010eb2604fc14945d77958a0aeda088b44d"
+    );
+    assert_upgraded(&old, &record.serialize()?);
+    Ok(())
+}
+
+#[test]
+fn test_v0_sender_key_record() -> Result<(), SignalProtocolError> {
+    let old = hex::decode(V0_SENDER_KEY_RECORD).unwrap();
+    let mut record = SenderKeyRecord::deserialize(&old)?;
+    assert!(!record.is_empty()?);
+    assert_eq!(
+        record.sender_key_state()?.sender_chain_key()?.iteration()?,
+        0
+    );
+    assert_upgraded(&old, &record.serialize()?);
+    Ok(())
+}
+
+#[test]
+fn test_parse_schema_version_rejects_garbage() {
+    assert_eq!(parse_schema_version(&[]), None);
+    assert_eq!(parse_schema_version(&[0xFF; 3]), None);
+    // A length prefix running past the end of the input.
+    assert_eq!(parse_schema_version(&[0x0A, 0x10, 0x00]), None);
+}
@@ -135,6 +135,124 @@ fn test_sender_cert() -> Result<(), SignalProtocolError> {
     Ok(())
 }
 
+#[test]
+fn test_sender_cert_validation() -> Result<(), SignalProtocolError> {
+    let mut rng = OsRng;
+    let trust_root = KeyPair::generate(&mut rng);
+    let server_key = KeyPair::generate(&mut rng);
+    let wrong_server_key = KeyPair::generate(&mut rng);
+    let key = KeyPair::generate(&mut rng);
+
+    let expires = 1605722925;
+    let make_cert = |signing_key: &PrivateKey, rng: &mut OsRng| {
+        let server_cert =
+            ServerCertificate::new(1, server_key.public_key, &trust_root.private_key, rng)?;
+        SenderCertificate::new(
+            "9d0652a3-dcc3-4d11-975f-74d61598733f".to_string(),
+            None,
+            key.public_key,
+            1,
+            expires,
+            server_cert,
+            signing_key,
+            rng,
+        )
+    };
+
+    // Valid up to and including the expiration time.
+    let valid_cert = make_cert(&server_key.private_key, &mut rng)?;
+    assert!(valid_cert.validate(&trust_root.public_key, 0)?);
+    assert!(valid_cert.validate(&trust_root.public_key, expires)?);
+
+    // Expired.
+    assert!(!valid_cert.validate(&trust_root.public_key, expires + 1)?);
+    assert!(!valid_cert.validate(&trust_root.public_key, u64::MAX)?);
+
+    // Signed by a key other than the one in the server certificate.
+    let wrongly_signed_cert = make_cert(&wrong_server_key.private_key, &mut rng)?;
+    assert!(!wrongly_signed_cert.validate(&trust_root.public_key, expires)?);
+
+    // Server certificate not signed by the trust root.
+    assert!(!valid_cert.validate(&wrong_server_key.public_key, expires)?);
+
+    // The result survives a round trip through serialization.
+    let recovered = SenderCertificate::deserialize(valid_cert.serialized()?)?;
+    assert!(recovered.validate(&trust_root.public_key, expires)?);
+    let recovered = SenderCertificate::deserialize(wrongly_signed_cert.serialized()?)?;
+    assert!(!recovered.validate(&trust_root.public_key, expires)?);
+
+    Ok(())
+}
+
+#[test]
+fn test_sender_cert_expiry() -> Result<(), SignalProtocolError> {
+    let mut rng = OsRng;
+    let trust_root = KeyPair::generate(&mut rng);
+    let server_key = KeyPair::generate(&mut rng);
+    let key = KeyPair::generate(&mut rng);
+
+    let server_cert =
+        ServerCertificate::new(1, server_key.public_key, &trust_root.private_key, &mut rng)?;
+
+    let sender_cert = SenderCertificate::new(
+        "9d0652a3-dcc3-4d11-975f-74d61598733f".to_string(),
+        None,
+        key.public_key,
+        1,
+        0,
+        server_cert,
+        &server_key.private_key,
+        &mut rng,
+    )?;
+
+    // Still valid at the instant it expires...
+    validate_sender_certificate(&sender_cert, 0)?;
+    assert!(sender_cert.validate(&trust_root.public_key, 0)?);
+
+    // ...but not once the clock moves past it.
+    match validate_sender_certificate(&sender_cert, 1) {
+        Err(SignalProtocolError::CertificateExpired { expired_at, now }) => {
+            assert_eq!(expired_at, 0);
+            assert_eq!(now, 1);
+        }
+        result => panic!("unexpected result {:?}", result),
+    }
+    assert!(!sender_cert.validate(&trust_root.public_key, 1)?);
+
+    Ok(())
+}
+
+#[test]
+fn test_oversized_sealed_sender_protobufs() {
+    use rand::{Rng, RngCore};
+
+    for _ in 0..5 {
+        let extra = OsRng.gen_range(1, 4096);
+        let mut data = vec![0u8; 16 * 1024 + extra];
+        OsRng.fill_bytes(&mut data);
+        assert!(matches!(
+            ServerCertificate::deserialize(&data),
+            Err(SignalProtocolError::ProtobufTooLarge(_, _))
+        ));
+        assert!(matches!(
+            SenderCertificate::deserialize(&data),
+            Err(SignalProtocolError::ProtobufTooLarge(_, _))
+        ));
+
+        let mut data = vec![0u8; (1024 + 64) * 1024 + extra];
+        OsRng.fill_bytes(&mut data);
+        data[0] = 0x11; // current version
+        assert!(matches!(
+            UnidentifiedSenderMessage::deserialize(&data),
+            Err(SignalProtocolError::ProtobufTooLarge(_, _))
+        ));
+        assert!(matches!(
+            UnidentifiedSenderMessageContent::deserialize(&data),
+            Err(SignalProtocolError::ProtobufTooLarge(_, _))
+        ));
+    }
+}
+
 #[test]
 fn test_sealed_sender() -> Result<(), SignalProtocolError> {
     block_on(async {
@@ -219,6 +337,37 @@ fn test_sealed_sender() -> Result<(), SignalProtocolError> {
         assert_eq!(bob_ptext.sender_e164, Some(alice_e164));
         assert_eq!(bob_ptext.device_id, alice_device_id);
 
+        // The largest message the session cipher accepts must still fit in an envelope.
+
+        let alice_ptext = vec![0x42u8; 1024 * 1024];
+        let alice_ctext = sealed_sender_encrypt(
+            &bob_uuid_address,
+            &sender_cert,
+            &alice_ptext,
+            &mut alice_store.session_store,
+            &mut alice_store.identity_store,
+            None,
+            &mut rng,
+        )
+        .await?;
+
+        let bob_ptext = sealed_sender_decrypt(
+            &alice_ctext,
+            &trust_root.public_key,
+            expires - 1,
+            Some(bob_e164.clone()),
+            bob_uuid.clone(),
+            bob_device_id,
+            &mut bob_store.identity_store,
+            &mut bob_store.session_store,
+            &mut bob_store.pre_key_store,
+            &mut bob_store.signed_pre_key_store,
+            None,
+        )
+        .await?;
+
+        assert_eq!(bob_ptext.message, alice_ptext);
+
         // Now test but with an expired cert:
 
         let alice_ctext = sealed_sender_encrypt(
@@ -248,7 +397,10 @@ fn test_sealed_sender() -> Result<(), SignalProtocolError> {
         .await;
 
         match bob_ptext {
-            Err(SignalProtocolError::InvalidSealedSenderMessage(_)) => { /* ok */ }
+            Err(SignalProtocolError::CertificateExpired { expired_at, now }) => {
+                assert_eq!(expired_at, expires);
+                assert_eq!(now, expires + 11);
+            }
             Err(err) => {
                 panic!("Unexpected error {}", err)
             }
@@ -300,3 +452,155 @@ fn test_sealed_sender() -> Result<(), SignalProtocolError> {
         Ok(())
     })
 }
+
+#[test]
+fn test_sealed_sender_multi_recipient() -> Result<(), SignalProtocolError> {
+    block_on(async {
+        let mut rng = OsRng;
+
+        let alice_uuid = "9d0652a3-dcc3-4d11-975f-74d61598733f".to_string();
+        let bob_address = ProtocolAddress::new("796abedb-ca4e-4f18-8803-1fde5b921f9f".into(), 1);
+        let carol_address = ProtocolAddress::new("38381c3b-2606-4ca7-9310-7cb927f2ab4a".into(), 2);
+
+        let mut alice_store = support::test_in_memory_protocol_store();
+        let mut bob_store = support::test_in_memory_protocol_store();
+        let mut carol_store = support::test_in_memory_protocol_store();
+
+        for (address, store) in &mut [
+            (&bob_address, &mut bob_store),
+            (&carol_address, &mut carol_store),
+        ] {
+            let bundle = create_pre_key_bundle(*store, &mut rng).await?;
+            process_prekey_bundle(
+                address,
+                &mut alice_store.session_store,
+                &mut alice_store.identity_store,
+                &bundle,
+                &mut rng,
+                None,
+            )
+            .await?;
+        }
+
+        let trust_root = KeyPair::generate(&mut rng);
+        let server_key = KeyPair::generate(&mut rng);
+        let server_cert =
+            ServerCertificate::new(1, server_key.public_key, &trust_root.private_key, &mut rng)?;
+        let sender_cert = SenderCertificate::new(
+            alice_uuid.clone(),
+            None,
+            *alice_store.get_identity_key_pair(None).await?.public_key(),
+            23,
+            1605722925,
+            server_cert,
+            &server_key.private_key,
+            &mut rng,
+        )?;
+
+        let usmc = UnidentifiedSenderMessageContent::new(
+            CiphertextMessageType::Whisper,
+            sender_cert,
+            vec![1, 2, 3, 23, 99],
+        )?;
+
+        let bob_session = alice_store
+            .load_session(&bob_address, None)
+            .await?
+            .expect("session established");
+        let carol_session = alice_store
+            .load_session(&carol_address, None)
+            .await?
+            .expect("session established");
+
+        let sent = sealed_sender_multi_recipient_encrypt(
+            &[&bob_address, &carol_address],
+            &[&bob_session, &carol_session],
+            &usmc,
+            &mut alice_store.identity_store,
+            None,
+            &mut rng,
+        )
+        .await?;
+
+        let parsed = SealedSenderMultiRecipientMessage::deserialize(&sent)?;
+        assert_eq!(
+            parsed.recipients()?,
+            &[bob_address.clone(), carol_address.clone()]
+        );
+
+        let bob_message = parsed.message_for_recipient(&bob_address)?;
+        let carol_message = parsed.message_for_recipient(&carol_address)?;
+        // The encrypted content is shared, so each recipient's message is much smaller than the
+        // whole.
+        assert!(bob_message.len() < sent.len());
+
+        for (message, store) in &mut [
+            (&bob_message, &mut bob_store),
+            (&carol_message, &mut carol_store),
+        ] {
+            let decrypted =
+                sealed_sender_decrypt_to_usmc(message, &mut store.identity_store, None).await?;
+            assert_eq!(decrypted.serialized()?, usmc.serialized()?);
+            assert_eq!(decrypted.sender()?.sender_uuid()?, alice_uuid);
+        }
+
+        // Each recipient can only decrypt their own message.
+        assert!(
+            sealed_sender_decrypt_to_usmc(&carol_message, &mut bob_store.identity_store, None)
+                .await
+                .is_err()
+        );
+
+        // Tampering with any part of a recipient's message is detected.
+        for i in 1..bob_message.len() {
+            let mut tampered = bob_message.clone();
+            tampered[i] ^= 0x01;
+            assert!(
+                sealed_sender_decrypt_to_usmc(&tampered, &mut bob_store.identity_store, None)
+                    .await
+                    .is_err(),
+                "flipped a bit in byte {}",
+                i
+            );
+        }
+
+        assert!(matches!(
+            parsed.message_for_recipient(&ProtocolAddress::new(alice_uuid, 23)),
+            Err(SignalProtocolError::InvalidArgument(_))
+        ));
+
+        // Truncated messages are rejected rather than misparsed, up to the point where only the
+        // length of the encrypted content is wrong (which decryption catches).
+        for len in 0..sent.len() - usmc.serialized()?.len() {
+            assert!(SealedSenderMultiRecipientMessage::deserialize(&sent[..len]).is_err());
+        }
+
+        // Recipients must be distinct, and match up with their sessions.
+        assert!(matches!(
+            sealed_sender_multi_recipient_encrypt(
+                &[&bob_address, &bob_address],
+                &[&bob_session, &bob_session],
+                &usmc,
+                &mut alice_store.identity_store,
+                None,
+                &mut rng,
+            )
+            .await,
+            Err(SignalProtocolError::InvalidArgument(_))
+        ));
+        assert!(matches!(
+            sealed_sender_multi_recipient_encrypt(
+                &[&bob_address, &carol_address],
+                &[&bob_session],
+                &usmc,
+                &mut alice_store.identity_store,
+                None,
+                &mut rng,
+            )
+            .await,
+            Err(SignalProtocolError::InvalidArgument(_))
+        ));
+
+        Ok(())
+    })
+}
@@ -7,7 +7,8 @@ mod support;
 
 use futures::executor::block_on;
 use libsignal_protocol::*;
-use rand::rngs::OsRng;
+use rand::rngs::{OsRng, StdRng};
+use rand::SeedableRng;
 use std::convert::TryFrom;
 use support::*;
 
@@ -36,15 +37,17 @@ fn test_basic_prekey_v3() -> Result<(), SignalProtocolError> {
         let pre_key_id = 31337;
         let signed_pre_key_id = 22;
 
-        let bob_pre_key_bundle = PreKeyBundle::new(
-            bob_store.get_local_registration_id(None).await?,
-            1,                                               // device id
-            Some((pre_key_id, bob_pre_key_pair.public_key)), // pre key
-            signed_pre_key_id,                               // signed pre key id
-            bob_signed_pre_key_pair.public_key,
-            bob_signed_pre_key_signature.to_vec(),
-            *bob_store.get_identity_key_pair(None).await?.identity_key(),
-        )?;
+        let bob_pre_key_bundle = PreKeyBundleBuilder::new()
+            .registration_id(bob_store.get_local_registration_id(None).await?)
+            .device_id(1)
+            .pre_key(pre_key_id, bob_pre_key_pair.public_key)
+            .signed_pre_key(
+                signed_pre_key_id,
+                bob_signed_pre_key_pair.public_key,
+                &bob_signed_pre_key_signature,
+            )
+            .identity_key(*bob_store.get_identity_key_pair(None).await?.identity_key())
+            .build()?;
 
         process_prekey_bundle(
             &bob_address,
@@ -147,15 +150,17 @@ fn test_basic_prekey_v3() -> Result<(), SignalProtocolError> {
         let pre_key_id = 31337;
         let signed_pre_key_id = 22;
 
-        let bob_pre_key_bundle = PreKeyBundle::new(
-            bob_store.get_local_registration_id(None).await?,
-            1,                                                   // device id
-            Some((pre_key_id + 1, bob_pre_key_pair.public_key)), // pre key,
-            signed_pre_key_id + 1,
-            bob_signed_pre_key_pair.public_key,
-            bob_signed_pre_key_signature.to_vec(),
-            *bob_store.get_identity_key_pair(None).await?.identity_key(),
-        )?;
+        let bob_pre_key_bundle = PreKeyBundleBuilder::new()
+            .registration_id(bob_store.get_local_registration_id(None).await?)
+            .device_id(1)
+            .pre_key(pre_key_id + 1, bob_pre_key_pair.public_key)
+            .signed_pre_key(
+                signed_pre_key_id + 1,
+                bob_signed_pre_key_pair.public_key,
+                &bob_signed_pre_key_signature,
+            )
+            .identity_key(*bob_store.get_identity_key_pair(None).await?.identity_key())
+            .build()?;
 
         bob_store
             .save_pre_key(
@@ -189,11 +194,22 @@ fn test_basic_prekey_v3() -> Result<(), SignalProtocolError> {
 
         let outgoing_message = encrypt(&mut alice_store, &bob_address, original_message).await?;
 
+        let old_alice_identity = bob_store
+            .get_identity(&alice_address, None)
+            .await?
+            .expect("saved during the first session");
+        let new_alice_identity = *alice_store
+            .get_identity_key_pair(None)
+            .await?
+            .identity_key();
         assert!(matches!(
             decrypt(&mut bob_store, &alice_address, &outgoing_message)
                 .await
                 .unwrap_err(),
-            SignalProtocolError::UntrustedIdentity(a) if a == alice_address
+            SignalProtocolError::IdentityKeyChanged(address, old_key, new_key)
+                if address == alice_address
+                    && old_key == old_alice_identity
+                    && new_key == new_alice_identity
         ));
 
         assert_eq!(
@@ -214,18 +230,22 @@ fn test_basic_prekey_v3() -> Result<(), SignalProtocolError> {
         assert_eq!(String::from_utf8(decrypted).unwrap(), original_message);
 
         // Sign pre-key with wrong key:
-        let bob_pre_key_bundle = PreKeyBundle::new(
-            bob_store.get_local_registration_id(None).await?,
-            1,                                               // device id
-            Some((pre_key_id, bob_pre_key_pair.public_key)), // pre key
-            signed_pre_key_id,
-            bob_signed_pre_key_pair.public_key,
-            bob_signed_pre_key_signature.to_vec(),
-            *alice_store
-                .get_identity_key_pair(None)
-                .await?
-                .identity_key(),
-        )?;
+        let bob_pre_key_bundle = PreKeyBundleBuilder::new()
+            .registration_id(bob_store.get_local_registration_id(None).await?)
+            .device_id(1)
+            .pre_key(pre_key_id, bob_pre_key_pair.public_key)
+            .signed_pre_key(
+                signed_pre_key_id,
+                bob_signed_pre_key_pair.public_key,
+                &bob_signed_pre_key_signature,
+            )
+            .identity_key(
+                *alice_store
+                    .get_identity_key_pair(None)
+                    .await?
+                    .identity_key(),
+            )
+            .build()?;
 
         assert!(process_prekey_bundle(
             &bob_address,
@@ -271,15 +291,17 @@ fn test_bad_signed_pre_key_signature() -> Result<(), SignalProtocolError> {
 
             bad_signature[bit / 8] ^= 0x01u8 << (bit % 8);
 
-            let bob_pre_key_bundle = PreKeyBundle::new(
-                bob_store.get_local_registration_id(None).await?,
-                1,
-                Some((pre_key_id, bob_pre_key_pair.public_key)),
-                signed_pre_key_id,
-                bob_signed_pre_key_pair.public_key,
-                bad_signature,
-                *bob_store.get_identity_key_pair(None).await?.identity_key(),
-            )?;
+            let bob_pre_key_bundle = PreKeyBundleBuilder::new()
+                .registration_id(bob_store.get_local_registration_id(None).await?)
+                .device_id(1)
+                .pre_key(pre_key_id, bob_pre_key_pair.public_key)
+                .signed_pre_key(
+                    signed_pre_key_id,
+                    bob_signed_pre_key_pair.public_key,
+                    &bad_signature,
+                )
+                .identity_key(*bob_store.get_identity_key_pair(None).await?.identity_key())
+                .build()?;
 
             assert!(process_prekey_bundle(
                 &bob_address,
@@ -295,15 +317,17 @@ fn test_bad_signed_pre_key_signature() -> Result<(), SignalProtocolError> {
 
         // Finally check that the non-corrupted signature is accepted:
 
-        let bob_pre_key_bundle = PreKeyBundle::new(
-            bob_store.get_local_registration_id(None).await?,
-            1,
-            Some((pre_key_id, bob_pre_key_pair.public_key)),
-            signed_pre_key_id,
-            bob_signed_pre_key_pair.public_key,
-            bob_signed_pre_key_signature,
-            *bob_store.get_identity_key_pair(None).await?.identity_key(),
-        )?;
+        let bob_pre_key_bundle = PreKeyBundleBuilder::new()
+            .registration_id(bob_store.get_local_registration_id(None).await?)
+            .device_id(1)
+            .pre_key(pre_key_id, bob_pre_key_pair.public_key)
+            .signed_pre_key(
+                signed_pre_key_id,
+                bob_signed_pre_key_pair.public_key,
+                &bob_signed_pre_key_signature,
+            )
+            .identity_key(*bob_store.get_identity_key_pair(None).await?.identity_key())
+            .build()?;
 
         process_prekey_bundle(
             &bob_address,
@@ -345,15 +369,17 @@ fn repeat_bundle_message_v3() -> Result<(), SignalProtocolError> {
         let pre_key_id = 31337;
         let signed_pre_key_id = 22;
 
-        let bob_pre_key_bundle = PreKeyBundle::new(
-            bob_store.get_local_registration_id(None).await?,
-            1,                                               // device id
-            Some((pre_key_id, bob_pre_key_pair.public_key)), // pre key
-            signed_pre_key_id,                               // signed pre key id
-            bob_signed_pre_key_pair.public_key,
-            bob_signed_pre_key_signature.to_vec(),
-            *bob_store.get_identity_key_pair(None).await?.identity_key(),
-        )?;
+        let bob_pre_key_bundle = PreKeyBundleBuilder::new()
+            .registration_id(bob_store.get_local_registration_id(None).await?)
+            .device_id(1)
+            .pre_key(pre_key_id, bob_pre_key_pair.public_key)
+            .signed_pre_key(
+                signed_pre_key_id,
+                bob_signed_pre_key_pair.public_key,
+                &bob_signed_pre_key_signature,
+            )
+            .identity_key(*bob_store.get_identity_key_pair(None).await?.identity_key())
+            .build()?;
 
         process_prekey_bundle(
             &bob_address,
@@ -466,15 +492,17 @@ fn bad_message_bundle() -> Result<(), SignalProtocolError> {
         let pre_key_id = 31337;
         let signed_pre_key_id = 22;
 
-        let bob_pre_key_bundle = PreKeyBundle::new(
-            bob_store.get_local_registration_id(None).await?,
-            1, // device id
-            Some((pre_key_id, bob_pre_key_pair.public_key)),
-            signed_pre_key_id, // signed pre key id
-            bob_signed_pre_key_pair.public_key,
-            bob_signed_pre_key_signature.to_vec(),
-            *bob_store.get_identity_key_pair(None).await?.identity_key(),
-        )?;
+        let bob_pre_key_bundle = PreKeyBundleBuilder::new()
+            .registration_id(bob_store.get_local_registration_id(None).await?)
+            .device_id(1)
+            .pre_key(pre_key_id, bob_pre_key_pair.public_key)
+            .signed_pre_key(
+                signed_pre_key_id,
+                bob_signed_pre_key_pair.public_key,
+                &bob_signed_pre_key_signature,
+            )
+            .identity_key(*bob_store.get_identity_key_pair(None).await?.identity_key())
+            .build()?;
 
         process_prekey_bundle(
             &bob_address,
@@ -581,15 +609,16 @@ fn optional_one_time_prekey() -> Result<(), SignalProtocolError> {
 
         let signed_pre_key_id = 22;
 
-        let bob_pre_key_bundle = PreKeyBundle::new(
-            bob_store.get_local_registration_id(None).await?,
-            1,                 // device id
-            None,              // no pre key
-            signed_pre_key_id, // signed pre key id
-            bob_signed_pre_key_pair.public_key,
-            bob_signed_pre_key_signature.to_vec(),
-            *bob_store.get_identity_key_pair(None).await?.identity_key(),
-        )?;
+        let bob_pre_key_bundle = PreKeyBundleBuilder::new()
+            .registration_id(bob_store.get_local_registration_id(None).await?)
+            .device_id(1)
+            .signed_pre_key(
+                signed_pre_key_id,
+                bob_signed_pre_key_pair.public_key,
+                &bob_signed_pre_key_signature,
+            )
+            .identity_key(*bob_store.get_identity_key_pair(None).await?.identity_key())
+            .build()?;
 
         process_prekey_bundle(
             &bob_address,
@@ -708,6 +737,359 @@ fn message_key_limits() -> Result<(), SignalProtocolError> {
     })
 }
 
+#[test]
+fn largest_message_round_trips() -> Result<(), SignalProtocolError> {
+    block_on(async {
+        let (alice_session_record, bob_session_record) = initialize_sessions_v3()?;
+
+        let alice_address = ProtocolAddress::new("+14159999999".to_owned(), 1);
+        let bob_address = ProtocolAddress::new("+14158888888".to_owned(), 1);
+
+        let mut alice_store = support::test_in_memory_protocol_store();
+        let mut bob_store = support::test_in_memory_protocol_store();
+
+        alice_store
+            .store_session(&bob_address, &alice_session_record, None)
+            .await?;
+        bob_store
+            .store_session(&alice_address, &bob_session_record, None)
+            .await?;
+
+        const MAX_MESSAGE_PLAINTEXT_BYTES: usize = 1024 * 1024; // same value as in library
+
+        let largest = "x".repeat(MAX_MESSAGE_PLAINTEXT_BYTES);
+        let ctext = encrypt(&mut alice_store, &bob_address, &largest).await?;
+        let received =
+            CiphertextMessage::SignalMessage(SignalMessage::try_from(ctext.serialize())?);
+        assert_eq!(
+            decrypt(&mut bob_store, &alice_address, &received).await?,
+            largest.as_bytes()
+        );
+
+        let too_large = "x".repeat(MAX_MESSAGE_PLAINTEXT_BYTES + 1);
+        assert!(matches!(
+            encrypt(&mut alice_store, &bob_address, &too_large).await,
+            Err(SignalProtocolError::InvalidArgument(_))
+        ));
+
+        Ok(())
+    })
+}
+
+#[test]
+fn configurable_skipped_message_key_limit() -> Result<(), SignalProtocolError> {
+    block_on(async {
+        let (alice_session_record, mut bob_session_record) = initialize_sessions_v3()?;
+
+        let alice_address = ProtocolAddress::new("+14159999999".to_owned(), 1);
+        let bob_address = ProtocolAddress::new("+14158888888".to_owned(), 1);
+
+        let mut alice_store = support::test_in_memory_protocol_store();
+        let mut bob_store = support::test_in_memory_protocol_store();
+
+        const LIMIT: usize = 10;
+        assert_eq!(bob_session_record.max_skipped_message_keys()?, 25_000);
+        bob_session_record.set_max_skipped_message_keys(LIMIT as u32)?;
+        // The limit is saved with the record.
+        let bob_session_record = SessionRecord::deserialize(&bob_session_record.serialize()?)?;
+        assert_eq!(bob_session_record.max_skipped_message_keys()?, LIMIT);
+
+        alice_store
+            .store_session(&bob_address, &alice_session_record, None)
+            .await?;
+        bob_store
+            .store_session(&alice_address, &bob_session_record, None)
+            .await?;
+
+        let mut inflight = Vec::new();
+        for i in 0..(LIMIT + 3) {
+            inflight.push(encrypt(&mut alice_store, &bob_address, &format!("msg {}", i)).await?);
+        }
+
+        // Skipping exactly LIMIT keys is fine...
+        assert_eq!(
+            decrypt(&mut bob_store, &alice_address, &inflight[LIMIT]).await?,
+            format!("msg {}", LIMIT).into_bytes()
+        );
+        // ...and so is a gap after that, as long as it's within the limit.
+        assert_eq!(
+            decrypt(&mut bob_store, &alice_address, &inflight[LIMIT + 1]).await?,
+            format!("msg {}", LIMIT + 1).into_bytes()
+        );
+
+        // A fresh session, with the same limit, and a gap one larger than it.
+        let (alice_session_record, mut bob_session_record) = initialize_sessions_v3()?;
+        bob_session_record.set_max_skipped_message_keys(LIMIT as u32)?;
+        let mut alice_store = support::test_in_memory_protocol_store();
+        let mut bob_store = support::test_in_memory_protocol_store();
+        alice_store
+            .store_session(&bob_address, &alice_session_record, None)
+            .await?;
+        bob_store
+            .store_session(&alice_address, &bob_session_record, None)
+            .await?;
+
+        let mut inflight = Vec::new();
+        for i in 0..(LIMIT + 2) {
+            inflight.push(encrypt(&mut alice_store, &bob_address, &format!("msg {}", i)).await?);
+        }
+        let err = decrypt(&mut bob_store, &alice_address, &inflight[LIMIT + 1])
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(err, SignalProtocolError::TooManySkippedKeys(jump, limit) if jump == LIMIT + 1 && limit == LIMIT),
+            "{:?}",
+            err
+        );
+
+        // The failed decryption didn't change anything, so earlier messages still work.
+        assert_eq!(
+            decrypt(&mut bob_store, &alice_address, &inflight[0]).await?,
+            b"msg 0".to_vec()
+        );
+        Ok(())
+    })
+}
+
+#[test]
+fn skipped_key_limit_falls_back_to_previous_states() -> Result<(), SignalProtocolError> {
+    block_on(async {
+        let mut csprng = OsRng;
+
+        let alice_address = ProtocolAddress::new("+14151111111".to_owned(), 1);
+        let bob_address = ProtocolAddress::new("+14151111112".to_owned(), 1);
+
+        let mut alice_store = support::test_in_memory_protocol_store();
+        let mut bob_store = support::test_in_memory_protocol_store();
+
+        let alice_pre_key_bundle = create_pre_key_bundle(&mut alice_store, &mut csprng).await?;
+        let bob_pre_key_bundle = create_pre_key_bundle(&mut bob_store, &mut csprng).await?;
+
+        process_prekey_bundle(
+            &bob_address,
+            &mut alice_store.session_store,
+            &mut alice_store.identity_store,
+            &bob_pre_key_bundle,
+            &mut csprng,
+            None,
+        )
+        .await?;
+
+        let first = encrypt(&mut alice_store, &bob_address, "msg 0").await?;
+        assert_eq!(
+            decrypt(&mut bob_store, &alice_address, &first).await?,
+            b"msg 0".to_vec()
+        );
+
+        // Bob starts a new session of his own, archiving the one Alice is still using, and limits
+        // the new one to a small jump.
+        process_prekey_bundle(
+            &alice_address,
+            &mut bob_store.session_store,
+            &mut bob_store.identity_store,
+            &alice_pre_key_bundle,
+            &mut csprng,
+            None,
+        )
+        .await?;
+        const LIMIT: usize = 10;
+        let mut bob_session_record = bob_store
+            .load_session(&alice_address, None)
+            .await?
+            .expect("session found");
+        bob_session_record.set_max_skipped_message_keys(LIMIT as u32)?;
+        bob_store
+            .store_session(&alice_address, &bob_session_record, None)
+            .await?;
+
+        let mut inflight = Vec::new();
+        for i in 1..(LIMIT + 3) {
+            inflight.push(encrypt(&mut alice_store, &bob_address, &format!("msg {}", i)).await?);
+        }
+
+        // The current state would have to skip too many keys, but the archived one only skips a
+        // few, so the message still decrypts.
+        assert_eq!(
+            decrypt(&mut bob_store, &alice_address, inflight.last().unwrap()).await?,
+            format!("msg {}", LIMIT + 2).into_bytes()
+        );
+
+        Ok(())
+    })
+}
+
+async fn streaming_decrypt(
+    store: &mut InMemSignalProtocolStore,
+    remote_address: &ProtocolAddress,
+    serialized: &[u8],
+    chunk_size: usize,
+) -> Result<Vec<u8>, SignalProtocolError> {
+    let mut decryptor = StreamingDecryptor::new(
+        remote_address,
+        &mut store.session_store,
+        &mut store.identity_store,
+        &mut OsRng,
+        None,
+    )
+    .await?;
+    let mut ptext = vec![];
+    for chunk in serialized.chunks(chunk_size) {
+        ptext.extend(decryptor.update(chunk)?);
+    }
+    ptext.extend(decryptor.finalize().await?);
+    Ok(ptext)
+}
+
+#[test]
+fn streaming_decrypt_round_trip() -> Result<(), SignalProtocolError> {
+    block_on(async {
+        let (alice_session_record, bob_session_record) = initialize_sessions_v3()?;
+
+        let alice_address = ProtocolAddress::new("+14159999999".to_owned(), 1);
+        let bob_address = ProtocolAddress::new("+14158888888".to_owned(), 1);
+
+        let mut alice_store = support::test_in_memory_protocol_store();
+        let mut bob_store = support::test_in_memory_protocol_store();
+
+        alice_store
+            .store_session(&bob_address, &alice_session_record, None)
+            .await?;
+        bob_store
+            .store_session(&alice_address, &bob_session_record, None)
+            .await?;
+
+        for (i, chunk_size) in [1, 7, 16, 1000, 1 << 20].iter().enumerate() {
+            let ptext: Vec<u8> = (0..100_000 + i).map(|j| (j * 31 + i) as u8).collect();
+            let ctext = message_encrypt(
+                &ptext,
+                &bob_address,
+                &mut alice_store.session_store,
+                &mut alice_store.identity_store,
+                None,
+            )
+            .await?;
+            assert_eq!(ctext.message_type(), CiphertextMessageType::Whisper);
+
+            let decrypted = streaming_decrypt(
+                &mut bob_store,
+                &alice_address,
+                ctext.serialize(),
+                *chunk_size,
+            )
+            .await?;
+            assert_eq!(decrypted, ptext);
+
+            // The ratchet state was saved, so the message can't be decrypted again.
+            let err = decrypt(&mut bob_store, &alice_address, &ctext)
+                .await
+                .unwrap_err();
+            assert!(matches!(err, SignalProtocolError::DuplicatedMessage(_, _)));
+        }
+
+        // Replies start new chains in both directions.
+        let bob_ciphertext = encrypt(&mut bob_store, &alice_address, "reply").await?;
+        let decrypted = streaming_decrypt(
+            &mut alice_store,
+            &bob_address,
+            bob_ciphertext.serialize(),
+            5,
+        )
+        .await?;
+        assert_eq!(decrypted, b"reply");
+
+        let alice_ciphertext = encrypt(&mut alice_store, &bob_address, "and back").await?;
+        let decrypted = decrypt(&mut bob_store, &alice_address, &alice_ciphertext).await?;
+        assert_eq!(decrypted, b"and back");
+
+        Ok(())
+    })
+}
+
+#[test]
+fn streaming_decrypt_errors() -> Result<(), SignalProtocolError> {
+    block_on(async {
+        let (alice_session_record, bob_session_record) = initialize_sessions_v3()?;
+
+        let alice_address = ProtocolAddress::new("+14159999999".to_owned(), 1);
+        let bob_address = ProtocolAddress::new("+14158888888".to_owned(), 1);
+
+        let mut alice_store = support::test_in_memory_protocol_store();
+        let mut bob_store = support::test_in_memory_protocol_store();
+
+        alice_store
+            .store_session(&bob_address, &alice_session_record, None)
+            .await?;
+        bob_store
+            .store_session(&alice_address, &bob_session_record, None)
+            .await?;
+
+        let ptext = vec![0x42u8; 1000];
+        let ctext = message_encrypt(
+            &ptext,
+            &bob_address,
+            &mut alice_store.session_store,
+            &mut alice_store.identity_store,
+            None,
+        )
+        .await?;
+        let serialized = ctext.serialize();
+
+        // Truncated final chunk.
+        for truncated_len in &[0, 10, serialized.len() - 100, serialized.len() - 1] {
+            let err = streaming_decrypt(
+                &mut bob_store,
+                &alice_address,
+                &serialized[..*truncated_len],
+                64,
+            )
+            .await
+            .unwrap_err();
+            assert!(
+                matches!(err, SignalProtocolError::CiphertextMessageTooShort(len) if len == *truncated_len),
+                "{}",
+                err
+            );
+        }
+
+        // Out-of-order chunks.
+        let mut reordered = serialized.to_vec();
+        reordered[100..200].rotate_left(50);
+        let err = streaming_decrypt(&mut bob_store, &alice_address, &reordered, 64)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, SignalProtocolError::InvalidCiphertext));
+
+        // Chunks after the end of the message, which fail the decryptor for good.
+        let mut decryptor = StreamingDecryptor::new(
+            &alice_address,
+            &mut bob_store.session_store,
+            &mut bob_store.identity_store,
+            &mut OsRng,
+            None,
+        )
+        .await?;
+        decryptor.update(serialized)?;
+        assert!(matches!(
+            decryptor.update(&[0]).unwrap_err(),
+            SignalProtocolError::InvalidMessage(_)
+        ));
+        assert!(matches!(
+            decryptor.update(&[]).unwrap_err(),
+            SignalProtocolError::InvalidState(_, _)
+        ));
+        assert!(matches!(
+            decryptor.finalize().await.unwrap_err(),
+            SignalProtocolError::InvalidState(_, _)
+        ));
+
+        // None of the failures advanced the session.
+        let decrypted = streaming_decrypt(&mut bob_store, &alice_address, serialized, 64).await?;
+        assert_eq!(decrypted, ptext);
+
+        Ok(())
+    })
+}
+
 #[allow(clippy::needless_range_loop)]
 fn run_session_interaction(
     alice_session: SessionRecord,
@@ -1715,3 +2097,300 @@ fn simultaneous_initiate_lost_message_repeated_messages() -> Result<(), SignalPr
         Ok(())
     })
 }
+
+#[test]
+fn oversized_protobufs_are_rejected() -> Result<(), SignalProtocolError> {
+    use rand::{Rng, RngCore};
+
+    fn random_bytes(len: usize) -> Vec<u8> {
+        let mut bytes = vec![0u8; len];
+        OsRng.fill_bytes(&mut bytes);
+        bytes
+    }
+
+    fn assert_too_large<T>(result: Result<T, SignalProtocolError>) {
+        match result {
+            Err(SignalProtocolError::ProtobufTooLarge(len, limit)) => assert!(len > limit),
+            Err(e) => panic!("unexpected error {}", e),
+            Ok(_) => panic!("unexpectedly succeeded"),
+        }
+    }
+
+    for _ in 0..5 {
+        let extra = OsRng.gen_range(1, 4096);
+
+        let mut message = random_bytes((1024 + 64) * 1024 + extra);
+        message[0] = 0x33; // current version
+        assert_too_large(SignalMessage::try_from(message.as_slice()));
+        assert_too_large(PreKeySignalMessage::try_from(message.as_slice()));
+        assert_too_large(SenderKeyMessage::try_from(message.as_slice()));
+        assert_too_large(SenderKeyDistributionMessage::try_from(message.as_slice()));
+
+        let small = random_bytes(16 * 1024 + extra);
+        assert_too_large(PreKeyRecord::deserialize(&small));
+        assert_too_large(SignedPreKeyRecord::deserialize(&small));
+        assert_too_large(IdentityKeyPair::try_from(small.as_slice()));
+    }
+
+    assert_too_large(SenderKeyRecord::deserialize(&random_bytes(
+        4 * 1024 * 1024 + 1,
+    )));
+    assert_too_large(SessionRecord::deserialize(&random_bytes(
+        64 * 1024 * 1024 + 1,
+    )));
+
+    Ok(())
+}
+
+/// Sets up a session from `seed` alone, with Bob replying once so that Alice ratchets, and
+/// returns both session records along with Alice's first message.
+async fn seeded_session(seed: u64) -> Result<(Vec<u8>, Vec<u8>, Vec<u8>), SignalProtocolError> {
+    let mut csprng = StdRng::seed_from_u64(seed);
+
+    let alice_address = ProtocolAddress::new("+14151111111".to_owned(), 1);
+    let bob_address = ProtocolAddress::new("+14151111112".to_owned(), 1);
+
+    let mut alice_store = InMemSignalProtocolStore::new(IdentityKeyPair::generate(&mut csprng), 1)?;
+    let mut bob_store = InMemSignalProtocolStore::new(IdentityKeyPair::generate(&mut csprng), 2)?;
+
+    let bob_pre_key_bundle = create_pre_key_bundle(&mut bob_store, &mut csprng).await?;
+
+    process_prekey_bundle(
+        &bob_address,
+        &mut alice_store.session_store,
+        &mut alice_store.identity_store,
+        &bob_pre_key_bundle,
+        &mut csprng,
+        None,
+    )
+    .await?;
+
+    let outgoing_message = encrypt(&mut alice_store, &bob_address, "hello").await?;
+    let incoming_message = CiphertextMessage::PreKeySignalMessage(PreKeySignalMessage::try_from(
+        outgoing_message.serialize(),
+    )?);
+    message_decrypt(
+        &incoming_message,
+        &alice_address,
+        &mut bob_store.session_store,
+        &mut bob_store.identity_store,
+        &mut bob_store.pre_key_store,
+        &mut bob_store.signed_pre_key_store,
+        &mut csprng,
+        None,
+    )
+    .await?;
+
+    let reply = encrypt(&mut bob_store, &alice_address, "hi").await?;
+    let incoming_reply =
+        CiphertextMessage::SignalMessage(SignalMessage::try_from(reply.serialize())?);
+    message_decrypt(
+        &incoming_reply,
+        &bob_address,
+        &mut alice_store.session_store,
+        &mut alice_store.identity_store,
+        &mut alice_store.pre_key_store,
+        &mut alice_store.signed_pre_key_store,
+        &mut csprng,
+        None,
+    )
+    .await?;
+
+    let alice_record = alice_store
+        .load_session(&bob_address, None)
+        .await?
+        .expect("session found")
+        .serialize()?;
+    let bob_record = bob_store
+        .load_session(&alice_address, None)
+        .await?
+        .expect("session found")
+        .serialize()?;
+    Ok((
+        alice_record,
+        bob_record,
+        outgoing_message.serialize().to_vec(),
+    ))
+}
+
+#[test]
+fn seeded_rng_makes_session_setup_deterministic() -> Result<(), SignalProtocolError> {
+    block_on(async {
+        let first = seeded_session(42).await?;
+        let second = seeded_session(42).await?;
+        assert_eq!(first, second);
+
+        let other = seeded_session(43).await?;
+        assert_ne!(first.0, other.0);
+        assert_ne!(first.1, other.1);
+        assert_ne!(first.2, other.2);
+
+        Ok(())
+    })
+}
+
+#[test]
+fn session_state_diff() -> Result<(), SignalProtocolError> {
+    async fn load_session(
+        store: &InMemSignalProtocolStore,
+        address: &ProtocolAddress,
+    ) -> Result<SessionRecord, SignalProtocolError> {
+        Ok(store
+            .load_session(address, None)
+            .await?
+            .expect("session found"))
+    }
+
+    block_on(async {
+        let alice_address = ProtocolAddress::new("+14159999999".to_owned(), 1);
+        let bob_address = ProtocolAddress::new("+14158888888".to_owned(), 1);
+
+        let (alice_session, bob_session) = initialize_sessions_v3()?;
+
+        // The same state serialized twice.
+        let first = SessionRecord::deserialize(&alice_session.serialize()?)?;
+        let second = SessionRecord::deserialize(&alice_session.serialize()?)?;
+        let diff = first.diff_current_session(&second);
+        assert!(!diff.has_changes());
+        assert_eq!(diff.to_string(), "no changes");
+        assert!(!SessionRecord::new_fresh()
+            .diff_current_session(&SessionRecord::new_fresh())
+            .has_changes());
+
+        let mut alice_store = support::test_in_memory_protocol_store();
+        let mut bob_store = support::test_in_memory_protocol_store();
+        alice_store
+            .store_session(&bob_address, &alice_session, None)
+            .await?;
+        bob_store
+            .store_session(&alice_address, &bob_session, None)
+            .await?;
+
+        // Sending a message only advances the sender chain.
+        let before = load_session(&alice_store, &bob_address).await?;
+        let alice_message = encrypt(&mut alice_store, &bob_address, "hello").await?;
+        let after = load_session(&alice_store, &bob_address).await?;
+        let diff = before.diff_current_session(&after);
+        assert!(diff.sender_chain_changed());
+        assert!(!diff.root_key_changed());
+        assert!(!diff.receiver_chains_changed());
+        assert!(!diff.other_fields_changed());
+        assert_eq!(diff.to_string(), "changed sender chain");
+
+        decrypt(&mut bob_store, &alice_address, &alice_message).await?;
+        let bob_message = encrypt(&mut bob_store, &alice_address, "hi").await?;
+
+        // Receiving Bob's reply is a DH ratchet step.
+        let before = load_session(&alice_store, &bob_address).await?;
+        decrypt(&mut alice_store, &bob_address, &bob_message).await?;
+        let after = load_session(&alice_store, &bob_address).await?;
+        let diff = before.diff_current_session(&after);
+        assert!(diff.root_key_changed());
+        assert!(diff.sender_chain_changed());
+        assert!(diff.receiver_chains_changed());
+        assert!(diff.has_changes());
+
+        // A completely new session.
+        let (new_session, _) = initialize_sessions_v3()?;
+        let diff = after.diff_current_session(&new_session);
+        assert!(diff.root_key_changed());
+        assert!(diff.sender_chain_changed());
+        assert!(diff.receiver_chains_changed());
+        assert!(diff.other_fields_changed());
+        assert_eq!(
+            diff.to_string(),
+            "changed root key, sender chain, receiver chains, other fields"
+        );
+        assert_eq!(
+            after.diff_current_session(&SessionRecord::new_fresh()),
+            diff
+        );
+
+        // Archived states are compared too, so a store doesn't skip writing them.
+        let mut archived_after = after.clone();
+        archived_after.archive_current_state()?;
+        let mut archived_new = new_session.clone();
+        archived_new.archive_current_state()?;
+        let diff = archived_after.diff_current_session(&archived_new);
+        assert!(diff.archived_sessions_changed());
+        assert!(!diff.root_key_changed());
+        assert_eq!(diff.to_string(), "changed archived sessions");
+        assert!(!archived_after
+            .diff_current_session(&SessionRecord::deserialize(&archived_after.serialize()?)?)
+            .has_changes());
+        assert!(after
+            .diff_current_session(&archived_after)
+            .archived_sessions_changed());
+
+        Ok(())
+    })
+}
+
+#[test]
+fn identity_key_changed() -> Result<(), SignalProtocolError> {
+    block_on(async {
+        let mut csprng = OsRng;
+
+        let bob_address = ProtocolAddress::new("+14151111112".to_owned(), 1);
+
+        let mut alice_store = support::test_in_memory_protocol_store();
+        let mut bob_store = support::test_in_memory_protocol_store();
+        let old_bob_identity = *bob_store.get_identity_key_pair(None).await?.identity_key();
+
+        let bob_pre_key_bundle = create_pre_key_bundle(&mut bob_store, &mut csprng).await?;
+        process_prekey_bundle(
+            &bob_address,
+            &mut alice_store.session_store,
+            &mut alice_store.identity_store,
+            &bob_pre_key_bundle,
+            &mut csprng,
+            None,
+        )
+        .await?;
+
+        // Bob reinstalls, and so has a new identity key.
+        let mut bob_store = support::test_in_memory_protocol_store();
+        let new_bob_identity = *bob_store.get_identity_key_pair(None).await?.identity_key();
+        assert_ne!(old_bob_identity, new_bob_identity);
+
+        let bob_pre_key_bundle = create_pre_key_bundle(&mut bob_store, &mut csprng).await?;
+        let error = process_prekey_bundle(
+            &bob_address,
+            &mut alice_store.session_store,
+            &mut alice_store.identity_store,
+            &bob_pre_key_bundle,
+            &mut csprng,
+            None,
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            format!("identity key changed for address {}", bob_address)
+        );
+        match error {
+            SignalProtocolError::IdentityKeyChanged(address, old_key, new_key) => {
+                assert_eq!(address, bob_address);
+                assert_eq!(old_key, old_bob_identity);
+                assert_eq!(new_key, new_bob_identity);
+            }
+            e => panic!("unexpected error {}", e),
+        }
+
+        // Once the app accepts the new key, the bundle can be processed.
+        alice_store
+            .save_identity(&bob_address, &new_bob_identity, None)
+            .await?;
+        process_prekey_bundle(
+            &bob_address,
+            &mut alice_store.session_store,
+            &mut alice_store.identity_store,
+            &bob_pre_key_bundle,
+            &mut csprng,
+            None,
+        )
+        .await?;
+
+        Ok(())
+    })
+}
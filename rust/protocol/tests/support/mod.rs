@@ -69,15 +69,17 @@ pub async fn create_pre_key_bundle<R: Rng + CryptoRng>(
     let pre_key_id: u32 = csprng.gen();
     let signed_pre_key_id: u32 = csprng.gen();
 
-    let pre_key_bundle = PreKeyBundle::new(
-        store.get_local_registration_id(None).await?,
-        device_id,
-        Some((pre_key_id, pre_key_pair.public_key)),
-        signed_pre_key_id,
-        signed_pre_key_pair.public_key,
-        signed_pre_key_signature.to_vec(),
-        *store.get_identity_key_pair(None).await?.identity_key(),
-    )?;
+    let pre_key_bundle = PreKeyBundleBuilder::new()
+        .registration_id(store.get_local_registration_id(None).await?)
+        .device_id(device_id)
+        .pre_key(pre_key_id, pre_key_pair.public_key)
+        .signed_pre_key(
+            signed_pre_key_id,
+            signed_pre_key_pair.public_key,
+            &signed_pre_key_signature,
+        )
+        .identity_key(*store.get_identity_key_pair(None).await?.identity_key())
+        .build()?;
 
     store
         .save_pre_key(
@@ -114,11 +116,11 @@ pub fn initialize_sessions_v3() -> Result<(SessionRecord, SessionRecord), Signal
     let alice_base_key = KeyPair::generate(&mut csprng);
 
     let bob_base_key = KeyPair::generate(&mut csprng);
-    let bob_ephemeral_key = bob_base_key;
+    let bob_ephemeral_key = bob_base_key.clone();
 
     let alice_params = AliceSignalProtocolParameters::new(
-        alice_identity,
-        alice_base_key,
+        alice_identity.clone(),
+        alice_base_key.clone(),
         *bob_identity.identity_key(),
         bob_base_key.public_key,
         None,
@@ -29,9 +29,12 @@ public enum SignalError: Error {
     case fingerprintVersionMismatch(String)
     case fingerprintParsingError(String)
     case sealedSenderSelfSend(String)
+    case certificateExpired(String)
     case untrustedIdentity(String)
+    case identityKeyChanged(String)
     case invalidKeyIdentifier(String)
     case sessionNotFound(String)
+    case unknownSessionVersion(String)
     case duplicatedMessage(String)
     case callbackError(String)
     case unknown(UInt32, String)
@@ -80,6 +83,8 @@ internal func checkError(_ error: SignalFfiErrorRef?) throws {
         throw SignalError.fingerprintParsingError(errStr)
     case SignalErrorCode_SealedSenderSelfSend:
         throw SignalError.sealedSenderSelfSend(errStr)
+    case SignalErrorCode_CertificateExpired:
+        throw SignalError.certificateExpired(errStr)
     case SignalErrorCode_InvalidKey:
         throw SignalError.invalidKey(errStr)
     case SignalErrorCode_InvalidSignature:
@@ -90,10 +95,14 @@ internal func checkError(_ error: SignalFfiErrorRef?) throws {
         throw SignalError.fingerprintVersionMismatch(errStr)
     case SignalErrorCode_UntrustedIdentity:
         throw SignalError.untrustedIdentity(errStr)
+    case SignalErrorCode_IdentityKeyChanged:
+        throw SignalError.identityKeyChanged(errStr)
     case SignalErrorCode_InvalidKeyIdentifier:
         throw SignalError.invalidKeyIdentifier(errStr)
     case SignalErrorCode_SessionNotFound:
         throw SignalError.sessionNotFound(errStr)
+    case SignalErrorCode_UnknownSessionVersion:
+        throw SignalError.unknownSessionVersion(errStr)
     case SignalErrorCode_DuplicatedMessage:
         throw SignalError.duplicatedMessage(errStr)
     case SignalErrorCode_CallbackError:
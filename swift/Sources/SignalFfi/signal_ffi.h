@@ -14,6 +14,12 @@ SPDX-License-Identifier: AGPL-3.0-only
 #include <stdint.h>
 #include <stdlib.h>
 
+#if defined(__GNUC__) || defined(__clang__)
+#define SIGNAL_DEPRECATED(note) __attribute__((deprecated(note)))
+#else
+#define SIGNAL_DEPRECATED(note)
+#endif
+
 typedef enum {
   SignalCiphertextMessageType_Whisper = 2,
   SignalCiphertextMessageType_PreKey = 3,
@@ -50,14 +56,17 @@ typedef enum {
   SignalErrorCode_UnrecognizedMessageVersion = 23,
   SignalErrorCode_InvalidMessage = 30,
   SignalErrorCode_SealedSenderSelfSend = 31,
+  SignalErrorCode_CertificateExpired = 32,
   SignalErrorCode_InvalidKey = 40,
   SignalErrorCode_InvalidSignature = 41,
   SignalErrorCode_FingerprintIdentifierMismatch = 50,
   SignalErrorCode_FingerprintVersionMismatch = 51,
   SignalErrorCode_FingerprintParsingError = 52,
   SignalErrorCode_UntrustedIdentity = 60,
+  SignalErrorCode_IdentityKeyChanged = 61,
   SignalErrorCode_InvalidKeyIdentifier = 70,
   SignalErrorCode_SessionNotFound = 80,
+  SignalErrorCode_UnknownSessionVersion = 81,
   SignalErrorCode_DuplicatedMessage = 90,
   SignalErrorCode_CallbackError = 100,
 } SignalErrorCode;
@@ -185,10 +194,14 @@ void signal_free_string(const char *buf);
 
 void signal_free_buffer(const unsigned char *buf, size_t buf_len);
 
+void signal_free_handle_list(void **list, size_t count);
+
 SignalFfiError *signal_error_get_message(const SignalFfiError *err, const char **out);
 
 uint32_t signal_error_get_type(const SignalFfiError *err);
 
+SignalErrorCode signal_error_get_code(const SignalFfiError *err);
+
 void signal_error_free(SignalFfiError *err);
 
 SignalFfiError *signal_identitykeypair_deserialize(SignalPrivateKey **private_key,
@@ -242,6 +255,13 @@ SignalFfiError *signal_aes256_gcm_siv_decrypt(const unsigned char **out,
                                               const unsigned char *associated_data,
                                               size_t associated_data_len);
 
+SignalFfiError *signal_poksho_multi_scalar_mul(const unsigned char **out,
+                                               size_t *out_len,
+                                               const unsigned char *scalars,
+                                               size_t scalars_len,
+                                               const unsigned char *points,
+                                               size_t points_len);
+
 SignalFfiError *signal_ciphertext_message_destroy(SignalCiphertextMessage *p);
 
 SignalFfiError *signal_fingerprint_destroy(SignalFingerprint *p);
@@ -322,6 +342,17 @@ SignalFfiError *signal_signed_pre_key_record_clone(SignalSignedPreKeyRecord **ne
 
 SignalFfiError *signal_unidentified_sender_message_content_destroy(SignalUnidentifiedSenderMessageContent *p);
 
+SignalFfiError *signal_hkdf_derive_secrets(const unsigned char **out,
+                                           size_t *out_len,
+                                           uint32_t output_length,
+                                           uint32_t version,
+                                           const unsigned char *ikm,
+                                           size_t ikm_len,
+                                           const unsigned char *label,
+                                           size_t label_len,
+                                           const unsigned char *salt,
+                                           size_t salt_len);
+
 SignalFfiError *signal_hkdf_derive(unsigned char *output,
                                    size_t output_len,
                                    uint32_t version,
@@ -363,6 +394,13 @@ SignalFfiError *signal_publickey_verify(bool *out,
                                         const unsigned char *signature,
                                         size_t signature_len);
 
+SignalFfiError *signal_publickey_verify_batch(bool *out,
+                                              const SignalPublicKey *key,
+                                              const unsigned char *messages,
+                                              size_t messages_len,
+                                              const unsigned char *signatures,
+                                              size_t signatures_len);
+
 SignalFfiError *signal_privatekey_deserialize(SignalPrivateKey **p,
                                               const unsigned char *data,
                                               size_t data_len);
@@ -449,7 +487,7 @@ SignalFfiError *signal_message_verify_mac(bool *out,
                                           size_t mac_key_len);
 
 SignalFfiError *signal_message_get_sender_ratchet_key(SignalPublicKey **out,
-                                                      const SignalMessage *m);
+                                                      const SignalMessage *obj);
 
 SignalFfiError *signal_pre_key_signal_message_new(SignalPreKeySignalMessage **out,
                                                   uint8_t message_version,
@@ -461,13 +499,13 @@ SignalFfiError *signal_pre_key_signal_message_new(SignalPreKeySignalMessage **ou
                                                   const SignalMessage *signal_message);
 
 SignalFfiError *signal_pre_key_signal_message_get_base_key(SignalPublicKey **out,
-                                                           const SignalPreKeySignalMessage *m);
+                                                           const SignalPreKeySignalMessage *obj);
 
 SignalFfiError *signal_pre_key_signal_message_get_identity_key(SignalPublicKey **out,
                                                                const SignalPreKeySignalMessage *m);
 
 SignalFfiError *signal_pre_key_signal_message_get_signal_message(SignalMessage **out,
-                                                                 const SignalPreKeySignalMessage *m);
+                                                                 const SignalPreKeySignalMessage *obj);
 
 SignalFfiError *signal_pre_key_signal_message_deserialize(SignalPreKeySignalMessage **p,
                                                           const unsigned char *data,
@@ -518,6 +556,12 @@ SignalFfiError *signal_sender_key_message_verify_signature(bool *out,
                                                            const SignalSenderKeyMessage *skm,
                                                            const SignalPublicKey *pubkey);
 
+SignalFfiError *signal_sender_key_derive_message_key(const unsigned char **out,
+                                                     size_t *out_len,
+                                                     const unsigned char *chain_key,
+                                                     size_t chain_key_len,
+                                                     uint32_t iteration);
+
 SignalFfiError *signal_sender_key_distribution_message_deserialize(SignalSenderKeyDistributionMessage **p,
                                                                    const unsigned char *data,
                                                                    size_t data_len);
@@ -544,7 +588,7 @@ SignalFfiError *signal_sender_key_distribution_message_new(SignalSenderKeyDistri
                                                            const SignalPublicKey *pk);
 
 SignalFfiError *signal_sender_key_distribution_message_get_signature_key(SignalPublicKey **out,
-                                                                         const SignalSenderKeyDistributionMessage *m);
+                                                                         const SignalSenderKeyDistributionMessage *obj);
 
 SignalFfiError *signal_pre_key_bundle_new(SignalPreKeyBundle **out,
                                           uint32_t registration_id,
@@ -633,6 +677,11 @@ SignalFfiError *signal_pre_key_record_new(SignalPreKeyRecord **out,
                                           const SignalPublicKey *pub_key,
                                           const SignalPrivateKey *priv_key);
 
+SignalFfiError *signal_pre_key_record_generate_batch(SignalPreKeyRecord ***out,
+                                                     size_t *out_count,
+                                                     uint32_t start_id,
+                                                     uint32_t count);
+
 SignalFfiError *signal_sender_key_name_get_group_id(const char **out,
                                                     const SignalSenderKeyName *obj);
 
@@ -721,7 +770,7 @@ SignalFfiError *signal_sender_certificate_validate(bool *out,
                                                    uint64_t time);
 
 SignalFfiError *signal_sender_certificate_get_server_certificate(SignalServerCertificate **out,
-                                                                 const SignalSenderCertificate *cert);
+                                                                 const SignalSenderCertificate *obj);
 
 SignalFfiError *signal_sender_certificate_new(SignalSenderCertificate **out,
                                               const char *sender_uuid,
@@ -745,7 +794,7 @@ SignalFfiError *signal_unidentified_sender_message_content_get_contents(const un
                                                                         const SignalUnidentifiedSenderMessageContent *obj);
 
 SignalFfiError *signal_unidentified_sender_message_content_get_sender_cert(SignalSenderCertificate **out,
-                                                                           const SignalUnidentifiedSenderMessageContent *m);
+                                                                           const SignalUnidentifiedSenderMessageContent *obj);
 
 SignalFfiError *signal_unidentified_sender_message_content_get_msg_type(uint8_t *out,
                                                                         const SignalUnidentifiedSenderMessageContent *m);
@@ -768,6 +817,10 @@ SignalFfiError *signal_session_record_serialize(const unsigned char **out,
                                                 size_t *out_len,
                                                 const SignalSessionRecord *obj);
 
+SignalFfiError *signal_session_record_get_remote_identity_key_public(const unsigned char **out,
+                                                                    size_t *out_len,
+                                                                    const SignalSessionRecord *obj);
+
 SignalFfiError *signal_session_record_get_local_registration_id(uint32_t *out,
                                                                 const SignalSessionRecord *obj);
 
@@ -816,6 +869,16 @@ SignalFfiError *signal_sealed_session_cipher_encrypt(const unsigned char **out,
                                                      const SignalIdentityKeyStore *identity_key_store,
                                                      void *ctx);
 
+SignalFfiError *signal_sealed_sender_multi_recipient_encrypt(const unsigned char **out,
+                                                             size_t *out_len,
+                                                             const SignalProtocolAddress *const *recipients,
+                                                             size_t recipients_len,
+                                                             const SignalSessionRecord *const *recipient_sessions,
+                                                             size_t recipient_sessions_len,
+                                                             const SignalUnidentifiedSenderMessageContent *content,
+                                                             const SignalIdentityKeyStore *identity_key_store,
+                                                             void *ctx);
+
 SignalFfiError *signal_sealed_session_cipher_decrypt_to_usmc(SignalUnidentifiedSenderMessageContent **out,
                                                              const unsigned char *ctext,
                                                              size_t ctext_len,
@@ -70,6 +70,15 @@ class PublicAPITests: TestCaseBase {
 
         XCTAssertThrowsError(try gcm_siv.decrypt(ptext, nonce, ad))
         XCTAssertThrowsError(try gcm_siv.decrypt(ctext, ad, nonce))
+
+        var corrupted_ctext = ctext
+        corrupted_ctext[0] ^= 1
+        XCTAssertThrowsError(try gcm_siv.decrypt(corrupted_ctext, nonce, ad)) { error in
+            guard case SignalError.invalidCiphertext = error else {
+                XCTFail("wrong error thrown")
+                return
+            }
+        }
     }
 
     func testAddress() {
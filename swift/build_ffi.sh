@@ -20,9 +20,10 @@ Usage: $(basename "$0") [-d]
 Options:
 	-d -- debug build (default is release)
 	-v -- verbose build
+	--testing -- include the signal_testing_* functions used by rust/bridge/ffi/tests
 
 	--generate-ffi -- regenerate ffi headers
-	--verify-ffi   -- verify that ffi headers are up to date
+	--verify-ffi   -- verify that ffi headers are up to date and match the built library
 
 Use CARGO_BUILD_TARGET for cross-compilation (such as for iOS).
 END
@@ -44,6 +45,7 @@ RELEASE_BUILD=1
 VERBOSE=
 SHOULD_CBINDGEN=
 CBINDGEN_VERIFY=
+TESTING=
 
 while [ "${1:-}" != "" ]; do
   case $1 in
@@ -53,6 +55,9 @@ while [ "${1:-}" != "" ]; do
     -v | --verbose )
       VERBOSE=1
       ;;
+    --testing )
+      TESTING=1
+      ;;
     --generate-ffi )
       SHOULD_CBINDGEN=1
       ;;
@@ -81,9 +86,13 @@ if [[ -n "${DEVELOPER_SDK_DIR:-}" ]]; then
   export LIBRARY_PATH="${DEVELOPER_SDK_DIR}/MacOSX.sdk/usr/lib:${LIBRARY_PATH:-}"
 fi
 
-echo_then_run cargo build -p libsignal-ffi ${RELEASE_BUILD:+--release} ${VERBOSE:+--verbose}
+# Features can only be selected for the package whose manifest cargo is pointed at.
+echo_then_run cargo build -p libsignal-ffi ${RELEASE_BUILD:+--release} ${VERBOSE:+--verbose} \
+  ${TESTING:+--manifest-path rust/bridge/ffi/Cargo.toml --features testing}
 
 FFI_HEADER_PATH=swift/Sources/SignalFfi/signal_ffi.h
+# Declarations for the testing functions aren't generated; they're kept alongside the C tests.
+FFI_TESTING_HEADER_PATH=rust/bridge/ffi/tests/signal_ffi_testing.h
 
 if [[ -n "${SHOULD_CBINDGEN}" ]]; then
   check_cbindgen
@@ -94,6 +103,13 @@ if [[ -n "${SHOULD_CBINDGEN}" ]]; then
       echo 'error: signal_ffi.h not up to date; run' "$0" '--generate-ffi' >&2
       exit 1
     fi
+    BUILD_PROFILE_DIR=debug
+    if [[ -n "${RELEASE_BUILD}" ]]; then
+      BUILD_PROFILE_DIR=release
+    fi
+    FFI_LIBRARY_PATH="target/${CARGO_BUILD_TARGET:+${CARGO_BUILD_TARGET}/}${BUILD_PROFILE_DIR}/libsignal_ffi.a"
+    echo_then_run rust/bridge/ffi/bin/verify_ffi_symbols.py "${FFI_LIBRARY_PATH}" "${FFI_HEADER_PATH}" \
+      ${TESTING:+"${FFI_TESTING_HEADER_PATH}"}
   else
     echo cbindgen ${RELEASE_BUILD:+--profile release} -o "${FFI_HEADER_PATH}" rust/bridge/ffi
     # Use sed to ignore irrelevant cbindgen warnings.